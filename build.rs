@@ -1,53 +1,48 @@
 use std::env;
-// use std::process::Command;
 
 fn main() {
+    // Audio backends can be compiled together and chosen at runtime with --audio-backend, but
+    // the two MPV features link different versions of the same library
     let user_mpv_0_34 = env::var("CARGO_FEATURE_MPV_0_34").is_ok();
     let user_mpv_0_35 = env::var("CARGO_FEATURE_MPV_0_35").is_ok();
-    let user_rodio_audio = env::var("CARGO_FEATURE_RODIO_AUDIO").is_ok();
 
-    let enabled_count = [user_mpv_0_34, user_mpv_0_35, user_rodio_audio]
-        .iter()
-        .filter(|&&x| x)
-        .count();
-
-    if enabled_count > 1 {
-        eprintln!("Error: At most one of the following features can be enabled at a time: mpv_0_34, mpv_0_35, rodio_audio.");
+    if user_mpv_0_34 && user_mpv_0_35 {
+        eprintln!("Error: At most one of the following features can be enabled at a time: mpv_0_34, mpv_0_35.");
         std::process::exit(1);
     }
 
-    if user_mpv_0_34 || user_mpv_0_35 || user_rodio_audio {
-        if user_mpv_0_34 {
-            println!("cargo:rustc-cfg=feature=\"mpv_0_34\"");
-        } else if user_mpv_0_35 {
-            println!("cargo:rustc-cfg=feature=\"mpv_0_35\"");
-        } else {
-            println!("cargo:rustc-cfg=feature=\"rodio_audio\"");
-        }
+    // Videos are decoded by ffmpeg when both decoders are enabled
+    if env::var("CARGO_FEATURE_OPENCV_DECODER").is_err()
+        && env::var("CARGO_FEATURE_FFMPEG_DECODER").is_err()
+    {
+        println!("cargo:warning=no video decoder library enabled, only YUV4MPEG2 and Motion JPEG AVI videos can be played: enable the opencv_decoder or ffmpeg_decoder feature for the others");
+    }
+
+    if env::var("CARGO_FEATURE_STATIC_RELEASE").is_ok() {
+        check_static_release();
     }
-    // else {
-    //     let output = Command::new("mpv").arg("--version").output();
-    //     if let Ok(output) = output {
-    //         let version_string = String::from_utf8_lossy(&output.stdout);
-    //         let version = version_string
-    //             .lines()
-    //             .next()
-    //             .unwrap_or("")
-    //             .split_whitespace()
-    //             .nth(1)
-    //             .unwrap_or("");
+}
 
-    //         if version.starts_with("0.34") {
-    //             println!("cargo:rustc-cfg=feature=\"mpv_0_34\"");
-    //         } else if version.starts_with("0.35") {
-    //             println!("cargo:rustc-cfg=feature=\"mpv_0_35\"");
-    //         } else {
-    //             // fallback to rodio
-    //             println!("cargo:rustc-cfg=feature=\"rodio_audio\"");
-    //         }
-    //     } else {
-    //         // fallback to rodio
-    //         println!("cargo:rustc-cfg=feature=\"rodio_audio\"");
-    //     }
-    // }
+/// Warns about the parts of a `static-release` build that would still be linked dynamically.
+/// libav is built by the `ffmpeg-next/build` feature, but OpenCV (when its decoder is enabled
+/// too) and the C runtime are configured by the environment and the target.
+fn check_static_release() {
+    println!("cargo:rerun-if-env-changed=OPENCV_LINK_LIBS");
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    let crt_static = env::var("CARGO_CFG_TARGET_FEATURE")
+        .unwrap_or_default()
+        .split(',')
+        .any(|feature| feature == "crt-static");
+
+    if target_os == "linux" && target_env != "musl" {
+        println!("cargo:warning=static-release: build for a musl target (e.g. --target x86_64-unknown-linux-musl) to get a binary that runs on any Linux distribution");
+    } else if target_os == "windows" && !crt_static {
+        println!("cargo:warning=static-release: the C runtime is linked dynamically, set RUSTFLAGS=\"-C target-feature=+crt-static\" to link it statically");
+    }
+    let opencv_static = env::var("OPENCV_LINK_LIBS")
+        .is_ok_and(|libs| libs.split(',').all(|lib| lib.trim().starts_with("static=")));
+    if env::var("CARGO_FEATURE_OPENCV_DECODER").is_ok() && !opencv_static {
+        println!("cargo:warning=static-release: OpenCV is linked dynamically, build with --no-default-features to leave it out or list its static libraries in OPENCV_LINK_LIBS (e.g. OPENCV_LINK_LIBS=\"static=opencv_videoio,static=opencv_imgproc,static=opencv_core\")");
+    }
 }
@@ -0,0 +1,176 @@
+//! Keeps an audio backend open from one media to the next (e.g. the tracks of a playlist), so that
+//! the audio device is not opened again for each of them, which takes time and can be heard as a
+//! pop.
+//!
+//! The backends cannot move between threads, so the `AudioDevice` runs its backend on a thread of
+//! its own. The playback of each media sends it the audio to play, which it loads in the same
+//! backend instance, and waits for it to be stopped.
+use super::{
+    levels::AudioLevels,
+    player::{AudioBackend, AudioPlayer},
+    runner::{Control, Feedback, Runner},
+};
+use crate::common::{errors::*, stats::AudioUnderruns};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Barrier},
+    thread,
+};
+
+/// The audio of a media to play.
+struct Request {
+    /// The path to the media whose audio track is played.
+    input_file: String,
+    /// The directory where the audio track can be extracted.
+    temp_dir: PathBuf,
+    /// The playback speed.
+    speed: f64,
+    /// Where the levels of the audio are published, if they are measured.
+    levels: Option<AudioLevels>,
+    /// The channel of the commands of the playback.
+    rx_controls: Receiver<Control>,
+    /// The channel reporting the changes of the audio to the broker.
+    tx_feedback: Sender<Feedback>,
+    /// The barrier synchronizing the start of the playback.
+    barrier: Arc<Barrier>,
+    /// Where the outcome of the playback is sent once it is stopped.
+    tx_done: Sender<Result<(), MyError>>,
+}
+
+/// An audio backend kept open on its own thread, playing the audio of one media after the other.
+#[derive(Clone)]
+pub struct AudioDevice {
+    /// The backend playing the audio.
+    backend: AudioBackend,
+    /// The channel of the audio to play. The thread stops once every copy of the device is gone.
+    tx_requests: Sender<Request>,
+    /// The underruns of the audio output, over all the media played.
+    underruns: AudioUnderruns,
+}
+
+impl AudioDevice {
+    /// Starts the thread of the device. The backend is only opened with the first audio played.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend playing the audio, it must be compiled in.
+    pub fn new(backend: AudioBackend) -> Self {
+        let (tx_requests, rx_requests) = unbounded();
+        let underruns = AudioUnderruns::default();
+        let counted = underruns.clone();
+        thread::spawn(move || serve(backend, rx_requests, counted));
+        Self {
+            backend,
+            tx_requests,
+            underruns,
+        }
+    }
+
+    /// Returns the backend playing the audio.
+    pub fn backend(&self) -> AudioBackend {
+        self.backend
+    }
+
+    /// Returns the underruns of the audio output, over all the media played, if the backend
+    /// detects them.
+    pub fn underruns(&self) -> Option<AudioUnderruns> {
+        self.backend
+            .detects_underruns()
+            .then(|| self.underruns.clone())
+    }
+
+    /// Plays the audio track of a media, from when the other threads of the playback reach the
+    /// barrier until it is stopped.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_file` - The path to the media whose audio track is played.
+    /// * `temp_dir` - The directory where the audio track can be extracted.
+    /// * `speed` - The playback speed (1.0 is the original speed).
+    /// * `levels` - Where the levels of the audio are published for the VU meter, if it is shown.
+    /// * `rx_controls` - The channel of the commands of the playback.
+    /// * `tx_feedback` - The channel reporting the changes of the audio (e.g. of the volume) to the
+    ///   broker.
+    /// * `barrier` - The barrier synchronizing the start of the playback.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the audio cannot be played, or the thread of the device is gone.
+    pub fn play(
+        &self,
+        input_file: String,
+        temp_dir: PathBuf,
+        speed: f64,
+        levels: Option<AudioLevels>,
+        (rx_controls, tx_feedback): (Receiver<Control>, Sender<Feedback>),
+        barrier: Arc<Barrier>,
+    ) -> Result<(), MyError> {
+        let (tx_done, rx_done) = bounded(1);
+        let request = Request {
+            input_file,
+            temp_dir,
+            speed,
+            levels,
+            rx_controls,
+            tx_feedback,
+            barrier,
+            tx_done,
+        };
+        let gone = || MyError::Audio(ERROR_AUDIO_DEVICE.to_string());
+        if let Err(request) = self.tx_requests.send(request) {
+            // The other threads still wait for this one
+            request.0.barrier.wait();
+            return Err(gone());
+        }
+        rx_done.recv().unwrap_or_else(|_| Err(gone()))
+    }
+}
+
+/// Plays the audio requested, one after the other, with the same backend instance.
+///
+/// # Arguments
+///
+/// * `backend` - The backend playing the audio.
+/// * `rx_requests` - The channel of the audio to play.
+/// * `underruns` - Where the underruns of the audio output are counted.
+fn serve(backend: AudioBackend, rx_requests: Receiver<Request>, underruns: AudioUnderruns) {
+    let mut player: Option<AudioPlayer> = None;
+    for request in rx_requests {
+        let loaded = match player.take() {
+            Some(mut player) => player
+                .load(&request.input_file, &request.temp_dir, request.levels)
+                .map(|_| player),
+            None => AudioPlayer::new(
+                backend,
+                &request.input_file,
+                &request.temp_dir,
+                request.levels,
+                underruns.clone(),
+            ),
+        };
+        let loaded = loaded.and_then(|mut player| {
+            if request.speed != 1.0 {
+                player.player.set_speed(request.speed)?;
+            }
+            Ok(player)
+        });
+        let result = match loaded {
+            Ok(loaded) => {
+                let mut runner = Runner::new(loaded, request.rx_controls, request.tx_feedback);
+                let result = runner.run(request.barrier);
+                // A backend that failed is opened again for the next media
+                if result.is_ok() {
+                    player = Some(runner.into_player());
+                }
+                result
+            }
+            Err(err) => {
+                // The playback goes on without audio
+                request.barrier.wait();
+                Err(err)
+            }
+        };
+        let _ = request.tx_done.send(result);
+    }
+}
@@ -0,0 +1,238 @@
+//! Measures the spectrum of the audio being played, for the VU meter drawn along the bottom row of
+//! the frames (`--vu`).
+//!
+//! The rodio backends tap the samples on their way to the output device: every window of samples
+//! is mixed down to mono, and the level of a few frequency bands, spaced logarithmically across
+//! the audible range, is measured with the Goertzel algorithm. The levels are shared with the
+//! pipeline through `AudioLevels`, and fall back slowly after a peak so that the meter does not
+//! flicker.
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+use rodio::{source::SeekError, Sample, Source};
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+use std::time::Duration;
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+/// Number of frequency bands measured.
+pub const BANDS: usize = 24;
+/// Number of mono samples analyzed at once, about 23 ms at 44.1 kHz.
+#[cfg_attr(
+    not(any(feature = "rodio_audio", feature = "symphonia_audio")),
+    allow(dead_code)
+)]
+const WINDOW: usize = 1024;
+/// Center frequency of the lowest and the highest band, in Hz.
+const FREQUENCY_RANGE: (f32, f32) = (60.0, 12000.0);
+/// Level, in dB below full scale, shown as an empty band.
+const FLOOR_DB: f32 = 60.0;
+/// How much a band falls after each window when the sound gets quieter (0-1).
+#[cfg_attr(
+    not(any(feature = "rodio_audio", feature = "symphonia_audio")),
+    allow(dead_code)
+)]
+const FALL_PER_WINDOW: f32 = 0.04;
+
+/// The levels of the frequency bands of the audio being played, shared between the audio
+/// backend measuring them and the pipeline drawing them.
+#[derive(Debug, Clone, Default)]
+pub struct AudioLevels {
+    /// The level of each band, from 0 (silence) to 1 (full scale), from the lowest frequency.
+    bands: Arc<Mutex<Vec<f32>>>,
+}
+
+impl AudioLevels {
+    /// Creates the levels of silent audio.
+    pub fn new() -> Self {
+        Self {
+            bands: Arc::new(Mutex::new(vec![0.0; BANDS])),
+        }
+    }
+
+    /// Returns the level of each band, from 0 to 1, from the lowest frequency.
+    pub fn bands(&self) -> Vec<f32> {
+        self.bands
+            .lock()
+            .map_or_else(|_| vec![0.0; BANDS], |bands| bands.clone())
+    }
+
+    /// Updates the levels with a new measure. Louder bands rise at once, quieter bands fall
+    /// slowly.
+    ///
+    /// # Arguments
+    ///
+    /// * `measured` - The level of each band in the last window of samples.
+    #[cfg_attr(
+        not(any(feature = "rodio_audio", feature = "symphonia_audio")),
+        allow(dead_code)
+    )]
+    fn update(&self, measured: &[f32]) {
+        if let Ok(mut bands) = self.bands.lock() {
+            for (band, level) in bands.iter_mut().zip(measured) {
+                *band = level.max(*band - FALL_PER_WINDOW);
+            }
+        }
+    }
+}
+
+/// Measures the level of the frequency bands in a window of mono samples.
+///
+/// # Arguments
+///
+/// * `samples` - The samples, from -1 to 1.
+/// * `sample_rate` - The sample rate, in Hz.
+///
+/// # Returns
+///
+/// The level of each band, from 0 (at or below `FLOOR_DB`) to 1 (full scale).
+pub fn measure_bands(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let len = samples.len() as f32;
+    // A Hann window, so that loud bands do not leak into their neighbours
+    let windowed: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * (0.5 - 0.5 * (2.0 * PI * i as f32 / len).cos()))
+        .collect();
+    let (low, high) = FREQUENCY_RANGE;
+    (0..BANDS)
+        .map(|band| {
+            let frequency = low * (high / low).powf(band as f32 / (BANDS - 1) as f32);
+            let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate as f32).cos();
+            let (mut previous, mut before) = (0.0f32, 0.0f32);
+            for sample in &windowed {
+                let current = sample + coefficient * previous - before;
+                before = previous;
+                previous = current;
+            }
+            let power = previous * previous + before * before - coefficient * previous * before;
+            // The Hann window halves the amplitude of a sine
+            let amplitude = 4.0 * power.max(0.0).sqrt() / len;
+            let db = 20.0 * amplitude.max(f32::MIN_POSITIVE).log10();
+            ((db + FLOOR_DB) / FLOOR_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// A rodio source passing the samples of another source through unchanged, measuring their
+/// levels on the way.
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+pub struct LevelTap<S> {
+    /// The source tapped.
+    source: S,
+    /// Where the levels are published.
+    levels: AudioLevels,
+    /// The mono samples of the current window.
+    window: Vec<f32>,
+    /// The sum of the samples of the current frame, over its channels.
+    frame_sum: f32,
+    /// The channel of the next sample.
+    channel: u16,
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> LevelTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    /// Taps a source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source played.
+    /// * `levels` - Where the levels are published.
+    pub fn new(source: S, levels: AudioLevels) -> Self {
+        Self {
+            source,
+            levels,
+            window: Vec::with_capacity(WINDOW),
+            frame_sum: 0.0,
+            channel: 0,
+        }
+    }
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> Iterator for LevelTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let channels = self.source.channels().max(1);
+        self.frame_sum += sample.to_f32();
+        self.channel += 1;
+        if self.channel >= channels {
+            self.window.push(self.frame_sum / channels as f32);
+            self.frame_sum = 0.0;
+            self.channel = 0;
+        }
+        if self.window.len() >= WINDOW {
+            let bands = measure_bands(&self.window, self.source.sample_rate());
+            self.levels.update(&bands);
+            self.window.clear();
+        }
+        Some(sample)
+    }
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> Source for LevelTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.window.clear();
+        (self.frame_sum, self.channel) = (0.0, 0);
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_bands() {
+        let sample_rate = 44100;
+        // A full scale sine at the frequency of the middle band
+        let (low, high) = FREQUENCY_RANGE;
+        let frequency = low * (high / low).powf(12.0 / (BANDS - 1) as f32);
+        let sine: Vec<f32> = (0..WINDOW)
+            .map(|i| (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect();
+        let bands = measure_bands(&sine, sample_rate);
+        assert_eq!(bands.len(), BANDS);
+        assert!(bands[12] > 0.95, "{bands:?}");
+        assert!(bands[0] < 0.5 && bands[BANDS - 1] < 0.5, "{bands:?}");
+        assert!(measure_bands(&[0.0; WINDOW], sample_rate)
+            .iter()
+            .all(|level| *level == 0.0));
+
+        let levels = AudioLevels::new();
+        levels.update(&bands);
+        levels.update(&[0.0; BANDS]);
+        assert!((levels.bands()[12] - (bands[12] - FALL_PER_WINDOW)).abs() < 1e-6);
+    }
+}
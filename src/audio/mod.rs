@@ -1,18 +1,29 @@
 //! The `audio` module contains the necessary components for playing audio files.
 //!
 //! It consists of the following sub-modules:
+//! - `device`: Keeps an audio backend open on its own thread, to play the audio of one media after
+//!   the other (e.g. the tracks of a playlist).
+//! - `levels`: Measures the levels of frequency bands of the audio played, for the VU meter.
 //! - `mpv_player`: Defines an `MpvPlayer` struct and related functionality for playing audio files
 //!   via the mpv player.
 //! - `player`: Defines an `AudioPlayer` struct and related functionality for playing audio files,
-//!   it also defines the trait AudioPlayerControls which an audio player backend should implement.
+//!   it also defines the trait AudioPlayerControls which an audio player backend should implement,
+//!   and the `AudioBackend` enum used to choose the backend at runtime.
 //! - `rodio_player`: Defines a `RodioPlayer` struct and related functionality for playing audio via
-//!   the rodio crate.
+//!   the rodio crate (used by both the rodio and symphonia backends).
 //! - `runner`: Implements the main functionality for running the audio playback.
+//! - `underruns`: Detects the times the audio output of the rodio backends ran out of samples.
+//! - `sync`: Aligns an external audio file with a video, by cross-correlating it with the audio
+//!   track of the video.
 //! - `utils`: Contains utility functions for working with audio files.
-#[cfg(not(feature = "rodio_audio"))]
+pub mod device;
+pub mod levels;
+#[cfg(any(feature = "mpv_0_34", feature = "mpv_0_35"))]
 pub mod mpv_player;
 pub mod player;
-#[cfg(feature = "rodio_audio")]
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
 pub mod rodio_player;
 pub mod runner;
+pub mod sync;
+pub mod underruns;
 pub mod utils;
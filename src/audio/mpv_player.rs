@@ -1,7 +1,11 @@
 //! High level audio player control based on MPV
-use crate::audio::player::AudioPlayerControls;
+use crate::audio::{
+    levels::AudioLevels,
+    player::{AudioPlayerControls, Volume},
+};
 use crate::common::errors::MyError;
 use libmpv::Mpv;
+use std::path::Path;
 
 /// The AudioPlayer struct handles audio playback using the libmpv backend.
 pub struct MpvAudioPlayer {
@@ -15,11 +19,12 @@ impl MpvAudioPlayer {
     /// # Arguments
     ///
     /// * input_path - The path to the audio file to be played.
+    /// * temp_dir - Unused, mpv plays the audio track directly from the input.
     ///
     /// # Returns
     ///
     /// A new AudioPlayer instance.
-    pub(crate) fn new(input_path: &str) -> Result<Self, MyError> {
+    pub(crate) fn new(input_path: &str, temp_dir: &Path) -> Result<Self, MyError> {
         let mpv = Mpv::new().expect("Failed to init MPV builder");
 
         mpv.set_property("vid", "no")
@@ -31,12 +36,9 @@ impl MpvAudioPlayer {
             ))
         })?;
 
-        mpv.command("loadfile", &[input_path])
-            .map_err(|err| MyError::Audio(format!("Failed to load audio file: {:?}", err)))?;
-        mpv.set_property("pause", true)
-            .map_err(|err| MyError::Audio(format!("Failed to set pause property: {:?}", err)))?;
-
-        Ok(Self { mpv })
+        let mut player = Self { mpv };
+        player.load(input_path, temp_dir, None)?;
+        Ok(player)
     }
 }
 impl AudioPlayerControls for MpvAudioPlayer {
@@ -120,6 +122,42 @@ impl AudioPlayerControls for MpvAudioPlayer {
         }
     }
 
+    /// Sets the volume of the audio, heard once it is unmuted if it is muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The volume level, from 0 (silent) to 1 (the original volume).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn set_volume(&mut self, level: f64) -> Result<(), MyError> {
+        // mpv's volume is in percent
+        self.mpv
+            .set_property("volume", level.clamp(0.0, 1.0) * 100.0)
+            .map_err(|err| MyError::Audio(format!("{:?}", err)))
+    }
+
+    /// Returns the volume of the audio.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the volume level and whether the audio is muted.
+    fn volume(&self) -> Result<Volume, MyError> {
+        let level: f64 = self
+            .mpv
+            .get_property("volume")
+            .map_err(|err| MyError::Audio(format!("{:?}", err)))?;
+        let muted = self
+            .mpv
+            .get_property("mute")
+            .map_err(|err| MyError::Audio(format!("{:?}", err)))?;
+        Ok(Volume {
+            level: level / 100.0,
+            muted,
+        })
+    }
+
     /// Stops the audio playback.
     ///
     /// # Returns
@@ -131,6 +169,62 @@ impl AudioPlayerControls for MpvAudioPlayer {
             .map_err(|err| MyError::Audio(format!("{:?}", err)))
     }
 
+    /// Sets the playback speed of the audio. mpv keeps the original pitch.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - The speed factor (1.0 is the original speed).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn set_speed(&mut self, speed: f64) -> Result<(), MyError> {
+        self.mpv
+            .set_property("speed", speed)
+            .map_err(|err| MyError::Audio(format!("{:?}", err)))
+    }
+
+    /// Moves the audio playback to the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The position to move to, in seconds from the start of the audio.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn seek(&mut self, seconds: f64) -> Result<(), MyError> {
+        self.mpv
+            .command("seek", &[&seconds.max(0.0).to_string(), "absolute"])
+            .map_err(|err| MyError::Audio(format!("{:?}", err)))
+    }
+
+    /// Replaces the audio played with the audio track of another input, in the same mpv
+    /// instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - The path to the audio file to be played.
+    /// * `_temp_dir` - Unused, mpv plays the audio track directly from the input.
+    /// * `_levels` - Unused, mpv does not measure the levels of the audio.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn load(
+        &mut self,
+        input_path: &str,
+        _temp_dir: &Path,
+        _levels: Option<AudioLevels>,
+    ) -> Result<(), MyError> {
+        self.mpv
+            .command("loadfile", &[input_path])
+            .map_err(|err| MyError::Audio(format!("Failed to load audio file: {:?}", err)))?;
+        self.mpv
+            .set_property("pause", true)
+            .map_err(|err| MyError::Audio(format!("Failed to set pause property: {:?}", err)))
+    }
+
     fn rewind(&mut self) -> Result<(), MyError> {
         // TODO
         Err(MyError::Audio(
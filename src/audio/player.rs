@@ -2,23 +2,211 @@
 //! basic structure that contains the audio player instance (depending on which
 //! audio backend is used). It also defines a trait AudioPlayerControls, which
 //! serves as the interface that audio backends are expected to implement.
+//!
+//! Several backends can be compiled in the same binary, the one used is chosen at runtime (see
+//! `AudioBackend`).
+use super::levels::AudioLevels;
+use crate::common::{errors::*, platform, stats::AudioUnderruns};
 use crate::MyError;
+use std::{fmt, path::Path};
 
-#[cfg(not(feature = "rodio_audio"))]
-use super::mpv_player::MpvAudioPlayer as BackendAudioPlayer;
+#[cfg(any(feature = "mpv_0_34", feature = "mpv_0_35"))]
+use super::mpv_player::MpvAudioPlayer;
 
-#[cfg(feature = "rodio_audio")]
-use super::rodio_player::RodioAudioPlayer as BackendAudioPlayer;
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+use super::rodio_player::RodioAudioPlayer;
+
+/// All the audio backends.
+const BACKENDS: [AudioBackend; 3] = [
+    AudioBackend::Rodio,
+    AudioBackend::Symphonia,
+    AudioBackend::Mpv,
+];
+
+/// An audio backend, which may or may not be compiled in this build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioBackend {
+    /// Plays the audio track extracted by `ffmpeg` with rodio (feature `rodio_audio`).
+    Rodio,
+    /// Decodes the audio track natively with symphonia, and plays it with rodio (feature
+    /// `symphonia_audio`). Does not need `ffmpeg`.
+    Symphonia,
+    /// Plays the audio track with libmpv (features `mpv_0_34` and `mpv_0_35`).
+    Mpv,
+}
+
+impl AudioBackend {
+    /// Returns the backend with the given name (`rodio`, `symphonia` or `mpv`), whether it is
+    /// compiled in or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the backend.
+    pub fn from_name(name: &str) -> Option<Self> {
+        BACKENDS
+            .into_iter()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the name of the backend, as accepted by `--audio-backend`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioBackend::Rodio => "rodio",
+            AudioBackend::Symphonia => "symphonia",
+            AudioBackend::Mpv => "mpv",
+        }
+    }
+
+    /// Returns whether the backend is compiled in this build.
+    pub fn is_available(&self) -> bool {
+        match self {
+            AudioBackend::Rodio => cfg!(feature = "rodio_audio"),
+            AudioBackend::Symphonia => cfg!(feature = "symphonia_audio"),
+            AudioBackend::Mpv => cfg!(any(feature = "mpv_0_34", feature = "mpv_0_35")),
+        }
+    }
+
+    /// Returns the backends compiled in this build.
+    pub fn available() -> Vec<Self> {
+        BACKENDS
+            .into_iter()
+            .filter(AudioBackend::is_available)
+            .collect()
+    }
+
+    /// Returns the backend used when none is chosen, which depends on the platform (see
+    /// `platform::default_audio_backend`), or `None` if the build has no audio backend.
+    pub fn preferred() -> Option<Self> {
+        platform::default_audio_backend()
+    }
+
+    /// Returns whether the backend measures the levels of the audio, for the VU meter.
+    pub fn measures_levels(&self) -> bool {
+        *self != AudioBackend::Mpv
+    }
+
+    /// Returns whether the backend detects the underruns of the audio output, see
+    /// `audio/underruns.rs`.
+    pub fn detects_underruns(&self) -> bool {
+        *self != AudioBackend::Mpv
+    }
+
+    /// Returns whether the backend needs `ffmpeg` to extract the audio track.
+    pub fn needs_ffmpeg(&self) -> bool {
+        *self == AudioBackend::Rodio
+    }
+
+    /// Returns the backend with the given name, checking that it is compiled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the backend, as given to `--audio-backend`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is unknown, or if the backend is not compiled in this build.
+    pub fn select(name: &str) -> Result<Self, MyError> {
+        let backend = Self::from_name(name).ok_or(MyError::Audio(format!(
+            "{ERROR_UNKNOWN_AUDIO_BACKEND}: {name}"
+        )))?;
+        if !backend.is_available() {
+            let available: Vec<&str> = Self::available().iter().map(|b| b.name()).collect();
+            return Err(MyError::Audio(format!(
+                "{ERROR_AUDIO_BACKEND_UNAVAILABLE}: {name} (available: {})",
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )));
+        }
+        Ok(backend)
+    }
+}
+
+impl fmt::Display for AudioBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The volume of the audio, reported to the terminal when it changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Volume {
+    /// The volume level, from 0 (silent) to 1 (the original volume).
+    pub level: f64,
+    /// Whether the audio is muted, the level being kept for when it is unmuted.
+    pub muted: bool,
+}
 
 pub struct AudioPlayer {
-    pub player: BackendAudioPlayer,
+    pub player: Box<dyn AudioPlayerControls>,
 }
 
 impl AudioPlayer {
-    pub fn new(input_file: &str) -> Result<Self, MyError> {
-        let player = BackendAudioPlayer::new(input_file)?;
+    /// Creates an audio player with the given backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend playing the audio, it must be compiled in.
+    /// * `input_file` - The path to the media whose audio track is played.
+    /// * `temp_dir` - The directory where the audio track can be extracted.
+    /// * `levels` - Where the levels of the audio are published for the VU meter, if it is shown.
+    ///   Only the rodio and symphonia backends measure them.
+    /// * `underruns` - Where the underruns of the audio output are counted, by the backends that
+    ///   detect them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is not compiled in, or cannot play the audio track.
+    // The arguments are unused by the backends that are not compiled in
+    #[allow(unused_variables)]
+    pub fn new(
+        backend: AudioBackend,
+        input_file: &str,
+        temp_dir: &Path,
+        levels: Option<AudioLevels>,
+        underruns: AudioUnderruns,
+    ) -> Result<Self, MyError> {
+        let player: Result<Box<dyn AudioPlayerControls>, MyError> = match backend {
+            #[cfg(feature = "rodio_audio")]
+            AudioBackend::Rodio => Ok(Box::new(RodioAudioPlayer::new(
+                input_file, temp_dir, levels, underruns,
+            )?)),
+            #[cfg(feature = "symphonia_audio")]
+            AudioBackend::Symphonia => Ok(Box::new(RodioAudioPlayer::new_native(
+                input_file, levels, underruns,
+            )?)),
+            #[cfg(any(feature = "mpv_0_34", feature = "mpv_0_35"))]
+            AudioBackend::Mpv => Ok(Box::new(MpvAudioPlayer::new(input_file, temp_dir)?)),
+            #[allow(unreachable_patterns)]
+            _ => Err(MyError::Audio(format!(
+                "{ERROR_AUDIO_BACKEND_UNAVAILABLE}: {backend}"
+            ))),
+        };
+
+        Ok(Self { player: player? })
+    }
 
-        Ok(Self { player })
+    /// Plays the audio track of another media with the same backend instance, keeping the audio
+    /// device open. Like a new player, the audio is loaded until the runner resumes it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_file` - The path to the media whose audio track is played.
+    /// * `temp_dir` - The directory where the audio track can be extracted.
+    /// * `levels` - Where the levels of the audio are published for the VU meter, if it is shown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot play the audio track.
+    pub fn load(
+        &mut self,
+        input_file: &str,
+        temp_dir: &Path,
+        levels: Option<AudioLevels>,
+    ) -> Result<(), MyError> {
+        self.player.load(input_file, temp_dir, levels)
     }
 }
 
@@ -31,4 +219,39 @@ pub trait AudioPlayerControls {
     fn unmute(&mut self) -> Result<(), MyError>;
     fn rewind(&mut self) -> Result<(), MyError>;
     fn toggle_mute(&mut self) -> Result<(), MyError>;
+    fn set_volume(&mut self, level: f64) -> Result<(), MyError>;
+    fn volume(&self) -> Result<Volume, MyError>;
+    fn set_speed(&mut self, speed: f64) -> Result<(), MyError>;
+    fn seek(&mut self, seconds: f64) -> Result<(), MyError>;
+    fn load(
+        &mut self,
+        input_file: &str,
+        temp_dir: &Path,
+        levels: Option<AudioLevels>,
+    ) -> Result<(), MyError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_backend_selection() {
+        for backend in BACKENDS {
+            assert_eq!(AudioBackend::from_name(backend.name()), Some(backend));
+        }
+        assert_eq!(AudioBackend::from_name("MPV"), Some(AudioBackend::Mpv));
+        assert!(AudioBackend::select("pulseaudio").is_err());
+
+        assert_eq!(
+            AudioBackend::preferred().is_some(),
+            !AudioBackend::available().is_empty()
+        );
+        for backend in BACKENDS {
+            assert_eq!(
+                AudioBackend::select(backend.name()).is_ok(),
+                backend.is_available()
+            );
+        }
+    }
 }
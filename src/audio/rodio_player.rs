@@ -1,8 +1,26 @@
 //! High level audio player control based on rodio
-use crate::audio::{player::AudioPlayerControls, utils::extract_audio};
-use crate::common::errors::MyError;
+#[cfg(feature = "rodio_audio")]
+use crate::audio::utils::extract_audio;
+use crate::audio::{
+    levels::{AudioLevels, LevelTap},
+    player::{AudioPlayerControls, Volume},
+    underruns::{UnderrunMonitor, UnderrunTap},
+};
+use crate::common::{errors::MyError, stats::AudioUnderruns};
 use rodio;
-use std::io::{BufReader, Cursor, Read, Seek};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The decoded audio file, read as it plays and seekable.
+type FileDecoder = rodio::decoder::Decoder<BufReader<File>>;
+
+/// Name of the temporary file where the audio track is extracted.
+#[cfg(feature = "rodio_audio")]
+const AUDIO_FILE_NAME: &str = "audio.mp3";
 
 /// The AudioPlayer struct handles audio playback using the rodio backend.
 pub struct RodioAudioPlayer {
@@ -10,8 +28,22 @@ pub struct RodioAudioPlayer {
     player: rodio::Sink,
     /// Keep OutputStream alive
     _stream: rodio::OutputStream,
-    /// Store content for rewind/replay
-    content: Vec<u8>,
+    /// The output stream, where the sinks of the audio loaded later are created.
+    stream_handle: rodio::OutputStreamHandle,
+    /// Whether the audio track of the inputs is extracted with ffmpeg before it is decoded,
+    /// rather than decoded directly with symphonia.
+    #[cfg_attr(not(feature = "rodio_audio"), allow(dead_code))]
+    extract: bool,
+    /// The audio file, decoded again to replay it once it has played to the end.
+    audio_file: PathBuf,
+    /// Where the levels of the audio are published, if they are measured.
+    levels: Option<AudioLevels>,
+    /// Detects the underruns of the audio played.
+    underruns: UnderrunMonitor,
+    /// The volume level, kept while the audio is muted.
+    volume: f64,
+    /// Whether the audio is muted.
+    muted: bool,
 }
 
 impl RodioAudioPlayer {
@@ -20,30 +52,140 @@ impl RodioAudioPlayer {
     /// # Arguments
     ///
     /// * input_path - The path to the audio file to be played.
+    /// * temp_dir - The directory where the audio track is extracted.
+    /// * levels - Where the levels of the audio are published, if they are measured.
+    /// * underruns - Where the underruns of the audio output are counted.
     ///
     /// # Returns
     ///
     /// A new AudioPlayer instance.
-    pub(crate) fn new(input_path: &str) -> Result<Self, MyError> {
+    #[cfg(feature = "rodio_audio")]
+    pub(crate) fn new(
+        input_path: &str,
+        temp_dir: &Path,
+        levels: Option<AudioLevels>,
+        underruns: AudioUnderruns,
+    ) -> Result<Self, MyError> {
+        Self::open(input_path, temp_dir, true, levels, underruns)
+    }
+
+    /// Creates a new AudioPlayer instance decoding the audio track of the input directly with
+    /// symphonia, without extracting it with ffmpeg first.
+    ///
+    /// # Arguments
+    ///
+    /// * input_path - The path to the media file to be played.
+    /// * levels - Where the levels of the audio are published, if they are measured.
+    /// * underruns - Where the underruns of the audio output are counted.
+    ///
+    /// # Returns
+    ///
+    /// A new AudioPlayer instance.
+    #[cfg(feature = "symphonia_audio")]
+    pub(crate) fn new_native(
+        input_path: &str,
+        levels: Option<AudioLevels>,
+        underruns: AudioUnderruns,
+    ) -> Result<Self, MyError> {
+        Self::open(input_path, Path::new(""), false, levels, underruns)
+    }
+
+    /// Opens the audio output and starts playing the audio track of an input with rodio.
+    ///
+    /// # Arguments
+    ///
+    /// * input_path - The path to the media file to be played.
+    /// * temp_dir - The directory where the audio track is extracted, if it is.
+    /// * extract - Whether the audio track is extracted with ffmpeg before it is decoded.
+    /// * levels - Where the levels of the audio are published, if they are measured.
+    /// * underruns - Where the underruns of the audio output are counted.
+    ///
+    /// # Returns
+    ///
+    /// A new AudioPlayer instance.
+    fn open(
+        input_path: &str,
+        temp_dir: &Path,
+        extract: bool,
+        levels: Option<AudioLevels>,
+        underruns: AudioUnderruns,
+    ) -> Result<Self, MyError> {
         let (_stream, stream_handle) = rodio::OutputStream::try_default().map_err(|err| {
             MyError::Audio(format!("Failed to initialize audio stream: {:?}", err))
         })?;
-        let audio_track = extract_audio(input_path)?;
-        // Play audio with rodio
-        let file = std::fs::File::open(audio_track.path())
-            .map_err(|err| MyError::Audio(format!("Failed to open audio file: {:?}", err)))?;
-        let mut buf = BufReader::new(file);
-        let mut content = Vec::new();
-        buf.by_ref().read_to_end(&mut content)?;
-        buf.rewind()?;
-        let player: rodio::Sink = stream_handle
-            .play_once(buf)
-            .map_err(|err| MyError::Audio(format!("Failed to start playback: {:?}", err)))?;
-        Ok(Self {
+        let player = Self::new_sink(&stream_handle)?;
+        let mut audio_player = Self {
             player,
             _stream,
-            content,
-        })
+            stream_handle,
+            extract,
+            audio_file: PathBuf::new(),
+            levels: None,
+            underruns: UnderrunMonitor::new(underruns),
+            volume: 1.0,
+            muted: false,
+        };
+        audio_player.load(input_path, temp_dir, levels)?;
+        Ok(audio_player)
+    }
+
+    /// Creates a sink playing on an output stream.
+    ///
+    /// # Arguments
+    ///
+    /// * stream_handle - The output stream.
+    fn new_sink(stream_handle: &rodio::OutputStreamHandle) -> Result<rodio::Sink, MyError> {
+        rodio::Sink::try_new(stream_handle)
+            .map_err(|err| MyError::Audio(format!("Failed to start playback: {:?}", err)))
+    }
+
+    /// Returns the file decoded to play the audio track of an input: the track extracted with
+    /// ffmpeg (rodio backend), or the input itself (symphonia backend).
+    ///
+    /// # Arguments
+    ///
+    /// * input_path - The path to the media file to be played.
+    /// * temp_dir - The directory where the audio track is extracted.
+    // The directory is unused without the rodio backend
+    #[allow(unused_variables)]
+    fn audio_track(&self, input_path: &str, temp_dir: &Path) -> Result<PathBuf, MyError> {
+        #[cfg(feature = "rodio_audio")]
+        if self.extract {
+            let audio_track = temp_dir.join(AUDIO_FILE_NAME);
+            extract_audio(input_path, &audio_track)?;
+            return Ok(audio_track);
+        }
+        Ok(PathBuf::from(input_path))
+    }
+
+    /// Decodes an audio file as it is read, rather than loading it whole in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * audio_file - The path to a file in a format rodio can decode.
+    ///
+    /// # Returns
+    ///
+    /// The decoded audio, which can be seeked.
+    fn decode(audio_file: &Path) -> Result<FileDecoder, MyError> {
+        let file = File::open(audio_file)
+            .map_err(|err| MyError::Audio(format!("Failed to open audio file: {:?}", err)))?;
+        rodio::decoder::Decoder::new(BufReader::new(file))
+            .map_err(|err| MyError::Audio(format!("Failed to start playback: {:?}", err)))
+    }
+
+    /// Queues decoded audio in the sink, tapping its underruns, and its levels if they are
+    /// measured.
+    ///
+    /// # Arguments
+    ///
+    /// * decoder - The decoded audio.
+    fn append(&self, decoder: FileDecoder) {
+        let decoder = UnderrunTap::new(decoder, self.underruns.clone());
+        match &self.levels {
+            Some(levels) => self.player.append(LevelTap::new(decoder, levels.clone())),
+            None => self.player.append(decoder),
+        }
     }
 }
 
@@ -55,6 +197,7 @@ impl AudioPlayerControls for RodioAudioPlayer {
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn pause(&mut self) -> Result<(), MyError> {
         self.player.pause();
+        self.underruns.pause();
         Ok(())
     }
 
@@ -65,6 +208,7 @@ impl AudioPlayerControls for RodioAudioPlayer {
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn resume(&mut self) -> Result<(), MyError> {
         self.player.play();
+        self.underruns.restart();
         Ok(())
     }
 
@@ -74,17 +218,12 @@ impl AudioPlayerControls for RodioAudioPlayer {
     ///
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn rewind(&mut self) -> Result<(), MyError> {
-        self.player.clear();
-        let input = Cursor::new(self.content.clone());
-        let input = rodio::decoder::Decoder::new(input).map_err(|err| {
-            MyError::Audio(format!(
-                "Could not set decoder on rewind content: {:?}",
-                err
-            ))
-        })?;
-        self.player.append(input);
-        self.player.play();
-        Ok(())
+        // The audio is dropped from the sink once it has played to the end, it is decoded again
+        if self.player.empty() || self.player.try_seek(Duration::ZERO).is_err() {
+            self.player.clear();
+            self.append(Self::decode(&self.audio_file)?);
+        }
+        self.resume()
     }
 
     /// Toggles the playback state (play/pause) of the audio.
@@ -106,6 +245,7 @@ impl AudioPlayerControls for RodioAudioPlayer {
     ///
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn mute(&mut self) -> Result<(), MyError> {
+        self.muted = true;
         self.player.set_volume(0.0);
         Ok(())
     }
@@ -116,7 +256,8 @@ impl AudioPlayerControls for RodioAudioPlayer {
     ///
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn unmute(&mut self) -> Result<(), MyError> {
-        self.player.set_volume(1.0);
+        self.muted = false;
+        self.player.set_volume(self.volume as f32);
         Ok(())
     }
 
@@ -126,13 +267,42 @@ impl AudioPlayerControls for RodioAudioPlayer {
     ///
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn toggle_mute(&mut self) -> Result<(), MyError> {
-        if self.player.volume() == 0.0 {
+        if self.muted {
             self.unmute()
         } else {
             self.mute()
         }
     }
 
+    /// Sets the volume of the audio, heard once it is unmuted if it is muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The volume level, from 0 (silent) to 1 (the original volume).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn set_volume(&mut self, level: f64) -> Result<(), MyError> {
+        self.volume = level.clamp(0.0, 1.0);
+        if !self.muted {
+            self.player.set_volume(self.volume as f32);
+        }
+        Ok(())
+    }
+
+    /// Returns the volume of the audio.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the volume level and whether the audio is muted.
+    fn volume(&self) -> Result<Volume, MyError> {
+        Ok(Volume {
+            level: self.volume,
+            muted: self.muted,
+        })
+    }
+
     /// Stops the audio playback.
     ///
     /// # Returns
@@ -140,6 +310,71 @@ impl AudioPlayerControls for RodioAudioPlayer {
     /// A `Result` indicating success or an `MyError::Audio` error.
     fn stop(&mut self) -> Result<(), MyError> {
         self.player.stop();
+        self.underruns.pause();
+        Ok(())
+    }
+
+    /// Sets the playback speed of the audio. The pitch changes with the speed.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - The speed factor (1.0 is the original speed).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn set_speed(&mut self, speed: f64) -> Result<(), MyError> {
+        self.player.set_speed(speed as f32);
+        self.underruns.set_speed(speed);
+        Ok(())
+    }
+
+    /// Moves the audio playback to the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The position to move to, in seconds from the start of the audio.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn seek(&mut self, seconds: f64) -> Result<(), MyError> {
+        self.player
+            .try_seek(Duration::from_secs_f64(seconds.max(0.0)))
+            .map_err(|err| MyError::Audio(format!("Failed to seek: {:?}", err)))
+    }
+
+    /// Replaces the audio played with the audio track of another input. The audio is played in a
+    /// new sink of the same output stream, which stays open.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - The path to the media file to be played.
+    /// * `temp_dir` - The directory where the audio track is extracted, if it is.
+    /// * `levels` - Where the levels of the audio are published, if they are measured.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or an `MyError::Audio` error.
+    fn load(
+        &mut self,
+        input_path: &str,
+        temp_dir: &Path,
+        levels: Option<AudioLevels>,
+    ) -> Result<(), MyError> {
+        // Dropping the previous sink stops its audio
+        self.player.stop();
+        let audio_file = self.audio_track(input_path, temp_dir)?;
+        let decoder = Self::decode(&audio_file)?;
+        self.player = Self::new_sink(&self.stream_handle)?;
+        // The volume carries over to the audio of the next input
+        self.player
+            .set_volume(if self.muted { 0.0 } else { self.volume as f32 });
+        self.audio_file = audio_file;
+        self.levels = levels;
+        self.append(decoder);
+        // The new sink plays at once
+        self.underruns.restart();
         Ok(())
     }
 }
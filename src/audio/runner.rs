@@ -2,10 +2,11 @@
 //! The Runner struct handles the audio pipeline, processing frames, managing
 //! playback state, and controlling the frame rate. It also handles commands for
 //! pausing/continuing, and stopping the playback.
-use crate::audio;
-use crate::audio::player::AudioPlayerControls;
+//!
+//! The changes of the volume are reported back to the terminal through the broker, to show them.
+use crate::audio::{self, player::Volume};
 use crate::common::errors::MyError;
-use crossbeam_channel::{select, Receiver};
+use crossbeam_channel::{select, Receiver, Sender};
 
 /// Represents the playback state of the Runner.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -29,6 +30,8 @@ pub struct Runner {
     state: State,
     /// The channel used to receive commands for pausing/continuing, and stopping.
     rx_controls: Receiver<Control>,
+    /// The channel reporting the changes of the audio to the broker.
+    tx_feedback: Sender<Feedback>,
 }
 
 /// Enum representing the different control commands that can be sent to the Runner.
@@ -42,17 +45,43 @@ pub enum Control {
     MuteUnmute,
     /// Command to stop the playback and exit the Runner.
     Exit,
+    /// Command to move playback to the given position, in seconds from the start of the audio.
+    Seek(f64),
+    /// Command to raise (or lower, if negative) the volume by the given step, from 0 to 1.
+    /// Changing the volume unmutes the audio.
+    ChangeVolume(f64),
+}
+
+/// Enum representing the changes of the audio reported to the broker, which forwards them to the
+/// terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feedback {
+    /// The volume changed, or the audio was muted or unmuted.
+    Volume(Volume),
+    /// The volume cannot change: the playback has no audio, e.g. the media has no audio track or
+    /// no audio backend is available. Reported by the broker itself.
+    Unavailable,
 }
 
 impl Runner {
-    pub fn new(audio_player: audio::player::AudioPlayer, rx_controls: Receiver<Control>) -> Self {
+    pub fn new(
+        audio_player: audio::player::AudioPlayer,
+        rx_controls: Receiver<Control>,
+        tx_feedback: Sender<Feedback>,
+    ) -> Self {
         Self {
             audio_player,
             state: State::Running,
             rx_controls,
+            tx_feedback,
         }
     }
 
+    /// Returns the audio player, e.g. to play other media with it once the playback is stopped.
+    pub fn into_player(self) -> audio::player::AudioPlayer {
+        self.audio_player
+    }
+
     /// The main function responsible playing the audio file. It handles the
     /// playback state, including handling commands for pausing/continuing, mute/unmute and
     /// stopping.
@@ -70,7 +99,8 @@ impl Runner {
         while self.state != State::Stopped {
             select! {
                 recv(self.rx_controls) -> msg => {
-                    match msg.unwrap() {
+                    // The broker closes the channel once the playback is stopped
+                    match msg.unwrap_or(Control::Exit) {
                         Control::PauseContinue => {
                             self.state = match self.state {
                                 State::Running => State::Paused,
@@ -81,10 +111,21 @@ impl Runner {
                         },
                         Control::MuteUnmute => {
                             self.audio_player.player.toggle_mute()?;
+                            self.report_volume();
+                        },
+                        Control::ChangeVolume(step) => {
+                            let volume = self.audio_player.player.volume()?;
+                            self.audio_player.player.unmute()?;
+                            self.audio_player.player.set_volume(volume.level + step)?;
+                            self.report_volume();
                         },
                         Control::Replay => {
                             self.audio_player.player.rewind()?;
                         },
+                        Control::Seek(seconds) => {
+                            // Best effort, not all audio formats can be seeked
+                            let _ = self.audio_player.player.seek(seconds);
+                        },
                         Control::Exit => {
                             self.state = State::Stopped;
                             self.audio_player.player.stop()?;
@@ -95,4 +136,11 @@ impl Runner {
         }
         Ok(())
     }
+
+    /// Reports the volume of the audio to the broker, once it changed.
+    fn report_volume(&self) {
+        if let Ok(volume) = self.audio_player.player.volume() {
+            let _ = self.tx_feedback.send(Feedback::Volume(volume));
+        }
+    }
 }
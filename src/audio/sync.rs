@@ -0,0 +1,209 @@
+//! Aligns an external audio file (`--audio-file`, e.g. a better dub or a separate recording) with
+//! a video, by cross-correlating it with the audio track of the video, which is muted.
+//!
+//! The beginning of both tracks is decoded with `ffmpeg` at a low sample rate, and reduced to its
+//! onset envelope: how much the loudness rises every 10 ms. Onsets (words, beats, hits) stand out
+//! the same way in different mixes and encodings of the same sound, so the lag at which the
+//! envelopes correlate best is the offset of the external audio. The audio played is then trimmed
+//! or delayed by that offset, so that it starts with the video.
+use crate::common::errors::*;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Name of the external audio aligned with the video, in the temporary directory.
+pub const SYNCED_AUDIO_FILE_NAME: &str = "synced_audio.mp3";
+/// Sample rate at which the tracks are decoded for the analysis.
+const SAMPLE_RATE: usize = 8000;
+/// Number of windows of the onset envelopes per second.
+const ENVELOPE_RATE: usize = 100;
+/// Duration of the beginning of the tracks that is analyzed, in seconds.
+const ANALYZED_SECONDS: usize = 90;
+/// Largest offset searched, in seconds, at most half of the analyzed duration.
+const MAX_OFFSET_SECONDS: usize = 30;
+/// Correlation of the envelopes (0-1) from which an offset is trusted.
+const MIN_CORRELATION: f32 = 0.2;
+
+/// Finds the offset of an external audio file relative to the audio track of a video.
+///
+/// # Arguments
+///
+/// * `video` - The path of the video, with an audio track.
+/// * `audio` - The path of the external audio file.
+///
+/// # Returns
+///
+/// A `Result` containing the position in the external audio where the video starts, in seconds
+/// (negative if the audio starts after the video), or `None` if the tracks do not match well
+/// enough to tell.
+///
+/// # Errors
+///
+/// Returns an error if `ffmpeg` cannot decode one of the tracks.
+pub fn find_offset(video: &Path, audio: &Path) -> Result<Option<f64>, MyError> {
+    let reference = onset_envelope(&decode_samples(video)?);
+    let external = onset_envelope(&decode_samples(audio)?);
+    Ok(best_lag(&reference, &external)
+        .filter(|(_, correlation)| *correlation >= MIN_CORRELATION)
+        .map(|(lag, _)| lag as f64 / ENVELOPE_RATE as f64))
+}
+
+/// Writes an audio file trimmed or delayed by an offset, with `ffmpeg`.
+///
+/// # Arguments
+///
+/// * `audio` - The path of the audio file.
+/// * `offset` - The position in the audio where the output starts, in seconds, or the silence
+///   added before it if negative.
+/// * `output` - The path of the aligned audio file.
+///
+/// # Errors
+///
+/// Returns an error if `ffmpeg` cannot be run or fails.
+pub fn write_aligned(audio: &Path, offset: f64, output: &Path) -> Result<(), MyError> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error"]);
+    if offset > 0.0 {
+        command.arg("-ss").arg(format!("{offset:.3}"));
+    }
+    command.arg("-i").arg(audio).arg("-vn");
+    if offset < 0.0 {
+        let delay = (-offset * 1000.0).round();
+        command.arg("-af").arg(format!("adelay={delay}:all=1"));
+    }
+    let status = command
+        .args(["-acodec", "mp3", "-y"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(MyError::Application(format!(
+            "{ERROR_AUDIO_SYNC}: {}",
+            audio.display()
+        ))),
+    }
+}
+
+/// Decodes the beginning of the audio of a file as mono samples, with `ffmpeg`.
+///
+/// # Arguments
+///
+/// * `path` - The path of the media file.
+///
+/// # Errors
+///
+/// Returns an error if `ffmpeg` cannot be run or decodes nothing.
+fn decode_samples(path: &Path) -> Result<Vec<f32>, MyError> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-t"])
+        .arg(ANALYZED_SECONDS.to_string())
+        .arg("-i")
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-ar"])
+        .arg(SAMPLE_RATE.to_string())
+        .args(["-f", "f32le", "-"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    let samples: Vec<f32> = output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    if samples.is_empty() {
+        return Err(MyError::Application(format!(
+            "{ERROR_AUDIO_SYNC}: {}",
+            path.display()
+        )));
+    }
+    Ok(samples)
+}
+
+/// Reduces audio samples to their onset envelope: the rise of the loudness from one window to the
+/// next, normalized to a mean of 0 and a standard deviation of 1.
+///
+/// # Arguments
+///
+/// * `samples` - The samples, at `SAMPLE_RATE`.
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    let loudness: Vec<f32> = samples
+        .chunks(SAMPLE_RATE / ENVELOPE_RATE)
+        .map(|window| {
+            let energy = window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32;
+            (energy + 1e-6).ln()
+        })
+        .collect();
+    let onsets: Vec<f32> = std::iter::once(0.0)
+        .chain(loudness.windows(2).map(|pair| (pair[1] - pair[0]).max(0.0)))
+        .collect();
+    let mean = onsets.iter().sum::<f32>() / onsets.len().max(1) as f32;
+    let variance =
+        onsets.iter().map(|o| (o - mean).powi(2)).sum::<f32>() / onsets.len().max(1) as f32;
+    let deviation = variance.sqrt().max(f32::EPSILON);
+    onsets.iter().map(|o| (o - mean) / deviation).collect()
+}
+
+/// Finds the lag at which an envelope correlates best with a reference envelope.
+///
+/// # Arguments
+///
+/// * `reference` - The envelope of the audio track of the video.
+/// * `other` - The envelope of the external audio.
+///
+/// # Returns
+///
+/// The lag, in windows of `other` after those of `reference`, and the correlation at that lag,
+/// or `None` if the envelopes are too short to be compared.
+fn best_lag(reference: &[f32], other: &[f32]) -> Option<(isize, f32)> {
+    let shortest = reference.len().min(other.len());
+    let max_lag = (MAX_OFFSET_SECONDS * ENVELOPE_RATE).min(shortest / 2) as isize;
+    if max_lag == 0 {
+        return None;
+    }
+    (-max_lag..=max_lag)
+        .map(|lag| {
+            let start = (-lag).max(0) as usize;
+            let end = reference.len().min((other.len() as isize - lag) as usize);
+            let overlap = &reference[start..end];
+            let sum: f32 = overlap
+                .iter()
+                .enumerate()
+                .map(|(i, r)| r * other[((start + i) as isize + lag) as usize])
+                .sum();
+            (lag, sum / overlap.len().max(1) as f32)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_lag() {
+        // Bursts of noise at irregular intervals, the external audio starting 1.5 s later
+        let mut seed = 7u32;
+        let mut samples = vec![0.0; SAMPLE_RATE * 20];
+        for start in [
+            1.0, 2.3, 2.9, 4.4, 6.1, 6.6, 8.7, 10.2, 11.0, 13.5, 15.8, 17.1,
+        ] {
+            let start = (start * SAMPLE_RATE as f64) as usize;
+            for sample in &mut samples[start..start + SAMPLE_RATE / 10] {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                *sample = (seed >> 16) as f32 / 65_536.0 - 0.5;
+            }
+        }
+        let reference = onset_envelope(&samples);
+        let external = onset_envelope(&samples[SAMPLE_RATE * 3 / 2..]);
+        let (lag, correlation) = best_lag(&reference, &external).unwrap();
+        assert_eq!(lag, -150);
+        assert!(correlation > MIN_CORRELATION);
+
+        let silence = onset_envelope(&vec![0.0; SAMPLE_RATE * 20]);
+        let (_, correlation) = best_lag(&reference, &silence).unwrap();
+        assert!(correlation < MIN_CORRELATION);
+        assert!(best_lag(&reference, &[]).is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Detects the underruns of the rodio backends: the times the audio output ran out of samples
+//! because the audio was not decoded in time (e.g. on a slow machine busy decoding the video),
+//! heard as crackles or gaps.
+//!
+//! The output pulls the samples it plays from the decoded audio, a little ahead of time and in
+//! bursts. The `UnderrunTap` compares how much audio was pulled with the time elapsed: the audio
+//! pulled ahead of time (the lead) stays above a floor when the output keeps up, and drops below
+//! it when the output starved and played silence meanwhile, which is counted as an underrun. The
+//! floor is measured again every few seconds, so that the clock of the audio device drifting from
+//! the system clock is not taken for underruns, and after pauses, seeks and changes of speed.
+use crate::common::stats::AudioUnderruns;
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+use rodio::{source::SeekError, Sample, Source};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How far the lead can drop below its floor before an underrun is counted.
+const TOLERANCE: Duration = Duration::from_millis(50);
+/// How often the floor of the lead is measured again.
+const FLOOR_WINDOW: Duration = Duration::from_secs(2);
+/// How long the lead is not measured after the playback restarts, while the output fills up.
+const SETTLE: Duration = Duration::from_millis(200);
+/// Number of frames (samples of all the channels) pulled between two measures of the lead.
+#[cfg_attr(
+    not(any(feature = "rodio_audio", feature = "symphonia_audio")),
+    allow(dead_code)
+)]
+const MEASURE_FRAMES: u32 = 256;
+
+/// Compares the audio pulled by the output with the time elapsed.
+#[derive(Debug)]
+struct LeadClock {
+    /// When the playback started or restarted, if it plays.
+    start: Option<Instant>,
+    /// The audio pulled since the start, in seconds of the source.
+    pulled: f64,
+    /// The playback speed, at which the output pulls the audio of the source.
+    speed: f64,
+    /// The lowest lead of the last window, in seconds, once a window was measured.
+    floor: Option<f64>,
+    /// The lowest lead of the current window, and when the window started.
+    window: (f64, Instant),
+}
+
+impl LeadClock {
+    /// Starts measuring the lead, at the given speed.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    /// * `speed` - The playback speed.
+    fn new(now: Instant, speed: f64) -> Self {
+        Self {
+            start: Some(now + SETTLE),
+            pulled: 0.0,
+            speed,
+            floor: None,
+            window: (f64::INFINITY, now + SETTLE),
+        }
+    }
+
+    /// Records audio pulled by the output.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The duration of the audio pulled, in the source.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// Whether the output ran out of samples since the audio was last pulled.
+    fn pulled(&mut self, seconds: f64, now: Instant) -> bool {
+        let Some(start) = self.start.filter(|start| now >= *start) else {
+            return false;
+        };
+        self.pulled += seconds;
+        let lead = self.pulled - now.duration_since(start).as_secs_f64() * self.speed;
+        let underrun = self
+            .floor
+            .is_some_and(|floor| lead < floor - TOLERANCE.as_secs_f64());
+        if underrun {
+            // The audio is played that much later from now on
+            self.floor = Some(lead);
+        }
+        let (lowest, window_start) = &mut self.window;
+        *lowest = lowest.min(lead);
+        if now.duration_since(*window_start) >= FLOOR_WINDOW {
+            self.floor = Some(*lowest);
+            self.window = (f64::INFINITY, now);
+        }
+        underrun
+    }
+}
+
+/// Detects the underruns of the audio played, counting them in `AudioUnderruns`. Shared between
+/// the player, which tells when the playback pauses and restarts, and the `UnderrunTap` of the
+/// audio played.
+#[derive(Debug, Clone)]
+pub struct UnderrunMonitor {
+    /// Where the underruns are counted.
+    underruns: AudioUnderruns,
+    /// The clock of the playback, `None` while it is paused.
+    clock: Arc<Mutex<Option<LeadClock>>>,
+    /// The playback speed.
+    speed: Arc<Mutex<f64>>,
+}
+
+impl UnderrunMonitor {
+    /// Constructs a monitor, for a paused playback.
+    ///
+    /// # Arguments
+    ///
+    /// * `underruns` - Where the underruns are counted.
+    pub fn new(underruns: AudioUnderruns) -> Self {
+        Self {
+            underruns,
+            clock: Arc::new(Mutex::new(None)),
+            speed: Arc::new(Mutex::new(1.0)),
+        }
+    }
+
+    /// Starts or restarts measuring the playback, e.g. after it resumed or seeked.
+    pub fn restart(&self) {
+        let speed = self.speed.lock().map_or(1.0, |speed| *speed);
+        if let Ok(mut clock) = self.clock.lock() {
+            *clock = Some(LeadClock::new(Instant::now(), speed));
+        }
+    }
+
+    /// Stops measuring the playback while it is paused or stopped, when the output pulls nothing.
+    pub fn pause(&self) {
+        if let Ok(mut clock) = self.clock.lock() {
+            *clock = None;
+        }
+    }
+
+    /// Sets the playback speed, restarting the measure if the playback is not paused.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - The speed factor (1.0 is the original speed).
+    pub fn set_speed(&self, speed: f64) {
+        if let Ok(mut current) = self.speed.lock() {
+            *current = speed;
+        }
+        let playing = self.clock.lock().is_ok_and(|clock| clock.is_some());
+        if playing {
+            self.restart();
+        }
+    }
+
+    /// Records audio pulled by the output, counting an underrun if it ran out of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The duration of the audio pulled.
+    #[cfg_attr(
+        not(any(feature = "rodio_audio", feature = "symphonia_audio")),
+        allow(dead_code)
+    )]
+    fn pulled(&self, seconds: f64) {
+        let underrun = match self.clock.lock() {
+            Ok(mut clock) => clock
+                .as_mut()
+                .is_some_and(|clock| clock.pulled(seconds, Instant::now())),
+            Err(_) => false,
+        };
+        if underrun {
+            self.underruns.record();
+        }
+    }
+}
+
+/// A rodio source passing the samples of another source through unchanged, telling an
+/// `UnderrunMonitor` how much audio the output pulled.
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+pub struct UnderrunTap<S> {
+    /// The source tapped.
+    source: S,
+    /// The monitor of the underruns.
+    monitor: UnderrunMonitor,
+    /// The samples pulled since the monitor was last told, over all the channels.
+    samples: u32,
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> UnderrunTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    /// Taps a source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source played.
+    /// * `monitor` - The monitor of the underruns.
+    pub fn new(source: S, monitor: UnderrunMonitor) -> Self {
+        Self {
+            source,
+            monitor,
+            samples: 0,
+        }
+    }
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> Iterator for UnderrunTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let channels = self.source.channels().max(1) as u32;
+        self.samples += 1;
+        if self.samples >= MEASURE_FRAMES * channels {
+            let frames = self.samples / channels;
+            self.monitor
+                .pulled(frames as f64 / self.source.sample_rate().max(1) as f64);
+            self.samples = 0;
+        }
+        Some(sample)
+    }
+}
+
+#[cfg(any(feature = "rodio_audio", feature = "symphonia_audio"))]
+impl<S> Source for UnderrunTap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // Seeking takes time, the output fills up again afterwards
+        let result = self.source.try_seek(pos);
+        self.samples = 0;
+        self.monitor.restart();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lead_clock() {
+        // The output pulls 20 ms of audio every 20 ms, 40 ms ahead
+        let start = Instant::now();
+        let mut clock = LeadClock::new(start, 1.0);
+        let at = |ms: u64| start + SETTLE + Duration::from_millis(ms);
+        let mut underruns = 0;
+        let mut time = 0;
+        for _ in 0..200 {
+            underruns += clock.pulled(0.02, at(time)) as u32;
+            time += 20;
+        }
+        assert!(clock.floor.is_some());
+        assert_eq!(underruns, 0);
+
+        // A stall of 150 ms, then the output keeps up again
+        time += 150;
+        for _ in 0..50 {
+            underruns += clock.pulled(0.02, at(time)) as u32;
+            time += 20;
+        }
+        assert_eq!(underruns, 1);
+
+        // Audio pulled while the output fills up after a restart is not measured
+        let mut clock = LeadClock::new(start, 1.0);
+        assert!(!clock.pulled(0.02, start));
+        assert_eq!(clock.pulled, 0.0);
+    }
+}
@@ -7,18 +7,11 @@
 //! the audio stream from the video file, and convert it to mp3 format.
 use crate::common::errors::MyError;
 use serde_json::Value;
-use std::path::PathBuf;
+use std::path::Path;
 use std::process::{Command, Stdio};
-use tempfile::NamedTempFile;
 
 #[allow(dead_code)]
-pub fn extract_audio(input_path: &str) -> std::io::Result<NamedTempFile> {
-    let output_temp = tempfile::Builder::new()
-        .prefix("my_temp_file_")
-        .suffix(".mp3")
-        .tempfile()?;
-    let output_path: PathBuf = output_temp.path().to_path_buf();
-
+pub fn extract_audio(input_path: &str, output_path: &Path) -> std::io::Result<()> {
     let status = Command::new("ffmpeg")
         .arg("-i")
         .arg(input_path)
@@ -26,11 +19,11 @@ pub fn extract_audio(input_path: &str) -> std::io::Result<NamedTempFile> {
         .arg("-acodec")
         .arg("mp3") // Use the mp3 codec
         .arg("-y") // Overwrite output file if it exists
-        .arg(&output_path)
+        .arg(output_path)
         .status()?;
 
     if status.success() {
-        Ok(output_temp)
+        Ok(())
     } else {
         Err(std::io::Error::new(
             std::io::ErrorKind::Other,
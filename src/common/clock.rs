@@ -0,0 +1,43 @@
+//! Shares the playback position between the threads: the pipeline sets it with the position of
+//! each frame it renders, and the terminal reads it to show what is timed with the media, such as
+//! the subtitles.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Bits stored while the position is unknown: a NaN, which no position can be.
+const UNKNOWN: u64 = u64::MAX;
+
+/// The position of the frame shown last, in seconds from the start of the media.
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    /// The bits of the position, as an `f64`.
+    position: Arc<AtomicU64>,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            position: Arc::new(AtomicU64::new(UNKNOWN)),
+        }
+    }
+}
+
+impl PlaybackClock {
+    /// Sets the position of the frame rendered last.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position in seconds, or `None` if the media has no position (e.g. live
+    ///   streams).
+    pub fn set(&self, position: Option<f64>) {
+        let bits = position.map_or(UNKNOWN, f64::to_bits);
+        self.position.store(bits, Ordering::Relaxed);
+    }
+
+    /// Returns the position of the frame rendered last, or `None` if it is unknown.
+    pub fn position(&self) -> Option<f64> {
+        Some(f64::from_bits(self.position.load(Ordering::Relaxed))).filter(|p| !p.is_nan())
+    }
+}
@@ -37,6 +37,7 @@ impl From<io::Error> for MyError {
     }
 }
 
+#[cfg(feature = "opencv_decoder")]
 impl From<opencv::Error> for MyError {
     fn from(error: opencv::Error) -> Self {
         MyError::Application(format!("{error}"))
@@ -47,6 +48,10 @@ impl From<opencv::Error> for MyError {
 pub const ERROR_DECODING_IMAGE: &str = "Error decoding image";
 /// Error message for issues related to opening a video.
 pub const ERROR_OPENING_VIDEO: &str = "Error opening video";
+/// Error message for the videos the built-in decoder does not read, when no video decoder library
+/// is compiled in.
+pub const ERROR_UNSUPPORTED_VIDEO: &str =
+    "Only YUV4MPEG2 and Motion JPEG AVI videos are supported by this build, rebuild tplay with `--features ffmpeg_decoder` to play the others";
 /// Error message for issues related to downloading a resource.
 pub const ERROR_DOWNLOADING_RESOURCE: &str = "Error downloading resource";
 /// Error message for issues related to opening a resource.
@@ -59,5 +64,99 @@ pub const ERROR_PARSE_DIGIT_FAILED: &str = "Failed to parse digit";
 pub const ERROR_CHANNEL: &str = "Error during channel communication";
 /// Error message for issues related to data processing.
 pub const ERROR_DATA: &str = "Data error";
+/// Error message for media that cannot be played without external tools.
+pub const ERROR_EXTERNAL_TOOLS_DISABLED: &str =
+    "YouTube playback requires yt-dlp, which is disabled by --no-external-tools";
+/// Error message for issues related to extracting the frame rate of a video.
+pub const ERROR_EXTRACTING_FPS: &str = "Failed to extract fps from video. Is ffprobe installed?";
+/// Error message for JPEG XL images when the `jxl` feature is disabled.
+pub const ERROR_JXL_UNSUPPORTED: &str =
+    "JPEG XL images are not supported by this build, rebuild tplay with `--features jxl`";
+/// Error message for HEIC/HEIF images when the `heic` feature is disabled.
+pub const ERROR_HEIC_UNSUPPORTED: &str =
+    "HEIC/HEIF images are not supported by this build, rebuild tplay with `--features heic`";
+/// Error message for malformed data URLs.
+pub const ERROR_DATA_URL: &str = "Invalid data URL";
+/// Error message for a color theme name that is not in the theme registry.
+pub const ERROR_UNKNOWN_THEME: &str = "Unknown color theme";
+/// Error message when the priority of a thread cannot be changed.
+pub const ERROR_THREAD_PRIORITY: &str = "Cannot change the thread priority";
+/// Error message when the frame statistics file cannot be written.
+pub const ERROR_STATS_FILE: &str = "Cannot write the frame statistics file";
+/// Error message when the frame hashes file cannot be written.
+pub const ERROR_FRAME_HASHES_FILE: &str = "Cannot write the frame hashes file";
+/// Error message when the properties of a media cannot be read by the linked libav libraries.
+pub const ERROR_PROBING_MEDIA: &str = "Failed to read the properties of the media";
+/// Error message for an unknown audio backend name.
+pub const ERROR_UNKNOWN_AUDIO_BACKEND: &str = "Unknown audio backend";
+/// Error message for an audio backend that is not compiled in this build.
+pub const ERROR_AUDIO_BACKEND_UNAVAILABLE: &str = "Audio backend not compiled in this build";
+/// Error message for a subtitle file that cannot be read.
+pub const ERROR_READING_SUBTITLES: &str = "Could not read the subtitle file";
+/// Error message for a palette file that cannot be read or holds no colors.
+pub const ERROR_READING_PALETTE: &str = "Could not read the palette";
+/// Error message for a keymap file that cannot be read or has a line that is not a binding.
+pub const ERROR_READING_KEYMAP: &str = "Could not read the keymap";
+/// Error message for the VU meter with an audio backend that does not measure the audio levels.
+pub const ERROR_VU_METER_BACKEND: &str = "--vu needs the rodio or symphonia audio backend";
+/// Error message for a frame rate given with `--fps` that is not a positive number.
+pub const ERROR_INVALID_FPS: &str = "--fps must be a positive number of frames per second";
+/// Error message for a missing media argument.
+pub const ERROR_MISSING_INPUT: &str = "No media to play was given";
+/// Error message for issues related to the temporary files directory.
+pub const ERROR_TEMP_DIR: &str = "Cannot set up the temporary files directory";
 /// Error message for issues related to resizing an image.
 pub const ERROR_RESIZE: &str = "Image resizing error";
+/// Error message for media without any frame to show.
+pub const ERROR_NO_FRAMES: &str = "The media has no frames";
+/// Error message for contact sheets too small to fit the frames.
+pub const ERROR_SHEET_TOO_SMALL: &str = "The contact sheet is too small for this many frames";
+/// Error message when the remote control is not supported on this platform.
+pub const ERROR_REMOTE_UNSUPPORTED: &str = "Remote control is only supported on Unix systems";
+/// Error message when no running tplay listens on the control socket.
+pub const ERROR_REMOTE_NOT_RUNNING: &str = "No running tplay instance to control";
+/// Error message when another running tplay already owns the control socket.
+pub const ERROR_REMOTE_IN_USE: &str = "The control socket is used by another tplay instance";
+/// Error message for a remote command rejected by the running tplay.
+pub const ERROR_REMOTE_COMMAND: &str = "Remote command failed";
+/// Error message for `--gamepad` when the `gamepad` feature is disabled.
+pub const ERROR_GAMEPAD_UNSUPPORTED: &str =
+    "Game controllers are not supported by this build, rebuild tplay with `--features gamepad`";
+/// Error message when the game controllers cannot be read.
+pub const ERROR_GAMEPAD_UNAVAILABLE: &str = "Cannot read the game controllers";
+/// Error message when the port of a watch-together session cannot be listened on.
+pub const ERROR_WATCH_HOST: &str = "Cannot host the watch-together session";
+/// Error message when the host of a watch-together session cannot be reached.
+pub const ERROR_WATCH_JOIN: &str = "Cannot join the watch-together session";
+/// Error message when the screensaver cannot be inhibited during playback.
+pub const ERROR_INHIBIT: &str = "Cannot inhibit the screensaver";
+/// Error message for playlists without tracks.
+pub const ERROR_EMPTY_PLAYLIST: &str = "The playlist has no tracks";
+/// Error message for an external audio file (`--audio-file`) that does not exist.
+pub const ERROR_AUDIO_FILE_NOT_FOUND: &str = "The audio file does not exist";
+/// Error message when the external audio file cannot be decoded or aligned with the video.
+pub const ERROR_AUDIO_SYNC: &str = "Cannot align the audio file with the video";
+/// Error message when the external audio file does not match the audio track of the video.
+pub const ERROR_AUDIO_NOT_SYNCED: &str =
+    "The audio file does not match the audio track of the video, playing it unaligned";
+/// Error message when a screenshot cannot be saved.
+pub const ERROR_SCREENSHOT: &str = "Could not save the screenshot";
+/// Error message when the frame cannot be copied to the clipboard.
+pub const ERROR_CLIPBOARD: &str = "Could not copy the frame to the clipboard";
+/// Error message when tplay cannot be suspended to the shell.
+pub const ERROR_SUSPEND: &str = "Suspending to the shell needs job control, only available on Unix";
+/// Error message for slideshow directories without images.
+pub const ERROR_EMPTY_SLIDESHOW: &str = "The directory has no images to show";
+/// Error message for a playback thread still running after the playback was stopped.
+pub const ERROR_THREAD_SHUTDOWN: &str = "A playback thread did not stop in time";
+/// Error message for a playback thread that panicked.
+pub const ERROR_THREAD_PANICKED: &str = "A playback thread crashed";
+/// Error message when the thread playing the audio is gone.
+pub const ERROR_AUDIO_DEVICE: &str = "The audio device stopped";
+/// Error message when the custom character maps file cannot be read.
+pub const ERROR_READING_CHAR_MAPS: &str = "Could not read the custom character maps";
+/// Error message when a character map cannot be saved to the custom character maps file.
+pub const ERROR_SAVING_CHAR_MAP: &str = "Could not save the character map";
+/// Error message for the removed `--w-mod` option, still accepted so that existing commands work.
+pub const ERROR_W_MOD_DEPRECATED: &str =
+    "--w-mod is deprecated and ignored: the layout now adapts to the width of the glyphs automatically";
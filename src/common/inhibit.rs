@@ -0,0 +1,250 @@
+//! This module keeps the screensaver and the system sleep away while media are playing, and lets
+//! them come back when the playback is paused or over.
+//!
+//! Each platform has its own mechanism: on Linux and the BSDs the desktop screensaver is asked
+//! over D-Bus (`org.freedesktop.ScreenSaver`), or without a session bus or a screensaver service
+//! on it the `systemd-inhibit` tool is run, blocking the idle state and the sleep. On macOS the
+//! `caffeinate` tool is run, and on Windows the execution state of the thread is raised. The
+//! screensaver service releases the inhibition when the connection asking for it closes, and the
+//! tools hold it for as long as they run.
+use crate::common::errors::*;
+
+/// Name of the application given to the screensaver and to `systemd-inhibit`, shown by some
+/// desktops and by `systemd-inhibit --list`.
+#[cfg(all(unix, not(target_vendor = "apple")))]
+const APPLICATION_NAME: &str = "tplay";
+/// Reason of the inhibition given to the screensaver and to `systemd-inhibit`.
+#[cfg(all(unix, not(target_vendor = "apple")))]
+const INHIBIT_REASON: &str = "Playing media";
+/// Name and interface of the desktop screensaver service.
+#[cfg(all(unix, not(target_vendor = "apple")))]
+const SCREENSAVER_SERVICE: &str = "org.freedesktop.ScreenSaver";
+/// Object path of the desktop screensaver service.
+#[cfg(all(unix, not(target_vendor = "apple")))]
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+
+/// Inhibits the screensaver and the system sleep while the playback is running.
+///
+/// Inhibiting is best effort: without a screensaver service or the tool of the platform, or on
+/// unsupported platforms, the playback goes on as usual. A failed attempt, including a tool that
+/// exits at once because the inhibition was refused, is not retried until the next playback.
+#[derive(Default)]
+pub struct ScreenSaverInhibitor {
+    /// The active inhibition, released when dropped.
+    inhibition: Option<Inhibition>,
+    /// Whether inhibiting failed, so that it is not attempted on every poll.
+    failed: bool,
+}
+
+impl ScreenSaverInhibitor {
+    /// Inhibits the screensaver, or releases the inhibition. Calling it again with the same value
+    /// does nothing, so it can be called on every poll of the playback state.
+    ///
+    /// # Arguments
+    ///
+    /// * `inhibited` - Whether the screensaver should be inhibited, e.g. while not paused.
+    pub fn set_inhibited(&mut self, inhibited: bool) {
+        if self
+            .inhibition
+            .as_mut()
+            .is_some_and(|inhibition| !inhibition.is_held())
+        {
+            self.inhibition = None;
+            self.failed = true;
+        }
+        if !inhibited {
+            self.inhibition = None;
+        } else if self.inhibition.is_none() && !self.failed {
+            match inhibit() {
+                Ok(inhibition) => self.inhibition = Some(inhibition),
+                Err(_) => self.failed = true,
+            }
+        }
+    }
+}
+
+/// An inhibition of the screensaver, released when dropped (or when tplay exits).
+#[cfg(all(unix, not(target_vendor = "apple")))]
+enum Inhibition {
+    /// Granted by the screensaver service, to the session-bus connection kept open meanwhile.
+    ScreenSaver {
+        /// The connection that asked for the inhibition.
+        connection: zbus::blocking::Connection,
+        /// The cookie identifying the inhibition, to release it.
+        cookie: u32,
+    },
+    /// The `systemd-inhibit` process, running `cat` until its input is closed.
+    Systemd(std::process::Child),
+}
+
+#[cfg(all(unix, not(target_vendor = "apple")))]
+impl Inhibition {
+    /// Returns whether the inhibition still holds: `systemd-inhibit` exits at once if the
+    /// inhibition is refused.
+    fn is_held(&mut self) -> bool {
+        match self {
+            Inhibition::ScreenSaver { .. } => true,
+            Inhibition::Systemd(child) => matches!(child.try_wait(), Ok(None)),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_vendor = "apple")))]
+impl Drop for Inhibition {
+    fn drop(&mut self) {
+        match self {
+            Inhibition::ScreenSaver { connection, cookie } => {
+                let _ = connection.call_method(
+                    Some(SCREENSAVER_SERVICE),
+                    SCREENSAVER_PATH,
+                    Some(SCREENSAVER_SERVICE),
+                    "UnInhibit",
+                    &(*cookie,),
+                );
+            }
+            Inhibition::Systemd(child) => {
+                drop(child.stdin.take());
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+/// An inhibition of the screensaver: the `caffeinate` process, stopped when dropped.
+#[cfg(target_vendor = "apple")]
+struct Inhibition(std::process::Child);
+
+#[cfg(target_vendor = "apple")]
+impl Inhibition {
+    /// Returns whether the inhibition still holds, while `caffeinate` runs.
+    fn is_held(&mut self) -> bool {
+        matches!(self.0.try_wait(), Ok(None))
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+impl Drop for Inhibition {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// An inhibition of the screensaver: the raised execution state of the thread, restored when
+/// dropped.
+#[cfg(windows)]
+struct Inhibition;
+
+#[cfg(windows)]
+impl Inhibition {
+    /// Returns whether the inhibition still holds, which it does until dropped.
+    fn is_held(&mut self) -> bool {
+        true
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Inhibition {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS};
+        // SAFETY: plain system call without pointers.
+        unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+    }
+}
+
+/// No inhibition is possible on the other platforms.
+#[cfg(not(any(unix, windows)))]
+enum Inhibition {}
+
+#[cfg(not(any(unix, windows)))]
+impl Inhibition {
+    /// Never called, as there is no inhibition.
+    fn is_held(&mut self) -> bool {
+        match *self {}
+    }
+}
+
+/// Inhibits the screensaver and the system sleep.
+///
+/// # Returns
+///
+/// A `Result` containing the inhibition, lasting until it is dropped.
+///
+/// # Errors
+///
+/// Returns an error if no screensaver service accepted the inhibition.
+fn inhibit() -> Result<Inhibition, MyError> {
+    #[cfg(all(unix, not(target_vendor = "apple")))]
+    {
+        use std::process::{Command, Stdio};
+        let screensaver = zbus::blocking::Connection::session().and_then(|connection| {
+            let reply = connection.call_method(
+                Some(SCREENSAVER_SERVICE),
+                SCREENSAVER_PATH,
+                Some(SCREENSAVER_SERVICE),
+                "Inhibit",
+                &(APPLICATION_NAME, INHIBIT_REASON),
+            )?;
+            let cookie = reply.body().deserialize::<u32>()?;
+            Ok((connection, cookie))
+        });
+        let screensaver_error = match screensaver {
+            Ok((connection, cookie)) => return Ok(Inhibition::ScreenSaver { connection, cookie }),
+            Err(err) => err,
+        };
+        // Without a session bus or a screensaver service on it (e.g. on tiling window managers),
+        // keep the idle state and the system sleep away while `cat` waits for its input to close
+        Command::new("systemd-inhibit")
+            .arg("--what=idle:sleep")
+            .arg(format!("--who={APPLICATION_NAME}"))
+            .arg(format!("--why={INHIBIT_REASON}"))
+            .arg("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(Inhibition::Systemd)
+            .map_err(|err| {
+                MyError::Application(format!("{ERROR_INHIBIT}: {screensaver_error}, {err}"))
+            })
+    }
+
+    #[cfg(target_vendor = "apple")]
+    {
+        use std::process::{Command, Stdio};
+        // Keep the display and the system awake, until tplay exits at the latest
+        Command::new("caffeinate")
+            .args(["-d", "-i", "-w", &std::process::id().to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(Inhibition)
+            .map_err(|err| MyError::Application(format!("{ERROR_INHIBIT}: {err}")))
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Power::{
+            SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+        };
+        // SAFETY: plain system call without pointers.
+        let state = unsafe {
+            SetThreadExecutionState(ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED)
+        };
+        match state {
+            0 => Err(MyError::Application(format!(
+                "{ERROR_INHIBIT}: {}",
+                std::io::Error::last_os_error()
+            ))),
+            _ => Ok(Inhibition),
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(MyError::Application(format!(
+            "{ERROR_INHIBIT}: not supported on this platform"
+        )))
+    }
+}
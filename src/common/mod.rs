@@ -1,7 +1,23 @@
 //! This module contains error handling utilities for the application.
 //!
 //! It consists of the following sub-modules:
+//! - `clock`: Shares the playback position of the pipeline with the terminal.
 //! - `errors`: Defines an `ApplicationError` enum and related functionality for handling application errors.
+//! - `inhibit`: Inhibits the screensaver and the system sleep during playback.
+//! - `platform`: Detects the platform and chooses the default audio backend for it.
+//! - `priority`: Changes the scheduling priority of the playback threads.
+//! - `probe`: Reads the properties of video files with ffprobe, caching the results across runs.
+//! - `stats`: Records per-frame timing statistics as CSV.
+//! - `temp`: Manages the directory where temporary files (downloads, audio tracks) are stored.
+//! - `tools`: Detects the external command line tools (ffmpeg, ffprobe, yt-dlp) the application uses.
 //! - `utils`: Contains utility functions
+pub mod clock;
 pub mod errors;
+pub mod inhibit;
+pub mod platform;
+pub mod priority;
+pub mod probe;
+pub mod stats;
+pub mod temp;
+pub mod tools;
 pub mod utils;
@@ -0,0 +1,257 @@
+//! This module detects the platform tplay runs on, and chooses sensible defaults for it.
+//!
+//! The default audio backend depends on the OS: rodio (CoreAudio) on macOS, rodio (WASAPI) on
+//! Windows, and mpv on Linux when it is compiled in, since it follows the user's mpv configuration
+//! (audio device, PipeWire/PulseAudio output). `SystemInfo` gathers what was detected, and is
+//! printed by `tplay info --system`. `peak_memory` reads the resource usage of the process, for
+//! the summary printed at the end of the playback.
+use crate::audio::player::AudioBackend;
+use crate::common::tools::ExternalTool;
+use crate::pipeline::video;
+use std::{
+    env, fmt,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Returns the audio backends in order of preference on the given OS, whether they are compiled
+/// in or not. Static builds prefer symphonia over rodio, since it does not need `ffmpeg`.
+///
+/// # Arguments
+///
+/// * `os` - The name of the OS, as in `std::env::consts::OS`.
+pub fn audio_backend_preference(os: &str) -> [AudioBackend; 3] {
+    let (rodio, symphonia) = if cfg!(feature = "static-release") {
+        (AudioBackend::Symphonia, AudioBackend::Rodio)
+    } else {
+        (AudioBackend::Rodio, AudioBackend::Symphonia)
+    };
+    match os {
+        "macos" | "windows" => [rodio, symphonia, AudioBackend::Mpv],
+        _ => [AudioBackend::Mpv, rodio, symphonia],
+    }
+}
+
+/// Returns the default audio backend on this platform, among the ones compiled in.
+pub fn default_audio_backend() -> Option<AudioBackend> {
+    audio_backend_preference(env::consts::OS)
+        .into_iter()
+        .find(AudioBackend::is_available)
+}
+
+/// Returns the name of the native audio API used by rodio on the given OS.
+///
+/// # Arguments
+///
+/// * `os` - The name of the OS, as in `std::env::consts::OS`.
+fn native_audio_api(os: &str) -> &'static str {
+    match os {
+        "macos" | "ios" => "CoreAudio",
+        "windows" => "WASAPI",
+        "linux" | "android" => "ALSA",
+        "freebsd" | "openbsd" | "netbsd" | "dragonfly" => "OSS",
+        _ => "unknown",
+    }
+}
+
+/// Returns the sound server running in the user's session, if any (Linux only).
+fn sound_server() -> Option<&'static str> {
+    let runtime_dir = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    if runtime_dir.join("pipewire-0").exists() {
+        Some("PipeWire")
+    } else if runtime_dir.join("pulse").join("native").exists() {
+        Some("PulseAudio")
+    } else {
+        None
+    }
+}
+
+/// Checks whether the `mpv` player is installed.
+fn mpv_installed() -> bool {
+    Command::new("mpv")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Returns the peak memory (resident set size) used by the process so far, in bytes, or `None` if
+/// it cannot be read on this platform.
+pub fn peak_memory() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getrusage only writes to the struct it is given
+        let usage = unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+                return None;
+            }
+            usage
+        };
+        let max_rss = u64::try_from(usage.ru_maxrss).ok()?;
+        // macOS reports bytes, the other systems kilobytes
+        if cfg!(target_os = "macos") {
+            Some(max_rss)
+        } else {
+            Some(max_rss * 1024)
+        }
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::{
+            ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+            Threading::GetCurrentProcess,
+        };
+        // SAFETY: GetProcessMemoryInfo only writes to the struct it is given, up to its size
+        unsafe {
+            let mut counters: PROCESS_MEMORY_COUNTERS = std::mem::zeroed();
+            let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+            if GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) == 0 {
+                return None;
+            }
+            Some(counters.PeakWorkingSetSize as u64)
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        None
+    }
+}
+
+/// What was detected about the platform, and the defaults chosen for it.
+#[derive(Debug, Clone)]
+pub struct SystemInfo {
+    /// The name of the OS.
+    pub os: &'static str,
+    /// The CPU architecture.
+    pub arch: &'static str,
+    /// The terminal type (`TERM`), if set.
+    pub terminal: Option<String>,
+    /// Whether the terminal advertises 24-bit colors (`COLORTERM`).
+    pub truecolor: bool,
+    /// The native audio API used by rodio.
+    pub audio_api: &'static str,
+    /// The sound server running in the session, if any.
+    pub sound_server: Option<&'static str>,
+    /// Whether the `mpv` player is installed.
+    pub mpv_installed: bool,
+    /// The audio backends compiled in this build.
+    pub audio_backends: Vec<AudioBackend>,
+    /// The audio backend used when `--audio-backend` is not given.
+    pub default_audio_backend: Option<AudioBackend>,
+    /// The video decoder compiled in this build.
+    pub video_decoder: &'static str,
+    /// The external tools and whether they are installed.
+    pub tools: Vec<(ExternalTool, bool)>,
+}
+
+impl SystemInfo {
+    /// Detects the platform.
+    pub fn detect() -> Self {
+        let os = env::consts::OS;
+        Self {
+            os,
+            arch: env::consts::ARCH,
+            terminal: env::var("TERM").ok(),
+            truecolor: env::var("COLORTERM")
+                .is_ok_and(|value| value == "truecolor" || value == "24bit"),
+            audio_api: native_audio_api(os),
+            sound_server: sound_server(),
+            mpv_installed: mpv_installed(),
+            audio_backends: AudioBackend::available(),
+            default_audio_backend: default_audio_backend(),
+            video_decoder: video::decoder_name(),
+            tools: [
+                ExternalTool::Ffmpeg,
+                ExternalTool::Ffprobe,
+                ExternalTool::YtDlp,
+            ]
+            .into_iter()
+            .map(|tool| (tool, tool.is_available()))
+            .collect(),
+        }
+    }
+}
+
+impl fmt::Display for SystemInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |found: bool| if found { "found" } else { "not found" };
+        let names = |backends: &[AudioBackend]| {
+            if backends.is_empty() {
+                "none".to_string()
+            } else {
+                let names: Vec<&str> = backends.iter().map(AudioBackend::name).collect();
+                names.join(", ")
+            }
+        };
+
+        writeln!(f, "System")?;
+        writeln!(f, "  OS:                    {} ({})", self.os, self.arch)?;
+        writeln!(
+            f,
+            "  Terminal:              {}{}",
+            self.terminal.as_deref().unwrap_or("unknown"),
+            if self.truecolor { " (truecolor)" } else { "" }
+        )?;
+        match self.sound_server {
+            Some(server) => writeln!(f, "  Audio output:          {} ({server})", self.audio_api)?,
+            None => writeln!(f, "  Audio output:          {}", self.audio_api)?,
+        }
+        writeln!(f, "  mpv:                   {}", yes_no(self.mpv_installed))?;
+        writeln!(
+            f,
+            "  Audio backends:        {}",
+            names(&self.audio_backends)
+        )?;
+        writeln!(
+            f,
+            "  Default audio backend: {}",
+            self.default_audio_backend
+                .map_or("none (no audio)", |backend| backend.name())
+        )?;
+        writeln!(f, "  Video decoder:         {}", self.video_decoder)?;
+        for (tool, found) in &self.tools {
+            writeln!(
+                f,
+                "  {:<22} {}",
+                format!("{}:", tool.command()),
+                yes_no(*found)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_backend_preference() {
+        for os in ["macos", "windows"] {
+            assert_ne!(audio_backend_preference(os)[0], AudioBackend::Mpv);
+        }
+        assert_eq!(audio_backend_preference("linux")[0], AudioBackend::Mpv);
+
+        // Every backend is listed once
+        for os in ["macos", "windows", "linux", "freebsd"] {
+            let mut names: Vec<&str> = audio_backend_preference(os)
+                .iter()
+                .map(AudioBackend::name)
+                .collect();
+            names.sort_unstable();
+            assert_eq!(names, ["mpv", "rodio", "symphonia"]);
+        }
+
+        assert!(default_audio_backend()
+            .iter()
+            .all(AudioBackend::is_available));
+    }
+
+    #[test]
+    #[cfg(any(unix, windows))]
+    fn test_peak_memory() {
+        assert!(peak_memory().is_some_and(|bytes| bytes > 0));
+    }
+}
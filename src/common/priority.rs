@@ -0,0 +1,192 @@
+//! This module changes the scheduling priority of the playback threads, so that frames keep
+//! coming on time when the machine is busy with other work (e.g. long encodes or renders).
+//!
+//! The decoding thread can be made nicer to the rest of the system, and the thread drawing the
+//! frames can be given a real-time priority. Both only affect the calling thread, using the
+//! native API of each platform.
+use crate::common::errors::*;
+
+/// Highest niceness accepted by `lower_current_thread_priority`.
+pub const MAX_NICENESS: i32 = 19;
+
+/// Lowers the priority of the calling thread.
+///
+/// # Arguments
+///
+/// * `niceness` - How much to lower the priority, from 1 to `MAX_NICENESS`. On Linux this is added
+///   to the nice value of the thread (up to `MAX_NICENESS`); on Windows, values from 10 use the
+///   lowest thread priority and smaller ones the below normal priority. On macOS the thread is
+///   moved to the background band, whatever the value.
+///
+/// # Errors
+///
+/// Returns an error if the platform refuses the change or does not support it.
+pub fn lower_current_thread_priority(niceness: i32) -> Result<(), MyError> {
+    let niceness = niceness.clamp(1, MAX_NICENESS);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        // On Linux the nice value is a per-thread attribute, addressed by the thread id
+        // SAFETY: plain system call without pointers.
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+        // -1 is a valid nice value, getpriority only reports failures through errno
+        // SAFETY: the errno location of the calling thread is always valid, the other calls are
+        // plain system calls without pointers.
+        let current = unsafe {
+            #[cfg(target_os = "linux")]
+            let errno = libc::__errno_location();
+            #[cfg(target_os = "android")]
+            let errno = libc::__errno();
+            *errno = 0;
+            let current = libc::getpriority(libc::PRIO_PROCESS, tid);
+            if current == -1 && *errno != 0 {
+                return Err(last_os_error());
+            }
+            current
+        };
+        // SAFETY: plain system call without pointers.
+        let result = unsafe {
+            libc::setpriority(
+                libc::PRIO_PROCESS,
+                tid,
+                (current + niceness).min(MAX_NICENESS),
+            )
+        };
+        if result != 0 {
+            return Err(last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(target_vendor = "apple")]
+    {
+        let _ = niceness;
+        // SAFETY: plain system call without pointers, 0 designates the calling thread.
+        let result =
+            unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) };
+        if result != 0 {
+            return Err(last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL,
+            THREAD_PRIORITY_LOWEST,
+        };
+        let priority = if niceness >= 10 {
+            THREAD_PRIORITY_LOWEST
+        } else {
+            THREAD_PRIORITY_BELOW_NORMAL
+        };
+        // SAFETY: GetCurrentThread returns a pseudo handle that is always valid.
+        if unsafe { SetThreadPriority(GetCurrentThread(), priority) } == 0 {
+            return Err(last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_vendor = "apple",
+        windows
+    )))]
+    {
+        let _ = niceness;
+        Err(MyError::Application(format!(
+            "{ERROR_THREAD_PRIORITY}: not supported on this platform"
+        )))
+    }
+}
+
+/// Gives the calling thread a real-time priority, so that it preempts the normal threads of all
+/// processes. The lowest real-time priority is used, to avoid starving system services.
+///
+/// # Errors
+///
+/// Returns an error if the platform refuses the change, which is usually the case on Unix unless
+/// the user is allowed to use real-time scheduling (e.g. `rtprio` in `limits.conf`).
+pub fn raise_current_thread_to_realtime() -> Result<(), MyError> {
+    #[cfg(unix)]
+    {
+        // SAFETY: `param` is a valid, initialized `sched_param` for the duration of the call.
+        let result = unsafe {
+            let mut param: libc::sched_param = std::mem::zeroed();
+            param.sched_priority = libc::sched_get_priority_min(libc::SCHED_RR);
+            libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_RR, &param)
+        };
+        if result != 0 {
+            return Err(MyError::Application(format!(
+                "{ERROR_THREAD_PRIORITY}: {}",
+                std::io::Error::from_raw_os_error(result)
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_TIME_CRITICAL,
+        };
+        // SAFETY: GetCurrentThread returns a pseudo handle that is always valid.
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_TIME_CRITICAL) } == 0 {
+            return Err(last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(MyError::Application(format!(
+            "{ERROR_THREAD_PRIORITY}: not supported on this platform"
+        )))
+    }
+}
+
+/// Wraps the last error reported by the operating system.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    windows
+))]
+fn last_os_error() -> MyError {
+    MyError::Application(format!(
+        "{ERROR_THREAD_PRIORITY}: {}",
+        std::io::Error::last_os_error()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_lower_current_thread_priority() {
+        // Lowering the priority never requires privileges, and only affects the calling thread
+        std::thread::spawn(|| {
+            lower_current_thread_priority(5).unwrap();
+            // SAFETY: plain system calls without pointers.
+            let nice = unsafe {
+                let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+                libc::getpriority(libc::PRIO_PROCESS, tid)
+            };
+            assert!(nice >= 5);
+            // Asking again lowers the priority further
+            lower_current_thread_priority(5).unwrap();
+            // SAFETY: plain system calls without pointers.
+            let nicer = unsafe {
+                let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+                libc::getpriority(libc::PRIO_PROCESS, tid)
+            };
+            assert_eq!(nicer, (nice + 5).min(MAX_NICENESS));
+        })
+        .join()
+        .unwrap();
+    }
+}
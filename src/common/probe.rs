@@ -0,0 +1,539 @@
+//! This module probes video files with `ffprobe` to read their frame rate, duration and whether
+//! they contain an audio track.
+//!
+//! A single `ffprobe` run reads all properties. The results are cached in `probe_cache.json`
+//! inside the state directory, keyed by the file path and checked against the modification time
+//! and size of the file, so playing the same file again does not spawn `ffprobe` at all.
+//!
+//! Static builds (feature `static-release`) read the same properties in-process with the libav
+//! libraries linked in the binary, so they do not need `ffprobe` to be installed.
+//!
+//! The tags of audio tracks (title, artist, album) are read on demand by `probe_tags`, and the
+//! subtitle streams of videos by `probe_subtitle_streams`. Neither is cached.
+use crate::common::{errors::*, utils::state_dir};
+use num::{Rational64, ToPrimitive};
+use serde_json::{json, Map, Value};
+#[cfg(not(feature = "static-release"))]
+use std::process::{Command, Stdio};
+use std::{fs, path::Path, str::FromStr, time::UNIX_EPOCH};
+
+/// Name of the cache file inside the state directory.
+const CACHE_FILE_NAME: &str = "probe_cache.json";
+/// Maximum number of files kept in the cache, the least recently probed are dropped first.
+const MAX_CACHE_ENTRIES: usize = 512;
+
+/// The properties of a video file read by `ffprobe`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProbeInfo {
+    /// The frame rate of the first video stream, if available.
+    pub fps: Option<f64>,
+    /// Whether the file contains an audio stream.
+    pub has_audio: bool,
+    /// The duration of the media in seconds, if available.
+    pub duration: Option<f64>,
+}
+
+/// The tags of an audio track, shown while it plays.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MediaTags {
+    /// The title of the track.
+    pub title: Option<String>,
+    /// The artist of the track.
+    pub artist: Option<String>,
+    /// The album the track belongs to.
+    pub album: Option<String>,
+}
+
+/// A subtitle stream embedded in a media file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SubtitleStream {
+    /// The codec of the stream, e.g. `subrip` or `mov_text` for text subtitles, or
+    /// `hdmv_pgs_subtitle` for bitmap ones.
+    pub codec: String,
+    /// The language of the stream, e.g. `eng`.
+    pub language: Option<String>,
+    /// The title of the stream, e.g. `Commentary`.
+    pub title: Option<String>,
+}
+
+/// Identifies a version of a file: its modification time (in nanoseconds since the epoch) and
+/// its size. A cached probe is only used if the file still has the same stamp.
+type FileStamp = (u128, u64);
+
+/// Probes a video file, using the cached result if the file did not change since it was last
+/// probed.
+///
+/// # Arguments
+///
+/// * `path` - The path of the video file.
+///
+/// # Returns
+///
+/// A `Result` containing the properties of the file, or a `MyError` if `ffprobe` cannot be run.
+pub fn probe_media(path: &str) -> Result<ProbeInfo, MyError> {
+    let cache_file = state_dir().map(|dir| dir.join(CACHE_FILE_NAME));
+    let stamp = file_stamp(Path::new(path));
+    let key = fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string());
+
+    if let (Some(cache_file), Some(stamp)) = (&cache_file, stamp) {
+        if let Some(info) = lookup_cache(cache_file, &key, stamp) {
+            return Ok(info);
+        }
+    }
+
+    #[cfg(not(feature = "static-release"))]
+    let info = run_ffprobe(path)?;
+    #[cfg(feature = "static-release")]
+    let info = run_libav_probe(path)?;
+    if let (Some(cache_file), Some(stamp)) = (&cache_file, stamp) {
+        // The cache is only an optimization, failing to update it is not an error
+        let _ = store_cache(cache_file, &key, stamp, &info);
+    }
+    Ok(info)
+}
+
+/// Reads the title, artist and album tags of a media file.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+///
+/// # Returns
+///
+/// A `Result` containing the tags found, or a `MyError` if `ffprobe` cannot be run.
+pub fn probe_tags(path: &str) -> Result<MediaTags, MyError> {
+    #[cfg(not(feature = "static-release"))]
+    {
+        let output = Command::new("ffprobe")
+            .arg("-v")
+            .arg("error")
+            .arg("-show_entries")
+            .arg("format_tags")
+            .arg("-of")
+            .arg("json")
+            .arg(path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| MyError::Application(format!("{ERROR_PROBING_MEDIA}: {err:?}")))?;
+        Ok(parse_tags_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+    #[cfg(feature = "static-release")]
+    {
+        let error = |err: ffmpeg_next::Error| {
+            MyError::Application(format!("{ERROR_PROBING_MEDIA}: {err:?}"))
+        };
+        ffmpeg_next::init().map_err(error)?;
+        let input = ffmpeg_next::format::input(path).map_err(error)?;
+        let tags: Map<String, Value> = input
+            .metadata()
+            .iter()
+            .map(|(key, value)| (key.to_string(), Value::from(value)))
+            .collect();
+        Ok(tags_from_json(&tags))
+    }
+}
+
+/// Lists the subtitle streams embedded in a media file, in the order of the file, which is the
+/// order `ffmpeg` numbers them in (`-map 0:s:N`).
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+///
+/// # Returns
+///
+/// A `Result` containing the subtitle streams, or a `MyError` if `ffprobe` cannot be run.
+pub fn probe_subtitle_streams(path: &str) -> Result<Vec<SubtitleStream>, MyError> {
+    #[cfg(not(feature = "static-release"))]
+    {
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "s"])
+            .args([
+                "-show_entries",
+                "stream=codec_name:stream_tags=language,title",
+            ])
+            .args(["-of", "json"])
+            .arg(path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| MyError::Application(format!("{ERROR_PROBING_MEDIA}: {err:?}")))?;
+        Ok(parse_subtitle_streams(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+    #[cfg(feature = "static-release")]
+    {
+        use ffmpeg_next::media;
+
+        let error = |err: ffmpeg_next::Error| {
+            MyError::Application(format!("{ERROR_PROBING_MEDIA}: {err:?}"))
+        };
+        ffmpeg_next::init().map_err(error)?;
+        let input = ffmpeg_next::format::input(path).map_err(error)?;
+        let streams = input
+            .streams()
+            .filter(|stream| stream.parameters().medium() == media::Type::Subtitle)
+            .map(|stream| {
+                let metadata = stream.metadata();
+                SubtitleStream {
+                    codec: stream.parameters().id().name().to_string(),
+                    language: metadata.get("language").map(String::from),
+                    title: metadata.get("title").map(String::from),
+                }
+            })
+            .collect();
+        Ok(streams)
+    }
+}
+
+/// Runs `ffprobe` on a file and parses its output.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to probe.
+///
+/// # Errors
+///
+/// Returns an error if `ffprobe` cannot be run or fails, so that nothing is cached for the file.
+#[cfg(not(feature = "static-release"))]
+fn run_ffprobe(path: &str) -> Result<ProbeInfo, MyError> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("stream=codec_type,r_frame_rate:format=duration")
+        .arg("-of")
+        .arg("json")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| MyError::Application(format!("{ERROR_EXTRACTING_FPS}: {err:?}")))?;
+    if !output.status.success() {
+        return Err(MyError::Application(format!(
+            "{ERROR_PROBING_MEDIA}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(parse_probe_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Probes a file with the libav libraries linked in the binary.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to probe.
+#[cfg(feature = "static-release")]
+fn run_libav_probe(path: &str) -> Result<ProbeInfo, MyError> {
+    use ffmpeg_next::{format, media, rescale::TIME_BASE};
+
+    let error =
+        |err: ffmpeg_next::Error| MyError::Application(format!("{ERROR_PROBING_MEDIA}: {err:?}"));
+    ffmpeg_next::init().map_err(error)?;
+    let input = format::input(path).map_err(error)?;
+
+    let fps = input
+        .streams()
+        .best(media::Type::Video)
+        .map(|stream| f64::from(stream.rate()))
+        .filter(|fps| fps.is_finite() && *fps > 0.0);
+    // The duration is in AV_TIME_BASE units, and negative when unknown
+    let duration = (input.duration() > 0).then(|| input.duration() as f64 * f64::from(TIME_BASE));
+
+    Ok(ProbeInfo {
+        fps,
+        has_audio: input.streams().best(media::Type::Audio).is_some(),
+        duration,
+    })
+}
+
+/// Parses the JSON output of `ffprobe`. Missing or malformed properties are left unset.
+///
+/// # Arguments
+///
+/// * `output` - The JSON printed by `ffprobe`.
+#[cfg_attr(feature = "static-release", allow(dead_code))]
+fn parse_probe_output(output: &str) -> ProbeInfo {
+    let json_value: Value = serde_json::from_str(output).unwrap_or(Value::Null);
+    let streams = json_value["streams"]
+        .as_array()
+        .map_or(&[][..], Vec::as_slice);
+    let of_type = |codec_type: &str| {
+        streams
+            .iter()
+            .find(|stream| stream["codec_type"].as_str() == Some(codec_type))
+    };
+
+    let fps = of_type("video")
+        .and_then(|stream| stream["r_frame_rate"].as_str())
+        .and_then(|rate| Rational64::from_str(rate).ok())
+        .and_then(|rate| rate.to_f64())
+        .filter(|fps| fps.is_finite() && *fps > 0.0);
+    let duration = json_value["format"]["duration"]
+        .as_str()
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| duration.is_finite() && *duration >= 0.0);
+
+    ProbeInfo {
+        fps,
+        has_audio: of_type("audio").is_some(),
+        duration,
+    }
+}
+
+/// Parses the tags printed by `ffprobe` as JSON. Missing tags are left unset.
+///
+/// # Arguments
+///
+/// * `output` - The JSON printed by `ffprobe`.
+#[cfg_attr(feature = "static-release", allow(dead_code))]
+fn parse_tags_output(output: &str) -> MediaTags {
+    let json_value: Value = serde_json::from_str(output).unwrap_or(Value::Null);
+    json_value["format"]["tags"]
+        .as_object()
+        .map(tags_from_json)
+        .unwrap_or_default()
+}
+
+/// Parses the subtitle streams printed by `ffprobe` as JSON.
+///
+/// # Arguments
+///
+/// * `output` - The JSON printed by `ffprobe`.
+#[cfg_attr(feature = "static-release", allow(dead_code))]
+fn parse_subtitle_streams(output: &str) -> Vec<SubtitleStream> {
+    let json_value: Value = serde_json::from_str(output).unwrap_or(Value::Null);
+    let tag = |stream: &Value, name: &str| {
+        stream["tags"][name]
+            .as_str()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
+    json_value["streams"]
+        .as_array()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .map(|stream| SubtitleStream {
+            codec: stream["codec_name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            language: tag(stream, "language"),
+            title: tag(stream, "title"),
+        })
+        .collect()
+}
+
+/// Picks the title, artist and album among the tags of a file. The case of the tag names
+/// depends on the container (e.g. `TITLE` in FLAC files), so it is ignored.
+///
+/// # Arguments
+///
+/// * `tags` - The tags, by name.
+fn tags_from_json(tags: &Map<String, Value>) -> MediaTags {
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value.as_str())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
+    MediaTags {
+        title: tag("title"),
+        artist: tag("artist"),
+        album: tag("album"),
+    }
+}
+
+/// Returns the stamp of a regular file, or `None` for anything that cannot be cached (devices,
+/// pipes, missing files).
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((mtime.as_nanos(), metadata.len()))
+}
+
+/// Reads the cache file, or returns an empty cache if it does not exist or is corrupted.
+///
+/// # Arguments
+///
+/// * `cache_file` - The path of the cache file.
+fn read_cache(cache_file: &Path) -> Map<String, Value> {
+    fs::read_to_string(cache_file)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .and_then(|value| value.as_object().cloned())
+        .unwrap_or_default()
+}
+
+/// Looks up the cached properties of a file.
+///
+/// # Arguments
+///
+/// * `cache_file` - The path of the cache file.
+/// * `key` - The path of the probed file.
+/// * `stamp` - The current stamp of the probed file.
+///
+/// # Returns
+///
+/// The cached properties, or `None` if the file is not cached or changed since it was probed.
+fn lookup_cache(cache_file: &Path, key: &str, stamp: FileStamp) -> Option<ProbeInfo> {
+    let cache = read_cache(cache_file);
+    let entry = cache.get(key)?;
+    let cached_mtime = entry["mtime"].as_str()?.parse::<u128>().ok()?;
+    if (cached_mtime, entry["size"].as_u64()?) != stamp {
+        return None;
+    }
+    Some(ProbeInfo {
+        fps: entry["fps"].as_f64(),
+        has_audio: entry["has_audio"].as_bool()?,
+        duration: entry["duration"].as_f64(),
+    })
+}
+
+/// Stores the properties of a file in the cache, dropping the oldest entries if the cache is
+/// full. The cache file is replaced atomically so that concurrent runs never read a partial file.
+///
+/// # Arguments
+///
+/// * `cache_file` - The path of the cache file.
+/// * `key` - The path of the probed file.
+/// * `stamp` - The stamp of the probed file.
+/// * `info` - The properties to store.
+fn store_cache(
+    cache_file: &Path,
+    key: &str,
+    stamp: FileStamp,
+    info: &ProbeInfo,
+) -> std::io::Result<()> {
+    let mut cache = read_cache(cache_file);
+    let probed = cache
+        .values()
+        .filter_map(|entry| entry["probed"].as_u64())
+        .max()
+        .map_or(0, |last| last + 1);
+    cache.insert(
+        key.to_string(),
+        json!({
+            // Nanoseconds do not fit in a JSON number without losing precision
+            "mtime": stamp.0.to_string(),
+            "size": stamp.1,
+            "fps": info.fps,
+            "has_audio": info.has_audio,
+            "duration": info.duration,
+            "probed": probed,
+        }),
+    );
+
+    if cache.len() > MAX_CACHE_ENTRIES {
+        let mut by_age: Vec<(u64, String)> = cache
+            .iter()
+            .map(|(key, entry)| (entry["probed"].as_u64().unwrap_or(0), key.clone()))
+            .collect();
+        by_age.sort_unstable();
+        for (_, key) in by_age.into_iter().take(cache.len() - MAX_CACHE_ENTRIES) {
+            cache.remove(&key);
+        }
+    }
+
+    if let Some(dir) = cache_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial_file = cache_file.with_extension(format!("json.{}", std::process::id()));
+    fs::write(&partial_file, Value::Object(cache).to_string())?;
+    fs::rename(&partial_file, cache_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let output = r#"{
+            "streams": [
+                { "codec_type": "video", "r_frame_rate": "30000/1001" },
+                { "codec_type": "audio", "r_frame_rate": "0/0" }
+            ],
+            "format": { "duration": "12.500000" }
+        }"#;
+        let info = parse_probe_output(output);
+        assert!((info.fps.unwrap() - 29.97).abs() < 0.01);
+        assert!(info.has_audio);
+        assert_eq!(info.duration, Some(12.5));
+
+        let info = parse_probe_output(r#"{"streams": [{"codec_type": "video"}]}"#);
+        assert_eq!(info, ProbeInfo::default());
+        assert_eq!(parse_probe_output("not json"), ProbeInfo::default());
+    }
+
+    #[test]
+    fn test_parse_tags_output() {
+        let output = r#"{
+            "format": { "tags": { "TITLE": "Song", "artist": "Band", "album": " " } }
+        }"#;
+        assert_eq!(
+            parse_tags_output(output),
+            MediaTags {
+                title: Some("Song".into()),
+                artist: Some("Band".into()),
+                album: None,
+            }
+        );
+        assert_eq!(parse_tags_output(r#"{"format": {}}"#), MediaTags::default());
+    }
+
+    #[test]
+    fn test_parse_subtitle_streams() {
+        let output = r#"{
+            "streams": [
+                { "codec_name": "subrip", "tags": { "language": "eng" } },
+                { "codec_name": "mov_text", "tags": { "language": "fre", "title": "Forced" } }
+            ]
+        }"#;
+        let streams = parse_subtitle_streams(output);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].codec, "subrip");
+        assert_eq!(streams[0].title, None);
+        assert_eq!(streams[1].language.as_deref(), Some("fre"));
+        assert_eq!(streams[1].title.as_deref(), Some("Forced"));
+        assert!(parse_subtitle_streams("{}").is_empty());
+    }
+
+    #[test]
+    fn test_probe_cache() {
+        let dir = std::env::temp_dir().join(format!("tplay-probe-test-{}", std::process::id()));
+        let cache_file = dir.join(CACHE_FILE_NAME);
+        let info = ProbeInfo {
+            fps: Some(25.0),
+            has_audio: true,
+            duration: None,
+        };
+
+        assert_eq!(lookup_cache(&cache_file, "a.mp4", (1, 2)), None);
+        store_cache(&cache_file, "a.mp4", (1, 2), &info).unwrap();
+        assert_eq!(lookup_cache(&cache_file, "a.mp4", (1, 2)), Some(info));
+        // A modified file is probed again
+        assert_eq!(lookup_cache(&cache_file, "a.mp4", (3, 2)), None);
+        assert_eq!(lookup_cache(&cache_file, "b.mp4", (1, 2)), None);
+
+        for i in 0..MAX_CACHE_ENTRIES {
+            store_cache(&cache_file, &format!("{i}.mp4"), (1, 2), &info).unwrap();
+        }
+        assert_eq!(read_cache(&cache_file).len(), MAX_CACHE_ENTRIES);
+        assert_eq!(lookup_cache(&cache_file, "a.mp4", (1, 2)), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,621 @@
+//! This module records per-frame timing statistics, written as CSV with `--stats-out`, and the
+//! totals of the session, summarized when the playback ends.
+//!
+//! The pipeline thread records how long each frame took to decode and convert, and what happened
+//! to it; the display thread records how long the frames it received took to draw. Both share a
+//! `StatsLog`, which matches the draws with the frames sent to the display (frames are drawn in
+//! the order they are sent) and writes one row per frame, in order.
+//!
+//! The CSV columns are:
+//! * `frame` - The sequence number of the frame (frames shown again while paused count too).
+//! * `time_ms` - When the frame was processed, in milliseconds since the log was created.
+//! * `decode_us` - The time spent reading the frame from the media, in microseconds.
+//! * `convert_us` - The time spent converting the frame to characters, in microseconds (empty if
+//!   the frame was not converted).
+//! * `draw_us` - The time spent drawing the frame, in microseconds (empty if it was not drawn).
+//! * `skipped` - The number of source frames skipped before this one to catch up with the frame
+//!   rate.
+//! * `outcome` - What happened to the frame: `drawn`, `display_busy` (the display was not ready,
+//!   the frame was dropped), `send_failed` (the frame could not be sent to the display),
+//!   `discarded` (the display dropped the frame, e.g. after a resize) or `not_drawn` (playback
+//!   ended before the frame was drawn).
+//! * `audio_underruns` - The number of times the audio output ran out of samples so far, see
+//!   `audio/underruns.rs` (empty if the audio backend does not detect them).
+//!
+//! For the performance overlay (`F` during playback), the pipeline also publishes a
+//! `PipelineStats` snapshot twice a second through a stats channel, see `StatsPublisher`.
+use crate::common::{errors::*, platform};
+use crossbeam_channel::Sender;
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// Header line of the CSV file.
+const CSV_HEADER: &str =
+    "frame,time_ms,decode_us,convert_us,draw_us,skipped,outcome,audio_underruns";
+/// Over how long the rates are measured.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+/// How often the pipeline publishes its statistics.
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What happened to a frame read by the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOutcome {
+    /// The frame was sent to the display, and is waiting to be drawn.
+    Sent,
+    /// The display was not ready for a new frame, so the frame was dropped.
+    DisplayBusy,
+    /// The frame could not be sent to the display.
+    SendFailed,
+}
+
+/// The timings of a frame, measured by the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTimings {
+    /// The time spent reading the frame from the media.
+    pub decode: Duration,
+    /// The time spent converting the frame to characters, if it was converted.
+    pub convert: Option<Duration>,
+    /// The number of source frames skipped before this one to catch up with the frame rate.
+    pub skipped: usize,
+    /// What happened to the frame.
+    pub outcome: FrameOutcome,
+}
+
+/// A frame not written to the CSV file yet.
+struct Row {
+    frame: u64,
+    time: Duration,
+    timings: FrameTimings,
+    draw: Option<Duration>,
+    /// The outcome written to the file, once known.
+    outcome: Option<&'static str>,
+    /// The audio underruns detected when the frame was read, if they are.
+    audio_underruns: Option<u64>,
+}
+
+/// The state shared by the threads recording statistics.
+struct StatsState {
+    /// Where the CSV is written, if enabled.
+    writer: Option<Box<dyn Write + Send>>,
+    start: Instant,
+    next_frame: u64,
+    pending: VecDeque<Row>,
+    /// The number of frames drawn.
+    frames_drawn: u64,
+    /// The number of frames not drawn: dropped by the pipeline or the display, or skipped to
+    /// catch up with the frame rate.
+    frames_dropped: u64,
+    /// The audio underruns of the audio played, and their number when the log started counting
+    /// them, if they are detected.
+    audio_underruns: Option<(AudioUnderruns, u64)>,
+}
+
+/// The number of underruns detected by an audio backend: the times the audio output ran out of
+/// samples, heard as crackles or gaps. Shared between the backend and the statistics.
+#[derive(Debug, Clone, Default)]
+pub struct AudioUnderruns {
+    count: Arc<AtomicU64>,
+}
+
+impl AudioUnderruns {
+    /// Records an underrun.
+    pub fn record(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of underruns so far.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+/// The totals of a playback session, printed when the playback ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionSummary {
+    /// How long the playback lasted.
+    pub duration: Duration,
+    /// The number of frames drawn.
+    pub frames_drawn: u64,
+    /// The number of frames not drawn.
+    pub frames_dropped: u64,
+    /// The peak memory used by the process, in bytes, if known.
+    pub peak_memory: Option<u64>,
+    /// The number of times the audio output ran out of samples, if detected.
+    pub audio_underruns: Option<u64>,
+}
+
+impl SessionSummary {
+    /// Returns the average number of frames drawn per second.
+    pub fn average_fps(&self) -> f64 {
+        let seconds = self.duration.as_secs_f64();
+        if seconds > 0.0 {
+            self.frames_drawn as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.duration.as_secs();
+        writeln!(f, "Session summary")?;
+        writeln!(
+            f,
+            "  Duration:       {}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        writeln!(f, "  Average fps:    {:.1}", self.average_fps())?;
+        writeln!(f, "  Frames drawn:   {}", self.frames_drawn)?;
+        writeln!(f, "  Frames dropped: {}", self.frames_dropped)?;
+        match self.peak_memory {
+            Some(bytes) => writeln!(
+                f,
+                "  Peak memory:    {:.1} MiB",
+                bytes as f64 / (1024.0 * 1024.0)
+            ),
+            None => writeln!(f, "  Peak memory:    unknown"),
+        }?;
+        match self.audio_underruns {
+            Some(underruns) => writeln!(f, "  Underruns:      {underruns}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A log of frame statistics, shared between the pipeline and display threads. The rows are
+/// written as soon as the fate of each frame is known, and the log is flushed when the last
+/// clone is dropped.
+#[derive(Clone)]
+pub struct StatsLog {
+    state: Arc<Mutex<StatsState>>,
+}
+
+impl StatsLog {
+    /// Creates a CSV file and writes the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the CSV file, replaced if it exists.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the log, or a `MyError` if the file cannot be created.
+    pub fn create(path: &Path) -> Result<Self, MyError> {
+        let file = File::create(path).map_err(|err| {
+            MyError::Application(format!("{ERROR_STATS_FILE}: {}: {err:?}", path.display()))
+        })?;
+        Self::from_writer(Box::new(BufWriter::new(file)))
+    }
+
+    /// Creates a log that only keeps the totals of the session, without writing a CSV file.
+    pub fn new() -> Self {
+        Self::with_writer(None)
+    }
+
+    /// Creates a log writing to any writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the CSV is written.
+    fn from_writer(mut writer: Box<dyn Write + Send>) -> Result<Self, MyError> {
+        writeln!(writer, "{CSV_HEADER}")
+            .map_err(|err| MyError::Application(format!("{ERROR_STATS_FILE}: {err:?}")))?;
+        Ok(Self::with_writer(Some(writer)))
+    }
+
+    /// Creates a log writing the rows to the given writer, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the CSV is written, after its header.
+    fn with_writer(writer: Option<Box<dyn Write + Send>>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(StatsState {
+                writer,
+                start: Instant::now(),
+                next_frame: 0,
+                pending: VecDeque::new(),
+                frames_drawn: 0,
+                frames_dropped: 0,
+                audio_underruns: None,
+            })),
+        }
+    }
+
+    /// Counts the underruns of the audio played from now on, in the rows and the summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `underruns` - The underruns detected by the audio backend, possibly counting those of
+    ///   the media played before.
+    pub fn set_audio_underruns(&self, underruns: AudioUnderruns) {
+        let count = underruns.count();
+        self.lock().audio_underruns = Some((underruns, count));
+    }
+
+    /// Returns the number of audio underruns since they are counted, if they are.
+    pub fn audio_underruns(&self) -> Option<u64> {
+        self.lock().audio_underruns()
+    }
+
+    /// Returns the totals of the session so far.
+    pub fn summary(&self) -> SessionSummary {
+        let state = self.lock();
+        SessionSummary {
+            duration: state.start.elapsed(),
+            frames_drawn: state.frames_drawn,
+            frames_dropped: state.frames_dropped,
+            peak_memory: platform::peak_memory(),
+            audio_underruns: state.audio_underruns(),
+        }
+    }
+
+    /// Records a frame read by the pipeline. Must be called in the order the frames are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `timings` - The timings of the frame, and what happened to it.
+    pub fn record_frame(&self, timings: FrameTimings) {
+        let mut state = self.lock();
+        let row = Row {
+            frame: state.next_frame,
+            time: state.start.elapsed(),
+            timings,
+            draw: None,
+            outcome: match timings.outcome {
+                FrameOutcome::Sent => None,
+                FrameOutcome::DisplayBusy => Some("display_busy"),
+                FrameOutcome::SendFailed => Some("send_failed"),
+            },
+            audio_underruns: state.audio_underruns(),
+        };
+        state.next_frame += 1;
+        state.frames_dropped += timings.skipped as u64;
+        if row.outcome.is_some() {
+            state.frames_dropped += 1;
+        }
+        state.pending.push_back(row);
+        state.write_completed_rows();
+    }
+
+    /// Records that the display drew the oldest frame it received.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - The time spent drawing the frame.
+    pub fn record_draw(&self, draw: Duration) {
+        self.resolve_sent_frame("drawn", Some(draw));
+    }
+
+    /// Records that the display dropped the oldest frame it received without drawing it.
+    pub fn record_discard(&self) {
+        self.resolve_sent_frame("discarded", None);
+    }
+
+    /// Sets the outcome of the oldest frame sent to the display and not resolved yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - The outcome written to the file.
+    /// * `draw` - The time spent drawing the frame, if it was drawn.
+    fn resolve_sent_frame(&self, outcome: &'static str, draw: Option<Duration>) {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        if let Some(row) = state.pending.iter_mut().find(|row| row.outcome.is_none()) {
+            row.outcome = Some(outcome);
+            row.draw = draw;
+            if draw.is_some() {
+                state.frames_drawn += 1;
+            } else {
+                state.frames_dropped += 1;
+            }
+        }
+        state.write_completed_rows();
+    }
+
+    /// Locks the shared state, recovering it if a thread panicked while holding the lock.
+    fn lock(&self) -> MutexGuard<'_, StatsState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for StatsLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsState {
+    /// Returns the number of audio underruns since they are counted, if they are.
+    fn audio_underruns(&self) -> Option<u64> {
+        self.audio_underruns
+            .as_ref()
+            .map(|(underruns, start)| underruns.count() - start)
+    }
+
+    /// Writes the rows of the frames whose outcome is known, keeping the frames in order.
+    /// Statistics are best effort, write errors are ignored.
+    fn write_completed_rows(&mut self) {
+        while self
+            .pending
+            .front()
+            .is_some_and(|row| row.outcome.is_some())
+        {
+            if let Some(row) = self.pending.pop_front() {
+                let _ = self.write_row(&row);
+            }
+        }
+    }
+
+    /// Writes a row to the CSV file.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The frame to write.
+    fn write_row(&mut self, row: &Row) -> std::io::Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let micros = |duration: Option<Duration>| {
+            duration.map_or(String::new(), |d| d.as_micros().to_string())
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            row.frame,
+            row.time.as_millis(),
+            row.timings.decode.as_micros(),
+            micros(row.timings.convert),
+            micros(row.draw),
+            row.timings.skipped,
+            row.outcome.unwrap_or("not_drawn"),
+            row.audio_underruns
+                .map_or(String::new(), |count| count.to_string()),
+        )
+    }
+}
+
+impl Drop for StatsState {
+    fn drop(&mut self) {
+        for row in std::mem::take(&mut self.pending) {
+            let _ = self.write_row(&row);
+        }
+        if let Some(writer) = &mut self.writer {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Measures how many times per second something happens, over the last second.
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    /// When it happened during the last `RATE_WINDOW`.
+    events: VecDeque<Instant>,
+}
+
+impl RateMeter {
+    /// Records that it happened now.
+    pub fn record(&mut self) {
+        let now = Instant::now();
+        self.events.push_back(now);
+        while self
+            .events
+            .front()
+            .is_some_and(|event| now.duration_since(*event) > RATE_WINDOW)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    /// Returns how many times it happened per second, over the last second.
+    pub fn rate(&self) -> f64 {
+        let now = Instant::now();
+        let recent = self
+            .events
+            .iter()
+            .filter(|event| now.duration_since(**event) <= RATE_WINDOW)
+            .count();
+        recent as f64 / RATE_WINDOW.as_secs_f64()
+    }
+}
+
+/// A snapshot of the performance of the pipeline, shown by the performance overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineStats {
+    /// The frames read from the media per second.
+    pub decode_fps: f64,
+    /// The frames converted to characters per second.
+    pub render_fps: f64,
+    /// The frames dropped since the playback started, because the display was not ready.
+    pub frames_dropped: u64,
+    /// The source frames skipped since the playback started, to catch up with the frame rate.
+    pub frames_skipped: u64,
+    /// The frames sent to the display and not drawn yet.
+    pub backlog: usize,
+    /// The times the audio output ran out of samples since the playback started, if detected.
+    pub audio_underruns: Option<u64>,
+}
+
+/// Measures the performance of the pipeline, and publishes it through the stats channel.
+pub struct StatsPublisher {
+    /// The stats channel, read by the display.
+    tx_stats: Sender<PipelineStats>,
+    decoded: RateMeter,
+    rendered: RateMeter,
+    frames_dropped: u64,
+    frames_skipped: u64,
+    /// When the last snapshot was published.
+    published: Instant,
+}
+
+impl StatsPublisher {
+    /// Constructs a publisher.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_stats` - The stats channel. A bounded channel is enough: snapshots the display did
+    ///   not read yet are replaced by the next ones.
+    pub fn new(tx_stats: Sender<PipelineStats>) -> Self {
+        Self {
+            tx_stats,
+            decoded: RateMeter::default(),
+            rendered: RateMeter::default(),
+            frames_dropped: 0,
+            frames_skipped: 0,
+            published: Instant::now(),
+        }
+    }
+
+    /// Records a frame read by the pipeline, and publishes a snapshot if the last one is old
+    /// enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `timings` - The timings of the frame, and what happened to it.
+    /// * `backlog` - The frames sent to the display and not drawn yet.
+    /// * `audio_underruns` - The audio underruns since the playback started, if detected.
+    pub fn record_frame(
+        &mut self,
+        timings: &FrameTimings,
+        backlog: usize,
+        audio_underruns: Option<u64>,
+    ) {
+        self.decoded.record();
+        if timings.convert.is_some() {
+            self.rendered.record();
+        }
+        if timings.outcome != FrameOutcome::Sent {
+            self.frames_dropped += 1;
+        }
+        self.frames_skipped += timings.skipped as u64;
+        if self.published.elapsed() >= PUBLISH_INTERVAL {
+            self.published = Instant::now();
+            // Best effort: the display may still have the previous snapshot to read
+            let _ = self.tx_stats.try_send(PipelineStats {
+                decode_fps: self.decoded.rate(),
+                render_fps: self.rendered.rate(),
+                frames_dropped: self.frames_dropped,
+                frames_skipped: self.frames_skipped,
+                backlog,
+                audio_underruns,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer that can be read back after the log is dropped.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stats_log_rows_follow_frame_order() {
+        let buffer = SharedBuffer::default();
+        let log = StatsLog::from_writer(Box::new(buffer.clone())).unwrap();
+        let timings = |outcome| FrameTimings {
+            decode: Duration::from_micros(10),
+            convert: Some(Duration::from_micros(20)),
+            skipped: 0,
+            outcome,
+        };
+
+        log.record_frame(timings(FrameOutcome::Sent));
+        let underruns = AudioUnderruns::default();
+        underruns.record();
+        // Only the underruns from then on are counted
+        log.set_audio_underruns(underruns.clone());
+        underruns.record();
+        log.record_frame(FrameTimings {
+            convert: None,
+            skipped: 2,
+            ..timings(FrameOutcome::DisplayBusy)
+        });
+        log.record_frame(timings(FrameOutcome::Sent));
+        log.record_frame(timings(FrameOutcome::Sent));
+        log.record_draw(Duration::from_micros(30));
+        log.record_discard();
+        let summary = log.summary();
+        assert_eq!((summary.frames_drawn, summary.frames_dropped), (1, 4));
+        assert_eq!(summary.audio_underruns, Some(1));
+        drop(log);
+
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let rows: Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows[0].join(","), CSV_HEADER);
+        let outcomes: Vec<(&str, &str, &str, &str)> = rows[1..]
+            .iter()
+            .map(|row| (row[0], row[4], row[5], row[6]))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("0", "30", "0", "drawn"),
+                ("1", "", "2", "display_busy"),
+                ("2", "", "0", "discarded"),
+                ("3", "", "0", "not_drawn"),
+            ]
+        );
+        assert_eq!(rows[2][3], "");
+        assert_eq!((rows[1][7], rows[2][7]), ("", "1"));
+    }
+
+    #[test]
+    fn test_stats_publisher() {
+        let (tx_stats, rx_stats) = crossbeam_channel::bounded(1);
+        let mut publisher = StatsPublisher::new(tx_stats);
+        let rendered = FrameTimings {
+            decode: Duration::ZERO,
+            convert: Some(Duration::ZERO),
+            skipped: 3,
+            outcome: FrameOutcome::Sent,
+        };
+        publisher.record_frame(&rendered, 0, None);
+        // Nothing is published before the interval
+        assert!(rx_stats.try_recv().is_err());
+        publisher.published -= PUBLISH_INTERVAL;
+        let dropped = FrameTimings {
+            convert: None,
+            outcome: FrameOutcome::DisplayBusy,
+            ..rendered
+        };
+        publisher.record_frame(&dropped, 1, Some(2));
+        assert_eq!(
+            rx_stats.try_recv(),
+            Ok(PipelineStats {
+                decode_fps: 2.0,
+                render_fps: 1.0,
+                frames_dropped: 1,
+                frames_skipped: 6,
+                backlog: 1,
+                audio_underruns: Some(2),
+            })
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! This module manages the temporary files created while playing media (downloaded media,
+//! YouTube videos and extracted audio tracks).
+//!
+//! All temporary files of a run are stored in a single session directory named
+//! `tplay-<pid>`, created inside the system temporary directory or the directory given with
+//! `--tmp-dir`. Further sessions of the same process (e.g. several embedded players) are named
+//! `tplay-<pid>-<n>`. The session directory is removed when the `TempStore` is dropped, or by the
+//! Ctrl-C handler if the application is interrupted. Leftovers from runs that were killed can be
+//! removed with `tplay clean`.
+use crate::common::errors::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Prefix of the session directories created by `TempStore`.
+const SESSION_DIR_PREFIX: &str = "tplay-";
+/// Prefix of the temporary files created by previous versions of the application.
+const LEGACY_FILE_PREFIX: &str = "my_temp_file_";
+/// Number of session directories created by this process.
+static SESSION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The `TempStore` struct owns the session directory where temporary files are stored.
+pub struct TempStore {
+    /// The session directory.
+    dir: PathBuf,
+}
+
+impl TempStore {
+    /// Creates a session directory for the current process.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_dir` - The directory in which to create the session directory, or `None` to use
+    ///   the system temporary directory.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TempStore`, or a `MyError` if the directory cannot be created.
+    pub fn new(base_dir: Option<&Path>) -> Result<Self, MyError> {
+        let pid = std::process::id();
+        let name = match SESSION_COUNT.fetch_add(1, Ordering::Relaxed) {
+            0 => format!("{SESSION_DIR_PREFIX}{pid}"),
+            n => format!("{SESSION_DIR_PREFIX}{pid}-{n}"),
+        };
+        let dir = base_directory(base_dir).join(name);
+        fs::create_dir_all(&dir).map_err(|err| {
+            MyError::Application(format!("{ERROR_TEMP_DIR}: {}: {err:?}", dir.display()))
+        })?;
+
+        Ok(Self { dir })
+    }
+
+    /// Installs a Ctrl-C handler that removes the session directory and exits if the application
+    /// is interrupted. Only one handler can be installed per process, so applications embedding
+    /// the player and handling Ctrl-C themselves should not call this.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success, or a `MyError` if a handler is already installed.
+    pub fn remove_on_interrupt(&self) -> Result<(), MyError> {
+        let handler_dir = self.dir.clone();
+        ctrlc::set_handler(move || {
+            let _ = fs::remove_dir_all(&handler_dir);
+            std::process::exit(130);
+        })
+        .map_err(|err| MyError::Application(format!("{ERROR_TEMP_DIR}: {err:?}")))
+    }
+
+    /// Returns the path of a temporary file with the given name inside the session directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The file name.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Returns the session directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for TempStore {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Removes the temporary files left behind by runs that did not exit cleanly.
+///
+/// Session directories belonging to running processes are kept on systems exposing `/proc`;
+/// elsewhere every leftover is removed, so make sure no other instance is running.
+///
+/// # Arguments
+///
+/// * `base_dir` - The directory to clean, or `None` to use the system temporary directory.
+///
+/// # Returns
+///
+/// A `Result` containing the number of removed entries.
+pub fn clean(base_dir: Option<&Path>) -> Result<usize, MyError> {
+    let mut removed = 0;
+    for entry in fs::read_dir(base_directory(base_dir))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if let Some(pid) = name
+            .strip_prefix(SESSION_DIR_PREFIX)
+            .and_then(|session| session.split('-').next())
+            .and_then(|pid| pid.parse::<u32>().ok())
+        {
+            if pid != std::process::id() && !is_process_running(pid) {
+                fs::remove_dir_all(entry.path())?;
+                removed += 1;
+            }
+        } else if name.starts_with(LEGACY_FILE_PREFIX) && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns the directory where session directories are created.
+fn base_directory(base_dir: Option<&Path>) -> PathBuf {
+    base_dir.map_or_else(std::env::temp_dir, Path::to_path_buf)
+}
+
+/// Checks whether a process is running, on systems exposing processes under `/proc`.
+fn is_process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
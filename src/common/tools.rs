@@ -0,0 +1,98 @@
+//! This module describes the external command line tools the application may rely on
+//! (`ffmpeg`, `ffprobe` and `yt-dlp`), and provides helpers to check whether they are installed
+//! and to explain how to install them when they are not.
+use std::process::{Command, Stdio};
+
+/// An external command line tool used by the application.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExternalTool {
+    /// Used to extract the audio track of videos.
+    Ffmpeg,
+    /// Used to read the frame rate of videos and to detect audio streams.
+    Ffprobe,
+    /// Used to download YouTube videos.
+    YtDlp,
+}
+
+impl ExternalTool {
+    /// Returns the name of the executable for this tool.
+    pub fn command(&self) -> &'static str {
+        match self {
+            ExternalTool::Ffmpeg => "ffmpeg",
+            ExternalTool::Ffprobe => "ffprobe",
+            ExternalTool::YtDlp => "yt-dlp",
+        }
+    }
+
+    /// Returns the flag that makes the tool print its version and exit.
+    fn version_flag(&self) -> &'static str {
+        match self {
+            ExternalTool::Ffmpeg | ExternalTool::Ffprobe => "-version",
+            ExternalTool::YtDlp => "--version",
+        }
+    }
+
+    /// Returns a short explanation of how to install this tool.
+    pub fn install_hint(&self) -> &'static str {
+        match self {
+            ExternalTool::Ffmpeg | ExternalTool::Ffprobe => {
+                "part of FFmpeg. Install it with your package manager (e.g. `sudo apt install ffmpeg`) \
+                 or see https://ffmpeg.org/download.html"
+            }
+            ExternalTool::YtDlp => {
+                "needed for YouTube playback. Install it with your package manager (e.g. `sudo apt install yt-dlp`) \
+                 or see https://github.com/yt-dlp/yt-dlp/wiki/Installation"
+            }
+        }
+    }
+
+    /// Checks whether the tool can be launched.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the executable was found and started, `false` otherwise.
+    pub fn is_available(&self) -> bool {
+        Command::new(self.command())
+            .arg(self.version_flag())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+}
+
+/// Returns the subset of the given tools that cannot be launched.
+///
+/// # Arguments
+///
+/// * `tools` - The tools to check.
+pub fn missing_tools(tools: &[ExternalTool]) -> Vec<ExternalTool> {
+    tools
+        .iter()
+        .copied()
+        .filter(|tool| !tool.is_available())
+        .collect()
+}
+
+/// Builds a human readable report explaining which tools are missing and how to install them.
+///
+/// # Arguments
+///
+/// * `input` - The media the user asked to play.
+/// * `missing` - The tools that could not be found.
+pub fn missing_tools_report(input: &str, missing: &[ExternalTool]) -> String {
+    let mut report =
+        format!("The following tools are required to play {input} but were not found:\n");
+    for tool in missing {
+        report.push_str(&format!(
+            "  - {}: {}\n",
+            tool.command(),
+            tool.install_hint()
+        ));
+    }
+    report.push_str(
+        "Install them and try again, or run with --no-external-tools to use the built-in decoders only \
+         (no audio, no YouTube).",
+    );
+    report
+}
@@ -1,9 +1,10 @@
-use image::{DynamicImage, ImageBuffer};
+use crate::common::errors::*;
 use num::{Rational64, ToPrimitive};
-use opencv::{imgproc, prelude::*};
 use serde_json::Value;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
+use std::time::Duration;
 
 /// Extracts the frame rate from a video file using `ffprobe`.
 ///
@@ -13,9 +14,9 @@ use std::str::FromStr;
 ///
 /// # Returns
 ///
-/// An `Option` containing the frame rate if the frame rate is successfully
-/// extracted, or `None` if an error occurs.
-pub fn extract_fps(video_path: &str) -> Option<f64> {
+/// A `Result` containing the frame rate if it could be extracted, `None` if the file does not
+/// report one, or a `MyError` if `ffprobe` could not be run.
+pub fn extract_fps(video_path: &str) -> Result<Option<f64>, MyError> {
     let output = Command::new("ffprobe")
         .arg("-v")
         .arg("error")
@@ -29,58 +30,261 @@ pub fn extract_fps(video_path: &str) -> Option<f64> {
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .output()
-        .expect("Failed to extract fps from video. Is ffprobe installed?");
+        .map_err(|err| MyError::Application(format!("{ERROR_EXTRACTING_FPS}: {err:?}")))?;
 
-    let output_str = String::from_utf8(output.stdout).unwrap_or("".to_string());
+    let output_str = String::from_utf8(output.stdout).unwrap_or_default();
     let json_value: Value = serde_json::from_str(&output_str).unwrap_or(Value::Null);
-    if json_value != Value::Null {
-        let r_frame_rate = json_value["streams"][0]["r_frame_rate"]
-            .as_str()
-            .ok_or("".to_string())
-            .unwrap_or("");
-
-        let frame_rate_f = Rational64::from_str(r_frame_rate);
-        if let Ok(frame_rate) = frame_rate_f {
-            return Some(frame_rate.to_f64().expect("Failed to parse FPS value"));
-        }
+    let r_frame_rate = json_value["streams"][0]["r_frame_rate"]
+        .as_str()
+        .unwrap_or("");
+
+    Ok(Rational64::from_str(r_frame_rate)
+        .ok()
+        .and_then(|frame_rate| frame_rate.to_f64())
+        .filter(|fps| fps.is_finite() && *fps > 0.0))
+}
+
+/// Returns the directory where the application keeps data across runs, such as the probe cache.
+///
+/// # Returns
+///
+/// The `tplay` directory inside the platform state directory (or local data directory on
+/// platforms without one), or `None` if the home directory cannot be determined.
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("tplay"))
+}
+
+/// Returns the directory where the user configures the application, e.g. its keymap.
+///
+/// # Returns
+///
+/// The `tplay` directory inside the platform configuration directory (e.g. `~/.config/tplay` on
+/// Linux), or `None` if the home directory cannot be determined.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tplay"))
+}
+
+/// Parses a playback speed factor such as `0.25x` or `10x` (the trailing `x` is optional).
+///
+/// # Arguments
+///
+/// * `factor` - The factor as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the factor, or an error message if it is not a positive number.
+pub fn parse_speed_factor(factor: &str) -> Result<f64, String> {
+    let value = factor.trim().trim_end_matches(['x', 'X']);
+    match value.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!(
+            "`{factor}` is not a positive speed factor (e.g. 0.25x)"
+        )),
     }
+}
 
-    None
+/// Parses the factor of the `--timelapse` preset, a whole number of at least `1x`.
+///
+/// # Arguments
+///
+/// * `factor` - The factor as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the factor, or an error message if it is not a whole number.
+pub fn parse_timelapse_factor(factor: &str) -> Result<u32, String> {
+    let value = parse_speed_factor(factor)?;
+    if value.fract() != 0.0 || value < 1.0 || value > u32::MAX as f64 {
+        return Err(format!(
+            "`{factor}` is not a whole speed-up factor (e.g. 10x)"
+        ));
+    }
+    Ok(value as u32)
 }
 
-/// Converts an opencv Mat frame to a dynamic image.
+/// Parses the aspect of the terminal cells, their width relative to their height, given as a
+/// number (e.g. `0.5`) or as a ratio (e.g. `1:2`).
+///
+/// # Arguments
 ///
-/// This helper function takes a reference to a video frame in BGR format and returns an optional
-/// `DynamicImage`.
+/// * `aspect` - The aspect as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the aspect, or an error message if it is not a positive number.
+pub fn parse_cell_aspect(aspect: &str) -> Result<f64, String> {
+    let value = match aspect.split_once(':') {
+        Some((width, height)) => width
+            .trim()
+            .parse::<f64>()
+            .and_then(|width| Ok(width / height.trim().parse::<f64>()?)),
+        None => aspect.trim().parse::<f64>(),
+    };
+    match value {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!(
+            "`{aspect}` is not a positive cell aspect (e.g. 0.5 or 1:2)"
+        )),
+    }
+}
+
+/// Parses the factor of the `--slowmo` preset, a slow-down factor between `0x` and `1x`.
 ///
 /// # Arguments
 ///
-/// * `mat` - A reference to a `Mat` object containing the video frame.
+/// * `factor` - The factor as given on the command line.
 ///
 /// # Returns
 ///
-/// An `Option` containing a `DynamicImage` if the frame is successfully converted, or
-/// `None` if an error occurs.
-pub fn mat_to_dynamic_image(mat: &Mat) -> Option<DynamicImage> {
-    let mut rgb_mat = Mat::default();
-    if imgproc::cvt_color(&mat, &mut rgb_mat, imgproc::COLOR_BGR2RGB, 0).is_ok() {
-        if let Ok(_elem_size) = rgb_mat.elem_size() {
-            if let Ok(size) = rgb_mat.size() {
-                let reshaped_mat = rgb_mat.reshape(1, size.width * size.height).ok()?;
-                let data_vec: Vec<u8> = reshaped_mat
-                    .data_typed::<u8>()
-                    .expect("Unexpected invalid data")
-                    .to_vec();
-
-                if let Some(img_buf) = ImageBuffer::<image::Rgb<u8>, _>::from_raw(
-                    size.width as u32,
-                    size.height as u32,
-                    data_vec,
-                ) {
-                    return Some(DynamicImage::ImageRgb8(img_buf));
-                }
+/// A `Result` containing the factor, or an error message if it is out of range.
+pub fn parse_slowmo_factor(factor: &str) -> Result<f64, String> {
+    let value = parse_speed_factor(factor)?;
+    if value > 1.0 {
+        return Err(format!(
+            "`{factor}` would speed playback up, use --timelapse instead"
+        ));
+    }
+    Ok(value)
+}
+
+/// Parses a size given as `<columns>x<rows>`, e.g. `80x24`.
+///
+/// # Arguments
+///
+/// * `size` - The size as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the number of columns and rows, or an error message if the size is
+/// malformed or empty.
+pub fn parse_size(size: &str) -> Result<(u16, u16), String> {
+    let error = || format!("`{size}` is not a size in characters (e.g. 80x24)");
+    let (columns, rows) = size.split_once(['x', 'X']).ok_or_else(error)?;
+    let columns = columns.trim().parse::<u16>().map_err(|_| error())?;
+    let rows = rows.trim().parse::<u16>().map_err(|_| error())?;
+    if columns == 0 || rows == 0 {
+        return Err(error());
+    }
+    Ok((columns, rows))
+}
+
+/// Parses a margin given as `<rows>,<columns>`, e.g. `2,4`, or as a single number used for both.
+///
+/// # Arguments
+///
+/// * `margin` - The margin as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the number of blank rows (above and below) and columns (left and
+/// right), or an error message if the margin is malformed.
+pub fn parse_margin(margin: &str) -> Result<(u16, u16), String> {
+    let error = || format!("`{margin}` is not a margin in characters (e.g. 2,4 or 1)");
+    let (rows, columns) = margin.split_once(',').unwrap_or((margin, margin));
+    let rows = rows.trim().parse::<u16>().map_err(|_| error())?;
+    let columns = columns.trim().parse::<u16>().map_err(|_| error())?;
+    Ok((rows, columns))
+}
+
+/// Parses a duration such as `30m`, `1h30m` or `90s`: numbers followed by `h`, `m` or `s`. A
+/// number alone is a number of minutes.
+///
+/// # Arguments
+///
+/// * `duration` - The duration as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the duration, or an error message if it is malformed or zero.
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let error = || format!("`{duration}` is not a duration (e.g. 30m, 1h30m, 90s)");
+    let text = duration.trim().to_ascii_lowercase();
+    if let Ok(minutes) = text.parse::<f64>() {
+        return Duration::try_from_secs_f64(minutes * 60.0)
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(error);
+    }
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in text.chars() {
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => {
+                number.push(c);
+                continue;
             }
-        }
+        };
+        let value: f64 = number.parse().map_err(|_| error())?;
+        seconds += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(error());
+    }
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_speed_factors() {
+        assert_eq!(parse_speed_factor("0.25x"), Ok(0.25));
+        assert_eq!(parse_speed_factor("2"), Ok(2.0));
+        assert!(parse_speed_factor("-1x").is_err());
+        assert!(parse_speed_factor("fast").is_err());
+        assert_eq!(parse_timelapse_factor("10x"), Ok(10));
+        assert!(parse_timelapse_factor("2.5x").is_err());
+        assert_eq!(parse_slowmo_factor("0.5X"), Ok(0.5));
+        assert!(parse_slowmo_factor("4x").is_err());
+    }
+
+    #[test]
+    fn test_parse_cell_aspect() {
+        assert_eq!(parse_cell_aspect("0.5"), Ok(0.5));
+        assert_eq!(parse_cell_aspect("1:2"), Ok(0.5));
+        assert_eq!(parse_cell_aspect(" 9 : 20 "), Ok(0.45));
+        assert!(parse_cell_aspect("1:0").is_err());
+        assert!(parse_cell_aspect("0").is_err());
+        assert!(parse_cell_aspect("wide").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("80x24"), Ok((80, 24)));
+        assert_eq!(parse_size("120X40"), Ok((120, 40)));
+        assert!(parse_size("80").is_err());
+        assert!(parse_size("0x24").is_err());
+        assert!(parse_size("axb").is_err());
+    }
+
+    #[test]
+    fn test_parse_margin() {
+        assert_eq!(parse_margin("2,4"), Ok((2, 4)));
+        assert_eq!(parse_margin("1"), Ok((1, 1)));
+        assert_eq!(parse_margin(" 0 , 3 "), Ok((0, 3)));
+        assert!(parse_margin("2x4").is_err());
+        assert!(parse_margin("-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("90S"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("20"), Ok(Duration::from_secs(1200)));
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-5m").is_err());
     }
-    None
 }
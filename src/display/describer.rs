@@ -0,0 +1,268 @@
+//! A front-end describing the media in words instead of drawing it (`--describe`), for screen
+//! reader users: every few seconds, a line is printed with the position of the playback, the
+//! resolution of the media, its brightness, its dominant colors, and the scene cuts since the
+//! previous line.
+//!
+//! The frames are rendered at a small size, enough to count their colors. The scene cuts are told
+//! by the perceptual hashes of consecutive frames differing by many bits (see `frame_hash`).
+use super::{Display, Progress, SourceArea};
+use crate::{
+    common::errors::*,
+    msg::broker::Control as MediaControl,
+    pipeline::frame_hash::{hamming_distance, perceptual_hash},
+    terminal::status_line::format_time,
+    CellGrid,
+};
+use image::DynamicImage;
+use std::{
+    io::{stdout, Write},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Size the frames are rendered at (width, height), in cells.
+const RESOLUTION: (u16, u16) = (32, 18);
+/// How long to wait between two polls, there are no commands to read.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Number of bits from which the hashes of consecutive frames tell a scene cut, out of 64.
+const SCENE_CUT_BITS: u32 = 24;
+/// Number of colors named in each description.
+const DOMINANT_COLORS: usize = 3;
+/// The colors the cells are named after, the nearest one naming each cell.
+const COLOR_NAMES: [(&str, [u8; 3]); 12] = [
+    ("black", [0, 0, 0]),
+    ("gray", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("red", [200, 30, 30]),
+    ("orange", [230, 130, 30]),
+    ("yellow", [230, 210, 40]),
+    ("green", [40, 160, 60]),
+    ("cyan", [40, 190, 200]),
+    ("blue", [40, 70, 200]),
+    ("purple", [130, 50, 170]),
+    ("pink", [230, 120, 170]),
+    ("brown", [120, 75, 40]),
+];
+
+/// Describes the frames in lines of text printed to the standard output.
+pub struct Describer {
+    /// The name of the media, in the first line.
+    title: String,
+    /// How long between two descriptions.
+    interval: Duration,
+    /// When the last description was printed, if any.
+    described: Option<Instant>,
+    /// Where the playback is, as last reported by the media pipeline.
+    progress: Progress,
+    /// The resolution of the frames of the media, if known.
+    source_size: Option<(u32, u32)>,
+    /// The perceptual hash of the previous frame.
+    hash: Option<u64>,
+    /// The positions of the scene cuts since the last description, `None` if unknown.
+    cuts: Vec<Option<f64>>,
+}
+
+impl Describer {
+    /// Constructs a front-end describing the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The name of the media.
+    /// * `interval` - How long between two descriptions.
+    pub fn new(title: String, interval: Duration) -> Self {
+        Self {
+            title,
+            interval,
+            described: None,
+            progress: Progress::default(),
+            source_size: None,
+            hash: None,
+            cuts: Vec::new(),
+        }
+    }
+}
+
+/// Returns the description of a frame.
+///
+/// # Arguments
+///
+/// * `frame` - The frame, the colors of its cells (or its pixels) are counted.
+/// * `progress` - Where the playback is.
+/// * `source_size` - The resolution of the media, if known.
+/// * `cuts` - The positions of the scene cuts since the last description.
+///
+/// # Returns
+///
+/// A line such as `00:12 / 02:46, 1920x1080, dark, blue 45%, black 30%, white 10%, scene cut at
+/// 00:10`.
+fn describe(
+    frame: &CellGrid,
+    progress: Progress,
+    source_size: Option<(u32, u32)>,
+    cuts: &[Option<f64>],
+) -> String {
+    let mut parts = Vec::new();
+    match (progress.position, progress.duration) {
+        (Some(position), Some(duration)) => parts.push(format!(
+            "{} / {}",
+            format_time(position),
+            format_time(duration)
+        )),
+        (Some(position), None) => parts.push(format_time(position)),
+        _ => {}
+    }
+    if let Some((width, height)) = source_size {
+        parts.push(format!("{width}x{height}"));
+    }
+
+    let colors: Vec<[u8; 3]> = match frame.pixels() {
+        Some(pixels) => pixels.pixels().map(|pixel| pixel.0).collect(),
+        None => frame
+            .cells()
+            .map(|cell| cell.background.unwrap_or(cell.color))
+            .collect(),
+    };
+    if !colors.is_empty() {
+        let luma = colors
+            .iter()
+            .map(|[r, g, b]| (*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000)
+            .sum::<u32>()
+            / colors.len() as u32;
+        parts.push(
+            match luma {
+                0..=69 => "dark",
+                70..=169 => "medium brightness",
+                _ => "bright",
+            }
+            .to_string(),
+        );
+        let mut counts = [0usize; COLOR_NAMES.len()];
+        for color in &colors {
+            counts[nearest_color(*color)] += 1;
+        }
+        let mut ranked: Vec<(usize, usize)> = counts.into_iter().enumerate().collect();
+        ranked.sort_by_key(|(index, count)| (std::cmp::Reverse(*count), *index));
+        parts.extend(
+            ranked
+                .into_iter()
+                .take(DOMINANT_COLORS)
+                .filter(|(_, count)| *count > 0)
+                .map(|(index, count)| {
+                    let percent = count * 100 / colors.len();
+                    format!("{} {percent}%", COLOR_NAMES[index].0)
+                }),
+        );
+    }
+
+    let times: Vec<String> = cuts.iter().flatten().map(|cut| format_time(*cut)).collect();
+    match (cuts.len(), times.is_empty()) {
+        (0, _) => {}
+        (1, false) => parts.push(format!("scene cut at {}", times[0])),
+        (1, true) => parts.push("scene cut".to_string()),
+        (_, false) => parts.push(format!("scene cuts at {}", times.join(", "))),
+        (count, true) => parts.push(format!("{count} scene cuts")),
+    }
+    parts.join(", ")
+}
+
+/// Returns the index of the named color nearest to a color.
+///
+/// # Arguments
+///
+/// * `color` - The color.
+fn nearest_color([r, g, b]: [u8; 3]) -> usize {
+    let distance = |[nr, ng, nb]: [u8; 3]| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, nr) + d(g, ng) + d(b, nb)
+    };
+    (0..COLOR_NAMES.len())
+        .min_by_key(|index| distance(COLOR_NAMES[*index].1))
+        .unwrap_or(0)
+}
+
+impl Display for Describer {
+    /// Prints the first line, naming the media.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the small size the frames are rendered at.
+    fn init(&mut self) -> Result<(u16, u16), MyError> {
+        let seconds = self.interval.as_secs_f64();
+        let mut out = stdout();
+        writeln!(out, "Describing {} every {seconds} seconds", self.title)?;
+        out.flush()?;
+        Ok(RESOLUTION)
+    }
+
+    /// Waits a moment: there are no commands, the playback ends with the media.
+    fn poll_controls(&mut self) -> Result<Vec<MediaControl>, MyError> {
+        thread::sleep(POLL_INTERVAL);
+        Ok(Vec::new())
+    }
+
+    /// Records the scene cuts, and prints a description of the frame if the last one is old
+    /// enough.
+    fn draw(&mut self, frame: &CellGrid) -> Result<(), MyError> {
+        let hash = perceptual_hash(frame);
+        if let Some(previous) = self.hash.replace(hash) {
+            if hamming_distance(previous, hash) >= SCENE_CUT_BITS {
+                self.cuts.push(self.progress.position);
+            }
+        }
+        if self
+            .described
+            .is_some_and(|described| described.elapsed() < self.interval)
+        {
+            return Ok(());
+        }
+        self.described = Some(Instant::now());
+        let line = describe(frame, self.progress, self.source_size, &self.cuts);
+        self.cuts.clear();
+        let mut out = stdout();
+        writeln!(out, "{line}")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    fn set_source(&mut self, source: Option<Arc<DynamicImage>>, _area: Option<SourceArea>) {
+        if let Some(source) = source {
+            self.source_size = Some((source.width(), source.height()));
+        }
+    }
+
+    fn cleanup(&mut self) -> Result<(), MyError> {
+        Ok(stdout().flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        // Three quarters blue, a quarter white
+        let mut frame = CellGrid::new(4, 1, false);
+        for color in [[30, 60, 210], [40, 70, 190], [20, 80, 220], [250, 250, 250]] {
+            frame.push("#", color);
+        }
+        let progress = Progress {
+            position: Some(12.0),
+            duration: Some(166.0),
+            buffered: None,
+        };
+        assert_eq!(
+            describe(&frame, progress, Some((1920, 1080)), &[Some(10.0)]),
+            "00:12 / 02:46, 1920x1080, medium brightness, blue 75%, white 25%, scene cut at 00:10"
+        );
+        assert_eq!(
+            describe(&frame, Progress::default(), None, &[None, None]),
+            "medium brightness, blue 75%, white 25%, 2 scene cuts"
+        );
+    }
+}
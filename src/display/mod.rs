@@ -0,0 +1,371 @@
+//! The `display` module defines the interface between the media pipeline and the front-end that
+//! shows the frames to the user.
+//!
+//! A front-end implements the `Display` trait, and is driven by a `DisplayRunner` which feeds it
+//! the rendered frames and forwards the user commands it produces to the message broker. The
+//! `terminal::Terminal` front-end draws the frames directly on the terminal.
+//!
+//! It consists of the following sub-modules:
+//! - `describer`: A front-end describing the media in lines of text instead of drawing it, for
+//!   screen readers.
+//! - `ratatui_display`: A front-end drawing into a ratatui widget, so playback can be embedded in
+//!   an existing TUI (requires the `ratatui_widget` feature).
+//! - `palette`: Custom palettes the colors of the frames are reduced to.
+//! - `themes`: Color themes deciding how the characters are colored, and their registry.
+pub mod describer;
+pub mod palette;
+#[cfg(feature = "ratatui_widget")]
+pub mod ratatui_display;
+pub mod themes;
+
+use crate::{
+    common::{errors::*, stats::StatsLog},
+    msg::broker::Control as MediaControl,
+    CellGrid,
+};
+use crossbeam_channel::{Receiver, Sender};
+use image::DynamicImage;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A frame rendered by the media pipeline, as sent to the display.
+pub struct RenderedFrame {
+    /// The glyph and color of each cell of the frame.
+    pub grid: CellGrid,
+    /// Where the playback is at this frame.
+    pub progress: Progress,
+    /// The frame of the media the grid was rendered from, if any.
+    pub source: Option<Arc<DynamicImage>>,
+    /// The cells of the grid showing the frame of the media, if they show it as a whole, unturned.
+    pub source_area: Option<SourceArea>,
+    /// The number of `Resize` commands the pipeline had received when it rendered the frame,
+    /// telling the frames rendered for a previous size of the display.
+    pub generation: u64,
+}
+
+/// Where the playback is, reported by the media pipeline with each frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Progress {
+    /// The position of the frame in seconds from the start of the media, if the media has
+    /// positions (not for still images and live streams).
+    pub position: Option<f64>,
+    /// The duration of the media in seconds, if known.
+    pub duration: Option<f64>,
+    /// The position up to which the media is decoded ahead of the playback, in seconds, for the
+    /// media decoded in the background (e.g. animations).
+    pub buffered: Option<f64>,
+}
+
+/// The cells of a rendered frame showing the frame of the media, e.g. without the letterbox
+/// bars, so that a cell can be traced back to the pixels it was rendered from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceArea {
+    /// The column of the first cell showing the frame.
+    pub x: u32,
+    /// The row of the first cell showing the frame.
+    pub y: u32,
+    /// The number of columns showing the frame.
+    pub width: u32,
+    /// The number of rows showing the frame.
+    pub height: u32,
+}
+
+impl SourceArea {
+    /// Returns the pixel of an image at the middle of a cell of the area.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - The column and row of the cell in the rendered frame.
+    /// * `image` - The width and height of the frame of the media, in pixels.
+    ///
+    /// # Returns
+    ///
+    /// The column and row of the pixel, or `None` if the cell is outside the area.
+    pub fn pixel_at(&self, cell: (u32, u32), image: (u32, u32)) -> Option<(u32, u32)> {
+        let column = cell.0.checked_sub(self.x).filter(|x| *x < self.width)?;
+        let row = cell.1.checked_sub(self.y).filter(|y| *y < self.height)?;
+        let scale = |cell: u32, cells: u32, pixels: u32| {
+            let pixel = (cell as f64 + 0.5) * pixels as f64 / cells as f64;
+            (pixel as u32).min(pixels.saturating_sub(1))
+        };
+        Some((
+            scale(column, self.width, image.0),
+            scale(row, self.height, image.1),
+        ))
+    }
+}
+
+/// A front-end able to show the frames produced by the media pipeline.
+pub trait Display {
+    /// Prepares the front-end before the playback starts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the size of the drawing area (width, height) in characters.
+    fn init(&mut self) -> Result<(u16, u16), MyError>;
+
+    /// Collects the commands issued by the user since the last call. A `Resize` command is
+    /// expected whenever the drawing area changes size, and `Exit` ends the playback.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the commands to forward to the media pipeline, in order.
+    fn poll_controls(&mut self) -> Result<Vec<MediaControl>, MyError>;
+
+    /// Shows a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The glyph and color of each cell of the frame.
+    fn draw(&mut self, frame: &CellGrid) -> Result<(), MyError>;
+
+    /// Receives where the playback is, before the frame at that position is drawn. Front-ends
+    /// without a progress display ignore it.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - The position and duration of the media.
+    fn set_progress(&mut self, _progress: Progress) {}
+
+    /// Receives the frame of the media the next frame drawn was rendered from, e.g. to save its
+    /// pixels. Front-ends that do not need it ignore it.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The frame of the media, if any.
+    /// * `area` - The cells of the next frame showing the frame of the media, if known.
+    fn set_source(&mut self, _source: Option<Arc<DynamicImage>>, _area: Option<SourceArea>) {}
+
+    /// Restores the front-end after the playback has stopped.
+    fn cleanup(&mut self) -> Result<(), MyError>;
+}
+
+/// The `DisplayRunner` struct drives a `Display`: it passes the rendered frames to it and sends
+/// the user commands it produces to the message broker.
+pub struct DisplayRunner<D: Display> {
+    /// The front-end being driven.
+    display: D,
+    /// The channel for receiving the processed frames from the media processing thread.
+    rx_buffer: Receiver<Option<RenderedFrame>>,
+    /// The channel for sending control events to the media processing thread.
+    tx_control: Sender<MediaControl>,
+    /// Where the time spent drawing each frame is recorded, if enabled.
+    stats: Option<StatsLog>,
+    /// The number of `Resize` commands sent, the generation of the frames of the current size.
+    generation: u64,
+    /// How long each turn of the loop waits for the next frame, zero to poll without waiting.
+    frame_wait: Duration,
+}
+
+impl<D: Display> DisplayRunner<D> {
+    /// Constructs a new DisplayRunner.
+    ///
+    /// # Arguments
+    ///
+    /// * `display` - The front-end to drive.
+    /// * `rx_buffer` - The channel for receiving the processed frames from the media processing
+    ///   thread.
+    /// * `tx_control` - The channel for sending control events to the media processing thread.
+    pub fn new(
+        display: D,
+        rx_buffer: Receiver<Option<RenderedFrame>>,
+        tx_control: Sender<MediaControl>,
+    ) -> Self {
+        Self {
+            display,
+            rx_buffer,
+            tx_control,
+            stats: None,
+            generation: 0,
+            frame_wait: Duration::ZERO,
+        }
+    }
+
+    /// Waits for the next frame at each turn of the loop, rather than polling for it and for the
+    /// user commands without pause. Needed when the thread has a real-time priority, which would
+    /// otherwise spin a core and starve the other threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `wait` - How long to wait for a frame before polling the user commands again.
+    pub fn set_frame_wait(&mut self, wait: Duration) {
+        self.frame_wait = wait;
+    }
+
+    /// Records the time spent drawing each frame in the given log.
+    ///
+    /// # Arguments
+    ///
+    /// * `stats` - The log, shared with the media pipeline.
+    pub fn set_stats_log(&mut self, stats: StatsLog) {
+        self.stats = Some(stats);
+    }
+
+    /// The main loop showing the frames and handling user commands until the playback is exited.
+    /// If the front-end fails, the playback is exited too, and the front-end is restored either
+    /// way.
+    ///
+    /// # Arguments
+    ///
+    /// * `barrier` - A reference to the `Barrier` used to synchronize the start of the animation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the front-end or communication with the
+    /// pipeline.
+    pub fn run(&mut self, barrier: std::sync::Arc<std::sync::Barrier>) -> Result<(), MyError> {
+        // Initialize the display and pass its size to the pipeline
+        let started = self
+            .display
+            .init()
+            .and_then(|(width, height)| self.send_control(MediaControl::Resize(width, height)));
+        // The other threads wait for this one, even if it cannot start
+        barrier.wait();
+        let result = started.and_then(|_| self.show_frames());
+        if result.is_err() {
+            // Best effort, the broker may be gone already
+            let _ = self.send_control(MediaControl::Exit);
+        }
+        let cleanup = self.display.cleanup();
+        result.and(cleanup)
+    }
+
+    /// Shows the frames and forwards the user commands until the playback is exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the front-end or communication with the
+    /// pipeline.
+    fn show_frames(&mut self) -> Result<(), MyError> {
+        let mut running = true;
+        while running {
+            for control in self.display.poll_controls()? {
+                running &= !matches!(control, MediaControl::Exit | MediaControl::ChangeTrack(_));
+                self.send_control(control)?;
+            }
+
+            // Wait for next frame to draw
+            let received = if self.frame_wait.is_zero() {
+                let frame = self.rx_buffer.try_recv();
+                frame.map_err(|err| err.is_disconnected())
+            } else {
+                let frame = self.rx_buffer.recv_timeout(self.frame_wait);
+                frame.map_err(|err| err.is_disconnected())
+            };
+            match received {
+                // Frames rendered for a previous size are dropped
+                Ok(Some(frame)) if frame.generation < self.generation => {
+                    if let Some(stats) = &self.stats {
+                        stats.record_discard();
+                    }
+                }
+                Ok(Some(frame)) => {
+                    let draw_start = Instant::now();
+                    self.display.set_progress(frame.progress);
+                    self.display.set_source(frame.source, frame.source_area);
+                    self.display.draw(&frame.grid)?;
+                    if let Some(stats) = &self.stats {
+                        stats.record_draw(draw_start.elapsed());
+                    }
+                }
+                // The playback was stopped without the display, e.g. remotely
+                Err(true) => running = false,
+                Ok(None) | Err(false) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a control command to the media processing thread, counting the `Resize` commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The control command to send.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the communication with the pipeline.
+    fn send_control(&mut self, control: MediaControl) -> Result<(), MyError> {
+        if matches!(control, MediaControl::Resize(..)) {
+            self.generation += 1;
+        }
+        self.tx_control
+            .send(control)
+            .map_err(|e| MyError::Terminal(format!("{error}: {e:?}", error = ERROR_CHANNEL, e = e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use std::sync::{Barrier, Mutex};
+
+    /// A front-end resized once, recording the frames it draws.
+    struct FakeDisplay {
+        polls: usize,
+        drawn: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Display for FakeDisplay {
+        fn init(&mut self) -> Result<(u16, u16), MyError> {
+            Ok((1, 1))
+        }
+
+        fn poll_controls(&mut self) -> Result<Vec<MediaControl>, MyError> {
+            self.polls += 1;
+            Ok(match self.polls {
+                1 => vec![MediaControl::Resize(1, 1)],
+                5 => vec![MediaControl::Exit],
+                _ => Vec::new(),
+            })
+        }
+
+        fn draw(&mut self, frame: &CellGrid) -> Result<(), MyError> {
+            self.drawn.lock().unwrap().push(frame.to_text());
+            Ok(())
+        }
+
+        fn cleanup(&mut self) -> Result<(), MyError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stale_frames() {
+        let (tx_frames, rx_frames) = unbounded();
+        for (generation, glyph) in ["a", "b", "c"].into_iter().enumerate() {
+            let mut grid = CellGrid::new(1, 1, false);
+            grid.push(glyph, [255; 3]);
+            let frame = RenderedFrame {
+                grid,
+                progress: Progress::default(),
+                source: None,
+                source_area: None,
+                generation: generation as u64,
+            };
+            tx_frames.send(Some(frame)).unwrap();
+        }
+        let (tx_controls, rx_controls) = unbounded();
+        let drawn = Arc::new(Mutex::new(Vec::new()));
+        let display = FakeDisplay {
+            polls: 0,
+            drawn: Arc::clone(&drawn),
+        };
+        let mut runner = DisplayRunner::new(display, rx_frames, tx_controls);
+        runner.run(Arc::new(Barrier::new(1))).unwrap();
+        // The frames rendered before the second resize are dropped
+        assert_eq!(*drawn.lock().unwrap(), ["c"]);
+        let controls: Vec<MediaControl> = rx_controls.try_iter().collect();
+        assert_eq!(
+            controls,
+            [
+                MediaControl::Resize(1, 1),
+                MediaControl::Resize(1, 1),
+                MediaControl::Exit
+            ]
+        );
+    }
+}
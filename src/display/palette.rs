@@ -0,0 +1,203 @@
+//! Custom palettes (`--palette`), to which the colors of the frames are reduced, e.g. to match the
+//! colors of a terminal theme or for the retro look of CGA and EGA graphics.
+//!
+//! A palette is a GIMP palette (`.gpl`), a plain list of hex colors (`#rrggbb`, one per line,
+//! as exported by most palette sites), or the name of a built-in palette. Each color of the frames
+//! is replaced by the closest color of the palette, before the colors are reduced to the color
+//! depth of the terminal.
+use crate::common::errors::*;
+use std::{fs, path::Path};
+
+/// The built-in palettes, by name.
+const BUILT_IN_PALETTES: [(&str, &[[u8; 3]]); 3] = [
+    // The 4 colors of the CGA graphics mode 4, with its high intensity palette 1
+    (
+        "cga",
+        &[[0, 0, 0], [85, 255, 255], [255, 85, 255], [255, 255, 255]],
+    ),
+    // The 16 colors of the default EGA palette
+    (
+        "ega",
+        &[
+            [0, 0, 0],
+            [0, 0, 170],
+            [0, 170, 0],
+            [0, 170, 170],
+            [170, 0, 0],
+            [170, 0, 170],
+            [170, 85, 0],
+            [170, 170, 170],
+            [85, 85, 85],
+            [85, 85, 255],
+            [85, 255, 85],
+            [85, 255, 255],
+            [255, 85, 85],
+            [255, 85, 255],
+            [255, 255, 85],
+            [255, 255, 255],
+        ],
+    ),
+    // The 4 shades of green of the original Game Boy
+    (
+        "gameboy",
+        &[[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]],
+    ),
+];
+
+/// A list of colors, to which the colors of the frames are reduced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    /// The colors, never empty.
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Loads a palette: a built-in palette by name (cga, ega, gameboy), or a palette file.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The name of the built-in palette, or the path of the file.
+    ///
+    /// # Returns
+    ///
+    /// The palette.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or holds no colors.
+    pub fn load(source: &str) -> Result<Self, MyError> {
+        let built_in = BUILT_IN_PALETTES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(source));
+        if let Some((_, colors)) = built_in {
+            return Ok(Self {
+                colors: colors.to_vec(),
+            });
+        }
+        let text = fs::read_to_string(Path::new(source))
+            .map_err(|e| MyError::Application(format!("{ERROR_READING_PALETTE}: {source}: {e}")))?;
+        Self::parse(&text)
+            .map_err(|e| MyError::Application(format!("{ERROR_READING_PALETTE}: {source}: {e}")))
+    }
+
+    /// Parses a GIMP palette or a list of hex colors.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The content of the palette file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the palette, or an error message naming the line that is not a
+    /// color, or if there are no colors.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().peekable();
+        let gimp = lines
+            .peek()
+            .is_some_and(|(_, line)| line.trim() == "GIMP Palette");
+        if gimp {
+            lines.next();
+        }
+        let mut colors = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim();
+            let color = match gimp {
+                true => parse_gimp_line(line),
+                false => parse_hex_line(line),
+            };
+            match color {
+                Ok(Some(color)) => colors.push(color),
+                Ok(None) => {}
+                Err(()) => return Err(format!("line {} is not a color: {line}", number + 1)),
+            }
+        }
+        if colors.is_empty() {
+            return Err("no colors".to_string());
+        }
+        Ok(Self { colors })
+    }
+
+    /// Returns the color of the palette closest to a color.
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - The color.
+    pub fn closest(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let distance = |color: &&[u8; 3]| {
+            color
+                .iter()
+                .zip([r, g, b])
+                .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+        let [r, g, b] = *self
+            .colors
+            .iter()
+            .min_by_key(distance)
+            .unwrap_or(&[r, g, b]);
+        (r, g, b)
+    }
+}
+
+/// Parses a line of a GIMP palette: the red, green and blue values followed by an optional name.
+/// The header fields (`Name:`, `Columns:`) and the comments (`#`) hold no color.
+///
+/// # Arguments
+///
+/// * `line` - The line, trimmed.
+fn parse_gimp_line(line: &str) -> Result<Option<[u8; 3]>, ()> {
+    if line.is_empty() || line.starts_with('#') || line.contains(':') {
+        return Ok(None);
+    }
+    let mut values = line.split_whitespace().map(|value| value.parse::<u8>());
+    match (values.next(), values.next(), values.next()) {
+        (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Ok(Some([r, g, b])),
+        _ => Err(()),
+    }
+}
+
+/// Parses a line of a list of hex colors: `#rrggbb` or `rrggbb`, or `aarrggbb` as in paint.net
+/// palettes, optionally followed by a name. Comments start with `;`, or with `#` when it is not
+/// followed by a color.
+///
+/// # Arguments
+///
+/// * `line` - The line, trimmed.
+fn parse_hex_line(line: &str) -> Result<Option<[u8; 3]>, ()> {
+    let hex = line.split_whitespace().next().unwrap_or("");
+    let is_hex = |text: &str| text.chars().all(|c| c.is_ascii_hexdigit());
+    let digits = match hex.strip_prefix('#') {
+        Some(digits) if digits.len() == 6 && is_hex(digits) => digits,
+        Some(_) => return Ok(None),
+        None if line.is_empty() || line.starts_with(';') => return Ok(None),
+        None if hex.len() == 8 && is_hex(hex) => &hex[2..],
+        None if hex.len() == 6 && is_hex(hex) => hex,
+        None => return Err(()),
+    };
+    let value = u32::from_str_radix(digits, 16).map_err(|_| ())?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Ok(Some([r, g, b]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_palette() {
+        let gimp = "GIMP Palette\nName: Duo\nColumns: 2\n# comment\n  0   0   0\tBlack\n255 128 0 Orange\n";
+        let palette = Palette::parse(gimp).unwrap();
+        assert_eq!(palette.colors, [[0, 0, 0], [255, 128, 0]]);
+        assert_eq!(palette.closest((200, 100, 40)), (255, 128, 0));
+        assert_eq!(palette.closest((30, 20, 10)), (0, 0, 0));
+
+        let hex = "# my palette\n#ff0000\n00ff00 green\n; paint.net\nFF0000FF\n\n";
+        let palette = Palette::parse(hex).unwrap();
+        assert_eq!(palette.colors, [[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+
+        assert!(Palette::parse("GIMP Palette\n1 2\n").is_err());
+        assert!(Palette::parse("#ff0000\nnot a color\n").is_err());
+        assert!(Palette::parse("# nothing\n").is_err());
+        assert_eq!(Palette::load("CGA").unwrap().colors.len(), 4);
+    }
+}
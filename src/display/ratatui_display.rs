@@ -0,0 +1,188 @@
+//! A `Display` front-end that renders the frames into a ratatui widget, so that playback can be
+//! embedded inside an existing TUI.
+//!
+//! `RatatuiDisplay::new` returns the display, to be driven by a `DisplayRunner` on its own thread,
+//! and a `FrameView` handle. The host application renders the `FrameView` as a widget in its own
+//! draw loop, and uses it to send playback commands. The size of the area the widget is rendered
+//! in is passed on to the media pipeline.
+use crate::{
+    common::errors::*,
+    display::{themes::Theme, Display},
+    msg::broker::Control as MediaControl,
+    CellGrid,
+};
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Size of the drawing area reported before the widget is first rendered.
+const DEFAULT_AREA: (u16, u16) = (80, 24);
+
+/// The state shared between the display thread and the widget.
+struct SharedState {
+    /// The last frame received from the media pipeline.
+    frame: Option<CellGrid>,
+    /// The size of the area the widget was last rendered in.
+    area: (u16, u16),
+    /// Whether the area changed since it was last reported to the media pipeline.
+    area_changed: bool,
+    /// Commands issued through the `FrameView`, not yet forwarded to the media pipeline.
+    controls: Vec<MediaControl>,
+    /// Whether to draw without colors.
+    use_grayscale: bool,
+}
+
+/// The `RatatuiDisplay` struct receives the frames from the media pipeline and stores them for the
+/// associated `FrameView` widget.
+pub struct RatatuiDisplay {
+    shared: Arc<Mutex<SharedState>>,
+}
+
+/// A ratatui widget showing the frames received by a `RatatuiDisplay`. It can be cloned, and used
+/// from any thread to control the playback.
+#[derive(Clone)]
+pub struct FrameView {
+    shared: Arc<Mutex<SharedState>>,
+}
+
+impl RatatuiDisplay {
+    /// Constructs a new RatatuiDisplay and its associated widget.
+    ///
+    /// # Arguments
+    ///
+    /// * `use_grayscale` - Whether to draw without colors.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the display, to be driven by a `DisplayRunner`, and the widget to render.
+    pub fn new(use_grayscale: bool) -> (Self, FrameView) {
+        let shared = Arc::new(Mutex::new(SharedState {
+            frame: None,
+            area: DEFAULT_AREA,
+            area_changed: false,
+            controls: Vec::new(),
+            use_grayscale,
+        }));
+        (
+            Self {
+                shared: Arc::clone(&shared),
+            },
+            FrameView { shared },
+        )
+    }
+}
+
+impl Display for RatatuiDisplay {
+    fn init(&mut self) -> Result<(u16, u16), MyError> {
+        let mut shared = lock(&self.shared);
+        shared.area_changed = false;
+        Ok(shared.area)
+    }
+
+    fn poll_controls(&mut self) -> Result<Vec<MediaControl>, MyError> {
+        let mut shared = lock(&self.shared);
+        let mut controls = std::mem::take(&mut shared.controls);
+        if shared.area_changed {
+            shared.area_changed = false;
+            controls.push(MediaControl::Resize(shared.area.0, shared.area.1));
+        }
+        Ok(controls)
+    }
+
+    fn draw(&mut self, frame: &CellGrid) -> Result<(), MyError> {
+        lock(&self.shared).frame = Some(frame.clone());
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), MyError> {
+        lock(&self.shared).frame = None;
+        Ok(())
+    }
+}
+
+impl FrameView {
+    /// Sends a control command to the media pipeline, e.g. `Control::PauseContinue`.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The control command to send.
+    pub fn send_control(&self, control: MediaControl) {
+        let mut shared = lock(&self.shared);
+        if let MediaControl::SetGrayscale(use_grayscale) = control {
+            shared.use_grayscale = use_grayscale;
+        }
+        shared.controls.push(control);
+    }
+}
+
+impl Widget for &FrameView {
+    /// Draws the last received frame in the given area. Rendering the widget in an area of a
+    /// different size asks the media pipeline to render the next frames at that size.
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut shared = lock(&self.shared);
+        if shared.area != (area.width, area.height) {
+            shared.area = (area.width, area.height);
+            shared.area_changed = true;
+        }
+        let Some(frame) = &shared.frame else {
+            return;
+        };
+
+        let grayscale = Theme::grayscale();
+        for y in 0..frame.height().min(area.height as u32) {
+            for (x, frame_cell) in frame.row(y).take(area.width as usize).enumerate() {
+                if let Some(cell) = buf.cell_mut((area.x + x as u16, area.y + y as u16)) {
+                    cell.set_symbol(frame_cell.glyph);
+                    // Transparent cells keep the colors of the buffer
+                    if !shared.use_grayscale && !frame_cell.transparent {
+                        let [r, g, b] = frame_cell.color;
+                        cell.set_fg(Color::Rgb(r, g, b));
+                    }
+                    // Backgrounds draw the picture itself, they are kept in grayscale mode
+                    if let Some([r, g, b]) = frame_cell.background {
+                        let (r, g, b) = match shared.use_grayscale {
+                            true => grayscale.background_color((r, g, b)),
+                            false => (r, g, b),
+                        };
+                        cell.set_bg(Color::Rgb(r, g, b));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Locks the shared state, recovering it if a thread panicked while holding the lock.
+fn lock(shared: &Mutex<SharedState>) -> MutexGuard<'_, SharedState> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_view_renders_frame_and_reports_resize() {
+        let (mut display, view) = RatatuiDisplay::new(false);
+        assert_eq!(display.init().unwrap(), DEFAULT_AREA);
+
+        let area = Rect::new(0, 0, 3, 2);
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        assert_eq!(
+            display.poll_controls().unwrap(),
+            vec![MediaControl::Resize(3, 2)]
+        );
+
+        let mut frame = CellGrid::new(3, 2, false);
+        for glyph in ["a", "b", "c", "d", "e", "f"] {
+            frame.push(glyph, [255; 3]);
+        }
+        display.draw(&frame).unwrap();
+        view.render(area, &mut buf);
+        let mut expected = Buffer::with_lines(["abc", "def"]);
+        expected.set_style(area, Color::Rgb(255, 255, 255));
+        assert_eq!(buf, expected);
+    }
+}
@@ -0,0 +1,203 @@
+//! Color themes, deciding how the characters of a frame are colored by the front-ends.
+//!
+//! The `ThemeRegistry` names the available themes, so they can be selected on the command line and
+//! cycled through during playback. Custom themes can be registered too.
+use super::palette::Palette;
+
+/// How a theme colors the characters of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThemeColors {
+    /// The colors of the media.
+    Source,
+    /// No colors, the characters use the terminal foreground color.
+    Monochrome,
+    /// Shades of a single color, following the brightness of the media.
+    Tint(u8, u8, u8),
+    /// The colors of the media, reduced to the closest colors of a palette.
+    Palette(Palette),
+}
+
+/// A named color theme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// The name used to select the theme.
+    pub name: String,
+    /// How the characters are colored.
+    pub colors: ThemeColors,
+}
+
+impl Theme {
+    /// Returns the theme of the grayscale mode: no colors, but backgrounds in shades of gray.
+    pub fn grayscale() -> Self {
+        Self {
+            name: String::new(),
+            colors: ThemeColors::Monochrome,
+        }
+    }
+
+    /// Returns the color of a character.
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - The color of the media at the character position.
+    ///
+    /// # Returns
+    ///
+    /// The RGB color of the character, or `None` to use the terminal foreground color.
+    pub fn color(&self, (r, g, b): (u8, u8, u8)) -> Option<(u8, u8, u8)> {
+        match &self.colors {
+            ThemeColors::Source => Some((r, g, b)),
+            ThemeColors::Monochrome => None,
+            &ThemeColors::Tint(tr, tg, tb) => {
+                let luma = luma((r, g, b));
+                let shade = |c: u8| (c as u32 * luma / u8::MAX as u32) as u8;
+                Some((shade(tr), shade(tg), shade(tb)))
+            }
+            ThemeColors::Palette(palette) => Some(palette.closest((r, g, b))),
+        }
+    }
+
+    /// Returns the color of the background of a cell. Backgrounds draw the picture itself, so
+    /// themes without colors use shades of gray instead of the terminal background color.
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - The color of the media behind the character.
+    pub fn background_color(&self, rgb: (u8, u8, u8)) -> (u8, u8, u8) {
+        self.color(rgb).unwrap_or_else(|| {
+            let luma = luma(rgb) as u8;
+            (luma, luma, luma)
+        })
+    }
+}
+
+/// Returns the brightness of a color (0-255).
+///
+/// # Arguments
+///
+/// * `rgb` - The color.
+fn luma((r, g, b): (u8, u8, u8)) -> u32 {
+    (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+}
+
+/// An ordered collection of named color themes. The default registry holds the built-in themes,
+/// in the order they are cycled through during playback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeRegistry {
+    themes: Vec<Theme>,
+}
+
+impl Default for ThemeRegistry {
+    fn default() -> Self {
+        let mut registry = Self { themes: Vec::new() };
+        for (name, colors) in [
+            ("color", ThemeColors::Source),
+            ("mono", ThemeColors::Monochrome),
+            ("green", ThemeColors::Tint(0, 255, 70)),
+            ("amber", ThemeColors::Tint(255, 176, 0)),
+            ("blue", ThemeColors::Tint(80, 160, 255)),
+        ] {
+            registry.register(name, colors);
+        }
+        registry
+    }
+}
+
+impl ThemeRegistry {
+    /// Registers a theme, replacing the one with the same name if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the theme.
+    /// * `colors` - How the theme colors the characters.
+    ///
+    /// # Returns
+    ///
+    /// The index of the theme in the registry.
+    pub fn register(&mut self, name: &str, colors: ThemeColors) -> usize {
+        let theme = Theme {
+            name: name.to_string(),
+            colors,
+        };
+        match self.index_of(name) {
+            Some(index) => {
+                self.themes[index] = theme;
+                index
+            }
+            None => {
+                self.themes.push(theme);
+                self.themes.len() - 1
+            }
+        }
+    }
+
+    /// Returns an iterator over the themes, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &Theme> {
+        self.themes.iter()
+    }
+
+    /// Returns the number of themes.
+    pub fn len(&self) -> usize {
+        self.themes.len()
+    }
+
+    /// Returns whether the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.themes.is_empty()
+    }
+
+    /// Looks up a theme by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the theme.
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.index_of(name).map(|index| &self.themes[index])
+    }
+
+    /// Returns the theme at the given index, wrapping around past the last theme.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the theme.
+    pub fn get_index(&self, index: usize) -> Option<&Theme> {
+        self.themes.get(index % self.themes.len().max(1))
+    }
+
+    /// Returns the index of the theme with the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the theme.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.themes.iter().position(|theme| theme.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_registry() {
+        let mut registry = ThemeRegistry::default();
+        let color = registry.get("color").unwrap();
+        assert_eq!(color.color((1, 2, 3)), Some((1, 2, 3)));
+        assert_eq!(registry.get("mono").unwrap().color((1, 2, 3)), None);
+        let mono = registry.get("mono").unwrap();
+        assert_eq!(mono.background_color((255, 0, 0)), (76, 76, 76));
+        assert_eq!(color.background_color((1, 2, 3)), (1, 2, 3));
+        let green = registry.get("green").unwrap();
+        assert_eq!(green.color((255, 255, 255)), Some((0, 255, 70)));
+        assert_eq!(green.color((0, 0, 0)), Some((0, 0, 0)));
+
+        let index = registry.register("red", ThemeColors::Tint(255, 0, 0));
+        assert_eq!(registry.get_index(index + 1), registry.get_index(0));
+        assert_eq!(registry.index_of("red"), Some(index));
+
+        let palette = Palette::parse("#000000\n#ffffff\n").unwrap();
+        registry.register("palette", ThemeColors::Palette(palette));
+        let palette = registry.get("palette").unwrap();
+        assert_eq!(palette.color((200, 180, 190)), Some((255, 255, 255)));
+    }
+}
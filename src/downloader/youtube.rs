@@ -1,23 +1,25 @@
 //! This module provides a function to download a video from a given URL.
 //!
 //! The main function `download_video` uses the `yt-dlp` tool to download a video
-//! from a given URL and stores it in a temporary file.
-//! The function returns a temporary file path to the downloaded video.
-//! The temporary file is deleted when the file is closed.
-//! The temporary file is created in a temporary directory (OS dependent).
+//! from a given URL and stores it in the given file, usually inside the session
+//! directory of the `TempStore`, which takes care of deleting it.
 use crate::common::errors::MyError;
-use std::process::{Command, Stdio};
-use tempfile::{self, TempPath};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 
-/// Downloads a video from the given URL using `yt-dlp` and saves it to a temporary file.
+/// Downloads a video from the given URL using `yt-dlp` and saves it to the given file.
 ///
 /// # Arguments
 ///
 /// * `url` - The URL of the video to download.
+/// * `output_path` - The path of the file where the video is saved.
 ///
 /// # Returns
 ///
-/// * `Ok(TempPath)` - The path to the downloaded temporary video file.
+/// * `Ok(PathBuf)` - The path to the downloaded video file.
 /// * `Err(MyError)` - An error if the video download fails or if `yt-dlp` is not installed.
 ///
 /// # Errors
@@ -27,7 +29,7 @@ use tempfile::{self, TempPath};
 /// * `yt-dlp` is not installed on the system.
 /// * The video download fails for any reason.
 /// * There is an issue with creating or writing to the temporary file.
-pub fn download_video(url: &str) -> Result<TempPath, MyError> {
+pub fn download_video(url: &str, output_path: &Path) -> Result<PathBuf, MyError> {
     // Check that yt-dlp is installed
     if Command::new("yt-dlp").output().is_err() {
         return Err(MyError::Application(
@@ -37,17 +39,13 @@ See https://github.com/yt-dlp/yt-dlp/wiki/Installation"
                 .to_string(),
         ));
     };
-    // Create a temporary file in the current working directory with the prefix "my_temp_file_" and the suffix ".mp4"
-    let temp_file = tempfile::Builder::new()
-        .prefix("my_temp_file_")
-        .suffix(".webm")
-        .tempfile()?;
+    let output_file = File::create(output_path)?;
 
     let mut cmd = Command::new("yt-dlp");
     cmd.arg(url)
         .arg("-o")
         .arg("-")
-        .stdout(Stdio::from(temp_file.as_file().try_clone()?));
+        .stdout(Stdio::from(output_file.try_clone()?));
 
     let child = cmd
         .spawn()
@@ -59,15 +57,11 @@ See https://github.com/yt-dlp/yt-dlp/wiki/Installation"
 
     if output.status.success() {
         // Flush the buffer to ensure that all the data is written to disk
-        temp_file
-            .as_file()
+        output_file
             .sync_all()
             .map_err(|e| MyError::Application(e.to_string()))?;
 
-        // Get the path to the temporary file
-        let temp_file_path = temp_file.into_temp_path();
-
-        Ok(temp_file_path)
+        Ok(output_path.to_path_buf())
     } else {
         Err(MyError::Application(format!(
             "Error downloading video: {:?}",
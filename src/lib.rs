@@ -0,0 +1,30 @@
+//! Library interface of tplay.
+//!
+//! The `tplay` binary is built on top of this crate, which can also be used to embed ASCII
+//! playback in other applications. The main building blocks are:
+//! - `pipeline::frames::open_media`: opens a media file or stream and returns its frames.
+//! - `pipeline::runner::Runner`: converts frames to ASCII at the media frame rate and sends them
+//!   to a display. Observers registered with `Runner::add_frame_observer` receive every rendered
+//!   frame, e.g. to tee the output to a GUI widget or over the network.
+//! - `CellGrid`: a rendered frame, the glyph and color of each cell, as sent to the displays.
+//! - `display::Display`: the interface of the front-ends showing the frames, driven by
+//!   `display::DisplayRunner`. `terminal::Terminal` draws the frames on the terminal and forwards
+//!   user input, `display::ratatui_display` embeds playback in a ratatui application.
+//! - `CharMapRegistry` and `ThemeRegistry`: the named character maps and color themes, which
+//!   can be extended with custom entries.
+//! - `widget::AsciiPlayer`: a ready-made ratatui widget playing media, with play/pause/seek
+//!   controls (requires the `ratatui_widget` feature).
+//! - `msg::broker::MessageBroker`: dispatches user commands to the pipeline and audio threads.
+pub mod audio;
+pub mod common;
+pub mod display;
+pub mod downloader;
+pub mod msg;
+pub mod pipeline;
+pub mod terminal;
+#[cfg(feature = "ratatui_widget")]
+pub mod widget;
+
+pub use common::errors::MyError;
+pub use display::themes::ThemeRegistry;
+pub use pipeline::{cell_grid::CellGrid, char_maps::CharMapRegistry};
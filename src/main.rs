@@ -2,74 +2,761 @@
 //!
 //! This module contains the main function and handles command line arguments,
 //! and launches the audio and image pipelines as well as the terminal.
-mod audio;
-mod common;
-mod downloader;
-mod msg;
-mod pipeline;
-mod terminal;
-
-use audio::runner::Control as AudioControl;
-use clap::Parser;
-use common::errors::*;
+use clap::{Parser, Subcommand};
 use crossbeam_channel::{bounded, unbounded};
-use either::Either;
-use msg::broker::Control as MediaControl;
-use pipeline::{
-    char_maps::CHARS1, frames::open_media, frames::FrameIterator, image_pipeline::ImagePipeline,
-    runner::Control as PipelineControl, runner::RunnerOptions,
+use crossterm::{cursor::MoveToPreviousLine, queue, style::Color};
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use tplay::{
+    audio::{
+        device::AudioDevice,
+        levels::AudioLevels,
+        player::AudioBackend,
+        runner::{Control as AudioControl, Feedback as AudioFeedback},
+        sync::{find_offset, write_aligned, SYNCED_AUDIO_FILE_NAME},
+    },
+    common::{
+        clock::PlaybackClock,
+        errors::*,
+        platform::SystemInfo,
+        priority::{lower_current_thread_priority, raise_current_thread_to_realtime, MAX_NICENESS},
+        stats::{PipelineStats, StatsLog},
+        temp::{self, TempStore},
+        tools::{missing_tools, missing_tools_report, ExternalTool},
+        utils::{
+            parse_cell_aspect, parse_duration, parse_margin, parse_size, parse_slowmo_factor,
+            parse_timelapse_factor,
+        },
+    },
+    display::{
+        describer::Describer,
+        palette::Palette,
+        themes::{Theme, ThemeColors, ThemeRegistry},
+        DisplayRunner, RenderedFrame,
+    },
+    msg::{
+        self,
+        broker::{Control as MediaControl, Feedback as BrokerFeedback},
+        gamepad::GamepadInput,
+        remote::{self, ControlServer, RemoteCommand},
+        shutdown::{join_in_order, PlaybackThread, SHUTDOWN_TIMEOUT},
+        watch_together::{SharedControls, WatchTogether},
+    },
+    pipeline::{
+        self,
+        blocks::BlockMode,
+        braille::{BrailleOptions, Dither},
+        char_maps::{split_glyphs, CharMapRegistry, CHARS1},
+        color_vision::CvdFilter,
+        comparison::Comparison,
+        contact_sheet::{render_sheet, sample_frames},
+        demo,
+        edges::DEFAULT_EDGE_THRESHOLD,
+        frame_hash::FrameHashWriter,
+        frames::open_media, frames::required_tools,
+        frames::{FrameIterator, MediaData},
+        image_pipeline::{ImagePipeline, PaintMode, DEFAULT_BACKGROUND_GLYPH},
+        live::{is_network_stream, DEFAULT_BUFFER_SECONDS},
+        luma::LumaFormula,
+        motion::DEFAULT_MOTION_THRESHOLD,
+        music::{self, Playlist},
+        orientation::Orientation,
+        runner::Control as PipelineControl,
+        runner::RunnerOptions,
+        panorama::{Viewport, CELL_ASPECT, DEFAULT_FOV},
+        slideshow::{Transition, DEFAULT_SLIDE_DURATION, DEFAULT_TRANSITION_FRAMES},
+        stereo::StereoMode,
+        subtitles::{find_subtitle_file, Subtitles},
+        transform::{Rotation, Transform},
+    },
+    terminal::{
+        capabilities::Capabilities,
+        colorize,
+        colors::{parse_color, ColorDepth},
+        keymap::Keymap,
+        screenshot::ScreenshotFormat,
+        sixel,
+        sixel::Backend,
+        sleep_timer::{SleepAction, SleepCommand, SleepTimer},
+        Terminal,
+    },
 };
-use std::thread;
-use terminal::Terminal;
-
-pub type StringInfo = (String, Vec<u8>);
 
 /// Command line arguments structure.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Name of the file/stream to process
-    #[arg(required = true, index = 1)]
-    input: String,
+    #[arg(required_unless_present = "daemon", index = 1)]
+    input: Option<String>,
+    /// Wait for media added with `tplay remote add`, and play them one after the other
+    #[arg(long, default_value = "false", conflicts_with = "preview")]
+    daemon: bool,
+    /// Control the playback with a game controller (A: pause, triggers: seek, Y: mute)
+    #[arg(long, default_value = "false")]
+    gamepad: bool,
+    /// Experimental: host a watch-together session on the given TCP port, sharing play/pause/seek with the tplay that joins it
+    #[arg(long, value_name = "PORT", conflicts_with = "watch_join")]
+    watch_host: Option<u16>,
+    /// Experimental: join the watch-together session hosted at the given address (host:port), playing the same file
+    #[arg(long, value_name = "ADDRESS")]
+    watch_join: Option<String>,
+    /// Quit after the given duration (e.g. 30m, 1h30m), across all the media played
+    #[arg(long, value_parser = parse_duration)]
+    quit_after: Option<Duration>,
+    /// Music mode: play a directory or an M3U playlist track after track, with cover art and tags (n/p change track)
+    #[arg(long, default_value = "false", conflicts_with = "preview")]
+    music: bool,
+    /// Play the tracks of the music mode in random order
+    #[arg(long, default_value = "false", requires = "music")]
+    shuffle: bool,
+    /// Seed of the order of --shuffle, which it requires, the same seed giving the same order
+    #[arg(long, requires = "shuffle")]
+    seed: Option<u64>,
+    /// How long each image of a slideshow (a directory of images) is shown, in seconds
+    #[arg(long, default_value_t = DEFAULT_SLIDE_DURATION)]
+    slide_duration: f64,
+    /// Transition between the images of a slideshow (cut, crossfade, wipe)
+    #[arg(long, default_value = "crossfade")]
+    transition: Transition,
+    /// How many frames the transitions of a slideshow last
+    #[arg(long, default_value_t = DEFAULT_TRANSITION_FRAMES)]
+    transition_frames: usize,
+    /// Seconds of a network stream received before it is played, and after it ran dry (0 plays
+    /// the newest frame at once)
+    #[arg(long, default_value_t = DEFAULT_BUFFER_SECONDS)]
+    buffer: f64,
     /// Force a user-specified FPS
     #[arg(short, long)]
     fps: Option<String>,
     /// Loop playing of video/gif
     #[arg(short, long, default_value = "false")]
     loop_playback: bool,
-    /// Custom lookup char table
+    /// Custom lookup char table, or the name of a built-in one (e.g. gradient, braille)
     #[arg(short, long, default_value = CHARS1)]
     char_map: String,
+    /// Color theme (color, mono, green, amber, blue)
+    #[arg(long, default_value = "color")]
+    theme: String,
+    /// Palette the colors are reduced to: a GIMP palette or hex color list file, or cga, ega,
+    /// gameboy (selects the `palette` theme)
+    #[arg(long)]
+    palette: Option<String>,
     /// Grayscale mode
     #[arg(short, long, default_value = "false")]
     gray: bool,
-    /// Experimental width modifier (emojis have 2x width)
-    #[arg(short, long, default_value = "1")]
-    w_mod: u32,
+    /// Animate the switches of char map and grayscale mode over this many frames (0 switches at
+    /// once)
+    #[arg(long, default_value = "0")]
+    switch_transition: u32,
+    /// Deprecated and ignored, the frames are laid out for the width of the glyphs
+    #[arg(short, long, hide = true)]
+    w_mod: Option<u32>,
     /// Experimental frame skip flag
     #[arg(short, long, default_value = "false")]
     allow_frame_skip: bool,
     /// Experimental flag to add newlines
     #[arg(short, long, default_value = "false")]
     new_lines: bool,
+    /// Draw with braille dots (2x4 per character) instead of the char map, for 8x the resolution
+    #[arg(long, default_value = "false")]
+    braille: bool,
+    /// Dithering of the braille dots (threshold, ordered, floyd-steinberg)
+    #[arg(long, default_value = "floyd-steinberg")]
+    braille_dither: Dither,
+    /// Dithering of the char map, against banding with small char maps (none, ordered, fs)
+    #[arg(long, default_value = "none")]
+    dither: Dither,
+    /// Formula converting the colors to gray levels for the char map, braille dots and edges
+    /// (rec709, rec601, average; cycle with l)
+    #[arg(long, default_value = "rec709")]
+    luma: LumaFormula,
+    /// Gray level from which braille dots are lit (0-255)
+    #[arg(long, default_value = "128")]
+    braille_threshold: u8,
+    /// Draw the edges of the frames with directional glyphs (/ \ | - _) over the char map
+    #[arg(long, default_value = "false")]
+    edges: bool,
+    /// Strength of the gradients from which edges are drawn (0-255)
+    #[arg(long, default_value_t = DEFAULT_EDGE_THRESHOLD)]
+    edge_threshold: u8,
+    /// Draw only what moves: the cells whose brightness changed, static areas fade to the background
+    #[arg(long, default_value = "false")]
+    motion_only: bool,
+    /// Change of brightness from which cells are drawn with --motion-only (0-255)
+    #[arg(long, default_value_t = DEFAULT_MOTION_THRESHOLD)]
+    motion_threshold: u8,
+    /// Turn or mirror the frames, e.g. for a vertically mounted display (normal, rtl, cw, 180, ccw)
+    #[arg(long, default_value = "normal")]
+    orientation: Orientation,
+    /// Mirror the media left to right, e.g. for a webcam (toggle with |)
+    #[arg(long, default_value = "false")]
+    flip_h: bool,
+    /// Mirror the media top to bottom (toggle with _)
+    #[arg(long, default_value = "false")]
+    flip_v: bool,
+    /// Rotate the media clockwise, e.g. for a phone video recorded sideways (0, 90, 180, 270;
+    /// turn a quarter turn more with r)
+    #[arg(long, default_value = "0")]
+    rotate: Rotation,
+    /// Filter the colors for a color vision deficiency: cvd=protanopia, cvd=deuteranopia or
+    /// cvd=tritanopia simulate it, with :daltonize to compensate it instead (e.g.
+    /// cvd=deuteranopia:daltonize)
+    #[arg(long = "vf")]
+    video_filter: Option<CvdFilter>,
+    /// Show 3D media as a red/cyan anaglyph: sbs (side by side) or tab (top and bottom), with
+    /// -left or -right to keep one eye instead (e.g. sbs-left)
+    #[arg(long)]
+    stereo: Option<StereoMode>,
+    /// View equirectangular 360° media through a viewport panned with the arrow keys
+    #[arg(long = "360", default_value = "false")]
+    panorama: bool,
+    /// Horizontal field of view of the 360° viewport, in degrees (10-150)
+    #[arg(long, default_value_t = DEFAULT_FOV)]
+    fov: f64,
+    /// Width of a terminal cell relative to its height (e.g. 0.5 or 1:2), to keep the aspect ratio
+    /// of the frames
+    #[arg(
+        long,
+        visible_alias = "char-width-ratio",
+        default_value_t = CELL_ASPECT,
+        value_parser = parse_cell_aspect
+    )]
+    cell_aspect: f64,
+    /// Draw every row twice, for fonts whose cells are too wide for their height
+    #[arg(long, default_value = "false")]
+    double_rows: bool,
+    /// Stretch the frames to the size of the terminal instead of keeping their aspect ratio
+    #[arg(long, default_value = "false")]
+    stretch: bool,
+    /// Draw with two-color block elements instead of the char map (quadrant: 2x2, sextant: 2x3)
+    #[arg(long)]
+    blocks: Option<BlockMode>,
+    /// What the pixel colors paint: glyph (colored characters) or bg (cell backgrounds)
+    #[arg(long, default_value = "glyph")]
+    mode: PaintMode,
+    /// Glyph drawn over the painted backgrounds (bg mode)
+    #[arg(long, default_value = DEFAULT_BACKGROUND_GLYPH)]
+    bg_char: String,
+    /// Glyph drawn for the transparent pixels (GIF, WebP, APNG), over the terminal background
+    #[arg(long, default_value = " ")]
+    transparent_char: String,
+    /// Split-screen comparison: render each half with a value of a setting (e.g. charmap=ascii:braille)
+    #[arg(long)]
+    ab: Option<Comparison>,
+    /// Subtitle file (.srt, .ass) shown over the video; by default, one named like the video is used
+    #[arg(long)]
+    sub: Option<PathBuf>,
+    /// How frames are drawn: ascii, sixel (pixel graphics, on supporting terminals) or auto
+    #[arg(long, default_value = "ascii")]
+    backend: Backend,
+    /// Colors of the terminal: truecolor, 256, 16 or auto (detected from COLORTERM and TERM)
+    #[arg(long, default_value = "auto")]
+    colors: ColorDepth,
+    /// Only render every Nth source frame (timelapse-style viewing, audio is disabled)
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    decimate: u32,
+    /// Timelapse preset: play N times faster by rendering every Nth frame, without audio (e.g. 10x)
+    #[arg(long, conflicts_with_all = ["decimate", "slowmo"], value_parser = parse_timelapse_factor)]
+    timelapse: Option<u32>,
+    /// Slow-motion preset: slow video and audio down by the given factor (e.g. 0.25x)
+    #[arg(long, value_parser = parse_slowmo_factor)]
+    slowmo: Option<f64>,
+    /// Do not use ffmpeg/ffprobe/yt-dlp, only the built-in decoders (no audio, no YouTube)
+    #[arg(long, default_value = "false")]
+    no_external_tools: bool,
+    /// Directory where temporary files (downloads, audio tracks) are stored
+    #[arg(long)]
+    tmp_dir: Option<PathBuf>,
+    /// Audio backend (rodio, symphonia, mpv; default: depends on the OS, see `tplay info --system`)
+    #[arg(long)]
+    audio_backend: Option<String>,
+    /// Play this audio file instead of the audio track of the video, aligned with it automatically
+    #[arg(long)]
+    audio_file: Option<PathBuf>,
+    /// Play the --audio-file as it is, without aligning it with the audio track of the video
+    #[arg(long, requires = "audio_file", default_value = "false")]
+    no_audio_sync: bool,
+    /// Show a VU meter along the bottom row: the levels of the frequency bands of the audio
+    #[arg(long, default_value = "false")]
+    vu: bool,
+    /// Lower the priority of the decoding thread by the given nice value (1-19)
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=MAX_NICENESS as i64))]
+    nice: Option<i32>,
+    /// Give the thread drawing the frames a real-time priority (may require privileges)
+    #[arg(long, default_value = "false")]
+    rt_frame_thread: bool,
+    /// Low-power mode: cap the frame rate at 15 fps, disable colors and poll input less often
+    #[arg(long, default_value = "false")]
+    battery: bool,
+    /// Write per-frame decode/convert/draw timings and drop decisions to a CSV file
+    #[arg(long)]
+    stats_out: Option<PathBuf>,
+    /// Write a perceptual hash of each rendered frame to a CSV file, to find duplicates or compare
+    /// renderings
+    #[arg(long)]
+    frame_hashes: Option<PathBuf>,
+    /// Do not print the session summary (duration, average fps, dropped frames...) on exit
+    #[arg(long, default_value = "false")]
+    no_summary: bool,
+    /// Inline mode: draw in the given number of rows at the cursor, without clearing the screen
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    rows: Option<u16>,
+    /// Color of the characters when the frame does not color them, e.g. with --gray (name, #rrggbb)
+    #[arg(long, default_value = "white", value_parser = parse_color)]
+    fg: Color,
+    /// Color of the background when the frame does not color it (name, #rrggbb, default)
+    #[arg(long, default_value = "black", value_parser = parse_color)]
+    bg: Color,
+    /// Blank rows and columns around the frame, as <rows>,<columns> (e.g. 2,4) or one number
+    #[arg(long, value_parser = parse_margin)]
+    margin: Option<(u16, u16)>,
+    /// Surround the frame with a border glowing with the colors of its edges (ambient light)
+    #[arg(long, default_value = "false")]
+    ambilight: bool,
+    /// Pause while the terminal window is not focused, and resume when it is (if supported)
+    #[arg(long, default_value = "false")]
+    pause_on_unfocus: bool,
+    /// Keymap file rebinding the playback keys (default: keymap.conf in the configuration directory)
+    #[arg(long)]
+    keymap: Option<PathBuf>,
+    /// File format of the screenshots saved with the `s` key: png (source pixels), txt or ans
+    #[arg(long, default_value = "png")]
+    screenshot_format: ScreenshotFormat,
+    /// Copy the frame with its colors (as ANSI escape sequences) with the `y` key, not plain text
+    #[arg(long, default_value = "false")]
+    copy_ansi: bool,
+    /// Do not inhibit the screensaver and the system sleep during playback
+    #[arg(long, default_value = "false")]
+    no_inhibit: bool,
+    /// Show a status line below the frame: play/pause state, elapsed/total time, fps and filename
+    #[arg(long, default_value = "false")]
+    status_line: bool,
+    /// Describe the media in lines of text instead of drawing it, for screen readers: position,
+    /// resolution, brightness, dominant colors and scene cuts
+    #[arg(long, default_value = "false")]
+    describe: bool,
+    /// Seconds between two descriptions with --describe
+    #[arg(long, default_value = "5")]
+    describe_every: f64,
+    /// File manager preview mode: print the first frame as plain text, without audio or raw mode
+    #[arg(long, default_value = "false")]
+    preview: bool,
+    /// Size of the preview, in characters (e.g. 80x24; default: the terminal size)
+    #[arg(long, requires = "preview", value_parser = parse_size)]
+    preview_size: Option<(u16, u16)>,
+    /// Play the first seconds of the media in the preview, instead of a single frame
+    #[arg(long, requires = "preview")]
+    preview_seconds: Option<f64>,
+}
+
+/// Subcommands, used instead of playing a media file.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Remove temporary files left behind by interrupted runs
+    Clean {
+        /// Directory to clean, if a custom one was used with --tmp-dir
+        #[arg(long)]
+        tmp_dir: Option<PathBuf>,
+    },
+    /// Print information about tplay and the system it runs on
+    Info {
+        /// Show the detected platform and the defaults chosen for it (audio backend, tools)
+        #[arg(long)]
+        system: bool,
+    },
+    /// Play a short built-in animation, to check the colors and shades shown by the terminal
+    Demo,
+    /// Print what the terminal supports, and which tools and audio backends are available, for
+    /// bug reports
+    Doctor,
+    /// Print a contact sheet: evenly spaced frames of a video, tiled in a grid
+    Sheet(SheetArgs),
+    /// Control the running tplay: pause, mute, seek <seconds>, load <file/url>, add <file/url>, quit
+    Remote {
+        /// The command and its argument (e.g. `seek 90`)
+        #[arg(required = true, num_args = 1..)]
+        command: Vec<String>,
+    },
+}
+
+/// Command line arguments of `tplay sheet`.
+#[derive(clap::Args, Debug)]
+struct SheetArgs {
+    /// Name of the file/stream to preview
+    input: String,
+    /// Number of frames to show
+    #[arg(short = 'n', long, default_value = "9", value_parser = clap::value_parser!(u32).range(1..))]
+    frames: u32,
+    /// Number of frames in each row (default: as many as rows)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    columns: Option<u32>,
+    /// Size of the contact sheet, in characters (e.g. 160x48; default: the terminal size)
+    #[arg(long, value_parser = parse_size)]
+    size: Option<(u16, u16)>,
+    /// Custom lookup char table, or the name of a built-in one (e.g. gradient, braille)
+    #[arg(short, long, default_value = CHARS1)]
+    char_map: String,
+    /// Color theme (color, mono, green, amber, blue)
+    #[arg(long, default_value = "color")]
+    theme: String,
+    /// Palette the colors are reduced to: a GIMP palette or hex color list file, or cga, ega,
+    /// gameboy (selects the `palette` theme)
+    #[arg(long)]
+    palette: Option<String>,
+    /// Grayscale mode
+    #[arg(short, long, default_value = "false")]
+    gray: bool,
+    /// Colors of the terminal: truecolor, 256, 16 or auto (detected from COLORTERM and TERM)
+    #[arg(long, default_value = "auto")]
+    colors: ColorDepth,
+    /// Do not use ffmpeg/ffprobe/yt-dlp (the timestamps assume the default frame rate)
+    #[arg(long, default_value = "false")]
+    no_external_tools: bool,
+}
+
+/// A track played from the playlist of the music mode.
+struct PlaylistTrack {
+    /// The position of the track in the playlist, from 0.
+    index: usize,
+    /// The number of tracks of the playlist.
+    count: usize,
+    /// Receives the number of tracks to move by when the playback stops (end of the track, `n`
+    /// and `p` keys).
+    tx_changes: crossbeam_channel::Sender<i32>,
+}
+
+/// The controls of the playback besides the terminal, attached to each media played.
+#[derive(Clone, Copy)]
+struct Controllers<'a> {
+    /// The control socket, if tplay listens on it.
+    remote: Option<&'a ControlServer>,
+    /// The game controllers, if enabled.
+    gamepad: Option<&'a GamepadInput>,
+    /// The watch-together session, if hosted or joined.
+    watch: Option<&'a WatchTogether>,
+    /// The sleep timer, shared by the terminals of all the media played.
+    sleep_timer: &'a SleepTimer,
+}
+
+impl Controllers<'_> {
+    /// Sets the playback the controls act on.
+    ///
+    /// # Arguments
+    ///
+    /// * `controls` - The channel to the message broker of the playback, or `None` once it is
+    ///   over.
+    fn attach(&self, controls: Option<crossbeam_channel::Sender<MediaControl>>) {
+        if let Some(remote) = self.remote {
+            remote.attach(controls.clone());
+        }
+        if let Some(gamepad) = self.gamepad {
+            gamepad.attach(controls);
+        }
+    }
+}
+
+/// The rate at which frames are rendered.
+#[derive(Debug, Clone, Copy)]
+struct PlaybackRate {
+    /// The number of frames rendered per second.
+    fps: f64,
+    /// How many source frames are played for each rendered frame.
+    decimate: usize,
+}
+
+impl Args {
+    /// Returns the glyph drawn over the painted backgrounds, toggled with `x` during playback:
+    /// the first glyph of `--bg-char`.
+    fn background_glyph(&self) -> String {
+        split_glyphs(&self.bg_char)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| DEFAULT_BACKGROUND_GLYPH.to_string())
+    }
+
+    /// Returns the glyph drawn for the transparent pixels: the first glyph of
+    /// `--transparent-char`.
+    fn transparent_glyph(&self) -> String {
+        split_glyphs(&self.transparent_char)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| " ".to_string())
+    }
+
+    /// Returns how the source frames are flipped and rotated, changed with `|`, `_` and `r` during
+    /// playback.
+    fn transform(&self) -> Transform {
+        Transform {
+            flip_horizontal: self.flip_h,
+            flip_vertical: self.flip_v,
+            rotation: self.rotate,
+        }
+    }
+
+    /// Returns the options of the braille dots, toggled with `b` during playback.
+    fn braille_options(&self) -> BrailleOptions {
+        BrailleOptions {
+            dither: self.braille_dither,
+            threshold: self.braille_threshold,
+        }
+    }
+
+    /// Returns the viewport of 360° media, if enabled with `--360`.
+    fn viewport(&self) -> Option<Viewport> {
+        self.panorama
+            .then(|| Viewport::new(self.fov, self.cell_aspect))
+    }
+
+    /// Returns the aspect of the cells the frames are letterboxed with, unless `--stretch` is set.
+    fn letterbox(&self) -> Option<f64> {
+        (!self.stretch).then_some(self.cell_aspect)
+    }
+
+    /// Applies `--slide-duration`, `--transition` and `--transition-frames` to the media, if it is
+    /// a slideshow.
+    ///
+    /// # Arguments
+    ///
+    /// * `media` - The media opened.
+    fn configure_slideshow(&self, media: &mut MediaData) {
+        if let FrameIterator::Slideshow(slideshow) = &mut media.frame_iter {
+            slideshow.set_timing(self.slide_duration, self.transition, self.transition_frames);
+        }
+    }
+
+    /// Applies `--buffer` to the media, if it is a network stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The path or URL of the media.
+    /// * `media` - The media opened.
+    /// * `fps` - The frame rate of the playback.
+    fn configure_buffer(&self, input: &str, media: &mut MediaData, fps: f64) {
+        if let FrameIterator::Live(live) = &mut media.frame_iter {
+            if is_network_stream(input) {
+                live.set_buffer(self.buffer.max(0.0), fps);
+            }
+        }
+    }
+
+    /// Opens the subtitles of a video: the file given with `--sub` or found next to the video,
+    /// then the text streams embedded in the video unless external tools are disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `video` - The path of the video, if the media is one.
+    ///
+    /// # Returns
+    ///
+    /// The subtitle tracks, or `None` if the media cannot have subtitles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file given with `--sub` cannot be read.
+    fn subtitles(&self, video: Option<&Path>) -> Result<Option<Subtitles>, MyError> {
+        if video.is_none() && self.sub.is_none() {
+            return Ok(None);
+        }
+        let mut subtitles = Subtitles::default();
+        match (&self.sub, video.and_then(find_subtitle_file)) {
+            (Some(file), _) => subtitles.load_file(file)?,
+            // A file found next to the video is only a guess, it is skipped if unreadable
+            (None, Some(found)) => {
+                let _ = subtitles.load_file(&found);
+            }
+            (None, None) => {}
+        }
+        if let Some(source) = video.and_then(Path::to_str) {
+            if !self.no_external_tools {
+                subtitles.extract_embedded(source);
+            }
+        }
+        Ok(Some(subtitles))
+    }
+
+    /// Returns the audio track to play: the file given with `--audio-file`, aligned with the
+    /// audio track of the video unless disabled, or else the audio track of the media.
+    ///
+    /// The external audio is aligned by cross-correlating it with the audio track of the video,
+    /// then trimmed or delayed into the temporary directory. When the tracks do not match well
+    /// enough, it is played as it is, with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `media_audio` - The audio track of the media, if it has one.
+    /// * `temp_store` - The store where the aligned audio is saved.
+    ///
+    /// # Returns
+    ///
+    /// The path of the audio to play, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the audio file does not exist, or cannot be decoded or aligned.
+    fn audio_track(
+        &self,
+        media_audio: Option<PathBuf>,
+        temp_store: &TempStore,
+    ) -> Result<Option<PathBuf>, MyError> {
+        let Some(file) = &self.audio_file else {
+            return Ok(media_audio);
+        };
+        if !file.is_file() {
+            return Err(MyError::Application(format!(
+                "{ERROR_AUDIO_FILE_NOT_FOUND}: {}",
+                file.display()
+            )));
+        }
+        let reference = media_audio.filter(|_| !self.no_audio_sync && !self.no_external_tools);
+        let Some(reference) = reference else {
+            return Ok(Some(file.clone()));
+        };
+        match find_offset(&reference, file)? {
+            Some(offset) => {
+                let aligned = temp_store.path(SYNCED_AUDIO_FILE_NAME);
+                write_aligned(file, offset, &aligned)?;
+                Ok(Some(aligned))
+            }
+            None => {
+                eprintln!("{ERROR_AUDIO_NOT_SYNCED}: {}", file.display());
+                Ok(Some(file.clone()))
+            }
+        }
+    }
+
+    /// Returns how many source frames are played for each rendered frame, from `--decimate` or
+    /// the `--timelapse` preset.
+    fn decimation(&self) -> u32 {
+        self.timelapse.unwrap_or(self.decimate)
+    }
+
+    /// Determines the rendering rate from the frame rate of the media and the playback options.
+    ///
+    /// # Arguments
+    ///
+    /// * `media_fps` - The frame rate of the media, if known.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the rendering rate, or a `MyError` if `--fps` is not a positive
+    /// number.
+    fn playback_rate(&self, media_fps: Option<f64>) -> Result<PlaybackRate, MyError> {
+        let is_valid = |fps: &f64| fps.is_finite() && *fps > 0.0;
+        let mut fps = media_fps.filter(is_valid).unwrap_or(DEFAULT_FPS);
+        if let Some(user_fps) = &self.fps {
+            fps = user_fps
+                .parse::<f64>()
+                .map_err(|err| MyError::Application(format!("{ERROR_DATA}:{err:?}")))?;
+            if !is_valid(&fps) {
+                return Err(MyError::Application(format!(
+                    "{ERROR_INVALID_FPS}, not {user_fps}"
+                )));
+            }
+        }
+        fps *= self.slowmo.unwrap_or(1.0);
+        let mut decimate = self.decimation() as usize;
+        if self.battery && fps > BATTERY_MAX_FPS {
+            // Skip frames rather than slowing down, so that playback keeps its speed
+            let skip = (fps / BATTERY_MAX_FPS).ceil();
+            decimate *= skip as usize;
+            fps /= skip;
+        }
+        Ok(PlaybackRate { fps, decimate })
+    }
 }
 
 const DEFAULT_TERMINAL_SIZE: (u32, u32) = (80, 24);
+/// Size of the preview when it is not given and the output is not a terminal.
+const DEFAULT_PREVIEW_SIZE: (u16, u16) = (80, 24);
 const DEFAULT_FPS: f64 = 30.0;
+/// Shown by `--daemon` while waiting for media to play.
+const DAEMON_IDLE_MESSAGE: &str =
+    "Waiting for media to play: tplay remote add <file/url> (Ctrl-C or tplay remote quit to exit)";
+/// Highest frame rate rendered in low-power mode.
+const BATTERY_MAX_FPS: f64 = 15.0;
+/// How long the terminal waits for user input at each poll in low-power mode.
+const BATTERY_POLL_TIMEOUT: Duration = Duration::from_millis(25);
+/// How long the real-time display thread waits for a frame before polling user input again.
+const RT_FRAME_WAIT: Duration = Duration::from_millis(5);
 
 use std::sync::{Arc, Barrier};
-use std::thread::JoinHandle;
+
+/// Name of the thread driving the terminal, which decides when the playback stops.
+const TERMINAL_THREAD: &str = "terminal";
 
 struct MediaProcessor {
-    handles: Vec<JoinHandle<Result<(), MyError>>>,
+    /// The threads launched, in order.
+    threads: Vec<PlaybackThread>,
+    /// The number of threads of the playback, waiting for each other at the barrier.
+    n_threads: usize,
     barrier: Arc<Barrier>,
+    /// Non-fatal errors reported by the threads, shown once the terminal is restored.
+    warnings: (
+        crossbeam_channel::Sender<MyError>,
+        crossbeam_channel::Receiver<MyError>,
+    ),
+    /// Where the pipeline and terminal threads record frame statistics.
+    stats: StatsLog,
+    /// The performance of the pipeline, published by the pipeline thread for the performance
+    /// overlay of the terminal thread. Only the latest snapshot is kept.
+    pipeline_stats: (
+        crossbeam_channel::Sender<PipelineStats>,
+        crossbeam_channel::Receiver<PipelineStats>,
+    ),
+    /// The sleep timer of the session, checked by the terminal thread.
+    sleep_timer: SleepTimer,
+    /// The levels of the audio, measured by the audio thread for the VU meter drawn by the
+    /// pipeline thread, if it is shown.
+    audio_levels: Option<AudioLevels>,
+    /// The playback position, published by the pipeline thread for the subtitles drawn by the
+    /// terminal thread and the watch-together session.
+    playback_clock: PlaybackClock,
+    /// The changes of the audio (e.g. of the volume), reported by the audio thread to the broker.
+    audio_feedback: (
+        crossbeam_channel::Sender<AudioFeedback>,
+        crossbeam_channel::Receiver<AudioFeedback>,
+    ),
+    /// The changes of the audio and the pauses of the playback, forwarded by the broker to the
+    /// terminal thread, which shows them.
+    terminal_feedback: (
+        crossbeam_channel::Sender<BrokerFeedback>,
+        crossbeam_channel::Receiver<BrokerFeedback>,
+    ),
 }
 
 impl MediaProcessor {
-    pub fn new(n_threads: usize) -> Self {
+    pub fn new(
+        n_threads: usize,
+        stats: StatsLog,
+        sleep_timer: SleepTimer,
+        audio_levels: Option<AudioLevels>,
+    ) -> Self {
         MediaProcessor {
-            handles: Vec::with_capacity(n_threads),
+            threads: Vec::with_capacity(n_threads),
+            n_threads,
             barrier: Arc::new(Barrier::new(n_threads)),
+            warnings: unbounded(),
+            stats,
+            pipeline_stats: bounded(1),
+            sleep_timer,
+            audio_levels,
+            playback_clock: PlaybackClock::default(),
+            audio_feedback: unbounded(),
+            terminal_feedback: unbounded(),
         }
     }
 
@@ -78,114 +765,772 @@ impl MediaProcessor {
         rx_controls: crossbeam_channel::Receiver<MediaControl>,
         tx_controls_pipeline: Option<crossbeam_channel::Sender<PipelineControl>>,
         tx_controls_audio: Option<crossbeam_channel::Sender<AudioControl>>,
+        tx_track_changes: Option<crossbeam_channel::Sender<i32>>,
+        shared_controls: Option<SharedControls>,
     ) -> Result<(), MyError> {
         let barrier = Arc::clone(&self.barrier);
+        let rx_feedback_audio = self.audio_feedback.1.clone();
+        let tx_feedback_terminal = self.terminal_feedback.0.clone();
         let handle = thread::spawn(move || -> Result<(), MyError> {
             let mut broker = msg::broker::MessageBroker::new(
                 rx_controls,
                 tx_controls_pipeline,
                 tx_controls_audio,
             );
+            broker.set_feedback(rx_feedback_audio, tx_feedback_terminal);
+            if let Some(tx_track_changes) = tx_track_changes {
+                broker.set_track_changes(tx_track_changes);
+            }
+            if let Some(shared_controls) = shared_controls {
+                broker.set_shared_controls(shared_controls);
+            }
             broker.run(barrier)
         });
-        self.handles.push(handle);
+        self.threads.push(PlaybackThread::new("broker", handle));
         Ok(())
     }
 
     pub fn launch_terminal_thread(
         &mut self,
         title: String,
-        gray: bool,
-        rx_frames: crossbeam_channel::Receiver<Option<StringInfo>>,
+        args: &Args,
+        rate: PlaybackRate,
+        video_path: Option<&Path>,
+        rx_frames: crossbeam_channel::Receiver<Option<RenderedFrame>>,
         tx_controls: crossbeam_channel::Sender<MediaControl>,
     ) -> Result<(), MyError> {
         let barrier = Arc::clone(&self.barrier);
+        let has_scene_index = video_path.is_some();
+        let subtitles = args.subtitles(video_path)?;
+        let playback_clock = self.playback_clock.clone();
+        let gray = args.gray || args.battery;
+        let poll_timeout = if args.battery {
+            BATTERY_POLL_TIMEOUT
+        } else {
+            Duration::ZERO
+        };
+        let (themes, theme) = load_themes(&args.theme, args.palette.as_deref())?;
+        let rt_frame_thread = args.rt_frame_thread;
+        // A real-time thread must block between frames, or it would spin a core
+        let frame_wait = if rt_frame_thread {
+            RT_FRAME_WAIT
+        } else {
+            Duration::ZERO
+        };
+        let inline_rows = args.rows;
+        let margin = args.margin;
+        let (fg, bg) = (args.fg, args.bg);
+        let pause_on_unfocus = args.pause_on_unfocus;
+        let ambilight = args.ambilight;
+        let switch_transition = args.switch_transition;
+        let keymap = Keymap::load(args.keymap.as_deref())?;
+        let char_maps = CharMapRegistry::load()?;
+        let char_map = args.char_map.clone();
+        let luma = args.luma;
+        let screenshot_format = args.screenshot_format;
+        let copy_ansi = args.copy_ansi;
+        let inhibit_screensaver = !args.no_inhibit;
+        let status_line = args.status_line;
+        let describe = args
+            .describe
+            .then(|| Duration::from_secs_f64(args.describe_every.max(0.0)));
+        let backend = args.backend;
+        let color_depth = args.colors;
+        let in_playlist = args.music;
+        let panorama = args.panorama;
+        let stats = self.stats.clone();
+        let rx_stats = self.pipeline_stats.1.clone();
+        let rx_feedback = self.terminal_feedback.1.clone();
+        let sleep_timer = self.sleep_timer.clone();
+        let tx_warnings = self.warnings.0.clone();
         let handle = thread::spawn(move || -> Result<(), MyError> {
-            let mut term = Terminal::new(title, gray, rx_frames, tx_controls);
-            term.run(barrier)
+            if rt_frame_thread {
+                if let Err(err) = raise_current_thread_to_realtime() {
+                    let _ = tx_warnings.send(err);
+                }
+            }
+            if let Some(interval) = describe {
+                let describer = Describer::new(title, interval);
+                let mut display_runner = DisplayRunner::new(describer, rx_frames, tx_controls);
+                display_runner.set_stats_log(stats);
+                display_runner.set_frame_wait(frame_wait);
+                return display_runner.run(barrier);
+            }
+            let mut term = Terminal::new(title, gray, themes, theme);
+            term.set_poll_timeout(poll_timeout);
+            term.set_frame_budget(Duration::from_secs_f64(1.0 / rate.fps));
+            if let Some(rows) = inline_rows {
+                term.set_inline_rows(rows);
+            }
+            if let Some((rows, columns)) = margin {
+                term.set_margin(rows, columns);
+            }
+            term.set_default_colors(fg, bg);
+            term.set_status_line(status_line);
+            term.set_ambilight(ambilight);
+            term.set_switch_transition(switch_transition);
+            term.set_keymap(keymap);
+            term.set_char_maps(&char_maps, &char_map);
+            term.set_luma(luma);
+            term.set_screenshot_format(screenshot_format);
+            term.set_copy_ansi(copy_ansi);
+            term.set_pause_on_unfocus(pause_on_unfocus);
+            term.set_inhibit_screensaver(inhibit_screensaver);
+            term.set_scene_index(has_scene_index);
+            term.set_panorama(panorama);
+            term.set_backend(backend);
+            term.set_color_depth(color_depth);
+            term.set_in_playlist(in_playlist);
+            term.set_sleep_timer(sleep_timer);
+            term.set_stats_channel(rx_stats);
+            term.set_feedback(rx_feedback);
+            if let Some(subtitles) = subtitles {
+                term.set_subtitles(subtitles, playback_clock);
+            }
+            let mut display_runner = DisplayRunner::new(term, rx_frames, tx_controls);
+            display_runner.set_stats_log(stats);
+            display_runner.set_frame_wait(frame_wait);
+            display_runner.run(barrier)
         });
-        self.handles.push(handle);
+        self.threads
+            .push(PlaybackThread::new(TERMINAL_THREAD, handle));
         Ok(())
     }
 
     pub fn launch_pipeline_thread(
         &mut self,
         args: &Args,
-        media: FrameIterator,
-        fps: Option<f64>,
-        tx_frames: crossbeam_channel::Sender<Option<StringInfo>>,
+        media: MediaData,
+        rate: PlaybackRate,
+        tx_frames: crossbeam_channel::Sender<Option<RenderedFrame>>,
         rx_controls_pipeline: crossbeam_channel::Receiver<PipelineControl>,
         tx_controls: crossbeam_channel::Sender<MediaControl>,
     ) -> Result<(), MyError> {
         let barrier = Arc::clone(&self.barrier);
-        let mut use_fps = DEFAULT_FPS;
-        if let Some(fps) = fps {
-            use_fps = fps;
-        }
-        if let Some(fps) = &args.fps {
-            use_fps = fps
-                .parse::<f64>()
-                .map_err(|err| MyError::Application(format!("{ERROR_DATA}:{err:?}")))?;
-        }
-        let cmaps = args.char_map.chars().collect();
-        let w_mod = args.w_mod;
+        let PlaybackRate {
+            fps: use_fps,
+            decimate,
+        } = rate;
+        let char_maps = CharMapRegistry::load()?;
+        let cmaps = char_maps.resolve(&args.char_map);
+        let cell_aspect = args.cell_aspect;
         let loop_playback = args.loop_playback;
         let allow_frame_skip = args.allow_frame_skip;
         let new_lines = args.new_lines;
+        let braille = args.braille_options();
+        let start_braille = args.braille;
+        let blocks = args.blocks;
+        let dither = args.dither;
+        let luma = args.luma;
+        let edges = args.edges.then_some(args.edge_threshold);
+        let orientation = args.orientation;
+        let transform = args.transform();
+        let color_filter = args.video_filter;
+        let double_rows = args.double_rows;
+        let stereo = args.stereo;
+        let viewport = args.viewport();
+        let letterbox = args.letterbox();
+        let background_glyph = args.background_glyph();
+        let transparent_glyph = args.transparent_glyph();
+        let paint_background = args.mode == PaintMode::Background;
+        let backend = args.backend.resolve();
+        let comparison = args.ab.clone();
+        let next_track_at_end = args.music;
+        let switch_transition = args.switch_transition;
+        let motion = args.motion_only.then_some(args.motion_threshold);
+        let nice = args.nice;
+        let stats = self.stats.clone();
+        let tx_stats = self.pipeline_stats.0.clone();
+        let audio_levels = self.audio_levels.clone();
+        let playback_clock = self.playback_clock.clone();
+        let frame_hashes = args
+            .frame_hashes
+            .as_deref()
+            .map(FrameHashWriter::create)
+            .transpose()?;
+        let tx_warnings = self.warnings.0.clone();
         let handle = thread::spawn(move || -> Result<(), MyError> {
+            if let Some(nice) = nice {
+                if let Err(err) = lower_current_thread_priority(nice) {
+                    let _ = tx_warnings.send(err);
+                }
+            }
+            let mut image_pipeline = ImagePipeline::new(DEFAULT_TERMINAL_SIZE, cmaps, new_lines);
+            image_pipeline.braille = start_braille.then_some(braille);
+            image_pipeline.blocks = blocks;
+            image_pipeline.dither = dither;
+            image_pipeline.luma = luma;
+            image_pipeline.edges = edges;
+            image_pipeline.orientation = orientation;
+            image_pipeline.transform = transform;
+            image_pipeline.color_filter = color_filter;
+            image_pipeline.double_rows = double_rows;
+            image_pipeline.stereo = stereo;
+            image_pipeline.panorama = viewport;
+            image_pipeline.letterbox = letterbox;
+            image_pipeline.background_glyph =
+                paint_background.then(|| background_glyph.clone());
+            image_pipeline.transparent_glyph = transparent_glyph;
+            image_pipeline.comparison = comparison;
+            if backend == Backend::Sixel {
+                image_pipeline.pixels_per_cell = Some(sixel::cell_size());
+            }
             let mut runner = pipeline::runner::Runner::new(
-                ImagePipeline::new(DEFAULT_TERMINAL_SIZE, cmaps, new_lines),
-                media,
+                image_pipeline,
+                media.frame_iter,
                 tx_frames,
                 rx_controls_pipeline,
                 tx_controls,
                 RunnerOptions {
                     fps: use_fps,
-                    w_mod,
+                    cell_aspect,
                     loop_playback,
+                    decimate,
+                    char_maps,
                 },
             );
+            runner.set_stats_log(stats);
+            runner.set_stats_channel(tx_stats);
+            runner.set_braille_options(braille);
+            runner.set_background_glyph(background_glyph);
+            if let Some(source) = media.video_path.as_deref().and_then(|path| path.to_str()) {
+                runner.set_scene_source(source, use_fps);
+            }
+            runner.set_playback_clock(playback_clock);
+            runner.set_caption(media.caption);
+            if let Some(levels) = audio_levels {
+                runner.set_audio_levels(levels);
+            }
+            runner.set_next_track_at_end(next_track_at_end);
+            runner.set_switch_transition(switch_transition);
+            if let Some(threshold) = motion {
+                runner.set_motion_only(threshold);
+            }
+            if let Some(mut frame_hashes) = frame_hashes {
+                runner.add_frame_observer(move |frame| frame_hashes.record(frame));
+            }
             runner.run(barrier, allow_frame_skip)
         });
-        self.handles.push(handle);
+        self.threads.push(PlaybackThread::new("pipeline", handle));
         Ok(())
     }
 
     pub fn launch_audio_thread(
         &mut self,
+        device: &AudioDevice,
         file_path: String,
+        temp_dir: PathBuf,
+        speed: f64,
         rx_controls_audio: crossbeam_channel::Receiver<AudioControl>,
     ) -> Result<(), MyError> {
         let barrier = Arc::clone(&self.barrier);
+        let levels = self.audio_levels.clone();
+        let tx_feedback = self.audio_feedback.0.clone();
+        let device = device.clone();
+        // The audio is played by the device, which stays open for the next media
         let handle = thread::spawn(move || -> Result<(), MyError> {
-            let player = audio::player::AudioPlayer::new(&file_path)?;
-            let mut runner = audio::runner::Runner::new(player, rx_controls_audio);
-            runner.run(barrier)
+            device.play(
+                file_path,
+                temp_dir,
+                speed,
+                levels,
+                (rx_controls_audio, tx_feedback),
+                barrier,
+            )
         });
-        self.handles.push(handle);
+        self.threads.push(PlaybackThread::new("audio", handle));
         Ok(())
     }
 
+    /// Waits for the threads to stop, in the order of the shutdown (see `msg/shutdown.rs`): the
+    /// terminal, for as long as the playback lasts, then the others within `SHUTDOWN_TIMEOUT`.
+    /// The errors of the threads are shown with the warnings, once the terminal is restored.
     pub fn join_threads(self) {
-        for handle in self.handles {
-            let _ = handle.join();
+        let (terminal, others): (Vec<_>, Vec<_>) = self
+            .threads
+            .into_iter()
+            .partition(|thread| thread.name == TERMINAL_THREAD);
+        let mut errors: Vec<MyError> = terminal
+            .into_iter()
+            .filter_map(|thread| thread.join(None).err())
+            .collect();
+        errors.extend(join_in_order(others, SHUTDOWN_TIMEOUT));
+        for warning in self.warnings.1.try_iter().chain(errors) {
+            eprintln!("{warning}");
+        }
+    }
+
+    /// Stops the threads launched so far, when the others cannot be launched.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_controls` - The channel of the commands to the broker.
+    pub fn abort(self, tx_controls: &crossbeam_channel::Sender<MediaControl>) {
+        // Stand in for the missing threads at the barrier, which the others wait at
+        for _ in self.threads.len()..self.n_threads {
+            let barrier = Arc::clone(&self.barrier);
+            thread::spawn(move || barrier.wait());
+        }
+        let _ = tx_controls.send(MediaControl::Exit);
+        self.join_threads();
+    }
+}
+
+/// Returns the color themes, with the `palette` theme when a palette is given.
+///
+/// # Arguments
+///
+/// * `theme` - The name of the theme to start with.
+/// * `palette` - The palette file, or the name of a built-in palette, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the themes and the index of the theme to start with: the `palette` theme
+/// when a palette is given, or else the theme with the given name.
+///
+/// # Errors
+///
+/// Returns an error if the theme is unknown or the palette cannot be read.
+fn load_themes(theme: &str, palette: Option<&str>) -> Result<(ThemeRegistry, usize), MyError> {
+    let mut themes = ThemeRegistry::default();
+    if let Some(palette) = palette {
+        let index = themes.register("palette", ThemeColors::Palette(Palette::load(palette)?));
+        return Ok((themes, index));
+    }
+    let index = themes.index_of(theme).ok_or(MyError::Application(format!(
+        "{ERROR_UNKNOWN_THEME}: {theme}"
+    )))?;
+    Ok((themes, index))
+}
+
+/// Prints a preview of the media for the preview pane of a file manager (ranger, lf, yazi...).
+///
+/// The preview starts fast: no raw mode, no alternate screen, no audio, and the frame rate is only
+/// probed when playing the first seconds. The frames are printed as lines of colored text, so the
+/// output can be captured by the file manager. The first seconds are only played when printing to
+/// a terminal, redrawing the frames in place.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments.
+/// * `input` - The media to preview.
+///
+/// # Errors
+///
+/// Returns an error if the media cannot be opened, if `--fps` is not a positive number (the delay
+/// between the frames is derived from it), or if the preview cannot be printed.
+fn preview(args: &Args, input: &str) -> Result<(), MyError> {
+    let is_terminal = std::io::stdout().is_terminal();
+    let (columns, rows) = match args.preview_size {
+        Some(size) => size,
+        None if is_terminal => crossterm::terminal::size().unwrap_or(DEFAULT_PREVIEW_SIZE),
+        None => DEFAULT_PREVIEW_SIZE,
+    };
+    let seconds = args.preview_seconds.filter(|_| is_terminal);
+    let (themes, theme) = load_themes(&args.theme, args.palette.as_deref())?;
+    let theme = themes
+        .get_index(theme)
+        .cloned()
+        .unwrap_or_else(Theme::grayscale);
+
+    let temp_store = TempStore::new(args.tmp_dir.as_deref())?;
+    temp_store.remove_on_interrupt()?;
+    let external_tools = !args.no_external_tools && seconds.is_some();
+    let mut media_data = open_media(input.to_string(), external_tools, &temp_store)?;
+    args.configure_slideshow(&mut media_data);
+    let rate = args.playback_rate(media_data.fps)?;
+    let frame_count = seconds.map_or(1, |seconds| (seconds * rate.fps).ceil().max(1.0) as usize);
+    let frame_duration = Duration::from_secs_f64(1.0 / rate.fps);
+
+    let mut pipeline = ImagePipeline::new(
+        (columns as u32, rows as u32),
+        CharMapRegistry::load()?.resolve(&args.char_map),
+        false,
+    );
+    pipeline.braille = args.braille.then(|| args.braille_options());
+    pipeline.blocks = args.blocks;
+    pipeline.dither = args.dither;
+    pipeline.luma = args.luma;
+    pipeline.edges = args.edges.then_some(args.edge_threshold);
+    pipeline.orientation = args.orientation;
+    pipeline.transform = args.transform();
+    pipeline.color_filter = args.video_filter;
+    pipeline.double_rows = args.double_rows;
+    pipeline.stereo = args.stereo;
+    pipeline.panorama = args.viewport();
+    pipeline.letterbox = args.letterbox();
+    pipeline.background_glyph =
+        (args.mode == PaintMode::Background).then(|| args.background_glyph());
+    pipeline.transparent_glyph = args.transparent_glyph();
+    pipeline.comparison = args.ab.clone();
+    pipeline.lay_out((columns as u32, rows as u32), args.cell_aspect);
+    let color_depth = args.colors.resolve();
+    let gray = Theme::grayscale();
+    let mut out = std::io::stdout().lock();
+    let frames = media_data.frame_iter.step_by(rate.decimate).take(frame_count);
+    let mut frame_start = Instant::now();
+    // The lines printed by the previous frame, fewer than the rows of the area when the layout
+    // does not fill it (e.g. --double-rows with an odd number of rows)
+    let mut printed_rows = 0;
+    for (index, frame) in frames.enumerate() {
+        if index > 0 {
+            thread::sleep(frame_duration.saturating_sub(frame_start.elapsed()));
+            frame_start = Instant::now();
+            if printed_rows > 0 {
+                queue!(out, MoveToPreviousLine(printed_rows as u16))?;
+            }
         }
+        let grid = pipeline.render(&frame)?;
+        printed_rows = grid.height();
+        for y in 0..grid.height() {
+            // Backgrounds draw the picture itself, they are kept in grayscale mode
+            match (args.gray, grid.has_backgrounds()) {
+                (true, false) => writeln!(out, "{}", grid.row_text(y))?,
+                (true, true) => writeln!(out, "{}", colorize(grid.row(y), &gray, color_depth))?,
+                (false, _) => writeln!(out, "{}", colorize(grid.row(y), &theme, color_depth))?,
+            }
+        }
+        out.flush()?;
     }
+    Ok(())
+}
+
+/// Prints a contact sheet of the media: evenly spaced frames tiled in a grid, each with its
+/// timestamp, as lines of colored text.
+///
+/// # Arguments
+///
+/// * `args` - The arguments of `tplay sheet`.
+///
+/// # Errors
+///
+/// Returns an error if the media cannot be opened, or the sheet cannot fit in the given size.
+fn contact_sheet(args: &SheetArgs) -> Result<(), MyError> {
+    let (columns, rows) = match args.size {
+        Some(size) => size,
+        None if std::io::stdout().is_terminal() => {
+            crossterm::terminal::size().unwrap_or(DEFAULT_PREVIEW_SIZE)
+        }
+        None => DEFAULT_PREVIEW_SIZE,
+    };
+    let (themes, theme) = load_themes(&args.theme, args.palette.as_deref())?;
+    let theme = themes
+        .get_index(theme)
+        .cloned()
+        .unwrap_or_else(Theme::grayscale);
+
+    let temp_store = TempStore::new(None)?;
+    temp_store.remove_on_interrupt()?;
+    let mut media_data = open_media(args.input.clone(), !args.no_external_tools, &temp_store)?;
+    let fps = media_data.fps.unwrap_or(DEFAULT_FPS);
+    let frames = sample_frames(&mut media_data.frame_iter, args.frames as usize, fps);
+    let tiles_per_row = args
+        .columns
+        .unwrap_or_else(|| (frames.len() as f64).sqrt().ceil() as u32);
+    let sheet = render_sheet(
+        &frames,
+        tiles_per_row,
+        (columns as u32, rows as u32),
+        CharMapRegistry::load()?.resolve(&args.char_map),
+        None,
+    )?;
+
+    let color_depth = args.colors.resolve();
+    let mut out = std::io::stdout().lock();
+    for y in 0..sheet.height() {
+        if args.gray {
+            writeln!(out, "{}", sheet.row_text(y))?;
+        } else {
+            writeln!(out, "{}", colorize(sheet.row(y), &theme, color_depth))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
 }
 
 fn main() -> Result<(), MyError> {
     let args = Args::parse();
+    if args.w_mod.is_some() {
+        eprintln!("{ERROR_W_MOD_DEPRECATED}");
+    }
 
-    let title = args.input.clone();
+    match &args.command {
+        Some(Command::Clean { tmp_dir }) => {
+            let removed = temp::clean(tmp_dir.as_deref())?;
+            println!("Removed {removed} temporary file(s)");
+            return Ok(());
+        }
+        Some(Command::Info { system }) => {
+            println!("tplay {}", env!("CARGO_PKG_VERSION"));
+            if *system {
+                print!("{}", SystemInfo::detect());
+            }
+            return Ok(());
+        }
+        Some(Command::Doctor) => {
+            println!("tplay {}", env!("CARGO_PKG_VERSION"));
+            print!("{}", Capabilities::detect());
+            print!("{}", SystemInfo::detect());
+            return Ok(());
+        }
+        Some(Command::Sheet(sheet_args)) => return contact_sheet(sheet_args),
+        Some(Command::Remote { command }) => {
+            let mut command: RemoteCommand = command
+                .join(" ")
+                .parse()
+                .map_err(|err| MyError::Application(format!("{ERROR_REMOTE_COMMAND}: {err}")))?;
+            // Local files are resolved from the current directory, not the one of the player
+            if let RemoteCommand::Load(input) | RemoteCommand::Add(input) = &mut command {
+                if let Ok(path) = std::fs::canonicalize(&*input) {
+                    *input = path.to_string_lossy().into_owned();
+                }
+            }
+            return remote::send(&remote::socket_path(), &command);
+        }
+        Some(Command::Demo) | None => {}
+    }
+
+    let title = match (&args.input, args.daemon) {
+        _ if matches!(args.command, Some(Command::Demo)) => Some(demo::DEMO_INPUT.to_string()),
+        (Some(input), _) => Some(input.clone()),
+        (None, true) => None,
+        (None, false) => return Err(MyError::Application(ERROR_MISSING_INPUT.to_string())),
+    };
+
+    if let (true, Some(title)) = (args.preview, &title) {
+        return preview(&args, title);
+    }
+
+    let audio_backend = match &args.audio_backend {
+        Some(name) => Some(AudioBackend::select(name)?),
+        None => AudioBackend::preferred(),
+    };
+    if let (true, Some(backend)) = (args.vu, audio_backend) {
+        if !backend.measures_levels() {
+            return Err(MyError::Audio(format!("{ERROR_VU_METER_BACKEND}, not {backend}")));
+        }
+    }
 
-    let media_data = open_media(title)?;
-    let media = media_data.frame_iter;
-    let fps = media_data.fps;
-    let audio = media_data.audio_path;
+    // The media given on the command line is checked before anything is set up, later ones
+    // (playlist tracks, remotely queued media) are reported by `play` and skipped
+    if let (false, false, Some(title)) = (args.no_external_tools, args.music, &title) {
+        let missing = missing_tools(&required_tools(title, audio_backend));
+        if !missing.is_empty() {
+            eprintln!("{}", missing_tools_report(title, &missing));
+            std::process::exit(1);
+        }
+    }
+
+    // The audio device stays open for all the media played
+    let audio_device = audio_backend.map(AudioDevice::new);
+    // A single session directory, and Ctrl-C handler, for all the media played
+    let temp_store = TempStore::new(args.tmp_dir.as_deref())?;
+    temp_store.remove_on_interrupt()?;
+    // Other terminals can control the playback, unless another tplay already listens. The
+    // daemon has nothing to play without it.
+    let remote = match ControlServer::start(&remote::socket_path()) {
+        Ok(server) => Some(server),
+        Err(err) if args.daemon => return Err(err),
+        Err(_) => None,
+    };
+    let gamepad = if args.gamepad {
+        Some(GamepadInput::start()?)
+    } else {
+        None
+    };
+    let watch = match (args.watch_host, &args.watch_join) {
+        (Some(port), _) => Some(WatchTogether::host(port)?),
+        (None, Some(address)) => Some(WatchTogether::join(address)?),
+        (None, None) => None,
+    };
+    let sleep_timer = SleepTimer::default();
+    if let Some(duration) = args.quit_after {
+        sleep_timer.apply(SleepCommand::Start(duration, SleepAction::Quit));
+    }
+    let controllers = Controllers {
+        remote: remote.as_ref(),
+        gamepad: gamepad.as_ref(),
+        watch: watch.as_ref(),
+        sleep_timer: &sleep_timer,
+    };
+    let mut next = title;
+    loop {
+        let input = match next.take() {
+            Some(input) => input,
+            None if args.daemon => {
+                println!("{DAEMON_IDLE_MESSAGE}");
+                match remote.as_ref().and_then(|server| server.queue().wait_pop()) {
+                    Some(input) => input,
+                    None => return Ok(()),
+                }
+            }
+            None => return Ok(()),
+        };
+        let played = if args.music {
+            play_playlist(
+                &args,
+                &input,
+                audio_device.as_ref(),
+                &temp_store,
+                controllers,
+            )
+        } else {
+            play(
+                &args,
+                input,
+                audio_device.as_ref(),
+                &temp_store,
+                controllers,
+                None,
+            )
+        };
+        match played {
+            // The daemon keeps playing the queue when a media cannot be played
+            Err(err) if args.daemon => eprintln!("{err}"),
+            result => result?,
+        }
+        if sleep_timer.has_quit() {
+            return Ok(());
+        }
+        // Media loaded or added remotely are played next
+        next = remote.as_ref().and_then(|server| server.queue().pop());
+    }
+}
+
+/// Plays the tracks of a playlist one after the other (`--music`), until the last one ends or the
+/// user quits. Tracks that cannot be played are reported and skipped.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments.
+/// * `input` - The directory, M3U playlist or single track to play.
+/// * `audio_device` - The audio backend, if any is available, kept open from one track to the
+///   next.
+/// * `temp_store` - The store where downloaded media and audio tracks are saved.
+/// * `controllers` - The controls besides the terminal, attached to the playback of each track.
+///
+/// # Errors
+///
+/// Returns an error if the playlist cannot be read.
+fn play_playlist(
+    args: &Args,
+    input: &str,
+    audio_device: Option<&AudioDevice>,
+    temp_store: &TempStore,
+    controllers: Controllers,
+) -> Result<(), MyError> {
+    let mut playlist = Playlist::open(input)?;
+    if args.shuffle {
+        playlist.shuffle(args.seed.unwrap_or_else(music::shuffle_seed));
+    }
+    let (tx_changes, rx_changes) = unbounded();
+    let mut index = 0;
+    while let Some(input) = playlist.get(index) {
+        let track = PlaylistTrack {
+            index,
+            count: playlist.len(),
+            tx_changes: tx_changes.clone(),
+        };
+        let played = play(
+            args,
+            input.to_string(),
+            audio_device,
+            temp_store,
+            controllers,
+            Some(&track),
+        );
+        if let Err(err) = played {
+            eprintln!("{err}");
+            index += 1;
+            continue;
+        }
+        if controllers.sleep_timer.has_quit() {
+            return Ok(());
+        }
+        // Quitting stops the playlist, while the end of the track and the n/p keys change track
+        match rx_changes.try_iter().last() {
+            Some(step) => index = index.saturating_add_signed(step as isize),
+            None => return Ok(()),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the tools needed to play a media are installed.
+///
+/// # Arguments
+///
+/// * `title` - The media to play.
+/// * `tools` - The tools needed to play it.
+///
+/// # Errors
+///
+/// Returns a report of the tools that cannot be found, if any of the given tools is missing.
+fn check_missing_tools(title: &str, tools: &[ExternalTool]) -> Result<(), MyError> {
+    let missing = missing_tools(tools);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(MyError::Application(missing_tools_report(title, &missing)))
+    }
+}
+
+/// Plays a media in the terminal, with its audio track, until it ends or the user quits.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments.
+/// * `title` - The media to play.
+/// * `audio_device` - The audio backend, if any is available, kept open from one media to the
+///   next.
+/// * `temp_store` - The store where downloaded media and audio tracks are saved.
+/// * `controllers` - The controls besides the terminal, attached to the playback while it lasts.
+/// * `track` - The position of the media in the playlist of the music mode, if played from one.
+///
+/// # Errors
+///
+/// Returns an error if a tool needed by the media is missing, if the media cannot be opened or if
+/// the playback threads cannot be started.
+fn play(
+    args: &Args,
+    title: String,
+    audio_device: Option<&AudioDevice>,
+    temp_store: &TempStore,
+    controllers: Controllers,
+    track: Option<&PlaylistTrack>,
+) -> Result<(), MyError> {
+    let audio_backend = audio_device.map(AudioDevice::backend);
+    let checked_tools = if args.no_external_tools {
+        Vec::new()
+    } else {
+        required_tools(&title, audio_backend)
+    };
+    check_missing_tools(&title, &checked_tools)?;
+
+    let mut media_data = open_media(title.clone(), !args.no_external_tools, temp_store)?;
+    // URLs of unknown kind are only checked once downloaded, the audio track may need ffmpeg
+    if !args.no_external_tools
+        && media_data.audio_path.is_some()
+        && audio_backend.is_some_and(|backend| backend.needs_ffmpeg())
+        && !checked_tools.contains(&ExternalTool::Ffmpeg)
+    {
+        check_missing_tools(&title, &[ExternalTool::Ffmpeg])?;
+    }
+    args.configure_slideshow(&mut media_data);
+    if let Some(track) = track {
+        media_data
+            .caption
+            .push(music::position_caption(track.index, track.count));
+    }
+    let rate = args.playback_rate(media_data.fps)?;
+    args.configure_buffer(&title, &mut media_data, rate.fps);
+    let audio = args.audio_track(media_data.audio_path.take(), temp_store)?;
+    // Decimated playback runs faster than the audio track, so there is nothing to sync it with
+    let audio = audio.zip(audio_device).filter(|_| args.decimation() == 1);
 
     let num_threads = if audio.is_some() { 4 } else { 3 };
 
-    let (tx_frames, rx_frames) = bounded::<Option<StringInfo>>(1);
+    let (tx_frames, rx_frames) = bounded::<Option<RenderedFrame>>(1);
 
     let (tx_controls, rx_controls) = unbounded::<MediaControl>();
     let (tx_controls_pipeline, rx_controls_pipeline) = unbounded::<PipelineControl>();
@@ -198,37 +1543,77 @@ fn main() -> Result<(), MyError> {
         None
     };
 
-    let mut media_processor = MediaProcessor::new(num_threads);
-    media_processor.launch_broker_thread(rx_controls, tx_controls_pipeline, tx_controls_audio)?;
-
-    media_processor.launch_terminal_thread(
-        args.input.clone(),
-        args.gray,
-        rx_frames,
-        tx_controls.clone(),
-    )?;
+    let stats = match &args.stats_out {
+        Some(path) => StatsLog::create(path)?,
+        None => StatsLog::new(),
+    };
+    if let Some(underruns) = audio.as_ref().and_then(|(_, device)| device.underruns()) {
+        stats.set_audio_underruns(underruns);
+    }
 
-    media_processor.launch_pipeline_thread(
-        &args,
-        media,
-        fps,
-        tx_frames,
-        rx_controls_pipeline,
-        tx_controls,
+    // The VU meter is only shown with the audio it measures
+    let audio_levels = audio.as_ref().filter(|_| args.vu).map(|_| AudioLevels::new());
+    let mut media_processor = MediaProcessor::new(
+        num_threads,
+        stats.clone(),
+        controllers.sleep_timer.clone(),
+        audio_levels,
+    );
+    let shared_controls = controllers
+        .watch
+        .map(|watch| watch.attach(media_processor.playback_clock.clone()));
+    media_processor.launch_broker_thread(
+        rx_controls,
+        tx_controls_pipeline,
+        tx_controls_audio,
+        track.map(|track| track.tx_changes.clone()),
+        shared_controls,
     )?;
+    controllers.attach(Some(tx_controls.clone()));
 
-    if let Some(audio) = &audio {
-        let title = args.input.clone();
-        let file_path = if let Either::Left(audio_track) = audio.as_ref() {
-            let x = audio_track.to_str().unwrap_or(&title);
-            String::from(x)
-        } else {
-            title
-        };
-        media_processor.launch_audio_thread(file_path, rx_controls_audio)?;
+    let launched = media_processor
+        .launch_terminal_thread(
+            title.clone(),
+            args,
+            rate,
+            media_data.video_path.as_deref(),
+            rx_frames,
+            tx_controls.clone(),
+        )
+        .and_then(|_| {
+            media_processor.launch_pipeline_thread(
+                args,
+                media_data,
+                rate,
+                tx_frames,
+                rx_controls_pipeline,
+                tx_controls.clone(),
+            )
+        })
+        .and_then(|_| match &audio {
+            Some((audio, device)) => media_processor.launch_audio_thread(
+                device,
+                String::from(audio.to_str().unwrap_or(&title)),
+                temp_store.dir().to_path_buf(),
+                args.slowmo.unwrap_or(1.0),
+                rx_controls_audio,
+            ),
+            None => Ok(()),
+        });
+    if let Err(err) = launched {
+        media_processor.abort(&tx_controls);
+        controllers.attach(None);
+        return Err(err);
     }
+    drop(tx_controls);
 
     media_processor.join_threads();
+    controllers.attach(None);
+
+    // The frame statistics of the tracks of a playlist are of little interest
+    if !args.no_summary && track.is_none() {
+        print!("{}", stats.summary());
+    }
 
     Ok(())
 }
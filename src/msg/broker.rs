@@ -4,11 +4,29 @@
 //! audio threads. It receives commands from the terminal and forwards them to the pipeline and
 //! audio threads, and receives commands from the pipeline and audio threads and forwards them to
 //! the terminal thread.
+//!
+//! The audio thread reports the changes of the audio (e.g. of the volume) back, which the broker
+//! forwards to the terminal to show them. The broker also tells the terminal when the playback is
+//! paused or resumed, since the commands do not all come from the terminal (e.g. `tplay remote
+//! pause`, a gamepad or the other tplay of a watch-together session).
+//!
+//! In a watch-together session, the play, pause and seek commands are also sent to the session,
+//! which shares them with the other tplay, and the commands of the other tplay are received on
+//! their own channel (see `msg/watch_together.rs`).
+//!
+//! The broker stops on `Exit` (or a track change), after forwarding it to the pipeline and audio
+//! threads, and when every sender of commands is gone. See `msg/shutdown.rs` for the whole
+//! shutdown sequence.
 use crate::{
-    audio::runner::Control as AudioControl, common::errors::MyError,
+    audio::runner::{Control as AudioControl, Feedback as AudioFeedback},
+    common::errors::MyError,
+    msg::watch_together::SharedControls,
+    pipeline::luma::LumaFormula,
     pipeline::runner::Control as PipelineControl,
+    pipeline::scenes::SceneNavigation,
+    pipeline::transform::TransformChange,
 };
-use crossbeam_channel::{select, Receiver, Sender};
+use crossbeam_channel::{never, select, Receiver, Sender};
 
 /// Enum representing the different control commands that can be sent to the Runner.
 #[derive(Debug, PartialEq)]
@@ -21,6 +39,8 @@ pub enum Control {
     Exit,
     /// Command to toggle between mute and unmute.
     MuteUnmute,
+    /// Command to raise (or lower, if negative) the volume by the given step, from 0 to 1.
+    ChangeVolume(f64),
     /// Command to set the character map used by the image pipeline.
     /// The argument represents the index of the desired character map.
     SetCharMap(u32),
@@ -30,10 +50,50 @@ pub enum Control {
     /// Command to set grayscale mode. We always extract rgb+grayscale from image, the terminal is
     /// responsible for the correct render mode.
     SetGrayscale(bool),
+    /// Command to move playback to the given position, in seconds from the start of the media.
+    Seek(f64),
+    /// Command to move playback forward (or backward, if negative) by the given number of
+    /// seconds. The pipeline, which knows the current position, turns it into a `Seek`.
+    SeekBy(f64),
+    /// Command to render the current frame again, e.g. after the display changed colors.
+    Redraw,
+    /// Command to switch between the character map and braille dots.
+    ToggleBraille,
+    /// Command to switch between coloring the glyphs and painting the background of the cells.
+    ToggleBackground,
+    /// Command to switch to the next dithering method of the character map.
+    CycleDither,
+    /// Command to convert the colors to gray levels with the given formula.
+    SetLuma(LumaFormula),
+    /// Command to open, close or navigate the scene index.
+    SceneIndex(SceneNavigation),
+    /// Command to turn the viewport of 360° media right and up by the given numbers of degrees
+    /// (left and down if negative).
+    Pan(f64, f64),
+    /// Command to flip the frames or turn them a quarter turn.
+    Transform(TransformChange),
+    /// Command to replace the glyphs of a character map while it is edited, and render with it.
+    /// The arguments represent the index of the character map and its new glyphs.
+    EditCharMap(u32, Vec<String>),
+    /// Command to render with the glyphs typed on the command line (`:charmap`), from darkest to
+    /// brightest, instead of the character map chosen with the keys.
+    SetCustomCharMap(String),
+    /// Command to stop the playback and move by the given number of tracks in the playlist
+    /// (1 for the next track, -1 for the previous one).
+    ChangeTrack(i32),
 }
 
 type BrokerControl = Control;
 
+/// Enum representing the changes reported by the broker to the terminal, which shows them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feedback {
+    /// A change of the audio, reported by the audio thread.
+    Audio(AudioFeedback),
+    /// The playback was paused (`true`) or resumed (`false`), by whichever sent the command.
+    Paused(bool),
+}
+
 /// The `MessageBroker` struct handles the communication between the terminal, pipeline and audio
 /// threads.
 ///
@@ -44,6 +104,13 @@ pub struct MessageBroker {
     rx_channel_terminal: Receiver<BrokerControl>,
     tx_channel_pipeline: Option<Sender<PipelineControl>>,
     tx_channel_audio: Option<Sender<AudioControl>>,
+    tx_track_changes: Option<Sender<i32>>,
+    rx_feedback_audio: Receiver<AudioFeedback>,
+    tx_feedback_terminal: Option<Sender<Feedback>>,
+    tx_shared: Option<Sender<BrokerControl>>,
+    rx_peer: Receiver<BrokerControl>,
+    /// Whether the playback is paused, toggled by each `PauseContinue`.
+    paused: bool,
 }
 
 impl MessageBroker {
@@ -56,9 +123,52 @@ impl MessageBroker {
             rx_channel_terminal,
             tx_channel_pipeline,
             tx_channel_audio,
+            tx_track_changes: None,
+            rx_feedback_audio: never(),
+            tx_feedback_terminal: None,
+            tx_shared: None,
+            rx_peer: never(),
+            paused: false,
         }
     }
 
+    /// Forwards the changes of the audio reported by the audio thread (e.g. of the volume) to the
+    /// terminal, along with the pauses and resumes of the playback.
+    ///
+    /// # Arguments
+    ///
+    /// * `rx_feedback_audio` - The channel of the changes reported by the audio thread.
+    /// * `tx_feedback_terminal` - The channel of the changes shown by the terminal.
+    pub fn set_feedback(
+        &mut self,
+        rx_feedback_audio: Receiver<AudioFeedback>,
+        tx_feedback_terminal: Sender<Feedback>,
+    ) {
+        self.rx_feedback_audio = rx_feedback_audio;
+        self.tx_feedback_terminal = Some(tx_feedback_terminal);
+    }
+
+    /// Forwards the track changes (`Control::ChangeTrack`) to the player of the playlist, which
+    /// starts the requested track once the playback is stopped.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_track_changes` - The channel receiving the number of tracks to move by.
+    pub fn set_track_changes(&mut self, tx_track_changes: Sender<i32>) {
+        self.tx_track_changes = Some(tx_track_changes);
+    }
+
+    /// Shares the play, pause and seek commands with the other tplay of a watch-together session,
+    /// and applies its commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared` - The channels to the watch-together session.
+    pub fn set_shared_controls(&mut self, shared: SharedControls) {
+        self.tx_shared = Some(shared.tx_local);
+        self.rx_peer = shared.rx_peer;
+    }
+
     /// The main function responsible for handling the communication between the terminal, pipeline
     /// and audio threads.
     ///
@@ -66,6 +176,9 @@ impl MessageBroker {
     /// and receives commands from the pipeline and audio threads and forwards them to the terminal
     /// thread.
     ///
+    /// It returns once the playback is stopped, closing the channels of the pipeline and audio
+    /// threads.
+    ///
     /// # Arguments
     ///
     /// * `barrier` - A barrier used to synchronize the start of the audio playback.
@@ -75,63 +188,282 @@ impl MessageBroker {
     /// * `Result<(), MyError>` - A result indicating whether the function succeeded or failed.
     pub fn run(&mut self, barrier: std::sync::Arc<std::sync::Barrier>) -> Result<(), MyError> {
         barrier.wait();
-        let mut running = true;
-        while running || !self.rx_channel_terminal.is_empty() {
+        loop {
             select! {
                 recv(self.rx_channel_terminal) -> msg => {
                     match msg {
-                        Ok(BrokerControl::Exit) => {
-                            running = false;
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::Exit);
-                            }
-                            if let Some(tx) = &self.tx_channel_audio{
-                                let _ = tx.send(AudioControl::Exit);
-                            }
-                        }
-                        Ok(BrokerControl::PauseContinue) => {
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::PauseContinue);
-                            }
-                            if let Some(tx) = &self.tx_channel_audio {
-                                let _ = tx.send(AudioControl::PauseContinue);
-                            }
-                        }
-                        Ok(BrokerControl::Replay) => {
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::Replay);
-                            }
-                            if let Some(tx) = &self.tx_channel_audio {
-                                let _ = tx.send(AudioControl::Replay);
-                            }
-                        }
-                        Ok(BrokerControl::Resize(width, height)) => {
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::Resize(width, height));
-                            }
-                        }
-                        Ok(BrokerControl::SetCharMap(char_map)) => {
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::SetCharMap(char_map));
+                        Ok(control) => {
+                            self.share(&control);
+                            if self.forward(control) {
+                                return Ok(());
                             }
                         }
-                        Ok(BrokerControl::SetGrayscale(grayscale)) => {
-                            if let Some(tx) = &self.tx_channel_pipeline {
-                                let _ = tx.send(PipelineControl::SetGrayscale(grayscale));
-                            }
+                        Err(_) => {
+                            // Nothing can send commands anymore, e.g. the display failed
+                            self.stop();
+                            return Ok(());
                         }
-                        Ok(BrokerControl::MuteUnmute) => {
-                            if let Some(tx) = &self.tx_channel_audio {
-                                let _ = tx.send(AudioControl::MuteUnmute);
+                    }
+                }
+                recv(self.rx_peer) -> msg => {
+                    match msg {
+                        // Not shared back to the other tplay
+                        Ok(control) => {
+                            if self.forward(control) {
+                                return Ok(());
                             }
                         }
-                        Err(_) => {
-                            // eprintln!("Error: {}", e);
-                        }
+                        Err(_) => self.rx_peer = never(),
+                    }
+                }
+                recv(self.rx_feedback_audio) -> msg => {
+                    match msg {
+                        Ok(feedback) => self.send_feedback(Feedback::Audio(feedback)),
+                        // The audio thread is gone, e.g. it failed
+                        Err(_) => self.rx_feedback_audio = never(),
                     }
                 }
             }
         }
-        Ok(())
+    }
+
+    /// Forwards a command to the pipeline and audio threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The command.
+    ///
+    /// # Returns
+    ///
+    /// Whether the playback was stopped (`Exit` or a track change).
+    fn forward(&mut self, control: BrokerControl) -> bool {
+        match control {
+            control @ (BrokerControl::Exit | BrokerControl::ChangeTrack(_)) => {
+                if let (BrokerControl::ChangeTrack(step), Some(tx)) =
+                    (control, &self.tx_track_changes)
+                {
+                    let _ = tx.send(step);
+                }
+                self.stop();
+                return true;
+            }
+            BrokerControl::PauseContinue => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::PauseContinue);
+                }
+                if let Some(tx) = &self.tx_channel_audio {
+                    let _ = tx.send(AudioControl::PauseContinue);
+                }
+                self.paused = !self.paused;
+                self.send_feedback(Feedback::Paused(self.paused));
+            }
+            BrokerControl::Replay => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Replay);
+                }
+                if let Some(tx) = &self.tx_channel_audio {
+                    let _ = tx.send(AudioControl::Replay);
+                }
+            }
+            BrokerControl::Resize(width, height) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Resize(width, height));
+                }
+            }
+            BrokerControl::SetCharMap(char_map) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SetCharMap(char_map));
+                }
+            }
+            BrokerControl::SetGrayscale(grayscale) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SetGrayscale(grayscale));
+                }
+            }
+            BrokerControl::Seek(seconds) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Seek(seconds));
+                }
+                if let Some(tx) = &self.tx_channel_audio {
+                    let _ = tx.send(AudioControl::Seek(seconds));
+                }
+            }
+            BrokerControl::SeekBy(seconds) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SeekBy(seconds));
+                }
+            }
+            BrokerControl::Redraw => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Redraw);
+                }
+            }
+            BrokerControl::ToggleBraille => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::ToggleBraille);
+                }
+            }
+            BrokerControl::ToggleBackground => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::ToggleBackground);
+                }
+            }
+            BrokerControl::CycleDither => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::CycleDither);
+                }
+            }
+            BrokerControl::SetLuma(formula) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SetLuma(formula));
+                }
+            }
+            BrokerControl::SceneIndex(navigation) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SceneIndex(navigation));
+                }
+            }
+            BrokerControl::Pan(yaw, pitch) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Pan(yaw, pitch));
+                }
+            }
+            BrokerControl::Transform(change) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::Transform(change));
+                }
+            }
+            BrokerControl::EditCharMap(char_map, glyphs) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::EditCharMap(char_map, glyphs));
+                }
+            }
+            BrokerControl::SetCustomCharMap(chars) => {
+                if let Some(tx) = &self.tx_channel_pipeline {
+                    let _ = tx.send(PipelineControl::SetCustomCharMap(chars));
+                }
+            }
+            BrokerControl::MuteUnmute => {
+                self.send_audio(AudioControl::MuteUnmute);
+            }
+            BrokerControl::ChangeVolume(step) => {
+                self.send_audio(AudioControl::ChangeVolume(step));
+            }
+        }
+        false
+    }
+
+    /// Sends the play, pause and seek commands to the watch-together session, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The command received.
+    fn share(&self, control: &BrokerControl) {
+        let shared = match control {
+            BrokerControl::PauseContinue => BrokerControl::PauseContinue,
+            BrokerControl::Seek(seconds) => BrokerControl::Seek(*seconds),
+            _ => return,
+        };
+        if let Some(tx) = &self.tx_shared {
+            let _ = tx.send(shared);
+        }
+    }
+
+    /// Sends a volume command to the audio thread, or tells the terminal that there is no audio
+    /// to change, so that the key pressed is not ignored silently.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The command.
+    fn send_audio(&self, control: AudioControl) {
+        match &self.tx_channel_audio {
+            Some(tx) => {
+                let _ = tx.send(control);
+            }
+            None => self.send_feedback(Feedback::Audio(AudioFeedback::Unavailable)),
+        }
+    }
+
+    /// Sends a change to the terminal, if it shows them.
+    ///
+    /// # Arguments
+    ///
+    /// * `feedback` - The change.
+    fn send_feedback(&self, feedback: Feedback) {
+        if let Some(tx) = &self.tx_feedback_terminal {
+            let _ = tx.send(feedback);
+        }
+    }
+
+    /// Stops the pipeline and audio threads: sends them `Exit` and closes their channels, which
+    /// they also take as `Exit`.
+    fn stop(&mut self) {
+        if let Some(tx) = self.tx_channel_pipeline.take() {
+            let _ = tx.send(PipelineControl::Exit);
+        }
+        if let Some(tx) = self.tx_channel_audio.take() {
+            let _ = tx.send(AudioControl::Exit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    #[test]
+    fn test_broker_shutdown() {
+        let start = |control: Option<BrokerControl>| {
+            let (tx_terminal, rx_terminal) = unbounded();
+            let (tx_pipeline, rx_pipeline) = unbounded();
+            let (tx_audio, rx_audio) = unbounded();
+            let mut broker = MessageBroker::new(rx_terminal, Some(tx_pipeline), Some(tx_audio));
+            let handle = thread::spawn(move || broker.run(Arc::new(Barrier::new(1))));
+            match control {
+                Some(control) => tx_terminal.send(control).unwrap(),
+                None => drop(tx_terminal),
+            }
+            handle.join().unwrap().unwrap();
+            (rx_pipeline, rx_audio)
+        };
+
+        for control in [Some(BrokerControl::Exit), None] {
+            let (rx_pipeline, rx_audio) = start(control);
+            assert_eq!(rx_pipeline.recv(), Ok(PipelineControl::Exit));
+            assert_eq!(rx_audio.recv(), Ok(AudioControl::Exit));
+            // The channels are closed once the broker is done
+            assert!(rx_pipeline.recv().is_err());
+            assert!(rx_audio.recv().is_err());
+        }
+    }
+
+    #[test]
+    fn test_broker_pause_feedback() {
+        let (tx_terminal, rx_terminal) = unbounded();
+        let (tx_peer, rx_peer) = unbounded();
+        let (tx_local, _rx_local) = unbounded();
+        let (tx_feedback, rx_feedback) = unbounded();
+        let mut broker = MessageBroker::new(rx_terminal, None, None);
+        broker.set_feedback(never(), tx_feedback);
+        broker.set_shared_controls(SharedControls { tx_local, rx_peer });
+        let handle = thread::spawn(move || broker.run(Arc::new(Barrier::new(1))));
+
+        // The pauses of the other tplay reach the terminal as well as its own
+        tx_peer.send(BrokerControl::PauseContinue).unwrap();
+        assert_eq!(rx_feedback.recv(), Ok(Feedback::Paused(true)));
+        tx_terminal.send(BrokerControl::PauseContinue).unwrap();
+        assert_eq!(rx_feedback.recv(), Ok(Feedback::Paused(false)));
+        tx_terminal.send(BrokerControl::ChangeVolume(0.1)).unwrap();
+        assert_eq!(
+            rx_feedback.recv(),
+            Ok(Feedback::Audio(AudioFeedback::Unavailable))
+        );
+        drop(tx_terminal);
+        handle.join().unwrap().unwrap();
     }
 }
@@ -0,0 +1,121 @@
+//! Controls the playback with a game controller (`--gamepad`), e.g. when tplay drives a projector
+//! or a big screen across the room.
+//!
+//! The buttons of the controllers are read on a background thread, and mapped to commands sent
+//! to the message broker like the keys pressed in the terminal: A pauses or resumes, the triggers
+//! seek backward and forward, the bumpers seek by larger steps and Y mutes. Controllers are only
+//! read in builds with the `gamepad` feature.
+use crate::{common::errors::*, msg::broker::Control as MediaControl};
+use crossbeam_channel::Sender;
+use std::sync::{Arc, Mutex};
+
+/// Seconds skipped by the triggers.
+#[cfg(feature = "gamepad")]
+const SEEK_STEP: f64 = 10.0;
+/// Seconds skipped by the bumpers.
+#[cfg(feature = "gamepad")]
+const SEEK_STEP_LONG: f64 = 60.0;
+
+/// The game controllers, read on a background thread.
+pub struct GamepadInput {
+    /// The channel to the message broker of the current playback, if any.
+    controls: Arc<Mutex<Option<Sender<MediaControl>>>>,
+}
+
+impl GamepadInput {
+    /// Starts reading the game controllers. Controllers connected later are picked up as well.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the running input.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the controllers cannot be read on this platform.
+    #[cfg(feature = "gamepad")]
+    pub fn start() -> Result<Self, MyError> {
+        use gilrs::{Event, EventType, Gilrs};
+
+        let controls: Arc<Mutex<Option<Sender<MediaControl>>>> = Arc::default();
+        let thread_controls = Arc::clone(&controls);
+        let (tx_started, rx_started) = crossbeam_channel::bounded(1);
+        // The controllers are read on the thread that opened them
+        std::thread::spawn(move || {
+            let mut gilrs = match Gilrs::new() {
+                Ok(gilrs) => {
+                    let _ = tx_started.send(Ok(()));
+                    gilrs
+                }
+                Err(err) => {
+                    let _ = tx_started.send(Err(err.to_string()));
+                    return;
+                }
+            };
+            loop {
+                let Some(Event {
+                    event: EventType::ButtonPressed(button, _),
+                    ..
+                }) = gilrs.next_event_blocking(None)
+                else {
+                    continue;
+                };
+                let Some(control) = control_for(button) else {
+                    continue;
+                };
+                if let Some(tx) = thread_controls
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .as_ref()
+                {
+                    let _ = tx.send(control);
+                }
+            }
+        });
+        match rx_started.recv() {
+            Ok(Ok(())) => Ok(Self { controls }),
+            Ok(Err(reason)) => Err(MyError::Application(format!(
+                "{ERROR_GAMEPAD_UNAVAILABLE}: {reason}"
+            ))),
+            Err(_) => Err(MyError::Application(ERROR_GAMEPAD_UNAVAILABLE.to_string())),
+        }
+    }
+
+    /// Starts reading the game controllers, see the version with the `gamepad` feature.
+    #[cfg(not(feature = "gamepad"))]
+    pub fn start() -> Result<Self, MyError> {
+        Err(MyError::Application(ERROR_GAMEPAD_UNSUPPORTED.to_string()))
+    }
+
+    /// Sets the playback the buttons act on.
+    ///
+    /// # Arguments
+    ///
+    /// * `controls` - The channel to the message broker of the playback, or `None` once it is
+    ///   over.
+    pub fn attach(&self, controls: Option<Sender<MediaControl>>) {
+        *self
+            .controls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = controls;
+    }
+}
+
+/// Returns the command mapped to a button of a controller, if any.
+///
+/// # Arguments
+///
+/// * `button` - The pressed button.
+#[cfg(feature = "gamepad")]
+fn control_for(button: gilrs::Button) -> Option<MediaControl> {
+    use gilrs::Button;
+
+    match button {
+        Button::South => Some(MediaControl::PauseContinue),
+        Button::North => Some(MediaControl::MuteUnmute),
+        Button::LeftTrigger2 => Some(MediaControl::SeekBy(-SEEK_STEP)),
+        Button::RightTrigger2 => Some(MediaControl::SeekBy(SEEK_STEP)),
+        Button::LeftTrigger => Some(MediaControl::SeekBy(-SEEK_STEP_LONG)),
+        Button::RightTrigger => Some(MediaControl::SeekBy(SEEK_STEP_LONG)),
+        _ => None,
+    }
+}
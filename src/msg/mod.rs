@@ -1,2 +1,14 @@
 //! Message broker module. See `msg/broker.rs` for more details.
+//!
+//! It consists of the following sub-modules:
+//! - `broker`: Forwards the commands between the terminal, pipeline and audio threads.
+//! - `gamepad`: Receives commands from game controllers (`--gamepad`).
+//! - `remote`: Receives commands from other terminals on a control socket (`tplay remote`).
+//! - `shutdown`: Joins the playback threads once the playback is stopped.
+//! - `watch_together`: Shares the play, pause and seek commands with another tplay over TCP
+//!   (`--watch-host`, `--watch-join`).
 pub mod broker;
+pub mod gamepad;
+pub mod remote;
+pub mod shutdown;
+pub mod watch_together;
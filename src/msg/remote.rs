@@ -0,0 +1,449 @@
+//! Remote control of a running tplay from another terminal (`tplay remote <command>`), e.g. from
+//! the keybindings of a window manager.
+//!
+//! While playing, tplay listens on a control socket: a Unix domain socket in the runtime
+//! directory of the user (`$XDG_RUNTIME_DIR`, or the temporary directory). Clients send one
+//! command per line, as text (`pause`, `seek 90`, `load <file/url>`...), and get `ok` or
+//! `error: <reason>` back for each of them. The commands are forwarded to the message broker like
+//! the keys pressed in the terminal. Loading a file stops the current media: the new one is put
+//! at the front of the play queue, which the main loop plays next. Added files go to the back of
+//! the queue, played one after the other (see `tplay --daemon`, waiting for media to play).
+use crate::{common::errors::*, msg::broker::Control as MediaControl};
+use crossbeam_channel::Sender;
+use std::{
+    collections::VecDeque,
+    env, fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// How long a client may stay connected without sending a command, before it is disconnected.
+#[cfg(unix)]
+const CLIENT_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// How long `send` waits for the running tplay to reply.
+#[cfg(unix)]
+const REPLY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A command sent to the running tplay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    /// Toggles between pause and playback.
+    Pause,
+    /// Toggles between mute and unmute.
+    Mute,
+    /// Moves playback to the given position, in seconds from the start of the media.
+    Seek(f64),
+    /// Stops the current media and plays the given file or URL.
+    Load(String),
+    /// Adds the given file or URL to the play queue.
+    Add(String),
+    /// Stops the playback, and exits.
+    Quit,
+}
+
+impl FromStr for RemoteCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        match (name.to_ascii_lowercase().as_str(), argument) {
+            ("pause", "") => Ok(RemoteCommand::Pause),
+            ("mute", "") => Ok(RemoteCommand::Mute),
+            ("quit", "") => Ok(RemoteCommand::Quit),
+            ("seek", seconds) => seconds
+                .parse()
+                .ok()
+                .filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)
+                .map(RemoteCommand::Seek)
+                .ok_or_else(|| format!("`{seconds}` is not a position in seconds")),
+            ("load" | "add", "") => Err(format!("{name} needs a file or URL")),
+            ("load", input) => Ok(RemoteCommand::Load(input.to_string())),
+            ("add", input) => Ok(RemoteCommand::Add(input.to_string())),
+            _ => Err(format!(
+                "`{line}` is not a command (pause, mute, seek <seconds>, load <file/url>, add <file/url>, quit)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RemoteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteCommand::Pause => write!(f, "pause"),
+            RemoteCommand::Mute => write!(f, "mute"),
+            RemoteCommand::Seek(seconds) => write!(f, "seek {seconds}"),
+            RemoteCommand::Load(input) => write!(f, "load {input}"),
+            RemoteCommand::Add(input) => write!(f, "add {input}"),
+            RemoteCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+/// The state of the play queue.
+#[derive(Debug, Default)]
+struct QueueState {
+    /// The files or URLs to play.
+    inputs: VecDeque<String>,
+    /// Whether the queue was closed by `quit`: nothing is played anymore.
+    closed: bool,
+}
+
+/// The media waiting to be played after the current one, in order.
+#[derive(Debug, Default)]
+pub struct PlayQueue {
+    /// The media, and whether the queue is closed.
+    state: Mutex<QueueState>,
+    /// Wakes up the threads waiting for media.
+    changed: Condvar,
+}
+
+impl PlayQueue {
+    /// Puts a media at the back of the queue, to be played after the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The file or URL to play.
+    pub fn push_back(&self, input: String) {
+        self.lock().inputs.push_back(input);
+        self.changed.notify_all();
+    }
+
+    /// Puts a media at the front of the queue, to be played next.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The file or URL to play.
+    pub fn push_front(&self, input: String) {
+        self.lock().inputs.push_front(input);
+        self.changed.notify_all();
+    }
+
+    /// Removes and returns the next media to play, if any.
+    pub fn pop(&self) -> Option<String> {
+        self.lock().inputs.pop_front()
+    }
+
+    /// Removes and returns the next media to play, waiting for one to be added if the queue is
+    /// empty.
+    ///
+    /// # Returns
+    ///
+    /// The next media, or `None` once the queue is closed.
+    pub fn wait_pop(&self) -> Option<String> {
+        let mut state = self.lock();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(input) = state.inputs.pop_front() {
+                return Some(input);
+            }
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Removes all the media from the queue, and stops waiting for new ones.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.inputs.clear();
+        state.closed = true;
+        self.changed.notify_all();
+    }
+
+    /// Locks the queue, even if a thread panicked while holding it.
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// What the commands received on the control socket act on.
+#[derive(Default)]
+struct Targets {
+    /// The channel to the message broker of the current playback, if any.
+    controls: Mutex<Option<Sender<MediaControl>>>,
+    /// The media to play after the current one.
+    queue: PlayQueue,
+}
+
+impl Targets {
+    /// Applies a command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command received.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` telling the client whether the command was applied, or why not.
+    fn apply(&self, command: RemoteCommand) -> Result<(), String> {
+        let controls = self
+            .controls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let send = |control| {
+            controls
+                .as_ref()
+                .ok_or("nothing is playing")?
+                .send(control)
+                .map_err(|_| "the playback is over")
+        };
+        match command {
+            RemoteCommand::Pause => send(MediaControl::PauseContinue)?,
+            RemoteCommand::Mute => send(MediaControl::MuteUnmute)?,
+            RemoteCommand::Seek(seconds) => send(MediaControl::Seek(seconds))?,
+            RemoteCommand::Load(input) => {
+                self.queue.push_front(input);
+                // Nothing may be playing yet, the queue is played in any case
+                let _ = send(MediaControl::Exit);
+            }
+            RemoteCommand::Add(input) => self.queue.push_back(input),
+            RemoteCommand::Quit => {
+                self.queue.close();
+                // Nothing may be playing, e.g. while waiting for media to play
+                let _ = send(MediaControl::Exit);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of the control socket of the current user.
+pub fn socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("tplay-control.sock"),
+        None => env::temp_dir().join(format!("tplay-control-{}.sock", user_id())),
+    }
+}
+
+/// Returns the identifier of the current user, keeping the sockets of several users apart in
+/// the shared temporary directory.
+#[cfg(unix)]
+fn user_id() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// Returns the identifier of the current user, see the Unix version.
+#[cfg(not(unix))]
+fn user_id() -> u32 {
+    0
+}
+
+/// The control socket of the running tplay, listening on a background thread.
+pub struct ControlServer {
+    /// The path of the socket, removed when the server is dropped.
+    path: PathBuf,
+    /// What the received commands act on.
+    targets: Arc<Targets>,
+}
+
+impl ControlServer {
+    /// Starts listening on the control socket.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the socket, see `socket_path`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the running server.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if another tplay already listens on the socket, if the socket cannot be
+    /// created, or if the platform is not supported.
+    #[cfg(unix)]
+    pub fn start(path: &Path) -> Result<Self, MyError> {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            os::unix::{
+                fs::PermissionsExt,
+                net::{UnixListener, UnixStream},
+            },
+        };
+
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(MyError::Application(format!(
+                    "{ERROR_REMOTE_IN_USE}: {}",
+                    path.display()
+                )));
+            }
+            // Left behind by a tplay that was killed
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+        let targets = Arc::new(Targets::default());
+        let thread_targets = Arc::clone(&targets);
+        std::thread::spawn(move || {
+            // Each client is served on its own thread, so that an idle one does not hold up the
+            // others
+            for stream in listener.incoming().flatten() {
+                let targets = Arc::clone(&thread_targets);
+                std::thread::spawn(move || {
+                    if stream.set_read_timeout(Some(CLIENT_IDLE_TIMEOUT)).is_err() {
+                        return;
+                    }
+                    let Ok(reader) = stream.try_clone().map(BufReader::new) else {
+                        return;
+                    };
+                    let mut writer = stream;
+                    for line in reader.lines().map_while(Result::ok) {
+                        let reply = match line.parse().and_then(|cmd| targets.apply(cmd)) {
+                            Ok(()) => "ok".to_string(),
+                            Err(reason) => format!("error: {reason}"),
+                        };
+                        if writeln!(writer, "{reply}").is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            targets,
+        })
+    }
+
+    /// Starts listening on the control socket, see the Unix version.
+    #[cfg(not(unix))]
+    pub fn start(_path: &Path) -> Result<Self, MyError> {
+        Err(MyError::Application(ERROR_REMOTE_UNSUPPORTED.to_string()))
+    }
+
+    /// Sets the playback the commands act on.
+    ///
+    /// # Arguments
+    ///
+    /// * `controls` - The channel to the message broker of the playback, or `None` once it is
+    ///   over.
+    pub fn attach(&self, controls: Option<Sender<MediaControl>>) {
+        *self
+            .targets
+            .controls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = controls;
+    }
+
+    /// Returns the media to play after the current one.
+    pub fn queue(&self) -> &PlayQueue {
+        &self.targets.queue
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Sends a command to the running tplay.
+///
+/// # Arguments
+///
+/// * `path` - The path of the control socket, see `socket_path`.
+/// * `command` - The command.
+///
+/// # Errors
+///
+/// Returns an error if no tplay is running, if it does not reply in time, or if it rejected the
+/// command.
+#[cfg(unix)]
+pub fn send(path: &Path, command: &RemoteCommand) -> Result<(), MyError> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let mut stream = UnixStream::connect(path).map_err(|err| {
+        MyError::Application(format!(
+            "{ERROR_REMOTE_NOT_RUNNING} ({}): {err}",
+            path.display()
+        ))
+    })?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    writeln!(stream, "{command}")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim().strip_prefix("error: ") {
+        Some(reason) => Err(MyError::Application(format!(
+            "{ERROR_REMOTE_COMMAND}: {reason}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Sends a command to the running tplay, see the Unix version.
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _command: &RemoteCommand) -> Result<(), MyError> {
+    Err(MyError::Application(ERROR_REMOTE_UNSUPPORTED.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+
+    #[test]
+    fn test_parse_remote_command() {
+        assert_eq!("pause".parse(), Ok(RemoteCommand::Pause));
+        assert_eq!("seek 90.5".parse(), Ok(RemoteCommand::Seek(90.5)));
+        assert_eq!(
+            "load /tmp/a b.mp4".parse(),
+            Ok(RemoteCommand::Load("/tmp/a b.mp4".to_string()))
+        );
+        assert!("seek -3".parse::<RemoteCommand>().is_err());
+        assert!("load".parse::<RemoteCommand>().is_err());
+        assert!("rewind".parse::<RemoteCommand>().is_err());
+
+        let command = RemoteCommand::Seek(12.0);
+        assert_eq!(command.to_string().parse(), Ok(command));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_control_server() {
+        let dir = env::temp_dir().join(format!("tplay-remote-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+        let server = ControlServer::start(&path).unwrap();
+        assert!(ControlServer::start(&path).is_err());
+
+        // A client that stays connected without sending anything does not hold up the others
+        let _idle = std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+        // Nothing is playing yet
+        assert!(send(&path, &RemoteCommand::Pause).is_err());
+        let (tx, rx) = unbounded();
+        server.attach(Some(tx));
+        send(&path, &RemoteCommand::Seek(3.0)).unwrap();
+        send(&path, &RemoteCommand::Load("next.mp4".to_string())).unwrap();
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [MediaControl::Seek(3.0), MediaControl::Exit]
+        );
+        assert_eq!(server.queue().pop(), Some("next.mp4".to_string()));
+
+        // Added media are played in order, until the queue is closed
+        send(&path, &RemoteCommand::Add("a.mp4".to_string())).unwrap();
+        send(&path, &RemoteCommand::Add("b.mp4".to_string())).unwrap();
+        assert_eq!(server.queue().wait_pop(), Some("a.mp4".to_string()));
+        send(&path, &RemoteCommand::Quit).unwrap();
+        assert_eq!(server.queue().wait_pop(), None);
+
+        drop(server);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,123 @@
+//! The shutdown sequence of the playback threads.
+//!
+//! The playback is stopped from the display, whose `Exit` (or any controller's) reaches the
+//! broker. The broker forwards `Exit` to the pipeline and audio threads and returns, closing their
+//! control channels, so they stop even if the message is lost. The pipeline also stops when the
+//! display is gone, and the broker when every controller is gone. The threads are then joined in
+//! that order (display, broker, pipeline, audio), within a timeout: a thread still blocked past it
+//! is left behind, rather than hanging the program.
+use crate::common::errors::*;
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long the threads have to stop once the display has exited.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the threads are checked while waiting for them to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A running playback thread.
+pub struct PlaybackThread {
+    /// The name of the thread, for the error messages.
+    pub name: &'static str,
+    /// The handle of the thread.
+    pub handle: JoinHandle<Result<(), MyError>>,
+}
+
+impl PlaybackThread {
+    /// Constructs a `PlaybackThread`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the thread.
+    /// * `handle` - The handle of the thread.
+    pub fn new(name: &'static str, handle: JoinHandle<Result<(), MyError>>) -> Self {
+        Self { name, handle }
+    }
+
+    /// Waits for the thread to stop.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - The time after which the thread is left running, or `None` to wait for as
+    ///   long as it takes.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the thread stopped with, or an error if it panicked or did not stop by
+    /// the deadline.
+    pub fn join(self, deadline: Option<Instant>) -> Result<(), MyError> {
+        if let Some(deadline) = deadline {
+            while !self.handle.is_finished() {
+                if Instant::now() >= deadline {
+                    // Dropping the handle detaches the thread
+                    return Err(MyError::Application(format!(
+                        "{ERROR_THREAD_SHUTDOWN}: {}",
+                        self.name
+                    )));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+        self.handle.join().unwrap_or_else(|_| {
+            Err(MyError::Application(format!(
+                "{ERROR_THREAD_PANICKED}: {}",
+                self.name
+            )))
+        })
+    }
+}
+
+/// Joins threads in order, all within the same timeout.
+///
+/// # Arguments
+///
+/// * `threads` - The threads, in the order they stop.
+/// * `timeout` - How long the threads have to stop, all together.
+///
+/// # Returns
+///
+/// The errors the threads stopped with, and those of the threads that panicked or were left
+/// running.
+pub fn join_in_order(threads: Vec<PlaybackThread>, timeout: Duration) -> Vec<MyError> {
+    let deadline = Instant::now() + timeout;
+    threads
+        .into_iter()
+        .filter_map(|thread| thread.join(Some(deadline)).err())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::bounded;
+
+    #[test]
+    fn test_join_in_order() {
+        let (tx, rx) = bounded::<()>(0);
+        let threads = vec![
+            PlaybackThread::new("done", thread::spawn(|| Ok(()))),
+            PlaybackThread::new(
+                "failed",
+                thread::spawn(|| Err(MyError::Application("failed".to_string()))),
+            ),
+            // Blocked on a channel nobody sends to
+            PlaybackThread::new(
+                "blocked",
+                thread::spawn(move || {
+                    let _ = rx.recv();
+                    Ok(())
+                }),
+            ),
+        ];
+        let start = Instant::now();
+        let errors = join_in_order(threads, Duration::from_millis(50));
+        assert!(start.elapsed() < SHUTDOWN_TIMEOUT);
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("failed"));
+        assert!(errors[1].contains(ERROR_THREAD_SHUTDOWN));
+        drop(tx);
+    }
+}
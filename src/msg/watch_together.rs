@@ -0,0 +1,346 @@
+//! Watch-together sessions (experimental): two tplay playing the same file connect over TCP, one
+//! hosting (`--watch-host <port>`) and the other joining it (`--watch-join <host:port>`), and
+//! share their play, pause and seek commands, so that two people watch in sync in their
+//! terminals.
+//!
+//! The commands are sent as text lines, with the position they apply at: `play 12.5`,
+//! `pause 12.5`, `seek 90`. Pauses are shared as the state they lead to rather than as toggles,
+//! and a viewer further than `SYNC_TOLERANCE` from the position received seeks to it. The host
+//! sends its state when the other tplay joins, so that it catches up. The commands received go to
+//! the message broker on their own channel, so that they are not shared back.
+//!
+//! There is no authentication: anyone reaching the port controls the playback, so only host on
+//! trusted networks.
+use crate::{
+    common::{clock::PlaybackClock, errors::*},
+    msg::broker::Control as MediaControl,
+};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+/// How far from the position of the other tplay the playback can be, in seconds, before seeking
+/// to it.
+const SYNC_TOLERANCE: f64 = 0.5;
+
+/// A command shared with the other tplay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncEvent {
+    /// The playback plays from the given position, if known.
+    Play(Option<f64>),
+    /// The playback is paused at the given position, if known.
+    Pause(Option<f64>),
+    /// The playback moved to the given position.
+    Seek(f64),
+}
+
+impl FromStr for SyncEvent {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let position = match argument.trim() {
+            "" => None,
+            seconds => Some(
+                seconds
+                    .parse()
+                    .ok()
+                    .filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)
+                    .ok_or_else(|| format!("`{seconds}` is not a position in seconds"))?,
+            ),
+        };
+        match (name, position) {
+            ("play", position) => Ok(SyncEvent::Play(position)),
+            ("pause", position) => Ok(SyncEvent::Pause(position)),
+            ("seek", Some(seconds)) => Ok(SyncEvent::Seek(seconds)),
+            _ => Err(format!("`{line}` is not a watch-together command")),
+        }
+    }
+}
+
+impl fmt::Display for SyncEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, position) = match self {
+            SyncEvent::Play(position) => ("play", *position),
+            SyncEvent::Pause(position) => ("pause", *position),
+            SyncEvent::Seek(seconds) => ("seek", Some(*seconds)),
+        };
+        match position {
+            Some(seconds) => write!(f, "{name} {seconds}"),
+            None => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The state of the session, shared by its threads.
+#[derive(Default)]
+struct Session {
+    /// The connection to the other tplay, if it is connected.
+    peer: Option<TcpStream>,
+    /// The channel to the message broker of the current playback, for the commands received.
+    tx_peer: Option<Sender<MediaControl>>,
+    /// The position of the current playback.
+    clock: Option<PlaybackClock>,
+    /// Whether the current playback is paused.
+    paused: bool,
+    /// The last command received while nothing was playing, applied to the next playback.
+    pending: Option<SyncEvent>,
+}
+
+impl Session {
+    /// Returns the position of the current playback, if known.
+    fn position(&self) -> Option<f64> {
+        self.clock.as_ref().and_then(PlaybackClock::position)
+    }
+
+    /// Sends a command to the other tplay, if it is connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The command.
+    fn send(&mut self, event: SyncEvent) {
+        if let Some(peer) = &mut self.peer {
+            if writeln!(peer, "{event}").is_err() {
+                self.peer = None;
+            }
+        }
+    }
+
+    /// Shares a command of the current playback with the other tplay, if it is a play, pause or
+    /// seek command.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The command sent to the message broker.
+    fn share(&mut self, control: &MediaControl) {
+        let event = match control {
+            MediaControl::PauseContinue => {
+                self.paused = !self.paused;
+                if self.paused {
+                    SyncEvent::Pause(self.position())
+                } else {
+                    SyncEvent::Play(self.position())
+                }
+            }
+            MediaControl::Seek(seconds) => SyncEvent::Seek(*seconds),
+            _ => return,
+        };
+        self.send(event);
+    }
+
+    /// Applies a command of the other tplay to the current playback, or keeps it for the next
+    /// playback if nothing is playing.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The command received.
+    fn apply(&mut self, event: SyncEvent) {
+        let Some(tx) = &self.tx_peer else {
+            self.pending = Some(event);
+            return;
+        };
+        let (target, paused) = match event {
+            SyncEvent::Play(position) => (position, false),
+            SyncEvent::Pause(position) => (position, true),
+            SyncEvent::Seek(seconds) => (Some(seconds), self.paused),
+        };
+        if let Some(target) = target {
+            let drifted = self
+                .position()
+                .is_none_or(|position| (position - target).abs() > SYNC_TOLERANCE);
+            if drifted || matches!(event, SyncEvent::Seek(_)) {
+                let _ = tx.send(MediaControl::Seek(target));
+            }
+        }
+        if paused != self.paused {
+            let _ = tx.send(MediaControl::PauseContinue);
+            self.paused = paused;
+        }
+    }
+}
+
+/// The channels between the message broker of a playback and the watch-together session.
+pub struct SharedControls {
+    /// Receives the commands sent to the broker, to share them with the other tplay.
+    pub tx_local: Sender<MediaControl>,
+    /// The commands of the other tplay, applied by the broker without sharing them back.
+    pub rx_peer: Receiver<MediaControl>,
+}
+
+/// A watch-together session with another tplay, connected on a background thread.
+pub struct WatchTogether {
+    /// The state of the session.
+    session: Arc<Mutex<Session>>,
+}
+
+impl WatchTogether {
+    /// Hosts a session, waiting for the other tplay to join on the given port. A tplay joining
+    /// after the previous one left takes its place.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The TCP port to listen on, on all the network interfaces.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the port cannot be listened on, e.g. because it is in use.
+    pub fn host(port: u16) -> Result<Self, MyError> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|err| MyError::Application(format!("{ERROR_WATCH_HOST} ({port}): {err}")))?;
+        let session: Arc<Mutex<Session>> = Arc::default();
+        let thread_session = Arc::clone(&session);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Ok(writer) = stream.try_clone() else {
+                    continue;
+                };
+                {
+                    let mut session = lock(&thread_session);
+                    session.peer = Some(writer);
+                    // The other tplay catches up with the playback
+                    let position = session.position();
+                    if position.is_some() {
+                        let state = if session.paused {
+                            SyncEvent::Pause(position)
+                        } else {
+                            SyncEvent::Play(position)
+                        };
+                        session.send(state);
+                    }
+                }
+                read_events(&thread_session, stream);
+            }
+        });
+        Ok(Self { session })
+    }
+
+    /// Joins the session hosted by another tplay.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the host, e.g. `192.168.1.20:7777`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the host cannot be reached.
+    pub fn join(address: &str) -> Result<Self, MyError> {
+        let error = |err: std::io::Error| {
+            MyError::Application(format!("{ERROR_WATCH_JOIN} ({address}): {err}"))
+        };
+        let stream = TcpStream::connect(address).map_err(error)?;
+        let session = Arc::new(Mutex::new(Session {
+            peer: Some(stream.try_clone().map_err(error)?),
+            ..Session::default()
+        }));
+        let thread_session = Arc::clone(&session);
+        thread::spawn(move || read_events(&thread_session, stream));
+        Ok(Self { session })
+    }
+
+    /// Shares the commands of a new playback with the other tplay, until the broker of the
+    /// playback is gone.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The position of the playback.
+    ///
+    /// # Returns
+    ///
+    /// The channels to give to the message broker of the playback.
+    pub fn attach(&self, clock: PlaybackClock) -> SharedControls {
+        let (tx_local, rx_local) = unbounded();
+        let (tx_peer, rx_peer) = unbounded();
+        {
+            let mut session = lock(&self.session);
+            session.tx_peer = Some(tx_peer);
+            session.clock = Some(clock);
+            session.paused = false;
+            if let Some(event) = session.pending.take() {
+                session.apply(event);
+            }
+        }
+        let session = Arc::clone(&self.session);
+        thread::spawn(move || {
+            for control in rx_local {
+                lock(&session).share(&control);
+            }
+        });
+        SharedControls { tx_local, rx_peer }
+    }
+}
+
+/// Applies the commands received from the other tplay, until it disconnects.
+///
+/// # Arguments
+///
+/// * `session` - The state of the session.
+/// * `stream` - The connection to the other tplay.
+fn read_events(session: &Mutex<Session>, stream: TcpStream) {
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if let Ok(event) = line.parse() {
+            lock(session).apply(event);
+        }
+    }
+    lock(session).peer = None;
+}
+
+/// Locks the state of the session, even if a thread panicked while holding it.
+fn lock(session: &Mutex<Session>) -> MutexGuard<'_, Session> {
+    session
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_event() {
+        assert_eq!("pause 12.5".parse(), Ok(SyncEvent::Pause(Some(12.5))));
+        assert_eq!("play".parse(), Ok(SyncEvent::Play(None)));
+        assert!("seek".parse::<SyncEvent>().is_err());
+        assert!("seek -1".parse::<SyncEvent>().is_err());
+        let event = SyncEvent::Seek(90.0);
+        assert_eq!(event.to_string().parse(), Ok(event));
+
+        // Pauses are applied as the state they lead to, seeking when the playback drifted
+        let (tx, rx) = unbounded();
+        let clock = PlaybackClock::default();
+        clock.set(Some(10.0));
+        let mut session = Session {
+            tx_peer: Some(tx),
+            clock: Some(clock),
+            ..Session::default()
+        };
+        session.apply(SyncEvent::Pause(Some(10.2)));
+        session.apply(SyncEvent::Pause(Some(10.2)));
+        session.apply(SyncEvent::Play(Some(30.0)));
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [
+                MediaControl::PauseContinue,
+                MediaControl::Seek(30.0),
+                MediaControl::PauseContinue
+            ]
+        );
+
+        // Commands received while nothing plays wait for the next playback
+        let host = WatchTogether {
+            session: Arc::default(),
+        };
+        lock(&host.session).apply(SyncEvent::Pause(None));
+        let shared = host.attach(PlaybackClock::default());
+        assert_eq!(
+            shared.rx_peer.try_iter().collect::<Vec<_>>(),
+            [MediaControl::PauseContinue]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Decodes animated images (GIF, WebP) on a background thread while they play.
+//!
+//! Decoding every frame of a large animation takes seconds. Instead, the frames are counted and
+//! timed first, which only reads their headers, then decoded one after the other on a background
+//! thread: the playback starts with the first frame, and only waits if it catches up with the
+//! decoding.
+use image::DynamicImage;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+};
+
+/// The frames decoded so far.
+#[derive(Default)]
+struct Decoded {
+    /// The frames, in order.
+    frames: Vec<DynamicImage>,
+    /// Whether the decoding is over, having decoded every frame or failed.
+    finished: bool,
+}
+
+/// The frames decoded so far, shared with the background thread.
+#[derive(Default)]
+struct Shared {
+    /// The frames decoded so far.
+    decoded: Mutex<Decoded>,
+    /// Notified when a frame is decoded, and when the decoding is over.
+    progress: Condvar,
+}
+
+impl Shared {
+    /// Locks the frames decoded so far, even if the background thread panicked.
+    fn lock(&self) -> MutexGuard<'_, Decoded> {
+        self.decoded
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// An animation decoded on a background thread.
+pub struct Animation {
+    /// The frames decoded so far, shared with the background thread.
+    shared: Arc<Shared>,
+    /// The number of frames of the animation, known before they are decoded.
+    frames: usize,
+    /// The index of the next frame played.
+    current_frame: usize,
+}
+
+impl Animation {
+    /// Starts decoding an animation on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The number of frames of the animation.
+    /// * `decode` - Decodes the frames in order, passing each one to the given callback. The
+    ///   callback returns `false` once the animation was dropped, when the decoding can stop.
+    pub fn decode<F>(frames: usize, decode: F) -> Self
+    where
+        F: FnOnce(&mut dyn FnMut(DynamicImage) -> bool) + Send + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            // A panicking decoder ends the animation early rather than stalling the playback
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                decode(&mut |frame| {
+                    thread_shared.lock().frames.push(frame);
+                    thread_shared.progress.notify_all();
+                    // The background thread is left alone once the animation is dropped
+                    Arc::strong_count(&thread_shared) > 1
+                })
+            }));
+            thread_shared.lock().finished = true;
+            thread_shared.progress.notify_all();
+        });
+        Self {
+            shared,
+            frames,
+            current_frame: 0,
+        }
+    }
+
+    /// Returns the number of frames of the animation. Frames that failed to decode are not
+    /// counted once the decoding is over.
+    pub fn len(&self) -> usize {
+        let decoded = self.shared.lock();
+        match decoded.finished {
+            true => decoded.frames.len(),
+            false => self.frames.max(decoded.frames.len()),
+        }
+    }
+
+    /// Returns the number of frames decoded so far, played without waiting.
+    pub fn decoded(&self) -> usize {
+        self.shared.lock().frames.len()
+    }
+
+    /// Returns whether the animation has no frames.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index of the next frame played.
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    /// Moves to a frame, or to the end of the animation if the index is past it.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The index of the frame.
+    pub fn set_current_frame(&mut self, frame: usize) {
+        self.current_frame = frame.min(self.len());
+    }
+
+    /// Returns a frame, waiting for it to be decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or `None` if it is past the end of the animation.
+    pub fn frame(&self, index: usize) -> Option<DynamicImage> {
+        let mut decoded = self.shared.lock();
+        while decoded.frames.len() <= index && !decoded.finished {
+            decoded = self
+                .shared
+                .progress
+                .wait(decoded)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        decoded.frames.get(index).cloned()
+    }
+
+    /// Returns the next frame, waiting for it to be decoded, or `None` at the end of the
+    /// animation.
+    pub fn next_frame(&mut self) -> Option<DynamicImage> {
+        let frame = self.frame(self.current_frame)?;
+        self.current_frame += 1;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_animation_plays_while_decoding() {
+        let (tx_go, rx_go) = mpsc::channel::<()>();
+        let mut animation = Animation::decode(3, move |push| {
+            for width in 1..=3 {
+                push(DynamicImage::new_rgb8(width, 1));
+                // The rest of the frames are decoded once the first one was played
+                let _ = rx_go.recv();
+            }
+        });
+        assert_eq!(animation.len(), 3);
+        assert_eq!(animation.next_frame().map(|frame| frame.width()), Some(1));
+        tx_go.send(()).unwrap();
+        tx_go.send(()).unwrap();
+        drop(tx_go);
+        assert_eq!(animation.next_frame().map(|frame| frame.width()), Some(2));
+        assert_eq!(animation.next_frame().map(|frame| frame.width()), Some(3));
+        assert_eq!(animation.next_frame(), None);
+
+        animation.set_current_frame(10);
+        assert_eq!(animation.current_frame(), 3);
+    }
+
+    #[test]
+    fn test_animation_ends_early_when_decoding_fails() {
+        let animation = Animation::decode(5, |push| {
+            push(DynamicImage::new_rgb8(1, 1));
+        });
+        assert_eq!(animation.frame(3), None);
+        assert_eq!(animation.len(), 1);
+    }
+}
@@ -0,0 +1,191 @@
+//! Renders images with Unicode block elements, each cell showing 2x2 quadrants or 2x3 sextants
+//! in two colors.
+//!
+//! Every cell picks the block whose shape best splits its pixels in two groups: the glyph is drawn
+//! with the average color of one group and the cell background with the average color of the
+//! other, so that both the shape and the shades of the image are kept. Sextant blocks (from
+//! Unicode 13, "Symbols for Legacy Computing") are not supported by every font.
+use super::cell_grid::CellGrid;
+use image::RgbImage;
+use std::str::FromStr;
+
+/// Quadrant glyphs, indexed by their quadrants: bit 0 for the top left quadrant, then top right,
+/// bottom left and bottom right.
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+/// Code point of the first sextant glyph (top left sextant), the other ones follow in the order of
+/// their bits, without the four ones already existing as block elements.
+const SEXTANT_FIRST: u32 = 0x1FB00;
+/// Sextants of the left half block (`▌`).
+const SEXTANT_LEFT_HALF: usize = 0b010101;
+/// Sextants of the right half block (`▐`).
+const SEXTANT_RIGHT_HALF: usize = 0b101010;
+
+/// The block elements drawn in each cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockMode {
+    /// 2x2 quadrants per cell.
+    Quadrant,
+    /// 2x3 sextants per cell.
+    Sextant,
+}
+
+impl FromStr for BlockMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "quadrant" | "quadrants" => Ok(BlockMode::Quadrant),
+            "sextant" | "sextants" => Ok(BlockMode::Sextant),
+            _ => Err(format!("`{name}` is not a block mode (quadrant, sextant)")),
+        }
+    }
+}
+
+impl BlockMode {
+    /// Returns the number of pixels covered by a cell (width, height).
+    pub fn cell_size(self) -> (u32, u32) {
+        match self {
+            BlockMode::Quadrant => (2, 2),
+            BlockMode::Sextant => (2, 3),
+        }
+    }
+
+    /// Returns the glyph whose blocks are the set bits of the mask, bit 0 being the top left
+    /// block and the bits going left to right, then top to bottom.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - The blocks of the glyph.
+    fn glyph(self, mask: usize) -> char {
+        match (self, mask) {
+            (BlockMode::Quadrant, _) => QUADRANTS[mask & 0b1111],
+            (BlockMode::Sextant, 0) => ' ',
+            (BlockMode::Sextant, SEXTANT_LEFT_HALF) => '▌',
+            (BlockMode::Sextant, SEXTANT_RIGHT_HALF) => '▐',
+            (BlockMode::Sextant, 0b111111..) => '█',
+            (BlockMode::Sextant, _) => {
+                let skipped = 1
+                    + usize::from(mask > SEXTANT_LEFT_HALF)
+                    + usize::from(mask > SEXTANT_RIGHT_HALF);
+                char::from_u32(SEXTANT_FIRST + (mask - skipped) as u32).unwrap_or(' ')
+            }
+        }
+    }
+}
+
+/// Splits the pixels of a cell in two groups, the set bits of the mask and the other ones, and
+/// returns the average color of each group and how far the pixels are from their group's color.
+///
+/// # Arguments
+///
+/// * `pixels` - The colors of the pixels of the cell.
+/// * `mask` - The pixels of the first group.
+fn split(pixels: &[[u8; 3]], mask: usize) -> ([u8; 3], [u8; 3], u32) {
+    let mut sums = [[0u32; 3]; 2];
+    let mut counts = [0u32; 2];
+    for (i, pixel) in pixels.iter().enumerate() {
+        let group = usize::from(mask & (1 << i) == 0);
+        counts[group] += 1;
+        for (sum, channel) in sums[group].iter_mut().zip(pixel) {
+            *sum += *channel as u32;
+        }
+    }
+    let [fg, bg] = [0, 1].map(|group| sums[group].map(|sum| (sum / counts[group].max(1)) as u8));
+    let error = pixels
+        .iter()
+        .enumerate()
+        .map(|(i, pixel)| {
+            let mean = if mask & (1 << i) != 0 { fg } else { bg };
+            pixel
+                .iter()
+                .zip(mean)
+                .map(|(a, b)| (*a as i32 - b as i32).pow(2) as u32)
+                .sum::<u32>()
+        })
+        .sum();
+    (fg, bg, error)
+}
+
+/// Converts an image to a grid of block elements, each cell covering 2x2 or 2x3 pixels.
+///
+/// # Arguments
+///
+/// * `rgb` - The image, resized to the cell size of the mode times the size of the grid.
+/// * `mode` - The block elements to draw.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// A `CellGrid` whose cells have a glyph color and a background color.
+pub fn to_blocks(rgb: &RgbImage, mode: BlockMode, new_lines: bool) -> CellGrid {
+    let (cell_width, cell_height) = mode.cell_size();
+    let (columns, rows) = (rgb.width() / cell_width, rgb.height() / cell_height);
+    let full = (1 << (cell_width * cell_height)) - 1;
+    let mut grid = CellGrid::new(columns, rows, new_lines);
+    let mut pixels = Vec::with_capacity((cell_width * cell_height) as usize);
+    let mut buffer = [0; 4];
+    for row in 0..rows {
+        for column in 0..columns {
+            pixels.clear();
+            for dy in 0..cell_height {
+                for dx in 0..cell_width {
+                    let (x, y) = (column * cell_width + dx, row * cell_height + dy);
+                    pixels.push(rgb.get_pixel(x, y).0);
+                }
+            }
+            // A mask and its complement give the same picture, the top left pixel is always set.
+            // Uniform cells are drawn with a full block.
+            let (mask, fg, bg) = (1..=full)
+                .step_by(2)
+                .map(|mask| {
+                    let (fg, bg, error) = split(&pixels, mask);
+                    (error, mask, fg, bg)
+                })
+                .min_by_key(|(error, mask, ..)| (*error, full - mask))
+                .map(|(_, mask, fg, bg)| (mask, fg, if mask == full { fg } else { bg }))
+                .unwrap_or((full, [0; 3], [0; 3]));
+            let glyph = mode.glyph(mask);
+            grid.push_with_background(glyph.encode_utf8(&mut buffer), fg, bg);
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_glyphs() {
+        assert_eq!(BlockMode::Quadrant.glyph(0b1001), '▚');
+        assert_eq!(BlockMode::Sextant.glyph(1), '\u{1FB00}');
+        assert_eq!(BlockMode::Sextant.glyph(SEXTANT_LEFT_HALF), '▌');
+        assert_eq!(BlockMode::Sextant.glyph(22), '\u{1FB14}');
+        assert_eq!(BlockMode::Sextant.glyph(62), '\u{1FB3B}');
+        assert_eq!("Sextant".parse(), Ok(BlockMode::Sextant));
+        assert!("octant".parse::<BlockMode>().is_err());
+    }
+
+    #[test]
+    fn test_to_blocks() {
+        // Top left quadrant yellow, the rest red
+        let rgb = RgbImage::from_fn(2, 2, |x, y| Rgb([255, (x + y == 0) as u8 * 255, 0]));
+        let grid = to_blocks(&rgb, BlockMode::Quadrant, false);
+        let cell = grid.cell(0, 0).unwrap();
+        assert_eq!(cell.glyph, "▘");
+        assert_eq!(cell.color, [255, 255, 0]);
+        assert_eq!(cell.background, Some([255, 0, 0]));
+
+        // Bottom row dark, 2x3 sextants
+        let rgb = RgbImage::from_fn(4, 3, |_, y| Rgb([if y == 2 { 0 } else { 200 }; 3]));
+        let grid = to_blocks(&rgb, BlockMode::Sextant, false);
+        assert_eq!((grid.width(), grid.height()), (2, 1));
+        assert_eq!(grid.row_text(0), "\u{1FB0E}\u{1FB0E}");
+
+        let grid = to_blocks(&RgbImage::new(2, 3), BlockMode::Sextant, false);
+        assert_eq!(grid.row_text(0), "█");
+    }
+}
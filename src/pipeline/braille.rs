@@ -0,0 +1,172 @@
+//! Renders images with braille characters, each cell showing a 2x4 matrix of dots.
+//!
+//! Every dot is a pixel, either lit or not, so braille output has 8 times the spatial resolution
+//! of a character map, at the cost of shades: the gray levels are reduced to lit and unlit dots by
+//! a threshold, optionally with dithering to keep the impression of shades. Each cell takes the
+//! average color of its 8 pixels.
+pub use super::dither::Dither;
+use super::{cell_grid::CellGrid, dither::BAYER_4X4};
+use image::{GrayImage, RgbImage};
+
+/// Width of a braille cell, in dots.
+pub const DOTS_WIDTH: u32 = 2;
+/// Height of a braille cell, in dots.
+pub const DOTS_HEIGHT: u32 = 4;
+/// Code point of the blank braille character, the other ones add the bits of their dots.
+const BRAILLE_BLANK: u32 = 0x2800;
+/// Bit of each dot of a braille character, indexed by row then column.
+const DOT_BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+/// The options of the braille rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrailleOptions {
+    /// How gray levels are reduced to lit and unlit dots.
+    pub dither: Dither,
+    /// The gray level from which dots are lit (0-255).
+    pub threshold: u8,
+}
+
+impl Default for BrailleOptions {
+    fn default() -> Self {
+        Self {
+            dither: Dither::default(),
+            threshold: 128,
+        }
+    }
+}
+
+/// Returns which pixels of the image are lit dots, row by row.
+///
+/// # Arguments
+///
+/// * `gray` - The image, in gray levels.
+/// * `options` - The threshold and dithering method.
+fn lit_dots(gray: &GrayImage, options: BrailleOptions) -> Vec<bool> {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let threshold = options.threshold as i32;
+    match options.dither {
+        Dither::Threshold => gray.pixels().map(|p| p[0] as i32 >= threshold).collect(),
+        Dither::Ordered => gray
+            .enumerate_pixels()
+            .map(|(x, y, p)| {
+                // Centered on the threshold, the matrix spreads the levels over 16 steps
+                let offset = (BAYER_4X4[y as usize % 4][x as usize % 4] * 2 - 15) * 8;
+                p[0] as i32 + offset >= threshold
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            let mut levels: Vec<i32> = gray.pixels().map(|p| p[0] as i32).collect();
+            let mut lit = vec![false; levels.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let index = y * width + x;
+                    let level = levels[index];
+                    lit[index] = level >= threshold;
+                    let error = level - if lit[index] { 255 } else { 0 };
+                    let mut spread = |dx: isize, dy: usize, weight: i32| {
+                        let nx = x as isize + dx;
+                        if nx >= 0 && (nx as usize) < width && y + dy < height {
+                            levels[(y + dy) * width + nx as usize] += error * weight / 16;
+                        }
+                    };
+                    spread(1, 0, 7);
+                    spread(-1, 1, 3);
+                    spread(0, 1, 5);
+                    spread(1, 1, 1);
+                }
+            }
+            lit
+        }
+    }
+}
+
+/// Converts an image to a grid of braille characters, each cell covering 2x4 pixels.
+///
+/// # Arguments
+///
+/// * `gray` - The image in gray levels, deciding which dots are lit.
+/// * `rgb` - The same image in colors, deciding the color of each cell.
+/// * `options` - The threshold and dithering method.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// A `CellGrid` of `width / 2` x `height / 4` cells.
+pub fn to_braille(
+    gray: &GrayImage,
+    rgb: &RgbImage,
+    options: BrailleOptions,
+    new_lines: bool,
+) -> CellGrid {
+    let (columns, rows) = (gray.width() / DOTS_WIDTH, gray.height() / DOTS_HEIGHT);
+    let lit = lit_dots(gray, options);
+    let mut grid = CellGrid::new(columns, rows, new_lines);
+    let mut buffer = [0; 4];
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut bits = 0;
+            let mut sum = [0u32; 3];
+            for (dy, row_bits) in DOT_BITS.iter().enumerate() {
+                for (dx, bit) in row_bits.iter().enumerate() {
+                    let (x, y) = (
+                        column * DOTS_WIDTH + dx as u32,
+                        row * DOTS_HEIGHT + dy as u32,
+                    );
+                    if lit[(y * gray.width() + x) as usize] {
+                        bits |= bit;
+                    }
+                    for (total, channel) in sum.iter_mut().zip(rgb.get_pixel(x, y).0) {
+                        *total += channel as u32;
+                    }
+                }
+            }
+            let glyph = char::from_u32(BRAILLE_BLANK + bits).unwrap_or(' ');
+            let dots = DOTS_WIDTH * DOTS_HEIGHT;
+            let color = sum.map(|total| (total / dots) as u8);
+            grid.push(glyph.encode_utf8(&mut buffer), color);
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb};
+
+    #[test]
+    fn test_to_braille() {
+        // Left column lit, right column dark
+        let gray = GrayImage::from_fn(2, 4, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let rgb = RgbImage::from_fn(2, 4, |x, _| Rgb([if x == 0 { 200 } else { 100 }; 3]));
+        let options = BrailleOptions {
+            dither: Dither::Threshold,
+            threshold: 128,
+        };
+        let grid = to_braille(&gray, &rgb, options, false);
+        assert_eq!((grid.width(), grid.height()), (1, 1));
+        assert_eq!(grid.cell(0, 0).map(|cell| cell.glyph), Some("⡇"));
+        assert_eq!(grid.cell(0, 0).map(|cell| cell.color), Some([150; 3]));
+    }
+
+    #[test]
+    fn test_dithering_keeps_shades() {
+        let gray = GrayImage::from_pixel(16, 16, Luma([64]));
+        let options = |dither| BrailleOptions {
+            dither,
+            threshold: 128,
+        };
+        let count = |dither| {
+            lit_dots(&gray, options(dither))
+                .into_iter()
+                .filter(|lit| *lit)
+                .count()
+        };
+        // A quarter of the dots are lit with dithering, none without
+        assert_eq!(count(Dither::Threshold), 0);
+        assert_eq!(count(Dither::Ordered), 64);
+        assert!((48..=80).contains(&count(Dither::FloydSteinberg)));
+
+        assert_eq!("Floyd-Steinberg".parse(), Ok(Dither::FloydSteinberg));
+        assert!("random".parse::<Dither>().is_err());
+    }
+}
@@ -0,0 +1,636 @@
+//! Decodes a few video formats in pure Rust, when tplay is built without a video decoder library
+//! (`--no-default-features`), so that it installs without OpenCV or the FFmpeg development
+//! libraries:
+//! - YUV4MPEG2 (`.y4m`): uncompressed 8-bit frames, e.g. converted with `ffmpeg -i video.mp4
+//!   video.y4m`.
+//! - AVI holding Motion JPEG (e.g. recorded by cameras) or uncompressed RGB frames.
+//!
+//! The frames are indexed when the file is opened, reading only the headers of the chunks, so that
+//! seeking is exact and skipping frames does not decode them. Other formats and network streams
+//! need one of the decoder features.
+use super::video::{VideoDecoder, MAX_CONSECUTIVE_BAD_FRAMES};
+use crate::common::errors::*;
+use image::{DynamicImage, GrayImage, Rgb, RgbImage};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+};
+
+/// Maximum length of the header lines of YUV4MPEG2 files.
+const MAX_HEADER_LENGTH: u64 = 1024;
+/// Maximum size of the stream header chunks of AVI files read.
+const MAX_STREAM_HEADER_SIZE: u64 = 4096;
+
+/// How the frames of a video are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    /// A luma plane followed by two chroma planes subsampled by the given factors, or by none for
+    /// grayscale frames.
+    Yuv {
+        subsampling: Option<(u32, u32)>,
+        full_range: bool,
+    },
+    /// A JPEG image per frame.
+    Mjpeg,
+    /// Rows of BGR pixels padded to 4 bytes, from the bottom of the frame unless `top_down`.
+    Bgr { top_down: bool },
+}
+
+/// The frames of a video, indexed from its headers.
+struct Index {
+    codec: Codec,
+    width: u32,
+    height: u32,
+    fps: f64,
+    /// The offset and size of the data of each frame in the file.
+    frames: Vec<(u64, usize)>,
+}
+
+/// A video decoded by the built-in decoder.
+pub struct BuiltinDecoder {
+    /// The video file.
+    file: BufReader<File>,
+    /// The frames of the video.
+    index: Index,
+    /// The index of the next frame.
+    next: usize,
+}
+
+impl BuiltinDecoder {
+    /// Opens a YUV4MPEG2 or AVI video file.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path of the video.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoder, or a `MyError` if the file cannot be read or is in a
+    /// format the built-in decoder does not read.
+    pub fn open(source: &str) -> Result<Self, MyError> {
+        let error = |reason: String| {
+            MyError::Application(format!("{ERROR_OPENING_VIDEO}: {source}: {reason}"))
+        };
+        let mut file = File::open(source)
+            .map(BufReader::new)
+            .map_err(|err| error(err.to_string()))?;
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)
+            .and_then(|_| file.rewind())
+            .map_err(|err| error(err.to_string()))?;
+        let index = match &magic {
+            b"YUV4" => index_y4m(&mut file),
+            b"RIFF" => index_avi(&mut file),
+            _ => {
+                return Err(MyError::Application(format!(
+                    "{ERROR_UNSUPPORTED_VIDEO}: {source}"
+                )))
+            }
+        }
+        .map_err(error)?;
+        Ok(Self {
+            file,
+            index,
+            next: 0,
+        })
+    }
+
+    /// Reads and decodes a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the data of the frame in the file.
+    /// * `size` - The size of the data of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or `None` if it cannot be read or decoded.
+    fn decode(&mut self, offset: u64, size: usize) -> Option<DynamicImage> {
+        let mut data = vec![0; size];
+        self.file.seek(SeekFrom::Start(offset)).ok()?;
+        self.file.read_exact(&mut data).ok()?;
+        let Index { width, height, .. } = self.index;
+        match self.index.codec {
+            Codec::Yuv {
+                subsampling,
+                full_range,
+            } => yuv_to_rgb(&data, (width, height), subsampling, full_range),
+            Codec::Mjpeg => decode_jpeg(&data),
+            Codec::Bgr { top_down } => bgr_to_rgb(&data, (width, height), top_down),
+        }
+    }
+}
+
+impl VideoDecoder for BuiltinDecoder {
+    fn next_frame(&mut self) -> Option<DynamicImage> {
+        for _ in 0..MAX_CONSECUTIVE_BAD_FRAMES {
+            let (offset, size) = *self.index.frames.get(self.next)?;
+            self.next += 1;
+            if let Some(image) = self.decode(offset, size) {
+                return Some(image);
+            }
+        }
+        None
+    }
+
+    fn skip_frames(&mut self, n: usize) -> bool {
+        // The frames are indexed, skipping does not read them
+        let frames = self.index.frames.len();
+        let skipped = self.next.saturating_add(n) <= frames;
+        self.next = self.next.saturating_add(n).min(frames);
+        skipped
+    }
+
+    fn seek(&mut self, seconds: f64) {
+        self.seek_frame((seconds.max(0.0) * self.index.fps).round() as usize);
+    }
+
+    fn seek_frame(&mut self, index: usize) {
+        self.next = index.min(self.index.frames.len());
+    }
+
+    fn position(&self) -> Option<f64> {
+        Some(self.next as f64 / self.index.fps)
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        Some(self.index.frames.len()).filter(|frames| *frames > 0)
+    }
+
+    fn duration(&self) -> Option<f64> {
+        self.frame_count()
+            .map(|frames| frames as f64 / self.index.fps)
+    }
+
+    fn fps(&self) -> Option<f64> {
+        Some(self.index.fps)
+    }
+}
+
+/// Indexes the frames of a YUV4MPEG2 file: a header line giving the size, frame rate and color
+/// space of the frames, then each frame after a `FRAME` line.
+///
+/// # Arguments
+///
+/// * `file` - The file, read from its start.
+///
+/// # Returns
+///
+/// The index of the frames, or the reason the file cannot be read.
+fn index_y4m(file: &mut BufReader<File>) -> Result<Index, String> {
+    let header = read_header_line(file).map_err(|err| err.to_string())?;
+    let mut params = header.split_ascii_whitespace();
+    if params.next() != Some("YUV4MPEG2") {
+        return Err("not a YUV4MPEG2 file".to_string());
+    }
+    let (mut width, mut height, mut fps) = (0_u32, 0_u32, None);
+    let (mut color_space, mut full_range) = ("420jpeg", false);
+    for param in params {
+        let Some((tag, value)) = param.split_at_checked(1) else {
+            continue;
+        };
+        match tag {
+            "W" => width = value.parse().unwrap_or(0),
+            "H" => height = value.parse().unwrap_or(0),
+            "F" => {
+                fps = value
+                    .split_once(':')
+                    .and_then(|(rate, scale)| ratio(rate.parse().ok()?, scale.parse().ok()?))
+            }
+            "C" => color_space = value,
+            "X" => full_range |= value.eq_ignore_ascii_case("COLORRANGE=FULL"),
+            _ => {}
+        }
+    }
+    let subsampling = match color_space {
+        "420jpeg" | "420paldv" | "420mpeg2" | "420" => Some((2, 2)),
+        "422" => Some((2, 1)),
+        "444" => Some((1, 1)),
+        "mono" => None,
+        _ => {
+            return Err(format!(
+                "unsupported color space `{color_space}`, the built-in decoder reads 8-bit 420, 422, 444 and mono frames"
+            ))
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err("missing frame size".to_string());
+    }
+    let fps = fps.ok_or("missing frame rate")?;
+    let (chroma_width, chroma_height) = subsampling.map_or((0, 0), |(x, y)| {
+        (width.div_ceil(x) as usize, height.div_ceil(y) as usize)
+    });
+    let size = width as usize * height as usize + 2 * chroma_width * chroma_height;
+
+    let length = file
+        .get_ref()
+        .metadata()
+        .map_err(|err| err.to_string())?
+        .len();
+    let mut frames = Vec::new();
+    let mut offset = header.len() as u64;
+    while offset < length {
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|err| err.to_string())?;
+        let Ok(line) = read_header_line(file) else {
+            break;
+        };
+        let data = offset + line.len() as u64;
+        // A frame cut short ends the video
+        if !line.starts_with("FRAME") || !line.ends_with('\n') || data + size as u64 > length {
+            break;
+        }
+        frames.push((data, size));
+        offset = data + size as u64;
+    }
+    Ok(Index {
+        codec: Codec::Yuv {
+            subsampling,
+            full_range,
+        },
+        width,
+        height,
+        fps,
+        frames,
+    })
+}
+
+/// Reads a header line of a YUV4MPEG2 file, up to `MAX_HEADER_LENGTH`.
+///
+/// # Arguments
+///
+/// * `file` - The file, read from the start of the line.
+///
+/// # Returns
+///
+/// The line with its line break, or the error reading it.
+fn read_header_line(file: &mut BufReader<File>) -> io::Result<String> {
+    let mut line = String::new();
+    file.by_ref().take(MAX_HEADER_LENGTH).read_line(&mut line)?;
+    Ok(line)
+}
+
+/// The streams and frame chunks of an AVI file, as found while walking its chunks.
+#[derive(Default)]
+struct AviChunks {
+    /// The stream header (`strh`) and format (`strf`) of each stream, in order.
+    streams: Vec<(Vec<u8>, Vec<u8>)>,
+    /// The stream, offset and size of the data of each chunk of the streams.
+    chunks: Vec<(usize, u64, usize)>,
+}
+
+/// Indexes the frames of an AVI file: the chunks of its first video stream, in Motion JPEG or
+/// uncompressed RGB. Empty chunks repeat the previous frame, like players do.
+///
+/// # Arguments
+///
+/// * `file` - The file, read from its start.
+///
+/// # Returns
+///
+/// The index of the frames, or the reason the file cannot be read.
+fn index_avi(file: &mut BufReader<File>) -> Result<Index, String> {
+    let length = file
+        .get_ref()
+        .metadata()
+        .map_err(|err| err.to_string())?
+        .len();
+    let mut avi = AviChunks::default();
+    walk_avi(file, (0, length), &mut avi).map_err(|err| err.to_string())?;
+    let (stream, (header, format)) = avi
+        .streams
+        .iter()
+        .enumerate()
+        .find(|(_, (header, _))| header.starts_with(b"vids"))
+        .ok_or("no video stream")?;
+    let fps = le_u32(header, 24)
+        .zip(le_u32(header, 20))
+        .and_then(|(rate, scale)| ratio(f64::from(rate), f64::from(scale)))
+        .ok_or("missing frame rate")?;
+    let (width, height, bit_count) = le_u32(format, 4)
+        .zip(le_u32(format, 8))
+        .zip(format.get(14..16))
+        .map(|((width, height), bits)| (width as i32, height as i32, bits))
+        .ok_or("missing frame format")?;
+    let codec = match format.get(16..20).unwrap_or_default() {
+        b"MJPG" | b"mjpg" | b"AVRn" | b"jpeg" | b"JPEG" => Codec::Mjpeg,
+        [0, 0, 0, 0] if bit_count == [24, 0] => Codec::Bgr {
+            top_down: height < 0,
+        },
+        compression => {
+            return Err(format!(
+            "unsupported codec `{}`, the built-in decoder reads Motion JPEG and uncompressed RGB",
+            String::from_utf8_lossy(compression).trim_end_matches('\0')
+        ))
+        }
+    };
+    let mut frames: Vec<(u64, usize)> = Vec::new();
+    for &(_, offset, size) in avi.chunks.iter().filter(|(index, ..)| *index == stream) {
+        match (size, frames.last()) {
+            (0, Some(&previous)) => frames.push(previous),
+            (0, None) => {}
+            _ => frames.push((offset, size)),
+        }
+    }
+    Ok(Index {
+        codec,
+        width: width.unsigned_abs(),
+        height: height.unsigned_abs(),
+        fps,
+        frames,
+    })
+}
+
+/// Walks the chunks of an AVI file, descending into the lists holding the stream headers and the
+/// frames. The chunks of the frames are named after their stream, e.g. `00dc` for the compressed
+/// frames of the first stream.
+///
+/// # Arguments
+///
+/// * `file` - The file.
+/// * `range` - The start and end offsets of the chunks walked.
+/// * `avi` - The streams and chunks found.
+///
+/// # Errors
+///
+/// Returns the error reading the file.
+fn walk_avi(file: &mut BufReader<File>, range: (u64, u64), avi: &mut AviChunks) -> io::Result<()> {
+    let (mut offset, end) = range;
+    while offset + 8 <= end {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let (id, size) = (&header[..4], le_u32(&header, 4).unwrap_or(0) as u64);
+        let data = offset + 8;
+        let data_end = (data + size).min(end);
+        match id {
+            b"RIFF" | b"LIST" => {
+                let mut kind = [0; 4];
+                file.read_exact(&mut kind)?;
+                if &kind == b"strl" {
+                    avi.streams.push(Default::default());
+                }
+                if matches!(
+                    &kind,
+                    b"AVI " | b"AVIX" | b"hdrl" | b"strl" | b"movi" | b"rec "
+                ) {
+                    walk_avi(file, (data + 4, data_end), avi)?;
+                }
+            }
+            b"strh" | b"strf" => {
+                let mut bytes = Vec::new();
+                file.by_ref()
+                    .take(size.min(MAX_STREAM_HEADER_SIZE))
+                    .read_to_end(&mut bytes)?;
+                match (avi.streams.last_mut(), id) {
+                    (Some((header, _)), b"strh") => *header = bytes,
+                    (Some((_, format)), _) => *format = bytes,
+                    (None, _) => {}
+                }
+            }
+            [tens @ b'0'..=b'9', units @ b'0'..=b'9', b'd', b'c' | b'b'] => {
+                let stream = usize::from((tens - b'0') * 10 + (units - b'0'));
+                avi.chunks.push((stream, data, (data_end - data) as usize));
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        offset = data + size + (size & 1);
+    }
+    Ok(())
+}
+
+/// Returns the little-endian 32-bit number at an offset of the bytes, if they hold it.
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Returns a frame rate given as a ratio, if it is valid.
+///
+/// # Arguments
+///
+/// * `rate` - The numerator of the ratio.
+/// * `scale` - The denominator of the ratio.
+fn ratio(rate: f64, scale: f64) -> Option<f64> {
+    let fps = rate / scale;
+    (fps.is_finite() && fps > 0.0).then_some(fps)
+}
+
+/// Converts a frame of YUV planes to RGB, with the BT.601 coefficients.
+///
+/// # Arguments
+///
+/// * `data` - The luma plane followed by the chroma planes.
+/// * `size` - The width and height of the frame.
+/// * `subsampling` - How much the chroma planes are subsampled horizontally and vertically, or
+///   `None` for grayscale frames.
+/// * `full_range` - Whether the levels span 0-255 rather than 16-235.
+///
+/// # Returns
+///
+/// The image, or `None` if the data is too short.
+fn yuv_to_rgb(
+    data: &[u8],
+    size: (u32, u32),
+    subsampling: Option<(u32, u32)>,
+    full_range: bool,
+) -> Option<DynamicImage> {
+    let (width, height) = size;
+    let (luma, chroma) = data.split_at_checked(width as usize * height as usize)?;
+    let Some((x_factor, y_factor)) = subsampling else {
+        return Some(DynamicImage::ImageRgb8(RgbImage::from_fn(
+            width,
+            height,
+            |x, y| yuv_pixel(luma[(y * width + x) as usize], 128, 128, full_range),
+        )));
+    };
+    let chroma_width = width.div_ceil(x_factor);
+    let plane = (chroma_width * height.div_ceil(y_factor)) as usize;
+    let (u, v) = chroma.split_at_checked(plane)?;
+    if v.len() < plane {
+        return None;
+    }
+    Some(DynamicImage::ImageRgb8(RgbImage::from_fn(
+        width,
+        height,
+        |x, y| {
+            let chroma = ((y / y_factor) * chroma_width + x / x_factor) as usize;
+            yuv_pixel(
+                luma[(y * width + x) as usize],
+                u[chroma],
+                v[chroma],
+                full_range,
+            )
+        },
+    )))
+}
+
+/// Converts a YUV pixel to RGB, with the BT.601 coefficients.
+///
+/// # Arguments
+///
+/// * `y` - The luma of the pixel.
+/// * `u` - The blue-difference chroma of the pixel.
+/// * `v` - The red-difference chroma of the pixel.
+/// * `full_range` - Whether the levels span 0-255 rather than 16-235 (16-240 for the chroma).
+fn yuv_pixel(y: u8, u: u8, v: u8, full_range: bool) -> Rgb<u8> {
+    let (y, u, v) = (f32::from(y), f32::from(u) - 128.0, f32::from(v) - 128.0);
+    let (y, u, v) = match full_range {
+        true => (y, u, v),
+        false => (
+            (y - 16.0) * 255.0 / 219.0,
+            u * 255.0 / 224.0,
+            v * 255.0 / 224.0,
+        ),
+    };
+    Rgb([
+        y + 1.402 * v,
+        y - 0.344_136 * u - 0.714_136 * v,
+        y + 1.772 * u,
+    ]
+    .map(|channel| channel.round().clamp(0.0, 255.0) as u8))
+}
+
+/// Decodes a Motion JPEG frame, whose Huffman tables may be left out for the standard ones.
+///
+/// # Arguments
+///
+/// * `data` - The JPEG image.
+///
+/// # Returns
+///
+/// The image, or `None` if it cannot be decoded.
+fn decode_jpeg(data: &[u8]) -> Option<DynamicImage> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = decoder.decode().ok()?;
+    let info = decoder.info()?;
+    let (width, height) = (u32::from(info.width), u32::from(info.height));
+    match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        _ => None,
+    }
+}
+
+/// Converts an uncompressed AVI frame to RGB.
+///
+/// # Arguments
+///
+/// * `data` - The rows of BGR pixels, each padded to 4 bytes.
+/// * `size` - The width and height of the frame.
+/// * `top_down` - Whether the rows start from the top of the frame rather than the bottom.
+///
+/// # Returns
+///
+/// The image, or `None` if the data is too short.
+fn bgr_to_rgb(data: &[u8], size: (u32, u32), top_down: bool) -> Option<DynamicImage> {
+    let (width, height) = size;
+    let stride = (width as usize * 3).next_multiple_of(4);
+    if data.len() < stride * height as usize {
+        return None;
+    }
+    Some(DynamicImage::ImageRgb8(RgbImage::from_fn(
+        width,
+        height,
+        |x, y| {
+            let row = if top_down { y } else { height - 1 - y };
+            let pixel = row as usize * stride + x as usize * 3;
+            Rgb([data[pixel + 2], data[pixel + 1], data[pixel]])
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+    use std::fs;
+
+    /// Returns an AVI chunk, or a list of chunks if `kind` is given.
+    fn chunk(id: &[u8; 4], kind: Option<&[u8; 4]>, data: &[u8]) -> Vec<u8> {
+        let body = [kind.map_or(&[][..], |kind| &kind[..]), data].concat();
+        let mut chunk = [&id[..], &(body.len() as u32).to_le_bytes()[..], &body].concat();
+        if body.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    #[test]
+    fn test_builtin_decoder() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("tplay-builtin-{}.{name}", std::process::id()));
+
+        // Three 2x2 frames in 4:2:0, the second one red
+        let y4m = path("y4m");
+        let mut data = b"YUV4MPEG2 W2 H2 F25:1 Ip C420jpeg\n".to_vec();
+        for (y, u, v) in [(16, 128, 128), (81, 90, 240), (235, 128, 128)] {
+            data.extend(b"FRAME\n");
+            data.extend([y, y, y, y, u, v]);
+        }
+        fs::write(&y4m, &data).unwrap();
+        let mut video = BuiltinDecoder::open(y4m.to_str().unwrap()).unwrap();
+        assert_eq!(video.frame_count(), Some(3));
+        assert_eq!(video.duration(), Some(0.12));
+        video.seek(0.04);
+        let frame = video.next_frame().unwrap();
+        let [r, g, b, _] = frame.get_pixel(1, 1).0;
+        assert!(r > 250 && g < 5 && b < 5);
+        assert!(!video.skip_frames(2));
+        assert!(video.next_frame().is_none());
+        fs::remove_file(&y4m).unwrap();
+
+        // Two 1x2 uncompressed frames, bottom-up, the second chunk empty
+        let avi = path("avi");
+        let mut header = vec![0; 56];
+        header[..4].copy_from_slice(b"vids");
+        header[20..24].copy_from_slice(&1u32.to_le_bytes());
+        header[24..28].copy_from_slice(&10u32.to_le_bytes());
+        let mut format = vec![0; 40];
+        format[4..8].copy_from_slice(&1i32.to_le_bytes());
+        format[8..12].copy_from_slice(&2i32.to_le_bytes());
+        format[14..16].copy_from_slice(&24u16.to_le_bytes());
+        let stream = [chunk(b"strh", None, &header), chunk(b"strf", None, &format)].concat();
+        let headers = chunk(
+            b"LIST",
+            Some(b"hdrl"),
+            &chunk(b"LIST", Some(b"strl"), &stream),
+        );
+        let pixels = [255, 0, 0, 0, 0, 0, 255, 0];
+        let frames = [chunk(b"00dc", None, &pixels), chunk(b"00dc", None, &[])].concat();
+        let movi = chunk(b"LIST", Some(b"movi"), &frames);
+        fs::write(
+            &avi,
+            chunk(b"RIFF", Some(b"AVI "), &[headers, movi].concat()),
+        )
+        .unwrap();
+        let mut video = BuiltinDecoder::open(avi.to_str().unwrap()).unwrap();
+        assert_eq!(video.fps(), Some(10.0));
+        assert_eq!(video.frame_count(), Some(2));
+        let frame = video.next_frame().unwrap();
+        assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(0, 1).0, [0, 0, 255, 255]);
+        assert!(video.next_frame().is_some());
+        fs::remove_file(&avi).unwrap();
+
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])))
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+        let [r, g, b, _] = decode_jpeg(&jpeg).unwrap().get_pixel(4, 4).0;
+        assert!(r > 240 && g < 15 && b < 15);
+
+        let text = path("txt");
+        fs::write(&text, "not a video").unwrap();
+        assert!(BuiltinDecoder::open(text.to_str().unwrap()).is_err());
+        fs::remove_file(&text).unwrap();
+    }
+}
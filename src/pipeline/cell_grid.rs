@@ -0,0 +1,355 @@
+//! The `cell_grid` module defines `CellGrid`, the frame rendered by the image pipeline and drawn by
+//! the displays.
+//!
+//! A frame is a grid of cells, each holding a glyph (a grapheme cluster, possibly made of several
+//! code points or bytes) and its RGB color. Keeping the glyphs and their colors together in the
+//! same structure, row by row, avoids having to match the characters of a string with a separate
+//! array of colors, which goes wrong as soon as glyphs span several characters or line breaks are
+//! involved.
+//!
+//! Cells can also have a background color, for the render modes drawing two colors per cell
+//! (e.g. quadrant blocks). For the displays drawing pixels rather than characters (e.g. Sixel
+//! graphics), a grid can carry the pixels of the frame instead of cells. Cells showing the fully
+//! transparent pixels of the media are marked as such, and drawn in the default colors of the
+//! terminal.
+use image::RgbImage;
+
+/// A cell of a `CellGrid`: a glyph and its colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell<'a> {
+    /// The glyph (grapheme cluster) of the cell.
+    pub glyph: &'a str,
+    /// The RGB color of the glyph.
+    pub color: [u8; 3],
+    /// The RGB color behind the glyph, or `None` to keep the background of the terminal.
+    pub background: Option<[u8; 3]>,
+    /// Whether the cell shows a transparent pixel, its glyph being drawn in the default
+    /// foreground color of the terminal rather than `color`.
+    pub transparent: bool,
+}
+
+/// A rendered frame: a grid of `width` x `height` cells, stored row by row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellGrid {
+    /// The number of cells in each row.
+    width: u32,
+    /// The number of rows.
+    height: u32,
+    /// The glyphs of all the cells, one after the other.
+    glyphs: String,
+    /// The end of the glyph of each cell in `glyphs`, in bytes.
+    glyph_ends: Vec<usize>,
+    /// The color of each cell.
+    colors: Vec<[u8; 3]>,
+    /// The background color of each cell, empty until a cell with a background is pushed.
+    backgrounds: Vec<Option<[u8; 3]>>,
+    /// Whether each cell is transparent, empty until a transparent cell is pushed.
+    transparent: Vec<bool>,
+    /// Whether the text of the grid separates the rows with line breaks.
+    new_lines: bool,
+    /// The pixels of the frame, for the displays drawing pixels.
+    pixels: Option<RgbImage>,
+}
+
+impl CellGrid {
+    /// Constructs an empty grid of the given size, to be filled with `push`.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The number of cells in each row.
+    /// * `height` - The number of rows.
+    /// * `new_lines` - Whether the text of the grid separates the rows with line breaks (`\r\n`).
+    pub fn new(width: u32, height: u32, new_lines: bool) -> Self {
+        let cells = (width * height) as usize;
+        Self {
+            width,
+            height,
+            glyphs: String::with_capacity(cells),
+            glyph_ends: Vec::with_capacity(cells),
+            colors: Vec::with_capacity(cells),
+            backgrounds: Vec::new(),
+            transparent: Vec::new(),
+            new_lines,
+            pixels: None,
+        }
+    }
+
+    /// Constructs a grid holding the pixels of a frame instead of cells, for the displays drawing
+    /// pixels. The grid has no cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The number of cells covered by the pixels in each row.
+    /// * `height` - The number of rows covered by the pixels.
+    /// * `pixels` - The pixels of the frame.
+    pub fn from_pixels(width: u32, height: u32, pixels: RgbImage) -> Self {
+        Self {
+            width,
+            height,
+            pixels: Some(pixels),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the pixels of the frame, if the grid holds pixels instead of cells.
+    pub fn pixels(&self) -> Option<&RgbImage> {
+        self.pixels.as_ref()
+    }
+
+    /// Returns whether some cells of the grid have a background color.
+    pub fn has_backgrounds(&self) -> bool {
+        !self.backgrounds.is_empty()
+    }
+
+    /// Appends a cell to the grid, after the last one pushed. Cells beyond the size of the grid,
+    /// or pushed to a grid holding pixels, are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `glyph` - The glyph of the cell.
+    /// * `color` - The RGB color of the glyph.
+    pub fn push(&mut self, glyph: &str, color: [u8; 3]) {
+        self.push_cell(Cell {
+            glyph,
+            color,
+            background: None,
+            transparent: false,
+        });
+    }
+
+    /// Appends a glyph drawn over a background color to the grid, see `push`.
+    ///
+    /// # Arguments
+    ///
+    /// * `glyph` - The glyph of the cell.
+    /// * `color` - The RGB color of the glyph.
+    /// * `background` - The RGB color behind the glyph.
+    pub fn push_with_background(&mut self, glyph: &str, color: [u8; 3], background: [u8; 3]) {
+        self.push_cell(Cell {
+            glyph,
+            color,
+            background: Some(background),
+            transparent: false,
+        });
+    }
+
+    /// Appends a cell showing a transparent pixel to the grid, see `push`. The glyph is drawn in
+    /// the default colors of the terminal.
+    ///
+    /// # Arguments
+    ///
+    /// * `glyph` - The glyph of the cell.
+    pub fn push_transparent(&mut self, glyph: &str) {
+        self.push_cell(Cell {
+            glyph,
+            color: [0; 3],
+            background: None,
+            transparent: true,
+        });
+    }
+
+    /// Appends a cell to the grid, with its background color if it has one. Cells beyond the
+    /// size of the grid, or pushed to a grid holding pixels, are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - The cell, e.g. copied from another grid.
+    pub fn push_cell(&mut self, cell: Cell<'_>) {
+        if self.pixels.is_some() || self.len() >= (self.width * self.height) as usize {
+            return;
+        }
+        if cell.background.is_some() && self.backgrounds.len() < self.len() {
+            // The cells pushed before have no background
+            self.backgrounds.resize(self.len(), None);
+        }
+        if cell.background.is_some() || !self.backgrounds.is_empty() {
+            self.backgrounds.push(cell.background);
+        }
+        if cell.transparent && self.transparent.len() < self.len() {
+            self.transparent.resize(self.len(), false);
+        }
+        if cell.transparent || !self.transparent.is_empty() {
+            self.transparent.push(cell.transparent);
+        }
+        self.glyphs.push_str(cell.glyph);
+        self.glyph_ends.push(self.glyphs.len());
+        self.colors.push(cell.color);
+    }
+
+    /// Returns the number of cells in each row.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the number of rows.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the number of cells pushed so far.
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Returns whether no cell was pushed.
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Returns the cell at the given position, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column of the cell.
+    /// * `y` - The row of the cell.
+    pub fn cell(&self, x: u32, y: u32) -> Option<Cell<'_>> {
+        if x >= self.width {
+            return None;
+        }
+        self.cell_at((y * self.width + x) as usize)
+    }
+
+    /// Returns the cell with the given index, counting row by row.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the cell.
+    fn cell_at(&self, index: usize) -> Option<Cell<'_>> {
+        let end = *self.glyph_ends.get(index)?;
+        let start = index.checked_sub(1).map_or(0, |i| self.glyph_ends[i]);
+        Some(Cell {
+            glyph: &self.glyphs[start..end],
+            color: self.colors[index],
+            background: self.backgrounds.get(index).copied().flatten(),
+            transparent: self.transparent.get(index).copied().unwrap_or(false),
+        })
+    }
+
+    /// Returns the cells of the given row, from left to right.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - The row.
+    pub fn row(&self, y: u32) -> impl Iterator<Item = Cell<'_>> + '_ {
+        let start = (y * self.width) as usize;
+        (start..start + self.width as usize).map_while(|index| self.cell_at(index))
+    }
+
+    /// Returns the glyphs of the given row, as a string.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - The row.
+    pub fn row_text(&self, y: u32) -> &str {
+        let start = (y * self.width) as usize;
+        let end = (start + self.width as usize).min(self.len());
+        if start >= end {
+            return "";
+        }
+        let from = start.checked_sub(1).map_or(0, |i| self.glyph_ends[i]);
+        &self.glyphs[from..self.glyph_ends[end - 1]]
+    }
+
+    /// Returns all the cells, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = Cell<'_>> + '_ {
+        (0..self.len()).filter_map(|index| self.cell_at(index))
+    }
+
+    /// Returns the color of each cell, row by row.
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// Returns the grid with each row drawn several times, one under the other. Grids holding
+    /// pixels are returned as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `times` - The number of times each row is drawn.
+    pub fn repeat_rows(&self, times: u32) -> CellGrid {
+        if self.pixels.is_some() {
+            return self.clone();
+        }
+        let mut grid = CellGrid::new(self.width, self.height * times, self.new_lines);
+        for y in 0..self.height {
+            for _ in 0..times {
+                self.row(y).for_each(|cell| grid.push_cell(cell));
+            }
+        }
+        grid
+    }
+
+    /// Returns the text of the grid: the glyphs, row by row, with line breaks between the rows if
+    /// the grid was created with `new_lines`.
+    pub fn to_text(&self) -> String {
+        if !self.new_lines {
+            return self.glyphs.clone();
+        }
+        let rows: Vec<&str> = (0..self.height).map(|y| self.row_text(y)).collect();
+        rows.join("\r\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_grid() {
+        let mut grid = CellGrid::new(2, 2, true);
+        for (glyph, color) in [("a", 1), ("❤️", 2), ("😊", 3), ("b", 4), ("c", 5)] {
+            grid.push(glyph, [color; 3]);
+        }
+        // Cells beyond the size of the grid are ignored
+        assert_eq!(grid.len(), 4);
+
+        assert_eq!(
+            grid.cell(1, 0),
+            Some(Cell {
+                glyph: "❤️",
+                color: [2; 3],
+                background: None,
+                transparent: false,
+            })
+        );
+        assert_eq!(grid.cell(0, 1).map(|cell| cell.glyph), Some("😊"));
+        assert_eq!(grid.cell(2, 0), None);
+        assert_eq!(grid.cell(0, 2), None);
+
+        let row: Vec<&str> = grid.row(1).map(|cell| cell.glyph).collect();
+        assert_eq!(row, ["😊", "b"]);
+        assert_eq!(grid.row_text(0), "a❤️");
+        assert_eq!(grid.to_text(), "a❤️\r\n😊b");
+        assert_eq!(grid.repeat_rows(2).to_text(), "a❤️\r\na❤️\r\n😊b\r\n😊b");
+        assert_eq!(grid.colors().len(), grid.cells().count());
+
+        let mut grid = CellGrid::new(3, 2, false);
+        grid.push("x", [0; 3]);
+        grid.push_cell(Cell {
+            glyph: "▀",
+            color: [1; 3],
+            background: Some([2; 3]),
+            transparent: false,
+        });
+        assert_eq!(grid.cell(0, 0).and_then(|cell| cell.background), None);
+        assert_eq!(
+            grid.cell(1, 0).and_then(|cell| cell.background),
+            Some([2; 3])
+        );
+        assert_eq!(grid.row_text(0), "x▀");
+        assert!(grid.has_backgrounds());
+        grid.push_transparent(" ");
+        assert!(grid.cell(2, 0).is_some_and(|cell| cell.transparent));
+        assert!(!grid.cell(1, 0).unwrap().transparent);
+        let mut grid = CellGrid::new(3, 2, false);
+        grid.push("x", [0; 3]);
+        assert_eq!(grid.row_text(0), "x");
+        assert_eq!(grid.row_text(1), "");
+        assert_eq!(grid.to_text(), "x");
+
+        let mut grid = CellGrid::from_pixels(2, 1, RgbImage::new(20, 20));
+        grid.push("x", [0; 3]);
+        assert!(grid.is_empty());
+        assert_eq!(grid.pixels().map(|pixels| pixels.width()), Some(20));
+    }
+}
@@ -13,6 +13,24 @@
 //! * `BLACKWHITE`: 2 characters, a solid block and a space.
 //! * `BW_DOTTED`: 2 characters, a dotted block and a space.
 //! * `BRAILLE`: 16 characters, a braille-based gradient of solid blocks.
+//!
+//! The `CharMapRegistry` names these character maps, so they can be selected by name on the
+//! command line and cycled through during playback. Custom character maps can be registered too,
+//! and are read from `char_maps.conf` in the configuration directory (e.g.
+//! `~/.config/tplay/char_maps.conf` on Linux), where the character map editor (`e` during
+//! playback) saves them. Each line names a character map, quoting its characters from darkest to
+//! brightest, and replaces the built-in character map with the same name if any:
+//!
+//! ```text
+//! # Shades of dots
+//! dots = " .:oO@"
+//! ```
+use crate::common::errors::*;
+use std::{fs, path::Path};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Name of the custom character maps file, in the configuration directory.
+pub const CHAR_MAPS_FILE_NAME: &str = "char_maps.conf";
 
 // ASCII-127 Only
 pub const CHARS1: &str = r##" .:-=+*#%@"##; // 10 chars
@@ -26,3 +44,293 @@ pub const GRADIENT: &str = r#" ░▒▓█"#; // 5 chars
 pub const BLACKWHITE: &str = r#" █"#; // 2 chars
 pub const BW_DOTTED: &str = r#" ⣿"#; // 2 dotted block
 pub const BRAILLE: &str = r#" ··⣀⣀⣤⣤⣤⣀⡀⢀⠠⠔⠒⠑⠊⠉⠁"#; // 16 chars (braille-based)
+
+/// A named character map, the characters going from darkest to brightest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharMap {
+    /// The name used to select the character map.
+    pub name: String,
+    /// The lookup glyphs. Each glyph is a grapheme cluster, so that characters made of several
+    /// code points (e.g. emoji with a variation selector) fill a single cell.
+    pub chars: Vec<String>,
+}
+
+/// An ordered collection of named character maps. The default registry holds the built-in
+/// character maps, in the order they are selected with the digit keys during playback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharMapRegistry {
+    maps: Vec<CharMap>,
+}
+
+impl Default for CharMapRegistry {
+    fn default() -> Self {
+        let mut registry = Self { maps: Vec::new() };
+        for (name, chars) in [
+            ("ascii", CHARS1),
+            ("ascii_long", CHARS2),
+            ("ascii_extended", CHARS3),
+            ("solid", SOLID),
+            ("dotted", DOTTED),
+            ("gradient", GRADIENT),
+            ("blackwhite", BLACKWHITE),
+            ("bw_dotted", BW_DOTTED),
+            ("braille", BRAILLE),
+        ] {
+            registry.register(name, chars);
+        }
+        registry
+    }
+}
+
+impl CharMapRegistry {
+    /// Loads the built-in character maps, and the custom ones of `char_maps.conf` in the
+    /// configuration directory if it exists.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the custom character maps file cannot be read, or has a line that is
+    /// not a character map.
+    pub fn load() -> Result<Self, MyError> {
+        let mut registry = Self::default();
+        let Some(path) = custom_maps_path().filter(|path| path.is_file()) else {
+            return Ok(registry);
+        };
+        let error = |e: String| {
+            MyError::Application(format!(
+                "{ERROR_READING_CHAR_MAPS}: {}: {e}",
+                path.display()
+            ))
+        };
+        let text = fs::read_to_string(&path).map_err(|e| error(e.to_string()))?;
+        registry.apply(&text).map_err(error)?;
+        Ok(registry)
+    }
+
+    /// Registers the character maps of a custom character maps file.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The content of the file: `name = "characters"` lines, and `#` comments.
+    ///
+    /// # Returns
+    ///
+    /// A `Result`, or an error message naming the line that is not a character map.
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, chars) = parse_line(line)
+                .ok_or_else(|| format!("line {}: expected `name = \"characters\"`", number + 1))?;
+            self.register(name, chars);
+        }
+        Ok(())
+    }
+
+    /// Registers a character map, replacing the one with the same name if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the character map.
+    /// * `chars` - The lookup characters, from darkest to brightest.
+    ///
+    /// # Returns
+    ///
+    /// The index of the character map in the registry.
+    pub fn register(&mut self, name: &str, chars: &str) -> usize {
+        let map = CharMap {
+            name: name.to_string(),
+            chars: split_glyphs(chars),
+        };
+        match self.index_of(name) {
+            Some(index) => {
+                self.maps[index] = map;
+                index
+            }
+            None => {
+                self.maps.push(map);
+                self.maps.len() - 1
+            }
+        }
+    }
+
+    /// Returns an iterator over the character maps, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &CharMap> {
+        self.maps.iter()
+    }
+
+    /// Returns the number of character maps.
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// Returns whether the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    /// Looks up a character map by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the character map.
+    pub fn get(&self, name: &str) -> Option<&CharMap> {
+        self.index_of(name).map(|index| &self.maps[index])
+    }
+
+    /// Returns the index of the character map with the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the character map.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.maps.iter().position(|map| map.name == name)
+    }
+
+    /// Returns the character map given on the command line, with the name of the registered
+    /// character map it is or has the characters of, or `custom`.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - A character map name or a string of lookup characters.
+    pub fn named(&self, spec: &str) -> CharMap {
+        let chars = self.resolve(spec);
+        let name = self
+            .get(spec)
+            .or_else(|| self.maps.iter().find(|map| map.chars == chars))
+            .map_or("custom", |map| map.name.as_str());
+        CharMap {
+            name: name.to_string(),
+            chars,
+        }
+    }
+
+    /// Resolves a character map given on the command line, either the name of a registered
+    /// character map or the lookup characters themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - A character map name or a string of lookup characters.
+    pub fn resolve(&self, spec: &str) -> Vec<String> {
+        self.get(spec)
+            .map_or_else(|| split_glyphs(spec), |map| map.chars.clone())
+    }
+}
+
+/// Returns the path of the custom character maps file, in the configuration directory.
+pub fn custom_maps_path() -> Option<std::path::PathBuf> {
+    crate::common::utils::config_dir().map(|dir| dir.join(CHAR_MAPS_FILE_NAME))
+}
+
+/// Saves a character map to a custom character maps file, replacing the line of the character map
+/// with the same name if any. The other lines are kept as they are.
+///
+/// # Arguments
+///
+/// * `path` - The custom character maps file, created with its directory if needed.
+/// * `map` - The character map.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written.
+pub fn save_custom_map(path: &Path, map: &CharMap) -> Result<(), MyError> {
+    let error = |e: std::io::Error| {
+        MyError::Application(format!("{ERROR_SAVING_CHAR_MAP}: {}: {e}", path.display()))
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(error(e)),
+    };
+    let saved = format!("{} = \"{}\"", map.name, map.chars.concat());
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    match lines
+        .iter()
+        .position(|line| parse_line(line.trim()).is_some_and(|(name, _)| name == map.name))
+    {
+        Some(index) => lines[index] = saved,
+        None => lines.push(saved),
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    fs::write(path, lines.join("\n") + "\n").map_err(error)
+}
+
+/// Parses a line of a custom character maps file.
+///
+/// # Arguments
+///
+/// * `line` - The line, e.g. `dots = " .:oO@"`. The characters are those between the first and
+///   the last quote, so they may contain quotes themselves.
+///
+/// # Returns
+///
+/// The name and the characters of the character map, or `None` if the line is not one.
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let (name, chars) = line.split_once('=')?;
+    let chars = chars.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let name = name.trim();
+    (!name.is_empty() && !chars.is_empty()).then_some((name, chars))
+}
+
+/// Splits lookup characters into glyphs (grapheme clusters).
+///
+/// # Arguments
+///
+/// * `chars` - The lookup characters, from darkest to brightest.
+pub fn split_glyphs(chars: &str) -> Vec<String> {
+    chars.graphemes(true).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_map_registry() {
+        let mut registry = CharMapRegistry::default();
+        assert_eq!(registry.iter().next().unwrap().chars, split_glyphs(CHARS1));
+        assert_eq!(registry.resolve("gradient").len(), GRADIENT.chars().count());
+        assert_eq!(registry.resolve(" xX"), [" ", "x", "X"]);
+        // Emoji made of several code points stay whole
+        assert_eq!(registry.resolve("🍎❤️😊"), ["🍎", "❤️", "😊"]);
+
+        let len = registry.len();
+        assert_eq!(registry.register("custom", " oO"), len);
+        assert_eq!(registry.register("custom", " o"), len);
+        assert_eq!(registry.get("custom").unwrap().chars, [" ", "o"]);
+        assert_eq!(registry.named(CHARS1).name, "ascii");
+        assert_eq!(registry.named("xyz").name, "custom");
+    }
+
+    #[test]
+    fn test_custom_maps() {
+        let dir = std::env::temp_dir().join(format!("tplay-char-maps-{}", std::process::id()));
+        let path = dir.join(CHAR_MAPS_FILE_NAME);
+        let map = |name: &str, chars: &str| CharMap {
+            name: name.to_string(),
+            chars: split_glyphs(chars),
+        };
+        save_custom_map(&path, &map("dots", " .:oO")).unwrap();
+        save_custom_map(&path, &map("quotes", " '\"")).unwrap();
+        save_custom_map(&path, &map("dots", " .:oO@")).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "dots = \" .:oO@\"\nquotes = \" '\"\"\n");
+
+        let mut registry = CharMapRegistry::default();
+        registry.apply(&format!("# comment\n\n{text}")).unwrap();
+        assert_eq!(registry.resolve("dots"), split_glyphs(" .:oO@"));
+        assert_eq!(registry.resolve("quotes"), [" ", "'", "\""]);
+        assert_eq!(
+            registry.apply("dots = .:oO"),
+            Err("line 1: expected `name = \"characters\"`".to_string())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+//! Simulates or compensates color vision deficiencies (`--vf cvd=deuteranopia`), to check how
+//! colorblind viewers see the media, or to make its colors easier to tell apart for them.
+//!
+//! Simulating a deficiency maps the colors to those seen with it (Machado et al., 2009, at full
+//! severity). Compensating it ("daltonizing") shifts the color differences lost with the
+//! deficiency to the channels still seen (Fidaner et al., 2005). Both are applied in linear RGB,
+//! to the frames once resized, so they cost little.
+use image::DynamicImage;
+use std::{str::FromStr, sync::OnceLock};
+
+/// A color vision deficiency: one of the three kinds of cones of the eye is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    /// No red cones, red looks dark and close to green.
+    Protanopia,
+    /// No green cones, the most common deficiency: red and green look alike.
+    Deuteranopia,
+    /// No blue cones: blue and green look alike, and yellow looks pink.
+    Tritanopia,
+}
+
+impl Deficiency {
+    /// Returns the matrix mapping the linear RGB colors to those seen with the deficiency.
+    fn simulation(self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Protanopia => [
+                [0.152_286, 1.052_583, -0.204_868],
+                [0.114_503, 0.786_281, 0.099_216],
+                [-0.003_882, -0.048_116, 1.051_998],
+            ],
+            Deficiency::Deuteranopia => [
+                [0.367_322, 0.860_646, -0.227_968],
+                [0.280_085, 0.672_501, 0.047_413],
+                [-0.011_820, 0.042_940, 0.968_881],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255_528, -0.076_749, -0.178_779],
+                [-0.078_411, 0.930_809, 0.147_602],
+                [0.004_733, 0.691_367, 0.303_900],
+            ],
+        }
+    }
+
+    /// Returns the matrix spreading the color differences lost with the deficiency over the
+    /// channels still seen.
+    fn error_shift(self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Protanopia | Deficiency::Deuteranopia => {
+                [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]
+            }
+            Deficiency::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+        }
+    }
+}
+
+/// What the filter does about the deficiency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CvdMode {
+    /// Shows the colors as seen with the deficiency.
+    #[default]
+    Simulate,
+    /// Shifts the colors so that those confused with the deficiency look different.
+    Daltonize,
+}
+
+/// A color vision deficiency filter, as given with `--vf`: `cvd=<deficiency>[:<mode>]`, e.g.
+/// `cvd=deuteranopia` or `cvd=protanopia:daltonize`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CvdFilter {
+    /// The deficiency.
+    pub deficiency: Deficiency,
+    /// Whether the deficiency is simulated or compensated.
+    pub mode: CvdMode,
+    /// The matrix applied to the linear RGB colors.
+    matrix: [[f32; 3]; 3],
+}
+
+impl CvdFilter {
+    /// Constructs a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `deficiency` - The deficiency.
+    /// * `mode` - Whether the deficiency is simulated or compensated.
+    pub fn new(deficiency: Deficiency, mode: CvdMode) -> Self {
+        let simulation = deficiency.simulation();
+        let matrix = match mode {
+            CvdMode::Simulate => simulation,
+            // color + shift * (color - simulated), as a single matrix
+            CvdMode::Daltonize => {
+                let shift = deficiency.error_shift();
+                let mut matrix = [[0.0; 3]; 3];
+                for (row, matrix_row) in matrix.iter_mut().enumerate() {
+                    for (column, value) in matrix_row.iter_mut().enumerate() {
+                        let identity = |r: usize, c: usize| if r == c { 1.0 } else { 0.0 };
+                        let lost: f32 = (0..3)
+                            .map(|k| shift[row][k] * (identity(k, column) - simulation[k][column]))
+                            .sum();
+                        *value = identity(row, column) + lost;
+                    }
+                }
+                matrix
+            }
+        };
+        Self {
+            deficiency,
+            mode,
+            matrix,
+        }
+    }
+
+    /// Filters the colors of an image in place. The alpha channel is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The image, e.g. a frame resized to the target resolution.
+    pub fn apply(&self, img: &mut DynamicImage) {
+        match img {
+            DynamicImage::ImageRgb8(pixels) => pixels
+                .pixels_mut()
+                .for_each(|pixel| self.filter(&mut pixel.0)),
+            DynamicImage::ImageRgba8(pixels) => pixels.pixels_mut().for_each(|pixel| {
+                let [r, g, b, _] = &mut pixel.0;
+                let mut rgb = [*r, *g, *b];
+                self.filter(&mut rgb);
+                [*r, *g, *b] = rgb;
+            }),
+            _ => {
+                let mut rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+                self.apply(&mut rgb);
+                *img = rgb;
+            }
+        }
+    }
+
+    /// Filters an sRGB color.
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - The color, replaced with the filtered color.
+    fn filter(&self, rgb: &mut [u8; 3]) {
+        let (to_linear, to_srgb) = gamma_tables();
+        let linear = rgb.map(|channel| to_linear[channel as usize]);
+        for (channel, row) in rgb.iter_mut().zip(&self.matrix) {
+            let value: f32 = row.iter().zip(&linear).map(|(m, c)| m * c).sum();
+            let index = (value.clamp(0.0, 1.0) * (to_srgb.len() - 1) as f32).round() as usize;
+            *channel = to_srgb[index];
+        }
+    }
+}
+
+impl FromStr for CvdFilter {
+    type Err = String;
+
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "`{filter}` is not a video filter \
+                 (cvd=protanopia|deuteranopia|tritanopia, optionally :simulate or :daltonize)"
+            )
+        };
+        let options = filter
+            .trim()
+            .to_ascii_lowercase()
+            .strip_prefix("cvd=")
+            .ok_or_else(error)?
+            .to_string();
+        let (deficiency, mode) = options.split_once(':').unwrap_or((&options, "simulate"));
+        let deficiency = match deficiency {
+            "protanopia" | "protan" => Deficiency::Protanopia,
+            "deuteranopia" | "deutan" => Deficiency::Deuteranopia,
+            "tritanopia" | "tritan" => Deficiency::Tritanopia,
+            _ => return Err(error()),
+        };
+        let mode = match mode {
+            "simulate" => CvdMode::Simulate,
+            "daltonize" | "correct" => CvdMode::Daltonize,
+            _ => return Err(error()),
+        };
+        Ok(Self::new(deficiency, mode))
+    }
+}
+
+/// Number of entries of the table converting linear values back to sRGB, finer than 256 since
+/// the dark sRGB values are close together in linear RGB.
+const TO_SRGB_ENTRIES: usize = 4096;
+
+/// Returns the tables converting the sRGB values to linear RGB (0-1), and the linear values
+/// (sampled over 0-1) back to sRGB.
+fn gamma_tables() -> &'static ([f32; 256], Vec<u8>) {
+    static TABLES: OnceLock<([f32; 256], Vec<u8>)> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut to_linear = [0.0; 256];
+        for (value, linear) in to_linear.iter_mut().enumerate() {
+            let c = value as f32 / 255.0;
+            *linear = match c <= 0.04045 {
+                true => c / 12.92,
+                false => ((c + 0.055) / 1.055).powf(2.4),
+            };
+        }
+        let to_srgb = (0..TO_SRGB_ENTRIES)
+            .map(|index| {
+                let c = index as f32 / (TO_SRGB_ENTRIES - 1) as f32;
+                let srgb = match c <= 0.003_130_8 {
+                    true => c * 12.92,
+                    false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+                };
+                (srgb * 255.0).round() as u8
+            })
+            .collect();
+        (to_linear, to_srgb)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_cvd_filter() {
+        let filtered = |filter: &str, colors: &[[u8; 3]]| {
+            let filter: CvdFilter = filter.parse().unwrap();
+            let mut img =
+                DynamicImage::ImageRgb8(RgbImage::from_fn(colors.len() as u32, 1, |x, _| {
+                    image::Rgb(colors[x as usize])
+                }));
+            filter.apply(&mut img);
+            img.to_rgb8()
+                .pixels()
+                .map(|pixel| pixel.0)
+                .collect::<Vec<_>>()
+        };
+        let distance = |a: [u8; 3], b: [u8; 3]| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (*a as i32 - b as i32).abs())
+                .sum::<i32>()
+        };
+        let (red, green) = ([200, 60, 40], [90, 140, 40]);
+
+        // Grays are seen the same, red and green look alike with deuteranopia
+        let simulated = filtered("cvd=deuteranopia", &[[128; 3], red, green]);
+        for channel in simulated[0] {
+            assert!(channel.abs_diff(128) <= 1);
+        }
+        assert!(distance(simulated[1], simulated[2]) < distance(red, green) / 2);
+
+        // Daltonizing keeps them apart once simulated again
+        let corrected = filtered("cvd=deutan:daltonize", &[red, green]);
+        let seen = filtered("cvd=deuteranopia", &corrected);
+        assert!(distance(seen[0], seen[1]) > distance(simulated[1], simulated[2]));
+
+        assert!("cvd=tritanopia:simulate".parse::<CvdFilter>().is_ok());
+        assert!("cvd=blue".parse::<CvdFilter>().is_err());
+        assert!("blur=2".parse::<CvdFilter>().is_err());
+    }
+}
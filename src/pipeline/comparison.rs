@@ -0,0 +1,229 @@
+//! Renders split-screen A/B comparisons (`--ab`): the left half of each frame is rendered with one
+//! value of a setting and the right half with another, so that char maps or dithering methods can
+//! be compared side by side on live content.
+//!
+//! A comparison is given as `<setting>=<A>:<B>`, e.g. `charmap=ascii:braille` or
+//! `dither=ordered:floyd-steinberg`. Both halves are rendered from the whole frame with the same
+//! pipeline, the compared setting aside, and are separated by a divider. The value used by each
+//! half is shown in its top left corner.
+use super::{
+    braille::{BrailleOptions, Dither},
+    cell_grid::CellGrid,
+    char_maps::{split_glyphs, CharMapRegistry, CHARS1, CHARS2, CHARS3},
+    image_pipeline::ImagePipeline,
+};
+use crate::common::errors::*;
+use image::DynamicImage;
+use std::str::FromStr;
+
+/// Glyph of the divider between the two halves.
+const DIVIDER: &str = "│";
+/// Color of the divider and of the labels.
+const DIVIDER_COLOR: [u8; 3] = [255, 255, 255];
+/// Names of the built-in char maps as constants, also accepted.
+const CONSTANT_NAMES: [(&str, &str); 3] =
+    [("chars1", CHARS1), ("chars2", CHARS2), ("chars3", CHARS3)];
+
+/// The value of the compared setting used by one half.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Variant {
+    /// The character map.
+    CharMap(Vec<String>),
+    /// The dithering of braille dots.
+    Dither(Dither),
+    /// The gray level from which braille dots are lit.
+    Threshold(u8),
+}
+
+impl Variant {
+    /// Parses the value of a setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `setting` - The name of the setting.
+    /// * `value` - The value.
+    fn parse(setting: &str, value: &str) -> Result<Self, String> {
+        match setting {
+            "charmap" | "char-map" => {
+                // Names are case insensitive, other values are the lookup characters themselves
+                let name = value.to_ascii_lowercase();
+                let constant = CONSTANT_NAMES
+                    .iter()
+                    .find(|(constant, _)| *constant == name);
+                let registry = CharMapRegistry::default();
+                let chars = match (registry.get(&name), constant) {
+                    (Some(map), _) => map.chars.clone(),
+                    (None, Some((_, chars))) => split_glyphs(chars),
+                    (None, None) => split_glyphs(value),
+                };
+                Ok(Variant::CharMap(chars))
+            }
+            "dither" => value.parse().map(Variant::Dither),
+            "threshold" => value
+                .parse()
+                .map(Variant::Threshold)
+                .map_err(|_| format!("`{value}` is not a gray level (0-255)")),
+            _ => Err(format!(
+                "`{setting}` cannot be compared (charmap, dither, threshold)"
+            )),
+        }
+    }
+
+    /// Applies the value to a pipeline. Dithering and threshold switch it to braille dots.
+    ///
+    /// # Arguments
+    ///
+    /// * `pipeline` - The pipeline rendering the half.
+    fn apply(&self, pipeline: &mut ImagePipeline) {
+        let braille = pipeline.braille.unwrap_or_default();
+        match self {
+            Variant::CharMap(chars) => {
+                pipeline.char_map = chars.clone();
+                pipeline.braille = None;
+                pipeline.blocks = None;
+                pipeline.background_glyph = None;
+            }
+            Variant::Dither(dither) => {
+                pipeline.braille = Some(BrailleOptions {
+                    dither: *dither,
+                    ..braille
+                })
+            }
+            Variant::Threshold(threshold) => {
+                pipeline.braille = Some(BrailleOptions {
+                    threshold: *threshold,
+                    ..braille
+                })
+            }
+        }
+    }
+}
+
+/// A split-screen comparison of two values of a setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The values used by the left and right halves.
+    variants: [Variant; 2],
+    /// The values as given on the command line, shown on each half.
+    labels: [String; 2],
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{spec}` is not a comparison (e.g. charmap=ascii:braille)");
+        let (setting, values) = spec.split_once('=').ok_or_else(invalid)?;
+        let (a, b) = values.split_once(':').ok_or_else(invalid)?;
+        let setting = setting.trim().to_ascii_lowercase();
+        Ok(Self {
+            variants: [Variant::parse(&setting, a)?, Variant::parse(&setting, b)?],
+            labels: [a.to_string(), b.to_string()],
+        })
+    }
+}
+
+impl Comparison {
+    /// Renders the image at the target resolution of the pipeline, the left half with the first
+    /// value and the right half with the second one.
+    ///
+    /// # Arguments
+    ///
+    /// * `pipeline` - The pipeline whose settings are compared.
+    /// * `img` - The image to render.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CellGrid` of the comparison.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the image cannot be rendered, see `ImagePipeline::render`.
+    pub fn render(
+        &self,
+        pipeline: &ImagePipeline,
+        img: &DynamicImage,
+    ) -> Result<CellGrid, MyError> {
+        let mut halves = Vec::with_capacity(2);
+        for variant in &self.variants {
+            let mut half = pipeline.clone();
+            half.comparison = None;
+            half.pixels_per_cell = None;
+            variant.apply(&mut half);
+            halves.push(half.render(img)?);
+        }
+        Ok(self.compose(&halves[0], &halves[1], pipeline.new_lines))
+    }
+
+    /// Joins the left half of the first grid and the right half of the second one, with the
+    /// divider and the labels.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - The frame rendered with the first value.
+    /// * `right` - The frame rendered with the second value, of the same size.
+    /// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+    fn compose(&self, left: &CellGrid, right: &CellGrid, new_lines: bool) -> CellGrid {
+        let (width, height) = (left.width(), left.height());
+        let split = width / 2;
+        // The labels are drawn over the first row
+        let mut top_row: Vec<Option<char>> = vec![None; width as usize];
+        for (label, start, end) in [
+            (&self.labels[0], 0, split),
+            (&self.labels[1], split + 1, width),
+        ] {
+            let text = format!(" {label} ");
+            for (x, c) in (start..end).zip(text.chars()) {
+                top_row[x as usize] = Some(c);
+            }
+        }
+
+        let mut grid = CellGrid::new(width, height, new_lines);
+        let mut buffer = [0; 4];
+        for y in 0..height {
+            for x in 0..width {
+                let label = top_row[x as usize].filter(|_| y == 0);
+                let cell = match x.cmp(&split) {
+                    std::cmp::Ordering::Less => left.cell(x, y),
+                    std::cmp::Ordering::Equal => None,
+                    std::cmp::Ordering::Greater => right.cell(x, y),
+                };
+                match (label, cell) {
+                    (Some(c), _) => grid.push(c.encode_utf8(&mut buffer), DIVIDER_COLOR),
+                    (None, Some(cell)) => grid.push_cell(cell),
+                    (None, None) => grid.push(DIVIDER, DIVIDER_COLOR),
+                }
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comparison() {
+        let comparison: Comparison = "charmap=CHARS1:braille".parse().unwrap();
+        assert_eq!(
+            comparison.variants[0],
+            Variant::CharMap(split_glyphs(CHARS1))
+        );
+        let comparison: Comparison = "dither=ordered:threshold".parse().unwrap();
+        assert_eq!(comparison.variants[1], Variant::Dither(Dither::Threshold));
+
+        assert!("charmap=ascii".parse::<Comparison>().is_err());
+        assert!("speed=1:2".parse::<Comparison>().is_err());
+        assert!("threshold=10:999".parse::<Comparison>().is_err());
+    }
+
+    #[test]
+    fn test_render_comparison() {
+        let mut pipeline = ImagePipeline::new((9, 2), split_glyphs("a"), false);
+        pipeline.comparison = Some("charmap=x:y".parse().unwrap());
+        let grid = pipeline.render(&DynamicImage::new_rgb8(9, 2)).unwrap();
+        assert_eq!(grid.row_text(0), " x x│ y y");
+        assert_eq!(grid.row_text(1), "xxxx│yyyy");
+    }
+}
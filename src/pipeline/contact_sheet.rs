@@ -0,0 +1,281 @@
+//! Renders contact sheets: a grid of evenly spaced frames of a video, to preview its content at a
+//! glance (`tplay sheet`).
+//!
+//! `sample_frames` picks the frames, in the middle of evenly sized segments of the media, and
+//! `render_sheet` lays them out in a single `CellGrid`, each tile followed by a line with its
+//! timestamp.
+use super::{cell_grid::CellGrid, frames::FrameIterator, image_pipeline::ImagePipeline};
+use crate::common::errors::*;
+use image::DynamicImage;
+
+/// Number of blank columns between two tiles.
+const GUTTER: u32 = 1;
+/// Color of the timestamps below the tiles.
+const LABEL_COLOR: [u8; 3] = [255, 255, 255];
+/// Color of the timestamp of the selected tile.
+const SELECTED_LABEL_COLOR: [u8; 3] = [255, 200, 0];
+/// Marks the timestamp of the selected tile.
+const SELECTED_MARKER: &str = "▶ ";
+
+/// A frame picked for the contact sheet.
+#[derive(Debug, Clone)]
+pub struct SampledFrame {
+    /// The time of the frame from the start of the media, in seconds, if known.
+    pub time: Option<f64>,
+    /// The frame.
+    pub image: DynamicImage,
+}
+
+/// Picks evenly spaced frames of the media: the frame in the middle of each of `count` segments
+/// of equal length. Media with fewer frames give fewer frames, single images and live streams
+/// give a single frame.
+///
+/// # Arguments
+///
+/// * `media` - The media to sample, its position is changed.
+/// * `count` - The number of frames to pick.
+/// * `fps` - The frame rate of the media, used to compute the time of the frames.
+///
+/// # Returns
+///
+/// The frames picked, in order.
+pub fn sample_frames(media: &mut FrameIterator, count: usize, fps: f64) -> Vec<SampledFrame> {
+    let time = |index: usize| Some(index as f64 / fps);
+    match media {
+        FrameIterator::Image(_)
+        | FrameIterator::Progressive(_)
+        | FrameIterator::Live(_)
+        | FrameIterator::Still { .. } => media
+            .next()
+            .map(|image| SampledFrame { time: None, image })
+            .into_iter()
+            .collect(),
+        FrameIterator::AnimatedImage { frames, .. } => sample_positions(frames.len(), count)
+            .map(|index| SampledFrame {
+                time: time(index),
+                image: frames[index].clone(),
+            })
+            .collect(),
+        FrameIterator::Animation(animation) => sample_positions(animation.len(), count)
+            .filter_map(|index| {
+                animation.frame(index).map(|image| SampledFrame {
+                    time: time(index),
+                    image,
+                })
+            })
+            .collect(),
+        FrameIterator::Slideshow(slideshow) => sample_positions(slideshow.len(), count)
+            .map(|index| SampledFrame {
+                time: time(index * slideshow.slide_frames()),
+                image: DynamicImage::ImageRgb8(slideshow.slide(index, None)),
+            })
+            .collect(),
+        FrameIterator::Video(video) => {
+            let Some(total) = video.frame_count() else {
+                // The length is unknown (e.g. a stream), use the first frames
+                return media
+                    .take(count)
+                    .enumerate()
+                    .map(|(index, image)| SampledFrame {
+                        time: time(index),
+                        image,
+                    })
+                    .collect();
+            };
+            sample_positions(total, count)
+                .filter_map(|index| {
+                    video.seek_frame(index);
+                    video.next_frame().map(|image| SampledFrame {
+                        time: time(index),
+                        image,
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Returns the index of the middle frame of each of `count` segments of equal length.
+///
+/// # Arguments
+///
+/// * `total` - The number of frames of the media.
+/// * `count` - The number of segments, capped to the number of frames.
+fn sample_positions(total: usize, count: usize) -> impl Iterator<Item = usize> {
+    let count = count.min(total);
+    (0..count).map(move |i| (2 * i + 1) * total / (2 * count))
+}
+
+/// Lays out the frames in a grid of tiles, row by row, each tile followed by the timestamp of
+/// its frame.
+///
+/// # Arguments
+///
+/// * `frames` - The frames to show.
+/// * `columns` - The number of tiles in each row.
+/// * `size` - The size of the contact sheet (width, height), in characters.
+/// * `char_map` - The character lookup table used to draw the frames.
+/// * `selected` - The index of the frame to highlight, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the contact sheet.
+///
+/// # Errors
+///
+/// Returns an error if there are no frames, or if the sheet is too small to fit them.
+pub fn render_sheet(
+    frames: &[SampledFrame],
+    columns: u32,
+    size: (u32, u32),
+    char_map: Vec<String>,
+    selected: Option<usize>,
+) -> Result<CellGrid, MyError> {
+    if frames.is_empty() {
+        return Err(MyError::Application(ERROR_NO_FRAMES.to_string()));
+    }
+    let columns = columns.clamp(1, frames.len() as u32);
+    let rows = (frames.len() as u32).div_ceil(columns);
+    let (width, height) = size;
+    let tile_width = width.saturating_sub((columns - 1) * GUTTER) / columns;
+    // Each tile is followed by the line of its timestamp
+    let tile_height = (height / rows).saturating_sub(1);
+    if tile_width == 0 || tile_height == 0 {
+        return Err(MyError::Application(format!(
+            "{ERROR_SHEET_TOO_SMALL}: {} frames in {width}x{height}",
+            frames.len()
+        )));
+    }
+
+    let pipeline = ImagePipeline::new((tile_width, tile_height), char_map, false);
+    let tiles = frames
+        .iter()
+        .map(|frame| Ok(pipeline.to_cells(&pipeline.resize(&frame.image)?)))
+        .collect::<Result<Vec<CellGrid>, MyError>>()?;
+    let labels: Vec<String> = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let time = frame.time.map(format_timestamp).unwrap_or_default();
+            if selected == Some(index) {
+                format!("{SELECTED_MARKER}{time}")
+            } else {
+                time
+            }
+        })
+        .collect();
+
+    let sheet_width = columns * tile_width + (columns - 1) * GUTTER;
+    let mut sheet = CellGrid::new(sheet_width, rows * (tile_height + 1), false);
+    let push_blank = |sheet: &mut CellGrid, cells: u32| {
+        for _ in 0..cells {
+            sheet.push(" ", [0; 3]);
+        }
+    };
+    for (tile_row, row_tiles) in tiles.chunks(columns as usize).enumerate() {
+        for y in 0..tile_height {
+            for column in 0..columns as usize {
+                if column > 0 {
+                    push_blank(&mut sheet, GUTTER);
+                }
+                match row_tiles.get(column) {
+                    Some(tile) => tile.row(y).for_each(|cell| sheet.push_cell(cell)),
+                    None => push_blank(&mut sheet, tile_width),
+                }
+            }
+        }
+        for column in 0..columns as usize {
+            if column > 0 {
+                push_blank(&mut sheet, GUTTER);
+            }
+            let index = tile_row * columns as usize + column;
+            let label = labels.get(index).map_or("", String::as_str);
+            let color = if selected == Some(index) {
+                SELECTED_LABEL_COLOR
+            } else {
+                LABEL_COLOR
+            };
+            let mut buffer = [0; 4];
+            let mut pushed = 0;
+            for c in label.chars().take(tile_width as usize) {
+                sheet.push(c.encode_utf8(&mut buffer), color);
+                pushed += 1;
+            }
+            push_blank(&mut sheet, tile_width - pushed);
+        }
+    }
+    Ok(sheet)
+}
+
+/// Formats a time as `H:MM:SS`.
+///
+/// # Arguments
+///
+/// * `seconds` - The time, in seconds.
+fn format_timestamp(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::char_maps::split_glyphs;
+
+    #[test]
+    fn test_sample_frames() {
+        assert_eq!(
+            sample_positions(100, 4).collect::<Vec<_>>(),
+            [12, 37, 62, 87]
+        );
+        assert_eq!(sample_positions(2, 4).collect::<Vec<_>>(), [0, 1]);
+
+        let frames = (0..10)
+            .map(|i| DynamicImage::new_rgb8(i + 1, 1))
+            .collect::<Vec<_>>();
+        let mut media = FrameIterator::AnimatedImage {
+            frames,
+            current_frame: 0,
+        };
+        let sampled = sample_frames(&mut media, 2, 10.0);
+        let picked: Vec<(Option<f64>, u32)> = sampled
+            .iter()
+            .map(|frame| (frame.time, frame.image.width()))
+            .collect();
+        assert_eq!(picked, [(Some(0.2), 3), (Some(0.7), 8)]);
+    }
+
+    #[test]
+    fn test_render_sheet() {
+        let frame = |time| SampledFrame {
+            time: Some(time),
+            image: DynamicImage::new_rgb8(4, 4),
+        };
+        let frames = [frame(0.0), frame(61.0), frame(3600.0)];
+        let sheet = render_sheet(&frames, 2, (21, 10), split_glyphs("x"), Some(1)).unwrap();
+        // Two tiles of 10x4 per row, separated by one column, each followed by its timestamp
+        assert_eq!((sheet.width(), sheet.height()), (21, 10));
+        assert_eq!(
+            sheet.row_text(0),
+            format!("{} {}", "x".repeat(10), "x".repeat(10))
+        );
+        assert_eq!(sheet.row_text(4), "0:00:00    ▶ 0:01:01 ");
+        assert_eq!(
+            sheet.cell(13, 4).map(|cell| cell.color),
+            Some(SELECTED_LABEL_COLOR)
+        );
+        assert_eq!(
+            sheet.row_text(5),
+            format!("{}{}", "x".repeat(10), " ".repeat(11))
+        );
+        assert_eq!(sheet.row_text(9).trim_end(), "1:00:00");
+
+        assert!(render_sheet(&frames, 3, (4, 10), split_glyphs("x"), None).is_err());
+        assert!(render_sheet(&[], 3, (80, 24), split_glyphs("x"), None).is_err());
+    }
+}
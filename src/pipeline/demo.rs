@@ -0,0 +1,133 @@
+//! Plays a short animation built into tplay (`tplay demo`), to check that the terminal shows the
+//! frames, their colors and their shades before looking into the decoders or the audio.
+//!
+//! The frames are drawn by the code, so the demo needs no file, no decoder and no external tool:
+//! a rainbow scrolling across the top of the frame, a ball bouncing over it, and a ramp of gray
+//! levels along the bottom. Smooth colors and evenly spaced shades mean the terminal is set up.
+use super::frames::{FrameIterator, MediaData};
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// The input naming the demo, played by `tplay demo`.
+pub const DEMO_INPUT: &str = "tplay:demo";
+/// Frame rate of the demo.
+const DEMO_FPS: f64 = 24.0;
+/// Number of frames of the demo, five seconds.
+const DEMO_FRAMES: u32 = 120;
+/// Size of the frames (width, height), in pixels. Roughly the aspect of a terminal.
+const DEMO_SIZE: (u32, u32) = (128, 72);
+/// Number of gray levels of the ramp.
+const GRAY_STEPS: u32 = 16;
+/// Radius of the ball, in pixels.
+const BALL_RADIUS: f64 = 7.0;
+/// Lines written below the frames.
+const DEMO_CAPTION: [&str; 2] = [
+    "tplay demo",
+    "smooth colors and evenly spaced shades: the terminal is ready",
+];
+
+/// Returns whether the given input is the built-in demo.
+///
+/// # Arguments
+///
+/// * `path` - The path or URL of the input.
+pub fn is_demo(path: &str) -> bool {
+    path == DEMO_INPUT
+}
+
+/// Opens the built-in demo, drawing its frames.
+///
+/// # Returns
+///
+/// The media of the demo, played like an animated image.
+pub fn open() -> MediaData {
+    MediaData {
+        frame_iter: FrameIterator::AnimatedImage {
+            frames: (0..DEMO_FRAMES).map(frame).collect(),
+            current_frame: 0,
+        },
+        fps: Some(DEMO_FPS),
+        audio_path: None,
+        duration: Some(DEMO_FRAMES as f64 / DEMO_FPS),
+        video_path: None,
+        caption: DEMO_CAPTION.iter().map(|line| line.to_string()).collect(),
+    }
+}
+
+/// Draws a frame of the demo.
+///
+/// # Arguments
+///
+/// * `index` - The number of the frame, from 0.
+fn frame(index: u32) -> DynamicImage {
+    let (width, height) = DEMO_SIZE;
+    let ramp_top = height * 3 / 4;
+    let t = index as f64 / DEMO_FRAMES as f64;
+    // The ball crosses the frame and back, bouncing on the ramp halfway
+    let ball_x = BALL_RADIUS + (width as f64 - 2.0 * BALL_RADIUS) * (1.0 - (2.0 * t - 1.0).abs());
+    let bounce = (t * 2.0 * std::f64::consts::PI).sin().abs();
+    let ball_y = ramp_top as f64 - BALL_RADIUS - bounce * (ramp_top as f64 - 2.0 * BALL_RADIUS);
+
+    DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f64 + 0.5 - ball_x, y as f64 + 0.5 - ball_y);
+        if dx * dx + dy * dy <= BALL_RADIUS * BALL_RADIUS {
+            return Rgb([255, 255, 255]);
+        }
+        if y >= ramp_top {
+            let level = (x * GRAY_STEPS / width * 255 / (GRAY_STEPS - 1)) as u8;
+            return Rgb([level; 3]);
+        }
+        // Full colors in the middle, darker towards the top and the ramp
+        let hue = (x as f64 / width as f64 + t).fract();
+        let value = 1.0 - ((y as f64 / ramp_top as f64) * 2.0 - 1.0).abs() * 0.8;
+        hue_to_rgb(hue, value)
+    }))
+}
+
+/// Returns the fully saturated color of a hue.
+///
+/// # Arguments
+///
+/// * `hue` - The hue, from 0 to 1 (red, yellow, green, cyan, blue, magenta, and back to red).
+/// * `value` - The brightness, from 0 to 1.
+fn hue_to_rgb(hue: f64, value: f64) -> Rgb<u8> {
+    let sector = hue * 6.0;
+    let rising = sector.fract();
+    let (r, g, b) = match sector as u32 {
+        0 => (1.0, rising, 0.0),
+        1 => (1.0 - rising, 1.0, 0.0),
+        2 => (0.0, 1.0, rising),
+        3 => (0.0, 1.0 - rising, 1.0),
+        4 => (rising, 0.0, 1.0),
+        _ => (1.0, 0.0, 1.0 - rising),
+    };
+    Rgb([r, g, b].map(|channel| (channel * value * 255.0).round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo() {
+        let media = open();
+        assert_eq!(media.duration, Some(5.0));
+        let frames: Vec<DynamicImage> = media.frame_iter.collect();
+        assert_eq!(frames.len(), DEMO_FRAMES as usize);
+
+        // The ramp goes from black to white, below the rainbow
+        let first = frames[0].to_rgb8();
+        let (width, height) = DEMO_SIZE;
+        assert_eq!(first.get_pixel(0, height - 1).0, [0; 3]);
+        assert_eq!(first.get_pixel(width - 1, height - 1).0, [255; 3]);
+        assert_eq!(hue_to_rgb(0.0, 1.0).0, [255, 0, 0]);
+        assert_eq!(hue_to_rgb(0.5, 1.0).0, [0, 255, 255]);
+
+        // The rainbow scrolls
+        assert_ne!(
+            first.get_pixel(width / 2, 4),
+            frames[1].to_rgb8().get_pixel(width / 2, 4)
+        );
+        assert!(is_demo(DEMO_INPUT));
+        assert!(!is_demo("demo.gif"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Reduces the gray levels of an image to the few levels of a character map, optionally with
+//! dithering, so that small char maps (e.g. `BLACKWHITE` or `BRAILLE`) keep the impression of
+//! shades instead of banding.
+//!
+//! The dithering methods are shared with the braille dots, which reduce the gray levels to lit and
+//! unlit dots (see `braille`).
+use image::GrayImage;
+use std::str::FromStr;
+
+/// The 4x4 Bayer matrix used for ordered dithering.
+pub(crate) const BAYER_4X4: [[i32; 4]; 4] =
+    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// How gray levels are reduced to fewer levels (the glyphs of a char map, or lit and unlit dots).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// Each pixel takes the level of its own gray level, without dithering.
+    Threshold,
+    /// Ordered dithering with a Bayer matrix: a regular pattern, stable from frame to frame.
+    Ordered,
+    /// Floyd-Steinberg error diffusion: the most detailed, but the pattern changes with the
+    /// slightest change of the image.
+    #[default]
+    FloydSteinberg,
+}
+
+impl Dither {
+    /// Returns the method following this one, to cycle through them during playback.
+    pub fn next(self) -> Self {
+        match self {
+            Dither::Threshold => Dither::Ordered,
+            Dither::Ordered => Dither::FloydSteinberg,
+            Dither::FloydSteinberg => Dither::Threshold,
+        }
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "threshold" | "none" => Ok(Dither::Threshold),
+            "ordered" | "bayer" => Ok(Dither::Ordered),
+            "floyd-steinberg" | "floyd" | "fs" => Ok(Dither::FloydSteinberg),
+            _ => Err(format!(
+                "`{name}` is not a dithering method (none, ordered, floyd-steinberg)"
+            )),
+        }
+    }
+}
+
+/// Returns the level of each pixel of the image, row by row, out of the given number of levels.
+/// Without dithering, the gray levels are split in ranges of equal size, one per level.
+///
+/// # Arguments
+///
+/// * `gray` - The image, in gray levels.
+/// * `count` - The number of levels, e.g. the length of the char map.
+/// * `dither` - The dithering method.
+///
+/// # Returns
+///
+/// The level of each pixel, from 0 (darkest) to `count - 1`.
+pub fn levels(gray: &GrayImage, count: usize, dither: Dither) -> Vec<usize> {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    if count < 2 {
+        return vec![0; width * height];
+    }
+    let max = count as i32 - 1;
+    // The level nearest to a gray level, and the gray level shown by a level
+    let nearest = |value: i32| ((value.clamp(0, 255) * max + 127) / 255) as usize;
+    let shown = |level: usize| level as i32 * 255 / max;
+    match dither {
+        Dither::Threshold => gray
+            .pixels()
+            .map(|p| count * p[0] as usize / (u8::MAX as usize + 1))
+            .collect(),
+        Dither::Ordered => gray
+            .enumerate_pixels()
+            .map(|(x, y, p)| {
+                // The matrix offsets the gray levels by up to half a level either way
+                let offset = BAYER_4X4[y as usize % 4][x as usize % 4] * 2 - 15;
+                let scaled = p[0] as i32 * max * 32 + offset * 255 + 255 * 16;
+                (scaled / (255 * 32)).clamp(0, max) as usize
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            let mut values: Vec<i32> = gray.pixels().map(|p| p[0] as i32).collect();
+            let mut levels = vec![0; values.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let index = y * width + x;
+                    let value = values[index].clamp(0, 255);
+                    levels[index] = nearest(value);
+                    let error = value - shown(levels[index]);
+                    let mut spread = |dx: isize, dy: usize, weight: i32| {
+                        let nx = x as isize + dx;
+                        if nx >= 0 && (nx as usize) < width && y + dy < height {
+                            values[(y + dy) * width + nx as usize] += error * weight / 16;
+                        }
+                    };
+                    spread(1, 0, 7);
+                    spread(-1, 1, 3);
+                    spread(0, 1, 5);
+                    spread(1, 1, 1);
+                }
+            }
+            levels
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_levels() {
+        let gray = GrayImage::from_pixel(8, 8, Luma([64]));
+        let count = |dither| {
+            levels(&gray, 2, dither)
+                .into_iter()
+                .filter(|&level| level == 1)
+                .count()
+        };
+        // A quarter of the pixels take the bright level with dithering, none without
+        assert_eq!(count(Dither::Threshold), 0);
+        assert_eq!(count(Dither::Ordered), 16);
+        assert!((12..=20).contains(&count(Dither::FloydSteinberg)));
+
+        let gradient = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        for dither in [Dither::Threshold, Dither::Ordered, Dither::FloydSteinberg] {
+            let levels = levels(&gradient, 10, dither);
+            assert_eq!((levels[0], levels[255]), (0, 9));
+            assert!(levels.iter().all(|&level| level < 10));
+        }
+        assert_eq!(levels(&gray, 1, Dither::Ordered), vec![0; 64]);
+        assert_eq!("fs".parse(), Ok(Dither::FloydSteinberg));
+        assert_eq!(Dither::FloydSteinberg.next(), Dither::Threshold);
+    }
+}
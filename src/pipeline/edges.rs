@@ -0,0 +1,148 @@
+//! Draws the edges of the frames with directional glyphs (`--edges`), for line art that is much
+//! easier to recognize than shades alone, e.g. with cartoons and screen captures.
+//!
+//! The image is sampled at 2x4 pixels per cell, square pixels for cells twice as tall as wide,
+//! and a Sobel operator measures the gradient of each sample. The gradients of a cell are averaged
+//! into its strongest orientation: cells with a strong enough edge are drawn with the glyph
+//! following it (`/ \ | - _`), the other cells keep the glyph of their brightness.
+use super::cell_grid::{Cell, CellGrid};
+use image::GrayImage;
+
+/// Samples of the image per cell (width, height) the gradients are measured at.
+pub const SAMPLES_PER_CELL: (u32, u32) = (2, 4);
+/// Strength of the gradients (0-255) from which edges are drawn, by default.
+pub const DEFAULT_EDGE_THRESHOLD: u8 = 48;
+
+/// Replaces the glyphs of the cells crossed by an edge with the glyph following its orientation,
+/// keeping their colors. Frames holding pixels are left as they are.
+///
+/// # Arguments
+///
+/// * `grid` - The frame.
+/// * `samples` - The image in gray levels, of `SAMPLES_PER_CELL` pixels per cell.
+/// * `threshold` - The strength of the gradients (0-255) from which edges are drawn.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// The frame with its edges.
+pub fn draw_edges(grid: CellGrid, samples: &GrayImage, threshold: u8, new_lines: bool) -> CellGrid {
+    if grid.pixels().is_some() {
+        return grid;
+    }
+    let mut edged = CellGrid::new(grid.width(), grid.height(), new_lines);
+    for y in 0..grid.height() {
+        for (x, cell) in grid.row(y).enumerate() {
+            match edge_glyph(samples, (x as u32, y), threshold) {
+                Some(glyph) => edged.push_cell(Cell { glyph, ..cell }),
+                None => edged.push_cell(cell),
+            }
+        }
+    }
+    edged
+}
+
+/// Returns the glyph following the edge crossing a cell, or `None` if its gradients are too weak.
+///
+/// # Arguments
+///
+/// * `samples` - The image in gray levels, of `SAMPLES_PER_CELL` pixels per cell.
+/// * `cell` - The column and row of the cell.
+/// * `threshold` - The strength of the gradients (0-255) from which edges are drawn.
+fn edge_glyph(samples: &GrayImage, cell: (u32, u32), threshold: u8) -> Option<&'static str> {
+    let (cell_width, cell_height) = SAMPLES_PER_CELL;
+    // The structure tensor of the cell, and the strength of its gradients by row
+    let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+    let (mut strength, mut rows) = (0.0, 0.0);
+    for row in 0..cell_height {
+        for column in 0..cell_width {
+            let x = cell.0 * cell_width + column;
+            let y = cell.1 * cell_height + row;
+            let (gx, gy) = sobel(samples, x, y);
+            (xx, xy, yy) = (xx + gx * gx, xy + gx * gy, yy + gy * gy);
+            let magnitude = (gx * gx + gy * gy).sqrt();
+            strength += magnitude;
+            rows += magnitude * row as f32;
+        }
+    }
+    // Across a sharp edge the gradients reach 4 x 255, on about half the samples of a cell
+    let samples_per_cell = (cell_width * cell_height) as f32;
+    if strength / samples_per_cell / 2.0 < threshold.max(1) as f32 {
+        return None;
+    }
+    // Averaging the doubled angles, the gradients on both sides of a line agree
+    let gradient = 0.5 * (2.0 * xy).atan2(xx - yy);
+    // Edges run across the gradients, in degrees from the x axis (y pointing down)
+    let edge = (gradient.to_degrees() + 90.0).rem_euclid(180.0);
+    let glyph = match edge {
+        edge if !(22.5..157.5).contains(&edge) => {
+            // Horizontal edges in the bottom of the cell are drawn at its bottom
+            let row = rows / strength;
+            if row > (cell_height - 1) as f32 * 0.6 {
+                "_"
+            } else {
+                "-"
+            }
+        }
+        edge if edge < 67.5 => "\\",
+        edge if edge < 112.5 => "|",
+        _ => "/",
+    };
+    Some(glyph)
+}
+
+/// Returns the horizontal and vertical gradients of the image at a pixel, with the Sobel
+/// operator. The pixels beyond the borders repeat the border.
+///
+/// # Arguments
+///
+/// * `image` - The image in gray levels.
+/// * `x` - The column of the pixel.
+/// * `y` - The row of the pixel.
+fn sobel(image: &GrayImage, x: u32, y: u32) -> (f32, f32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return (0.0, 0.0);
+    }
+    let pixel = |dx: i32, dy: i32| {
+        let x = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+        let y = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+        image.get_pixel(x, y).0[0] as f32
+    };
+    let gx = pixel(1, -1) + 2.0 * pixel(1, 0) + pixel(1, 1)
+        - pixel(-1, -1)
+        - 2.0 * pixel(-1, 0)
+        - pixel(-1, 1);
+    let gy = pixel(-1, 1) + 2.0 * pixel(0, 1) + pixel(1, 1)
+        - pixel(-1, -1)
+        - 2.0 * pixel(0, -1)
+        - pixel(1, -1);
+    (gx, gy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    /// Draws the edges of an image of 2x2 cells over a grid of dots.
+    fn edges_of(lit: impl Fn(u32, u32) -> bool) -> Vec<String> {
+        let samples = GrayImage::from_fn(4, 8, |x, y| Luma([if lit(x, y) { 255 } else { 0 }]));
+        let mut grid = CellGrid::new(2, 2, false);
+        for _ in 0..4 {
+            grid.push(".", [255; 3]);
+        }
+        let edged = draw_edges(grid, &samples, DEFAULT_EDGE_THRESHOLD, false);
+        (0..2).map(|y| edged.row_text(y).to_string()).collect()
+    }
+
+    #[test]
+    fn test_draw_edges() {
+        assert_eq!(edges_of(|_, _| true), ["..", ".."]);
+        assert_eq!(edges_of(|x, _| x < 2), ["||", "||"]);
+        assert_eq!(edges_of(|_, y| y < 2), ["--", ".."]);
+        assert_eq!(edges_of(|_, y| y < 3), ["__", ".."]);
+        assert_eq!(edges_of(|x, y| x + y < 4)[0], "//");
+        assert_eq!(edges_of(|x, y| x > y), ["\\\\", ".."]);
+    }
+}
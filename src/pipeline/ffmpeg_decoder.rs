@@ -0,0 +1,235 @@
+//! Decodes videos with libav through ffmpeg-next (`ffmpeg_decoder` feature), so that tplay builds
+//! without OpenCV.
+//!
+//! The packets of the best video stream are read from the container and sent to its decoder, and
+//! the decoded frames converted to RGB by swscale. Seeking moves to the keyframe before the
+//! position, then drops the frames decoded before it. Cameras are not opened by their device path,
+//! they need the OpenCV decoder.
+use super::video::{VideoDecoder, MAX_CONSECUTIVE_BAD_FRAMES};
+use crate::common::errors::*;
+use ffmpeg_next::{
+    codec, decoder,
+    format::{self, Pixel},
+    frame, media, rescale,
+    software::scaling,
+    Packet,
+};
+use image::{DynamicImage, RgbImage};
+
+/// A video decoded by libav.
+pub struct FfmpegDecoder {
+    /// The container the packets are read from.
+    input: format::context::Input,
+    /// The decoder of the video stream.
+    decoder: decoder::Video,
+    /// The index of the video stream in the container.
+    stream_index: usize,
+    /// The duration of a tick of the timestamps of the stream, in seconds.
+    time_base: f64,
+    /// The frame rate of the stream, 0 if unknown.
+    fps: f64,
+    /// The number of frames of the stream, 0 if unknown.
+    frames: i64,
+    /// The duration of the video in seconds, 0 if unknown.
+    duration: f64,
+    /// The conversion of the frames to RGB, and the format, width and height it converts from.
+    scaler: Option<((Pixel, u32, u32), scaling::Context)>,
+    /// The position of the next frame, in seconds.
+    position: f64,
+    /// The position seeked to, the frames before it being dropped.
+    seek_target: Option<f64>,
+    /// Whether the end of the container was reached, the decoder then returns the frames left.
+    eof: bool,
+}
+
+// SAFETY: the libav contexts are only used by the thread owning the decoder, never shared
+unsafe impl Send for FfmpegDecoder {}
+
+impl FfmpegDecoder {
+    /// Opens a video file or network stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path or URL of the video.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoder, or a `MyError` if libav cannot open the video or it has
+    /// no video stream.
+    pub fn open(source: &str) -> Result<Self, MyError> {
+        let error = |err: ffmpeg_next::Error| {
+            MyError::Application(format!("{ERROR_OPENING_VIDEO}: {source}: {err}"))
+        };
+        ffmpeg_next::init().map_err(error)?;
+        let input = format::input(source).map_err(error)?;
+        let stream = input.streams().best(media::Type::Video).ok_or_else(|| {
+            MyError::Application(format!("{ERROR_OPENING_VIDEO}: {source}: no video stream"))
+        })?;
+        let stream_index = stream.index();
+        let time_base = f64::from(stream.time_base());
+        let fps = [stream.avg_frame_rate(), stream.rate()]
+            .into_iter()
+            .map(f64::from)
+            .find(|fps| fps.is_finite() && *fps > 0.0)
+            .unwrap_or(0.0);
+        let frames = stream.frames();
+        let duration = if input.duration() > 0 {
+            input.duration() as f64 * f64::from(rescale::TIME_BASE)
+        } else {
+            (stream.duration().max(0) as f64 * time_base).max(0.0)
+        };
+        let decoder = codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|context| context.decoder().video())
+            .map_err(error)?;
+        Ok(Self {
+            input,
+            decoder,
+            stream_index,
+            time_base,
+            fps,
+            frames,
+            duration,
+            scaler: None,
+            position: 0.0,
+            seek_target: None,
+            eof: false,
+        })
+    }
+
+    /// Decodes the next frame, without converting it.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or `None` if the video has ended.
+    fn decode(&mut self) -> Option<frame::Video> {
+        loop {
+            let mut frame = frame::Video::empty();
+            if self.decoder.receive_frame(&mut frame).is_ok() {
+                let time = frame
+                    .timestamp()
+                    .map(|timestamp| timestamp as f64 * self.time_base);
+                let frame_duration = if self.fps > 0.0 { 1.0 / self.fps } else { 0.0 };
+                if let (Some(target), Some(time)) = (self.seek_target, time) {
+                    // Drop the frames between the keyframe and the position seeked to
+                    if time + frame_duration / 2.0 < target {
+                        continue;
+                    }
+                }
+                self.seek_target = None;
+                self.position = time.unwrap_or(self.position) + frame_duration;
+                return Some(frame);
+            }
+            if self.eof {
+                return None;
+            }
+            let mut packet = Packet::empty();
+            match packet.read(&mut self.input) {
+                Ok(()) if packet.stream() == self.stream_index => {
+                    // A corrupted packet is skipped, like the frames that cannot be converted
+                    let _ = self.decoder.send_packet(&packet);
+                }
+                Ok(()) => {}
+                Err(_) => {
+                    // Let the decoder return the frames it holds back
+                    let _ = self.decoder.send_eof();
+                    self.eof = true;
+                }
+            }
+        }
+    }
+
+    /// Converts a decoded frame to an RGB image.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The decoded frame.
+    ///
+    /// # Returns
+    ///
+    /// The image, or `None` if the frame cannot be converted.
+    fn convert(&mut self, frame: &frame::Video) -> Option<DynamicImage> {
+        let (width, height) = (frame.width(), frame.height());
+        let source = (frame.format(), width, height);
+        if self.scaler.as_ref().map(|(scaled, _)| *scaled) != Some(source) {
+            // The size of the frames can change along a stream
+            let scaler = scaling::Context::get(
+                frame.format(),
+                width,
+                height,
+                Pixel::RGB24,
+                width,
+                height,
+                scaling::Flags::BILINEAR,
+            )
+            .ok()?;
+            self.scaler = Some((source, scaler));
+        }
+        let (_, scaler) = self.scaler.as_mut()?;
+        let mut rgb = frame::Video::empty();
+        scaler.run(frame, &mut rgb).ok()?;
+        // The rows of the frame are padded to the stride
+        let row = width as usize * 3;
+        let pixels: Vec<u8> = rgb
+            .data(0)
+            .chunks(rgb.stride(0))
+            .take(height as usize)
+            .flat_map(|line| line.get(..row).unwrap_or_default())
+            .copied()
+            .collect();
+        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    }
+}
+
+impl VideoDecoder for FfmpegDecoder {
+    fn next_frame(&mut self) -> Option<DynamicImage> {
+        for _ in 0..MAX_CONSECUTIVE_BAD_FRAMES {
+            let frame = self.decode()?;
+            if let Some(image) = self.convert(&frame) {
+                return Some(image);
+            }
+        }
+        None
+    }
+
+    fn skip_frames(&mut self, n: usize) -> bool {
+        (0..n).all(|_| self.decode().is_some())
+    }
+
+    fn seek(&mut self, seconds: f64) {
+        let timestamp = (seconds / f64::from(rescale::TIME_BASE)) as i64;
+        // To the keyframe before the position, the decoder then starts over from it
+        if self.input.seek(timestamp, ..timestamp).is_ok() {
+            self.decoder.flush();
+            self.eof = false;
+            self.seek_target = Some(seconds);
+            self.position = seconds;
+        }
+    }
+
+    fn seek_frame(&mut self, index: usize) {
+        if self.fps > 0.0 {
+            self.seek(index as f64 / self.fps);
+        }
+    }
+
+    fn position(&self) -> Option<f64> {
+        Some(self.position)
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        if self.frames > 0 {
+            Some(self.frames as usize)
+        } else {
+            let frames = (self.duration * self.fps).round();
+            (frames >= 1.0).then_some(frames as usize)
+        }
+    }
+
+    fn duration(&self) -> Option<f64> {
+        (self.duration > 0.0).then_some(self.duration)
+    }
+
+    fn fps(&self) -> Option<f64> {
+        (self.fps > 0.0).then_some(self.fps)
+    }
+}
@@ -0,0 +1,179 @@
+//! Computes a perceptual hash of each rendered frame, written to a file with `--frame-hashes`.
+//!
+//! The hash is a 64-bit difference hash of the brightness of the cells: similar frames get hashes
+//! differing by a few bits only, so duplicate segments of a video can be found by comparing the
+//! hashes (see `hamming_distance`), and the output of two rendering backends can be checked
+//! against each other in regression tests.
+//!
+//! The file is a CSV with the columns:
+//! * `frame` - The sequence number of the rendered frame.
+//! * `hash` - The hash of the frame, as 16 hexadecimal digits.
+use super::cell_grid::CellGrid;
+use crate::common::errors::*;
+use image::{imageops::FilterType, DynamicImage, GrayImage, Luma};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Header line of the CSV file.
+const CSV_HEADER: &str = "frame,hash";
+/// Width and height of the thumbnail compared by the hash: each of its rows gives 8 bits.
+const HASH_SIZE: (u32, u32) = (9, 8);
+
+/// Returns the perceptual hash of a frame: the bits tell whether the brightness decreases from
+/// one point to the next of a 9x8 thumbnail of the frame.
+///
+/// # Arguments
+///
+/// * `frame` - The rendered frame. The cells count with the brightness of their background if
+///   they have one, of their color otherwise; frames holding pixels count with their pixels.
+pub fn perceptual_hash(frame: &CellGrid) -> u64 {
+    let brightness = match frame.pixels() {
+        Some(pixels) => DynamicImage::ImageRgb8(pixels.clone()).into_luma8(),
+        None => {
+            let mut cells = frame.cells();
+            GrayImage::from_fn(frame.width().max(1), frame.height().max(1), |_, _| {
+                let [r, g, b] = cells
+                    .next()
+                    .map_or([0; 3], |cell| cell.background.unwrap_or(cell.color));
+                Luma([((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8])
+            })
+        }
+    };
+    let (width, height) = HASH_SIZE;
+    let thumbnail = image::imageops::resize(&brightness, width, height, FilterType::Triangle);
+    let mut hash = 0;
+    for y in 0..height {
+        for x in 0..width - 1 {
+            let bit = thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | bit as u64;
+        }
+    }
+    hash
+}
+
+/// Returns the number of bits differing between two hashes: 0 for identical frames, a few bits
+/// for near duplicates.
+///
+/// # Arguments
+///
+/// * `a` - A hash.
+/// * `b` - The other hash.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Writes the hash of each rendered frame to a CSV file.
+pub struct FrameHashWriter {
+    writer: Box<dyn Write + Send>,
+    next_frame: u64,
+}
+
+impl FrameHashWriter {
+    /// Creates a CSV file and writes the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the CSV file, replaced if it exists.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the writer, or a `MyError` if the file cannot be created.
+    pub fn create(path: &Path) -> Result<Self, MyError> {
+        let file = File::create(path).map_err(|err| {
+            MyError::Application(format!(
+                "{ERROR_FRAME_HASHES_FILE}: {}: {err:?}",
+                path.display()
+            ))
+        })?;
+        Self::from_writer(Box::new(BufWriter::new(file)))
+    }
+
+    /// Creates a writer writing to any writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the CSV is written.
+    fn from_writer(mut writer: Box<dyn Write + Send>) -> Result<Self, MyError> {
+        writeln!(writer, "{CSV_HEADER}")
+            .map_err(|err| MyError::Application(format!("{ERROR_FRAME_HASHES_FILE}: {err:?}")))?;
+        Ok(Self {
+            writer,
+            next_frame: 0,
+        })
+    }
+
+    /// Writes the hash of a rendered frame. Must be called in the order the frames are rendered,
+    /// e.g. from a frame observer (see `Runner::add_frame_observer`).
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The rendered frame.
+    pub fn record(&mut self, frame: &CellGrid) {
+        // Best effort: a failing write must not stop the playback
+        let _ = writeln!(
+            self.writer,
+            "{},{:016x}",
+            self.next_frame,
+            perceptual_hash(frame)
+        );
+        self.next_frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer that can be read back after the hash writer took it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn gradient(width: u32, height: u32, offset: u8) -> CellGrid {
+        let mut grid = CellGrid::new(width, height, false);
+        for _ in 0..height {
+            for x in 0..width {
+                let level = (255 - x * 255 / width) as u8;
+                grid.push("#", [level.saturating_add(offset); 3]);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_perceptual_hash() {
+        // Brightness decreasing from left to right sets every bit
+        let hash = perceptual_hash(&gradient(40, 20, 0));
+        assert_eq!(hash, u64::MAX);
+        // Near duplicates (e.g. a small change of brightness) get close hashes
+        let brighter = perceptual_hash(&gradient(40, 20, 10));
+        assert!(hamming_distance(hash, brighter) <= 4);
+        assert_eq!(perceptual_hash(&CellGrid::new(4, 2, false)), 0);
+    }
+
+    #[test]
+    fn test_frame_hash_writer() {
+        let buffer = SharedBuffer::default();
+        let mut writer = FrameHashWriter::from_writer(Box::new(buffer.clone())).unwrap();
+        writer.record(&gradient(40, 20, 0));
+        writer.record(&CellGrid::new(4, 2, false));
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "frame,hash\n0,ffffffffffffffff\n1,0000000000000000\n"
+        );
+    }
+}
@@ -4,16 +4,23 @@
 //! different media types such as images, videos, and animated GIFs. It also includes helper
 //! functions to open and process media files, as well as downloading and opening YouTube videos.
 use crate::{
-    audio::utils::has_audio,
-    common::{errors::*, utils::*},
+    audio::player::AudioBackend,
+    common::{errors::*, probe::{probe_media, probe_tags, MediaTags, ProbeInfo}, temp::TempStore, tools::{missing_tools, missing_tools_report, ExternalTool}, utils::*},
     downloader::youtube,
+    pipeline::{
+        animation::Animation,
+        demo,
+        live::{is_live_source, BufferStatus, LiveSource},
+        music::{has_extension, track_caption, AUDIO_EXTENSIONS},
+        progressive::ProgressiveImage,
+        slideshow::{Slideshow, SLIDESHOW_FPS},
+        video::{self, VideoDecoder},
+    },
 };
-use either::Either;
 use gif;
-use image::{ImageReader as ImageReader, DynamicImage};
-use opencv::{prelude::*, videoio::VideoCapture};
-use std::{fs::File, io::{Read, Write}, path::Path};
-use tempfile::{tempdir, TempPath};
+use image::{ImageReader as ImageReader, DynamicImage, ImageDecoder};
+use std::{fs::File, io::{Read, Write}, path::{Path, PathBuf}};
+use base64::Engine;
 use url::Url;
 use libwebp_sys as webp;
 
@@ -26,16 +33,36 @@ use libwebp_sys as webp;
 ///
 /// * `Image` - Represents a single-frame static image. Contains an
 ///   `Option<DynamicImage>`.
-/// * `Video` - Represents a video file. Contains a `VideoCapture` object.
+/// * `Progressive` - Represents a very large JPEG image. Contains a `ProgressiveImage`, shown as a
+///   quick low-resolution render until the full image is decoded on a background thread.
+/// * `Video` - Represents a video file. Contains the `VideoDecoder` compiled in.
 /// * `AnimatedGif` - Represents an animated GIF file. Contains a vector of
 ///   `DynamicImage` frames and the index of the current frame.
+/// * `Animation` - Represents an animated GIF/WEBP. Contains an `Animation`, which decodes the
+///   frames on a background thread while they play.
+/// * `Live` - Represents a camera or network stream. Contains a `LiveSource`, which never ends
+///   and shows a "no signal" placeholder while the stream is stalled.
+/// * `Still` - Represents an image shown for a number of frames, e.g. the cover art of an audio
+///   track for the duration of the track. Contains the image, the number of frames and the index
+///   of the current frame.
+/// * `Slideshow` - Represents the images of a directory shown one after the other, with
+///   transitions between them. Contains a `Slideshow`, which decodes the images as they are shown.
 pub enum FrameIterator {
     Image(Option<DynamicImage>),
-    Video(VideoCapture),
+    Progressive(ProgressiveImage),
+    Video(Box<dyn VideoDecoder>),
     AnimatedImage {
         frames: Vec<DynamicImage>,
         current_frame: usize,
     },
+    Animation(Animation),
+    Live(LiveSource),
+    Still {
+        image: DynamicImage,
+        frames: usize,
+        current_frame: usize,
+    },
+    Slideshow(Slideshow),
 }
 
 /// A named struct for storing the data returned by `open_media`.
@@ -45,10 +72,16 @@ pub enum FrameIterator {
 /// * `frame_iter` - A `FrameIterator` for iterating over the frames of the media file.
 /// * `fps` - The frame rate of the media file, if available.
 /// * `audio_path` - The path to the audio track of the media file, if available.
+/// * `duration` - The duration of the media file in seconds, if available.
+/// * `video_path` - The path to the video file, for decoding it again (e.g. the scene index).
+/// * `caption` - Lines of text shown over the frames, e.g. the title and artist of an audio track.
 pub struct MediaData {
     pub frame_iter: FrameIterator,
     pub fps: Option<f64>,
-    pub audio_path: Option<Either<TempPath, String>>,
+    pub audio_path: Option<PathBuf>,
+    pub duration: Option<f64>,
+    pub video_path: Option<PathBuf>,
+    pub caption: Vec<String>,
 }
 
 /// Implements the `Iterator` trait for `FrameIterator`.
@@ -58,15 +91,23 @@ pub struct MediaData {
 /// depends on the variant of the `FrameIterator`:
 ///
 /// * `Image` - Returns the single `DynamicImage` and sets the `Option` to `None`.
+/// * `Progressive` - Returns the quick render, then the full image once it is decoded.
 /// * `Video` - Captures and returns the next video frame as a grayscale `DynamicImage`.
 /// * `AnimatedGif` - Returns the next frame in the animation sequence as a `DynamicImage`.
+/// * `Animation` - Returns the next frame of the animation, waiting for it to be decoded.
+/// * `Live` - Returns the latest frame of the stream, or the "no signal" placeholder.
+/// * `Still` - Returns the image until the number of frames is reached.
+/// * `Slideshow` - Returns the current image, blended with the previous one during transitions.
 impl Iterator for FrameIterator {
     type Item = DynamicImage;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             FrameIterator::Image(ref mut img) => img.take(),
-            FrameIterator::Video(ref mut video) => capture_video_frame(video),
+            FrameIterator::Progressive(ref mut image) => image.next_frame(),
+            FrameIterator::Video(ref mut video) => video.next_frame(),
+            FrameIterator::Animation(ref mut animation) => animation.next_frame(),
+            FrameIterator::Live(ref mut live) => Some(live.next_frame()),
             FrameIterator::AnimatedImage {
                 ref frames,
                 ref mut current_frame,
@@ -79,6 +120,19 @@ impl Iterator for FrameIterator {
                     frame
                 }
             }
+            FrameIterator::Still {
+                ref image,
+                frames,
+                ref mut current_frame,
+            } => {
+                if *current_frame >= *frames {
+                    None
+                } else {
+                    *current_frame += 1;
+                    Some(image.clone())
+                }
+            }
+            FrameIterator::Slideshow(ref mut slideshow) => slideshow.next_frame(),
         }
     }
 }
@@ -95,178 +149,738 @@ impl FrameIterator {
     /// A relevant FrameIterator.
     pub fn skip_frames(&mut self, n: usize) {
         match self {
-            FrameIterator::Image(_) => {
+            FrameIterator::Image(_) | FrameIterator::Progressive(_) => {
                 // For a single image, skipping is a no-op, since there's only one frame
             }
             FrameIterator::Video(ref mut video) => {
-                for _ in 0..n {
-                    let mut frame = Mat::default();
-                    if !video.read(&mut frame).unwrap_or(false) || frame.empty() {
-                        break;
-                    }
-                }
+                video.skip_frames(n);
             }
             FrameIterator::AnimatedImage {
                 ref mut current_frame,
                 frames,
             } => {
-                *current_frame = (*current_frame + n) % frames.len();
+                // Stop at the end of the animation, looping is handled by the runner
+                *current_frame = (*current_frame + n).min(frames.len());
+            }
+            FrameIterator::Animation(ref mut animation) => {
+                animation.set_current_frame(animation.current_frame().saturating_add(n));
+            }
+            FrameIterator::Live(_) => {
+                // For a live stream, skipping is a no-op, since only the latest frame is kept
+            }
+            FrameIterator::Still {
+                frames,
+                ref mut current_frame,
+                ..
+            } => {
+                *current_frame = current_frame.saturating_add(n).min(*frames);
+            }
+            FrameIterator::Slideshow(ref mut slideshow) => {
+                slideshow.set_current_frame(slideshow.current_frame().saturating_add(n));
             }
         }
     }
 
     pub fn reset(&mut self) {
         match self {
-            FrameIterator::Image(_) => {
+            FrameIterator::Image(_) | FrameIterator::Progressive(_) => {
                 // For a single image, reset is a no-op, since there's only one frame
             }
-            FrameIterator::Video(ref mut video) => {
-                let _ = video.set(opencv::videoio::CAP_PROP_POS_AVI_RATIO, 0.0);
-            }
+            FrameIterator::Video(ref mut video) => video.seek(0.0),
             FrameIterator::AnimatedImage {
                 ref mut current_frame,
                 ..
             } => {
                 *current_frame = 0;
             }
+            FrameIterator::Animation(ref mut animation) => animation.set_current_frame(0),
+            FrameIterator::Live(_) => {
+                // For a live stream, reset is a no-op, since it cannot be rewound
+            }
+            FrameIterator::Still {
+                ref mut current_frame,
+                ..
+            } => {
+                *current_frame = 0;
+            }
+            FrameIterator::Slideshow(ref mut slideshow) => slideshow.set_current_frame(0),
+        }
+    }
+
+    /// Returns the position of the next frame, in seconds from the start of the media, or `None`
+    /// for media without positions (still images, live streams).
+    ///
+    /// # Arguments
+    ///
+    /// * `fps` - The frame rate of the media, used to locate frames of animated images.
+    pub fn position(&self, fps: f64) -> Option<f64> {
+        match self {
+            FrameIterator::Image(_) | FrameIterator::Progressive(_) | FrameIterator::Live(_) => {
+                None
+            }
+            FrameIterator::Video(video) => video.position(),
+            FrameIterator::AnimatedImage { current_frame, .. }
+            | FrameIterator::Still { current_frame, .. } => Some(*current_frame as f64 / fps),
+            FrameIterator::Animation(animation) => Some(animation.current_frame() as f64 / fps),
+            FrameIterator::Slideshow(slideshow) => Some(slideshow.current_frame() as f64 / fps),
+        }
+    }
+
+    /// Returns the duration of the media in seconds, or `None` if it is unknown (still images,
+    /// live streams, videos not reporting their length).
+    ///
+    /// # Arguments
+    ///
+    /// * `fps` - The frame rate of the media, used to time animated images.
+    pub fn duration(&self, fps: f64) -> Option<f64> {
+        match self {
+            FrameIterator::Image(_) | FrameIterator::Progressive(_) | FrameIterator::Live(_) => {
+                None
+            }
+            FrameIterator::Video(video) => video.duration(),
+            FrameIterator::AnimatedImage { frames, .. } => Some(frames.len() as f64 / fps),
+            FrameIterator::Animation(animation) => Some(animation.len() as f64 / fps),
+            // Tracks of unknown duration are shown for as long as possible
+            FrameIterator::Still { frames, .. } if *frames == usize::MAX => None,
+            FrameIterator::Still { frames, .. } => Some(*frames as f64 / fps),
+            FrameIterator::Slideshow(slideshow) => Some(slideshow.frames() as f64 / fps),
+        }
+    }
+
+    /// Returns the frame rate of a video as reported by its decoder, or `None` for other media.
+    pub fn fps(&self) -> Option<f64> {
+        match self {
+            FrameIterator::Video(video) => video.fps(),
+            _ => None,
+        }
+    }
+
+    /// Returns the position up to which the media is decoded ahead of the playback, in seconds,
+    /// or `None` for media decoded as it plays.
+    ///
+    /// # Arguments
+    ///
+    /// * `fps` - The frame rate of the media, used to time animated images.
+    pub fn buffered(&self, fps: f64) -> Option<f64> {
+        match self {
+            FrameIterator::Animation(animation) => Some(animation.decoded() as f64 / fps),
+            _ => None,
+        }
+    }
+
+    /// Returns how full the buffer of a network stream is, or `None` for media that is not
+    /// buffered.
+    pub fn buffer_status(&self) -> Option<BufferStatus> {
+        match self {
+            FrameIterator::Live(live) => live.buffer_status(),
+            _ => None,
+        }
+    }
+
+    /// Moves to the frame shown at the given time from the start of the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The position to move to, in seconds. Positions past the end move to the end.
+    /// * `fps` - The frame rate of the media, used to locate frames of animated images.
+    pub fn seek(&mut self, seconds: f64, fps: f64) {
+        let seconds = seconds.max(0.0);
+        match self {
+            FrameIterator::Image(_) | FrameIterator::Progressive(_) => {
+                // For a single image, seeking is a no-op, since there's only one frame
+            }
+            FrameIterator::Video(ref mut video) => video.seek(seconds),
+            FrameIterator::AnimatedImage {
+                ref mut current_frame,
+                frames,
+            } => {
+                *current_frame = ((seconds * fps) as usize).min(frames.len());
+            }
+            FrameIterator::Animation(ref mut animation) => {
+                animation.set_current_frame((seconds * fps) as usize);
+            }
+            FrameIterator::Live(_) => {
+                // For a live stream, seeking is a no-op, since it cannot be rewound
+            }
+            FrameIterator::Still {
+                frames,
+                ref mut current_frame,
+                ..
+            } => {
+                *current_frame = ((seconds * fps) as usize).min(*frames);
+            }
+            FrameIterator::Slideshow(ref mut slideshow) => {
+                slideshow.set_current_frame((seconds * fps) as usize);
+            }
         }
     }
 }
 
+/// The broad category of a media file, which decides how it is decoded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MediaKind {
+    /// A single-frame static image.
+    Image,
+    /// A TIFF image, which may contain multiple pages.
+    Tiff,
+    /// An ICO icon, which may contain multiple sizes.
+    Ico,
+    /// A JPEG XL image, decoded when the `jxl` feature is enabled.
+    Jxl,
+    /// A HEIC/HEIF image, decoded when the `heic` feature is enabled.
+    Heic,
+    /// An animated (or static) GIF.
+    Gif,
+    /// An animated (or static) WEBP.
+    Webp,
+    /// A video file or stream. Unknown extensions are treated as videos.
+    Video,
+    /// An audio track, shown as its cover art.
+    Audio,
+}
+
+impl MediaKind {
+    /// Maps a file extension to the kind of media it denotes.
+    ///
+    /// # Arguments
+    ///
+    /// * `ext` - The file extension, without the leading dot.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the kind of media, or `None` if the extension is not recognized.
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" | "bmp" | "jpg" | "jpeg" => Some(MediaKind::Image),
+            "tif" | "tiff" => Some(MediaKind::Tiff),
+            "ico" => Some(MediaKind::Ico),
+            "jxl" => Some(MediaKind::Jxl),
+            "heic" | "heif" => Some(MediaKind::Heic),
+            "gif" => Some(MediaKind::Gif),
+            "webp" => Some(MediaKind::Webp),
+            "mp4" | "avi" | "webm" | "mkv" | "mov" | "flv" | "ogg" => Some(MediaKind::Video),
+            ext if AUDIO_EXTENSIONS.contains(&ext) => Some(MediaKind::Audio),
+            _ => None,
+        }
+    }
+
+    /// Infers the kind of media from the extension of the given path. Unknown extensions are
+    /// treated as videos.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to the path of the media file.
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .and_then(Self::from_extension)
+            .unwrap_or(MediaKind::Video)
+    }
+
+    /// Infers the kind of media from the content of the given file (its magic bytes), falling
+    /// back to its extension if the content is not recognized or cannot be read.
+    ///
+    /// This allows files with a missing or misleading extension, such as media downloaded from
+    /// URLs, to be opened with the right decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to the path of the media file.
+    pub fn detect(path: &Path) -> Self {
+        // Only sniff regular files, reading from devices such as webcams may block
+        if !path.is_file() {
+            return Self::from_path(path);
+        }
+        infer::get_from_path(path)
+            .ok()
+            .flatten()
+            .and_then(Self::from_infer_type)
+            .unwrap_or_else(|| Self::from_path(path))
+    }
+
+    /// Infers the kind of media from its content (its magic bytes).
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The content of the media, or at least its first bytes.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the kind of media, or `None` if the content is not recognized.
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        infer::get(buf).and_then(Self::from_infer_type)
+    }
+
+    /// Maps a type detected by the `infer` crate to the kind of media it denotes.
+    fn from_infer_type(kind: infer::Type) -> Option<Self> {
+        if kind.matcher_type() == infer::MatcherType::Video {
+            Some(MediaKind::Video)
+        } else {
+            Self::from_extension(kind.extension())
+        }
+    }
+}
+
+/// Name of the temporary file where YouTube videos are downloaded.
+const YOUTUBE_FILE_NAME: &str = "youtube.webm";
+
+/// Checks whether a domain belongs to YouTube, whose videos are downloaded with `yt-dlp`.
+fn is_youtube_domain(domain: &str) -> bool {
+    domain.ends_with("youtube.com") || domain.ends_with("youtu.be")
+}
+
+/// Returns the file name used to store the content downloaded from a url.
+fn url_file_name(url: &Url) -> &str {
+    // use the last segment of the url path (for the ext) or a random name otherwise with no extension
+    url.path_segments()
+        .and_then(|mut s| s.next_back())
+        .unwrap_or("unknown_media")
+}
+
+/// Infers the kind of media a url points to from the extension of its file name.
+///
+/// # Returns
+///
+/// The kind of media, or `None` if the file name has no known extension (e.g.
+/// `https://picsum.photos/200`), in which case the kind is only known once downloaded.
+fn url_kind(url: &Url) -> Option<MediaKind> {
+    Path::new(url_file_name(url))
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .and_then(MediaKind::from_extension)
+}
+
+/// Name of the temporary file where data URL content that cannot be decoded in memory is saved.
+const DATA_URL_FILE_NAME: &str = "data_url";
+
+/// Decodes the content of a data URL (`data:[<mediatype>][;base64],<data>`).
+///
+/// # Arguments
+///
+/// * `url` - The data URL.
+///
+/// # Returns
+///
+/// A `Result` containing the decoded bytes, or a `MyError` if the URL is malformed.
+fn decode_data_url(url: &str) -> Result<Vec<u8>, MyError> {
+    let (header, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or(MyError::Application(format!("{ERROR_DATA_URL}: missing data")))?;
+
+    if header.ends_with(";base64") {
+        // Line breaks and spaces are common when the data is wrapped or pasted
+        let data: String = percent_encoding::percent_decode_str(data)
+            .decode_utf8_lossy()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|err| MyError::Application(format!("{ERROR_DATA_URL}: {err:?}")))
+    } else {
+        Ok(percent_encoding::percent_decode_str(data).collect())
+    }
+}
+
+/// Opens the media contained in a data URL.
+///
+/// Still images are decoded directly from memory. Other media (animations, videos) need a file
+/// to be decoded from, so their content is saved in the temporary store first.
+///
+/// # Arguments
+///
+/// * `url` - The data URL.
+/// * `external_tools` - Whether ffprobe may be used to read the frame rate and audio streams.
+/// * `temp_store` - The store where content that cannot be decoded in memory is saved.
+///
+/// # Returns
+///
+/// A `Result` containing a `MediaData` struct if the media is successfully opened, or a
+/// `MyError` if an error occurs.
+fn open_data_url(
+    url: &str,
+    external_tools: bool,
+    temp_store: &TempStore,
+) -> Result<MediaData, MyError> {
+    let data = decode_data_url(url)?;
+    match MediaKind::from_bytes(&data) {
+        Some(MediaKind::Image) | None => {
+            let img = image::load_from_memory(&data).map_err(|e| {
+                MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
+            })?;
+            Ok(MediaData {
+                frame_iter: FrameIterator::Image(Some(img)),
+                fps: None,
+                audio_path: None,
+                duration: None,
+                video_path: None,
+                caption: Vec::new(),
+            })
+        }
+        Some(_) => {
+            let path = temp_store.path(DATA_URL_FILE_NAME);
+            let mut file = File::create(&path)?;
+            file.write_all(&data)?;
+            open_media_from_path(path.to_str().unwrap_or(""), &path, external_tools)
+        }
+    }
+}
+
+/// Lists the external tools needed to play the given media.
+///
+/// Images and animations are decoded natively, while videos and audio tracks need `ffprobe` (and
+/// `ffmpeg` for the rodio audio backend), and YouTube links additionally need `yt-dlp`. The
+/// built-in video decoder, used without a decoder feature, reads the frame rate itself and only
+/// uses `ffprobe` if it is installed.
+///
+/// URLs without a known extension need no tool up front: `open_media` checks `ffprobe` once the
+/// media is downloaded and its kind is known.
+///
+/// # Arguments
+///
+/// * `path` - A reference to a path or a URL of the media file.
+/// * `audio_backend` - The backend that will play the audio track, if any.
+pub fn required_tools(path: &str, audio_backend: Option<AudioBackend>) -> Vec<ExternalTool> {
+    let mut tools = Vec::new();
+    if demo::is_demo(path) {
+        // The demo is drawn by tplay itself
+        return tools;
+    }
+    if is_live_source(path) {
+        // Live sources are read by OpenCV alone, without audio
+        return tools;
+    }
+    let kind = match Url::parse(path) {
+        Ok(url) if url.scheme() == "data" => decode_data_url(path)
+            .ok()
+            .and_then(|data| MediaKind::from_bytes(&data))
+            .unwrap_or(MediaKind::Image),
+        Ok(url) => match url.domain() {
+            Some(domain) if is_youtube_domain(domain) => {
+                tools.push(ExternalTool::YtDlp);
+                MediaKind::Video
+            }
+            Some(_) => match url_kind(&url) {
+                Some(kind) => kind,
+                None => return tools,
+            },
+            None => MediaKind::detect(Path::new(path)),
+        },
+        Err(_) => MediaKind::detect(Path::new(path)),
+    };
+    if matches!(kind, MediaKind::Video | MediaKind::Audio) {
+        // Static builds probe videos with the libav libraries linked in the binary
+        let builtin_decoder = kind == MediaKind::Video
+            && !cfg!(any(feature = "opencv_decoder", feature = "ffmpeg_decoder"));
+        if !cfg!(feature = "static-release") && !builtin_decoder {
+            tools.push(ExternalTool::Ffprobe);
+        }
+        if audio_backend.is_some_and(|backend| backend.needs_ffmpeg()) {
+            tools.push(ExternalTool::Ffmpeg);
+        }
+    }
+    tools
+}
+
 /// Opens the specified media file and returns a `FrameIterator` for iterating over its frames.
 ///
-/// This function takes a path or downloadable URL to a media file and identifies its type based on the file extension.
-/// It supports images (PNG, BMP, ICO, TIF, TIFF, JPG, JPEG), videos (MP4, AVI, WEBM, MKV, MOV, FLV,
-/// OGG), and animated GIFs/WEBPs. If the URL pointing to a YouTube video, the content will be handled in a custom manner.
+/// This function takes a path or downloadable URL to a media file and identifies its type based on its content,
+/// or on the file extension if the content is not recognized.
+/// It supports images (PNG, BMP, ICO, TIF, TIFF, JPG, JPEG, including multi-page TIFFs and
+/// multi-size ICOs, and JPEG XL/HEIC when the corresponding features are enabled), videos (MP4, AVI, WEBM, MKV, MOV, FLV,
+/// OGG), animated GIFs/WEBPs, and audio tracks (MP3, FLAC, WAV, ...) shown as their cover art. If the URL pointing to a YouTube video, the content will be handled in a custom manner.
+/// Data URLs (`data:image/png;base64,...`) are decoded without downloading anything. Cameras
+/// (`/dev/video*`) and network streams (`rtsp://`, ...) are read live, see `LiveSource`.
 ///
 /// # Arguments
 ///
 /// * `path` - A reference to a path or a URL of the media file.
+/// * `external_tools` - Whether external tools (ffprobe, yt-dlp) may be used. When disabled, videos
+///   play at the default frame rate without audio and YouTube links are rejected.
+/// * `temp_store` - The store where downloaded media is saved.
 ///
 /// # Returns
 ///
 /// A `Result` containing a `FrameData` struct if the media file is successfully opened, or a
 /// `MyError` if an error occurs.
-pub fn open_media(path: String) -> Result<MediaData, MyError> {
+pub fn open_media(
+    path: String,
+    external_tools: bool,
+    temp_store: &TempStore,
+) -> Result<MediaData, MyError> {
+    if demo::is_demo(&path) {
+        return Ok(demo::open());
+    }
+    if is_live_source(&path) {
+        return Ok(MediaData {
+            frame_iter: FrameIterator::Live(LiveSource::open(&path)),
+            fps: None,
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
+        });
+    }
     // Check if the path is a URL
     if let Ok(url) = Url::parse(path.as_str()) {
+        if url.scheme() == "data" {
+            return open_data_url(path.as_str(), external_tools, temp_store);
+        }
         if let Some(domain) = url.domain() {
             // handle YouTube domains specially
-            if domain.ends_with("youtube.com") || domain.ends_with("youtu.be") {
-                let video = youtube::download_video(path.as_str())?;
-                let fps = extract_fps(video.as_os_str().to_str().unwrap_or(""));
+            if is_youtube_domain(domain) {
+                if !external_tools {
+                    return Err(MyError::Application(ERROR_EXTERNAL_TOOLS_DISABLED.to_string()));
+                }
+                let video =
+                    youtube::download_video(path.as_str(), &temp_store.path(YOUTUBE_FILE_NAME))?;
+                // A missing frame rate is not fatal, the default fps is used instead
+                let fps = extract_fps(video.as_os_str().to_str().unwrap_or("")).unwrap_or(None);
                 let video_open = open_video(&video)?;
                 return Ok(MediaData {
                     frame_iter: video_open,
                     fps,
-                    audio_path: Some(Either::Left(video)),
+                    audio_path: Some(video.clone()),
+                    duration: None,
+                    video_path: Some(video),
+                    caption: Vec::new(),
                 });
             } else {
                 // otherwise download the url to a temp file and open media from there.
-                let tmp = tempdir()?;
-                // use the last segment of the url path (for the ext) or a random name otherwise with no extension
-                let name = url.path_segments().and_then(|s| s.last()).unwrap_or("unknown_media");
-                let p = tmp.path().join(name);
+                let p = temp_store.path(url_file_name(&url));
+                let kind = url_kind(&url);
                 download_url_to_file(p.as_path(), url)?;
-                open_media_from_path(p.as_os_str().to_str().unwrap_or(""), p.as_path())
+                let p_str = p.as_os_str().to_str().unwrap_or("");
+                // The tools were not checked up front if the kind of the media was unknown
+                if external_tools && kind.is_none() {
+                    let missing = missing_tools(&required_tools(p_str, None));
+                    if !missing.is_empty() {
+                        return Err(MyError::Application(missing_tools_report(&path, &missing)));
+                    }
+                }
+                open_media_from_path(p_str, p.as_path(), external_tools)
             }
         } else {
-            open_media_from_path(path.as_str(), &Path::new(path.as_str()))
+            open_media_from_path(path.as_str(), &Path::new(path.as_str()), external_tools)
         }
     } else {
-        open_media_from_path(path.as_str(), &Path::new(path.as_str()))
+        open_media_from_path(path.as_str(), &Path::new(path.as_str()), external_tools)
     }
 }
 
 /// Opens the media file from a local path and returns a `FrameIterator` for iterating over its frames.
 ///
-/// This function is called from open_media
+/// This function is called from open_media. Directories are played as a slideshow of their
+/// images.
 ///
 /// # Arguments
 ///
 /// * `path_str` - A reference to the path str.
 /// * `path` - A reference to a corresponding Path structure.
+/// * `external_tools` - Whether ffprobe may be used to read the frame rate and audio streams.
 ///
 /// # Returns
 ///
 /// A `Result` containing a `FrameData` struct if the media file is successfully opened, or a
 /// `MyError` if an error occurs.
-fn open_media_from_path(path_str: &str, path: &Path) -> Result<MediaData, MyError> {
-    let fps = extract_fps(path_str);
-    let audio = has_audio(path_str)?;
-    let audio_track = if audio {
-        Some(Either::Right(path_str.to_owned()))
-    } else {
-        None
-    };
-
-    let ext = path.extension().and_then(std::ffi::OsStr::to_str);
-    match ext {
-        // Image extensions
-        Some("png") | Some("bmp") | Some("ico") | Some("tif") | Some("tiff") | Some("jpg")
-        | Some("jpeg") => Ok(MediaData {
+fn open_media_from_path(path_str: &str, path: &Path, external_tools: bool) -> Result<MediaData, MyError> {
+    if path.is_dir() {
+        return Ok(MediaData {
+            frame_iter: FrameIterator::Slideshow(Slideshow::open(path)?),
+            fps: Some(SLIDESHOW_FPS),
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
+        });
+    }
+    match MediaKind::detect(path) {
+        MediaKind::Image => Ok(MediaData {
             frame_iter: open_image(path)?,
             fps: None,
             audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
         }),
 
-        // Video extensions
-        Some("mp4") | Some("avi") | Some("webm") | Some("mkv") | Some("mov") | Some("flv")
-        | Some("ogg") => Ok(MediaData {
-            frame_iter: open_video(path)?,
-            fps,
-            audio_path: audio_track,
+        MediaKind::Tiff => Ok(MediaData {
+            frame_iter: open_tiff(path)?,
+            fps: Some(MULTI_IMAGE_FPS),
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
+        }),
+
+        MediaKind::Ico => Ok(MediaData {
+            frame_iter: open_ico(path)?,
+            fps: Some(MULTI_IMAGE_FPS),
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
+        }),
+
+        MediaKind::Jxl => Ok(MediaData {
+            frame_iter: open_jxl(path)?,
+            fps: None,
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
+        }),
+
+        MediaKind::Heic => Ok(MediaData {
+            frame_iter: open_heic(path)?,
+            fps: None,
+            audio_path: None,
+            duration: None,
+            video_path: None,
+            caption: Vec::new(),
         }),
 
-        // Gif
-        Some("gif") => {
+        MediaKind::Gif => {
             let (frame_iter, fps) = open_gif(path)?;
             Ok(MediaData {
                 frame_iter,
                 fps: Some(fps),
                 audio_path: None,
+                duration: None,
+                video_path: None,
+                caption: Vec::new(),
             })
         },
 
-        // Webp
-        Some("webp") => {
+        MediaKind::Webp => {
             let (frame_iter, fps) = open_webp(path)?;
             Ok(MediaData {
                 frame_iter,
                 fps: Some(fps),
                 audio_path: None,
+                duration: None,
+                video_path: None,
+                caption: Vec::new(),
             })
         },
 
-        // Unknown extension, try open as video
-        _ => Ok(MediaData {
-            frame_iter: open_video(path)?,
-            fps,
-            audio_path: audio_track,
-        }),
+        // Videos (and unknown extensions, which are tried as videos)
+        MediaKind::Video => {
+            let probe = if !external_tools {
+                ProbeInfo::default()
+            } else if cfg!(any(feature = "opencv_decoder", feature = "ffmpeg_decoder")) {
+                probe_media(path_str)?
+            } else {
+                // The built-in decoder does not need ffprobe, which only finds the audio track
+                probe_media(path_str).unwrap_or_default()
+            };
+            let frame_iter = open_video(path)?;
+            Ok(MediaData {
+                // The frame rate of the decoder when ffprobe is not used
+                fps: probe.fps.or_else(|| frame_iter.fps()),
+                frame_iter,
+                audio_path: probe.has_audio.then(|| path.to_path_buf()),
+                duration: probe.duration,
+                video_path: Some(path.to_path_buf()),
+                caption: Vec::new(),
+            })
+        }
+
+        MediaKind::Audio => open_audio(path_str, path, external_tools),
     }
 }
 
-/// Captures the next video frame as a dynamic image.
-///
-/// This helper function reads the next frame from the provided video and converts it into a
-/// `DynamicImage`.
+/// Frame rate of the cover art shown while an audio track plays.
+const AUDIO_FPS: f64 = 4.0;
+/// Names of the image files holding the cover art of the tracks of an album, without extension.
+const COVER_ART_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+/// Extensions of the image files holding the cover art of an album.
+const COVER_ART_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Opens an audio track, shown as its cover art for its duration, with its tags as caption.
 ///
 /// # Arguments
 ///
-/// * `video` - A mutable reference to a `VideoCapture` object.
+/// * `path_str` - A reference to the path str.
+/// * `path` - A reference to a corresponding Path structure.
+/// * `external_tools` - Whether ffprobe and ffmpeg may be used to read the duration, the tags and
+///   the embedded cover art. Without them, the cover art is shown until the playback is exited.
 ///
 /// # Returns
 ///
-/// An `Option` containing a `DynamicImage` if the frame is successfully captured and
-/// converted, or `None` if an error occurs or the video has ended.
-fn capture_video_frame(video: &mut VideoCapture) -> Option<DynamicImage> {
-    let mut frame = Mat::default();
-    if video.read(&mut frame).unwrap_or(false) && !frame.empty() {
-        mat_to_dynamic_image(&frame)
+/// A `Result` containing a `MediaData` struct, or a `MyError` if the track cannot be probed.
+fn open_audio(path_str: &str, path: &Path, external_tools: bool) -> Result<MediaData, MyError> {
+    let (probe, tags) = if external_tools {
+        // Tags are only shown, a track without them still plays
+        (probe_media(path_str)?, probe_tags(path_str).unwrap_or_default())
     } else {
-        None
-    }
+        (ProbeInfo::default(), MediaTags::default())
+    };
+    let image = external_tools
+        .then(|| embedded_cover_art(path_str))
+        .flatten()
+        .or_else(|| album_cover_art(path))
+        .unwrap_or_else(|| DynamicImage::new_rgb8(1, 1));
+    let frames = probe
+        .duration
+        .map_or(usize::MAX, |duration| (duration * AUDIO_FPS).ceil() as usize);
+    Ok(MediaData {
+        frame_iter: FrameIterator::Still {
+            image,
+            frames,
+            current_frame: 0,
+        },
+        fps: Some(AUDIO_FPS),
+        audio_path: Some(path.to_path_buf()),
+        duration: probe.duration,
+        video_path: None,
+        caption: track_caption(&tags, path_str),
+    })
+}
+
+/// Extracts the cover art embedded in an audio file (e.g. the `APIC` frame of an MP3) with
+/// `ffmpeg`.
+///
+/// # Arguments
+///
+/// * `path` - The path of the audio file.
+///
+/// # Returns
+///
+/// The cover art, or `None` if the file has none or `ffmpeg` cannot be run.
+fn embedded_cover_art(path: &str) -> Option<DynamicImage> {
+    let output = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-i", path, "-an", "-frames:v", "1"])
+        .args(["-f", "image2pipe", "-c:v", "png", "-"])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    image::load_from_memory(&output.stdout).ok()
+}
+
+/// Looks for the cover art of an album next to one of its tracks, e.g. `cover.jpg`.
+///
+/// # Arguments
+///
+/// * `path` - The path of the track.
+///
+/// # Returns
+///
+/// The cover art, or `None` if there is none.
+fn album_cover_art(path: &Path) -> Option<DynamicImage> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty())?;
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| has_extension(file, &COVER_ART_EXTENSIONS))
+        .filter(|file| {
+            file.file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| COVER_ART_NAMES.contains(&stem.to_ascii_lowercase().as_str()))
+        })
+        .find_map(|file| image::open(file).ok())
 }
 
 /// Writes the content downloaded from a url to a file.
@@ -293,7 +907,8 @@ fn download_url_to_file(path: &Path, url: Url) -> Result<(), MyError> {
 
 /// Opens the specified image file and returns a `FrameIterator`.
 ///
-/// This helper function opens an image file and creates a `FrameIterator::Image` variant.
+/// This helper function opens an image file and creates a `FrameIterator::Image` variant, or a
+/// `FrameIterator::Progressive` variant for very large JPEG images.
 ///
 /// # Arguments
 ///
@@ -304,15 +919,252 @@ fn download_url_to_file(path: &Path, url: Url) -> Result<(), MyError> {
 /// A `Result` containing a `FrameIterator` if the image file is successfully opened, or a
 /// `MyError` if an error occurs.
 fn open_image(path: &Path) -> Result<FrameIterator, MyError> {
-    let img = ImageReader::open(path)?.decode().map_err(|e| {
+    if let Some(image) = ProgressiveImage::open(path)? {
+        return Ok(FrameIterator::Progressive(image));
+    }
+    Ok(FrameIterator::Image(Some(decode_image(path)?)))
+}
+
+/// Decodes an image file, turned the way its EXIF orientation tag says. Cameras and phones store
+/// photos as the sensor captured them, sideways when taken in portrait, and only record how to
+/// turn them.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the image file.
+///
+/// # Returns
+///
+/// A `Result` containing the image upright, or a `MyError` if it cannot be decoded.
+pub fn decode_image(path: &Path) -> Result<DynamicImage, MyError> {
+    let decoding_error =
+        |e| MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE));
+    // Guess the format from the content, the extension may be missing or wrong
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(decoding_error)?;
+    // Images without EXIF data, or with unreadable EXIF data, are shown as they are stored
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(decoding_error)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Rate at which the pages of multi-page TIFFs and the sizes of ICOs are flipped through.
+const MULTI_IMAGE_FPS: f64 = 1.0;
+
+/// Wraps decoded images in a `FrameIterator`, as a static image if there is only one.
+fn multi_image_iterator(mut frames: Vec<DynamicImage>) -> FrameIterator {
+    if frames.len() == 1 {
+        FrameIterator::Image(frames.pop())
+    } else {
+        FrameIterator::AnimatedImage {
+            frames,
+            current_frame: 0,
+        }
+    }
+}
+
+/// Opens the specified TIFF file and returns a `FrameIterator`.
+///
+/// Every page of a multi-page TIFF (e.g. a scientific image stack) becomes a frame of a
+/// `FrameIterator::AnimatedImage`. Pages with an unsupported color type are skipped, and if none
+/// can be decoded the file is opened as a regular single image.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the TIFF file.
+///
+/// # Returns
+///
+/// A `Result` containing a `FrameIterator` if the TIFF file is successfully opened, or a
+/// `MyError` if an error occurs.
+fn open_tiff(path: &Path) -> Result<FrameIterator, MyError> {
+    let file = File::open(path)
+        .map_err(|e| MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE)))?;
+    let mut decoder = tiff::decoder::Decoder::new(std::io::BufReader::new(file)).map_err(|e| {
+        MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
+    })?;
+
+    let mut frames = Vec::new();
+    loop {
+        if let Some(frame) = decode_tiff_page(&mut decoder) {
+            frames.push(frame);
+        }
+        if !decoder.more_images() || decoder.next_image().is_err() {
+            break;
+        }
+    }
+
+    if frames.is_empty() {
+        open_image(path)
+    } else {
+        Ok(multi_image_iterator(frames))
+    }
+}
+
+/// Decodes the current page of a TIFF file.
+///
+/// # Arguments
+///
+/// * `decoder` - A mutable reference to the TIFF decoder, positioned on the page to decode.
+///
+/// # Returns
+///
+/// An `Option` containing the page as a `DynamicImage`, or `None` if the page cannot be decoded
+/// or its color type is not supported.
+fn decode_tiff_page<R: Read + std::io::Seek>(
+    decoder: &mut tiff::decoder::Decoder<R>,
+) -> Option<DynamicImage> {
+    use tiff::{decoder::DecodingResult, ColorType};
+
+    let (width, height) = decoder.dimensions().ok()?;
+    let color_type = decoder.colortype().ok()?;
+    match (color_type, decoder.read_image().ok()?) {
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            image::GrayImage::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            image::GrayAlphaImage::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            image::RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            image::RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+        }
+        _ => None,
+    }
+}
+
+/// Opens the specified ICO file and returns a `FrameIterator`.
+///
+/// Every size stored in the icon becomes a frame of a `FrameIterator::AnimatedImage`, from the
+/// smallest to the largest.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the ICO file.
+///
+/// # Returns
+///
+/// A `Result` containing a `FrameIterator` if the ICO file is successfully opened, or a
+/// `MyError` if an error occurs.
+fn open_ico(path: &Path) -> Result<FrameIterator, MyError> {
+    let file = File::open(path)
+        .map_err(|e| MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE)))?;
+    let icon_dir = ico::IconDir::read(std::io::BufReader::new(file)).map_err(|e| {
+        MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
+    })?;
+
+    let mut entries: Vec<_> = icon_dir.entries().iter().collect();
+    entries.sort_by_key(|entry| entry.width() * entry.height());
+    let frames: Vec<DynamicImage> = entries
+        .into_iter()
+        .filter_map(|entry| entry.decode().ok())
+        .filter_map(|icon| {
+            image::RgbaImage::from_raw(icon.width(), icon.height(), icon.rgba_data().to_vec())
+                .map(DynamicImage::ImageRgba8)
+        })
+        .collect();
+
+    if frames.is_empty() {
+        open_image(path)
+    } else {
+        Ok(multi_image_iterator(frames))
+    }
+}
+
+/// Opens the specified JPEG XL file and returns a `FrameIterator`.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the JPEG XL file.
+///
+/// # Returns
+///
+/// A `Result` containing a `FrameIterator` if the JPEG XL file is successfully opened, or a
+/// `MyError` if an error occurs.
+#[cfg(feature = "jxl")]
+fn open_jxl(path: &Path) -> Result<FrameIterator, MyError> {
+    let file = File::open(path)
+        .map_err(|e| MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE)))?;
+    let decoder = jxl_oxide::integration::JxlDecoder::new(file).map_err(|e| {
+        MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
+    })?;
+    let img = DynamicImage::from_decoder(decoder).map_err(|e| {
         MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
     })?;
     Ok(FrameIterator::Image(Some(img)))
 }
 
+/// Reports that JPEG XL support was not compiled in.
+#[cfg(not(feature = "jxl"))]
+fn open_jxl(_path: &Path) -> Result<FrameIterator, MyError> {
+    Err(MyError::Application(ERROR_JXL_UNSUPPORTED.to_string()))
+}
+
+/// Opens the specified HEIC/HEIF file and returns a `FrameIterator`.
+///
+/// Only the primary image of the file is decoded.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the HEIC/HEIF file.
+///
+/// # Returns
+///
+/// A `Result` containing a `FrameIterator` if the HEIC/HEIF file is successfully opened, or a
+/// `MyError` if an error occurs.
+#[cfg(feature = "heic")]
+fn open_heic(path: &Path) -> Result<FrameIterator, MyError> {
+    use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+
+    let decoding_error =
+        |e: HeifError| MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE));
+    let context = HeifContext::read_from_file(path.to_str().unwrap_or("")).map_err(decoding_error)?;
+    let handle = context.primary_image_handle().map_err(decoding_error)?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(decoding_error)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or(MyError::Application(ERROR_DECODING_IMAGE.to_string()))?;
+
+    // Rows may be padded, only keep the pixel data
+    let row_len = plane.width as usize * 3;
+    let mut buf = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        buf.extend_from_slice(&row[..row_len]);
+    }
+    let img = image::RgbImage::from_raw(plane.width, plane.height, buf)
+        .ok_or(MyError::Application(ERROR_DECODING_IMAGE.to_string()))?;
+    Ok(FrameIterator::Image(Some(DynamicImage::ImageRgb8(img))))
+}
+
+/// Reports that HEIC/HEIF support was not compiled in.
+#[cfg(not(feature = "heic"))]
+fn open_heic(_path: &Path) -> Result<FrameIterator, MyError> {
+    Err(MyError::Application(ERROR_HEIC_UNSUPPORTED.to_string()))
+}
+
 /// Opens the specified video file and returns a `FrameIterator`.
 ///
-/// This helper function opens a video file and creates a `FrameIterator::Video` variant.
+/// This helper function opens a video file with the decoder compiled in and creates a
+/// `FrameIterator::Video` variant.
 ///
 /// # Arguments
 ///
@@ -323,22 +1175,15 @@ fn open_image(path: &Path) -> Result<FrameIterator, MyError> {
 /// A `Result` containing a `FrameIterator` if the video file is successfully opened, or a
 /// `MyError` if an error occurs.
 fn open_video(path: &Path) -> Result<FrameIterator, MyError> {
-    let video = VideoCapture::from_file(
-        path.to_str().expect(ERROR_OPENING_VIDEO),
-        opencv::videoio::CAP_ANY,
-    )?;
-
-    if video.is_opened()? {
-        Ok(FrameIterator::Video(video))
-    } else {
-        Err(MyError::Application(ERROR_OPENING_VIDEO.to_string()))
-    }
+    video::open(path.to_str().expect(ERROR_OPENING_VIDEO)).map(FrameIterator::Video)
 }
 
 /// Opens the specified animated GIF file and returns a `FrameIterator`.
 ///
-/// This helper function opens an animated GIF file and creates a `FrameIterator::AnimatedGif`
-/// variant containing all the frames of the animation.
+/// This helper function opens an animated GIF file and creates a `FrameIterator::Animation`
+/// variant, decoding the frames of the animation on a background thread while it plays. The
+/// frames are counted and timed first, reading their headers without decoding them. If a frame
+/// cannot be decoded, the animation ends with the frame before it.
 ///
 /// # Arguments
 ///
@@ -349,50 +1194,59 @@ fn open_video(path: &Path) -> Result<FrameIterator, MyError> {
 /// A `Result` containing a `FrameIterator` and fps if the animated GIF file is successfully opened, or a
 /// `MyError` if an error occurs.
 fn open_gif(path: &Path) -> Result<(FrameIterator, f64), MyError> {
-    let file = File::open(path)
-        .map_err(|e| MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE)))?;
-    let mut options = gif::DecodeOptions::new();
-    // https://lib.rs/crates/gif-dispose
-    // for gif_dispose frame composing for rgba output, we need to set this as indexed.
-    options.set_color_output(gif::ColorOutput::Indexed);
-    let mut decoder = options.read_info(file).map_err(|e| {
-        MyError::Application(format!("{error}: {e:?}", error = ERROR_READING_GIF_HEADER))
-    })?;
+    let open = |skip_frame_decoding: bool| {
+        let file = File::open(path).map_err(|e| {
+            MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE))
+        })?;
+        let mut options = gif::DecodeOptions::new();
+        // https://lib.rs/crates/gif-dispose
+        // for gif_dispose frame composing for rgba output, we need to set this as indexed.
+        options.set_color_output(gif::ColorOutput::Indexed);
+        options.skip_frame_decoding(skip_frame_decoding);
+        options.read_info(file).map_err(|e| {
+            MyError::Application(format!("{error}: {e:?}", error = ERROR_READING_GIF_HEADER))
+        })
+    };
 
     // delay is in units of 10ms, so we'll divide by 100.0, not 1000.0
     let mut delay: u64 = 0;
-    let mut frames = Vec::new();
-    // The gif crate only exposes raw frame data that is not sufficient to render animated GIFs properly.
-    // GIF requires special composing of frames which is non-trivial.
-    let mut screen = gif_dispose::Screen::new_decoder(&decoder);
-    while let Ok(Some(frame)) = decoder.read_next_frame() {
+    let mut frames = 0;
+    let mut headers = open(true)?;
+    while let Ok(Some(frame)) = headers.read_next_frame() {
         delay += frame.delay as u64;
-        screen.blit_frame(&frame).map_err(|e| {
-            MyError::Application(format!("{error}: {e:?}", error = ERROR_DECODING_IMAGE))
-        })?;
-        let (buf, width, height) = screen.pixels_rgba().to_contiguous_buf();
-        frames.push(DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
-            width as u32,
-            height as u32,
-            |x, y| {
+        frames += 1;
+    }
+
+    let mut decoder = open(false)?;
+    let animation = Animation::decode(frames, move |push| {
+        // The gif crate only exposes raw frame data that is not sufficient to render animated GIFs properly.
+        // GIF requires special composing of frames which is non-trivial.
+        let mut screen = gif_dispose::Screen::new_decoder(&decoder);
+        while let Ok(Some(frame)) = decoder.read_next_frame() {
+            if screen.blit_frame(frame).is_err() {
+                break;
+            }
+            let (buf, width, height) = screen.pixels_rgba().to_contiguous_buf();
+            let image = image::RgbaImage::from_fn(width as u32, height as u32, |x, y| {
                 let rgba = buf.as_ref()[y as usize * width + x as usize];
                 image::Rgba([rgba.r, rgba.g, rgba.b, rgba.a])
-            },
-        )));
-    }
+            });
+            if !push(DynamicImage::ImageRgba8(image)) {
+                break;
+            }
+        }
+    });
 
     // fps is only an average across all frames, there is no per frame delay modelling
-    let fps = frames.len() as f64 / (delay.max(1) as f64 / 100.0);
-    Ok((FrameIterator::AnimatedImage {
-        frames,
-        current_frame: 0,
-    }, fps))
+    let fps = frames as f64 / (delay.max(1) as f64 / 100.0);
+    Ok((FrameIterator::Animation(animation), fps))
 }
 
 /// Opens the specified animated WEBP file and returns a `FrameIterator`.
 ///
-/// This helper function opens an animated WEBP file and creates a `FrameIterator::AnimatedWebp`
-/// variant containing all the frames of the animation.
+/// This helper function opens an animated WEBP file and creates a `FrameIterator::Animation`
+/// variant, decoding the frames of the animation on a background thread while it plays. The
+/// frames are counted and timed first, reading the headers of the container.
 ///
 /// # Arguments
 ///
@@ -407,48 +1261,192 @@ fn open_webp(path: &Path) -> Result<(FrameIterator, f64), MyError> {
         .map_err(|e| MyError::Application(format!("{error}: {e:?}", error = ERROR_OPENING_RESOURCE)))?;
     let mut buf = Vec::new();
     file.read_to_end(&mut buf)?;
-    let mut frames = Vec::new();
-    let mut first_timestamp: i32 = i32::MAX;
-    let mut last_timestamp: i32 = i32::MIN;
     // this code is based on the code example here:
     // https://developers.google.com/speed/webp/docs/container-api#webpanimdecoder_api
-    unsafe {
-        let mut options = webp::WebPAnimDecoderOptions{
-            color_mode: webp::WEBP_CSP_MODE::MODE_RGBA,
-            use_threads: 0,
-            padding: [0, 0, 0, 0, 0, 0, 0],
-        };
-        webp::WebPAnimDecoderOptionsInit(&mut options);
-        let dec = webp::WebPAnimDecoderNew(&webp::WebPData{bytes: buf.as_ptr(), size: buf.len()}, &options);
+    let (frames, duration) = unsafe {
+        let dec = new_webp_decoder(&buf);
+        if dec.is_null() {
+            return Err(MyError::Application(ERROR_DECODING_IMAGE.to_string()));
+        }
         let mut info = webp::WebPAnimInfo::default();
         webp::WebPAnimDecoderGetInfo(dec, &mut info);
-        let frame_sz = (info.canvas_width * info.canvas_height * 4) as usize;
-        for _ in 0..info.loop_count {
-            while webp::WebPAnimDecoderHasMoreFrames(dec) != 0 {
-                let mut buf: *mut u8 = std::ptr::null_mut();
-                let mut timestamp: i32 = 0;
-                webp::WebPAnimDecoderGetNext(dec, &mut buf, &mut timestamp);
-                first_timestamp = first_timestamp.min(timestamp);
-                last_timestamp = last_timestamp.max(timestamp);
-                if let Some(image) = image::RgbaImage::from_raw(
-                    info.canvas_width,
-                    info.canvas_height,
-                    std::slice::from_raw_parts(buf, frame_sz).to_vec(),
-                ) {
-                    frames.push(DynamicImage::ImageRgba8(image));
-                } else {
-                    // eprintln!("Failed to decode frame");
+        let demuxer = webp::WebPAnimDecoderGetDemuxer(dec);
+        let mut iter: webp::WebPIterator = std::mem::zeroed();
+        let mut duration: i64 = 0;
+        if webp::WebPDemuxGetFrame(demuxer, 1, &mut iter) != 0 {
+            loop {
+                duration += iter.duration as i64;
+                if webp::WebPDemuxNextFrame(&mut iter) == 0 {
+                    break;
                 }
             }
-            webp::WebPAnimDecoderReset(dec);
+            webp::WebPDemuxReleaseIterator(&mut iter);
         }
         webp::WebPAnimDecoderDelete(dec);
-    }
+        (info.frame_count as usize, duration)
+    };
+
+    let animation = Animation::decode(frames, move |push| unsafe {
+        let dec = new_webp_decoder(&buf);
+        if dec.is_null() {
+            return;
+        }
+        let mut info = webp::WebPAnimInfo::default();
+        webp::WebPAnimDecoderGetInfo(dec, &mut info);
+        let frame_sz = (info.canvas_width * info.canvas_height * 4) as usize;
+        while webp::WebPAnimDecoderHasMoreFrames(dec) != 0 {
+            let mut pixels: *mut u8 = std::ptr::null_mut();
+            let mut timestamp: i32 = 0;
+            if webp::WebPAnimDecoderGetNext(dec, &mut pixels, &mut timestamp) == 0 {
+                break;
+            }
+            let Some(image) = image::RgbaImage::from_raw(
+                info.canvas_width,
+                info.canvas_height,
+                std::slice::from_raw_parts(pixels, frame_sz).to_vec(),
+            ) else {
+                break;
+            };
+            if !push(DynamicImage::ImageRgba8(image)) {
+                break;
+            }
+        }
+        webp::WebPAnimDecoderDelete(dec);
+    });
 
     // fps is only an average across all frames, there is no per frame delay modelling
-    let fps = frames.len() as f64 / ((last_timestamp.saturating_sub(first_timestamp).max(1)) as f64 / 1000.0);
-    Ok((FrameIterator::AnimatedImage {
-        frames,
-        current_frame: 0,
-    }, fps))
+    let fps = frames as f64 / (duration.max(1) as f64 / 1000.0);
+    Ok((FrameIterator::Animation(animation), fps))
+}
+
+/// Creates a decoder of an animated WEBP file, decoding the frames to RGBA.
+///
+/// # Arguments
+///
+/// * `data` - The content of the file, which must outlive the decoder.
+///
+/// # Returns
+///
+/// The decoder, to delete with `WebPAnimDecoderDelete`, or a null pointer if the file is not a
+/// valid WEBP file.
+unsafe fn new_webp_decoder(data: &[u8]) -> *mut webp::WebPAnimDecoder {
+    let mut options = webp::WebPAnimDecoderOptions {
+        color_mode: webp::WEBP_CSP_MODE::MODE_RGBA,
+        use_threads: 0,
+        padding: [0, 0, 0, 0, 0, 0, 0],
+    };
+    webp::WebPAnimDecoderOptionsInit(&mut options);
+    webp::WebPAnimDecoderNew(
+        &webp::WebPData {
+            bytes: data.as_ptr(),
+            size: data.len(),
+        },
+        &options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 transparent PNG
+    const PNG_BASE64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_decode_data_url() {
+        let data = decode_data_url(&format!("data:image/png;base64,{PNG_BASE64}")).unwrap();
+        assert_eq!(MediaKind::from_bytes(&data), Some(MediaKind::Image));
+        assert_eq!(decode_data_url("data:text/plain,a%20b").unwrap(), b"a b");
+        assert!(decode_data_url("data:image/png;base64").is_err());
+    }
+
+    #[test]
+    fn test_media_kind_from_path() {
+        assert_eq!(MediaKind::from_path(Path::new("photo.JPG")), MediaKind::Image);
+        assert_eq!(MediaKind::from_path(Path::new("stack.tif")), MediaKind::Tiff);
+        assert_eq!(MediaKind::from_path(Path::new("/dev/video0")), MediaKind::Video);
+        assert_eq!(MediaKind::from_path(Path::new("song.FLAC")), MediaKind::Audio);
+        let url = |url| Url::parse(url).unwrap();
+        assert_eq!(url_kind(&url("https://example.com/a/clip.mkv?t=1")), Some(MediaKind::Video));
+        // Only known once downloaded, no tool is required up front
+        assert_eq!(url_kind(&url("https://picsum.photos/200")), None);
+        assert!(required_tools("https://picsum.photos/200", None).is_empty());
+    }
+
+    #[test]
+    fn test_decode_image_applies_exif_orientation() {
+        let mut jpeg = Vec::new();
+        DynamicImage::new_rgb8(4, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+        // An APP1 segment with an orientation tag of 6: turn the image a quarter clockwise
+        let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0]);
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(&exif);
+        jpeg.splice(2..2, segment);
+
+        let path = std::env::temp_dir().join(format!("tplay-exif-test-{}.jpg", std::process::id()));
+        std::fs::write(&path, &jpeg).unwrap();
+        let image = decode_image(&path);
+        std::fs::remove_file(&path).unwrap();
+        let image = image.unwrap();
+        assert_eq!((image.width(), image.height()), (2, 4));
+    }
+
+    #[test]
+    fn test_open_gif() {
+        let path = std::env::temp_dir().join(format!("tplay-gif-test-{}.gif", std::process::id()));
+        {
+            let mut file = File::create(&path).unwrap();
+            let mut encoder =
+                gif::Encoder::new(&mut file, 2, 1, &[0, 0, 0, 255, 255, 255]).unwrap();
+            for color in [0, 1, 0, 1] {
+                let mut frame = gif::Frame::from_indexed_pixels(2, 1, vec![color; 2], None);
+                frame.delay = 10;
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        let (mut media, fps) = open_gif(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(fps, 10.0);
+        assert_eq!(media.duration(fps), Some(0.4));
+        media.skip_frames(1);
+        let frame = media.next().unwrap().into_rgb8();
+        assert_eq!(frame.get_pixel(0, 0).0, [255; 3]);
+        assert_eq!(media.count(), 2);
+    }
+
+    #[test]
+    fn test_skip_frames_stops_at_end_of_animation() {
+        let mut media = FrameIterator::AnimatedImage {
+            frames: vec![DynamicImage::new_rgb8(1, 1); 5],
+            current_frame: 0,
+        };
+        media.skip_frames(3);
+        assert!(media.next().is_some());
+        media.skip_frames(3);
+        assert!(media.next().is_none());
+        media.seek(0.2, 10.0);
+        assert_eq!(media.count(), 3);
+    }
+
+    #[test]
+    fn test_still_lasts_its_frames() {
+        let mut media = FrameIterator::Still {
+            image: DynamicImage::new_rgb8(1, 1),
+            frames: 8,
+            current_frame: 0,
+        };
+        media.skip_frames(5);
+        assert_eq!(media.by_ref().count(), 3);
+        media.seek(0.5, 4.0);
+        assert_eq!(media.position(4.0), Some(0.5));
+        assert_eq!(media.count(), 6);
+        assert_eq!(FrameIterator::Image(None).position(4.0), None);
+    }
 }
@@ -0,0 +1,58 @@
+//! Measures how many terminal columns the glyphs of a character map span, so that the frames are
+//! laid out for them: emoji and CJK characters span two columns, most other characters one.
+//!
+//! The frames have as many glyphs per row as fit in the terminal, and the narrower glyphs of a
+//! character map mixing widths are padded with spaces to the widest one, keeping the columns
+//! aligned.
+use unicode_width::UnicodeWidthStr;
+
+/// Returns the number of terminal columns a glyph spans, at least one.
+///
+/// # Arguments
+///
+/// * `glyph` - The glyph (grapheme cluster).
+pub fn width(glyph: &str) -> u32 {
+    glyph.width().max(1) as u32
+}
+
+/// Returns the number of terminal columns the widest of the glyphs spans, at least one.
+///
+/// # Arguments
+///
+/// * `glyphs` - The glyphs of a character map.
+pub fn columns(glyphs: &[String]) -> u32 {
+    glyphs.iter().map(|glyph| width(glyph)).max().unwrap_or(1)
+}
+
+/// Pads a glyph narrower than a number of columns with spaces.
+///
+/// # Arguments
+///
+/// * `glyph` - The glyph.
+/// * `columns` - The number of columns the glyph spans once padded.
+pub fn pad(glyph: &str, columns: u32) -> String {
+    let padding = columns.saturating_sub(width(glyph)) as usize;
+    format!("{glyph}{}", " ".repeat(padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::char_maps::split_glyphs;
+
+    #[test]
+    fn test_glyph_width() {
+        assert_eq!(width("@"), 1);
+        assert_eq!(width("🍎"), 2);
+        assert_eq!(width("字"), 2);
+        assert_eq!(width(""), 1);
+        assert_eq!(columns(&split_glyphs(" .:-=+*#%@")), 1);
+        assert_eq!(columns(&split_glyphs("🍎🍏❤️😊")), 2);
+
+        // Mixed widths are padded to the widest
+        let glyphs = split_glyphs(".o🍎");
+        let columns = columns(&glyphs);
+        let padded: Vec<String> = glyphs.iter().map(|glyph| pad(glyph, columns)).collect();
+        assert_eq!(padded, [". ", "o ", "🍎"]);
+    }
+}
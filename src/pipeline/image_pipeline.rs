@@ -1,20 +1,103 @@
 //! The `ImagePipeline` module contains a struct and implementation for converting images to ASCII
 //! art. It offers a pipeline for processing images by resizing and converting them into ASCII
 //! representations using a character lookup table.
-use crate::common::errors::*;
+use super::{
+    blocks::{self, BlockMode},
+    braille::{self, BrailleOptions},
+    cell_grid::CellGrid,
+    color_vision::CvdFilter,
+    comparison::Comparison,
+    dither::{self, Dither},
+    edges, glyph_width, letterbox,
+    luma::LumaFormula,
+    orientation::Orientation,
+    panorama::Viewport,
+    stereo::StereoMode,
+    transform::Transform,
+};
+use crate::{common::errors::*, display::SourceArea};
 use fast_image_resize as fr;
 use image::{DynamicImage, GrayImage};
 
+/// Pixels of the reprojected 360° view per cell, enough for the braille dots (2x4).
+const PANORAMA_SAMPLES: (u32, u32) = (2, 4);
+/// Glyph drawn over the painted backgrounds by default: none, only the colors are shown.
+pub const DEFAULT_BACKGROUND_GLYPH: &str = " ";
+
+/// What the color of each pixel is applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaintMode {
+    /// The glyph of the cell, looked up from the brightness of the pixel.
+    #[default]
+    Glyph,
+    /// The background of the cell, for a "pixel art" look.
+    Background,
+}
+
+impl std::str::FromStr for PaintMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "glyph" | "fg" => Ok(PaintMode::Glyph),
+            "bg" | "background" => Ok(PaintMode::Background),
+            _ => Err(format!("`{name}` is not a paint mode (glyph, bg)")),
+        }
+    }
+}
+
 /// The `ImagePipeline` struct encapsulates the process of converting an image to ASCII art. It
 /// stores the target resolution (width and height) and the character lookup table used for the
 /// conversion.
+#[derive(Clone)]
 pub struct ImagePipeline {
     /// The target resolution (width and height) for the pipeline.
     pub target_resolution: (u32, u32),
-    /// The character lookup table used for the conversion.
-    pub char_map: Vec<char>,
+    /// The character lookup table used for the conversion, one glyph (grapheme cluster) per entry.
+    pub char_map: Vec<String>,
     /// Whether to add newlines to the output at the end of each line
     pub new_lines: bool,
+    /// Draws braille dots instead of the characters of the lookup table, if set.
+    pub braille: Option<BrailleOptions>,
+    /// Draws two-color block elements instead of the characters of the lookup table, if set and
+    /// braille dots are not.
+    pub blocks: Option<BlockMode>,
+    /// Paints the background of each cell with the color of its pixel, drawing the given glyph
+    /// over it, instead of coloring the glyphs of the lookup table, if set.
+    pub background_glyph: Option<String>,
+    /// How the gray levels are reduced to the glyphs of the lookup table.
+    pub dither: Dither,
+    /// How the colors are converted to the gray levels the glyphs, dots and edges are looked up
+    /// from.
+    pub luma: LumaFormula,
+    /// Draws the edges of the image with directional glyphs over the characters of the lookup
+    /// table, if set to the strength of the gradients (0-255) from which edges are drawn.
+    pub edges: Option<u8>,
+    /// Renders the pixels of the image instead of characters, with the given number of pixels
+    /// per cell (width, height), for the displays drawing pixels (e.g. Sixel graphics).
+    pub pixels_per_cell: Option<(u32, u32)>,
+    /// Renders each half of the frame with a different value of a setting, if set.
+    pub comparison: Option<Comparison>,
+    /// How the frames are turned in the target resolution.
+    pub orientation: Orientation,
+    /// How the source frames are flipped and rotated before they are rendered.
+    pub transform: Transform,
+    /// Merges the views of stereoscopic 3D frames into a single image, if set.
+    pub stereo: Option<StereoMode>,
+    /// Shows the part of equirectangular 360° frames seen through a viewport, if set.
+    pub panorama: Option<Viewport>,
+    /// Keeps the aspect ratio of the frames, fitting them in the target resolution between blank
+    /// bars, if set to the width of a cell relative to its height. Frames are stretched to the
+    /// target resolution otherwise.
+    pub letterbox: Option<f64>,
+    /// The glyph drawn, in the default colors of the terminal, for the fully transparent pixels
+    /// of the images with an alpha channel (e.g. GIF, WebP and APNG animations).
+    pub transparent_glyph: String,
+    /// Simulates or compensates a color vision deficiency in the resized frames, if set.
+    pub color_filter: Option<CvdFilter>,
+    /// Draws every row twice, for the fonts whose cells are too wide for their height (e.g.
+    /// square cells): half as many rows are rendered, each one glyph wide and two rows tall.
+    pub double_rows: bool,
 }
 
 impl ImagePipeline {
@@ -25,13 +108,29 @@ impl ImagePipeline {
     ///
     /// * `target_resolution` - A tuple of two u32 integers representing the target width and
     ///   height.
-    /// * `char_map` - A vector of characters to be used as the lookup table for ASCII
-    ///   conversion.
-    pub fn new(target_resolution: (u32, u32), char_map: Vec<char>, new_lines: bool) -> Self {
+    /// * `char_map` - A vector of glyphs to be used as the lookup table for ASCII conversion (see
+    ///   `char_maps::split_glyphs`).
+    pub fn new(target_resolution: (u32, u32), char_map: Vec<String>, new_lines: bool) -> Self {
         Self {
             target_resolution,
             char_map,
             new_lines,
+            braille: None,
+            blocks: None,
+            background_glyph: None,
+            dither: Dither::Threshold,
+            luma: LumaFormula::default(),
+            edges: None,
+            pixels_per_cell: None,
+            comparison: None,
+            orientation: Orientation::Normal,
+            transform: Transform::default(),
+            stereo: None,
+            panorama: None,
+            letterbox: None,
+            transparent_glyph: " ".to_string(),
+            color_filter: None,
+            double_rows: false,
         }
     }
 
@@ -47,6 +146,47 @@ impl ImagePipeline {
         self
     }
 
+    /// Returns the number of terminal columns each glyph spans: the width of the widest glyph of
+    /// the lookup table (2 for emoji and CJK characters), or of the background glyph. Braille
+    /// dots, blocks and pixels span a single column.
+    pub fn glyph_columns(&self) -> u32 {
+        if self.braille.is_some() || self.blocks.is_some() || self.pixels_per_cell.is_some() {
+            return 1;
+        }
+        match &self.background_glyph {
+            Some(glyph) => glyph_width::width(glyph),
+            None => glyph_width::columns(&self.char_map),
+        }
+    }
+
+    /// Returns the number of times each row is drawn: twice with `double_rows`, unless the
+    /// pipeline keeps the pixels of the frames.
+    fn row_repeat(&self) -> u32 {
+        match self.double_rows && self.pixels_per_cell.is_none() {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    /// Lays the glyphs out in an area of the terminal: the target resolution is set to the number
+    /// of glyphs fitting in the area (see `glyph_columns`) and of rows drawn (see `double_rows`),
+    /// and the letterbox and the viewport of 360° media are given the aspect of the glyphs.
+    /// Laying out again, e.g. after the character map changed, starts over from the area.
+    ///
+    /// # Arguments
+    ///
+    /// * `area` - The size of the area (columns, rows), in terminal cells.
+    /// * `cell_aspect` - The width of a terminal cell relative to its height.
+    pub fn lay_out(&mut self, area: (u32, u32), cell_aspect: f64) {
+        let (columns, rows) = (self.glyph_columns(), self.row_repeat());
+        let glyph_aspect = cell_aspect * columns as f64 / rows as f64;
+        self.target_resolution = ((area.0 / columns).max(1), (area.1 / rows).max(1));
+        self.letterbox = self.letterbox.map(|_| glyph_aspect);
+        if let Some(viewport) = &mut self.panorama {
+            viewport.cell_aspect = glyph_aspect;
+        }
+    }
+
     /// Resizes a given `DynamicImage` to the target resolution specified in the `self` object.
     ///
     /// This function takes a reference to a `DynamicImage` and resizes it using the nearest
@@ -71,21 +211,38 @@ impl ImagePipeline {
     /// * An error occurs while resizing the image using the `fr::Resizer`.
     /// * An error occurs while creating an `ImageBuffer` from the resized image data.
     pub fn resize(&self, img: &DynamicImage) -> Result<DynamicImage, MyError> {
+        self.resize_to(img, self.target_resolution)
+    }
+
+    /// Resizes a given `DynamicImage` to the given resolution, see `resize`.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - A reference to the `DynamicImage` to be resized.
+    /// * `resolution` - The width and height of the resized image.
+    ///
+    /// The `color_filter` is applied to the resized image, at the few pixels rendered.
+    fn resize_to(
+        &self,
+        img: &DynamicImage,
+        resolution: (u32, u32),
+    ) -> Result<DynamicImage, MyError> {
         let width =
             img.width();
         let height =
             img.height();
-        let src_image = fr::images::Image::from_vec_u8(
-            width,
-            height,
-            img.to_owned().into_rgb8().to_vec(),
-            fr::PixelType::U8x3,
-        )
-        .map_err(|err| MyError::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
+        // The alpha channel is kept for the transparent pixels, see `transparent_glyph`
+        let alpha = img.color().has_alpha();
+        let (pixels, pixel_type) = match alpha {
+            true => (img.to_rgba8().into_raw(), fr::PixelType::U8x4),
+            false => (img.to_owned().into_rgb8().to_vec(), fr::PixelType::U8x3),
+        };
+        let src_image = fr::images::Image::from_vec_u8(width, height, pixels, pixel_type)
+            .map_err(|err| MyError::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
         let mut dst_image = fr::images::Image::new(
-            self.target_resolution.0,
-            self.target_resolution.1,
-            fr::PixelType::U8x3,
+            resolution.0,
+            resolution.1,
+            pixel_type,
         );
 
         let mut resizer = fr::Resizer::new();
@@ -94,21 +251,24 @@ impl ImagePipeline {
             .map_err(|err| MyError::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
 
         let dst_image = dst_image.into_vec();
-        let img_buff = image::ImageBuffer::<image::Rgb<u8>, _>::from_vec(
-            self.target_resolution.0,
-            self.target_resolution.1,
-            dst_image,
-        )
-        .ok_or(MyError::Pipeline(ERROR_DATA.to_string()))?;
-        Ok(DynamicImage::ImageRgb8(img_buff))
+        let (width, height) = resolution;
+        let resized = match alpha {
+            true => image::RgbaImage::from_vec(width, height, dst_image).map(Into::into),
+            false => image::RgbImage::from_vec(width, height, dst_image).map(Into::into),
+        };
+        let mut resized = resized.ok_or(MyError::Pipeline(ERROR_DATA.to_string()))?;
+        if let Some(filter) = &self.color_filter {
+            filter.apply(&mut resized);
+        }
+        Ok(resized)
     }
 
     /// Converts the given grayscale image to ASCII art using the character lookup table stored in
     /// this `ImagePipeline`.
     ///
     /// This method iterates through the pixels of the input image and maps each pixel's grayscale
-    /// value to a character from the lookup table. The resulting ASCII art is returned as a
-    /// `String`.
+    /// value to a character from the lookup table, dithered with the `dither` method. The
+    /// resulting ASCII art is returned as a `String`.
     ///
     /// # Arguments
     ///
@@ -121,13 +281,12 @@ impl ImagePipeline {
         let (width, height) = (input.width(), input.height());
         let capacity = (width + 1) * height + 1;
         let mut output = String::with_capacity(capacity as usize);
+        let levels = dither::levels(input, self.char_map.len(), self.dither);
 
         for y in 0..height {
-            output.extend((0..width).map(|x| {
-                let lum = input.get_pixel(x, y)[0] as u32;
-                let lookup_idx = self.char_map.len() * lum as usize / (u8::MAX as usize + 1);
-                self.char_map[lookup_idx]
-            }));
+            for x in 0..width {
+                output.push_str(&self.char_map[levels[(y * width + x) as usize]]);
+            }
 
             // Add newlines to the end of each row except the last. NOTE: these
             // are not really needed because the terminal will wrap lines. But
@@ -141,12 +300,232 @@ impl ImagePipeline {
 
         output
     }
+
+    /// Renders the given image at the target resolution: resizes it and converts it to a grid of
+    /// cells, with the characters of the lookup table (and their `edges`), braille dots or
+    /// blocks, or keeps its pixels if `pixels_per_cell` is set. With a `comparison`, each half uses its own value of the
+    /// compared setting. With an `orientation`, the frame is turned to fill the target resolution.
+    /// With a `stereo` mode, the views of both eyes are merged first. With a `panorama` viewport,
+    /// the part of the frame it sees is reprojected before it is rendered. With a `transform`, the
+    /// frame is flipped and rotated next. With a `letterbox`, the frame is rendered at the largest
+    /// size keeping its aspect ratio, and centered. With a `color_filter`, the colors of the
+    /// resized frame are filtered. With `double_rows`, each row of the frame is drawn twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - A reference to the `DynamicImage` to be rendered.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CellGrid` of the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the image cannot be resized, see `resize`.
+    pub fn render(&self, img: &DynamicImage) -> Result<CellGrid, MyError> {
+        if self.row_repeat() > 1 {
+            let mut single = self.clone();
+            single.double_rows = false;
+            return Ok(single.render(img)?.repeat_rows(self.row_repeat()));
+        }
+        if let Some(stereo) = self.stereo {
+            let mut mono = self.clone();
+            mono.stereo = None;
+            return mono.render(&stereo.merge(img));
+        }
+        if let Some(viewport) = &self.panorama {
+            let mut flat = self.clone();
+            flat.panorama = None;
+            // The viewport is reprojected at the aspect of the target resolution
+            flat.letterbox = None;
+            // Sized for the frame before it is turned, see `orientation`
+            let (width, height) = self.orientation.upright_resolution(self.target_resolution);
+            let (cell_width, cell_height) = self.pixels_per_cell.unwrap_or(PANORAMA_SAMPLES);
+            let aspect = match self.pixels_per_cell {
+                Some(_) => (width * cell_width) as f64 / (height * cell_height).max(1) as f64,
+                None => width as f64 * viewport.cell_aspect / height.max(1) as f64,
+            };
+            let size = (width * cell_width, height * cell_height);
+            return flat.render(&viewport.reproject(img, size, aspect));
+        }
+        if !self.transform.is_identity() {
+            let mut plain = self.clone();
+            plain.transform = Transform::default();
+            return plain.render(&self.transform.apply(img));
+        }
+        if let Some(cell_aspect) = self.letterbox {
+            let cell_aspect = match self.pixels_per_cell {
+                Some((cell_width, cell_height)) => cell_width as f64 / cell_height.max(1) as f64,
+                None => cell_aspect,
+            };
+            // The size of the frame once turned, see `orientation`
+            let size = self.orientation.upright_resolution((img.width(), img.height()));
+            let fitted = letterbox::fit(size, self.target_resolution, cell_aspect);
+            if fitted != self.target_resolution {
+                let mut boxed = self.clone();
+                boxed.letterbox = None;
+                boxed.target_resolution = fitted;
+                let grid = boxed.render(img)?;
+                return Ok(letterbox::center(&grid, self.target_resolution, self.new_lines));
+            }
+        }
+        if self.orientation != Orientation::Normal {
+            let mut upright = self.clone();
+            upright.orientation = Orientation::Normal;
+            if self.braille.is_none() && self.blocks.is_none() {
+                upright.target_resolution =
+                    self.orientation.upright_resolution(self.target_resolution);
+                let grid = upright.render(img)?;
+                return Ok(self.orientation.reorient(&grid, self.new_lines));
+            }
+            // Braille dots and blocks draw shapes that do not turn with the grid
+            return upright.render(&self.orientation.turn_image(img));
+        }
+        if let Some(comparison) = &self.comparison {
+            return comparison.render(self, img);
+        }
+        if let Some((cell_width, cell_height)) = self.pixels_per_cell {
+            let (width, height) = self.target_resolution;
+            let pixels = self.resize_to(img, (width * cell_width, height * cell_height))?;
+            return Ok(CellGrid::from_pixels(width, height, pixels.into_rgb8()));
+        }
+        match (self.braille, self.blocks) {
+            (Some(options), _) => {
+                let (width, height) = self.target_resolution;
+                let dots = self.resize_to(
+                    img,
+                    (width * braille::DOTS_WIDTH, height * braille::DOTS_HEIGHT),
+                )?;
+                Ok(braille::to_braille(
+                    &self.luma.to_gray(&dots),
+                    &dots.to_rgb8(),
+                    options,
+                    self.new_lines,
+                ))
+            }
+            (None, Some(mode)) => {
+                let (width, height) = self.target_resolution;
+                let (cell_width, cell_height) = mode.cell_size();
+                let pixels = self.resize_to(img, (width * cell_width, height * cell_height))?;
+                Ok(blocks::to_blocks(&pixels.to_rgb8(), mode, self.new_lines))
+            }
+            (None, None) => {
+                let grid = self.to_cells(&self.resize(img)?);
+                let Some(threshold) = self.edges else {
+                    return Ok(grid);
+                };
+                let (width, height) = self.target_resolution;
+                let (cell_width, cell_height) = edges::SAMPLES_PER_CELL;
+                let samples = self.resize_to(img, (width * cell_width, height * cell_height))?;
+                Ok(edges::draw_edges(grid, &self.luma.to_gray(&samples), threshold, self.new_lines))
+            }
+        }
+    }
+
+    /// Returns the cells of the frames rendered by `render` showing an image, so that a cell can
+    /// be traced back to its pixels (see `SourceArea::pixel_at`).
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The width and height of the image, in pixels.
+    ///
+    /// # Returns
+    ///
+    /// The area, or `None` if the image is not shown as a whole and unturned (e.g. 360° media,
+    /// stereo pairs, comparisons or rotated frames).
+    pub fn source_area(&self, image: (u32, u32)) -> Option<SourceArea> {
+        if self.row_repeat() > 1 {
+            let mut single = self.clone();
+            single.double_rows = false;
+            let area = single.source_area(image)?;
+            return Some(SourceArea {
+                y: area.y * self.row_repeat(),
+                height: area.height * self.row_repeat(),
+                ..area
+            });
+        }
+        let turned = self.orientation != Orientation::Normal || !self.transform.is_identity();
+        if self.stereo.is_some() || self.panorama.is_some() || self.comparison.is_some() || turned {
+            return None;
+        }
+        let (width, height) = self.target_resolution;
+        let fitted = match (self.letterbox, self.pixels_per_cell) {
+            (Some(_), Some((cell_width, cell_height))) => letterbox::fit(
+                image,
+                self.target_resolution,
+                cell_width as f64 / cell_height.max(1) as f64,
+            ),
+            (Some(cell_aspect), None) => letterbox::fit(image, self.target_resolution, cell_aspect),
+            (None, _) => self.target_resolution,
+        };
+        // Centered like `letterbox::center` does
+        Some(SourceArea {
+            x: width.saturating_sub(fitted.0) / 2,
+            y: height.saturating_sub(fitted.1) / 2,
+            width: fitted.0,
+            height: fitted.1,
+        })
+    }
+
+    /// Converts the given image to a grid of cells, the glyph of each cell being looked up from
+    /// the brightness of the pixel (with the `luma` formula, dithered with the `dither` method), and its color being the
+    /// color of the pixel. With a `background_glyph`, the background of each cell is painted with
+    /// the color of the pixel instead, under the glyph drawn in a darker shade. The fully
+    /// transparent pixels are drawn with the `transparent_glyph` instead. The glyphs narrower than
+    /// the widest are padded with spaces, keeping the columns aligned.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A reference to the `DynamicImage` to be converted, already resized.
+    ///
+    /// # Returns
+    ///
+    /// A `CellGrid` with one cell per pixel of the input image.
+    pub fn to_cells(&self, input: &DynamicImage) -> CellGrid {
+        let (gray, rgb) = (self.luma.to_gray(input), input.to_rgb8());
+        let mut grid = CellGrid::new(input.width(), input.height(), self.new_lines);
+        let alpha = input.color().has_alpha().then(|| input.to_rgba8());
+        let transparent = |index: usize| {
+            alpha
+                .as_ref()
+                .is_some_and(|alpha| alpha.as_raw()[index * 4 + 3] == 0)
+        };
+        let columns = self.glyph_columns();
+        let transparent_glyph = glyph_width::pad(&self.transparent_glyph, columns);
+        match &self.background_glyph {
+            Some(glyph) => {
+                for (index, color) in rgb.pixels().enumerate() {
+                    if transparent(index) {
+                        grid.push_transparent(&transparent_glyph);
+                        continue;
+                    }
+                    let shade = color.0.map(|c| c / 2);
+                    grid.push_with_background(glyph, shade, color.0);
+                }
+            }
+            None => {
+                let char_map: Vec<String> = self
+                    .char_map
+                    .iter()
+                    .map(|glyph| glyph_width::pad(glyph, columns))
+                    .collect();
+                let levels = dither::levels(&gray, char_map.len(), self.dither);
+                for (index, (level, color)) in levels.into_iter().zip(rgb.pixels()).enumerate() {
+                    match transparent(index) {
+                        true => grid.push_transparent(&transparent_glyph),
+                        false => grid.push(&char_map[level], color.0),
+                    }
+                }
+            }
+        }
+        grid
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pipeline::char_maps::CHARS1;
+    use crate::pipeline::char_maps::{split_glyphs, CHARS1};
     use image::{DynamicImage, ImageError};
     use reqwest;
     use std::io::Cursor;
@@ -165,14 +544,14 @@ mod tests {
 
     #[test]
     fn test_new() {
-        let image = ImagePipeline::new((120, 80), vec!['a', 'b', 'c'], false);
+        let image = ImagePipeline::new((120, 80), split_glyphs("abc"), false);
         assert_eq!(image.target_resolution, (120, 80));
-        assert_eq!(image.char_map, vec!['a', 'b', 'c']);
+        assert_eq!(image.char_map, ["a", "b", "c"]);
     }
 
     #[test]
     fn test_process() {
-        let image = ImagePipeline::new((120, 80), vec!['a', 'b', 'c'], false);
+        let image = ImagePipeline::new((120, 80), split_glyphs("abc"), false);
         let input = download_image(TEST_IMAGE_URL).expect("Failed to download image");
 
         let output = image.resize(&input).expect("Failed to resize image");
@@ -182,7 +561,7 @@ mod tests {
 
     #[test]
     fn test_to_ascii_ext() {
-        let image = ImagePipeline::new((120, 80), CHARS1.chars().collect(), false);
+        let image = ImagePipeline::new((120, 80), split_glyphs(CHARS1), false);
         let input = download_image(TEST_IMAGE_URL).expect("Failed to download image");
         let output = image.to_ascii(
             &image
@@ -195,7 +574,7 @@ mod tests {
 
     #[test]
     fn test_to_ascii() {
-        let image = ImagePipeline::new((120, 80), vec!['a', 'b', 'c'], false);
+        let image = ImagePipeline::new((120, 80), split_glyphs("abc"), false);
         let input = download_image(TEST_IMAGE_URL).expect("Failed to download image");
         let output = image.to_ascii(
             &image
@@ -205,4 +584,60 @@ mod tests {
         );
         assert_eq!(output.len(), 120 * 80);
     }
+
+    #[test]
+    fn test_to_cells() {
+        let image = ImagePipeline::new((2, 2), split_glyphs("🍎❤️"), true);
+        let input = DynamicImage::ImageRgb8(image::RgbImage::from_fn(2, 2, |x, _| {
+            image::Rgb(if x == 0 { [0, 0, 0] } else { [255, 255, 255] })
+        }));
+        let grid = image.to_cells(&input);
+        assert_eq!((grid.width(), grid.height()), (2, 2));
+        assert_eq!(grid.row_text(1), "🍎❤️");
+        assert_eq!(grid.cell(1, 0).map(|cell| cell.color), Some([255; 3]));
+        assert_eq!(grid.to_text(), "🍎❤️\r\n🍎❤️");
+
+        let mut image = image;
+        image.background_glyph = Some(DEFAULT_BACKGROUND_GLYPH.to_string());
+        let grid = image.to_cells(&input);
+        assert_eq!(grid.row_text(0), "  ");
+        assert_eq!(grid.cell(1, 0).and_then(|cell| cell.background), Some([255; 3]));
+
+        // The transparent pixels keep the default colors, through the resize, their glyph padded
+        // to the width of the emoji
+        image.background_glyph = None;
+        image.transparent_glyph = ".".to_string();
+        let input = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(4, 4, |x, _| {
+            image::Rgba([255, 255, 255, if x < 2 { 0 } else { 255 }])
+        }));
+        let grid = image.render(&input).unwrap();
+        assert_eq!(grid.row_text(0), ". ❤️");
+        assert!(grid.cell(0, 1).is_some_and(|cell| cell.transparent));
+        assert!(!grid.cell(1, 1).unwrap().transparent);
+    }
+
+    #[test]
+    fn test_lay_out() {
+        // Emoji span two columns, half as many fit in the area and they are twice as wide
+        let mut image = ImagePipeline::new((80, 24), split_glyphs("🍎🍏❤️😊"), false);
+        image.letterbox = Some(0.5);
+        image.lay_out((80, 24), 0.5);
+        assert_eq!(image.target_resolution, (40, 24));
+        assert_eq!(image.letterbox, Some(1.0));
+
+        // Braille dots span one column
+        image.braille = Some(BrailleOptions::default());
+        image.lay_out((80, 24), 0.5);
+        assert_eq!(image.target_resolution, (80, 24));
+        assert_eq!(image.letterbox, Some(0.5));
+
+        // Doubled rows are rendered half as many, and drawn twice
+        image.double_rows = true;
+        image.lay_out((80, 24), 0.5);
+        assert_eq!(image.target_resolution, (80, 12));
+        assert_eq!(image.letterbox, Some(0.25));
+        image.letterbox = None;
+        let grid = image.render(&DynamicImage::new_rgb8(8, 8)).unwrap();
+        assert_eq!((grid.width(), grid.height()), (80, 24));
+    }
 }
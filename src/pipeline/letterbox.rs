@@ -0,0 +1,113 @@
+//! Keeps the aspect ratio of the frames: instead of stretching them to the size of the terminal,
+//! they are fitted in it, with blank bars above and below (letterboxing) or on each side
+//! (pillarboxing).
+//!
+//! Terminal cells are not square, about twice as tall as they are wide, so the size of the frame
+//! in cells depends on the aspect of the cells (`--cell-aspect`). Frames drawn with pixels (e.g.
+//! Sixel graphics) use the exact size of the cells in pixels instead.
+use super::cell_grid::CellGrid;
+use image::RgbImage;
+
+/// Returns the largest area of cells showing an image with its aspect ratio within an area.
+///
+/// # Arguments
+///
+/// * `image` - The width and height of the image, in pixels.
+/// * `area` - The width and height of the area, in cells.
+/// * `cell_aspect` - The width of a cell relative to its height.
+///
+/// # Returns
+///
+/// The width and height of the image once fitted, in cells, at least one cell.
+pub fn fit(image: (u32, u32), area: (u32, u32), cell_aspect: f64) -> (u32, u32) {
+    let (columns, rows) = area;
+    if image.0 == 0 || image.1 == 0 || columns == 0 || rows == 0 || cell_aspect <= 0.0 {
+        return area;
+    }
+    let image_aspect = image.0 as f64 / image.1 as f64;
+    let area_aspect = columns as f64 * cell_aspect / rows as f64;
+    if image_aspect > area_aspect {
+        let height = (columns as f64 * cell_aspect / image_aspect).round() as u32;
+        (columns, height.clamp(1, rows))
+    } else {
+        let width = (rows as f64 * image_aspect / cell_aspect).round() as u32;
+        (width.clamp(1, columns), rows)
+    }
+}
+
+/// Centers a frame in a larger area, surrounded with blank cells (or black pixels for frames
+/// holding pixels).
+///
+/// # Arguments
+///
+/// * `frame` - The frame, fitted in the area.
+/// * `area` - The width and height of the area, in cells.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// A grid of the size of the area, with the frame in its middle.
+pub fn center(frame: &CellGrid, area: (u32, u32), new_lines: bool) -> CellGrid {
+    let (width, height) = area;
+    let left = width.saturating_sub(frame.width()) / 2;
+    let top = height.saturating_sub(frame.height()) / 2;
+    if let Some(pixels) = frame.pixels() {
+        let cell_width = pixels.width() / frame.width().max(1);
+        let cell_height = pixels.height() / frame.height().max(1);
+        let mut canvas = RgbImage::new(width * cell_width, height * cell_height);
+        image::imageops::replace(
+            &mut canvas,
+            pixels,
+            (left * cell_width) as i64,
+            (top * cell_height) as i64,
+        );
+        return CellGrid::from_pixels(width, height, canvas);
+    }
+    let mut centered = CellGrid::new(width, height, new_lines);
+    for y in 0..height {
+        let row = y.checked_sub(top).filter(|row| *row < frame.height());
+        let mut cells = row.into_iter().flat_map(|row| frame.row(row));
+        for x in 0..width {
+            match x.checked_sub(left).and_then(|_| cells.next()) {
+                Some(cell) => centered.push_cell(cell),
+                None => centered.push(" ", [0; 3]),
+            }
+        }
+    }
+    centered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit() {
+        // A 16:9 video in an 80x24 terminal with cells twice as tall as wide
+        assert_eq!(fit((1920, 1080), (80, 24), 0.5), (80, 23));
+        assert_eq!(fit((1920, 1080), (100, 24), 0.5), (85, 24));
+        // A square image is twice as wide as tall in cells
+        assert_eq!(fit((100, 100), (80, 24), 0.5), (48, 24));
+        assert_eq!(fit((100, 100), (80, 24), 1.0), (24, 24));
+        assert_eq!(fit((0, 100), (80, 24), 0.5), (80, 24));
+    }
+
+    #[test]
+    fn test_center() {
+        let mut frame = CellGrid::new(2, 1, false);
+        frame.push("a", [255; 3]);
+        frame.push("b", [255; 3]);
+        let centered = center(&frame, (4, 3), false);
+        assert_eq!(
+            (0..3).map(|y| centered.row_text(y)).collect::<Vec<_>>(),
+            ["    ", " ab ", "    "]
+        );
+
+        let pixels = RgbImage::from_pixel(2, 4, image::Rgb([255; 3]));
+        let centered = center(&CellGrid::from_pixels(1, 1, pixels), (3, 1), false);
+        let canvas = centered.pixels().unwrap();
+        assert_eq!(canvas.dimensions(), (6, 4));
+        assert_eq!(canvas.get_pixel(0, 0).0, [0; 3]);
+        assert_eq!(canvas.get_pixel(2, 0).0, [255; 3]);
+    }
+}
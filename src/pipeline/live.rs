@@ -0,0 +1,460 @@
+//! Provides playback of live sources, such as cameras and network streams (RTSP, RTMP...).
+//!
+//! Unlike files, live sources can stall: a camera gets unplugged, a network stream drops. The
+//! `LiveSource` reads frames on a background thread, reopening the source whenever it fails, so
+//! that reading frames never blocks the playback. When no frame arrives for a while, a "no signal"
+//! placeholder is shown instead of freezing on the last frame, until the source comes back.
+//!
+//! Cameras show the newest frame at once. Network streams can be buffered instead (`--buffer`):
+//! a few seconds of frames are received before the playback starts, smoothing over the jitter of
+//! the network. When the buffer runs dry the playback pauses on the last frame until the buffer is
+//! refilled, and its fill is shown below the frames.
+use crate::pipeline::video::{self, VideoDecoder};
+use image::{DynamicImage, Rgb, RgbImage};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long without frames before the "no signal" placeholder is shown.
+pub const NO_SIGNAL_AFTER: Duration = Duration::from_secs(3);
+/// How long to wait before reopening a source that failed.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// URL schemes of network streams.
+const LIVE_SCHEMES: [&str; 7] = ["rtsp", "rtsps", "rtmp", "rtmps", "srt", "udp", "tcp"];
+/// Default number of seconds of a network stream received before it is played.
+pub const DEFAULT_BUFFER_SECONDS: f64 = 2.0;
+/// How many times the frames played before the playback starts the buffer can hold. When the
+/// stream arrives faster than it is played, the oldest frames are dropped beyond that.
+const BUFFER_HEADROOM: usize = 2;
+/// Width of the bar of the buffer indicator, in cells.
+const BUFFER_BAR_WIDTH: usize = 10;
+
+/// Size of the "no signal" placeholder, in pixels. Roughly the aspect of a terminal, and small
+/// enough for the text to stay readable once resized to the terminal.
+const PLACEHOLDER_SIZE: (u32, u32) = (64, 18);
+/// Text of the placeholder.
+const PLACEHOLDER_TEXT: &str = "NO SIGNAL";
+/// Colors of the bars in the background of the placeholder.
+const PLACEHOLDER_BARS: [[u8; 3]; 7] = [
+    [192, 192, 192],
+    [192, 192, 0],
+    [0, 192, 192],
+    [0, 192, 0],
+    [192, 0, 192],
+    [192, 0, 0],
+    [0, 0, 192],
+];
+
+/// Returns whether the given input is a live source: a camera device or a network stream.
+///
+/// # Arguments
+///
+/// * `path` - The path or URL of the input.
+pub fn is_live_source(path: &str) -> bool {
+    if path.starts_with("/dev/video") {
+        return true;
+    }
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| LIVE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+}
+
+/// Returns whether the given input is a network stream, which can be buffered.
+///
+/// # Arguments
+///
+/// * `path` - The path or URL of the input.
+pub fn is_network_stream(path: &str) -> bool {
+    is_live_source(path) && !path.starts_with("/dev/video")
+}
+
+/// The frames read by the background thread and not played yet.
+struct Received {
+    /// The frames, oldest first.
+    frames: VecDeque<DynamicImage>,
+    /// How many frames are kept, the oldest being dropped beyond that.
+    capacity: usize,
+    /// When the last frame was received.
+    at: Instant,
+}
+
+/// The buffering of a network stream.
+struct Buffer {
+    /// How many frames are received before the playback starts or resumes.
+    target: usize,
+    /// The frame rate of the playback, to tell the fill of the buffer in seconds.
+    fps: f64,
+    /// Whether the buffer is filling, the playback waiting on the last frame.
+    filling: bool,
+}
+
+/// How full the buffer of a network stream is, see `LiveSource::buffer_status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferStatus {
+    /// The seconds of frames received and not played yet.
+    pub seconds: f64,
+    /// The seconds of frames received before the playback starts or resumes.
+    pub target_seconds: f64,
+    /// Whether the playback is waiting for the buffer to fill.
+    pub filling: bool,
+}
+
+/// A live source read on a background thread.
+pub struct LiveSource {
+    /// The frames read by the background thread, not played yet.
+    received: Arc<Mutex<Received>>,
+    /// Tells the background thread to stop.
+    stop: Arc<AtomicBool>,
+    /// The last frame played.
+    last_frame: DynamicImage,
+    /// How long without frames before the placeholder is shown.
+    no_signal_after: Duration,
+    /// The buffering of the stream, if enabled. Otherwise the newest frame is played at once.
+    buffer: Option<Buffer>,
+}
+
+impl LiveSource {
+    /// Opens a camera or network stream with the video decoder compiled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The device path or URL of the stream.
+    pub fn open(source: &str) -> Self {
+        let source = source.to_string();
+        let mut capture: Option<Box<dyn VideoDecoder>> = None;
+        Self::with_reader(
+            move || {
+                if capture.is_none() {
+                    capture = video::open(&source).ok();
+                }
+                let frame = capture.as_mut().and_then(|video| video.next_frame());
+                if frame.is_none() {
+                    // Reopen the source at the next attempt
+                    capture = None;
+                }
+                frame
+            },
+            NO_SIGNAL_AFTER,
+        )
+    }
+
+    /// Starts reading frames on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `read_frame` - Reads the next frame, blocking until it arrives. Returns `None` when the
+    ///   source failed, in which case it is called again after a while.
+    /// * `no_signal_after` - How long without frames before the placeholder is shown.
+    pub fn with_reader<F>(mut read_frame: F, no_signal_after: Duration) -> Self
+    where
+        F: FnMut() -> Option<DynamicImage> + Send + 'static,
+    {
+        let received = Arc::new(Mutex::new(Received {
+            frames: VecDeque::new(),
+            capacity: 1,
+            at: Instant::now(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_received, thread_stop) = (Arc::clone(&received), Arc::clone(&stop));
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match read_frame() {
+                    Some(frame) => {
+                        let mut received = thread_received
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                        received.frames.push_back(frame);
+                        while received.frames.len() > received.capacity {
+                            received.frames.pop_front();
+                        }
+                        received.at = Instant::now();
+                    }
+                    None => thread::sleep(RETRY_INTERVAL),
+                }
+            }
+        });
+        let (width, height) = PLACEHOLDER_SIZE;
+        Self {
+            received,
+            stop,
+            last_frame: DynamicImage::new_rgb8(width, height),
+            no_signal_after,
+            buffer: None,
+        }
+    }
+
+    /// Buffers the frames of the stream: they are played in order once enough of them are
+    /// received, instead of the newest frame at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - How many seconds of frames are received before the playback starts, and
+    ///   resumes after the buffer ran dry. `0` plays the newest frame at once.
+    /// * `fps` - The frame rate of the playback.
+    pub fn set_buffer(&mut self, seconds: f64, fps: f64) {
+        let target = (seconds * fps).ceil();
+        self.buffer = (target >= 1.0).then_some(Buffer {
+            target: target as usize,
+            fps,
+            filling: true,
+        });
+        let capacity = self
+            .buffer
+            .as_ref()
+            .map_or(1, |buffer| buffer.target * BUFFER_HEADROOM);
+        self.lock_received().capacity = capacity;
+    }
+
+    /// Returns how full the buffer is, or `None` if the stream is not buffered.
+    pub fn buffer_status(&self) -> Option<BufferStatus> {
+        let buffer = self.buffer.as_ref()?;
+        let buffered = self.lock_received().frames.len();
+        Some(BufferStatus {
+            seconds: buffered as f64 / buffer.fps,
+            target_seconds: buffer.target as f64 / buffer.fps,
+            filling: buffer.filling,
+        })
+    }
+
+    /// Returns the frame to play now: the next frame received, the last one if no new frame
+    /// arrived yet or while the buffer fills, or the "no signal" placeholder if the source
+    /// stalled. Never blocks.
+    pub fn next_frame(&mut self) -> DynamicImage {
+        let mut received = self
+            .received
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let playing = match &mut self.buffer {
+            Some(buffer) => {
+                if received.frames.is_empty() {
+                    // Pause on the last frame until the buffer is refilled
+                    buffer.filling = true;
+                } else if received.frames.len() >= buffer.target {
+                    buffer.filling = false;
+                }
+                !buffer.filling
+            }
+            None => true,
+        };
+        let frame = if playing {
+            received.frames.pop_front()
+        } else {
+            None
+        };
+        if let Some(frame) = frame {
+            self.last_frame = frame;
+        } else if received.at.elapsed() >= self.no_signal_after {
+            return no_signal_placeholder();
+        }
+        self.last_frame.clone()
+    }
+
+    /// Locks the frames received, even if the background thread panicked.
+    fn lock_received(&self) -> std::sync::MutexGuard<'_, Received> {
+        self.received
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Returns the line showing how full the buffer of a network stream is, e.g.
+/// `Buffering ▕█████     ▏ 1.0/2.0s`.
+///
+/// # Arguments
+///
+/// * `status` - The status of the buffer.
+pub fn buffer_indicator(status: &BufferStatus) -> String {
+    let fill = (status.seconds / status.target_seconds).clamp(0.0, 1.0);
+    let filled = (fill * BUFFER_BAR_WIDTH as f64).round() as usize;
+    let bar = format!(
+        "{}{}",
+        "█".repeat(filled),
+        " ".repeat(BUFFER_BAR_WIDTH - filled)
+    );
+    let label = match status.filling {
+        true => "Buffering",
+        false => "Buffer",
+    };
+    format!(
+        "{label} ▕{bar}▏ {:.1}/{:.1}s",
+        status.seconds, status.target_seconds
+    )
+}
+
+impl Drop for LiveSource {
+    fn drop(&mut self) {
+        // The thread may be blocked reading the source, it stops after its current read
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Draws the "no signal" placeholder: color bars with the text in a black box.
+pub fn no_signal_placeholder() -> DynamicImage {
+    let (width, height) = PLACEHOLDER_SIZE;
+    let mut image = RgbImage::from_fn(width, height, |x, _| {
+        Rgb(PLACEHOLDER_BARS[(x * PLACEHOLDER_BARS.len() as u32 / width) as usize])
+    });
+
+    // Each glyph is 5x7 pixels, followed by a 1 pixel space
+    let text_width = PLACEHOLDER_TEXT.len() as u32 * 6 - 1;
+    let (left, top) = ((width - text_width) / 2, (height - 7) / 2);
+    for y in top - 1..top + 8 {
+        for x in left - 1..left + text_width + 1 {
+            image.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+    }
+    for (i, c) in PLACEHOLDER_TEXT.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0b10000 >> column) != 0 {
+                    let x = left + i as u32 * 6 + column;
+                    image.put_pixel(x, top + row as u32, Rgb([255, 255, 255]));
+                }
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Returns the 5x7 bitmap of a character of the placeholder text, one byte per row.
+///
+/// # Arguments
+///
+/// * `c` - The character.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        'A' => [
+            0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+        'G' => [
+            0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+        ],
+        'I' => [
+            0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        'L' => [
+            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+        ],
+        'N' => [
+            0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0b10001,
+        ],
+        'O' => [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+        'S' => [
+            0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+        ],
+        _ => [0; 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_live_source() {
+        assert!(is_live_source("/dev/video0"));
+        assert!(is_live_source("rtsp://camera.local:554/stream"));
+        assert!(is_live_source("RTMP://example.com/live"));
+        assert!(!is_live_source("https://example.com/video.mp4"));
+        assert!(!is_live_source("video.mp4"));
+        assert!(is_network_stream("rtsp://camera.local:554/stream"));
+        assert!(!is_network_stream("/dev/video0"));
+    }
+
+    #[test]
+    fn test_live_source_shows_placeholder_when_stalled() {
+        let frame = DynamicImage::new_rgb8(2, 2);
+        let mut frames = vec![frame.clone()];
+        let mut source = LiveSource::with_reader(
+            move || match frames.pop() {
+                Some(frame) => Some(frame),
+                None => {
+                    thread::sleep(Duration::from_millis(10));
+                    None
+                }
+            },
+            Duration::from_millis(200),
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while source.next_frame() != frame {
+            assert!(Instant::now() < deadline, "the frame never arrived");
+            thread::sleep(Duration::from_millis(1));
+        }
+        // The last frame is repeated until the source is considered stalled
+        assert_eq!(source.next_frame(), frame);
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(source.next_frame(), no_signal_placeholder());
+    }
+
+    #[test]
+    fn test_live_source_buffers_network_streams() {
+        let frames: Vec<DynamicImage> = (1..=4).map(|i| DynamicImage::new_rgb8(i, 1)).collect();
+        let mut source = LiveSource::with_reader(|| None, Duration::from_secs(60));
+        source.set_buffer(1.0, 3.0);
+        let push = |source: &LiveSource, frame: &DynamicImage| {
+            source.lock_received().frames.push_back(frame.clone());
+        };
+        let blank = source.next_frame();
+
+        // The playback waits until a second of frames is received
+        push(&source, &frames[0]);
+        push(&source, &frames[1]);
+        assert_eq!(source.next_frame(), blank);
+        let status = source.buffer_status().unwrap();
+        assert!(status.filling);
+        assert!((status.seconds - 2.0 / 3.0).abs() < 1e-9);
+        push(&source, &frames[2]);
+        assert_eq!(source.next_frame(), frames[0]);
+        assert!(!source.buffer_status().unwrap().filling);
+        push(&source, &frames[3]);
+        assert_eq!(source.next_frame(), frames[1]);
+        assert_eq!(source.next_frame(), frames[2]);
+        assert_eq!(source.next_frame(), frames[3]);
+
+        // It pauses on the last frame once the buffer ran dry
+        assert_eq!(source.next_frame(), frames[3]);
+        assert!(source.buffer_status().unwrap().filling);
+        push(&source, &frames[0]);
+        assert_eq!(source.next_frame(), frames[3]);
+    }
+
+    #[test]
+    fn test_buffer_indicator() {
+        let status = BufferStatus {
+            seconds: 1.0,
+            target_seconds: 2.0,
+            filling: true,
+        };
+        assert_eq!(buffer_indicator(&status), "Buffering ▕█████     ▏ 1.0/2.0s");
+        let status = BufferStatus {
+            seconds: 3.0,
+            filling: false,
+            ..status
+        };
+        assert_eq!(buffer_indicator(&status), "Buffer ▕██████████▏ 3.0/2.0s");
+    }
+
+    #[test]
+    fn test_no_signal_placeholder() {
+        let placeholder = no_signal_placeholder().into_rgb8();
+        assert_eq!(placeholder.dimensions(), PLACEHOLDER_SIZE);
+        let white = placeholder
+            .pixels()
+            .filter(|pixel| pixel.0 == [255, 255, 255])
+            .count();
+        let lit: u32 = PLACEHOLDER_TEXT
+            .chars()
+            .flat_map(glyph)
+            .map(|row| row.count_ones())
+            .sum();
+        assert_eq!(white as u32, lit);
+    }
+}
@@ -0,0 +1,106 @@
+//! Converts the colors of the frames to the gray levels from which the glyphs of the character
+//! map, the braille dots and the edges are looked up (`--luma`, cycled with `l` during playback).
+//!
+//! The formulas weigh the red, green and blue channels differently: Rec.709 (the default, made for
+//! HD video) counts blue for little, so that saturated blues and reds look darker than with
+//! Rec.601 (made for SD video), or than with the plain average of the channels.
+use image::{DynamicImage, GrayImage, Luma};
+use std::str::FromStr;
+
+/// How the colors are converted to gray levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LumaFormula {
+    /// The luma of HD video (0.2126 R + 0.7152 G + 0.0722 B).
+    #[default]
+    Rec709,
+    /// The luma of SD video (0.299 R + 0.587 G + 0.114 B), brighter for reds and blues.
+    Rec601,
+    /// The average of the channels, brightest for saturated colors.
+    Average,
+}
+
+impl LumaFormula {
+    /// Returns the formula following this one, to cycle through them during playback.
+    pub fn next(self) -> Self {
+        match self {
+            LumaFormula::Rec709 => LumaFormula::Rec601,
+            LumaFormula::Rec601 => LumaFormula::Average,
+            LumaFormula::Average => LumaFormula::Rec709,
+        }
+    }
+
+    /// Converts an image to gray levels.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The image, e.g. a frame resized to the target resolution.
+    ///
+    /// # Returns
+    ///
+    /// The gray level of each pixel.
+    pub fn to_gray(self, img: &DynamicImage) -> GrayImage {
+        // Grayscale images are already gray levels, the same for every formula
+        if self == LumaFormula::Rec709 || !img.color().has_color() {
+            return img.to_luma8();
+        }
+        let rgb = img.to_rgb8();
+        GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0.map(u32::from);
+            let gray = match self {
+                LumaFormula::Rec601 => (299 * r + 587 * g + 114 * b + 500) / 1000,
+                _ => (r + g + b + 1) / 3,
+            };
+            Luma([gray as u8])
+        })
+    }
+}
+
+impl FromStr for LumaFormula {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().replace('.', "").as_str() {
+            "rec709" | "709" | "bt709" => Ok(LumaFormula::Rec709),
+            "rec601" | "601" | "bt601" => Ok(LumaFormula::Rec601),
+            "average" | "avg" | "mean" => Ok(LumaFormula::Average),
+            _ => Err(format!(
+                "`{name}` is not a luma formula (rec709, rec601, average)"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for LumaFormula {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            LumaFormula::Rec709 => "Rec.709",
+            LumaFormula::Rec601 => "Rec.601",
+            LumaFormula::Average => "average",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_luma_formula() {
+        let blue = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([0, 0, 255])));
+        let gray = |formula: LumaFormula| formula.to_gray(&blue).get_pixel(0, 0).0[0];
+        assert_eq!(gray(LumaFormula::Rec709), 18);
+        assert_eq!(gray(LumaFormula::Rec601), 29);
+        assert_eq!(gray(LumaFormula::Average), 85);
+
+        // Grays stay the same
+        let white = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([255; 3])));
+        for formula in [LumaFormula::Rec601, LumaFormula::Average] {
+            assert_eq!(formula.to_gray(&white).get_pixel(0, 0).0[0], 255);
+        }
+        assert_eq!("Rec.601".parse(), Ok(LumaFormula::Rec601));
+        assert!("hsl".parse::<LumaFormula>().is_err());
+        assert_eq!(LumaFormula::Average.next(), LumaFormula::Rec709);
+    }
+}
@@ -1,11 +1,76 @@
 //! The `pipeline` module contains the necessary components for processing images and creating ASCII art animations.
 //!
 //! It consists of the following sub-modules:
+//! - `animation`: Decodes animated GIFs and WEBPs on a background thread while they play.
+//! - `blocks`: Renders images with two-color quadrant (2x2) or sextant (2x3) block elements.
+//! - `braille`: Renders images with braille dots (2x4 per character), with optional dithering.
+//! - `builtin_decoder`: Decodes YUV4MPEG2 and Motion JPEG AVI videos in pure Rust, when no video decoder library is compiled in.
+//! - `cell_grid`: Defines the `CellGrid` struct, a rendered frame made of a glyph and a color per cell.
 //! - `char_maps`: Provides character lookup tables used for converting image pixels to ASCII characters.
+//! - `color_vision`: Simulates or compensates color vision deficiencies, for accessibility testing or colorblind viewers.
+//! - `comparison`: Renders the halves of each frame with different settings, to compare them side by side.
+//! - `contact_sheet`: Lays out evenly spaced frames of a video in a grid, to preview its content.
+//! - `demo`: Draws the short animation built into tplay, played by `tplay demo` to check the terminal.
+//! - `dither`: Reduces the gray levels to the glyphs of a character map, with optional dithering.
+//! - `edges`: Draws the edges of the frames with directional glyphs, blended with the character map.
+//! - `ffmpeg_decoder`: Decodes videos with libav through ffmpeg-next, to build without OpenCV.
+//! - `frame_hash`: Computes perceptual hashes of the rendered frames, written with `--frame-hashes`.
 //! - `frames`: Defines a `Frame` struct and related functionality for representing individual frames in an ASCII animation.
+//! - `glyph_width`: Measures the columns the glyphs of a character map span, 2 for emoji and CJK characters.
+//! - `letterbox`: Fits the frames in the terminal keeping their aspect ratio, between blank bars.
+//! - `luma`: Converts the colors to gray levels with the Rec.709 or Rec.601 luma, or the average of the channels.
+//! - `live`: Reads camera and network streams, showing a "no signal" placeholder when they stall.
+//! - `motion`: Shows only the cells that changed since the previous frame, fading the static ones.
+//! - `music`: Reads the playlists of the music mode, and captions the tracks with their tags.
 //! - `image_pipeline`: Contains a pipeline for processing images, resizing them, and converting them to ASCII art.
+//! - `panorama`: Reprojects the part of equirectangular 360° frames seen through a viewport panned with the arrow keys.
+//! - `progressive`: Shows very large JPEG images as a quick low-resolution render, refined once fully decoded.
+//! - `opencv_decoder`: Decodes videos, cameras and streams with OpenCV's videoio.
+//! - `orientation`: Mirrors or turns the frames by a quarter turn, for vertically mounted displays.
+//! - `scenes`: Detects the scenes of a video in the background, for the scene index shown during playback.
+//! - `slideshow`: Shows the images of a directory one after the other, with transitions between them.
 //! - `runner`: Implements the main functionality for running the ASCII animation, including frame rate control and output.
+//! - `stereo`: Merges the views of side-by-side or top-and-bottom 3D media into an anaglyph, or keeps one eye.
+//! - `subtitles`: Loads subtitle files and the text subtitle tracks embedded in videos, shown as closed captions.
+//! - `transform`: Flips and rotates the source frames, e.g. for mirrored webcams or sideways phone videos.
+//! - `transition`: Animates the switches of character map and grayscale mode over a few frames.
+//! - `video`: Defines the `VideoDecoder` trait of the video decoders, and opens videos with the one compiled in.
+//! - `vu_meter`: Draws the levels of the frequency bands of the audio along the bottom row of the frames.
+pub mod blocks;
+pub mod braille;
+#[cfg(not(any(feature = "opencv_decoder", feature = "ffmpeg_decoder")))]
+pub mod builtin_decoder;
+pub mod cell_grid;
 pub mod char_maps;
+pub mod color_vision;
+pub mod comparison;
+pub mod contact_sheet;
+pub mod animation;
+pub mod demo;
+pub mod dither;
+pub mod edges;
+#[cfg(feature = "ffmpeg_decoder")]
+pub mod ffmpeg_decoder;
+pub mod frame_hash;
 pub mod frames;
+pub mod glyph_width;
 pub mod image_pipeline;
+pub mod letterbox;
+pub mod live;
+pub mod luma;
+pub mod motion;
+pub mod music;
+#[cfg(feature = "opencv_decoder")]
+pub mod opencv_decoder;
+pub mod orientation;
+pub mod panorama;
+pub mod progressive;
 pub mod runner;
+pub mod scenes;
+pub mod slideshow;
+pub mod stereo;
+pub mod subtitles;
+pub mod transform;
+pub mod transition;
+pub mod video;
+pub mod vu_meter;
@@ -0,0 +1,126 @@
+//! Shows only what moves in the frames (`--motion-only`), a motion extraction effect.
+//!
+//! The luminance of each cell is compared with the previous frame: the cells that changed are
+//! drawn, and the cells that stay the same fade to the background over a few frames. Besides the
+//! effect, mostly static media (e.g. lectures, screen recordings) then print very few cells per
+//! frame, since the display only prints the cells that changed.
+use super::cell_grid::{Cell, CellGrid};
+use image::{Pixel, Rgb};
+
+/// Change of the luminance of a cell (0-255) from which it is drawn, by default.
+pub const DEFAULT_MOTION_THRESHOLD: u8 = 12;
+/// Number of frames over which the cells that stopped changing fade to the background.
+const FADE_FRAMES: u8 = 8;
+
+/// Keeps the cells that changed since the previous frame, fading the others to the background.
+#[derive(Debug, Clone, Default)]
+pub struct MotionFilter {
+    /// The change of luminance (0-255) from which cells are drawn.
+    threshold: u8,
+    /// The size of the previous frame, in cells.
+    size: (u32, u32),
+    /// The luminance of each cell of the previous frame.
+    luma: Vec<u8>,
+    /// The number of frames each cell is still drawn for, from `FADE_FRAMES` when it changes.
+    heat: Vec<u8>,
+}
+
+impl MotionFilter {
+    /// Constructs a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The change of luminance (0-255) from which cells are drawn.
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the cells of a frame that changed since the previous one. The first frame, and the
+    /// first frame after a change of size, are drawn whole, and fade out if they stay still.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The frame.
+    /// * `advance` - Whether the frame follows the previous one, rather than drawing the same
+    ///   frame again (e.g. after a change of theme), in which case the cells keep their fade.
+    /// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+    ///
+    /// # Returns
+    ///
+    /// The frame with only the moving cells. Frames holding pixels are returned as they are.
+    pub fn apply(&mut self, grid: CellGrid, advance: bool, new_lines: bool) -> CellGrid {
+        if grid.pixels().is_some() {
+            return grid;
+        }
+        let luma: Vec<u8> = grid
+            .cells()
+            .map(|cell| Rgb(cell.background.unwrap_or(cell.color)).to_luma().0[0])
+            .collect();
+        if self.size != (grid.width(), grid.height()) {
+            self.size = (grid.width(), grid.height());
+            self.heat = vec![FADE_FRAMES; luma.len()];
+        } else if advance {
+            for ((heat, previous), current) in self.heat.iter_mut().zip(&self.luma).zip(&luma) {
+                *heat = match previous.abs_diff(*current) >= self.threshold {
+                    true => FADE_FRAMES,
+                    false => heat.saturating_sub(1),
+                };
+            }
+        }
+        self.luma = luma;
+
+        let mut moving = CellGrid::new(grid.width(), grid.height(), new_lines);
+        for (cell, heat) in grid.cells().zip(&self.heat) {
+            let fade = |color: [u8; 3]| {
+                color.map(|c| (c as u16 * *heat as u16 / FADE_FRAMES as u16) as u8)
+            };
+            match heat {
+                0 => moving.push(" ", [0; 3]),
+                &FADE_FRAMES => moving.push_cell(cell),
+                _ => moving.push_cell(Cell {
+                    color: fade(cell.color),
+                    background: cell.background.map(fade),
+                    ..cell
+                }),
+            }
+        }
+        moving
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_motion_filter() {
+        let grid = |colors: [[u8; 3]; 2]| {
+            let mut grid = CellGrid::new(2, 1, false);
+            colors.iter().for_each(|color| grid.push("#", *color));
+            grid
+        };
+        let mut filter = MotionFilter::new(DEFAULT_MOTION_THRESHOLD);
+        let first = filter.apply(grid([[200; 3], [200; 3]]), true, false);
+        assert_eq!(first.row_text(0), "##");
+
+        // The first cell moves, the second one fades out
+        for frame in 1..=FADE_FRAMES {
+            let color = [if frame % 2 == 0 { 200 } else { 100 }; 3];
+            let moving = filter.apply(grid([color, [200; 3]]), true, false);
+            assert_eq!(moving.colors()[0], color);
+            if frame < FADE_FRAMES {
+                assert_eq!(moving.row_text(0), "##");
+                assert!(moving.colors()[1][0] < 200);
+            } else {
+                assert_eq!(moving.row_text(0), "# ");
+            }
+        }
+
+        // Drawing the same frame again keeps the fade
+        let again = filter.apply(grid([[200; 3], [200; 3]]), false, false);
+        assert_eq!(again.row_text(0), "# ");
+    }
+}
@@ -0,0 +1,344 @@
+//! Provides the music mode (`--music`): playlists of audio tracks, and the caption with the tags
+//! of the track shown over its cover art.
+//!
+//! A playlist is read from a directory (its audio files, sorted by path so that albums play in
+//! order), from an M3U playlist, or is a single track. The tracks are played one after the other
+//! by `main`, the playback threads asking for the next or previous track when a track ends or
+//! when `n`/`p` is pressed.
+use super::cell_grid::CellGrid;
+use crate::common::{errors::*, probe::MediaTags};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Extensions of the audio files picked from directories.
+pub const AUDIO_EXTENSIONS: [&str; 9] = [
+    "mp3", "flac", "wav", "m4a", "aac", "opus", "oga", "aif", "aiff",
+];
+/// Extensions of M3U playlists.
+const PLAYLIST_EXTENSIONS: [&str; 2] = ["m3u", "m3u8"];
+/// Color of the caption.
+const CAPTION_COLOR: [u8; 3] = [255, 255, 255];
+
+/// The tracks played in music mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playlist {
+    /// The paths or URLs of the tracks, in playing order.
+    tracks: Vec<String>,
+}
+
+impl Playlist {
+    /// Reads the tracks of a playlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A directory, whose audio files are played (including those of its
+    ///   subdirectories), an M3U playlist, or a single track.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the directory or the playlist cannot be read, or has no tracks.
+    pub fn open(input: &str) -> Result<Self, MyError> {
+        let path = Path::new(input);
+        let tracks = if path.is_dir() {
+            let mut files = Vec::new();
+            collect_audio_files(path, &mut files)?;
+            files.sort();
+            files
+                .into_iter()
+                .map(|file| file.to_string_lossy().into_owned())
+                .collect()
+        } else if has_extension(path, &PLAYLIST_EXTENSIONS) {
+            let base = path.parent().unwrap_or(Path::new(""));
+            parse_m3u(&fs::read_to_string(path)?, base)
+        } else {
+            vec![input.to_string()]
+        };
+        if tracks.is_empty() {
+            return Err(MyError::Application(format!(
+                "{ERROR_EMPTY_PLAYLIST}: {input}"
+            )));
+        }
+        Ok(Self { tracks })
+    }
+
+    /// Shuffles the tracks.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed of the shuffle, the same seed gives the same order.
+    pub fn shuffle(&mut self, seed: u64) {
+        // xorshift64, seeded with a splitmix64 step so that every seed gives its own order; the
+        // state must not be zero
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        state = (state ^ (state >> 31)).max(1);
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for i in (1..self.tracks.len()).rev() {
+            let j = (random() % (i as u64 + 1)) as usize;
+            self.tracks.swap(i, j);
+        }
+    }
+
+    /// Returns the track at the given position, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the track, from 0.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.tracks.get(index).map(String::as_str)
+    }
+
+    /// Returns the number of tracks.
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Returns whether the playlist has no tracks.
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+}
+
+/// Returns a seed for `Playlist::shuffle` that changes at every run, unless `--seed` is given.
+pub fn shuffle_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Returns whether the extension of a file is one of the given ones, ignoring case.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+/// * `extensions` - The extensions, in lowercase and without the leading dot.
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Adds the audio files of a directory and of its subdirectories to a list.
+///
+/// # Arguments
+///
+/// * `dir` - The directory.
+/// * `files` - The list of files.
+fn collect_audio_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), MyError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_audio_files(&path, files)?;
+        } else if has_extension(&path, &AUDIO_EXTENSIONS) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the tracks of an M3U playlist: one path or URL per line, comments and directives
+/// starting with `#`. Relative paths are relative to the directory of the playlist.
+///
+/// # Arguments
+///
+/// * `content` - The content of the playlist.
+/// * `base` - The directory of the playlist.
+fn parse_m3u(content: &str, base: &Path) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('\u{feff}'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let is_url = url::Url::parse(line).is_ok_and(|url| url.scheme().len() > 1);
+            if is_url || Path::new(line).is_absolute() {
+                line.to_string()
+            } else {
+                base.join(line).to_string_lossy().into_owned()
+            }
+        })
+        .collect()
+}
+
+/// Formats the caption of a track: its title (or its file name if it has none), its artist and
+/// album.
+///
+/// # Arguments
+///
+/// * `tags` - The tags of the track.
+/// * `input` - The path or URL of the track.
+///
+/// # Returns
+///
+/// The lines of the caption.
+pub fn track_caption(tags: &MediaTags, input: &str) -> Vec<String> {
+    let title = tags.title.clone().unwrap_or_else(|| {
+        Path::new(input).file_stem().map_or_else(
+            || input.to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+    });
+    let mut lines = vec![format!("♪ {title}")];
+    let credits: Vec<&str> = [&tags.artist, &tags.album]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if !credits.is_empty() {
+        lines.push(credits.join(" — "));
+    }
+    lines
+}
+
+/// Formats the line of the caption giving the position of the track in the playlist.
+///
+/// # Arguments
+///
+/// * `index` - The position of the track, from 0.
+/// * `count` - The number of tracks of the playlist.
+pub fn position_caption(index: usize, count: usize) -> String {
+    format!("{}/{count}  [p] previous  [n] next", index + 1)
+}
+
+/// Draws a caption over the bottom rows of a frame, each line centered on a blank row. Frames
+/// holding pixels are left as they are.
+///
+/// # Arguments
+///
+/// * `grid` - The frame.
+/// * `lines` - The lines of the caption.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// The frame with the caption.
+pub fn draw_caption(grid: CellGrid, lines: &[String], new_lines: bool) -> CellGrid {
+    let (width, height) = (grid.width(), grid.height());
+    if grid.pixels().is_some() || lines.is_empty() {
+        return grid;
+    }
+    let first_row = height.saturating_sub(lines.len() as u32);
+    let mut captioned = CellGrid::new(width, height, new_lines);
+    for y in 0..height {
+        let Some(line) = y.checked_sub(first_row).and_then(|i| lines.get(i as usize)) else {
+            grid.row(y).for_each(|cell| captioned.push_cell(cell));
+            continue;
+        };
+        let glyphs: Vec<&str> = line.graphemes(true).take(width as usize).collect();
+        let start = (width as usize - glyphs.len()) / 2;
+        for x in 0..width as usize {
+            let glyph = x
+                .checked_sub(start)
+                .and_then(|i| glyphs.get(i))
+                .copied()
+                .unwrap_or(" ");
+            captioned.push(glyph, CAPTION_COLOR);
+        }
+    }
+    captioned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist() {
+        let dir = std::env::temp_dir().join(format!("tplay-music-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("b")).unwrap();
+        for file in ["b/2.flac", "b/1.MP3", "a.wav", "cover.jpg"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(
+            dir.join("list.m3u"),
+            "#EXTM3U\n#EXTINF:1,A\na.wav\n\nhttp://x/y.mp3\n",
+        )
+        .unwrap();
+
+        let playlist = Playlist::open(dir.to_str().unwrap()).unwrap();
+        let names: Vec<&str> = playlist
+            .tracks
+            .iter()
+            .map(|t| &t[dir.as_os_str().len() + 1..])
+            .collect();
+        assert_eq!(names, ["a.wav", "b/1.MP3", "b/2.flac"]);
+
+        let playlist = Playlist::open(dir.join("list.m3u").to_str().unwrap()).unwrap();
+        assert_eq!(playlist.get(0), dir.join("a.wav").to_str());
+        assert_eq!(playlist.get(1), Some("http://x/y.mp3"));
+        assert_eq!(playlist.len(), 2);
+
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        assert!(Playlist::open(dir.join("empty").to_str().unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let tracks: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let mut playlist = Playlist {
+            tracks: tracks.clone(),
+        };
+        playlist.shuffle(42);
+        assert_ne!(playlist.tracks, tracks);
+        let mut sorted = playlist.tracks.clone();
+        sorted.sort_by_key(|track| track.parse::<u32>().unwrap());
+        assert_eq!(sorted, tracks);
+
+        let mut again = Playlist { tracks };
+        again.shuffle(42);
+        assert_eq!(again, playlist);
+    }
+
+    #[test]
+    fn test_shuffle_seeds() {
+        let tracks: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let shuffled = |seed| {
+            let mut playlist = Playlist {
+                tracks: tracks.clone(),
+            };
+            playlist.shuffle(seed);
+            playlist
+        };
+        // Seeds differing in their lowest bit, or only in their highest bits
+        assert_ne!(shuffled(2), shuffled(3));
+        assert_ne!(shuffled(0), shuffled(1));
+        assert_ne!(shuffled(1), shuffled(1 << 63 | 1));
+    }
+
+    #[test]
+    fn test_caption() {
+        let tags = MediaTags {
+            title: None,
+            artist: Some("Band".into()),
+            album: Some("Album".into()),
+        };
+        assert_eq!(
+            track_caption(&tags, "/music/song.mp3"),
+            ["♪ song", "Band — Album"]
+        );
+        assert_eq!(position_caption(1, 3), "2/3  [p] previous  [n] next");
+
+        let mut grid = CellGrid::new(6, 3, false);
+        for _ in 0..18 {
+            grid.push("x", [0; 3]);
+        }
+        let grid = draw_caption(grid, &["ab".into(), "toolonglabel".into()], false);
+        assert_eq!(grid.row_text(0), "xxxxxx");
+        assert_eq!(grid.row_text(1), "  ab  ");
+        assert_eq!(grid.row_text(2), "toolon");
+    }
+}
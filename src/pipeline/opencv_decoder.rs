@@ -0,0 +1,115 @@
+//! Decodes videos with OpenCV's videoio (`opencv_decoder` feature), which also opens cameras by
+//! their device path (e.g. `/dev/video0`).
+use super::video::{VideoDecoder, MAX_CONSECUTIVE_BAD_FRAMES};
+use crate::common::errors::*;
+use image::{DynamicImage, ImageBuffer};
+use opencv::{
+    imgproc,
+    prelude::*,
+    videoio::{self, VideoCapture},
+};
+
+/// A video decoded by OpenCV.
+pub struct OpenCvDecoder {
+    video: VideoCapture,
+}
+
+impl OpenCvDecoder {
+    /// Opens a video file, camera or network stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path, device path or URL of the video.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoder, or a `MyError` if OpenCV cannot open the video.
+    pub fn open(source: &str) -> Result<Self, MyError> {
+        let video = VideoCapture::from_file(source, videoio::CAP_ANY)?;
+        if video.is_opened()? {
+            Ok(Self { video })
+        } else {
+            Err(MyError::Application(format!(
+                "{ERROR_OPENING_VIDEO}: {source}"
+            )))
+        }
+    }
+}
+
+impl VideoDecoder for OpenCvDecoder {
+    fn next_frame(&mut self) -> Option<DynamicImage> {
+        for _ in 0..MAX_CONSECUTIVE_BAD_FRAMES {
+            let mut frame = Mat::default();
+            if !self.video.read(&mut frame).unwrap_or(false) || frame.empty() {
+                return None;
+            }
+            if let Ok(image) = mat_to_dynamic_image(&frame) {
+                return Some(image);
+            }
+        }
+        None
+    }
+
+    fn skip_frames(&mut self, n: usize) -> bool {
+        // Grabbing skips the conversion of the frames
+        (0..n).all(|_| self.video.grab().unwrap_or(false))
+    }
+
+    fn seek(&mut self, seconds: f64) {
+        let _ = self.video.set(videoio::CAP_PROP_POS_MSEC, seconds * 1000.0);
+    }
+
+    fn seek_frame(&mut self, index: usize) {
+        let _ = self.video.set(videoio::CAP_PROP_POS_FRAMES, index as f64);
+    }
+
+    fn position(&self) -> Option<f64> {
+        self.video
+            .get(videoio::CAP_PROP_POS_MSEC)
+            .ok()
+            .map(|millis| millis / 1000.0)
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        let frames = self.video.get(videoio::CAP_PROP_FRAME_COUNT).ok()?;
+        (frames >= 1.0).then_some(frames as usize)
+    }
+
+    fn duration(&self) -> Option<f64> {
+        let frames = self.video.get(videoio::CAP_PROP_FRAME_COUNT).ok()?;
+        let fps = self.video.get(videoio::CAP_PROP_FPS).ok()?;
+        (frames > 0.0 && fps > 0.0).then(|| frames / fps)
+    }
+
+    fn fps(&self) -> Option<f64> {
+        let fps = self.video.get(videoio::CAP_PROP_FPS).ok()?;
+        (fps.is_finite() && fps > 0.0).then_some(fps)
+    }
+}
+
+/// Converts an opencv Mat frame to a dynamic image.
+///
+/// This helper function takes a reference to a video frame in BGR format and returns a
+/// `DynamicImage`.
+///
+/// # Arguments
+///
+/// * `mat` - A reference to a `Mat` object containing the video frame.
+///
+/// # Returns
+///
+/// A `Result` containing a `DynamicImage` if the frame is successfully converted, or a `MyError`
+/// describing why the frame could not be read.
+fn mat_to_dynamic_image(mat: &Mat) -> Result<DynamicImage, MyError> {
+    let mut rgb_mat = Mat::default();
+    imgproc::cvt_color(&mat, &mut rgb_mat, imgproc::COLOR_BGR2RGB, 0)?;
+    let size = rgb_mat.size()?;
+    let reshaped_mat = rgb_mat.reshape(1, size.width * size.height)?;
+    let data_vec: Vec<u8> = reshaped_mat.data_typed::<u8>()?.to_vec();
+
+    ImageBuffer::<image::Rgb<u8>, _>::from_raw(size.width as u32, size.height as u32, data_vec)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or(MyError::Pipeline(format!(
+            "{ERROR_DECODING_IMAGE}: unexpected frame data size"
+        )))
+}
@@ -0,0 +1,150 @@
+//! Renders the frames mirrored or turned by a quarter turn, for displays mounted vertically or
+//! seen through a mirror, without having to rotate the media itself.
+//!
+//! The frames are rendered upright at the size they have once turned, then their cells are laid
+//! out in the turned order when the grid is assembled. The glyphs of braille dots and blocks draw
+//! shapes inside their cell, which would not turn with the grid: for them the image is turned
+//! before the dots are assembled instead.
+use super::cell_grid::CellGrid;
+use image::{imageops, DynamicImage};
+use std::str::FromStr;
+
+/// How the frames are turned on the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    /// Upright.
+    #[default]
+    Normal,
+    /// Mirrored, drawn from right to left.
+    RightToLeft,
+    /// Turned a quarter turn clockwise, for a screen turned counterclockwise.
+    Clockwise,
+    /// Turned upside down.
+    UpsideDown,
+    /// Turned a quarter turn counterclockwise, for a screen turned clockwise.
+    CounterClockwise,
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "normal" | "0" => Ok(Orientation::Normal),
+            "rtl" | "mirror" => Ok(Orientation::RightToLeft),
+            "cw" | "90" => Ok(Orientation::Clockwise),
+            "180" => Ok(Orientation::UpsideDown),
+            "ccw" | "270" => Ok(Orientation::CounterClockwise),
+            _ => Err(format!(
+                "`{name}` is not an orientation (normal, rtl, cw, 180, ccw)"
+            )),
+        }
+    }
+}
+
+impl Orientation {
+    /// Returns the size of the upright frame that fills an area once turned.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - The width and height of the area, in cells.
+    pub fn upright_resolution(self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            Orientation::Clockwise | Orientation::CounterClockwise => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// Returns the position in the upright frame of the cell drawn at the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The column and row of the cell in the turned frame.
+    /// * `size` - The width and height of the turned frame.
+    fn upright_position(self, (x, y): (u32, u32), (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            Orientation::Normal => (x, y),
+            Orientation::RightToLeft => (width - 1 - x, y),
+            Orientation::Clockwise => (y, width - 1 - x),
+            Orientation::UpsideDown => (width - 1 - x, height - 1 - y),
+            Orientation::CounterClockwise => (height - 1 - y, x),
+        }
+    }
+
+    /// Lays out the cells of an upright frame in the turned order.
+    ///
+    /// # Arguments
+    ///
+    /// * `upright` - The frame rendered upright, see `upright_resolution`.
+    /// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+    ///
+    /// # Returns
+    ///
+    /// The turned frame.
+    pub fn reorient(self, upright: &CellGrid, new_lines: bool) -> CellGrid {
+        let (width, height) = self.upright_resolution((upright.width(), upright.height()));
+        if let Some(pixels) = upright.pixels() {
+            let pixels = match self {
+                Orientation::Normal => pixels.clone(),
+                Orientation::RightToLeft => imageops::flip_horizontal(pixels),
+                Orientation::Clockwise => imageops::rotate90(pixels),
+                Orientation::UpsideDown => imageops::rotate180(pixels),
+                Orientation::CounterClockwise => imageops::rotate270(pixels),
+            };
+            return CellGrid::from_pixels(width, height, pixels);
+        }
+        let mut grid = CellGrid::new(width, height, new_lines);
+        for y in 0..height {
+            for x in 0..width {
+                let (ux, uy) = self.upright_position((x, y), (width, height));
+                if let Some(cell) = upright.cell(ux, uy) {
+                    grid.push_cell(cell);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Turns an image, for the render modes drawing shapes inside the cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The upright image.
+    pub fn turn_image(self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            Orientation::Normal => img.clone(),
+            Orientation::RightToLeft => img.fliph(),
+            Orientation::Clockwise => img.rotate90(),
+            Orientation::UpsideDown => img.rotate180(),
+            Orientation::CounterClockwise => img.rotate270(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorient() {
+        // The upright frame:
+        // ab
+        // cd
+        // ef
+        let mut upright = CellGrid::new(2, 3, false);
+        for glyph in ["a", "b", "c", "d", "e", "f"] {
+            upright.push(glyph, [0; 3]);
+        }
+        let turned = |orientation: Orientation| orientation.reorient(&upright, false).to_text();
+        assert_eq!(turned(Orientation::Normal), "abcdef");
+        assert_eq!(turned(Orientation::RightToLeft), "badcfe");
+        assert_eq!(turned(Orientation::Clockwise), "ecafdb");
+        assert_eq!(turned(Orientation::UpsideDown), "fedcba");
+        assert_eq!(turned(Orientation::CounterClockwise), "bdface");
+        assert_eq!(
+            Orientation::Clockwise.upright_resolution((80, 24)),
+            (24, 80)
+        );
+        assert_eq!("ccw".parse(), Ok(Orientation::CounterClockwise));
+    }
+}
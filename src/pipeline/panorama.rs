@@ -0,0 +1,145 @@
+//! Views equirectangular 360° media (`--360`) through a viewport panned with the arrow keys.
+//!
+//! Each frame of a 360° video maps the whole sphere around the camera: the longitude runs along
+//! the width and the latitude along the height. Drawing it as-is shows a distorted panorama;
+//! instead, the part of the sphere seen in the direction of the viewport is reprojected to a flat
+//! (rectilinear) image, as a camera with the field of view of the viewport would see it, before
+//! it is converted to characters.
+use image::{DynamicImage, GenericImageView, RgbImage};
+use std::f64::consts::PI;
+
+/// Horizontal field of view of the viewport by default, in degrees.
+pub const DEFAULT_FOV: f64 = 90.0;
+/// Width of a terminal cell relative to its height, for typical fonts.
+pub const CELL_ASPECT: f64 = 0.5;
+/// Narrowest and widest fields of view, in degrees: wider views get too distorted.
+const FOV_RANGE: (f64, f64) = (10.0, 150.0);
+
+/// The direction and field of view of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// The direction looked at, in degrees to the right of the center of the frames.
+    pub yaw: f64,
+    /// The direction looked at, in degrees above the horizon.
+    pub pitch: f64,
+    /// The horizontal field of view, in degrees.
+    pub fov: f64,
+    /// The width of a cell of the target resolution relative to its height, to keep the
+    /// proportions of the view (see `CELL_ASPECT`).
+    pub cell_aspect: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: DEFAULT_FOV,
+            cell_aspect: CELL_ASPECT,
+        }
+    }
+}
+
+impl Viewport {
+    /// Constructs a viewport looking at the center of the frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `fov` - The horizontal field of view, in degrees, capped to 10-150.
+    /// * `cell_aspect` - The width of a cell of the target resolution relative to its height.
+    pub fn new(fov: f64, cell_aspect: f64) -> Self {
+        Self {
+            fov: fov.clamp(FOV_RANGE.0, FOV_RANGE.1),
+            cell_aspect,
+            ..Self::default()
+        }
+    }
+
+    /// Turns the viewport. The yaw wraps around, the pitch stops at the poles.
+    ///
+    /// # Arguments
+    ///
+    /// * `yaw` - How far to turn right (left if negative), in degrees.
+    /// * `pitch` - How far to turn up (down if negative), in degrees.
+    pub fn pan(&mut self, yaw: f64, pitch: f64) {
+        self.yaw = (self.yaw + yaw + 180.0).rem_euclid(360.0) - 180.0;
+        self.pitch = (self.pitch + pitch).clamp(-90.0, 90.0);
+    }
+
+    /// Reprojects the part of an equirectangular frame seen through the viewport.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The equirectangular frame, covering 360° horizontally and 180° vertically.
+    /// * `size` - The width and height of the reprojected image, in pixels.
+    /// * `aspect` - The width of the reprojected image relative to its height once drawn, which
+    ///   can differ from the ratio of its size when its pixels are not square.
+    ///
+    /// # Returns
+    ///
+    /// The view, looked up with the nearest pixel of the frame.
+    pub fn reproject(
+        &self,
+        img: &DynamicImage,
+        (width, height): (u32, u32),
+        aspect: f64,
+    ) -> DynamicImage {
+        let (source_width, source_height) = img.dimensions();
+        if source_width == 0 || source_height == 0 || width == 0 || height == 0 {
+            return img.clone();
+        }
+        let source = img.to_rgb8();
+        let half_width = (self.fov.to_radians() / 2.0).tan();
+        let half_height = half_width / aspect.max(f64::EPSILON);
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.to_radians().sin_cos();
+        let view = RgbImage::from_fn(width, height, |x, y| {
+            // Direction of the pixel from the camera, looking along z
+            let u = (2.0 * (x as f64 + 0.5) / width as f64 - 1.0) * half_width;
+            let v = (1.0 - 2.0 * (y as f64 + 0.5) / height as f64) * half_height;
+            // Turned up by the pitch, then right by the yaw
+            let (dy, dz) = (v * cos_pitch + sin_pitch, cos_pitch - v * sin_pitch);
+            let (dx, dz) = (u * cos_yaw + dz * sin_yaw, dz * cos_yaw - u * sin_yaw);
+            let longitude = dx.atan2(dz);
+            let latitude = dy.atan2(dx.hypot(dz));
+            let sx = ((longitude / (2.0 * PI) + 0.5) * source_width as f64) as u32;
+            let sy = ((0.5 - latitude / PI) * source_height as f64) as u32;
+            *source.get_pixel(sx.min(source_width - 1), sy.min(source_height - 1))
+        });
+        DynamicImage::ImageRgb8(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_reproject() {
+        // Above the horizon red west of the center and blue east of it, black below
+        let sphere = RgbImage::from_fn(360, 180, |x, y| match (x < 180, y < 90) {
+            (_, false) => Rgb([0; 3]),
+            (true, true) => Rgb([255, 0, 0]),
+            (false, true) => Rgb([0, 0, 255]),
+        });
+        let sphere = DynamicImage::ImageRgb8(sphere);
+        let view = |viewport: &Viewport| viewport.reproject(&sphere, (8, 8), 1.0).to_rgb8();
+
+        let mut viewport = Viewport::default();
+        let ahead = view(&viewport);
+        assert_eq!(ahead.get_pixel(1, 1).0, [255, 0, 0]);
+        assert_eq!(ahead.get_pixel(6, 1).0, [0, 0, 255]);
+        assert_eq!(ahead.get_pixel(4, 6).0, [0; 3]);
+        viewport.pan(90.0, 0.0);
+        assert!(view(&viewport)
+            .pixels()
+            .take(32)
+            .all(|p| p.0 == [0, 0, 255]));
+        viewport.pan(0.0, -120.0);
+        assert_eq!(viewport.pitch, -90.0);
+        assert!(view(&viewport).pixels().all(|p| p.0 == [0; 3]));
+        viewport.pan(100.0, 0.0);
+        assert_eq!(viewport.yaw, -170.0);
+    }
+}
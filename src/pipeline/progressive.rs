@@ -0,0 +1,171 @@
+//! Shows very large JPEG images (e.g. 100 MP panoramas) progressively: a quick low-resolution
+//! render first, refined once the full image is decoded.
+//!
+//! JPEG images can be decoded at 1/2, 1/4 or 1/8 of their size straight from their DCT
+//! coefficients, in a fraction of the time the full decoding takes. The quick render is shown as
+//! soon as the playback starts, while the full image is decoded on a background thread and
+//! replaces it once ready. The 360° viewport pans over the quick render meanwhile.
+use super::frames::decode_image;
+use crate::common::errors::*;
+use crossbeam_channel::{bounded, Receiver, TryRecvError};
+use image::{
+    metadata::Orientation, DynamicImage, GrayImage, ImageDecoder, ImageFormat, ImageReader,
+    RgbImage,
+};
+use std::{fs::File, io::BufReader, path::Path, thread};
+
+/// Number of pixels from which images are shown progressively.
+pub const LARGE_IMAGE_PIXELS: u64 = 16_000_000;
+/// Size of the quick render (width and height), at least on one side when it can be reached.
+const QUICK_RENDER_SIZE: u16 = 1024;
+
+/// A large image shown as a quick render, then at full resolution once decoded.
+pub struct ProgressiveImage {
+    /// The quick render, until it is shown.
+    quick: Option<DynamicImage>,
+    /// Receives the full image from the background thread, disconnected if it fails to decode.
+    rx_full: Receiver<DynamicImage>,
+    /// Whether the full image was shown, or failed to decode.
+    refined: bool,
+}
+
+impl ProgressiveImage {
+    /// Opens an image to show progressively, if it is a large JPEG image.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to the path of the image file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the image, or `None` if it is small enough to be decoded at once, is
+    /// not a JPEG image or its quick render cannot be decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn open(path: &Path) -> Result<Option<Self>, MyError> {
+        Self::open_with(path, LARGE_IMAGE_PIXELS, QUICK_RENDER_SIZE)
+    }
+
+    /// Opens an image to show progressively, see `open`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to the path of the image file.
+    /// * `min_pixels` - The number of pixels from which the image is shown progressively.
+    /// * `quick_size` - The size of the quick render, see `QUICK_RENDER_SIZE`.
+    fn open_with(path: &Path, min_pixels: u64, quick_size: u16) -> Result<Option<Self>, MyError> {
+        let reader = ImageReader::open(path)?.with_guessed_format()?;
+        if reader.format() != Some(ImageFormat::Jpeg) {
+            return Ok(None);
+        }
+        let Ok(mut decoder) = reader.into_decoder() else {
+            return Ok(None);
+        };
+        let (width, height) = decoder.dimensions();
+        if (width as u64 * height as u64) < min_pixels {
+            return Ok(None);
+        }
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+        let Some(mut quick) = quick_render(path, quick_size) else {
+            return Ok(None);
+        };
+        quick.apply_orientation(orientation);
+
+        let (tx_full, rx_full) = bounded(1);
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            // A failed decoding disconnects the channel, and the quick render stays
+            if let Ok(image) = decode_image(&path) {
+                let _ = tx_full.send(image);
+            }
+        });
+        Ok(Some(Self {
+            quick: Some(quick),
+            rx_full,
+            refined: false,
+        }))
+    }
+
+    /// Returns the next image to show: the quick render, then the full image once it is
+    /// decoded, or `None` when there is nothing new to show.
+    pub fn next_frame(&mut self) -> Option<DynamicImage> {
+        if let Some(quick) = self.quick.take() {
+            return Some(quick);
+        }
+        if self.refined {
+            return None;
+        }
+        match self.rx_full.try_recv() {
+            Ok(image) => {
+                self.refined = true;
+                Some(image)
+            }
+            Err(TryRecvError::Disconnected) => {
+                self.refined = true;
+                None
+            }
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+}
+
+/// Decodes a JPEG image at a reduced scale, from its DCT coefficients.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the image file.
+/// * `size` - The size the image is reduced to, at least on one side when it can be reached.
+///
+/// # Returns
+///
+/// The reduced image, or `None` if it cannot be decoded.
+fn quick_render(path: &Path, size: u16) -> Option<DynamicImage> {
+    let file = File::open(path).ok()?;
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(file));
+    let (width, height) = decoder.scale(size, size).ok()?;
+    let pixels = decoder.decode().ok()?;
+    let (width, height) = (width as u32, height as u32);
+    match decoder.info()?.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        // Less common formats (16-bit, CMYK) wait for the full image
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_progressive_image() {
+        let path =
+            std::env::temp_dir().join(format!("tplay-progressive-{}.jpg", std::process::id()));
+        DynamicImage::ImageRgb8(RgbImage::from_fn(128, 64, |x, _| image::Rgb([x as u8; 3])))
+            .save_with_format(&path, ImageFormat::Jpeg)
+            .unwrap();
+        assert!(ProgressiveImage::open(&path).unwrap().is_none());
+        let mut image = ProgressiveImage::open_with(&path, 0, 16).unwrap().unwrap();
+
+        let quick = image.next_frame().unwrap();
+        assert_eq!((quick.width(), quick.height()), (16, 8));
+        let started = Instant::now();
+        let full = loop {
+            if let Some(full) = image.next_frame() {
+                break full;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((full.width(), full.height()), (128, 64));
+        assert!(image.next_frame().is_none());
+    }
+}
@@ -4,14 +4,41 @@
 //! The `Runner` struct is responsible for handling the image pipeline, processing frames, managing
 //! playback state, and controlling the frame rate. It also handles commands for pausing/continuing,
 //! resizing, and changing character maps during playback.
-use super::{frames::FrameIterator, image_pipeline::ImagePipeline};
+//!
+//! Library consumers can register frame observers on the `Runner` to receive every rendered
+//! frame (a `CellGrid`) alongside the display. The timings of each frame can be recorded in a `StatsLog`.
+use super::{
+    braille::BrailleOptions,
+    cell_grid::CellGrid,
+    frames::FrameIterator,
+    image_pipeline::{ImagePipeline, DEFAULT_BACKGROUND_GLYPH},
+    live::buffer_indicator,
+    luma::LumaFormula,
+    motion::MotionFilter,
+    music::draw_caption,
+    scenes::{SceneBrowser, SceneNavigation},
+    transform::TransformChange,
+    transition::{dissolve, Transition},
+    vu_meter::draw_vu_meter,
+};
 use crate::{
-    common::errors::MyError, msg::broker::Control as MediaControl, pipeline::char_maps::*,
-    StringInfo,
+    audio::levels::AudioLevels,
+    common::{
+        clock::PlaybackClock,
+        errors::MyError,
+        stats::{FrameOutcome, FrameTimings, PipelineStats, StatsLog, StatsPublisher},
+    },
+    display::{Progress, RenderedFrame, SourceArea},
+    msg::broker::Control as MediaControl,
+    pipeline::char_maps::*,
 };
-use crossbeam_channel::{select, Receiver, Sender};
+use crossbeam_channel::{select, Receiver, RecvTimeoutError, Sender};
 use image::DynamicImage;
-use std::{thread, time::Duration};
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 /// Represents the playback state of the Runner.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -35,27 +62,67 @@ pub struct Runner {
     media: FrameIterator,
     /// The current playback state of the Runner.
     state: State,
-    /// A channel for receiving processed frames as strings.
-    tx_frames: Sender<Option<StringInfo>>,
+    /// A channel for sending the processed frames to the display.
+    tx_frames: Sender<Option<RenderedFrame>>,
     /// A channel for sending control commands to the Runner.
     rx_controls: Receiver<Control>,
     /// A channel for sending control events to the media processing thread.
     tx_control: Sender<MediaControl>,
     /// A collection of character maps available for the image pipeline.
-    char_maps: Vec<Vec<char>>,
+    char_maps: Vec<Vec<String>>,
+    /// The braille options used when braille dots are toggled on.
+    braille_options: BrailleOptions,
+    /// The glyph drawn over the painted backgrounds when background painting is toggled on.
+    background_glyph: String,
+    /// The scene index of the video, if the media supports it.
+    scenes: Option<SceneBrowser>,
+    /// Lines of text drawn over the bottom of the frames.
+    caption: Vec<String>,
+    /// Where the position of the frames rendered is published, if connected.
+    playback_clock: Option<PlaybackClock>,
+    /// The levels of the audio, drawn as a VU meter along the bottom row if set.
+    audio_levels: Option<AudioLevels>,
+    /// Whether the playback ends with the media, asking for the next track of the playlist.
+    next_track_at_end: bool,
+    /// The number of frames the switches of character map and grayscale mode are animated over,
+    /// 0 to switch at once.
+    switch_transition: u32,
+    /// The switch being animated, and the pipeline before a switch of character map.
+    transition: Option<(Transition, Option<ImagePipeline>)>,
+    /// Keeps only the cells that move, if enabled.
+    motion: Option<MotionFilter>,
     /// The last frame that was processed by the Runner.
-    last_frame: Option<DynamicImage>,
+    last_frame: Option<Arc<DynamicImage>>,
+    /// The size of the area the frames are drawn in (columns, rows), in terminal cells.
+    area: (u32, u32),
+    /// The number of `Resize` commands received, sent with the frames so that the display drops
+    /// those rendered for a previous size.
+    generation: u64,
     /// Runner options
     runner_options: RunnerOptions,
+    /// Callbacks invoked with every rendered frame.
+    frame_observers: Vec<FrameObserver>,
+    /// Where the timings of each frame are recorded, if enabled.
+    stats: Option<StatsLog>,
+    /// Publishes the performance of the pipeline through the stats channel, if connected.
+    stats_publisher: Option<StatsPublisher>,
 }
 
+/// A callback receiving every frame rendered by the Runner.
+pub type FrameObserver = Box<dyn FnMut(&CellGrid) + Send>;
+
 pub struct RunnerOptions {
     /// The target frames per second (frame rate) for the Runner.
     pub fps: f64,
-    /// The width modifier (use 2 for emojis).
-    pub w_mod: u32,
+    /// The width of a terminal cell relative to its height, the glyphs spanning several columns
+    /// being as many times wider (see `ImagePipeline::lay_out`).
+    pub cell_aspect: f64,
     /// loop_playback back to the first frame after iterating through frames.
     pub loop_playback: bool,
+    /// Only render every Nth source frame (1 renders every frame).
+    pub decimate: usize,
+    /// The character maps selected with `Control::SetCharMap`, after the pipeline one.
+    pub char_maps: CharMapRegistry,
 }
 /// Enum representing the different control commands that can be sent to the Runner.
 #[derive(Debug, PartialEq)]
@@ -75,6 +142,30 @@ pub enum Control {
     /// Command to set grayscale mode. We always extract rgb+grayscale from image, the
     /// terminal is responsible for the correct render mode.
     SetGrayscale(bool),
+    /// Command to move playback to the given position, in seconds from the start of the media.
+    Seek(f64),
+    /// Command to move playback by the given number of seconds, see `MediaControl::SeekBy`.
+    SeekBy(f64),
+    /// Command to render the current frame again, e.g. after the display changed colors.
+    Redraw,
+    /// Command to switch between the character map and braille dots.
+    ToggleBraille,
+    /// Command to switch between coloring the glyphs and painting the background of the cells.
+    ToggleBackground,
+    /// Command to switch to the next dithering method of the character map.
+    CycleDither,
+    /// Command to convert the colors to gray levels with the given formula.
+    SetLuma(LumaFormula),
+    /// Command to open, close or navigate the scene index.
+    SceneIndex(SceneNavigation),
+    /// Command to turn the viewport of 360° media, see `MediaControl::Pan`.
+    Pan(f64, f64),
+    /// Command to flip the frames or turn them a quarter turn.
+    Transform(TransformChange),
+    /// Command to replace the glyphs of a character map, see `MediaControl::EditCharMap`.
+    EditCharMap(u32, Vec<String>),
+    /// Command to render with typed glyphs, see `MediaControl::SetCustomCharMap`.
+    SetCustomCharMap(String),
 }
 
 impl Runner {
@@ -85,31 +176,30 @@ impl Runner {
     /// * `pipeline` - The image pipeline responsible for processing images.
     /// * `media` - The FrameIterator that handles iterating through frames.
     /// * `fps` - The target frames per second (frame rate) for the Runner.
-    /// * `tx_frames` - A channel for receiving processed frames as strings.
+    /// * `tx_frames` - A channel for sending the processed frames to the display.
     /// * `rx_controls` - A channel for sending control commands to the Runner.
     /// * `tx_controls` - A channel for sending control events to the media processing thread.
-    /// * `w_mod` - The width modifier (use 2 for emojis).
     /// * `loop_playback` - Flags whether the runner will loop round after processing all frames.
     pub fn new(
         pipeline: ImagePipeline,
         media: FrameIterator,
-        tx_frames: Sender<Option<StringInfo>>,
+        tx_frames: Sender<Option<RenderedFrame>>,
         rx_controls: Receiver<Control>,
         tx_control: Sender<MediaControl>,
         runner_options: RunnerOptions,
     ) -> Self {
-        let char_maps: Vec<Vec<char>> = vec![
-            pipeline.char_map.clone(),
-            CHARS1.to_string().chars().collect(),
-            CHARS2.to_string().chars().collect(),
-            CHARS3.to_string().chars().collect(),
-            SOLID.to_string().chars().collect(),
-            DOTTED.to_string().chars().collect(),
-            GRADIENT.to_string().chars().collect(),
-            BLACKWHITE.to_string().chars().collect(),
-            BW_DOTTED.to_string().chars().collect(),
-            BRAILLE.to_string().chars().collect(),
-        ];
+        let char_maps: Vec<Vec<String>> = std::iter::once(pipeline.char_map.clone())
+            .chain(runner_options.char_maps.iter().map(|map| map.chars.clone()))
+            .collect();
+        let braille_options = pipeline.braille.unwrap_or_default();
+        let background_glyph = pipeline
+            .background_glyph
+            .clone()
+            .unwrap_or_else(|| DEFAULT_BACKGROUND_GLYPH.to_string());
+        // The pipeline is created with the size of the area, the glyphs are laid out in it
+        let area = pipeline.target_resolution;
+        let mut pipeline = pipeline;
+        pipeline.lay_out(area, runner_options.cell_aspect);
         Self {
             pipeline,
             media,
@@ -118,15 +208,180 @@ impl Runner {
             rx_controls,
             tx_control,
             char_maps,
+            braille_options,
+            background_glyph,
+            scenes: None,
+            caption: Vec::new(),
+            playback_clock: None,
+            audio_levels: None,
+            next_track_at_end: false,
+            switch_transition: 0,
+            transition: None,
+            motion: None,
             last_frame: None,
+            area,
+            generation: 0,
             runner_options,
+            frame_observers: Vec::new(),
+            stats: None,
+            stats_publisher: None,
+        }
+    }
+
+    /// Records the timings of each frame read from the media in the given log.
+    ///
+    /// # Arguments
+    ///
+    /// * `stats` - The log, shared with the display.
+    pub fn set_stats_log(&mut self, stats: StatsLog) {
+        self.stats = Some(stats);
+    }
+
+    /// Publishes the performance of the pipeline (decode and render rates, dropped and skipped
+    /// frames, frames waiting for the display) twice a second, for the performance overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_stats` - The stats channel, read by the display.
+    pub fn set_stats_channel(&mut self, tx_stats: Sender<PipelineStats>) {
+        self.stats_publisher = Some(StatsPublisher::new(tx_stats));
+    }
+
+    /// Sets the braille options used when braille dots are toggled on (`Control::ToggleBraille`).
+    /// By default, the options of the pipeline, or the default ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The threshold and dithering of the braille dots.
+    pub fn set_braille_options(&mut self, options: BrailleOptions) {
+        self.braille_options = options;
+    }
+
+    /// Sets the glyph drawn over the painted backgrounds when background painting is toggled on
+    /// (`Control::ToggleBackground`). By default, the glyph of the pipeline, or a space.
+    ///
+    /// # Arguments
+    ///
+    /// * `glyph` - The glyph, drawn in a darker shade of the background.
+    pub fn set_background_glyph(&mut self, glyph: String) {
+        self.background_glyph = glyph;
+    }
+
+    /// Enables the scene index (`Control::SceneIndex`), scanning the given video for scene cuts the
+    /// first time the index is opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path of the video, decoded again by the scan.
+    /// * `fps` - The frame rate of the video.
+    pub fn set_scene_source(&mut self, source: &str, fps: f64) {
+        self.scenes = Some(SceneBrowser::new(source, fps));
+    }
+
+    /// Draws lines of text over the bottom of the frames, e.g. the title and artist of a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `caption` - The lines of text, none to draw the frames as they are.
+    pub fn set_caption(&mut self, caption: Vec<String>) {
+        self.caption = caption;
+    }
+
+    /// Publishes the position of each frame rendered, for what the display times with the
+    /// media (e.g. the subtitles).
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The playback clock, shared with the display.
+    pub fn set_playback_clock(&mut self, clock: PlaybackClock) {
+        self.playback_clock = Some(clock);
+    }
+
+    /// Draws a VU meter along the bottom row of the frames, above which the caption and the
+    /// subtitles are moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `levels` - The levels of the audio, measured by the audio backend.
+    pub fn set_audio_levels(&mut self, levels: AudioLevels) {
+        self.audio_levels = Some(levels);
+    }
+
+    /// Ends the playback when the media ends, sending `MediaControl::ChangeTrack(1)` so that the
+    /// next track of the playlist is played. Looping playback takes precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `next_track_at_end` - Whether the playback ends with the media.
+    pub fn set_next_track_at_end(&mut self, next_track_at_end: bool) {
+        self.next_track_at_end = next_track_at_end;
+    }
+
+    /// Animates the switches of character map and grayscale mode over a number of frames: the
+    /// cells change over from the previous character map in a dithered pattern, and the frames
+    /// keep being rendered while the display fades the colors.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The number of frames of the transitions, 0 to switch at once.
+    pub fn set_switch_transition(&mut self, frames: u32) {
+        self.switch_transition = frames;
+    }
+
+    /// Draws only the cells whose luminance changed since the previous frame, the static cells
+    /// fading to the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The change of luminance (0-255) from which cells are drawn.
+    pub fn set_motion_only(&mut self, threshold: u8) {
+        self.motion = Some(MotionFilter::new(threshold));
+    }
+
+    /// Records the timings of a frame, if statistics are enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `timings` - The timings of the frame, and what happened to it.
+    fn record_stats(&mut self, timings: FrameTimings) {
+        if let Some(stats) = &self.stats {
+            stats.record_frame(timings);
+        }
+        if let Some(publisher) = &mut self.stats_publisher {
+            let audio_underruns = self.stats.as_ref().and_then(StatsLog::audio_underruns);
+            publisher.record_frame(&timings, self.tx_frames.len(), audio_underruns);
+        }
+    }
+
+    /// Registers an observer that is called with every frame rendered by the Runner, before the
+    /// frame is sent to the display. Observers run on the pipeline thread, so they should return
+    /// quickly to keep up with the frame rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer` - The callback receiving the rendered frames.
+    pub fn add_frame_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(&CellGrid) + Send + 'static,
+    {
+        self.frame_observers.push(Box::new(observer));
+    }
+
+    /// Passes a rendered frame to the registered frame observers.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The rendered frame.
+    fn notify_frame_observers(&mut self, frame: &CellGrid) {
+        for observer in self.frame_observers.iter_mut() {
+            observer(frame);
         }
     }
 
     /// The main function responsible for running the animation.
     ///
     /// It processes control commands, updates the state of the Runner, processes frames, and sends
-    /// the resulting ASCII strings to the string buffer.
+    /// the resulting cell grids to the display.
     ///
     /// # Returns
     ///
@@ -141,31 +396,87 @@ impl Runner {
         // make sure the first frame is shown immediately
         time_count -= self.target_frame_duration();
         while self.state != State::Stopped {
-            let frame_needs_refresh = self.process_control_commands();
+            // Transitions are animated on still and paused frames too
+            let frame_needs_refresh = self.process_control_commands() || self.transition.is_some();
 
             let (should_process_frame, frames_to_skip) = self.should_process_frame(&mut time_count);
             if should_process_frame {
-                if frames_to_skip > 0 && allow_frame_skip {
-                    self.media.skip_frames(frames_to_skip);
+                let decode_start = Instant::now();
+                let skipped = if allow_frame_skip { frames_to_skip } else { 0 };
+                if skipped > 0 {
+                    self.media.skip_frames(skipped);
                 }
                 let frame = self.get_current_frame();
+                let decode = decode_start.elapsed();
 
                 if self.runner_options.loop_playback && frame.is_none() {
                     // make sure the first frame on replay is shown immediately
                     time_count -= self.target_frame_duration();
-                    // send command to broker to replay
-                    self.send_control(MediaControl::Replay)?;
+                    // send command to broker to replay, which is only gone once the playback
+                    // is stopped
+                    if self.send_control(MediaControl::Replay).is_err() {
+                        self.state = State::Stopped;
+                        continue;
+                    }
+                } else if self.next_track_at_end
+                    && frame.is_none()
+                    && self.state == State::Running
+                {
+                    let _ = self.send_control(MediaControl::ChangeTrack(1));
+                    self.state = State::Stopped;
+                    continue;
                 }
 
                 // Check if terminal is ready for the next frame
                 select! {
-                    send(self.tx_frames, None) -> _ => {
-                        let string_info = self.process_current_frame(frame.as_ref(), frame_needs_refresh);
+                    send(self.tx_frames, None) -> ready => {
+                        if ready.is_err() {
+                            // The display is gone
+                            self.state = State::Stopped;
+                            continue;
+                        }
+                        let convert_start = Instant::now();
+                        let grid = self.process_current_frame(frame.as_ref(), frame_needs_refresh);
+                        let convert = convert_start.elapsed();
+                        if let Some(grid) = &grid {
+                            self.notify_frame_observers(grid);
+                        }
+                        let rendered = grid.is_some();
+                        let progress = self.progress();
+                        let source = self.last_frame.clone();
+                        let source_area = self.source_area();
+                        let frame = grid.map(|grid| RenderedFrame {
+                            grid,
+                            progress,
+                            source,
+                            source_area,
+                            generation: self.generation,
+                        });
                         // Best effort send. If the buffer is full the frame will be dropped
-                        let _ = self.tx_frames.try_send(string_info);
+                        let sent = self.tx_frames.try_send(frame).is_ok();
+                        if rendered {
+                            self.record_stats(FrameTimings {
+                                decode,
+                                convert: Some(convert),
+                                skipped,
+                                outcome: if sent {
+                                    FrameOutcome::Sent
+                                } else {
+                                    FrameOutcome::SendFailed
+                                },
+                            });
+                        }
                     },
                     default(Duration::from_millis(5)) => {
                         // Terminal may be struggling to keep up. Give it some slack!
+                        if frame.is_some() {
+                            self.record_stats(FrameTimings {
+                                decode,
+                                convert: None,
+                                skipped,
+                                outcome: FrameOutcome::DisplayBusy,
+                            });
+                        }
                     }
                 }
             } else {
@@ -176,38 +487,50 @@ impl Runner {
         Ok(())
     }
 
-    /// Processes the given frame using the image pipeline and converts the processed image to an
-    /// ASCII string representation.
+    /// Processes the given frame using the image pipeline and converts the processed image to a
+    /// grid of glyphs and colors.
     ///
     /// # Arguments
     ///
     /// * `frame` - A reference to the DynamicImage to be processed.
+    /// * `advance` - Whether the frame is a new frame of the media, rather than the last frame
+    ///   processed again.
     ///
     /// # Returns
     ///
-    /// A Result containing a tuple of the ASCII string representation of the processed image and
-    /// the RGB data of the processed image.
-    fn process_frame(&mut self, frame: &DynamicImage) -> Result<StringInfo, MyError> {
-        let procimage = self.pipeline.resize(frame)?;
-        let grayimage = procimage.clone().into_luma8();
-        let rgb_info = procimage.into_rgb8().to_vec();
-
-        // Add newlines to the rgb_info to match the ascii string These are not
-        // really needed, but it's important if you want to copy/paste the
-        // output and preserve the aspect.
-        if self.pipeline.new_lines {
-            let mut rgb_info_newline =
-                Vec::with_capacity(rgb_info.len() + 6 * self.pipeline.target_resolution.0 as usize);
-
-            for (i, pixel) in rgb_info.chunks(3).enumerate() {
-                rgb_info_newline.extend_from_slice(pixel);
-                if (i + 1) % self.pipeline.target_resolution.0 as usize == 0 {
-                    rgb_info_newline.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    /// A Result containing the `CellGrid` of the processed image, or of the scene index while it
+    /// is open.
+    fn process_frame(&mut self, frame: &DynamicImage, advance: bool) -> Result<CellGrid, MyError> {
+        let index = self.scenes.as_ref().and_then(|scenes| {
+            scenes.render(self.pipeline.target_resolution, self.pipeline.char_map.clone())
+        });
+        match index {
+            Some(index) => Ok(index),
+            None => {
+                if let Some(clock) = &self.playback_clock {
+                    clock.set(self.media.position(self.runner_options.fps));
                 }
+                let grid = self.pipeline.render(frame)?;
+                let mut grid = self.animate_transition(frame, grid)?;
+                if let Some(motion) = &mut self.motion {
+                    grid = motion.apply(grid, advance, self.pipeline.new_lines);
+                }
+                let mut lines = self.caption.clone();
+                if let Some(status) = self.media.buffer_status() {
+                    lines.push(buffer_indicator(&status));
+                }
+                let Some(levels) = &self.audio_levels else {
+                    return Ok(draw_caption(grid, &lines, self.pipeline.new_lines));
+                };
+                // Keep the bottom row, where the meter is drawn, free of text
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                let bands = levels.bands();
+                let grid = draw_caption(grid, &lines, self.pipeline.new_lines);
+                Ok(draw_vu_meter(grid, &bands, self.pipeline.new_lines))
             }
-            return Ok((self.pipeline.to_ascii(&grayimage), rgb_info_newline));
         }
-        Ok((self.pipeline.to_ascii(&grayimage), rgb_info))
     }
 
     /// Processes control commands from the commands buffer and updates the Runner state and
@@ -220,12 +543,22 @@ impl Runner {
         let mut needs_refresh = false;
 
         // If we have control events, process them
-        while let Ok(control) = self.rx_controls.recv_timeout(Duration::from_millis(1)) {
+        loop {
+            let control = match self.rx_controls.recv_timeout(Duration::from_millis(1)) {
+                Ok(control) => control,
+                Err(RecvTimeoutError::Timeout) => break,
+                // The broker is gone, nothing would stop the playback anymore
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.state = State::Stopped;
+                    break;
+                }
+            };
             needs_refresh = true;
             match control {
                 Control::PauseContinue => self.toggle_pause(),
                 Control::Exit => self.state = State::Stopped,
                 Control::Resize(width, height) => {
+                    self.generation += 1;
                     self.resize_pipeline(width, height);
                 }
                 Control::Replay => {
@@ -234,9 +567,33 @@ impl Runner {
                 Control::SetCharMap(char_map) => {
                     self.set_char_map(char_map);
                 }
-                Control::SetGrayscale(_) => { /* ignore */ }
+                // The display fades the colors, the frames are rendered meanwhile
+                Control::SetGrayscale(_) => self.start_transition(None),
+                Control::Seek(seconds) => {
+                    self.media.seek(seconds, self.runner_options.fps);
+                }
+                Control::SeekBy(seconds) => self.seek_by(seconds),
+                Control::Redraw => { /* refresh only */ }
+                Control::ToggleBraille => self.toggle_braille(),
+                Control::ToggleBackground => self.toggle_background(),
+                Control::CycleDither => self.pipeline.dither = self.pipeline.dither.next(),
+                Control::SetLuma(formula) => self.pipeline.luma = formula,
+                Control::SceneIndex(navigation) => self.navigate_scenes(navigation),
+                Control::Pan(yaw, pitch) => {
+                    if let Some(viewport) = &mut self.pipeline.panorama {
+                        viewport.pan(yaw, pitch);
+                    }
+                }
+                Control::Transform(change) => self.pipeline.transform.change(change),
+                Control::EditCharMap(char_map, glyphs) => self.edit_char_map(char_map, glyphs),
+                Control::SetCustomCharMap(chars) => self.set_custom_char_map(&chars),
             }
         }
+        if needs_refresh {
+            // The glyphs may span other columns with the character map or the render mode
+            self.pipeline
+                .lay_out(self.area, self.runner_options.cell_aspect);
+        }
         needs_refresh
     }
 
@@ -249,17 +606,16 @@ impl Runner {
         }
     }
 
-    /// Resizes the image pipeline's target resolution based on the provided width and height.
+    /// Resizes the area the frames are drawn in, the glyphs are laid out in it.
     ///
     /// # Arguments
     ///
-    /// * `width` - The new target width.
-    /// * `height` - The new target height.
+    /// * `width` - The new width of the area, in terminal columns.
+    /// * `height` - The new height of the area, in rows.
     fn resize_pipeline(&mut self, width: u16, height: u16) {
-        let _ = self.pipeline.set_target_resolution(
-            (width / self.runner_options.w_mod as u16).into(),
-            height.into(),
-        );
+        self.area = (width.into(), height.into());
+        self.pipeline
+            .lay_out(self.area, self.runner_options.cell_aspect);
     }
 
     /// Sets the character map for the image pipeline based on the provided index.
@@ -268,8 +624,144 @@ impl Runner {
     ///
     /// * `char_map` - The index of the character map to use.
     fn set_char_map(&mut self, char_map: u32) {
+        self.start_transition(Some(self.pipeline.clone()));
         self.pipeline.char_map =
             self.char_maps[(char_map % self.char_maps.len() as u32) as usize].clone();
+        // Choosing a character map leaves the braille, block and background painting modes
+        self.pipeline.braille = None;
+        self.pipeline.blocks = None;
+        self.pipeline.background_glyph = None;
+    }
+
+    /// Replaces the glyphs of a character map being edited, and renders with it at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `char_map` - The index of the character map.
+    /// * `glyphs` - The new glyphs of the character map.
+    fn edit_char_map(&mut self, char_map: u32, glyphs: Vec<String>) {
+        if glyphs.is_empty() {
+            return;
+        }
+        let index = (char_map % self.char_maps.len() as u32) as usize;
+        self.char_maps[index] = glyphs;
+        self.pipeline.char_map = self.char_maps[index].clone();
+        self.pipeline.braille = None;
+        self.pipeline.blocks = None;
+        self.pipeline.background_glyph = None;
+    }
+
+    /// Renders with glyphs typed on the command line, at once: they change with each key typed,
+    /// so the switch is not animated. The character maps chosen with the keys are left as they
+    /// are.
+    ///
+    /// # Arguments
+    ///
+    /// * `chars` - The glyphs, from darkest to brightest.
+    fn set_custom_char_map(&mut self, chars: &str) {
+        let glyphs = split_glyphs(chars);
+        if glyphs.is_empty() {
+            return;
+        }
+        self.pipeline.char_map = glyphs;
+        self.pipeline.braille = None;
+        self.pipeline.blocks = None;
+        self.pipeline.background_glyph = None;
+    }
+
+    /// Starts animating a switch, if the switches are animated.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The pipeline before the switch, to blend the frames with, if any.
+    fn start_transition(&mut self, from: Option<ImagePipeline>) {
+        if self.switch_transition > 0 {
+            self.transition = Some((Transition::new(self.switch_transition), from));
+        }
+    }
+
+    /// Blends a rendered frame with the frame rendered by the pipeline before the switch of
+    /// character map being animated, if any, and moves the transition to its next frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame of the media.
+    /// * `grid` - The frame rendered by the pipeline.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the blended frame.
+    fn animate_transition(
+        &mut self,
+        frame: &DynamicImage,
+        grid: CellGrid,
+    ) -> Result<CellGrid, MyError> {
+        let Some((transition, from)) = &mut self.transition else {
+            return Ok(grid);
+        };
+        let progress = transition.advance();
+        let over = transition.is_over();
+        let grid = match from {
+            Some(from) if !over => {
+                // The previous pipeline follows the area and the viewport of the current one
+                from.panorama = self.pipeline.panorama;
+                from.lay_out(self.area, self.runner_options.cell_aspect);
+                dissolve(&from.render(frame)?, grid, progress, self.pipeline.new_lines)
+            }
+            _ => grid,
+        };
+        if over {
+            self.transition = None;
+        }
+        Ok(grid)
+    }
+
+    /// Switches between the character map and braille dots, with the braille options the
+    /// pipeline was created with.
+    fn toggle_braille(&mut self) {
+        self.pipeline.braille = match self.pipeline.braille {
+            Some(_) => None,
+            None => Some(self.braille_options),
+        };
+    }
+
+    /// Moves playback by a number of seconds from the current position, seeking the media and the
+    /// audio. Media without positions are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - How far to move, backward if negative.
+    fn seek_by(&mut self, seconds: f64) {
+        if let Some(position) = self.media.position(self.runner_options.fps) {
+            // The broker forwards the seek to the pipeline and the audio
+            let _ = self.send_control(MediaControl::Seek((position + seconds).max(0.0)));
+        }
+    }
+
+    /// Switches between coloring the glyphs of the character map and painting the background of
+    /// the cells, with the glyph set with `set_background_glyph`.
+    fn toggle_background(&mut self) {
+        self.pipeline.background_glyph = match self.pipeline.background_glyph {
+            Some(_) => None,
+            None => Some(self.background_glyph.clone()),
+        };
+    }
+
+    /// Applies a navigation command to the scene index, seeking the media and the audio to the
+    /// selected scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `navigation` - The navigation command.
+    fn navigate_scenes(&mut self, navigation: SceneNavigation) {
+        let seek_to = self
+            .scenes
+            .as_mut()
+            .and_then(|scenes| scenes.navigate(navigation));
+        if let Some(seconds) = seek_to {
+            // The broker forwards the seek to the pipeline and the audio
+            let _ = self.send_control(MediaControl::Seek(seconds));
+        }
     }
 
     /// Determines if a frame should be processed based on the current time and the Runner's state.
@@ -327,18 +819,45 @@ impl Runner {
         }
     }
 
-    /// Retrieves the current frame based on the Runner's state.
+    /// Retrieves the current frame based on the Runner's state. When decimating, the source frames
+    /// between two rendered frames are dropped.
     ///
     /// # Returns
     ///
     /// An Option containing a DynamicImage if the Runner's state is `Running`, or None otherwise.
     fn get_current_frame(&mut self) -> Option<DynamicImage> {
         match self.state {
-            State::Running => self.media.next(),
-            State::Paused | State::Stopped => self.last_frame.clone(),
+            State::Running => {
+                let frame = self.media.next();
+                if frame.is_some() && self.runner_options.decimate > 1 {
+                    self.media.skip_frames(self.runner_options.decimate - 1);
+                }
+                frame
+            }
+            State::Paused | State::Stopped => self.last_frame.as_deref().cloned(),
+        }
+    }
+
+    /// Returns where the playback is, sent to the display with each frame.
+    fn progress(&self) -> Progress {
+        let fps = self.runner_options.fps;
+        Progress {
+            position: self.media.position(fps),
+            duration: self.media.duration(fps),
+            buffered: self.media.buffered(fps),
         }
     }
 
+    /// Returns the cells of the frame sent to the display showing the last frame of the media, if
+    /// it is shown rather than the scene index.
+    fn source_area(&self) -> Option<SourceArea> {
+        if self.scenes.as_ref().is_some_and(|scenes| scenes.is_open()) {
+            return None;
+        }
+        let frame = self.last_frame.as_ref()?;
+        self.pipeline.source_area((frame.width(), frame.height()))
+    }
+
     /// Replays the pipeline
     ///
     /// # Returns
@@ -366,7 +885,7 @@ impl Runner {
         })
     }
 
-    /// Processes the current frame, if available, and returns the resulting ASCII string. If the
+    /// Processes the current frame, if available, and returns the resulting cell grid. If the
     /// frame is not available or doesn't need to be processed, it returns None.
     ///
     /// # Arguments
@@ -376,30 +895,24 @@ impl Runner {
     ///
     /// # Returns
     ///
-    /// An Optional StringInfo tuple containing the ASCII representation of the processed frame and
-    /// RGB info.
+    /// An Optional `CellGrid` of the processed frame.
     fn process_current_frame(
         &mut self,
         frame: Option<&DynamicImage>,
         refresh: bool,
-    ) -> Option<StringInfo> {
+    ) -> Option<CellGrid> {
         match frame {
             Some(frame) => {
-                self.last_frame = Some(frame.clone());
-                if let Ok(string_info) = self.process_frame(frame) {
-                    return Some(string_info);
+                self.last_frame = Some(Arc::new(frame.clone()));
+                if let Ok(grid) = self.process_frame(frame, true) {
+                    return Some(grid);
                 }
                 None
             }
             None => {
-                if self.last_frame.is_some() && refresh {
-                    if let Ok(string_info) = self.process_frame(
-                        &self
-                            .last_frame
-                            .clone()
-                            .expect("Last frame should be available"),
-                    ) {
-                        return Some(string_info);
+                if let (Some(last_frame), true) = (self.last_frame.clone(), refresh) {
+                    if let Ok(grid) = self.process_frame(&last_frame, false) {
+                        return Some(grid);
                     }
                 }
                 None
@@ -412,12 +925,13 @@ impl Runner {
 mod tests {
     use super::*;
     use crate::pipeline::{
-        char_maps::CHARS1,
+        char_maps::{split_glyphs, CHARS1},
         frames::open_media,
         image_pipeline::ImagePipeline,
+        panorama::CELL_ASPECT,
         runner::Control as PipelineControl,
     };
-    use crate::StringInfo;
+    use crate::common::temp::TempStore;
     use crossbeam_channel::{bounded, unbounded};
 
     const MEDIA_FILE: &str =
@@ -427,11 +941,12 @@ mod tests {
     fn test_time_to_send_next_frame() {
         let fps = 23.976;
         let loop_playback = false;
-        let media_data = open_media(MEDIA_FILE.to_string()).unwrap();
+        let temp_store = TempStore::new(None).unwrap();
+        let media_data = open_media(MEDIA_FILE.to_string(), true, &temp_store).unwrap();
         let media = media_data.frame_iter;
-        let pipeline = ImagePipeline::new((23, 80), CHARS1.chars().collect(), false);
+        let pipeline = ImagePipeline::new((23, 80), split_glyphs(CHARS1), false);
 
-        let (tx_frames, _rx_frames) = bounded::<Option<StringInfo>>(1);
+        let (tx_frames, _rx_frames) = bounded::<Option<RenderedFrame>>(1);
         let (_tx_controls_pipeline, rx_controls_pipeline) = unbounded::<PipelineControl>();
         let (tx_control, _rx_controls_media) = unbounded::<MediaControl>();
 
@@ -443,8 +958,10 @@ mod tests {
             tx_control,
             RunnerOptions {
                 fps,
-                w_mod: 1,
+                cell_aspect: CELL_ASPECT,
                 loop_playback,
+                decimate: 1,
+                char_maps: CharMapRegistry::default(),
             },
         );
 
@@ -483,4 +1000,42 @@ mod tests {
         assert_eq!(should_process, true);
         assert_eq!(frames_to_skip, 2);
     }
+
+    #[test]
+    fn test_frame_observers_receive_rendered_frames() {
+        let media = FrameIterator::Image(Some(DynamicImage::new_rgb8(4, 4)));
+        let pipeline = ImagePipeline::new((8, 2), split_glyphs(CHARS1), false);
+        let (tx_frames, _rx_frames) = bounded::<Option<RenderedFrame>>(1);
+        let (_tx_controls_pipeline, rx_controls_pipeline) = unbounded::<PipelineControl>();
+        let (tx_control, _rx_controls_media) = unbounded::<MediaControl>();
+        let mut runner = Runner::new(
+            pipeline,
+            media,
+            tx_frames,
+            rx_controls_pipeline,
+            tx_control,
+            RunnerOptions {
+                fps: 30.0,
+                cell_aspect: CELL_ASPECT,
+                loop_playback: false,
+                decimate: 1,
+                char_maps: CharMapRegistry::default(),
+            },
+        );
+
+        let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let observed = std::sync::Arc::clone(&received);
+        runner.add_frame_observer(move |frame| {
+            observed
+                .lock()
+                .unwrap()
+                .push((frame.cells().count(), frame.width(), frame.height()));
+        });
+
+        let frame = runner.get_current_frame();
+        let grid = runner.process_current_frame(frame.as_ref(), false).unwrap();
+        runner.notify_frame_observers(&grid);
+
+        assert_eq!(*received.lock().unwrap(), vec![(16, 8, 2)]);
+    }
 }
@@ -0,0 +1,340 @@
+//! Provides the scene index: thumbnails of the scenes of a video, detected by scene cuts, shown
+//! in a grid during playback (`Tab`) to seek to a scene.
+//!
+//! The video is scanned on a background thread, with its own decoder, the first time the index
+//! is opened. A scene cut is detected when a frame differs a lot from the previous analyzed frame.
+//! Scenes show up in the index as they are found, so it can be used before the scan is over.
+use super::{
+    cell_grid::CellGrid,
+    contact_sheet::{render_sheet, SampledFrame},
+    video::{self, VideoDecoder},
+};
+use image::{imageops::FilterType, DynamicImage};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// Number of frames analyzed per second of video.
+const ANALYZED_FPS: f64 = 4.0;
+/// Size of the frames compared to detect scene cuts.
+const SIGNATURE_SIZE: (u32, u32) = (32, 18);
+/// Mean difference between two analyzed frames (0-1) from which a scene cut is detected.
+const SCENE_CUT_THRESHOLD: f64 = 0.25;
+/// Minimum length of a scene, in seconds.
+const MIN_SCENE_LENGTH: f64 = 1.0;
+/// Size of the thumbnails kept for each scene.
+const THUMBNAIL_SIZE: (u32, u32) = (160, 90);
+/// Number of tiles in each row of the index.
+const INDEX_COLUMNS: usize = 4;
+/// Number of scenes shown at once, the index shows the page of the selected scene.
+const INDEX_PAGE: usize = 12;
+
+/// A scene of the video.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    /// When the scene starts, in seconds from the start of the video.
+    pub time: f64,
+    /// The first frame of the scene, downscaled.
+    pub thumbnail: DynamicImage,
+}
+
+/// A navigation command of the scene index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneNavigation {
+    /// Opens the index, or closes it if it is open.
+    Toggle,
+    /// Closes the index.
+    Close,
+    /// Moves the selection by the given number of columns and rows.
+    Move(i32, i32),
+    /// Seeks to the selected scene, and closes the index.
+    Select,
+}
+
+/// Detects scene cuts in a sequence of frames.
+#[derive(Debug, Default)]
+pub struct SceneDetector {
+    /// The downscaled gray levels of the previous frame.
+    previous: Option<Vec<u8>>,
+    /// When the last scene started.
+    last_cut: Option<f64>,
+}
+
+impl SceneDetector {
+    /// Analyzes the next frame, and returns whether a new scene starts with it. The first frame
+    /// starts a scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of the frame, in seconds.
+    /// * `frame` - The frame.
+    pub fn is_cut(&mut self, time: f64, frame: &DynamicImage) -> bool {
+        let (width, height) = SIGNATURE_SIZE;
+        let signature = frame
+            .resize_exact(width, height, FilterType::Triangle)
+            .into_luma8()
+            .into_raw();
+        let difference = self.previous.as_ref().map(|previous| {
+            let total: u64 = previous
+                .iter()
+                .zip(&signature)
+                .map(|(a, b)| a.abs_diff(*b) as u64)
+                .sum();
+            total as f64 / (signature.len() as f64 * u8::MAX as f64)
+        });
+        self.previous = Some(signature);
+
+        let is_cut = match (difference, self.last_cut) {
+            (None, _) | (_, None) => true,
+            (Some(difference), Some(last_cut)) => {
+                difference >= SCENE_CUT_THRESHOLD && time - last_cut >= MIN_SCENE_LENGTH
+            }
+        };
+        if is_cut {
+            self.last_cut = Some(time);
+        }
+        is_cut
+    }
+}
+
+/// The scenes found by a background scan.
+pub struct SceneIndex {
+    /// The scenes found so far, in order.
+    scenes: Arc<Mutex<Vec<Scene>>>,
+    /// Tells the background thread to stop.
+    stop: Arc<AtomicBool>,
+}
+
+impl SceneIndex {
+    /// Starts scanning a video file or stream with the video decoder compiled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path or URL of the video.
+    /// * `fps` - The frame rate of the video.
+    pub fn scan(source: &str, fps: f64) -> Self {
+        let source = source.to_string();
+        let step = (fps / ANALYZED_FPS).round().max(1.0) as usize;
+        let mut capture: Option<Box<dyn VideoDecoder>> = None;
+        let mut index = 0;
+        Self::with_frames(std::iter::from_fn(move || {
+            if capture.is_none() {
+                capture = video::open(&source).ok();
+            }
+            let video = capture.as_mut()?;
+            // Skip the frames that are not analyzed without converting them
+            let skipped = (step - index % step) % step;
+            if !video.skip_frames(skipped) {
+                return None;
+            }
+            index += skipped;
+            let frame = video.next_frame()?;
+            index += 1;
+            Some(((index - 1) as f64 / fps, frame))
+        }))
+    }
+
+    /// Starts scanning frames on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames to scan and their time, in seconds.
+    pub fn with_frames<I>(frames: I) -> Self
+    where
+        I: Iterator<Item = (f64, DynamicImage)> + Send + 'static,
+    {
+        let scenes = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_scenes, thread_stop) = (Arc::clone(&scenes), Arc::clone(&stop));
+        thread::spawn(move || {
+            let mut detector = SceneDetector::default();
+            let (width, height) = THUMBNAIL_SIZE;
+            for (time, frame) in frames {
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if detector.is_cut(time, &frame) {
+                    let scene = Scene {
+                        time,
+                        thumbnail: frame.resize(width, height, FilterType::Triangle),
+                    };
+                    thread_scenes
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .push(scene);
+                }
+            }
+        });
+        Self { scenes, stop }
+    }
+
+    /// Returns the scenes found so far.
+    pub fn scenes(&self) -> Vec<Scene> {
+        self.scenes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl Drop for SceneIndex {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The scene index shown during playback: the scan of the video, and the selected scene while
+/// the index is open.
+pub struct SceneBrowser {
+    /// The path or URL of the video.
+    source: String,
+    /// The frame rate of the video.
+    fps: f64,
+    /// The scan of the video, started the first time the index is opened.
+    index: Option<SceneIndex>,
+    /// The selected scene, `None` while the index is closed.
+    selected: Option<usize>,
+}
+
+impl SceneBrowser {
+    /// Constructs a closed scene index for the given video.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path or URL of the video.
+    /// * `fps` - The frame rate of the video.
+    pub fn new(source: &str, fps: f64) -> Self {
+        Self {
+            source: source.to_string(),
+            fps,
+            index: None,
+            selected: None,
+        }
+    }
+
+    /// Returns whether the index is shown.
+    pub fn is_open(&self) -> bool {
+        self.selected.is_some()
+    }
+
+    /// Applies a navigation command.
+    ///
+    /// # Arguments
+    ///
+    /// * `navigation` - The command.
+    ///
+    /// # Returns
+    ///
+    /// The time to seek to, in seconds, if a scene was selected.
+    pub fn navigate(&mut self, navigation: SceneNavigation) -> Option<f64> {
+        match navigation {
+            SceneNavigation::Toggle if self.is_open() => self.selected = None,
+            SceneNavigation::Toggle => {
+                let (source, fps) = (&self.source, self.fps);
+                self.index
+                    .get_or_insert_with(|| SceneIndex::scan(source, fps));
+                self.selected = Some(0);
+            }
+            SceneNavigation::Close => self.selected = None,
+            SceneNavigation::Move(columns, rows) => {
+                let count = self.index.as_ref().map_or(0, |index| index.scenes().len());
+                if let Some(selected) = self.selected.as_mut() {
+                    let target =
+                        *selected as i64 + columns as i64 + rows as i64 * INDEX_COLUMNS as i64;
+                    *selected = target.clamp(0, count.saturating_sub(1) as i64) as usize;
+                }
+            }
+            SceneNavigation::Select => {
+                let selected = self.selected.take()?;
+                let scenes = self.index.as_ref()?.scenes();
+                return scenes.get(selected).map(|scene| scene.time);
+            }
+        }
+        None
+    }
+
+    /// Renders the page of the index showing the selected scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the index (width, height), in characters.
+    /// * `char_map` - The character lookup table used to draw the thumbnails.
+    ///
+    /// # Returns
+    ///
+    /// The index, or `None` if it is closed, no scene was found yet or it does not fit.
+    pub fn render(&self, size: (u32, u32), char_map: Vec<String>) -> Option<CellGrid> {
+        let selected = self.selected?;
+        let scenes = self.index.as_ref()?.scenes();
+        let page = selected / INDEX_PAGE * INDEX_PAGE;
+        let frames: Vec<SampledFrame> = scenes
+            .into_iter()
+            .skip(page)
+            .take(INDEX_PAGE)
+            .map(|scene| SampledFrame {
+                time: Some(scene.time),
+                image: scene.thumbnail,
+            })
+            .collect();
+        render_sheet(
+            &frames,
+            INDEX_COLUMNS as u32,
+            size,
+            char_map,
+            Some(selected - page),
+        )
+        .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_scene_detector() {
+        let dark = DynamicImage::new_rgb8(64, 36);
+        let bright = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            64,
+            36,
+            image::Rgb([255, 255, 255]),
+        ));
+        let mut detector = SceneDetector::default();
+        assert!(detector.is_cut(0.0, &dark));
+        assert!(!detector.is_cut(0.5, &dark));
+        // Too close to the previous cut
+        assert!(!detector.is_cut(0.75, &bright));
+        assert!(!detector.is_cut(1.5, &bright));
+        assert!(detector.is_cut(2.0, &dark));
+    }
+
+    #[test]
+    fn test_scene_browser() {
+        let frames = (0..8).map(|i| {
+            let level = if i < 4 { 0 } else { 255 };
+            let frame =
+                DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([level; 3])));
+            (i as f64, frame)
+        });
+        let mut browser = SceneBrowser::new("", 1.0);
+        browser.index = Some(SceneIndex::with_frames(frames));
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while browser.index.as_ref().unwrap().scenes().len() < 2 {
+            assert!(Instant::now() < deadline, "the scenes were not found");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(!browser.is_open());
+        assert!(browser.render((80, 24), vec![" ".into()]).is_none());
+        assert_eq!(browser.navigate(SceneNavigation::Toggle), None);
+        assert!(browser.render((80, 24), vec![" ".into()]).is_some());
+        browser.navigate(SceneNavigation::Move(5, 0));
+        assert_eq!(browser.navigate(SceneNavigation::Select), Some(4.0));
+        assert!(!browser.is_open());
+    }
+}
@@ -0,0 +1,312 @@
+//! Plays a directory of images as a slideshow, e.g. a wallpaper collection used as a screensaver
+//! (with `--loop`).
+//!
+//! The images of the directory and of its subdirectories are shown one after the other, sorted by
+//! path, each for the same duration (`--slide-duration`). The change from one image to the next is
+//! a transition (`--transition`): a blending stage mixing the outgoing and the incoming image over
+//! a number of frames (`--transition-frames`), either fading one into the other or wiping the
+//! incoming image in from the left.
+//!
+//! The images are decoded when they are shown, only the current image and the one before it are
+//! kept in memory.
+use crate::{
+    common::errors::*,
+    pipeline::{frames::decode_image, music::has_extension},
+};
+use image::{imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Frame rate of slideshows, high enough for smooth transitions.
+pub const SLIDESHOW_FPS: f64 = 15.0;
+/// How long each image is shown by default, in seconds.
+pub const DEFAULT_SLIDE_DURATION: f64 = 10.0;
+/// How many frames a transition lasts by default.
+pub const DEFAULT_TRANSITION_FRAMES: usize = 15;
+/// Extensions of the images shown.
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+/// Largest width or height of the images once decoded, in pixels. Larger images (e.g. 4K
+/// wallpapers) are scaled down, as they are scaled down to the size of the terminal anyway and
+/// blending them at full size would be slow.
+const MAX_SLIDE_SIZE: u32 = 1280;
+
+/// How the slideshow changes from an image to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// The next image replaces the previous one at once.
+    Cut,
+    /// The previous image fades into the next one.
+    Crossfade,
+    /// The next image slides over the previous one from the left.
+    Wipe,
+}
+
+impl FromStr for Transition {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "cut" | "none" => Ok(Transition::Cut),
+            "crossfade" | "fade" => Ok(Transition::Crossfade),
+            "wipe" => Ok(Transition::Wipe),
+            _ => Err(format!(
+                "`{name}` is not a transition (cut, crossfade, wipe)"
+            )),
+        }
+    }
+}
+
+impl Transition {
+    /// Blends the outgoing and the incoming image of a transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `outgoing` - The previous image.
+    /// * `incoming` - The next image, of the same size.
+    /// * `progress` - How far the transition is, from 0 (the previous image) to 1 (the next one).
+    ///
+    /// # Returns
+    ///
+    /// The frame shown at this point of the transition.
+    pub fn blend(&self, outgoing: &RgbImage, incoming: &RgbImage, progress: f64) -> RgbImage {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            Transition::Cut => incoming.clone(),
+            Transition::Crossfade => {
+                RgbImage::from_fn(incoming.width(), incoming.height(), |x, y| {
+                    let (from, to) = (outgoing.get_pixel(x, y).0, incoming.get_pixel(x, y).0);
+                    Rgb(std::array::from_fn(|channel| {
+                        let (from, to) = (from[channel] as f64, to[channel] as f64);
+                        (from + (to - from) * progress).round() as u8
+                    }))
+                })
+            }
+            Transition::Wipe => {
+                let edge = (incoming.width() as f64 * progress).round() as u32;
+                RgbImage::from_fn(incoming.width(), incoming.height(), |x, y| match x < edge {
+                    true => *incoming.get_pixel(x, y),
+                    false => *outgoing.get_pixel(x, y),
+                })
+            }
+        }
+    }
+}
+
+/// A decoded image of the slideshow.
+struct Slide {
+    /// The position of the image in the slideshow.
+    index: usize,
+    /// The image, at the size it was requested.
+    image: RgbImage,
+}
+
+/// The images of a directory shown one after the other.
+pub struct Slideshow {
+    /// The paths of the images, in the order they are shown.
+    images: Vec<PathBuf>,
+    /// How many frames each image is shown, its transition included.
+    slide_frames: usize,
+    /// How the slideshow changes from an image to the next.
+    transition: Transition,
+    /// How many frames the transitions last.
+    transition_frames: usize,
+    /// The index of the next frame.
+    current_frame: usize,
+    /// The images decoded last: the current one, and the previous one during transitions.
+    slides: Vec<Slide>,
+}
+
+impl Slideshow {
+    /// Reads the images of a directory, and of its subdirectories.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the slideshow, with the default timing and transition.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the directory cannot be read, or has no images.
+    pub fn open(dir: &Path) -> Result<Self, MyError> {
+        let mut images = Vec::new();
+        collect_images(dir, &mut images)?;
+        images.sort();
+        if images.is_empty() {
+            return Err(MyError::Application(format!(
+                "{ERROR_EMPTY_SLIDESHOW}: {}",
+                dir.display()
+            )));
+        }
+        let mut slideshow = Self {
+            images,
+            slide_frames: 1,
+            transition: Transition::Crossfade,
+            transition_frames: 0,
+            current_frame: 0,
+            slides: Vec::new(),
+        };
+        slideshow.set_timing(
+            DEFAULT_SLIDE_DURATION,
+            Transition::Crossfade,
+            DEFAULT_TRANSITION_FRAMES,
+        );
+        Ok(slideshow)
+    }
+
+    /// Sets how long each image is shown, and how the slideshow changes from an image to the next.
+    ///
+    /// # Arguments
+    ///
+    /// * `slide_duration` - How long each image is shown, in seconds at `SLIDESHOW_FPS`.
+    /// * `transition` - How the slideshow changes from an image to the next.
+    /// * `transition_frames` - How many frames the transitions last, at most the frames of an
+    ///   image.
+    pub fn set_timing(
+        &mut self,
+        slide_duration: f64,
+        transition: Transition,
+        transition_frames: usize,
+    ) {
+        self.slide_frames = (slide_duration * SLIDESHOW_FPS).round().max(1.0) as usize;
+        self.transition = transition;
+        self.transition_frames = transition_frames.min(self.slide_frames - 1);
+    }
+
+    /// Returns the number of images of the slideshow.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns whether the slideshow has no images, which `open` does not allow.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Returns the number of frames of the whole slideshow.
+    pub fn frames(&self) -> usize {
+        self.images.len() * self.slide_frames
+    }
+
+    /// Returns the index of the next frame.
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    /// Returns how many frames each image is shown.
+    pub fn slide_frames(&self) -> usize {
+        self.slide_frames
+    }
+
+    /// Moves to a frame of the slideshow.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The index of the frame, frames past the end move to the end.
+    pub fn set_current_frame(&mut self, frame: usize) {
+        self.current_frame = frame.min(self.frames());
+    }
+
+    /// Returns the next frame of the slideshow: the current image, or a blend of the previous
+    /// and the current image during transitions.
+    pub fn next_frame(&mut self) -> Option<DynamicImage> {
+        if self.current_frame >= self.frames() {
+            return None;
+        }
+        let index = self.current_frame / self.slide_frames;
+        let frame = self.current_frame % self.slide_frames;
+        self.current_frame += 1;
+        let incoming = self.slide(index, None);
+        if index == 0 || frame >= self.transition_frames || self.transition == Transition::Cut {
+            return Some(DynamicImage::ImageRgb8(incoming));
+        }
+        let outgoing = self.slide(index - 1, Some(incoming.dimensions()));
+        let progress = (frame + 1) as f64 / (self.transition_frames + 1) as f64;
+        let blended = self.transition.blend(&outgoing, &incoming, progress);
+        Some(DynamicImage::ImageRgb8(blended))
+    }
+
+    /// Returns an image of the slideshow, decoding it unless it was decoded last. Images that
+    /// cannot be decoded are shown as a black frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the image in the slideshow.
+    /// * `size` - The size to scale the image to, or `None` for its own size (capped to
+    ///   `MAX_SLIDE_SIZE`).
+    pub fn slide(&mut self, index: usize, size: Option<(u32, u32)>) -> RgbImage {
+        let cached = self.slides.iter().find(|slide| {
+            slide.index == index && size.is_none_or(|size| slide.image.dimensions() == size)
+        });
+        if let Some(slide) = cached {
+            return slide.image.clone();
+        }
+        let image = decode_image(&self.images[index])
+            .map(
+                |image| match image.width().max(image.height()) > MAX_SLIDE_SIZE {
+                    true => image.resize(MAX_SLIDE_SIZE, MAX_SLIDE_SIZE, FilterType::Triangle),
+                    false => image,
+                },
+            )
+            .map_or_else(|_| RgbImage::new(1, 1), |image| image.to_rgb8());
+        let image = match size {
+            Some((width, height)) if image.dimensions() != (width, height) => {
+                image::imageops::resize(&image, width, height, FilterType::Triangle)
+            }
+            _ => image,
+        };
+        // Keep the two images of a transition, the incoming one is decoded first
+        if self.slides.len() >= 2 {
+            self.slides.remove(0);
+        }
+        self.slides.push(Slide {
+            index,
+            image: image.clone(),
+        });
+        image
+    }
+}
+
+/// Adds the images of a directory and of its subdirectories to a list.
+///
+/// # Arguments
+///
+/// * `dir` - The directory.
+/// * `files` - The list of images.
+fn collect_images(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), MyError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_images(&path, files)?;
+        } else if has_extension(&path, &IMAGE_EXTENSIONS) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let black = RgbImage::new(4, 1);
+        let white = RgbImage::from_pixel(4, 1, Rgb([255; 3]));
+        let faded = Transition::Crossfade.blend(&black, &white, 0.5);
+        assert!(faded.pixels().all(|p| p.0 == [128; 3]));
+        let wiped = Transition::Wipe.blend(&black, &white, 0.5);
+        assert_eq!(
+            wiped.pixels().map(|p| p.0[0]).collect::<Vec<_>>(),
+            [255, 255, 0, 0]
+        );
+        assert_eq!(Transition::Cut.blend(&black, &white, 0.1), white);
+        assert_eq!("fade".parse(), Ok(Transition::Crossfade));
+        assert!("dissolve".parse::<Transition>().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Shows stereoscopic 3D media, holding the views of both eyes in each frame, as a single image.
+//!
+//! Side-by-side media have the view of the left eye in the left half of the frame and the view of
+//! the right eye in the right half; top-and-bottom media stack them. Instead of drawing the double
+//! image, the views are merged into a red/cyan anaglyph (the red channel of the left view with the
+//! green and blue channels of the right view, for red/cyan glasses), or a single view is kept.
+use image::{DynamicImage, RgbImage};
+use std::str::FromStr;
+
+/// How the views of the two eyes are laid out in the frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// The left view in the left half, the right view in the right half.
+    SideBySide,
+    /// The left view in the top half, the right view in the bottom half.
+    TopBottom,
+}
+
+/// What is shown of the two views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoView {
+    /// Both views merged into a red/cyan anaglyph.
+    Anaglyph,
+    /// The view of the left eye only.
+    Left,
+    /// The view of the right eye only.
+    Right,
+}
+
+/// How stereoscopic frames are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StereoMode {
+    /// How the views are laid out in the frames.
+    pub layout: StereoLayout,
+    /// What is shown of them.
+    pub view: StereoView,
+}
+
+impl FromStr for StereoMode {
+    type Err = String;
+
+    /// Parses a mode such as `sbs` (side by side, anaglyph), `tab-left` (top and bottom, left eye
+    /// only) or `sbs-right`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!("`{name}` is not a stereo mode (sbs, tab, with -left or -right for one eye)")
+        };
+        let lowercase = name.to_ascii_lowercase();
+        let (layout, view) = lowercase.split_once('-').unwrap_or((&lowercase, ""));
+        let layout = match layout {
+            "sbs" => StereoLayout::SideBySide,
+            "tab" | "tb" => StereoLayout::TopBottom,
+            _ => return Err(error()),
+        };
+        let view = match view {
+            "" | "anaglyph" => StereoView::Anaglyph,
+            "left" => StereoView::Left,
+            "right" => StereoView::Right,
+            _ => return Err(error()),
+        };
+        Ok(Self { layout, view })
+    }
+}
+
+impl StereoMode {
+    /// Merges the views of a stereoscopic frame into a single image, half the size of the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The frame holding both views.
+    ///
+    /// # Returns
+    ///
+    /// The anaglyph, or the view of one eye.
+    pub fn merge(&self, img: &DynamicImage) -> DynamicImage {
+        let (width, height) = (img.width(), img.height());
+        let (view_width, view_height, right_offset) = match self.layout {
+            StereoLayout::SideBySide => (width / 2, height, (width / 2, 0)),
+            StereoLayout::TopBottom => (width, height / 2, (0, height / 2)),
+        };
+        if view_width == 0 || view_height == 0 {
+            return img.clone();
+        }
+        match self.view {
+            StereoView::Left => img.crop_imm(0, 0, view_width, view_height),
+            StereoView::Right => {
+                img.crop_imm(right_offset.0, right_offset.1, view_width, view_height)
+            }
+            StereoView::Anaglyph => {
+                let rgb = img.to_rgb8();
+                let anaglyph = RgbImage::from_fn(view_width, view_height, |x, y| {
+                    let [red, ..] = rgb.get_pixel(x, y).0;
+                    let [_, green, blue] = rgb.get_pixel(x + right_offset.0, y + right_offset.1).0;
+                    image::Rgb([red, green, blue])
+                });
+                DynamicImage::ImageRgb8(anaglyph)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        // A white left view next to a black right view
+        let frame = RgbImage::from_fn(4, 2, |x, _| image::Rgb([if x < 2 { 255 } else { 0 }; 3]));
+        let frame = DynamicImage::ImageRgb8(frame);
+        let merge = |mode: &str| mode.parse::<StereoMode>().unwrap().merge(&frame).to_rgb8();
+
+        let anaglyph = merge("sbs");
+        assert_eq!(anaglyph.dimensions(), (2, 2));
+        assert!(anaglyph.pixels().all(|p| p.0 == [255, 0, 0]));
+        assert!(merge("sbs-left").pixels().all(|p| p.0 == [255; 3]));
+        assert!(merge("sbs-right").pixels().all(|p| p.0 == [0; 3]));
+        assert_eq!(merge("tab").dimensions(), (4, 1));
+        assert!("sbs-both".parse::<StereoMode>().is_err());
+    }
+}
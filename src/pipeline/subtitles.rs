@@ -0,0 +1,451 @@
+//! Shows subtitles as closed captions over the bottom of the frames, timed with the playback
+//! clock shared by the pipeline.
+//!
+//! Subtitles come from a SubRip (`.srt`) or ASS (`.ass`, `.ssa`) file, given with `--sub` or
+//! found next to the video with the same name (e.g. `movie.srt` or `movie.en.srt` for
+//! `movie.mkv`), and from the text subtitle streams embedded in the video (e.g. `mov_text` in MP4
+//! files, SubRip or ASS in MKV files).
+//!
+//! The embedded streams are listed with the probing layer, and extracted with `ffmpeg` converting
+//! them to SubRip, on a background thread, as reading a stream means reading the whole file.
+//! Tracks show up as they are extracted, so playback starts right away. Bitmap subtitles (e.g.
+//! Blu-ray PGS) cannot be drawn with text and are skipped.
+//!
+//! The first track (the subtitle file, if any) is shown by default, `j` cycles through the tracks
+//! and then turns subtitles off.
+use crate::{
+    common::{errors::*, probe::probe_subtitle_streams},
+    pipeline::music::has_extension,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// Codecs of the subtitle streams that `ffmpeg` can convert to text.
+const TEXT_SUBTITLE_CODECS: [&str; 7] =
+    ["subrip", "srt", "mov_text", "ass", "ssa", "webvtt", "text"];
+/// Extensions of the subtitle files found next to videos.
+const SUBTITLE_EXTENSIONS: [&str; 3] = ["srt", "ass", "ssa"];
+
+/// A subtitle shown for a time span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// When the subtitle appears, in seconds from the start of the video.
+    pub start: f64,
+    /// When the subtitle disappears, in seconds from the start of the video.
+    pub end: f64,
+    /// The lines of the subtitle, without formatting tags.
+    pub lines: Vec<String>,
+}
+
+/// A subtitle track.
+#[derive(Debug, Clone)]
+struct SubtitleTrack {
+    /// The name of the track shown when switching to it: the name of the file, or the language
+    /// and title of the embedded stream.
+    label: String,
+    /// The subtitles of the track, `None` until the track is extracted.
+    cues: Option<Vec<Cue>>,
+}
+
+/// The subtitle tracks of a video, and the one shown.
+pub struct Subtitles {
+    /// The tracks, the embedded ones are added by a background thread.
+    tracks: Arc<Mutex<Vec<SubtitleTrack>>>,
+    /// The index of the track shown, `None` when subtitles are off.
+    selected: Option<usize>,
+}
+
+impl Default for Subtitles {
+    fn default() -> Self {
+        Self {
+            tracks: Arc::new(Mutex::new(Vec::new())),
+            selected: Some(0),
+        }
+    }
+}
+
+impl Subtitles {
+    /// Adds the track of a subtitle file, in the SubRip or ASS format (by extension).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MyError` if the file cannot be read.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), MyError> {
+        let bytes = fs::read(path).map_err(|err| {
+            MyError::Application(format!(
+                "{ERROR_READING_SUBTITLES}: {}: {err}",
+                path.display()
+            ))
+        })?;
+        let text = String::from_utf8_lossy(&bytes);
+        let cues = match has_extension(path, &["ass", "ssa"]) {
+            true => parse_ass(&text),
+            false => parse_srt(&text),
+        };
+        let label = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        if let Ok(mut tracks) = self.tracks.lock() {
+            tracks.push(SubtitleTrack {
+                label,
+                cues: Some(cues),
+            });
+        }
+        Ok(())
+    }
+
+    /// Lists and extracts the text subtitle streams embedded in a video on a background thread,
+    /// adding them after the tracks already loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The path of the video.
+    pub fn extract_embedded(&mut self, source: &str) {
+        let shared = Arc::clone(&self.tracks);
+        let source = source.to_string();
+        thread::spawn(move || {
+            let Ok(streams) = probe_subtitle_streams(&source) else {
+                return;
+            };
+            let text_streams: Vec<(usize, String)> = streams
+                .iter()
+                .enumerate()
+                .filter(|(_, stream)| TEXT_SUBTITLE_CODECS.contains(&stream.codec.as_str()))
+                .map(|(index, stream)| {
+                    let label = [&stream.language, &stream.title]
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(" - ");
+                    (index, label)
+                })
+                .collect();
+            let first = match shared.lock() {
+                Ok(mut tracks) => {
+                    let first = tracks.len();
+                    tracks.extend(text_streams.iter().map(|(index, label)| SubtitleTrack {
+                        label: match label.is_empty() {
+                            true => format!("Track {}", index + 1),
+                            false => label.clone(),
+                        },
+                        cues: None,
+                    }));
+                    first
+                }
+                Err(_) => return,
+            };
+            for (track, (index, _)) in text_streams.iter().enumerate() {
+                let cues = extract_track(&source, *index).unwrap_or_default();
+                if let Some(track) = shared
+                    .lock()
+                    .as_deref_mut()
+                    .ok()
+                    .and_then(|tracks| tracks.get_mut(first + track))
+                {
+                    track.cues = Some(cues);
+                }
+            }
+        });
+    }
+
+    /// Switches to the next track, or turns subtitles off after the last one.
+    ///
+    /// # Returns
+    ///
+    /// A message naming the track now shown, for the status bar.
+    pub fn cycle(&mut self) -> String {
+        let count = self.tracks.lock().map_or(0, |tracks| tracks.len());
+        self.selected = match self.selected {
+            _ if count == 0 => None,
+            None => Some(0),
+            Some(index) if index + 1 < count => Some(index + 1),
+            Some(_) => None,
+        };
+        match (self.selected, count) {
+            (_, 0) => "no subtitles".to_string(),
+            (None, _) => "subtitles off".to_string(),
+            (Some(index), _) => {
+                let label = self
+                    .tracks
+                    .lock()
+                    .ok()
+                    .and_then(|tracks| tracks.get(index).map(|track| track.label.clone()))
+                    .unwrap_or_default();
+                format!("subtitles: {label} ({}/{count})", index + 1)
+            }
+        }
+    }
+
+    /// Returns the lines of the subtitle of the track shown at the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The playback position, in seconds from the start of the video.
+    pub fn lines_at(&self, position: f64) -> Vec<String> {
+        let (Some(index), Ok(tracks)) = (self.selected, self.tracks.lock()) else {
+            return Vec::new();
+        };
+        tracks
+            .get(index)
+            .and_then(|track| track.cues.as_deref())
+            .map(|cues| cue_lines(cues, position))
+            .unwrap_or_default()
+    }
+}
+
+/// Looks for a subtitle file next to a video, named like it: `movie.srt`, or with a language
+/// (`movie.en.srt`) for `movie.mkv`.
+///
+/// # Arguments
+///
+/// * `video` - The path of the video.
+///
+/// # Returns
+///
+/// The path of the subtitle file, the one without a language first, or `None` if there is none.
+pub fn find_subtitle_file(video: &Path) -> Option<PathBuf> {
+    let stem = video.file_stem()?.to_str()?;
+    let dir = video.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let mut files: Vec<PathBuf> = fs::read_dir(dir.unwrap_or(Path::new(".")))
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| has_extension(file, &SUBTITLE_EXTENSIONS))
+        .filter(|file| {
+            file.file_stem()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name == stem || name.starts_with(&format!("{stem}.")))
+        })
+        .collect();
+    // The shortest name is the one without a language
+    files.sort_by_key(|file| (file.as_os_str().len(), file.clone()));
+    files.into_iter().next()
+}
+
+/// Returns the lines of the subtitles shown at a given time.
+///
+/// # Arguments
+///
+/// * `cues` - The subtitles of a track, sorted by start time.
+/// * `position` - The time, in seconds.
+fn cue_lines(cues: &[Cue], position: f64) -> Vec<String> {
+    // Cues may overlap (e.g. two speakers), so all the started ones are checked
+    let started = cues.partition_point(|cue| cue.start <= position);
+    cues[..started]
+        .iter()
+        .filter(|cue| position < cue.end)
+        .flat_map(|cue| cue.lines.iter().cloned())
+        .collect()
+}
+
+/// Extracts a subtitle stream of a video with `ffmpeg`, converted to SubRip.
+///
+/// # Arguments
+///
+/// * `source` - The path of the video.
+/// * `index` - The index of the stream among the subtitle streams of the video.
+///
+/// # Returns
+///
+/// The subtitles of the stream, or `None` if `ffmpeg` cannot be run.
+fn extract_track(source: &str, index: usize) -> Option<Vec<Cue>> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-i", source, "-map"])
+        .arg(format!("0:s:{index}"))
+        .args(["-f", "srt", "-"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(parse_srt(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses subtitles in the SubRip (`.srt`) format. Formatting tags (`<i>`, `{\an8}`) are removed,
+/// and malformed entries are skipped.
+///
+/// # Arguments
+///
+/// * `text` - The content of the SubRip file.
+///
+/// # Returns
+///
+/// The subtitles, sorted by start time.
+pub fn parse_srt(text: &str) -> Vec<Cue> {
+    let text = text.replace("\r\n", "\n");
+    let mut cues: Vec<Cue> = text
+        .split("\n\n")
+        .filter_map(|entry| {
+            let mut lines = entry.lines().skip_while(|line| !line.contains("-->"));
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let (start, end) = (parse_timestamp(start)?, parse_timestamp(end)?);
+            let lines = lines
+                .map(strip_tags)
+                .filter(|line| !line.is_empty())
+                .collect();
+            Some(Cue { start, end, lines })
+        })
+        .collect();
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    cues
+}
+
+/// Parses subtitles in the ASS (`.ass`, `.ssa`) format: the `Dialogue` lines of the `[Events]`
+/// section. Styles, positions and effects are ignored, formatting tags are removed.
+///
+/// # Arguments
+///
+/// * `text` - The content of the ASS file.
+///
+/// # Returns
+///
+/// The subtitles, sorted by start time.
+pub fn parse_ass(text: &str) -> Vec<Cue> {
+    // The fields of the dialogues, in the order given by the `Format` line of the events
+    let mut fields: Vec<String> = Vec::new();
+    let mut in_events = false;
+    let mut cues = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        let Some((kind, values)) = line.split_once(':').filter(|_| in_events) else {
+            continue;
+        };
+        match kind.trim() {
+            "Format" => {
+                fields = values
+                    .split(',')
+                    .map(|field| field.trim().to_ascii_lowercase())
+                    .collect();
+            }
+            "Dialogue" if !fields.is_empty() => {
+                // The text is the last field, and may contain commas
+                let values: Vec<&str> = values.splitn(fields.len(), ',').collect();
+                let field = |name: &str| {
+                    let index = fields.iter().position(|field| field == name)?;
+                    values.get(index).map(|value| value.trim())
+                };
+                let (Some(start), Some(end), Some(text)) =
+                    (field("start"), field("end"), field("text"))
+                else {
+                    continue;
+                };
+                let (Some(start), Some(end)) = (parse_timestamp(start), parse_timestamp(end))
+                else {
+                    continue;
+                };
+                let lines = text
+                    .replace("\\h", " ")
+                    .split("\\N")
+                    .flat_map(|line| line.split("\\n"))
+                    .map(strip_tags)
+                    .filter(|line| !line.is_empty())
+                    .collect();
+                cues.push(Cue { start, end, lines });
+            }
+            _ => {}
+        }
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    cues
+}
+
+/// Parses a SubRip (`01:02:03,456`) or ASS (`1:02:03.45`) timestamp.
+///
+/// # Arguments
+///
+/// * `timestamp` - The timestamp, possibly followed by position coordinates.
+///
+/// # Returns
+///
+/// The time in seconds, or `None` if the timestamp is malformed.
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let timestamp = timestamp.split_whitespace().next()?.replace(',', ".");
+    let mut parts = timestamp.rsplitn(3, ':');
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let hours: f64 = parts.next().map_or(Some(0.0), |hours| hours.parse().ok())?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Removes the HTML-like (`<i>`) and ASS (`{\an8}`) formatting tags of a line of subtitle.
+///
+/// # Arguments
+///
+/// * `line` - The line.
+fn strip_tags(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut closing = None;
+    for c in line.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (None, _) => stripped.push(c),
+            (Some(end), _) if c == end => closing = None,
+            _ => {}
+        }
+    }
+    stripped.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i>\r\n{\\an8}world\r\n\r\n\
+                   2\n00:00:02,000 --> 00:00:04,000 X1:10\nBye\n\nbroken entry\n";
+        let cues = parse_srt(srt);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start, 1.0);
+        assert_eq!(cues[0].end, 2.5);
+        assert_eq!(cues[0].lines, ["Hello", "world"]);
+        assert_eq!(cues[1].end, 4.0);
+
+        assert!(cue_lines(&cues, 0.5).is_empty());
+        assert_eq!(cue_lines(&cues, 2.2), ["Hello", "world", "Bye"]);
+        assert_eq!(cue_lines(&cues, 3.0), ["Bye"]);
+        assert!(cue_lines(&cues, 4.0).is_empty());
+        assert_eq!(parse_timestamp("1:02:03.5"), Some(3723.5));
+    }
+
+    #[test]
+    fn test_parse_ass() {
+        let ass = "[Script Info]\nTitle: Test\n\n[Events]\n\
+                   Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,Later\n\
+                   Comment: 0,0:00:00.00,0:00:09.00,Default,,0,0,0,,Not shown\n\
+                   Dialogue: 0,0:00:01.50,0:00:02.00,Default,,0,0,0,,{\\i1}Hello{\\i0}, you\\Nthere\n";
+        let cues = parse_ass(ass);
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start, cues[0].end), (1.5, 2.0));
+        assert_eq!(cues[0].lines, ["Hello, you", "there"]);
+        assert_eq!(cues[1].lines, ["Later"]);
+    }
+
+    #[test]
+    fn test_find_subtitle_file() {
+        let dir = std::env::temp_dir().join(format!("tplay-subtitles-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["movie.mkv", "movie.en.srt", "movies.srt", "other.srt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let video = dir.join("movie.mkv");
+        assert_eq!(find_subtitle_file(&video), Some(dir.join("movie.en.srt")));
+        fs::write(dir.join("movie.ASS"), "").unwrap();
+        assert_eq!(find_subtitle_file(&video), Some(dir.join("movie.ASS")));
+        assert_eq!(find_subtitle_file(&dir.join("clip.mp4")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Flips and rotates the media itself (`--flip-h`, `--flip-v`, `--rotate`, and their keys during
+//! playback), e.g. for webcams that mirror the picture or phone videos recorded sideways.
+//!
+//! Unlike the `orientation`, which turns the frames to fit a turned screen, the transform applies
+//! to the source image before it is rendered: a rotated frame keeps its own aspect ratio and is
+//! letterboxed as such.
+use image::DynamicImage;
+use std::str::FromStr;
+
+/// A change of the transform, bound to a key during playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformChange {
+    /// Mirrors the frames left to right.
+    FlipHorizontal,
+    /// Mirrors the frames top to bottom.
+    FlipVertical,
+    /// Turns the frames a quarter turn clockwise.
+    Rotate,
+}
+
+/// A rotation of the frames by quarter turns, as given with `--rotate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rotation(pub u8);
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(degrees: &str) -> Result<Self, Self::Err> {
+        match degrees.trim_end_matches('°') {
+            "0" => Ok(Rotation(0)),
+            "90" => Ok(Rotation(1)),
+            "180" => Ok(Rotation(2)),
+            "270" => Ok(Rotation(3)),
+            _ => Err(format!("`{degrees}` is not a rotation (0, 90, 180, 270)")),
+        }
+    }
+}
+
+/// How the source frames are flipped and rotated before they are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Transform {
+    /// Whether the frames are mirrored left to right.
+    pub flip_horizontal: bool,
+    /// Whether the frames are mirrored top to bottom.
+    pub flip_vertical: bool,
+    /// The number of quarter turns clockwise (0-3), applied after the flips.
+    pub rotation: Rotation,
+}
+
+impl Transform {
+    /// Returns whether the transform leaves the frames as they are.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies a change of the transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `change` - The flip to toggle, or the quarter turn to add.
+    pub fn change(&mut self, change: TransformChange) {
+        match change {
+            TransformChange::FlipHorizontal => self.flip_horizontal = !self.flip_horizontal,
+            TransformChange::FlipVertical => self.flip_vertical = !self.flip_vertical,
+            TransformChange::Rotate => self.rotation = Rotation((self.rotation.0 + 1) % 4),
+        }
+    }
+
+    /// Flips and rotates an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The source frame.
+    ///
+    /// # Returns
+    ///
+    /// The transformed frame.
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let img = match (self.flip_horizontal, self.flip_vertical) {
+            (false, false) => img.clone(),
+            (true, false) => img.fliph(),
+            (false, true) => img.flipv(),
+            (true, true) => img.rotate180(),
+        };
+        match self.rotation.0 % 4 {
+            1 => img.rotate90(),
+            2 => img.rotate180(),
+            3 => img.rotate270(),
+            _ => img,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn test_transform() {
+        // The image:
+        // 0 1 2
+        // 3 4 5
+        let img =
+            DynamicImage::ImageLuma8(GrayImage::from_fn(3, 2, |x, y| Luma([(y * 3 + x) as u8])));
+        let pixels = |transform: Transform| transform.apply(&img).to_luma8().into_raw();
+        let mut transform = Transform::default();
+        assert!(transform.is_identity());
+        assert_eq!(pixels(transform), [0, 1, 2, 3, 4, 5]);
+        transform.change(TransformChange::FlipHorizontal);
+        assert_eq!(pixels(transform), [2, 1, 0, 5, 4, 3]);
+        transform.change(TransformChange::FlipHorizontal);
+        transform.change(TransformChange::FlipVertical);
+        assert_eq!(pixels(transform), [3, 4, 5, 0, 1, 2]);
+        transform.change(TransformChange::FlipVertical);
+        transform.change(TransformChange::Rotate);
+        let rotated = transform.apply(&img);
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(rotated.to_luma8().into_raw(), [3, 0, 4, 1, 5, 2]);
+        for _ in 0..3 {
+            transform.change(TransformChange::Rotate);
+        }
+        assert!(transform.is_identity());
+        assert_eq!("270".parse(), Ok(Rotation(3)));
+        assert!("45".parse::<Rotation>().is_err());
+    }
+}
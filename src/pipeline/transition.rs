@@ -0,0 +1,137 @@
+//! Animates the switches of character map and of grayscale mode (`--switch-transition`), a purely
+//! cosmetic effect.
+//!
+//! When the character map changes, the frames are rendered with both maps for a few frames, and
+//! the cells change over from one to the other in a dithered pattern, as if the thresholds of the
+//! new map were brought in one after the other. When the grayscale mode is toggled, the colors fade
+//! to their gray level, or back from it.
+use super::cell_grid::{Cell, CellGrid};
+use image::{Pixel, Rgb};
+
+/// Order in which the cells of each 4x4 block change over (Bayer matrix).
+const DISSOLVE_ORDER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// A transition lasting a number of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    /// The number of frames shown so far.
+    frame: u32,
+    /// The number of frames the transition lasts.
+    frames: u32,
+}
+
+impl Transition {
+    /// Starts a transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The number of frames the transition lasts.
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frame: 0,
+            frames: frames.max(1),
+        }
+    }
+
+    /// Moves to the next frame of the transition.
+    ///
+    /// # Returns
+    ///
+    /// How far the transition is at this frame, from above 0 to 1 for its last frame.
+    pub fn advance(&mut self) -> f32 {
+        self.frame = (self.frame + 1).min(self.frames);
+        self.frame as f32 / self.frames as f32
+    }
+
+    /// Returns whether the last frame of the transition was shown.
+    pub fn is_over(&self) -> bool {
+        self.frame >= self.frames
+    }
+}
+
+/// Changes the cells of a frame over from one rendering to another, in a dithered pattern.
+///
+/// # Arguments
+///
+/// * `from` - The frame rendered the previous way.
+/// * `to` - The frame rendered the new way.
+/// * `progress` - How far the transition is, from 0 (all cells from `from`) to 1 (all from `to`).
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// The blended frame, or `to` if the frames cannot be blended (different sizes, pixels).
+pub fn dissolve(from: &CellGrid, to: CellGrid, progress: f32, new_lines: bool) -> CellGrid {
+    let same_size = (from.width(), from.height()) == (to.width(), to.height());
+    if !same_size || from.pixels().is_some() || to.pixels().is_some() {
+        return to;
+    }
+    let mut blended = CellGrid::new(to.width(), to.height(), new_lines);
+    for y in 0..to.height() {
+        for (x, (from, to)) in from.row(y).zip(to.row(y)).enumerate() {
+            let order = DISSOLVE_ORDER[y as usize % 4][x % 4];
+            let switched = (order as f32 + 0.5) / 16.0 < progress;
+            blended.push_cell(if switched { to } else { from });
+        }
+    }
+    blended
+}
+
+/// Fades the colors of a frame toward their gray level.
+///
+/// # Arguments
+///
+/// * `grid` - The frame.
+/// * `amount` - How far the colors are faded, from 0 (the colors) to 1 (shades of gray).
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// The faded frame. Frames holding pixels are returned as they are.
+pub fn fade_to_gray(grid: &CellGrid, amount: f32, new_lines: bool) -> CellGrid {
+    if grid.pixels().is_some() {
+        return grid.clone();
+    }
+    let fade = |color: [u8; 3]| {
+        let gray = Rgb(color).to_luma().0[0] as f32;
+        color.map(|c| (c as f32 + (gray - c as f32) * amount.clamp(0.0, 1.0)).round() as u8)
+    };
+    let mut faded = CellGrid::new(grid.width(), grid.height(), new_lines);
+    for cell in grid.cells() {
+        faded.push_cell(Cell {
+            color: fade(cell.color),
+            background: cell.background.map(fade),
+            ..cell
+        });
+    }
+    faded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let mut transition = Transition::new(2);
+        assert_eq!(transition.advance(), 0.5);
+        assert!(!transition.is_over());
+        assert_eq!(transition.advance(), 1.0);
+        assert!(transition.is_over());
+
+        let grid = |glyph: &str| {
+            let mut grid = CellGrid::new(4, 1, false);
+            (0..4).for_each(|_| grid.push(glyph, [200, 0, 0]));
+            grid
+        };
+        for (progress, text) in [(0.0, "aaaa"), (0.2, "baba"), (0.6, "bbba"), (1.0, "bbbb")] {
+            let blended = dissolve(&grid("a"), grid("b"), progress, false);
+            assert_eq!(blended.row_text(0), text);
+        }
+
+        let gray = fade_to_gray(&grid("a"), 1.0, false);
+        let [r, g, b] = gray.colors()[0];
+        assert!(r == g && g == b);
+        assert_eq!(fade_to_gray(&grid("a"), 0.0, false), grid("a"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Decodes the frames of videos, cameras and network streams with one of the decoders compiled
+//! in, behind the `VideoDecoder` trait:
+//! - `opencv_decoder` (default): OpenCV's videoio, which also opens cameras by their device path.
+//! - `ffmpeg_decoder`: libav through ffmpeg-next, so that tplay builds without OpenCV.
+//! - Without either feature, a built-in decoder written in Rust reads YUV4MPEG2 and Motion JPEG
+//!   AVI files, so that tplay builds without any native library.
+//!
+//! The ffmpeg decoder is used when both features are enabled.
+use crate::common::errors::*;
+use image::DynamicImage;
+
+/// Maximum number of consecutive unreadable frames skipped before a video is considered ended.
+pub const MAX_CONSECUTIVE_BAD_FRAMES: usize = 10;
+
+/// A video decoder, reading the frames of a video file or stream one after the other.
+pub trait VideoDecoder: Send {
+    /// Decodes the next frame. Frames that cannot be converted are skipped, so that a single
+    /// corrupted frame does not end the playback.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or `None` if the video has ended or `MAX_CONSECUTIVE_BAD_FRAMES` frames in a
+    /// row could not be read.
+    fn next_frame(&mut self) -> Option<DynamicImage>;
+
+    /// Skips frames, without converting them when the decoder allows it.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of frames to skip.
+    ///
+    /// # Returns
+    ///
+    /// Whether all the frames were skipped, `false` if the video ended first.
+    fn skip_frames(&mut self, n: usize) -> bool {
+        (0..n).all(|_| self.next_frame().is_some())
+    }
+
+    /// Moves to the frame shown at the given time from the start of the video.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The position to move to, in seconds.
+    fn seek(&mut self, seconds: f64);
+
+    /// Moves to the frame with the given index, from 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the frame.
+    fn seek_frame(&mut self, index: usize);
+
+    /// Returns the position of the next frame in seconds, if the video reports it.
+    fn position(&self) -> Option<f64>;
+
+    /// Returns the number of frames of the video, or `None` if it is unknown (e.g. a stream).
+    fn frame_count(&self) -> Option<usize>;
+
+    /// Returns the duration of the video in seconds, or `None` if it is unknown (e.g. a stream).
+    fn duration(&self) -> Option<f64>;
+
+    /// Returns the frame rate of the video, or `None` if it is unknown.
+    fn fps(&self) -> Option<f64>;
+}
+
+/// Opens a video file, camera or network stream with the decoder compiled in.
+///
+/// # Arguments
+///
+/// * `source` - The path, device path or URL of the video.
+///
+/// # Returns
+///
+/// A `Result` containing the decoder, or a `MyError` if the video cannot be opened, e.g. because
+/// the built-in decoder does not read its format.
+pub fn open(source: &str) -> Result<Box<dyn VideoDecoder>, MyError> {
+    #[cfg(feature = "ffmpeg_decoder")]
+    return Ok(Box::new(super::ffmpeg_decoder::FfmpegDecoder::open(
+        source,
+    )?));
+    #[cfg(all(feature = "opencv_decoder", not(feature = "ffmpeg_decoder")))]
+    return Ok(Box::new(super::opencv_decoder::OpenCvDecoder::open(
+        source,
+    )?));
+    #[cfg(not(any(feature = "opencv_decoder", feature = "ffmpeg_decoder")))]
+    Ok(Box::new(super::builtin_decoder::BuiltinDecoder::open(
+        source,
+    )?))
+}
+
+/// Returns the name of the video decoder compiled in.
+pub fn decoder_name() -> &'static str {
+    if cfg!(feature = "ffmpeg_decoder") {
+        "ffmpeg"
+    } else if cfg!(feature = "opencv_decoder") {
+        "OpenCV"
+    } else {
+        "built-in (YUV4MPEG2 and Motion JPEG AVI only)"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_missing_video() {
+        assert!(open("missing-video.mp4").is_err());
+    }
+}
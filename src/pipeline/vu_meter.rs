@@ -0,0 +1,58 @@
+//! Draws the VU meter (`--vu`): a strip along the bottom row of the frames showing the levels of
+//! the frequency bands of the audio being played, measured by the audio backend.
+//!
+//! Each band is a run of columns drawn with a block element as tall as its level, from green for
+//! quiet bands to red for loud ones.
+use super::cell_grid::CellGrid;
+
+/// Glyphs of the levels of a band, from silence to full scale.
+const LEVEL_GLYPHS: [&str; 9] = [" ", "▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+/// Colors of the bands, from quiet to loud.
+const LEVEL_COLORS: [[u8; 3]; 3] = [[40, 200, 80], [230, 200, 40], [230, 60, 40]];
+
+/// Draws the VU meter over the bottom row of a frame. Frames holding pixels are left as they are.
+///
+/// # Arguments
+///
+/// * `grid` - The frame.
+/// * `bands` - The level of each band, from 0 to 1, from the lowest frequency.
+/// * `new_lines` - Whether the text of the grid separates the rows with line breaks.
+///
+/// # Returns
+///
+/// The frame with the VU meter.
+pub fn draw_vu_meter(grid: CellGrid, bands: &[f32], new_lines: bool) -> CellGrid {
+    let (width, height) = (grid.width(), grid.height());
+    if grid.pixels().is_some() || bands.is_empty() || height == 0 {
+        return grid;
+    }
+    let mut metered = CellGrid::new(width, height, new_lines);
+    for y in 0..height - 1 {
+        grid.row(y).for_each(|cell| metered.push_cell(cell));
+    }
+    for x in 0..width as usize {
+        let level = bands[x * bands.len() / width as usize].clamp(0.0, 1.0);
+        let glyph = (level * (LEVEL_GLYPHS.len() - 1) as f32).round() as usize;
+        let color = ((level * LEVEL_COLORS.len() as f32) as usize).min(LEVEL_COLORS.len() - 1);
+        metered.push(LEVEL_GLYPHS[glyph], LEVEL_COLORS[color]);
+    }
+    metered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_vu_meter() {
+        let mut grid = CellGrid::new(4, 2, false);
+        for _ in 0..8 {
+            grid.push("#", [255; 3]);
+        }
+        let metered = draw_vu_meter(grid, &[0.0, 1.0], false);
+        let glyphs: Vec<String> = metered.row(1).map(|cell| cell.glyph.to_string()).collect();
+        assert_eq!(glyphs, [" ", " ", "█", "█"]);
+        assert!(metered.row(0).all(|cell| cell.glyph == "#"));
+        assert_eq!(metered.row(1).last().unwrap().color, LEVEL_COLORS[2]);
+    }
+}
@@ -0,0 +1,284 @@
+//! Reports what the terminal tplay runs in can do (`tplay doctor`), for bug reports: its size, its
+//! colors, the graphics protocols and the synchronized updates it supports, and whether it runs
+//! inside a terminal multiplexer.
+//!
+//! The terminal is asked itself: escape sequences querying the Kitty graphics protocol, the
+//! synchronized update mode (2026) and the device attributes (which list Sixel support) are
+//! written, and the answers read back for up to `QUERY_TIMEOUT`. All terminals answer the device
+//! attributes, so that answer ends the wait. Inside tmux, tmux answers for the outer terminal.
+//! Without answers (e.g. the output is not a terminal), only the environment tells what the
+//! terminal supports.
+use super::{colors::ColorDepth, sixel::supports_sixel};
+use crossterm::terminal;
+use std::{
+    env, fmt,
+    io::{stdin, stdout, IsTerminal, Read, Write},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the answers of the terminal.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+/// Asks whether the Kitty graphics protocol is supported, with a 1x1 image that is not shown.
+const KITTY_GRAPHICS_QUERY: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+/// The answer of the terminals supporting the Kitty graphics protocol.
+const KITTY_GRAPHICS_ANSWER: &[u8] = b"\x1b_Gi=31;OK";
+/// Asks for the state of the synchronized update mode (DECRQM).
+const SYNCHRONIZED_UPDATE_QUERY: &str = "\x1b[?2026$p";
+/// The start of the answer about the synchronized update mode, followed by its state.
+const SYNCHRONIZED_UPDATE_ANSWER: &[u8] = b"\x1b[?2026;";
+/// Asks for the primary device attributes (DA1).
+const DEVICE_ATTRIBUTES_QUERY: &str = "\x1b[c";
+/// The device attribute of the terminals supporting Sixel graphics.
+const SIXEL_ATTRIBUTE: &str = "4";
+
+/// What the terminal answered to the queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Answers {
+    /// Whether the device attributes list Sixel graphics.
+    pub sixel: bool,
+    /// Whether the Kitty graphics protocol is supported.
+    pub kitty_graphics: bool,
+    /// Whether the synchronized updates (mode 2026) are supported.
+    pub synchronized_updates: bool,
+}
+
+/// What was detected about the terminal.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// The size of the terminal (columns, rows), if known.
+    pub size: Option<(u16, u16)>,
+    /// The size of a character cell in pixels (width, height), if the terminal reports it.
+    pub cell_size: Option<(u16, u16)>,
+    /// The terminfo name of the terminal (`TERM`), if set.
+    pub term: Option<String>,
+    /// The terminal application (`TERM_PROGRAM`), if set.
+    pub program: Option<String>,
+    /// The colors the terminal can show, as detected for `--colors auto`.
+    pub color_depth: ColorDepth,
+    /// Whether the terminal is known to support Sixel graphics from its name, as detected for
+    /// `--backend auto`.
+    pub sixel_by_name: bool,
+    /// The answers of the terminal to the queries, if it answered.
+    pub answers: Option<Answers>,
+    /// The terminal multiplexer tplay runs in, if any.
+    pub multiplexer: Option<&'static str>,
+}
+
+impl Capabilities {
+    /// Detects what the terminal supports, asking the terminal if the input and the output are a
+    /// terminal.
+    pub fn detect() -> Self {
+        let window = terminal::window_size().ok();
+        let cell_size = window
+            .filter(|size| size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0)
+            .map(|size| (size.width / size.columns, size.height / size.rows));
+        let multiplexer = if env::var_os("TMUX").is_some() {
+            Some("tmux")
+        } else if env::var_os("ZELLIJ").is_some() {
+            Some("zellij")
+        } else if env::var_os("STY").is_some() {
+            Some("GNU screen")
+        } else {
+            None
+        };
+        Self {
+            size: terminal::size().ok(),
+            cell_size,
+            term: env::var("TERM").ok(),
+            program: env::var("TERM_PROGRAM").ok(),
+            color_depth: ColorDepth::Auto.resolve(),
+            sixel_by_name: supports_sixel(),
+            answers: query_terminal().and_then(|answer| parse_answers(&answer)),
+            multiplexer,
+        }
+    }
+}
+
+/// Writes the queries to the terminal and reads its answers, until it answers the device
+/// attributes or `QUERY_TIMEOUT` passes.
+///
+/// # Returns
+///
+/// The bytes the terminal answered, or `None` if the input or the output is not a terminal.
+fn query_terminal() -> Option<Vec<u8>> {
+    if cfg!(windows) || !stdin().is_terminal() || !stdout().is_terminal() {
+        return None;
+    }
+    // Without echo, and without waiting for the end of the line
+    terminal::enable_raw_mode().ok()?;
+    let (tx, rx) = mpsc::channel();
+    // The reading thread blocks until the next input, it is left behind as tplay exits
+    thread::spawn(move || {
+        let mut buffer = [0; 256];
+        while let Ok(read @ 1..) = stdin().read(&mut buffer) {
+            if tx.send(buffer[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut out = stdout();
+    let written = write!(
+        out,
+        "{KITTY_GRAPHICS_QUERY}{SYNCHRONIZED_UPDATE_QUERY}{DEVICE_ATTRIBUTES_QUERY}"
+    )
+    .and_then(|_| out.flush());
+    let mut answer = Vec::new();
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    while written.is_ok() && device_attributes(&answer).is_none() {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(bytes) => answer.extend(bytes),
+            Err(_) => break,
+        }
+    }
+    let _ = terminal::disable_raw_mode();
+    Some(answer)
+}
+
+/// Returns the primary device attributes in the answer of the terminal, e.g. `["62", "4"]` for
+/// `ESC [ ? 62 ; 4 c`.
+///
+/// # Arguments
+///
+/// * `answer` - The bytes the terminal answered.
+fn device_attributes(answer: &[u8]) -> Option<Vec<String>> {
+    (0..answer.len()).find_map(|start| {
+        let rest = answer[start..].strip_prefix(b"\x1b[?")?;
+        let length = rest
+            .iter()
+            .position(|byte| !byte.is_ascii_digit() && *byte != b';')?;
+        (rest[length] == b'c').then(|| {
+            String::from_utf8_lossy(&rest[..length])
+                .split(';')
+                .map(str::to_string)
+                .collect()
+        })
+    })
+}
+
+/// Reads the answers of the terminal to the queries.
+///
+/// # Arguments
+///
+/// * `answer` - The bytes the terminal answered.
+///
+/// # Returns
+///
+/// The answers, or `None` if the terminal did not answer the device attributes, e.g. because it
+/// answered too late.
+fn parse_answers(answer: &[u8]) -> Option<Answers> {
+    let attributes = device_attributes(answer)?;
+    let find = |pattern: &[u8]| answer.windows(pattern.len()).position(|w| w == pattern);
+    // The mode is set (1), reset (2) or permanently set (3) when supported
+    let synchronized_updates = find(SYNCHRONIZED_UPDATE_ANSWER)
+        .and_then(|start| answer.get(start + SYNCHRONIZED_UPDATE_ANSWER.len()))
+        .is_some_and(|state| matches!(state, b'1'..=b'3'));
+    Some(Answers {
+        sixel: attributes
+            .iter()
+            .any(|attribute| attribute == SIXEL_ATTRIBUTE),
+        kitty_graphics: find(KITTY_GRAPHICS_ANSWER).is_some(),
+        synchronized_updates,
+    })
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        let answered = |supported: Option<bool>| {
+            supported.map_or("unknown (the terminal did not answer)", yes_no)
+        };
+
+        writeln!(f, "Terminal")?;
+        match (self.size, self.cell_size) {
+            (Some((columns, rows)), Some((width, height))) => writeln!(
+                f,
+                "  Size:                  {columns}x{rows} cells of {width}x{height} pixels"
+            )?,
+            (Some((columns, rows)), None) => {
+                writeln!(f, "  Size:                  {columns}x{rows} cells")?
+            }
+            (None, _) => writeln!(f, "  Size:                  unknown")?,
+        }
+        writeln!(
+            f,
+            "  TERM:                  {}{}",
+            self.term.as_deref().unwrap_or("unset"),
+            self.program
+                .as_ref()
+                .map_or(String::new(), |program| format!(" ({program})"))
+        )?;
+        let colors = match self.color_depth {
+            ColorDepth::TrueColor | ColorDepth::Auto => "24-bit",
+            ColorDepth::Ansi256 => "256 colors",
+            ColorDepth::Ansi16 => "16 colors",
+        };
+        writeln!(f, "  Colors:                {colors}")?;
+        let sixel = match self.answers {
+            Some(answers) => yes_no(answers.sixel),
+            None => yes_no(self.sixel_by_name),
+        };
+        // `--backend auto` only knows the terminals by name
+        let by_name = match (self.answers, self.sixel_by_name) {
+            (Some(answers), false) if answers.sixel => {
+                " (not detected from TERM, use --backend sixel)"
+            }
+            (None, _) => " (from TERM)",
+            _ => "",
+        };
+        writeln!(f, "  Sixel:                 {sixel}{by_name}")?;
+        writeln!(
+            f,
+            "  Kitty graphics:        {}",
+            answered(self.answers.map(|answers| answers.kitty_graphics))
+        )?;
+        writeln!(
+            f,
+            "  Synchronized updates:  {}",
+            answered(self.answers.map(|answers| answers.synchronized_updates))
+        )?;
+        writeln!(
+            f,
+            "  Multiplexer:           {}",
+            self.multiplexer.unwrap_or("none")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_answers() {
+        // A terminal with every capability
+        let answer = b"\x1b_Gi=31;OK\x1b\\\x1b[?2026;2$y\x1b[?62;4;22c";
+        assert_eq!(
+            parse_answers(answer),
+            Some(Answers {
+                sixel: true,
+                kitty_graphics: true,
+                synchronized_updates: true,
+            })
+        );
+
+        // A terminal without them, not knowing the mode (0)
+        let answer = b"\x1b[?2026;0$y\x1b[?1;2c";
+        assert_eq!(
+            parse_answers(answer),
+            Some(Answers {
+                sixel: false,
+                kitty_graphics: false,
+                synchronized_updates: false,
+            })
+        );
+        assert_eq!(
+            device_attributes(b"\x1b[?1;2c"),
+            Some(vec!["1".into(), "2".into()])
+        );
+
+        // No answer to the device attributes
+        assert_eq!(parse_answers(b"\x1b[?2026;2$y"), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Edits the current character map during playback (`e` key), showing the effect live on the
+//! paused frame: the glyphs can be inserted, removed and moved, then the character map is saved to
+//! the custom character maps file (`char_maps.conf`), or the edit is canceled.
+//!
+//! The keys of the editor are:
+//! - `Left` / `Right`: move the cursor between the glyphs.
+//! - any character: insert it at the cursor.
+//! - `Backspace` / `Delete`: remove the glyph before / after the cursor.
+//! - `Ctrl` (or `Shift`) with `Left` / `Right`: move the glyph before the cursor left or right.
+//! - `Enter`: save the character map, `Esc`: restore it as it was.
+//!
+//! A character map can also be typed from scratch on the command line, e.g. `:charmap  .:oO@`
+//! (see `typed_glyphs`), the frames being rendered with it as it is typed.
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What a key pressed in the editor did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorAction {
+    /// The glyphs changed, the frames are rendered with the new ones.
+    Changed,
+    /// The cursor moved, the glyphs are the same.
+    Moved,
+    /// The edit is to be saved and the editor closed.
+    Save,
+    /// The edit is canceled, the original glyphs are rendered again.
+    Cancel,
+}
+
+/// The state of the character map editor.
+#[derive(Debug, Clone)]
+pub struct CharMapEditor {
+    /// The index of the character map edited, as selected with the character map keys.
+    pub index: u32,
+    /// The name the character map is saved under.
+    pub name: String,
+    /// The glyphs of the character map when the editor was opened.
+    pub original: Vec<String>,
+    /// The glyphs being edited, from darkest to brightest.
+    pub glyphs: Vec<String>,
+    /// The position of the cursor, between the glyphs (0 before the first one).
+    cursor: usize,
+    /// Whether the playback was paused when the editor was opened, to resume it when it closes.
+    pub resume: bool,
+}
+
+impl CharMapEditor {
+    /// Opens the editor on a character map, with the cursor after its last glyph.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the character map.
+    /// * `name` - The name the character map is saved under.
+    /// * `glyphs` - The glyphs of the character map.
+    /// * `resume` - Whether to resume the playback when the editor closes.
+    pub fn new(index: u32, name: &str, glyphs: Vec<String>, resume: bool) -> Self {
+        Self {
+            index,
+            name: name.to_string(),
+            cursor: glyphs.len(),
+            original: glyphs.clone(),
+            glyphs,
+            resume,
+        }
+    }
+
+    /// Edits the glyphs with a key. A character map is never left empty: removing its last glyph
+    /// does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key pressed.
+    ///
+    /// # Returns
+    ///
+    /// What the key did, or `None` if it does nothing in the editor.
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<EditorAction> {
+        let moves_glyph = key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Left if moves_glyph => {
+                if self.cursor < 2 {
+                    return None;
+                }
+                self.glyphs.swap(self.cursor - 2, self.cursor - 1);
+                self.cursor -= 1;
+                Some(EditorAction::Changed)
+            }
+            KeyCode::Right if moves_glyph => {
+                if self.cursor == 0 || self.cursor >= self.glyphs.len() {
+                    return None;
+                }
+                self.glyphs.swap(self.cursor - 1, self.cursor);
+                self.cursor += 1;
+                Some(EditorAction::Changed)
+            }
+            KeyCode::Left if self.cursor > 0 => {
+                self.cursor -= 1;
+                Some(EditorAction::Moved)
+            }
+            KeyCode::Right if self.cursor < self.glyphs.len() => {
+                self.cursor += 1;
+                Some(EditorAction::Moved)
+            }
+            KeyCode::Home => {
+                self.cursor = 0;
+                Some(EditorAction::Moved)
+            }
+            KeyCode::End => {
+                self.cursor = self.glyphs.len();
+                Some(EditorAction::Moved)
+            }
+            KeyCode::Backspace if self.cursor > 0 && self.glyphs.len() > 1 => {
+                self.cursor -= 1;
+                self.glyphs.remove(self.cursor);
+                Some(EditorAction::Changed)
+            }
+            KeyCode::Delete if self.cursor < self.glyphs.len() && self.glyphs.len() > 1 => {
+                self.glyphs.remove(self.cursor);
+                Some(EditorAction::Changed)
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.glyphs.insert(self.cursor, c.to_string());
+                self.cursor += 1;
+                Some(EditorAction::Changed)
+            }
+            KeyCode::Enter => Some(EditorAction::Save),
+            KeyCode::Esc => {
+                self.glyphs = self.original.clone();
+                Some(EditorAction::Cancel)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the text of the editor box: the name of the character map, its glyphs with the
+    /// cursor (spaces shown as `␣`), and the keys.
+    pub fn lines(&self) -> Vec<String> {
+        let shown = |glyphs: &[String]| -> String {
+            glyphs
+                .iter()
+                .map(|glyph| if glyph == " " { "␣" } else { glyph.as_str() })
+                .collect()
+        };
+        vec![
+            format!("Edit character map: {}", self.name),
+            String::new(),
+            format!(
+                "{}│{}",
+                shown(&self.glyphs[..self.cursor]),
+                shown(&self.glyphs[self.cursor..])
+            ),
+            String::new(),
+            "←/→ move  ctrl+←/→ move glyph  type to insert".to_string(),
+            "backspace/del remove  enter save  esc cancel".to_string(),
+        ]
+    }
+}
+
+/// Returns the glyphs of a character map typed on the command line: `charmap ` followed by the
+/// glyphs from darkest to brightest, optionally quoted (e.g. `charmap " .:oO@"`), since the
+/// darkest glyph is often a space.
+///
+/// # Arguments
+///
+/// * `line` - The command line, without the `:`, as typed so far.
+///
+/// # Returns
+///
+/// The glyphs, or `None` if the line is not a `charmap` command or has no glyphs yet.
+pub fn typed_glyphs(line: &str) -> Option<&str> {
+    let glyphs = line.strip_prefix("charmap ")?;
+    // The closing quote may not be typed yet
+    let glyphs = match glyphs.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"').unwrap_or(quoted),
+        None => glyphs,
+    };
+    (!glyphs.is_empty()).then_some(glyphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::char_maps::split_glyphs;
+
+    #[test]
+    fn test_char_map_editor() {
+        let key = |code: KeyCode| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut editor = CharMapEditor::new(1, "dots", split_glyphs(" .o"), true);
+        assert_eq!(editor.lines()[2], "␣.o│");
+        assert_eq!(
+            editor.handle_key(&key(KeyCode::Char('@'))),
+            Some(EditorAction::Changed)
+        );
+        editor.handle_key(&key(KeyCode::Left));
+        editor.handle_key(&key(KeyCode::Left));
+        assert_eq!(editor.lines()[2], "␣.│o@");
+        // Move the `.` right, past the `o`
+        editor.handle_key(&KeyEvent::new(KeyCode::Right, KeyModifiers::CONTROL));
+        assert_eq!(editor.glyphs, split_glyphs(" o.@"));
+        editor.handle_key(&key(KeyCode::Backspace));
+        editor.handle_key(&key(KeyCode::Delete));
+        assert_eq!(editor.glyphs, split_glyphs(" o"));
+        assert_eq!(
+            editor.handle_key(&key(KeyCode::Enter)),
+            Some(EditorAction::Save)
+        );
+
+        // The last glyph cannot be removed, and canceling restores the glyphs
+        editor.handle_key(&key(KeyCode::Home));
+        editor.handle_key(&key(KeyCode::Delete));
+        assert_eq!(editor.handle_key(&key(KeyCode::Delete)), None);
+        assert_eq!(editor.glyphs, ["o"]);
+        assert_eq!(
+            editor.handle_key(&key(KeyCode::Esc)),
+            Some(EditorAction::Cancel)
+        );
+        assert_eq!(editor.glyphs, split_glyphs(" .o"));
+
+        assert_eq!(typed_glyphs("charmap  .:oO@"), Some(" .:oO@"));
+        assert_eq!(typed_glyphs("charmap \" .o\""), Some(" .o"));
+        assert_eq!(typed_glyphs("charmap \" ."), Some(" ."));
+        assert_eq!(typed_glyphs("charmap \""), None);
+        assert_eq!(typed_glyphs("sleep 20m"), None);
+    }
+}
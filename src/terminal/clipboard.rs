@@ -0,0 +1,116 @@
+//! Copies text to the clipboard, e.g. the frame on screen with the `y` key.
+//!
+//! On a local session, the text is handed to the clipboard tool of the platform: `pbcopy` on
+//! macOS, `clip.exe` on Windows, and `wl-copy`, `xclip` or `xsel` on Linux and the BSDs. Over SSH,
+//! or without any of these tools, the text is sent to the terminal in an OSC 52 escape sequence
+//! instead, which most terminals copy to the clipboard of the machine they run on (some need it
+//! enabled in their settings). Inside tmux, the sequence is passed through to the outer terminal.
+use crate::common::errors::*;
+use base64::Engine;
+use std::{
+    env,
+    io::{stdout, Write},
+    process::{Command, Stdio},
+};
+
+/// The clipboard tools tried in order, with their arguments.
+#[cfg(target_vendor = "apple")]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+/// The clipboard tools tried in order, with their arguments.
+#[cfg(windows)]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[("clip.exe", &[])];
+/// The clipboard tools tried in order, with their arguments.
+#[cfg(not(any(target_vendor = "apple", windows)))]
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Copies text to the clipboard.
+///
+/// # Arguments
+///
+/// * `text` - The text to copy.
+///
+/// # Returns
+///
+/// A `Result` containing how the text was copied: the name of the clipboard tool, or `OSC 52`.
+///
+/// # Errors
+///
+/// Returns an error if the escape sequence cannot be written to the terminal.
+pub fn copy(text: &str) -> Result<&'static str, MyError> {
+    let remote = env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+    if !remote {
+        if let Some(tool) = CLIPBOARD_TOOLS
+            .iter()
+            .find(|(tool, arguments)| copy_with(tool, arguments, text))
+        {
+            return Ok(tool.0);
+        }
+    }
+    let sequence = osc52(text, env::var_os("TMUX").is_some());
+    let mut out = stdout();
+    out.write_all(sequence.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| MyError::Application(format!("{ERROR_CLIPBOARD}: {e}")))?;
+    Ok("OSC 52")
+}
+
+/// Pipes text to a clipboard tool.
+///
+/// # Arguments
+///
+/// * `tool` - The command of the tool.
+/// * `arguments` - The arguments of the tool.
+/// * `text` - The text to copy.
+///
+/// # Returns
+///
+/// Whether the tool copied the text, `false` if it is not installed or failed.
+fn copy_with(tool: &str, arguments: &[&str], text: &str) -> bool {
+    let child = Command::new(tool)
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    // Dropping the input closes it, so that the tool reads the end of the text
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut input| input.write_all(text.as_bytes()).is_ok());
+    let status = child.wait();
+    written && status.is_ok_and(|status| status.success())
+}
+
+/// Returns the OSC 52 escape sequence setting the clipboard to a text.
+///
+/// # Arguments
+///
+/// * `text` - The text to copy.
+/// * `tmux` - Whether to wrap the sequence for tmux to pass it through to the outer terminal.
+fn osc52(text: &str, tmux: bool) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let sequence = format!("\x1b]52;c;{encoded}\x07");
+    match tmux {
+        // The escape characters of the wrapped sequence are doubled
+        true => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+        false => sequence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(osc52("ab", false), "\x1b]52;c;YWI=\x07");
+        assert_eq!(osc52("ab", true), "\x1bPtmux;\x1b\x1b]52;c;YWI=\x07\x1b\\");
+    }
+}
@@ -0,0 +1,275 @@
+//! Reduces the colors of the frames to what the terminal can show.
+//!
+//! Frames are rendered in 24-bit colors, which most terminals support. Others only have the
+//! 256-color palette of xterm or the 16 ANSI colors, and show 24-bit escape sequences as wrong
+//! colors or garbage: for them, each color is replaced by the closest color of their palette. The
+//! color depth of the terminal is detected from `COLORTERM` and from its terminfo name (`TERM`).
+use crossterm::style::Color;
+use std::{env, str::FromStr};
+
+/// Levels of each channel in the 6x6x6 color cube of the 256-color palette (colors 16 to 231).
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+/// First color of the gray ramp of the 256-color palette (colors 232 to 255).
+const GRAY_RAMP_START: u8 = 232;
+/// Terminals known to support 24-bit colors without advertising it in `COLORTERM`, by their
+/// `TERM` prefix.
+const TRUECOLOR_TERMINALS: [&str; 5] = ["xterm-kitty", "alacritty", "wezterm", "foot", "contour"];
+/// The 16 ANSI colors, with their usual RGB values (those of xterm).
+const ANSI_COLORS: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::DarkRed, [205, 0, 0]),
+    (Color::DarkGreen, [0, 205, 0]),
+    (Color::DarkYellow, [205, 205, 0]),
+    (Color::DarkBlue, [0, 0, 238]),
+    (Color::DarkMagenta, [205, 0, 205]),
+    (Color::DarkCyan, [0, 205, 205]),
+    (Color::Grey, [229, 229, 229]),
+    (Color::DarkGrey, [127, 127, 127]),
+    (Color::Red, [255, 0, 0]),
+    (Color::Green, [0, 255, 0]),
+    (Color::Yellow, [255, 255, 0]),
+    (Color::Blue, [92, 92, 255]),
+    (Color::Magenta, [255, 0, 255]),
+    (Color::Cyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// The colors a terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorDepth {
+    /// Any 24-bit RGB color.
+    TrueColor,
+    /// The 256-color palette of xterm.
+    Ansi256,
+    /// The 16 ANSI colors.
+    Ansi16,
+    /// Detected from the environment of the terminal.
+    #[default]
+    Auto,
+}
+
+impl FromStr for ColorDepth {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" | "24-bit" => Ok(ColorDepth::TrueColor),
+            "256" | "ansi256" => Ok(ColorDepth::Ansi256),
+            "16" | "ansi16" | "ansi" => Ok(ColorDepth::Ansi16),
+            "auto" => Ok(ColorDepth::Auto),
+            _ => Err(format!(
+                "`{name}` is not a color depth (truecolor, 256, 16, auto)"
+            )),
+        }
+    }
+}
+
+impl ColorDepth {
+    /// Returns the color depth to use: `Auto` is replaced by the color depth of the terminal.
+    pub fn resolve(self) -> Self {
+        match self {
+            ColorDepth::Auto => detect_color_depth(
+                env::var("COLORTERM").ok().as_deref(),
+                env::var("TERM").ok().as_deref(),
+                env::var_os("WT_SESSION").is_some(),
+            ),
+            depth => depth,
+        }
+    }
+
+    /// Returns the color of the terminal closest to an RGB color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The RGB color.
+    pub fn color(self, (r, g, b): (u8, u8, u8)) -> Color {
+        match self {
+            ColorDepth::TrueColor | ColorDepth::Auto => Color::Rgb { r, g, b },
+            ColorDepth::Ansi256 => Color::AnsiValue(ansi256_index([r, g, b])),
+            ColorDepth::Ansi16 => ansi16_color([r, g, b]),
+        }
+    }
+
+    /// Returns the color of the terminal closest to any color: RGB colors are reduced like with
+    /// `color`, the palette colors are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The color.
+    pub fn reduce(self, color: Color) -> Color {
+        match color {
+            Color::Rgb { r, g, b } => self.color((r, g, b)),
+            color => color,
+        }
+    }
+}
+
+/// Parses a color given on the command line: the name of one of the 16 ANSI colors (e.g. `green`
+/// or `dark-green`), `default` for the color of the terminal, or an RGB color as `#rrggbb`.
+///
+/// # Arguments
+///
+/// * `name` - The color as given on the command line.
+///
+/// # Returns
+///
+/// A `Result` containing the color, or an error message if it is unknown.
+pub fn parse_color(name: &str) -> Result<Color, String> {
+    let error = || format!("`{name}` is not a color (e.g. green, dark-green, #33ff66, default)");
+    let lowercase = name.trim().to_ascii_lowercase().replace('-', "_");
+    if let Some(hex) = lowercase.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16).map_err(|_| error())?;
+        if hex.len() != 6 {
+            return Err(error());
+        }
+        let [_, r, g, b] = value.to_be_bytes();
+        return Ok(Color::Rgb { r, g, b });
+    }
+    match lowercase.as_str() {
+        "default" => Ok(Color::Reset),
+        "gray" => Ok(Color::Grey),
+        "dark_gray" => Ok(Color::DarkGrey),
+        "reset" => Err(error()),
+        name => Color::try_from(name).map_err(|_| error()),
+    }
+}
+
+/// Returns the color depth of a terminal.
+///
+/// # Arguments
+///
+/// * `colorterm` - The color support advertised by the terminal (`COLORTERM`), if set.
+/// * `term` - The terminfo name of the terminal (`TERM`), if set. Without it (e.g. in Windows
+///   consoles), 24-bit colors are assumed.
+/// * `is_windows_terminal` - Whether the terminal is Windows Terminal (`WT_SESSION` is set),
+///   which supports 24-bit colors without setting `COLORTERM`.
+fn detect_color_depth(
+    colorterm: Option<&str>,
+    term: Option<&str>,
+    is_windows_terminal: bool,
+) -> ColorDepth {
+    let Some(term) = term else {
+        return ColorDepth::TrueColor;
+    };
+    if matches!(colorterm, Some("truecolor" | "24bit"))
+        || is_windows_terminal
+        || term.ends_with("-direct")
+        || TRUECOLOR_TERMINALS
+            .iter()
+            .any(|name| term.starts_with(name))
+    {
+        ColorDepth::TrueColor
+    } else if term.contains("256color") {
+        ColorDepth::Ansi256
+    } else {
+        ColorDepth::Ansi16
+    }
+}
+
+/// Returns the squared distance between two colors.
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (*a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Returns the index of the color of the 256-color palette closest to an RGB color: the closest
+/// color of the color cube, or of the gray ramp if it is closer.
+///
+/// # Arguments
+///
+/// * `color` - The RGB color.
+fn ansi256_index(color: [u8; 3]) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - channel as i32).abs())
+            .unwrap_or(0)
+    };
+    let levels = color.map(level);
+    let cube = levels.map(|i| CUBE_LEVELS[i]);
+    let cube_index = 16 + 36 * levels[0] + 6 * levels[1] + levels[2];
+
+    // The ramp goes from 8 to 238 in steps of 10
+    let average = color.iter().map(|c| *c as u32).sum::<u32>() / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + step * 10;
+    if distance(color, [gray; 3]) < distance(color, cube) {
+        GRAY_RAMP_START + step
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Returns the ANSI color closest to an RGB color.
+///
+/// # Arguments
+///
+/// * `color` - The RGB color.
+fn ansi16_color(color: [u8; 3]) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(color, *rgb))
+        .map_or(Color::White, |(ansi, _)| *ansi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_color_depth() {
+        let detect = |colorterm, term| detect_color_depth(colorterm, term, false);
+        assert_eq!(
+            detect(Some("truecolor"), Some("xterm-256color")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(detect(None, Some("xterm-kitty")), ColorDepth::TrueColor);
+        assert_eq!(detect(None, Some("screen-256color")), ColorDepth::Ansi256);
+        assert_eq!(detect(None, Some("linux")), ColorDepth::Ansi16);
+        assert_eq!(detect(None, None), ColorDepth::TrueColor);
+        assert_eq!(
+            detect_color_depth(None, Some("xterm"), true),
+            ColorDepth::TrueColor
+        );
+        assert_eq!("256".parse(), Ok(ColorDepth::Ansi256));
+        assert!("65536".parse::<ColorDepth>().is_err());
+    }
+
+    #[test]
+    fn test_quantize() {
+        let depth = ColorDepth::Ansi256;
+        assert_eq!(depth.color((255, 0, 0)), Color::AnsiValue(196));
+        assert_eq!(depth.color((0, 0, 0)), Color::AnsiValue(16));
+        assert_eq!(depth.color((128, 128, 128)), Color::AnsiValue(244));
+        assert_eq!(depth.color((90, 140, 210)), Color::AnsiValue(68));
+
+        let depth = ColorDepth::Ansi16;
+        assert_eq!(depth.color((250, 10, 20)), Color::Red);
+        assert_eq!(depth.color((20, 20, 30)), Color::Black);
+        assert_eq!(depth.color((120, 130, 125)), Color::DarkGrey);
+        assert_eq!(
+            ColorDepth::TrueColor.color((1, 2, 3)),
+            Color::Rgb { r: 1, g: 2, b: 3 }
+        );
+        assert_eq!(depth.reduce(Color::DarkGreen), Color::DarkGreen);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("green"), Ok(Color::Green));
+        assert_eq!(parse_color("Dark-Green"), Ok(Color::DarkGreen));
+        assert_eq!(parse_color("gray"), Ok(Color::Grey));
+        assert_eq!(parse_color("default"), Ok(Color::Reset));
+        assert_eq!(
+            parse_color("#33ff66"),
+            Ok(Color::Rgb {
+                r: 0x33,
+                g: 0xff,
+                b: 0x66
+            })
+        );
+        assert!(parse_color("#3f6").is_err());
+        assert!(parse_color("phosphor").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Finds the cells of a frame that differ from the frame printed before it, so that only those are
+//! printed again (differential rendering).
+//!
+//! Reprinting every cell of every frame sends the whole colored text of the frame to the terminal,
+//! although consecutive frames of a video often differ in a small part of the screen. Instead, the
+//! terminal keeps the last frame it printed, and prints the runs of changed cells of each row after
+//! moving the cursor to them. Runs separated by a few unchanged cells are merged, as reprinting
+//! these cells costs less than moving the cursor again.
+//!
+//! The last frame is only valid as long as the screen still shows it: the terminal forgets it
+//! whenever something else is printed over the frame, or when the screen is cleared or resized.
+use crate::CellGrid;
+use std::ops::Range;
+
+/// Fewest unchanged cells between two runs of changed cells for them to be printed separately,
+/// about the length of the escape sequence moving the cursor.
+const MIN_GAP: u32 = 8;
+
+/// A frame printed on the screen, and how it was printed.
+pub struct PrintedFrame {
+    /// The cells of the frame.
+    grid: CellGrid,
+    /// The column and row of its top left corner.
+    origin: (u16, u16),
+    /// What decides the colors the cells were printed with: the grayscale mode and the theme.
+    style: (bool, usize),
+}
+
+impl PrintedFrame {
+    /// Keeps a frame just printed.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - The cells of the frame.
+    /// * `origin` - The column and row of its top left corner.
+    /// * `style` - The grayscale mode and the index of the theme it was printed with.
+    pub fn new(grid: CellGrid, origin: (u16, u16), style: (bool, usize)) -> Self {
+        Self {
+            grid,
+            origin,
+            style,
+        }
+    }
+
+    /// Returns whether a frame can be printed over this one by printing only the cells that
+    /// changed: it has the same size and position, and is printed the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The next frame.
+    /// * `origin` - The column and row of its top left corner.
+    /// * `style` - The grayscale mode and the index of the theme it is printed with.
+    pub fn matches(&self, frame: &CellGrid, origin: (u16, u16), style: (bool, usize)) -> bool {
+        self.origin == origin
+            && self.style == style
+            && self.grid.width() == frame.width()
+            && self.grid.height() == frame.height()
+            && self.grid.len() == frame.len()
+            && frame.pixels().is_none()
+    }
+
+    /// Returns the runs of cells of a row that changed since this frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The next frame, which this frame `matches`.
+    /// * `y` - The row.
+    ///
+    /// # Returns
+    ///
+    /// The columns of each run, from left to right.
+    pub fn changed_spans(&self, frame: &CellGrid, y: u32) -> Vec<Range<u32>> {
+        let mut spans: Vec<Range<u32>> = Vec::new();
+        for (x, (before, after)) in self.grid.row(y).zip(frame.row(y)).enumerate() {
+            if before == after {
+                continue;
+            }
+            let x = x as u32;
+            match spans.last_mut() {
+                Some(span) if x - span.end < MIN_GAP => span.end = x + 1,
+                _ => spans.push(x..x + 1),
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> CellGrid {
+        let mut grid = CellGrid::new(rows[0].len() as u32, rows.len() as u32, false);
+        for row in rows {
+            for c in row.chars() {
+                grid.push(&c.to_string(), [255; 3]);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_changed_spans() {
+        let previous = PrintedFrame::new(
+            grid(&["....................", "...................."]),
+            (0, 0),
+            (false, 0),
+        );
+        let frame = grid(&["#..#...............#", "...................."]);
+        assert!(previous.matches(&frame, (0, 0), (false, 0)));
+        assert!(!previous.matches(&frame, (1, 0), (false, 0)));
+        assert!(!previous.matches(&frame, (0, 0), (true, 0)));
+        assert_eq!(previous.changed_spans(&frame, 0), [0..4, 19..20]);
+        assert!(previous.changed_spans(&frame, 1).is_empty());
+
+        // A change of color alone is a change
+        let mut recolored = CellGrid::new(20, 2, false);
+        for i in 0..40 {
+            recolored.push(".", if i == 5 { [0; 3] } else { [255; 3] });
+        }
+        let changed = 5..6;
+        assert_eq!(previous.changed_spans(&recolored, 0), [changed]);
+    }
+}
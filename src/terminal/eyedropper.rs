@@ -0,0 +1,109 @@
+//! Reads the colors of the frames under the mouse with the `i` key (an eyedropper): while it is
+//! on, the status bar shows the pixel of the media the hovered cell was rendered from and its
+//! color, e.g. to read the values of an asciified plot.
+//!
+//! The cells are traced back to the pixels through the area of the frame showing the media (see
+//! `SourceArea`). When the frame cannot be traced back (e.g. rotated frames, 360° media or the
+//! scene index), the color of the cell itself is shown instead.
+use crate::{display::SourceArea, pipeline::glyph_width, CellGrid};
+use image::{DynamicImage, GenericImageView};
+
+/// Returns the cell of a frame under a position on screen, the glyphs spanning several columns
+/// (e.g. emoji) covering all of them.
+///
+/// # Arguments
+///
+/// * `frame` - The frame on screen.
+/// * `offset` - The column and row of the position, from the top left corner of the frame.
+///
+/// # Returns
+///
+/// The column and row of the cell, or `None` if the position is outside the frame.
+pub fn cell_at(frame: &CellGrid, offset: (u16, u16)) -> Option<(u32, u32)> {
+    let (column, row) = (u32::from(offset.0), u32::from(offset.1));
+    if row >= frame.height() {
+        return None;
+    }
+    let mut left = 0;
+    for (x, cell) in frame.row(row).enumerate() {
+        left += glyph_width::width(cell.glyph);
+        if column < left {
+            return Some((x as u32, row));
+        }
+    }
+    None
+}
+
+/// Describes the color under a cell of a frame, for the status bar.
+///
+/// # Arguments
+///
+/// * `frame` - The frame on screen.
+/// * `cell` - The column and row of the cell.
+/// * `source` - The frame of the media the frame was rendered from, if any.
+/// * `area` - The cells of the frame showing the frame of the media, if known.
+///
+/// # Returns
+///
+/// The pixel and its color (e.g. `x 120 y 45  #ff8800 (255, 136, 0)`), the cell and its color if
+/// it cannot be traced back to a pixel, or `None` if the cell is outside the frame.
+pub fn describe(
+    frame: &CellGrid,
+    cell: (u32, u32),
+    source: Option<&DynamicImage>,
+    area: Option<SourceArea>,
+) -> Option<String> {
+    let pixel = source.zip(area).and_then(|(source, area)| {
+        let (x, y) = area.pixel_at(cell, source.dimensions())?;
+        let [r, g, b, _] = source.get_pixel(x, y).0;
+        Some((format!("x {x} y {y}"), [r, g, b]))
+    });
+    let (position, [r, g, b]) = match pixel {
+        Some(pixel) => pixel,
+        None => {
+            let color = frame.cell(cell.0, cell.1)?.color;
+            (format!("cell {} {}", cell.0, cell.1), color)
+        }
+    };
+    Some(format!(
+        "{position}  #{r:02x}{g:02x}{b:02x} ({r}, {g}, {b})"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_describe() {
+        let mut frame = CellGrid::new(4, 2, false);
+        for glyph in ["a", "😀", "b", "c", " ", " ", " ", " "] {
+            frame.push(glyph, [1, 2, 3]);
+        }
+        assert_eq!(cell_at(&frame, (2, 0)), Some((1, 0)));
+        assert_eq!(cell_at(&frame, (3, 0)), Some((2, 0)));
+        assert_eq!(cell_at(&frame, (5, 0)), None);
+        assert_eq!(cell_at(&frame, (0, 2)), None);
+
+        // The image is pillarboxed in the middle two columns
+        let mut image = RgbImage::new(20, 10);
+        image.put_pixel(15, 7, Rgb([255, 136, 0]));
+        let source = DynamicImage::ImageRgb8(image);
+        let area = SourceArea {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+        assert_eq!(
+            describe(&frame, (2, 1), Some(&source), Some(area)).as_deref(),
+            Some("x 15 y 7  #ff8800 (255, 136, 0)")
+        );
+        assert_eq!(
+            describe(&frame, (0, 0), Some(&source), Some(area)).as_deref(),
+            Some("cell 0 0  #010203 (1, 2, 3)")
+        );
+        assert_eq!(describe(&frame, (4, 0), None, None), None);
+    }
+}
@@ -0,0 +1,432 @@
+//! The keys bound to the playback commands, which users can change in a keymap file.
+//!
+//! The keymap file is read from `keymap.conf` in the configuration directory (e.g.
+//! `~/.config/tplay/keymap.conf` on Linux), or from the file given with `--keymap`. Each line binds
+//! a key to an action, overriding the default binding of the key:
+//!
+//! ```text
+//! # Pause with p, seek with j and l, and free the space bar
+//! p = pause
+//! j = seek_backward
+//! l = seek_forward
+//! space = none
+//! ```
+//!
+//! Keys are single characters, names (`space`, `tab`, `esc`, `enter`, `left`, `right`, `up`,
+//! `down`, `pageup`, `pagedown`, `home`, `end`, `backspace`, `f1` to `f12`), optionally prefixed
+//! with `ctrl+`. Letters bound in lowercase also match their uppercase.
+use crate::common::errors::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Name of the keymap file, in the configuration directory.
+pub const KEYMAP_FILE_NAME: &str = "keymap.conf";
+/// How far the seek actions move the playback, in seconds.
+pub const SEEK_STEP: f64 = 10.0;
+/// How much the volume actions change the volume, from 0 to 1.
+pub const VOLUME_STEP: f64 = 0.1;
+
+/// A playback command that keys can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    Help,
+    Stats,
+    CommandLine,
+    SceneIndex,
+    Subtitles,
+    NextTrack,
+    PreviousTrack,
+    SeekBackward,
+    SeekForward,
+    SeekBar,
+    Eyedropper,
+    CharMap(u32),
+    NextCharMap,
+    PreviousCharMap,
+    Grayscale,
+    Theme,
+    Mute,
+    VolumeUp,
+    VolumeDown,
+    Braille,
+    Dither,
+    Luma,
+    Background,
+    Screenshot,
+    Copy,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate,
+    EditCharMap,
+    Refresh,
+    Suspend,
+}
+
+/// The actions that do not take a character map, with their names in keymap files.
+const ACTION_NAMES: [(Action, &str); 32] = [
+    (Action::Quit, "quit"),
+    (Action::Pause, "pause"),
+    (Action::Help, "help"),
+    (Action::Stats, "stats"),
+    (Action::CommandLine, "command_line"),
+    (Action::SceneIndex, "scene_index"),
+    (Action::Subtitles, "subtitles"),
+    (Action::NextTrack, "next_track"),
+    (Action::PreviousTrack, "previous_track"),
+    (Action::SeekBackward, "seek_backward"),
+    (Action::SeekForward, "seek_forward"),
+    (Action::SeekBar, "seek_bar"),
+    (Action::Eyedropper, "eyedropper"),
+    (Action::NextCharMap, "next_char_map"),
+    (Action::PreviousCharMap, "previous_char_map"),
+    (Action::Grayscale, "grayscale"),
+    (Action::Theme, "theme"),
+    (Action::Mute, "mute"),
+    (Action::VolumeUp, "volume_up"),
+    (Action::VolumeDown, "volume_down"),
+    (Action::Braille, "braille"),
+    (Action::Dither, "dither"),
+    (Action::Luma, "luma"),
+    (Action::Background, "background"),
+    (Action::Screenshot, "screenshot"),
+    (Action::Copy, "copy"),
+    (Action::FlipHorizontal, "flip_horizontal"),
+    (Action::FlipVertical, "flip_vertical"),
+    (Action::Rotate, "rotate"),
+    (Action::EditCharMap, "edit_char_map"),
+    (Action::Refresh, "refresh"),
+    (Action::Suspend, "suspend"),
+];
+
+/// The keys with a name, and their names in keymap files.
+const KEY_NAMES: [(KeyCode, &str); 13] = [
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::Esc, "esc"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::Backspace, "backspace"),
+];
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(index) = name.strip_prefix("char_map_") {
+            if let Ok(index) = index.parse() {
+                return Ok(Action::CharMap(index));
+            }
+        }
+        ACTION_NAMES
+            .iter()
+            .find(|(_, action_name)| *action_name == name)
+            .map(|(action, _)| *action)
+            .ok_or_else(|| format!("`{name}` is not an action"))
+    }
+}
+
+/// A key, with or without the Control modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// The key pressed.
+    pub code: KeyCode,
+    /// Whether Control is held.
+    pub control: bool,
+}
+
+impl Key {
+    /// A key pressed without Control.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The key.
+    const fn plain(code: KeyCode) -> Self {
+        Self {
+            code,
+            control: false,
+        }
+    }
+}
+
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || format!("`{text}` is not a key (e.g. q, space, left, ctrl+c)");
+        let trimmed = text.trim();
+        let (name, control) = match trimmed.to_ascii_lowercase().starts_with("ctrl+") {
+            true => (&trimmed["ctrl+".len()..], true),
+            false => (trimmed, false),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = name.to_ascii_lowercase();
+                let named = KEY_NAMES.iter().find(|(_, key_name)| *key_name == name);
+                match (named, name.strip_prefix('f').map(str::parse::<u8>)) {
+                    (Some((code, _)), _) => *code,
+                    (None, Some(Ok(number @ 1..=12))) => KeyCode::F(number),
+                    _ => return Err(error()),
+                }
+            }
+        };
+        Ok(Self { code, control })
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.control {
+            write!(f, "ctrl+")?;
+        }
+        match KEY_NAMES.iter().find(|(code, _)| *code == self.code) {
+            Some((_, name)) => write!(f, "{name}"),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{c}"),
+                KeyCode::F(number) => write!(f, "f{number}"),
+                code => write!(f, "{code:?}"),
+            },
+        }
+    }
+}
+
+/// The keys bound to each action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        for (code, action) in [
+            (KeyCode::Char('q'), Action::Quit),
+            (KeyCode::Esc, Action::Quit),
+            (KeyCode::Char(' '), Action::Pause),
+            (KeyCode::Char('?'), Action::Help),
+            (KeyCode::Char('h'), Action::Help),
+            (KeyCode::Char('f'), Action::Stats),
+            (KeyCode::Char(':'), Action::CommandLine),
+            (KeyCode::Tab, Action::SceneIndex),
+            (KeyCode::Char('j'), Action::Subtitles),
+            (KeyCode::Char('n'), Action::NextTrack),
+            (KeyCode::Char('p'), Action::PreviousTrack),
+            (KeyCode::Left, Action::SeekBackward),
+            (KeyCode::Right, Action::SeekForward),
+            (KeyCode::Char('o'), Action::SeekBar),
+            (KeyCode::Char('i'), Action::Eyedropper),
+            (KeyCode::Char(']'), Action::NextCharMap),
+            (KeyCode::Char('['), Action::PreviousCharMap),
+            (KeyCode::Char('g'), Action::Grayscale),
+            (KeyCode::Char('t'), Action::Theme),
+            (KeyCode::Char('m'), Action::Mute),
+            (KeyCode::Char('+'), Action::VolumeUp),
+            (KeyCode::Char('='), Action::VolumeUp),
+            (KeyCode::Char('-'), Action::VolumeDown),
+            (KeyCode::Char('b'), Action::Braille),
+            (KeyCode::Char('d'), Action::Dither),
+            (KeyCode::Char('l'), Action::Luma),
+            (KeyCode::Char('x'), Action::Background),
+            (KeyCode::Char('s'), Action::Screenshot),
+            (KeyCode::Char('y'), Action::Copy),
+            (KeyCode::Char('|'), Action::FlipHorizontal),
+            (KeyCode::Char('_'), Action::FlipVertical),
+            (KeyCode::Char('r'), Action::Rotate),
+            (KeyCode::Char('e'), Action::EditCharMap),
+        ] {
+            bindings.insert(Key::plain(code), action);
+        }
+        for (digit, index) in ('0'..='9').zip(0..) {
+            bindings.insert(Key::plain(KeyCode::Char(digit)), Action::CharMap(index));
+        }
+        let ctrl_c = Key {
+            code: KeyCode::Char('c'),
+            control: true,
+        };
+        bindings.insert(ctrl_c, Action::Quit);
+        let ctrl_l = Key {
+            code: KeyCode::Char('l'),
+            control: true,
+        };
+        bindings.insert(ctrl_l, Action::Refresh);
+        let ctrl_z = Key {
+            code: KeyCode::Char('z'),
+            control: true,
+        };
+        bindings.insert(ctrl_z, Action::Suspend);
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Loads the keymap: the default bindings, overridden by those of the keymap file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The keymap file. Without one, `keymap.conf` in the configuration directory is
+    ///   read if it exists.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the keymap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keymap file cannot be read, or has a line that is not a binding.
+    pub fn load(path: Option<&Path>) -> Result<Self, MyError> {
+        let default_path = crate::common::utils::config_dir().map(|dir| dir.join(KEYMAP_FILE_NAME));
+        let path = match path {
+            Some(path) => path,
+            None => match &default_path {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let error = |e: String| {
+            MyError::Application(format!("{ERROR_READING_KEYMAP}: {}: {e}", path.display()))
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let mut keymap = Self::default();
+        keymap.apply(&text).map_err(error)?;
+        Ok(keymap)
+    }
+
+    /// Applies the bindings of a keymap file over the current ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The content of the keymap file: `key = action` lines, `key = none` to unbind a
+    ///   key, and `#` comments.
+    ///
+    /// # Returns
+    ///
+    /// A `Result`, or an error message naming the line that is not a binding.
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| format!("line {}: {e}", number + 1);
+            // The key itself may be `=`
+            let (key, action) = line
+                .rsplit_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+                .ok_or_else(|| error("expected `key = action`".to_string()))?;
+            let key: Key = key.parse().map_err(error)?;
+            if action.trim().eq_ignore_ascii_case("none") {
+                self.bindings.remove(&key);
+            } else {
+                self.bindings.insert(key, action.parse().map_err(error)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the action bound to a key event. Letters bound in lowercase also match their
+    /// uppercase, unless the uppercase letter has its own binding.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The key pressed.
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = Key {
+            code: event.code,
+            control: event.modifiers.contains(KeyModifiers::CONTROL),
+        };
+        let lowercase = match key.code {
+            KeyCode::Char(c) => Key {
+                code: KeyCode::Char(c.to_ascii_lowercase()),
+                ..key
+            },
+            _ => key,
+        };
+        self.bindings
+            .get(&key)
+            .or_else(|| self.bindings.get(&lowercase))
+            .copied()
+    }
+
+    /// Returns the keys bound to an action, as written in keymap files, separated with ` / `.
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - Whether an action is one of those to list the keys of.
+    ///
+    /// # Returns
+    ///
+    /// The keys, sorted, or `None` if no key is bound.
+    pub fn keys(&self, matches: impl Fn(Action) -> bool) -> Option<String> {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, action)| matches(**action))
+            .map(|(key, _)| key.to_string())
+            .collect();
+        keys.sort_by_key(|key| (key.len(), key.clone()));
+        (!keys.is_empty()).then(|| keys.join(" / "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_keymap() {
+        let mut keymap = Keymap::default();
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('Q'), none)),
+            Some(Action::Quit)
+        );
+        let ctrl_c = press(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&ctrl_c), Some(Action::Quit));
+        assert_eq!(keymap.action(&press(KeyCode::Char('c'), none)), None);
+        let ctrl_l = press(KeyCode::Char('l'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&ctrl_l), Some(Action::Refresh));
+        let digit = press(KeyCode::Char('7'), none);
+        assert_eq!(keymap.action(&digit), Some(Action::CharMap(7)));
+
+        let text = "# comment\np = pause\nP = previous_track\nspace = none\nctrl+s = char_map_2\n= = next_char_map\n";
+        keymap.apply(text).unwrap();
+        assert_eq!(
+            keymap.action(&press(KeyCode::Char('p'), none)),
+            Some(Action::Pause)
+        );
+        let shift_p = press(KeyCode::Char('P'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(&shift_p), Some(Action::PreviousTrack));
+        assert_eq!(keymap.action(&press(KeyCode::Char(' '), none)), None);
+        let ctrl_s = press(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&ctrl_s), Some(Action::CharMap(2)));
+        let equals = press(KeyCode::Char('='), none);
+        assert_eq!(keymap.action(&equals), Some(Action::NextCharMap));
+        assert_eq!(
+            keymap.keys(|action| action == Action::Pause),
+            Some("p".to_string())
+        );
+        assert_eq!(
+            keymap.keys(|action| action == Action::Quit),
+            Some("q / esc / ctrl+c".to_string())
+        );
+
+        assert!(keymap.apply("q = fly").unwrap_err().starts_with("line 1"));
+        assert!(keymap.apply("\nhyper+q = quit").is_err());
+        assert!(keymap.apply("quit").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Detects terminals too slow to keep up with the frame rate.
+//!
+//! Writing a colored frame means a lot of escape sequences, which some terminals (or SSH links)
+//! cannot absorb at high frame rates. The `DrawLatencyMonitor` measures how long the first frames
+//! take to draw, and tells the terminal once if they take longer than the frame budget, so that
+//! the user can be given a hint.
+use std::time::Duration;
+
+/// Number of frames ignored at the start of the playback, while the terminal warms up.
+const WARMUP_FRAMES: usize = 2;
+/// Number of frames measured after the warmup.
+const SAMPLE_FRAMES: usize = 20;
+
+/// Measures the draw time of the first frames and compares it with the frame budget.
+#[derive(Debug, Default)]
+pub(crate) struct DrawLatencyMonitor {
+    /// The time available to draw a frame, or `None` to disable the monitor.
+    budget: Option<Duration>,
+    /// The number of frames drawn so far.
+    frames: usize,
+    /// The draw times measured after the warmup.
+    samples: Vec<Duration>,
+}
+
+impl DrawLatencyMonitor {
+    /// Sets the time available to draw a frame, and restarts the measurement.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The duration of a frame at the playback frame rate.
+    pub(crate) fn set_budget(&mut self, budget: Duration) {
+        *self = Self {
+            budget: Some(budget),
+            ..Self::default()
+        };
+    }
+
+    /// Records the draw time of a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - The time spent drawing the frame.
+    ///
+    /// # Returns
+    ///
+    /// `true` once, when enough frames were measured and their median draw time exceeds the
+    /// frame budget; `false` otherwise.
+    pub(crate) fn record(&mut self, draw: Duration) -> bool {
+        let Some(budget) = self.budget else {
+            return false;
+        };
+        self.frames += 1;
+        if self.frames <= WARMUP_FRAMES || self.samples.len() >= SAMPLE_FRAMES {
+            return false;
+        }
+        self.samples.push(draw);
+        if self.samples.len() < SAMPLE_FRAMES {
+            return false;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        sorted[SAMPLE_FRAMES / 2] > budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_latency_monitor() {
+        let budget = Duration::from_millis(33);
+        let slow = Duration::from_millis(50);
+
+        let mut monitor = DrawLatencyMonitor::default();
+        assert!((0..100).all(|_| !monitor.record(slow)));
+
+        monitor.set_budget(budget);
+        let verdicts: Vec<bool> = (0..100).map(|_| monitor.record(slow)).collect();
+        assert_eq!(verdicts.iter().filter(|slow| **slow).count(), 1);
+        assert!(verdicts[WARMUP_FRAMES + SAMPLE_FRAMES - 1]);
+
+        // A few slow frames do not make a slow terminal
+        monitor.set_budget(budget);
+        let draws = (0..100).map(|i| if i % 4 == 0 { slow } else { budget / 2 });
+        assert!(draws.map(|draw| monitor.record(draw)).all(|slow| !slow));
+    }
+}
@@ -1,19 +1,127 @@
 //! The `terminal` module provides functionality for displaying an animation in
 //! the terminal and handling user input events such as pausing/continuing,
 //! resizing, and changing character maps.
-use crate::{common::errors::*, msg::broker::Control as MediaControl, StringInfo};
-use crossbeam_channel::{Receiver, Sender};
+//!
+//! By default the animation takes the whole screen, on the alternate screen. In inline mode it
+//! is drawn in a fixed number of rows at the cursor position instead, without clearing the rest
+//! of the screen, and the last frame is left on screen when the playback ends.
+//!
+//! It consists of the following sub-modules:
+//! - `capabilities`: Reports the size, colors, graphics protocols and synchronized updates of the
+//!   terminal (`tplay doctor`), asking the terminal itself.
+//! - `char_map_editor`: Edits the current character map with the `e` key, showing the effect on
+//!   the paused frame, and saves it to the custom character maps.
+//! - `clipboard`: Copies the frame on screen to the clipboard with the `y` key, also over SSH.
+//! - `colors`: Reduces the colors to the 256-color or 16-color palette of terminals without 24-bit
+//!   colors.
+//! - `diff`: Finds the cells of a frame that changed since the previous one, to print only those.
+//! - `eyedropper`: Shows the pixel and the color under the mouse in the status bar, toggled with
+//!   the `i` key.
+//! - `keymap`: The keys bound to the playback commands, read from the keymap file.
+//! - `latency`: Detects terminals too slow to draw the frames at the playback frame rate.
+//! - `osd`: Shows boxes of text over a corner of the frame for a moment, e.g. the volume when it
+//!   changes.
+//! - `overlay`: Composes boxes of text over the frames, e.g. the help opened with `?` or the
+//!   performance stats opened with `F`.
+//! - `screenshot`: Saves the frame on screen to a file with the `s` key, as pixels or text.
+//! - `seek_bar`: Shows the position of the playback in a bar opened with the `o` key, seeking where
+//!   it is clicked.
+//! - `sixel`: Draws the frames as Sixel graphics, on the terminals supporting them.
+//! - `sleep_timer`: Pauses or quits the playback after a duration (`--quit-after`, `:sleep`).
+//! - `status_line`: Shows the position, the frame rate and the name of the media below the frame.
+//! - `suspend`: Suspends tplay to the shell with `Ctrl+Z`, restoring the terminal meanwhile.
+//! - `toast`: Shows notifications over the top right corner of the frame for a few seconds, telling
+//!   what the keys pressed did, or why they did nothing.
+pub mod capabilities;
+pub mod char_map_editor;
+pub mod clipboard;
+pub mod colors;
+mod diff;
+pub mod eyedropper;
+pub mod keymap;
+mod latency;
+pub mod osd;
+pub mod overlay;
+pub mod screenshot;
+pub mod seek_bar;
+pub mod sixel;
+pub mod sleep_timer;
+pub mod status_line;
+pub mod suspend;
+pub mod toast;
+
+use crate::{
+    audio::runner::Feedback as AudioFeedback,
+    common::{
+        errors::*,
+        clock::PlaybackClock,
+        inhibit::ScreenSaverInhibitor,
+        stats::{PipelineStats, RateMeter},
+    },
+    display::{
+        themes::{Theme, ThemeRegistry},
+        Display, Progress, SourceArea,
+    },
+    msg::broker::{Control as MediaControl, Feedback},
+    pipeline::{
+        cell_grid::Cell,
+        char_maps::{custom_maps_path, save_custom_map, CharMap, CharMapRegistry, CHARS1},
+        glyph_width,
+        luma::LumaFormula,
+        scenes::SceneNavigation,
+        subtitles::Subtitles,
+        transform::TransformChange,
+        transition::{fade_to_gray, Transition},
+    },
+    CellGrid,
+};
+use char_map_editor::{typed_glyphs, CharMapEditor, EditorAction};
+use colors::ColorDepth;
+use crossbeam_channel::Receiver;
 use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEvent},
-    execute,
+    cursor::{self, Hide, MoveTo, Show},
+    event::{
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute, queue,
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor, Stylize},
     terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
+use diff::PrintedFrame;
+use image::DynamicImage;
+use keymap::{Action, Keymap, SEEK_STEP, VOLUME_STEP};
+use latency::DrawLatencyMonitor;
+use osd::Osd;
+use overlay::Anchor;
+use screenshot::ScreenshotFormat;
+use seek_bar::SeekBar;
+use sixel::Backend;
+use sleep_timer::{SleepAction, SleepCommand, SleepTimer};
+use status_line::StatusLine;
 use std::{
     io::{stdout, Write, Result as IOResult},
-    time::Duration,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
+use toast::{ToastLevel, Toasts};
+
+/// Hint shown when the terminal draws frames slower than the frame rate.
+const SLOW_TERMINAL_HINT: &str =
+    " Terminal too slow for this frame rate: try a smaller window, --gray or a lower --fps ";
+/// How long the slow terminal hint stays on screen.
+const SLOW_TERMINAL_HINT_DURATION: Duration = Duration::from_secs(5);
+/// How far the arrow keys turn the viewport of 360° media, in degrees.
+const PAN_STEP: f64 = 15.0;
+/// How long the result of a command stays in the status bar.
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(3);
+/// Initial capacity of the frame buffer, in bytes: enough for a colored frame filling a large
+/// terminal, about 20 bytes per cell, so that it rarely grows.
+const FRAME_BUFFER_CAPACITY: usize = 1 << 20;
+/// How long the size of the terminal must stay the same before the pipeline is resized, so that
+/// dragging the window renders one size rather than every size on the way.
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
 
 /// Represents the playback state of the Terminal.
 #[derive(PartialEq)]
@@ -37,12 +145,131 @@ pub struct Terminal {
     title: String,
     /// The current playback state of the Terminal.
     state: State,
-    /// The channel for receiving the processed frames from the media processing thread.
-    rx_buffer: Receiver<Option<StringInfo>>,
-    /// The channel for sending control events to the media processing thread.
-    tx_control: Sender<MediaControl>,
     /// Whether to use grayscale colors.
     use_grayscale: bool,
+    /// The number of frames the switches of grayscale mode are animated over, 0 to switch at once.
+    switch_transition: u32,
+    /// The fade of the colors after a switch of grayscale mode, while it is animated.
+    grayscale_fade: Option<Transition>,
+    /// The color themes cycled through with the `t` key.
+    themes: ThemeRegistry,
+    /// The index of the current color theme.
+    theme: usize,
+    /// How long to wait for user input each time the events are polled.
+    poll_timeout: Duration,
+    /// Measures the draw time of the first frames, to detect slow terminals.
+    latency: DrawLatencyMonitor,
+    /// Until when the slow terminal hint is shown, once it was triggered.
+    hint_until: Option<Instant>,
+    /// The number of rows drawn in inline mode, or `None` to use the whole screen.
+    inline_rows: Option<u16>,
+    /// The first row of the drawing area.
+    origin: u16,
+    /// Whether to pause the playback while the terminal does not have the focus.
+    pause_on_unfocus: bool,
+    /// Whether the playback was paused because the terminal lost the focus.
+    paused_by_unfocus: bool,
+    /// Whether to suspend to the shell at the next poll, once the pause is sent (`Ctrl+Z`).
+    suspend_pending: bool,
+    /// Whether the media has a scene index, opened with the `Tab` key.
+    has_scene_index: bool,
+    /// Whether the media is viewed as a 360° panorama, the arrow keys then pan the viewport.
+    panorama: bool,
+    /// Whether the scene index is shown, the keys then navigate it.
+    scene_index_open: bool,
+    /// How the frames are drawn.
+    backend: Backend,
+    /// Whether the media is a track of a playlist, the `n` and `p` keys then change track.
+    in_playlist: bool,
+    /// The colors the terminal can show.
+    color_depth: ColorDepth,
+    /// The sleep timer, its countdown is shown in the status bar.
+    sleep_timer: SleepTimer,
+    /// The command being typed after `:`, if the command line is open.
+    prompt: Option<String>,
+    /// The result of the last command, and until when it is shown.
+    message: Option<(String, Instant)>,
+    /// The text of the status bar when it was last drawn.
+    status: (String, String),
+    /// Inhibits the screensaver while the playback runs, unless disabled.
+    inhibitor: Option<ScreenSaverInhibitor>,
+    /// The blank rows above and below the frame, and columns left and right of it.
+    margin: (u16, u16),
+    /// The status line shown below the frame, if enabled.
+    status_line: Option<StatusLine>,
+    /// Whether the help listing the keys is shown over the frames.
+    help_open: bool,
+    /// Whether the frames are surrounded with the ambient light border.
+    ambilight: bool,
+    /// The index of the character map chosen with the digit keys, if any.
+    char_map: Option<u32>,
+    /// The character maps cycled through with the `[` and `]` keys, the first one being the
+    /// character map the playback started with.
+    char_maps: Vec<CharMap>,
+    /// The character map editor, while it is open.
+    char_map_editor: Option<CharMapEditor>,
+    /// The glyphs typed on the command line (`:charmap`) the frames are rendered with, until a
+    /// character map is chosen with the keys.
+    typed_char_map: Option<String>,
+    /// Control commands to send along with those of the next events, e.g. resuming the playback
+    /// once the character map editor closes.
+    queued: Vec<MediaControl>,
+    /// The keys bound to the playback commands.
+    keymap: Keymap,
+    /// The file format of the screenshots taken with the `s` key.
+    screenshot_format: ScreenshotFormat,
+    /// The frame on screen, without the overlays, saved by the screenshots.
+    shown_frame: Option<CellGrid>,
+    /// Whether the frames copied with the `y` key keep their colors, as escape sequences.
+    copy_ansi: bool,
+    /// The frame of the media the frame on screen was rendered from, if any.
+    source_frame: Option<Arc<DynamicImage>>,
+    /// The cells of the frame on screen showing the frame of the media, if known.
+    source_area: Option<SourceArea>,
+    /// The playback frame rate, shown in the help.
+    fps: f64,
+    /// The stats channel, through which the media pipeline publishes its performance.
+    rx_stats: Option<Receiver<PipelineStats>>,
+    /// The last performance stats published by the media pipeline.
+    pipeline_stats: PipelineStats,
+    /// Whether the performance stats are shown over the frames.
+    stats_open: bool,
+    /// The rate at which the frames are drawn.
+    draw_rate: RateMeter,
+    /// The last frame printed, while the screen still shows it, so that the next frame only
+    /// prints the cells that changed.
+    printed_frame: Option<PrintedFrame>,
+    /// The `Resize` command for the latest size of the terminal, and when it changed, until the
+    /// size settles (see `RESIZE_DEBOUNCE`).
+    pending_resize: Option<(MediaControl, Instant)>,
+    /// The glyphs and escape sequences of the frame being printed, reused across frames.
+    frame_buffer: Vec<u8>,
+    /// The subtitle tracks of the media, if it may have subtitles.
+    subtitles: Option<Subtitles>,
+    /// The playback position published by the media pipeline, timing the subtitles.
+    playback_clock: PlaybackClock,
+    /// The changes of the audio reported by the audio thread and the pauses of the playback,
+    /// through the broker.
+    rx_feedback: Option<Receiver<Feedback>>,
+    /// The on-screen display shown over the top right corner of the frame, if any.
+    osd: Option<Osd>,
+    /// The step of the fade of the on-screen display when the frame was last drawn.
+    osd_step: Option<u8>,
+    /// The notifications shown under the on-screen display.
+    toasts: Toasts,
+    /// Where the playback is, as last reported by the media pipeline.
+    progress: Progress,
+    /// How the colors are converted to gray levels, cycled with the `l` key.
+    luma: LumaFormula,
+    /// Whether the seek bar is shown over the bottom of the frames, the mouse then seeks.
+    seek_bar_open: bool,
+    /// The column and row of the first character of the seek bar on screen, and the width of its
+    /// line, as last drawn.
+    seek_bar_area: Option<(u16, u16, usize)>,
+    /// Whether the status bar shows the pixel under the mouse and its color.
+    eyedropper_open: bool,
+    /// The column and row of the mouse on screen, as last moved while the eyedropper is on.
+    hovered: Option<(u16, u16)>,
 }
 
 impl Terminal {
@@ -52,82 +279,886 @@ impl Terminal {
     ///
     /// * `title` - The title for the terminal window.
     /// * `use_grayscale` - Whether to use grayscale colors.
-    /// * `rx_buffer` - The channel for receiving the processed frames from the media processing
-    ///   thread.
-    /// * `tx_control` - The channel for sending control events to the media processing thread.
-    /// * `barrier` - The barrier for synchronizing the media processing thread and the terminal
-    ///   thread.
-    pub fn new(
-        title: String,
-        use_grayscale: bool,
-        rx_buffer: Receiver<Option<StringInfo>>,
-        tx_control: Sender<MediaControl>,
-    ) -> Self {
+    /// * `themes` - The color themes to cycle through.
+    /// * `theme` - The index of the initial color theme.
+    pub fn new(title: String, use_grayscale: bool, themes: ThemeRegistry, theme: usize) -> Self {
         Self {
             fg_color: Color::White,
             bg_color: Color::Black,
             title,
             state: State::Running,
-            rx_buffer,
-            tx_control,
             use_grayscale,
+            switch_transition: 0,
+            grayscale_fade: None,
+            themes,
+            theme,
+            poll_timeout: Duration::ZERO,
+            latency: DrawLatencyMonitor::default(),
+            hint_until: None,
+            inline_rows: None,
+            origin: 0,
+            pause_on_unfocus: false,
+            paused_by_unfocus: false,
+            suspend_pending: false,
+            has_scene_index: false,
+            panorama: false,
+            scene_index_open: false,
+            backend: Backend::Ascii,
+            in_playlist: false,
+            color_depth: ColorDepth::TrueColor,
+            sleep_timer: SleepTimer::default(),
+            prompt: None,
+            message: None,
+            status: Default::default(),
+            inhibitor: Some(ScreenSaverInhibitor::default()),
+            margin: (0, 0),
+            status_line: None,
+            help_open: false,
+            ambilight: false,
+            char_map: None,
+            char_maps: char_map_list(&CharMapRegistry::default(), CHARS1),
+            char_map_editor: None,
+            typed_char_map: None,
+            queued: Vec::new(),
+            keymap: Keymap::default(),
+            screenshot_format: ScreenshotFormat::default(),
+            shown_frame: None,
+            copy_ansi: false,
+            source_frame: None,
+            source_area: None,
+            fps: 0.0,
+            rx_stats: None,
+            pipeline_stats: PipelineStats::default(),
+            stats_open: false,
+            draw_rate: RateMeter::default(),
+            printed_frame: None,
+            pending_resize: None,
+            frame_buffer: Vec::with_capacity(FRAME_BUFFER_CAPACITY),
+            subtitles: None,
+            playback_clock: PlaybackClock::default(),
+            rx_feedback: None,
+            osd: None,
+            osd_step: None,
+            toasts: Toasts::default(),
+            progress: Progress::default(),
+            luma: LumaFormula::default(),
+            seek_bar_open: false,
+            seek_bar_area: None,
+            eyedropper_open: false,
+            hovered: None,
+        }
+    }
+
+    /// Pauses the playback when the terminal loses the focus, and resumes it when the focus comes
+    /// back. Only terminals reporting focus changes are supported; others ignore the setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `pause_on_unfocus` - Whether to pause the playback while the terminal is not focused.
+    pub fn set_pause_on_unfocus(&mut self, pause_on_unfocus: bool) {
+        self.pause_on_unfocus = pause_on_unfocus;
+    }
+
+    /// Inhibits the screensaver and the system sleep while the playback runs (the default), and
+    /// releases them while it is paused.
+    ///
+    /// # Arguments
+    ///
+    /// * `inhibit` - Whether to inhibit the screensaver.
+    pub fn set_inhibit_screensaver(&mut self, inhibit: bool) {
+        self.inhibitor = inhibit.then(ScreenSaverInhibitor::default);
+    }
+
+    /// Sets how the frames are drawn. With the Sixel backend, the frames are expected to hold
+    /// pixels (see `ImagePipeline::pixels_per_cell`), and the last row of the screen is left free.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend, `Backend::Auto` is resolved for the current terminal.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend.resolve();
+    }
+
+    /// Sets the colors of the characters and of the background when the frames do not color them,
+    /// e.g. in grayscale mode. White on black by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `fg` - The color of the characters, e.g. `Color::Green` for a phosphor screen look.
+    /// * `bg` - The color of the background, `Color::Reset` to keep the one of the terminal.
+    pub fn set_default_colors(&mut self, fg: Color, bg: Color) {
+        self.fg_color = fg;
+        self.bg_color = bg;
+    }
+
+    /// Sets the colors the terminal can show, the colors of the frames are reduced to them.
+    ///
+    /// # Arguments
+    ///
+    /// * `color_depth` - The color depth, `ColorDepth::Auto` is resolved for the current terminal.
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth.resolve();
+    }
+
+    /// Leaves blank rows and columns around the frames, e.g. for terminals with rounded corners or
+    /// when recording the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The blank rows above and below the frames.
+    /// * `columns` - The blank columns left and right of the frames.
+    pub fn set_margin(&mut self, rows: u16, columns: u16) {
+        self.margin = (rows, columns);
+    }
+
+    /// Sets the stats channel through which the media pipeline publishes its performance, shown
+    /// by the performance overlay (`F`).
+    ///
+    /// # Arguments
+    ///
+    /// * `rx_stats` - The receiving end of the stats channel.
+    pub fn set_stats_channel(&mut self, rx_stats: Receiver<PipelineStats>) {
+        self.rx_stats = Some(rx_stats);
+    }
+
+    /// Shows the changes of the audio reported by the audio thread, e.g. the volume bar when the
+    /// volume changes or the audio is muted, and follows the pauses of the playback commanded
+    /// elsewhere, e.g. with `tplay remote pause`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rx_feedback` - The channel of the changes, forwarded by the broker.
+    pub fn set_feedback(&mut self, rx_feedback: Receiver<Feedback>) {
+        self.rx_feedback = Some(rx_feedback);
+    }
+
+    /// Shows subtitles over the bottom of the frames, timed with the playback position published
+    /// by the media pipeline. The `j` key cycles through the tracks.
+    ///
+    /// # Arguments
+    ///
+    /// * `subtitles` - The subtitle tracks of the media.
+    /// * `clock` - The playback clock, shared with the media pipeline.
+    pub fn set_subtitles(&mut self, subtitles: Subtitles, clock: PlaybackClock) {
+        self.subtitles = Some(subtitles);
+        self.playback_clock = clock;
+    }
+
+    /// Shows a status line below the frame, with the position and duration of the media, whether
+    /// the playback is paused, the frame rate and the name of the media. The frames are drawn one
+    /// row shorter to make room for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `show` - Whether to show the status line.
+    pub fn set_status_line(&mut self, show: bool) {
+        self.status_line = show.then(|| StatusLine::new(&self.title));
+    }
+
+    /// Animates the switches of grayscale mode over a number of frames, fading the colors to
+    /// their gray level or back from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The number of frames of the fade, 0 to switch at once.
+    pub fn set_switch_transition(&mut self, frames: u32) {
+        self.switch_transition = frames;
+    }
+
+    /// Surrounds the frames with a border of cells glowing with the colors of their edges, like
+    /// the lights behind some TV sets. The frames are drawn one cell smaller on each side to make
+    /// room for it. Sixel graphics are drawn without the border.
+    ///
+    /// # Arguments
+    ///
+    /// * `ambilight` - Whether to draw the border.
+    pub fn set_ambilight(&mut self, ambilight: bool) {
+        self.ambilight = ambilight;
+    }
+
+    /// Returns the width of the ambient light border around the frames: 1 if it is drawn, else 0.
+    fn border(&self) -> u16 {
+        u16::from(self.ambilight && self.backend != Backend::Sixel)
+    }
+
+    /// Returns the size of the drawing area for the given terminal size. Sixel images reaching
+    /// the bottom of the screen make it scroll, so the last row is kept free for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the terminal.
+    /// * `height` - The height of the terminal.
+    fn drawing_area(&self, width: u16, height: u16) -> (u16, u16) {
+        match self.backend {
+            Backend::Sixel => self.frame_area(width, height.saturating_sub(1).max(1)),
+            _ => self.frame_area(width, height),
+        }
+    }
+
+    /// Returns the size of the frames drawn in an area, once the margin and the ambient light
+    /// border are left around them and the row of the status line below them, at least one cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the area, margin included.
+    /// * `height` - The height of the area, margin included.
+    fn frame_area(&self, width: u16, height: u16) -> (u16, u16) {
+        let (rows, columns) = self.margin;
+        let (rows, columns) = (rows + self.border(), columns + self.border());
+        let status_rows = u16::from(self.status_line.is_some());
+        (
+            width.saturating_sub(columns.saturating_mul(2)).max(1),
+            height
+                .saturating_sub(rows.saturating_mul(2))
+                .saturating_sub(status_rows)
+                .max(1),
+        )
+    }
+
+    /// Returns the column and row of the top left corner of the frames, inside the margin and the
+    /// ambient light border.
+    fn frame_origin(&self) -> (u16, u16) {
+        let (rows, columns) = self.margin;
+        (
+            columns + self.border(),
+            self.origin.saturating_add(rows + self.border()),
+        )
+    }
+
+    /// Enables the `Tab` key, showing the scene index of the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `has_scene_index` - Whether the media has a scene index.
+    pub fn set_scene_index(&mut self, has_scene_index: bool) {
+        self.has_scene_index = has_scene_index;
+    }
+
+    /// Enables the arrow keys, panning the viewport of 360° media.
+    ///
+    /// # Arguments
+    ///
+    /// * `panorama` - Whether the media is viewed as a 360° panorama (see `ImagePipeline::panorama`).
+    pub fn set_panorama(&mut self, panorama: bool) {
+        self.panorama = panorama;
+    }
+
+    /// Enables the `n` and `p` keys, playing the next or previous track of the playlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `in_playlist` - Whether the media is a track of a playlist.
+    pub fn set_in_playlist(&mut self, in_playlist: bool) {
+        self.in_playlist = in_playlist;
+    }
+
+    /// Sets the sleep timer pausing or quitting the playback, shared with the terminals of the
+    /// other media played in the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `sleep_timer` - The sleep timer.
+    pub fn set_sleep_timer(&mut self, sleep_timer: SleepTimer) {
+        self.sleep_timer = sleep_timer;
+    }
+
+    /// Switches to inline mode: the frames are drawn in the given number of rows at the cursor
+    /// position, instead of on the alternate screen.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The height of the drawing area, capped to the height of the terminal.
+    pub fn set_inline_rows(&mut self, rows: u16) {
+        self.inline_rows = Some(rows.max(1));
+    }
+
+    /// Reserves the rows of the inline drawing area below the cursor, scrolling the screen if
+    /// needed, and remembers where the area starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The number of rows to reserve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the size of the drawing area.
+    fn reserve_inline_rows(&mut self, rows: u16) -> IOResult<(u16, u16)> {
+        let (width, height) = terminal::size()?;
+        // Keep a free line below the area, so that drawing the last cell never scrolls
+        let rows = rows.min(height.saturating_sub(1)).max(1);
+        let mut out = stdout();
+        execute!(out, Hide, Print("\r\n".repeat(rows as usize)))?;
+        let (_, row) = cursor::position()?;
+        self.origin = row.saturating_sub(rows);
+        self.inline_rows = Some(rows);
+        Ok((width, rows))
+    }
+
+    /// Sets the time available to draw each frame. If the first frames take longer than that to
+    /// draw, a hint suggesting how to speed the terminal up is shown once. The frame rate is also
+    /// shown in the help.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The duration of a frame at the playback frame rate.
+    pub fn set_frame_budget(&mut self, budget: Duration) {
+        self.latency.set_budget(budget);
+        self.fps = 1.0 / budget.as_secs_f64().max(f64::EPSILON);
+    }
+
+    /// Sets the keys bound to the playback commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `keymap` - The keymap, e.g. loaded from the keymap file.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Sets the character maps cycled through during playback, as the media pipeline has them.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The character maps, e.g. with the custom ones of the configuration.
+    /// * `char_map` - The character map the playback starts with, as given on the command line.
+    pub fn set_char_maps(&mut self, registry: &CharMapRegistry, char_map: &str) {
+        self.char_maps = char_map_list(registry, char_map);
+    }
+
+    /// Sets how the colors are converted to gray levels when the playback starts, as the media
+    /// pipeline does. The `l` key cycles through the formulas.
+    ///
+    /// # Arguments
+    ///
+    /// * `luma` - The luma formula.
+    pub fn set_luma(&mut self, luma: LumaFormula) {
+        self.luma = luma;
+    }
+
+    /// Sets the file format of the screenshots taken with the `s` key.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The file format.
+    pub fn set_screenshot_format(&mut self, format: ScreenshotFormat) {
+        self.screenshot_format = format;
+    }
+
+    /// Sets whether the frames copied with the `y` key keep their colors, as the escape sequences
+    /// of the `ans` screenshots, instead of plain text.
+    ///
+    /// # Arguments
+    ///
+    /// * `copy_ansi` - Whether to copy the colors.
+    pub fn set_copy_ansi(&mut self, copy_ansi: bool) {
+        self.copy_ansi = copy_ansi;
+    }
+
+    /// Saves the frame on screen to a new file in the current directory, in the screenshot format.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the path of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no frame was drawn yet, or the file cannot be written.
+    fn save_screenshot(&self) -> Result<PathBuf, MyError> {
+        let grid = self.shown_frame.as_ref().ok_or_else(|| {
+            MyError::Application(format!("{ERROR_SCREENSHOT}: no frame on screen"))
+        })?;
+        let grayscale = Theme::grayscale();
+        let theme = match self.shows_grayscale() {
+            true => &grayscale,
+            false => self.themes.get_index(self.theme).unwrap_or(&grayscale),
+        };
+        screenshot::save(
+            self.screenshot_format,
+            grid,
+            self.source_frame.as_deref(),
+            (theme, self.color_depth),
+            Path::new(""),
+        )
+    }
+
+    /// Names the character map chosen in a toast.
+    ///
+    /// # Arguments
+    ///
+    /// * `char_map` - The index of the character map.
+    fn notify_char_map(&mut self, char_map: u32) {
+        if let Some(map) = self.char_maps.get(char_map as usize) {
+            self.toasts
+                .push(format!("char map {}", map.name), ToastLevel::Info);
+        }
+    }
+
+    /// Copies the characters of the frame on screen to the clipboard, with their colors if
+    /// `--copy-ansi` is set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing how the frame was copied, e.g. `xclip` or `OSC 52`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no frame was drawn yet, the frame is drawn as pixels, or the clipboard
+    /// cannot be reached.
+    fn copy_frame(&self) -> Result<&'static str, MyError> {
+        let grid = self.shown_frame.as_ref().ok_or_else(|| {
+            MyError::Application(format!("{ERROR_CLIPBOARD}: no frame on screen"))
+        })?;
+        if grid.pixels().is_some() {
+            return Err(MyError::Application(format!(
+                "{ERROR_CLIPBOARD}: the frame is drawn as pixels, not characters"
+            )));
+        }
+        let grayscale = Theme::grayscale();
+        let colors = match (self.copy_ansi, self.shows_grayscale()) {
+            (false, _) => None,
+            (true, true) => Some((&grayscale, self.color_depth)),
+            (true, false) => Some((
+                self.themes.get_index(self.theme).unwrap_or(&grayscale),
+                self.color_depth,
+            )),
+        };
+        clipboard::copy(&screenshot::text(grid, colors))
+    }
+
+    /// Returns the text of the help: the keys available for the media, and the current settings.
+    fn help_lines(&self) -> Vec<String> {
+        let key = |action: Action| self.keymap.keys(|a| a == action);
+        let digits = (0..10)
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(" / ");
+        let char_map_keys = self
+            .keymap
+            .keys(|a| matches!(a, Action::CharMap(_)))
+            .map(|keys| {
+                if keys == digits {
+                    "0-9".to_string()
+                } else {
+                    keys
+                }
+            });
+        let mut keys = vec![
+            (key(Action::Pause), "pause/resume"),
+            (char_map_keys, "change character map"),
+            (
+                self.keymap
+                    .keys(|a| matches!(a, Action::NextCharMap | Action::PreviousCharMap)),
+                "next/previous character map",
+            ),
+            (
+                self.keymap
+                    .keys(|a| matches!(a, Action::SeekBackward | Action::SeekForward)),
+                "seek backward/forward",
+            ),
+            (key(Action::SeekBar), "show/hide the seek bar"),
+            (key(Action::Eyedropper), "read the colors under the mouse"),
+            (key(Action::Grayscale), "toggle grayscale/color"),
+            (key(Action::Theme), "cycle color themes"),
+            (key(Action::Mute), "mute/unmute"),
+            (
+                self.keymap
+                    .keys(|a| matches!(a, Action::VolumeUp | Action::VolumeDown)),
+                "volume up/down",
+            ),
+            (key(Action::Braille), "toggle braille dots"),
+            (key(Action::Dither), "cycle dithering"),
+            (key(Action::Luma), "cycle luma formulas"),
+            (key(Action::Background), "toggle background painting"),
+            (key(Action::Screenshot), "save a screenshot"),
+            (key(Action::Copy), "copy the frame"),
+            (key(Action::EditCharMap), "edit the character map"),
+            (
+                self.keymap
+                    .keys(|a| matches!(a, Action::FlipHorizontal | Action::FlipVertical)),
+                "flip horizontally/vertically",
+            ),
+            (key(Action::Rotate), "rotate 90°"),
+        ];
+        if self.has_scene_index {
+            keys.push((key(Action::SceneIndex), "scene index"));
+        }
+        if self.subtitles.is_some() {
+            keys.push((key(Action::Subtitles), "cycle subtitles"));
         }
+        if self.in_playlist {
+            keys.push((
+                self.keymap
+                    .keys(|a| matches!(a, Action::NextTrack | Action::PreviousTrack)),
+                "next/previous track",
+            ));
+        }
+        if self.panorama {
+            keys.push((Some("arrows".to_string()), "pan the 360° view"));
+        }
+        keys.extend([
+            (key(Action::CommandLine), "command line"),
+            (key(Action::Stats), "performance stats"),
+            (key(Action::Refresh), "redraw the screen"),
+            (key(Action::Suspend), "suspend to the shell"),
+            (key(Action::Help), "close this help"),
+            (key(Action::Quit), "quit"),
+        ]);
+        // Unbound actions are left out
+        let keys: Vec<_> = keys
+            .into_iter()
+            .filter_map(|(key, action)| key.map(|key| (key, action)))
+            .collect();
+        let width = keys
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let mut lines = vec!["Keys".to_string()];
+        lines.extend(
+            keys.iter()
+                .map(|(key, action)| format!("{key:<width$}{action}")),
+        );
+        let char_map = match (&self.typed_char_map, self.char_map) {
+            (Some(glyphs), _) => format!("\"{glyphs}\""),
+            (None, Some(index)) => index.to_string(),
+            (None, None) => "default".to_string(),
+        };
+        let theme = match self.themes.get_index(self.theme) {
+            Some(theme) if !self.use_grayscale => theme.name.as_str(),
+            _ => "grayscale",
+        };
+        lines.extend([
+            String::new(),
+            format!("char map {char_map} · {theme} · {:.2} fps", self.fps),
+        ]);
+        lines
     }
 
-    /// The main loop of the Terminal that runs the animation, handles user input,
-    /// and manages the playback state.
+    /// Returns the text of the performance overlay: the rates at which the frames are drawn,
+    /// converted and decoded, the frames lost, the frames waiting to be drawn, and the audio
+    /// underruns if they are detected.
+    fn stats_lines(&self) -> Vec<String> {
+        let stats = &self.pipeline_stats;
+        let mut lines = vec![
+            format!("drawn    {:>6.1} fps", self.draw_rate.rate()),
+            format!("rendered {:>6.1} fps", stats.render_fps),
+            format!("decoded  {:>6.1} fps", stats.decode_fps),
+            format!("dropped  {:>6}", stats.frames_dropped),
+            format!("skipped  {:>6}", stats.frames_skipped),
+            format!("backlog  {:>6}", stats.backlog),
+        ];
+        if let Some(underruns) = stats.audio_underruns {
+            lines.push(format!("underrun {underruns:>6}"));
+        }
+        lines
+    }
+
+    /// Prints lines of text over the top left corner of frames holding pixels, which boxes of
+    /// text cannot be composed into.
     ///
     /// # Arguments
     ///
-    /// * `barrier` - A reference to the `Barrier` used to synchronize the start of the animation.
+    /// * `lines` - The text, e.g. of the help.
     ///
     /// # Errors
     ///
-    /// Returns an error if there is an issue with the terminal operations or
-    /// communication with the pipeline.
-    pub fn run(&mut self, barrier: std::sync::Arc<std::sync::Barrier>) -> Result<(), MyError> {
-        execute!(stdout(), EnterAlternateScreen, SetTitle(&self.title))?;
-        terminal::enable_raw_mode()?;
+    /// Returns an error if there is an issue with the terminal operations.
+    fn print_lines(&self, lines: &[String]) -> IOResult<()> {
+        let (x, y) = self.frame_origin();
+        let mut out = stdout();
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(x, y.saturating_add(row as u16)),
+                Print(format!(" {line} ").reverse())
+            )?;
+        }
+        queue!(out, MoveTo(0, self.origin))?;
+        out.flush()
+    }
 
-        // Clear screen
-        self.clear()?;
+    /// Prints the slow terminal hint over the top left corner of the frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the terminal operations.
+    fn print_hint(&self) -> IOResult<()> {
+        let mut out = stdout();
+        execute!(
+            out,
+            MoveTo(0, self.origin),
+            Print(SLOW_TERMINAL_HINT.reverse()),
+            MoveTo(0, self.origin)
+        )?;
+        out.flush()
+    }
+
+    /// Returns the text of the status bar: the command line or the result of the last command on
+    /// the left, the countdown of the sleep timer on the right.
+    fn status_text(&self) -> (String, String) {
+        let left = match (&self.prompt, &self.message) {
+            (Some(prompt), _) => format!(":{prompt}"),
+            (None, Some((message, until))) if Instant::now() < *until => message.clone(),
+            _ => self.eyedropper_reading().unwrap_or_default(),
+        };
+        (left, self.sleep_timer.countdown().unwrap_or_default())
+    }
+
+    /// Returns the pixel under the mouse and its color while the eyedropper is on, if the mouse is
+    /// over the frame.
+    fn eyedropper_reading(&self) -> Option<String> {
+        if !self.eyedropper_open {
+            return None;
+        }
+        let (column, row) = self.hovered?;
+        let (x, y) = self.frame_origin();
+        let frame = self.shown_frame.as_ref()?;
+        let offset = (column.checked_sub(x)?, row.checked_sub(y)?);
+        let cell = eyedropper::cell_at(frame, offset)?;
+        eyedropper::describe(frame, cell, self.source_frame.as_deref(), self.source_area)
+    }
 
-        // Initialize terminal size and pass terminal size to pipeline
+    /// Prints the status bar over the last row of the frame, if there is anything to show.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the terminal operations.
+    fn print_status(&mut self) -> IOResult<()> {
+        let (left, right) = self.status_text();
+        self.status = (left.clone(), right.clone());
+        if left.is_empty() && right.is_empty() {
+            return Ok(());
+        }
         let (width, height) = terminal::size()?;
-        self.send_control(MediaControl::Resize(width, height))?;
+        let (width, height) = match self.inline_rows {
+            Some(rows) => self.frame_area(width, rows),
+            None => self.drawing_area(width, height),
+        };
+        let (x, y) = self.frame_origin();
+        let row = y + height - 1;
+        let mut out = stdout();
+        if !left.is_empty() {
+            queue!(out, MoveTo(x, row), Print(format!(" {left} ").reverse()))?;
+        }
+        if !right.is_empty() {
+            let column = x + width.saturating_sub(right.chars().count() as u16 + 2);
+            queue!(
+                out,
+                MoveTo(column, row),
+                Print(format!(" {right} ").reverse())
+            )?;
+        }
+        queue!(out, MoveTo(0, self.origin))?;
+        out.flush()
+    }
+
+    /// Prints the status line in the row below the frame, if enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the terminal operations.
+    fn print_status_line(&self) -> IOResult<()> {
+        let Some(status_line) = &self.status_line else {
+            return Ok(());
+        };
+        let (width, height) = terminal::size()?;
+        let (width, height) = match self.inline_rows {
+            Some(rows) => self.frame_area(width, rows),
+            None => self.drawing_area(width, height),
+        };
+        let (x, y) = self.frame_origin();
+        let text = status_line.text(self.state == State::Paused, width as usize);
+        let mut out = stdout();
+        queue!(
+            out,
+            SetForegroundColor(self.color_depth.reduce(self.fg_color)),
+            SetBackgroundColor(self.color_depth.reduce(self.bg_color)),
+            MoveTo(x, y + height),
+            Print(text),
+            MoveTo(0, self.origin)
+        )?;
+        out.flush()
+    }
 
-        barrier.wait();
-        // Begin drawing and event loop
-        while self.state != State::Stopped {
-            // Poll and handle events
-            if event::poll(Duration::from_millis(0))? {
-                let ev = event::read()?;
-                self.handle_event(ev)?;
+    /// Runs a command typed after `:`, and shows its result in the status bar.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The command, e.g. `sleep 20m pause`.
+    ///
+    /// # Returns
+    ///
+    /// The control command to send to the media processing thread, if any.
+    fn run_command(&mut self, line: &str) -> Option<MediaControl> {
+        let (name, arguments) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let mut control = MediaControl::Redraw;
+        let message = match name {
+            "charmap" => match typed_glyphs(line) {
+                Some(glyphs) => {
+                    self.typed_char_map = Some(glyphs.to_string());
+                    control = MediaControl::SetCustomCharMap(glyphs.to_string());
+                    "char map set".to_string()
+                }
+                None => "usage: charmap <glyphs from darkest to brightest>".to_string(),
+            },
+            "sleep" => match arguments.parse::<SleepCommand>() {
+                Ok(command) => {
+                    self.sleep_timer.apply(command);
+                    match command {
+                        SleepCommand::Start(..) => "sleep timer set".to_string(),
+                        SleepCommand::Cancel => "sleep timer off".to_string(),
+                    }
+                }
+                Err(err) => err,
+            },
+            "" => return Some(MediaControl::Redraw),
+            _ => format!("unknown command: {name}"),
+        };
+        self.message = Some((message, Instant::now() + STATUS_MESSAGE_DURATION));
+        Some(control)
+    }
+
+    /// Edits the command line open after `:`. `Enter` runs the command, `Esc` closes the line.
+    /// While a character map is typed (`:charmap`), the frames are rendered with it at each key,
+    /// and closing the line restores the character map rendered before.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The key pressed.
+    ///
+    /// # Returns
+    ///
+    /// The control command to send to the media processing thread, if any.
+    fn edit_prompt(&mut self, code: KeyCode) -> Option<MediaControl> {
+        let prompt = self.prompt.as_mut()?;
+        let typing_char_map = typed_glyphs(prompt).is_some();
+        match code {
+            KeyCode::Char(c) => prompt.push(c),
+            KeyCode::Backspace if !prompt.is_empty() => {
+                prompt.pop();
+            }
+            KeyCode::Enter => {
+                let line = self.prompt.take().unwrap_or_default();
+                return self.run_command(&line);
             }
+            KeyCode::Backspace | KeyCode::Esc => self.prompt = None,
+            _ => return None,
+        }
+        match self.prompt.as_deref().and_then(typed_glyphs) {
+            Some(glyphs) => Some(MediaControl::SetCustomCharMap(glyphs.to_string())),
+            None if typing_char_map => Some(self.restore_char_map()),
+            None => Some(MediaControl::Redraw),
+        }
+    }
+
+    /// Returns the command rendering the frames with the character map rendered before a
+    /// character map was typed: the one typed before, or the one chosen with the keys.
+    fn restore_char_map(&self) -> MediaControl {
+        match &self.typed_char_map {
+            Some(glyphs) => MediaControl::SetCustomCharMap(glyphs.clone()),
+            None => MediaControl::SetCharMap(self.char_map.unwrap_or(0)),
+        }
+    }
 
-            // Wait for next frame to draw
-            if let Ok(Some(s)) = self.rx_buffer.try_recv() {
-                self.draw(&s)?;
+    /// Edits the character map with a key while the editor is open. Saving writes the character
+    /// map to the custom character maps file, naming it in the status bar. Closing the editor
+    /// resumes the playback if it paused it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key pressed.
+    ///
+    /// # Returns
+    ///
+    /// The control command to send to the media processing thread, if any.
+    fn edit_char_map(&mut self, key: &KeyEvent) -> Option<MediaControl> {
+        let editor = self.char_map_editor.as_mut()?;
+        let action = editor.handle_key(key)?;
+        if action == EditorAction::Moved {
+            return Some(MediaControl::Redraw);
+        }
+        if action == EditorAction::Changed {
+            return Some(MediaControl::EditCharMap(
+                editor.index,
+                editor.glyphs.clone(),
+            ));
+        }
+        let editor = self.char_map_editor.take()?;
+        if action == EditorAction::Save {
+            let map = CharMap {
+                name: editor.name.clone(),
+                chars: editor.glyphs.clone(),
             };
+            let saved = custom_maps_path()
+                .ok_or_else(|| {
+                    MyError::Application(format!(
+                        "{ERROR_SAVING_CHAR_MAP}: no configuration directory"
+                    ))
+                })
+                .and_then(|path| save_custom_map(&path, &map).map(|()| path));
+            self.toasts
+                .push_result(saved.map(|path| format!("saved {} to {}", map.name, path.display())));
+            if let Some(edited) = self.char_maps.get_mut(editor.index as usize) {
+                *edited = map;
+            }
+        }
+        if editor.resume && self.state == State::Paused {
+            self.state = State::Running;
+            self.queued.push(MediaControl::PauseContinue);
+        }
+        Some(MediaControl::EditCharMap(editor.index, editor.glyphs))
+    }
+
+    /// Pauses or quits the playback when the sleep timer expires.
+    ///
+    /// # Returns
+    ///
+    /// The control command to send to the media processing thread, if any.
+    fn check_sleep_timer(&mut self) -> Option<MediaControl> {
+        match self.sleep_timer.poll()? {
+            SleepAction::Pause if self.state == State::Running => {
+                self.state = State::Paused;
+                self.paused_by_unfocus = false;
+                Some(MediaControl::PauseContinue)
+            }
+            SleepAction::Pause => None,
+            SleepAction::Quit => {
+                self.state = State::Stopped;
+                Some(MediaControl::Exit)
+            }
         }
+    }
 
-        self.cleanup()?;
-        Ok(())
+    /// Sets how long to wait for user input each time the events are polled. Longer timeouts
+    /// wake the display thread up less often, at the cost of showing frames a bit later.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The poll timeout, zero (the default) to only read pending events.
+    pub fn set_poll_timeout(&mut self, timeout: Duration) {
+        self.poll_timeout = timeout;
     }
 
-    /// Clears the terminal screen and sets the initial terminal state.
+    /// Clears the terminal screen (or the area below the cursor in inline mode) and sets the
+    /// initial terminal state.
     ///
     /// # Errors
     ///
     /// Returns an error if there is an issue with the terminal operations.
     fn clear(&self) -> IOResult<()> {
+        let clear_type = match self.inline_rows {
+            Some(_) => ClearType::FromCursorDown,
+            None => ClearType::All,
+        };
         execute!(
             stdout(),
-            Clear(ClearType::All),
+            MoveTo(0, self.origin),
+            Clear(clear_type),
             Hide,
-            SetForegroundColor(self.fg_color),
-            SetBackgroundColor(self.bg_color),
-            MoveTo(0, 0),
+            SetForegroundColor(self.color_depth.reduce(self.fg_color)),
+            SetBackgroundColor(self.color_depth.reduce(self.bg_color)),
+            MoveTo(0, self.origin),
         )?;
         stdout().flush()?;
         Ok(())
@@ -138,7 +1169,23 @@ impl Terminal {
     /// # Errors
     ///
     /// Returns an error if there is an issue with the terminal operations.
-    fn cleanup(&self) -> IOResult<()> {
+    fn restore(&mut self) -> IOResult<()> {
+        if let Some(inhibitor) = &mut self.inhibitor {
+            inhibitor.set_inhibited(false);
+        }
+        suspend::release_stop_signal();
+        if self.pause_on_unfocus {
+            execute!(stdout(), DisableFocusChange)?;
+        }
+        if self.captures_mouse() {
+            execute!(stdout(), DisableMouseCapture)?;
+        }
+        if let Some(rows) = self.inline_rows {
+            // Leave the last frame on screen, and the cursor below it
+            execute!(stdout(), ResetColor, MoveTo(0, self.origin + rows), Show)?;
+            terminal::disable_raw_mode()?;
+            return Ok(());
+        }
         // Restore terminal state
         execute!(
             stdout(),
@@ -151,42 +1198,166 @@ impl Terminal {
         Ok(())
     }
 
+    /// Suspends tplay to the shell, paused: restores the terminal, stops until the shell continues
+    /// tplay, then sets the terminal up again and draws the frame in full.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the control commands: the pause if the playback was running (e.g.
+    /// suspended with `SIGTSTP`), and the resize if the terminal was resized meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the terminal operations.
+    fn suspend(&mut self) -> Result<Vec<MediaControl>, MyError> {
+        let mut controls = Vec::new();
+        if self.state == State::Running {
+            self.state = State::Paused;
+            controls.push(MediaControl::PauseContinue);
+        }
+        let size = terminal::size()?;
+        self.restore()?;
+        suspend::stop();
+        self.init()?;
+        if self.captures_mouse() {
+            execute!(stdout(), EnableMouseCapture)?;
+        }
+        self.printed_frame = None;
+        let (width, height) = terminal::size()?;
+        if (width, height) != size {
+            controls.extend(self.handle_event(Event::Resize(width, height)));
+        } else if let Some(frame) = self.shown_frame.clone() {
+            self.draw(&frame)?;
+        }
+        Ok(controls)
+    }
+
     /// Draws the current frame of the animation in the terminal.
     ///
-    /// This function takes a reference to a `StringInfo` tuple containing the string representation
-    /// of the current frame and its associated RGB data. It either prints the string as-is (in grayscale)
-    /// or generates a colored string based on the RGB data and then prints it to the terminal.
+    /// Each row of the frame is printed at the start of its own line, so that the layout does not
+    /// depend on the terminal wrapping long lines. The glyphs are printed as-is (in grayscale) or
+    /// colored with the color of their cell. Frames holding pixels are drawn as a Sixel image.
+    ///
+    /// When the screen still shows the previous frame, printed at the same place and the same way,
+    /// only the runs of cells that changed since are printed, after moving the cursor to them.
     ///
     /// # Arguments
     ///
-    /// * `string_info` - A reference to the `StringInfo` tuple containing the string representation
-    ///                   of the current frame and its associated RGB data.
+    /// * `frame` - The grid of glyphs and colors of the current frame.
+    /// * `(x, top)` - The column and row of the top left corner of the frame.
+    /// * `previous` - The previous frame, if the screen still shows it.
     ///
     /// # Errors
     ///
     /// Returns an error if there is an issue with the terminal operations.
-    fn draw(&self, (string, rgb_data): &StringInfo) -> IOResult<()> {
-        let print_string = |string: &str| {
-            let mut out = stdout();
-            execute!(out, MoveTo(0, 0), Print(string), MoveTo(0, 0))?;
-            out.flush()?;
-            Ok(())
+    fn print_frame(
+        &mut self,
+        frame: &CellGrid,
+        (x, top): (u16, u16),
+        previous: Option<&PrintedFrame>,
+    ) -> IOResult<()> {
+        // Backgrounds draw the picture itself, they are kept in grayscale mode
+        let grayscale = Theme::grayscale();
+        let theme = match self.shows_grayscale() {
+            true if frame.has_backgrounds() => Some(&grayscale),
+            true => None,
+            false => self.themes.get_index(self.theme),
         };
-
-        if self.use_grayscale {
-            print_string(string)
-        } else {
-            let mut colored_string = String::with_capacity(string.len() * 10);
-            for (c, rgb) in string.chars().zip(rgb_data.chunks(3)) {
-                let color = Color::Rgb {
-                    r: rgb[0],
-                    g: rgb[1],
-                    b: rgb[2],
-                };
-                colored_string.push_str(&format!("{}", c.stylize().with(color)));
+        // The whole frame is written to the terminal at once, from a buffer reused across frames
+        let mut out = std::mem::take(&mut self.frame_buffer);
+        out.clear();
+        if let Some(pixels) = frame.pixels() {
+            let sixel = if self.shows_grayscale() {
+                sixel::encode(&DynamicImage::ImageRgb8(pixels.clone()).grayscale().into_rgb8())
+            } else {
+                sixel::encode(pixels)
+            };
+            queue!(out, MoveTo(x, top), Print(sixel), MoveTo(0, self.origin))?;
+            return self.write_frame_buffer(out);
+        }
+        // Colored glyphs restore the default colors, set those of the glyphs printed as-is
+        queue!(
+            out,
+            SetForegroundColor(self.color_depth.reduce(self.fg_color)),
+            SetBackgroundColor(self.color_depth.reduce(self.bg_color))
+        )?;
+        let style = (self.shows_grayscale(), self.theme);
+        let previous = previous.filter(|previous| previous.matches(frame, (x, top), style));
+        for y in 0..frame.height() {
+            let spans = match previous {
+                Some(previous) => previous.changed_spans(frame, y),
+                None => {
+                    let row = 0..frame.width();
+                    vec![row]
+                }
+            };
+            for span in spans {
+                // The glyphs before the span may span several columns, e.g. emoji
+                let offset: u32 = frame
+                    .row(y)
+                    .take(span.start as usize)
+                    .map(|cell| glyph_width::width(cell.glyph))
+                    .sum();
+                let column = x.saturating_add(offset as u16);
+                queue!(out, MoveTo(column, top.saturating_add(y as u16)))?;
+                let mut cells = frame.row(y).skip(span.start as usize).take(span.len());
+                match theme {
+                    Some(theme) => write_colored(&mut out, cells, theme, self.color_depth)?,
+                    None => cells.try_for_each(|cell| out.write_all(cell.glyph.as_bytes()))?,
+                }
             }
-            print_string(&colored_string)
         }
+        queue!(out, MoveTo(0, self.origin))?;
+        self.write_frame_buffer(out)
+    }
+
+    /// Returns whether the frames are printed in grayscale: in grayscale mode, once the colors
+    /// faded to gray.
+    fn shows_grayscale(&self) -> bool {
+        self.use_grayscale && self.grayscale_fade.is_none()
+    }
+
+    /// Fades the colors of a frame while a switch of grayscale mode is animated, and moves the
+    /// fade to its next frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame.
+    ///
+    /// # Returns
+    ///
+    /// The faded frame, or `None` if no switch is animated.
+    fn fade_grayscale(&mut self, frame: &CellGrid) -> Option<CellGrid> {
+        let fade = self.grayscale_fade.as_mut()?;
+        let progress = fade.advance();
+        if fade.is_over() {
+            self.grayscale_fade = None;
+        }
+        let amount = if self.use_grayscale {
+            progress
+        } else {
+            1.0 - progress
+        };
+        Some(fade_to_gray(frame, amount, false))
+    }
+
+    /// Writes a frame built in the frame buffer to the terminal with a single write, and keeps the
+    /// buffer for the next frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The frame buffer, holding the glyphs and escape sequences of the frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an issue with the terminal operations.
+    fn write_frame_buffer(&mut self, buffer: Vec<u8>) -> IOResult<()> {
+        let result = {
+            let mut out = stdout().lock();
+            out.write_all(&buffer).and_then(|_| out.flush())
+        };
+        self.frame_buffer = buffer;
+        result
     }
 
     /// Handles user input events such as pausing/continuing, resizing, and
@@ -196,97 +1367,798 @@ impl Terminal {
     ///
     /// * `event` - The event received from the terminal.
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Returns an error if there is an issue with the terminal operations.
-    fn handle_event(&mut self, event: Event) -> IOResult<()> {
+    /// The control command to send to the media processing thread, if any.
+    fn handle_event(&mut self, event: Event) -> Option<MediaControl> {
+        if self.char_map_editor.is_some() {
+            if let Event::Key(key) = event {
+                return self.edit_char_map(&key);
+            }
+        }
+        if self.prompt.is_some() {
+            if let Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) = event
+            {
+                if !modifiers.contains(event::KeyModifiers::CONTROL) {
+                    return self.edit_prompt(code);
+                }
+            }
+        }
+        if self.scene_index_open {
+            if let Event::Key(KeyEvent { code, .. }) = event {
+                if let Some(navigation) = self.navigate_scene_index(code) {
+                    return Some(MediaControl::SceneIndex(navigation));
+                }
+            }
+        }
         match event {
-            // Quit
             Event::Key(KeyEvent {
-                code: KeyCode::Char('q') | KeyCode::Char('Q'),
-                ..
-            })
-            | Event::Key(KeyEvent {
-                code: KeyCode::Char('c') | KeyCode::Char('C'),
-                modifiers: event::KeyModifiers::CONTROL,
-                ..
-            })
-            | Event::Key(KeyEvent {
                 code: KeyCode::Esc, ..
-            }) => {
-                self.state = State::Stopped;
-                self.send_control(MediaControl::Exit)?;
+            }) if self.help_open => {
+                self.help_open = false;
+                Some(MediaControl::Redraw)
             }
 
-            // Pause/Continue
+            // Pan the viewport of 360° media, unless the arrow keys seek along the seek bar
             Event::Key(KeyEvent {
-                code: KeyCode::Char(' '),
+                code: code @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down),
                 ..
-            }) => {
-                self.send_control(MediaControl::PauseContinue)?;
+            }) if self.panorama && !self.seek_bar_open => Some(match code {
+                KeyCode::Left => MediaControl::Pan(-PAN_STEP, 0.0),
+                KeyCode::Right => MediaControl::Pan(PAN_STEP, 0.0),
+                KeyCode::Up => MediaControl::Pan(0.0, PAN_STEP),
+                _ => MediaControl::Pan(0.0, -PAN_STEP),
+            }),
+
+            // Pause while the terminal is not focused
+            Event::FocusLost if self.pause_on_unfocus && self.state == State::Running => {
+                self.state = State::Paused;
+                self.paused_by_unfocus = true;
+                Some(MediaControl::PauseContinue)
+            }
+            Event::FocusGained if self.paused_by_unfocus && self.state == State::Paused => {
+                self.state = State::Running;
+                self.paused_by_unfocus = false;
+                Some(MediaControl::PauseContinue)
+            }
+
+            // Resize, once the size settles (see `poll_controls`)
+            Event::Resize(width, height) => {
+                // The terminal may have moved or cut what it showed
+                self.printed_frame = None;
+                let resize = match self.inline_rows {
+                    Some(rows) => {
+                        // Keep the inline area on screen
+                        let rows = rows.min(height.saturating_sub(1)).max(1);
+                        self.origin = self.origin.min(height.saturating_sub(rows + 1));
+                        self.inline_rows = Some(rows);
+                        let (width, rows) = self.frame_area(width, rows);
+                        MediaControl::Resize(width, rows)
+                    }
+                    None => {
+                        let (width, height) = self.drawing_area(width, height);
+                        MediaControl::Resize(width, height)
+                    }
+                };
+                self.pending_resize = Some((resize, Instant::now()));
+                None
+            }
+
+            Event::Key(key) => self
+                .keymap
+                .action(&key)
+                .and_then(|action| self.perform(action)),
+
+            // Seek where the seek bar is clicked, or dragged along
+            Event::Mouse(MouseEvent {
+                kind:
+                    MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) if self.seek_bar_open => self.seek_at(column, row),
+
+            // Read the color under the mouse
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Moved | MouseEventKind::Drag(_),
+                column,
+                row,
+                ..
+            }) if self.eyedropper_open && self.hovered != Some((column, row)) => {
+                self.hovered = Some((column, row));
+                Some(MediaControl::Redraw)
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Performs the action bound to a key. Actions that do not apply to the media (e.g. the next
+    /// track outside of a playlist) do nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - The action.
+    ///
+    /// # Returns
+    ///
+    /// The control command to send to the media processing thread, if any.
+    fn perform(&mut self, action: Action) -> Option<MediaControl> {
+        match action {
+            // Open the scene index
+            Action::SceneIndex if self.has_scene_index => {
+                self.scene_index_open = true;
+                Some(MediaControl::SceneIndex(SceneNavigation::Toggle))
+            }
+
+            // Show or hide the help
+            Action::Help => {
+                self.help_open = !self.help_open;
+                Some(MediaControl::Redraw)
+            }
+
+            // Show or hide the performance stats
+            Action::Stats => {
+                self.stats_open = !self.stats_open;
+                Some(MediaControl::Redraw)
+            }
+
+            // Cycle the subtitle tracks, naming the track in a toast
+            Action::Subtitles if self.subtitles.is_some() => {
+                if let Some(track) = self.subtitles.as_mut().map(Subtitles::cycle) {
+                    self.toasts.push(track, ToastLevel::Info);
+                }
+                Some(MediaControl::Redraw)
+            }
+
+            // Open the command line
+            Action::CommandLine => {
+                self.prompt = Some(String::new());
+                Some(MediaControl::Redraw)
+            }
+
+            Action::Quit => {
+                self.state = State::Stopped;
+                Some(MediaControl::Exit)
+            }
+
+            // Next/previous track
+            Action::NextTrack | Action::PreviousTrack if self.in_playlist => {
+                self.state = State::Stopped;
+                let step = if action == Action::NextTrack { 1 } else { -1 };
+                Some(MediaControl::ChangeTrack(step))
+            }
+
+            Action::Pause => {
                 self.state = match self.state {
                     State::Running => State::Paused,
                     State::Paused => State::Running,
                     State::Stopped => State::Stopped,
                 };
+                // Pausing or resuming by hand takes precedence over the focus
+                self.paused_by_unfocus = false;
+                Some(MediaControl::PauseContinue)
             }
 
-            // Resize
-            Event::Resize(width, height) => {
-                self.send_control(MediaControl::Resize(width, height))?;
-                // Drain buffer
-                while self
-                    .rx_buffer
-                    .recv_timeout(Duration::from_millis(1))
-                    .is_ok()
-                { /* Do nothing */ }
+            Action::SeekBackward | Action::SeekForward => {
+                let step = match action {
+                    Action::SeekForward => SEEK_STEP,
+                    _ => -SEEK_STEP,
+                };
+                self.toasts
+                    .push(format!("seek {step:+}s"), ToastLevel::Info);
+                Some(MediaControl::SeekBy(step))
             }
 
-            // Change character map
-            Event::Key(KeyEvent {
-                code: KeyCode::Char(digit),
-                ..
-            }) if digit.is_ascii_digit() => {
-                self.send_control(MediaControl::SetCharMap(digit.to_digit(10).unwrap_or_else(
-                    || panic!("{error}: {digit:?}", error = ERROR_PARSE_DIGIT_FAILED),
-                )))?;
+            // Show or hide the seek bar, the mouse is only captured while it is shown so that the
+            // text of the terminal can be selected otherwise
+            Action::SeekBar => {
+                let captured = self.captures_mouse();
+                self.seek_bar_open = !self.seek_bar_open;
+                self.update_mouse_capture(captured);
+                Some(MediaControl::Redraw)
+            }
+
+            // Turn the eyedropper on or off, capturing the mouse meanwhile like the seek bar
+            Action::Eyedropper => {
+                let captured = self.captures_mouse();
+                self.eyedropper_open = !self.eyedropper_open;
+                self.hovered = None;
+                self.update_mouse_capture(captured);
+                let state = if self.eyedropper_open { "on" } else { "off" };
+                self.toasts
+                    .push(format!("eyedropper {state}"), ToastLevel::Info);
+                Some(MediaControl::Redraw)
+            }
+
+            // Change character map, naming it in a toast
+            Action::CharMap(char_map) => {
+                self.char_map = Some(char_map);
+                self.typed_char_map = None;
+                self.notify_char_map(char_map);
+                Some(MediaControl::SetCharMap(char_map))
+            }
+            Action::NextCharMap | Action::PreviousCharMap => {
+                let step = if action == Action::NextCharMap { 1 } else { -1 };
+                let char_map = self.char_map.unwrap_or(0) as i64 + step;
+                let char_map = char_map.rem_euclid(self.char_maps.len().max(1) as i64) as u32;
+                self.char_map = Some(char_map);
+                self.typed_char_map = None;
+                self.notify_char_map(char_map);
+                Some(MediaControl::SetCharMap(char_map))
             }
 
             // Toggle grayscale mode
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('g') | KeyCode::Char('G'),
-                ..
-            }) => {
+            Action::Grayscale => {
                 self.use_grayscale = !self.use_grayscale;
-                self.send_control(MediaControl::SetGrayscale(self.use_grayscale))?;
+                if self.switch_transition > 0 {
+                    self.grayscale_fade = Some(Transition::new(self.switch_transition));
+                }
+                let state = if self.use_grayscale { "on" } else { "off" };
+                self.toasts
+                    .push(format!("grayscale {state}"), ToastLevel::Info);
+                Some(MediaControl::SetGrayscale(self.use_grayscale))
             }
 
-            // Mute/unmute
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('m') | KeyCode::Char('M'),
-                ..
-            }) => {
-                self.send_control(MediaControl::MuteUnmute)?;
+            // Cycle color themes, naming the theme in a toast
+            Action::Theme => {
+                self.theme = (self.theme + 1) % self.themes.len().max(1);
+                if let Some(theme) = self.themes.get_index(self.theme) {
+                    self.toasts
+                        .push(format!("theme {}", theme.name), ToastLevel::Info);
+                }
+                Some(MediaControl::Redraw)
             }
 
-            _ => {}
+            Action::Mute => Some(MediaControl::MuteUnmute),
+            Action::VolumeUp => Some(MediaControl::ChangeVolume(VOLUME_STEP)),
+            Action::VolumeDown => Some(MediaControl::ChangeVolume(-VOLUME_STEP)),
+            Action::Braille => Some(MediaControl::ToggleBraille),
+            Action::Background => Some(MediaControl::ToggleBackground),
+            Action::Dither => Some(MediaControl::CycleDither),
+
+            // Cycle the luma formulas, naming the formula in a toast
+            Action::Luma => {
+                self.luma = self.luma.next();
+                self.toasts
+                    .push(format!("luma {}", self.luma), ToastLevel::Info);
+                Some(MediaControl::SetLuma(self.luma))
+            }
+
+            // Flip or rotate the source frames
+            Action::FlipHorizontal => {
+                Some(MediaControl::Transform(TransformChange::FlipHorizontal))
+            }
+            Action::FlipVertical => Some(MediaControl::Transform(TransformChange::FlipVertical)),
+            Action::Rotate => Some(MediaControl::Transform(TransformChange::Rotate)),
+
+            // Open the character map editor on the current character map, pausing the playback
+            Action::EditCharMap => {
+                let index = self.char_map.unwrap_or(0);
+                let map = self.char_maps.get(index as usize)?;
+                let resume = self.state == State::Running;
+                // The editor renders the character map it edits
+                self.typed_char_map = None;
+                self.char_map_editor = Some(CharMapEditor::new(
+                    index,
+                    &map.name,
+                    map.chars.clone(),
+                    resume,
+                ));
+                self.help_open = false;
+                if resume {
+                    self.state = State::Paused;
+                    self.paused_by_unfocus = false;
+                    self.queued.push(MediaControl::PauseContinue);
+                }
+                // Rendered with the character map, even if braille dots or blocks were shown
+                Some(MediaControl::EditCharMap(index, map.chars.clone()))
+            }
+
+            // Save the frame on screen, naming the file in a toast
+            Action::Screenshot => {
+                let saved = self.save_screenshot();
+                self.toasts
+                    .push_result(saved.map(|path| format!("saved {}", path.display())));
+                Some(MediaControl::Redraw)
+            }
+
+            // Copy the frame on screen, telling how in a toast
+            Action::Copy => {
+                let copied = self.copy_frame();
+                self.toasts.push_result(
+                    copied.map(|method| format!("copied the frame to the clipboard ({method})")),
+                );
+                Some(MediaControl::Redraw)
+            }
+
+            // Pause, and suspend to the shell once the pause is sent (see `poll_controls`)
+            Action::Suspend if !suspend::is_supported() => {
+                self.toasts
+                    .push(ERROR_SUSPEND.to_string(), ToastLevel::Error);
+                Some(MediaControl::Redraw)
+            }
+            Action::Suspend => {
+                self.suspend_pending = true;
+                self.paused_by_unfocus = false;
+                (self.state == State::Running).then(|| {
+                    self.state = State::Paused;
+                    MediaControl::PauseContinue
+                })
+            }
+
+            // Clear the screen, e.g. after another program wrote over it, and print the last
+            // frame again in full
+            Action::Refresh => {
+                let _ = self.clear();
+                self.printed_frame = None;
+                if let Some(frame) = self.shown_frame.clone() {
+                    let _ = self.draw(&frame);
+                }
+                None
+            }
+
+            Action::SceneIndex | Action::Subtitles | Action::NextTrack | Action::PreviousTrack => {
+                None
+            }
         }
-        Ok(())
     }
 
-    /// Sends a control command to the media processing thread.
+    /// Returns the line of the seek bar drawn over a frame while it is shown, in a box over the
+    /// bottom of the frame as wide as the frame.
     ///
     /// # Arguments
     ///
-    /// * `control` - The control command to send.
+    /// * `frame` - The frame, which the bar is drawn over.
+    /// * `(x, y)` - The column and row of the top left corner of the frame on screen.
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Returns an error if there is an issue with the terminal operations.
-    /// or communication with the pipeline.
-    fn send_control(&self, control: MediaControl) -> Result<(), MyError> {
-        self.tx_control
-            .send(control)
-            .map_err(|e| MyError::Terminal(format!("{error}: {e:?}", error = ERROR_CHANNEL, e = e)))
+    /// The text of the bar, and the column and row of its first character on screen and its
+    /// width, or `None` if the bar is hidden, the media cannot be seeked or the frame is too small.
+    fn seek_bar_line(
+        &self,
+        frame: &CellGrid,
+        (x, y): (u16, u16),
+    ) -> Option<(String, (u16, u16, usize))> {
+        if !self.seek_bar_open || frame.height() < 3 {
+            return None;
+        }
+        // The border and a column of padding on each side
+        let width = frame.width().saturating_sub(4) as usize;
+        let text = SeekBar::new(self.progress)?.text(width)?;
+        let row = y.saturating_add(frame.height() as u16 - 2);
+        Some((text, (x + 2, row, width)))
+    }
+
+    /// Returns whether the mouse is captured: while the seek bar is shown or the eyedropper is on.
+    fn captures_mouse(&self) -> bool {
+        self.seek_bar_open || self.eyedropper_open
+    }
+
+    /// Enables or disables the capture of the mouse, if it changed (see `captures_mouse`).
+    ///
+    /// # Arguments
+    ///
+    /// * `captured` - Whether the mouse was captured before.
+    fn update_mouse_capture(&self, captured: bool) {
+        let _ = match (captured, self.captures_mouse()) {
+            (false, true) => execute!(stdout(), EnableMouseCapture),
+            (true, false) => execute!(stdout(), DisableMouseCapture),
+            _ => Ok(()),
+        };
+    }
+
+    /// Seeks to the position under the mouse, when it is on the seek bar.
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - The column of the mouse on screen.
+    /// * `row` - The row of the mouse on screen.
+    ///
+    /// # Returns
+    ///
+    /// The seek command, if the mouse is on the bar.
+    fn seek_at(&self, column: u16, row: u16) -> Option<MediaControl> {
+        let (x, y, width) = self.seek_bar_area?;
+        if row != y || column < x {
+            return None;
+        }
+        let position = SeekBar::new(self.progress)?.position_at(width, (column - x) as usize)?;
+        Some(MediaControl::Seek(position))
+    }
+
+    /// Maps the keys navigating the open scene index to their command. Other keys keep their
+    /// usual function.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The key pressed.
+    ///
+    /// # Returns
+    ///
+    /// The navigation command, if the key navigates the index.
+    fn navigate_scene_index(&mut self, code: KeyCode) -> Option<SceneNavigation> {
+        let navigation = match code {
+            KeyCode::Tab => SceneNavigation::Toggle,
+            KeyCode::Esc => SceneNavigation::Close,
+            KeyCode::Enter => SceneNavigation::Select,
+            KeyCode::Left => SceneNavigation::Move(-1, 0),
+            KeyCode::Right => SceneNavigation::Move(1, 0),
+            KeyCode::Up => SceneNavigation::Move(0, -1),
+            KeyCode::Down => SceneNavigation::Move(0, 1),
+            _ => return None,
+        };
+        if !matches!(navigation, SceneNavigation::Move(..)) {
+            self.scene_index_open = false;
+        }
+        Some(navigation)
+    }
+}
+
+/// Lists the character maps the media pipeline cycles through: the character map the playback
+/// starts with, then those of the registry.
+///
+/// # Arguments
+///
+/// * `registry` - The character maps.
+/// * `char_map` - The character map the playback starts with, a name or the characters.
+fn char_map_list(registry: &CharMapRegistry, char_map: &str) -> Vec<CharMap> {
+    std::iter::once(registry.named(char_map))
+        .chain(registry.iter().cloned())
+        .collect()
+}
+
+/// Adds the escape sequences coloring each glyph of a row of cells, and the background of the
+/// cells having one (in shades of gray with themes without colors).
+///
+/// # Arguments
+///
+/// * `cells` - The cells to color, e.g. a row of a `CellGrid`.
+/// * `theme` - The color theme deciding the color of each character.
+/// * `color_depth` - The colors the terminal can show, see `ColorDepth::resolve`.
+///
+/// # Returns
+///
+/// The colored string, ready to be printed.
+pub fn colorize<'a>(
+    cells: impl IntoIterator<Item = Cell<'a>>,
+    theme: &Theme,
+    color_depth: ColorDepth,
+) -> String {
+    let mut colored = Vec::new();
+    // Writing to a vector cannot fail
+    let _ = write_colored(&mut colored, cells, theme, color_depth);
+    String::from_utf8_lossy(&colored).into_owned()
+}
+
+/// Writes a row of cells with the escape sequences coloring them, like `colorize`, without
+/// allocating: the sequences are written straight to `out`, e.g. the buffer holding a whole
+/// frame. A color is only set when it differs from the color of the previous glyph, and the
+/// default colors are restored after the last glyph.
+///
+/// # Arguments
+///
+/// * `out` - Where the colored cells are written.
+/// * `cells` - The cells to color, e.g. a row of a `CellGrid`.
+/// * `theme` - The color theme deciding the color of each character.
+/// * `color_depth` - The colors the terminal can show, see `ColorDepth::resolve`.
+///
+/// # Errors
+///
+/// Returns an error if `out` cannot be written to.
+pub fn write_colored<'a>(
+    out: &mut impl Write,
+    cells: impl IntoIterator<Item = Cell<'a>>,
+    theme: &Theme,
+    color_depth: ColorDepth,
+) -> IOResult<()> {
+    // The colors set by the last escape sequences, `None` for the default colors
+    let (mut fg, mut bg) = (None, None);
+    for Cell {
+        glyph,
+        color: [r, g, b],
+        background,
+        transparent,
+    } in cells
+    {
+        let color = match transparent {
+            true => None,
+            false => theme.color((r, g, b)).map(|color| color_depth.color(color)),
+        };
+        let background =
+            background.map(|[r, g, b]| color_depth.color(theme.background_color((r, g, b))));
+        if color != fg {
+            queue!(out, SetForegroundColor(color.unwrap_or(Color::Reset)))?;
+            fg = color;
+        }
+        if background != bg {
+            queue!(out, SetBackgroundColor(background.unwrap_or(Color::Reset)))?;
+            bg = background;
+        }
+        out.write_all(glyph.as_bytes())?;
+    }
+    if fg.is_some() {
+        queue!(out, SetForegroundColor(Color::Reset))?;
+    }
+    if bg.is_some() {
+        queue!(out, SetBackgroundColor(Color::Reset))?;
+    }
+    Ok(())
+}
+
+impl Display for Terminal {
+    /// Enters the alternate screen and raw mode, and clears the terminal. In inline mode, the
+    /// drawing area is reserved below the cursor instead.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the size of the drawing area.
+    fn init(&mut self) -> Result<(u16, u16), MyError> {
+        suspend::catch_stop_signal();
+        if self.pause_on_unfocus {
+            execute!(stdout(), EnableFocusChange)?;
+        }
+        if let Some(rows) = self.inline_rows {
+            // The cursor position can only be read in raw mode
+            terminal::enable_raw_mode()?;
+            let (width, rows) = self.reserve_inline_rows(rows)?;
+            self.clear()?;
+            return Ok(self.frame_area(width, rows));
+        }
+        execute!(stdout(), EnterAlternateScreen, SetTitle(&self.title))?;
+        terminal::enable_raw_mode()?;
+
+        // Clear screen
+        self.clear()?;
+
+        let (width, height) = terminal::size()?;
+        Ok(self.drawing_area(width, height))
+    }
+
+    /// Reads the pending terminal events and converts them to control commands.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the control commands.
+    fn poll_controls(&mut self) -> Result<Vec<MediaControl>, MyError> {
+        let mut controls = Vec::new();
+        if std::mem::take(&mut self.suspend_pending) || suspend::take_stop_request() {
+            controls.extend(self.suspend()?);
+        }
+        // Poll and handle events, only waiting for the first one
+        let mut timeout = self.poll_timeout;
+        while event::poll(timeout)? {
+            let ev = event::read()?;
+            controls.extend(self.handle_event(ev));
+            timeout = Duration::ZERO;
+        }
+        controls.append(&mut self.queued);
+        if let Some((_, changed)) = &self.pending_resize {
+            if changed.elapsed() >= RESIZE_DEBOUNCE {
+                controls.extend(self.pending_resize.take().map(|(resize, _)| resize));
+            }
+        }
+        controls.extend(self.check_sleep_timer());
+        if let Some(stats) = self.rx_stats.as_ref().and_then(|rx| rx.try_iter().last()) {
+            self.pipeline_stats = stats;
+        }
+        let feedback: Vec<Feedback> = self
+            .rx_feedback
+            .as_ref()
+            .map_or_else(Vec::new, |rx| rx.try_iter().collect());
+        for feedback in feedback {
+            match feedback {
+                Feedback::Audio(AudioFeedback::Volume(volume)) => {
+                    self.osd = Some(Osd::volume(volume))
+                }
+                Feedback::Audio(AudioFeedback::Unavailable) => {
+                    let message = "no audio: the media has none, or no audio backend is available";
+                    self.toasts.push(message.to_string(), ToastLevel::Error);
+                    controls.push(MediaControl::Redraw);
+                }
+                // Confirms the pauses sent by the terminal, and brings the pauses sent by the
+                // remote, a gamepad or a watch-together session to the status line and keys
+                Feedback::Paused(paused) => {
+                    let state = match paused {
+                        true => State::Paused,
+                        false => State::Running,
+                    };
+                    if self.state != State::Stopped && self.state != state {
+                        self.state = state;
+                        self.paused_by_unfocus = false;
+                        controls.push(MediaControl::Redraw);
+                    }
+                }
+            }
+        }
+        // Draw the frame again without the toasts shown long enough, as for the fade
+        if self.toasts.expire(Instant::now()) && self.state != State::Running {
+            controls.push(MediaControl::Redraw);
+        }
+        // Draw each step of the fade of the on-screen display, which the frames of a running
+        // playback do anyway
+        let osd_step = self.osd.as_ref().and_then(|osd| osd.step(Instant::now()));
+        if osd_step.is_none() {
+            self.osd = None;
+        }
+        if self.state != State::Running && self.osd_step != osd_step {
+            self.osd_step = osd_step;
+            controls.push(MediaControl::Redraw);
+        }
+        if let Some(inhibitor) = &mut self.inhibitor {
+            inhibitor.set_inhibited(self.state == State::Running);
+        }
+        // Refresh the status bar when its text changes, e.g. every second of the countdown
+        let status = self.status_text();
+        if controls.is_empty() && self.status != status {
+            self.status = status;
+            controls.push(MediaControl::Redraw);
+        }
+        Ok(controls)
+    }
+
+    fn draw(&mut self, frame: &CellGrid) -> Result<(), MyError> {
+        if self.pending_resize.is_some() {
+            // The frame would wrap at the new width, the frames of the new size follow
+            return Ok(());
+        }
+        let draw_start = Instant::now();
+        self.draw_rate.record();
+        let (x, y) = self.frame_origin();
+        let mut origin = (x, y);
+        let faded = self.fade_grayscale(frame);
+        let frame = faded.as_ref().unwrap_or(frame);
+        self.shown_frame = Some(frame.clone());
+        let mut composed = None;
+        let seek_bar = self.seek_bar_line(frame, (x, y));
+        self.seek_bar_area = seek_bar.as_ref().map(|(_, area)| *area);
+        if frame.pixels().is_none() {
+            let subtitles = self.subtitles.as_ref().zip(self.playback_clock.position());
+            let lines = subtitles.map_or_else(Vec::new, |(subtitles, position)| {
+                subtitles.lines_at(position)
+            });
+            if !lines.is_empty() {
+                composed = Some(overlay::subtitles(frame, &lines));
+            }
+            if let Some((line, _)) = &seek_bar {
+                let under = composed.as_ref().unwrap_or(frame);
+                composed = Some(overlay::compose(
+                    under,
+                    std::slice::from_ref(line),
+                    Anchor::Bottom,
+                ));
+            }
+            if self.stats_open {
+                composed = Some(overlay::compose(
+                    composed.as_ref().unwrap_or(frame),
+                    &self.stats_lines(),
+                    Anchor::TopLeft,
+                ));
+            }
+            if let Some(osd) = &self.osd {
+                let now = Instant::now();
+                let under = composed.as_ref().unwrap_or(frame);
+                let over = overlay::compose(under, osd.lines(), Anchor::TopRight);
+                composed = Some(overlay::blend(under, &over, osd.opacity(now)));
+                self.osd_step = osd.step(now);
+            }
+            if !self.toasts.is_empty() {
+                // Under the box of the on-screen display, if any
+                let row = self
+                    .osd
+                    .as_ref()
+                    .map_or(0, |osd| osd.lines().len() as u32 + 2);
+                let under = composed.as_ref().unwrap_or(frame);
+                composed = Some(overlay::compose(
+                    under,
+                    &self.toasts.lines(),
+                    Anchor::Right(row),
+                ));
+            }
+            if self.help_open {
+                let under = composed.as_ref().unwrap_or(frame);
+                composed = Some(overlay::compose(under, &self.help_lines(), Anchor::Center));
+            }
+            if let Some(editor) = &self.char_map_editor {
+                let under = composed.as_ref().unwrap_or(frame);
+                composed = Some(overlay::compose(under, &editor.lines(), Anchor::Bottom));
+            }
+            if self.border() > 0 {
+                composed = Some(overlay::ambilight(composed.as_ref().unwrap_or(frame)));
+                origin = (x - 1, y - 1);
+            }
+        }
+        // The status bar is printed over the last row of the frame, a new text can leave cells of
+        // the previous one that the frame must cover again
+        if self.status_text() != self.status {
+            self.printed_frame = None;
+        }
+        let printed = composed.unwrap_or_else(|| frame.clone());
+        let previous = self.printed_frame.take();
+        self.print_frame(&printed, origin, previous.as_ref())?;
+        if printed.pixels().is_none() {
+            let style = (self.shows_grayscale(), self.theme);
+            self.printed_frame = Some(PrintedFrame::new(printed, origin, style));
+        }
+        if frame.pixels().is_some() {
+            if let Some((line, (column, row, _))) = &seek_bar {
+                let mut out = stdout();
+                queue!(out, MoveTo(*column, *row), Print(line.as_str().reverse()))?;
+                queue!(out, MoveTo(0, self.origin))?;
+                out.flush()?;
+            }
+            if let Some(editor) = &self.char_map_editor {
+                self.print_lines(&editor.lines())?;
+            } else if self.help_open {
+                self.print_lines(&self.help_lines())?;
+            } else if self.stats_open {
+                self.print_lines(&self.stats_lines())?;
+            }
+        }
+        if self.latency.record(draw_start.elapsed()) {
+            self.hint_until = Some(Instant::now() + SLOW_TERMINAL_HINT_DURATION);
+        }
+        match self.hint_until {
+            Some(until) if Instant::now() < until => self.print_hint()?,
+            Some(_) => {
+                // Uncover the cells under the hint
+                self.hint_until = None;
+                self.printed_frame = None;
+            }
+            None => {}
+        }
+        if let Some(status_line) = &mut self.status_line {
+            status_line.record_draw();
+        }
+        self.print_status_line()?;
+        self.print_status()?;
+        Ok(())
+    }
+
+    fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+        if let Some(status_line) = &mut self.status_line {
+            status_line.set_progress(progress);
+        }
+    }
+
+    fn set_source(&mut self, source: Option<Arc<DynamicImage>>, area: Option<SourceArea>) {
+        self.source_frame = source;
+        self.source_area = area;
+    }
+
+    fn cleanup(&mut self) -> Result<(), MyError> {
+        Ok(self.restore()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::themes::ThemeColors;
+
+    #[test]
+    fn test_write_colored() {
+        let theme = Theme {
+            name: "color".to_string(),
+            colors: ThemeColors::Source,
+        };
+        let mut row = CellGrid::new(4, 1, false);
+        row.push("a", [255, 0, 0]);
+        row.push("b", [255, 0, 0]);
+        row.push_with_background("c", [0, 0, 255], [0, 255, 0]);
+        row.push("d", [0, 0, 255]);
+        // Each color is only set when it changes
+        assert_eq!(
+            colorize(row.row(0), &theme, ColorDepth::TrueColor),
+            "\x1b[38;2;255;0;0mab\x1b[38;2;0;0;255m\x1b[48;2;0;255;0mc\x1b[49md\x1b[39m"
+        );
+        assert_eq!(
+            colorize(row.row(0), &Theme::grayscale(), ColorDepth::TrueColor),
+            "ab\x1b[48;2;149;149;149mc\x1b[49md"
+        );
     }
 }
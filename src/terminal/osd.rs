@@ -0,0 +1,113 @@
+//! On-screen displays: boxes of text shown over the top right corner of the frame for a moment,
+//! then fading out, e.g. the volume bar when the volume changes or the audio is muted.
+//!
+//! The display is drawn with the frames, so the box fades in steps: the terminal asks for the
+//! frame to be drawn again at each step while the playback is paused.
+use crate::audio::player::Volume;
+use std::time::{Duration, Instant};
+
+/// How long an on-screen display is shown before it fades.
+const SHOW_DURATION: Duration = Duration::from_secs(1);
+/// How long an on-screen display takes to fade out.
+const FADE_DURATION: Duration = Duration::from_millis(400);
+/// The number of steps of the fade.
+const FADE_STEPS: u8 = 8;
+/// The number of cells of the volume bar.
+const VOLUME_BAR_WIDTH: usize = 10;
+
+/// A box of text shown for a moment.
+#[derive(Debug, Clone)]
+pub struct Osd {
+    /// The text of the box, one line per row.
+    lines: Vec<String>,
+    /// When the box was shown.
+    since: Instant,
+}
+
+impl Osd {
+    /// Shows a box of text from now on.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The text of the box, one line per row.
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            lines,
+            since: Instant::now(),
+        }
+    }
+
+    /// Shows the volume of the audio: a bar filled up to the volume level, and the level in
+    /// percent, or `muted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `volume` - The volume of the audio.
+    pub fn volume(volume: Volume) -> Self {
+        let filled = (volume.level.clamp(0.0, 1.0) * VOLUME_BAR_WIDTH as f64).round() as usize;
+        let bar = "█".repeat(filled) + &"░".repeat(VOLUME_BAR_WIDTH - filled);
+        let level = match volume.muted {
+            true => "muted".to_string(),
+            false => format!("{:>4}%", (volume.level * 100.0).round()),
+        };
+        Self::new(vec![format!("vol {bar} {level}")])
+    }
+
+    /// Returns the text of the box.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Returns the step of the fade the box is at.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// `FADE_STEPS` while the box is fully shown, down to 1 as it fades, or `None` once it is gone.
+    pub fn step(&self, now: Instant) -> Option<u8> {
+        let fading = now.saturating_duration_since(self.since + SHOW_DURATION);
+        let left = 1.0 - fading.as_secs_f64() / FADE_DURATION.as_secs_f64();
+        let step = (left * FADE_STEPS as f64).ceil();
+        (step > 0.0).then_some(step as u8)
+    }
+
+    /// Returns how much the box shows, from 0 (gone) to 1 (fully shown).
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn opacity(&self, now: Instant) -> f32 {
+        self.step(now)
+            .map_or(0.0, |step| step as f32 / FADE_STEPS as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osd() {
+        let osd = Osd::volume(Volume {
+            level: 0.7,
+            muted: false,
+        });
+        assert_eq!(osd.lines(), ["vol ███████░░░   70%"]);
+        let muted = Osd::volume(Volume {
+            level: 0.7,
+            muted: true,
+        });
+        assert_eq!(muted.lines(), ["vol ███████░░░ muted"]);
+
+        let at = |ms: u64| osd.since + Duration::from_millis(ms);
+        assert_eq!(osd.step(at(0)), Some(FADE_STEPS));
+        assert_eq!(osd.opacity(at(999)), 1.0);
+        assert_eq!(osd.step(at(1200)), Some(FADE_STEPS / 2));
+        assert_eq!(osd.step(at(1399)), Some(1));
+        assert_eq!(osd.step(at(1400)), None);
+        assert_eq!(osd.opacity(at(2000)), 0.0);
+    }
+}
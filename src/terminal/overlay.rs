@@ -0,0 +1,346 @@
+//! Composes the frames with what is drawn around or over them before they are printed: boxes of
+//! text, e.g. the help listing the keys (`?` or `h`) or the performance stats (`F`), and the
+//! ambient light border (`--ambilight`).
+//!
+//! The box is drawn into a copy of the frame before it is printed, so that the next frames (or the
+//! frame rendered again once the box is closed) restore what it covered. The cells under the box
+//! keep a dimmed copy of their color as background, so that the picture shows through the box.
+//!
+//! Subtitles are drawn the same way without a border, centered over the bottom of the frame.
+//!
+//! Boxes shown for a moment (e.g. the volume, see `osd.rs`) fade out by blending the composed
+//! frame back into the frame under the box.
+//!
+//! The ambient light border surrounds the frame with a row or column of cells painted with the
+//! average color of the nearby edge of the frame, like the lights behind some TV sets.
+use crate::{pipeline::cell_grid::Cell, CellGrid};
+
+/// Color of the text and the border of the box.
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+/// How much of the color of the frame shows through the box, from 0 (opaque black) to 1.
+const SHOW_THROUGH: f32 = 0.25;
+/// How many cells on each side of the nearest edge cell the ambient light averages.
+const GLOW_RADIUS: i64 = 2;
+
+/// Where a box is drawn over the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// In the middle of the frame.
+    Center,
+    /// In the top left corner of the frame.
+    TopLeft,
+    /// In the top right corner of the frame.
+    TopRight,
+    /// Along the right edge of the frame, from the given row, e.g. under another box in the top
+    /// right corner.
+    Right(u32),
+    /// Centered over the bottom of the frame.
+    Bottom,
+}
+
+/// Draws a box of text over a frame. The box is cut to the size of the frame.
+///
+/// # Arguments
+///
+/// * `frame` - The frame, made of cells (frames holding pixels are returned as they are).
+/// * `lines` - The text of the box, one line per row.
+/// * `anchor` - Where the box is drawn.
+///
+/// # Returns
+///
+/// A copy of the frame with the box drawn over it.
+pub fn compose(frame: &CellGrid, lines: &[String], anchor: Anchor) -> CellGrid {
+    if frame.pixels().is_some() {
+        return frame.clone();
+    }
+    let (width, height) = (frame.width(), frame.height());
+    let text_width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as u32;
+    // The border and a column of padding on each side
+    let box_width = (text_width + 4).min(width);
+    let box_height = (lines.len() as u32 + 2).min(height);
+    let (left, top) = match anchor {
+        Anchor::Center => ((width - box_width) / 2, (height - box_height) / 2),
+        Anchor::TopLeft => (0, 0),
+        Anchor::TopRight => (width - box_width, 0),
+        Anchor::Right(row) => (width - box_width, row.min(height - box_height)),
+        Anchor::Bottom => ((width - box_width) / 2, height - box_height),
+    };
+
+    let mut composed = CellGrid::new(width, height, false);
+    let mut glyph = [0; 4];
+    for y in 0..height {
+        for (x, cell) in frame.row(y).enumerate() {
+            let x = x as u32;
+            if x < left || x >= left + box_width || y < top || y >= top + box_height {
+                composed.push_cell(cell);
+                continue;
+            }
+            let (bx, by) = (x - left, y - top);
+            let c = match (bx, by) {
+                (0, 0) => '┌',
+                (0, _) if by == box_height - 1 => '└',
+                (_, 0) if bx == box_width - 1 => '┐',
+                _ if bx == box_width - 1 && by == box_height - 1 => '┘',
+                (0, _) => '│',
+                _ if bx == box_width - 1 => '│',
+                _ if by == 0 || by == box_height - 1 => '─',
+                _ => lines[by as usize - 1]
+                    .chars()
+                    .nth((bx as usize).wrapping_sub(2))
+                    .unwrap_or(' '),
+            };
+            let background = cell
+                .background
+                .unwrap_or(cell.color)
+                .map(|channel| (channel as f32 * SHOW_THROUGH) as u8);
+            composed.push_cell(Cell {
+                glyph: c.encode_utf8(&mut glyph),
+                color: TEXT_COLOR,
+                background: Some(background),
+                transparent: false,
+            });
+        }
+    }
+    composed
+}
+
+/// Draws subtitles over the bottom of a frame, each line centered, keeping the last row of the
+/// frame (where the VU meter is drawn) free. The text is cut to the width of the frame.
+///
+/// # Arguments
+///
+/// * `frame` - The frame, made of cells (frames holding pixels are returned as they are).
+/// * `lines` - The lines of the subtitle.
+///
+/// # Returns
+///
+/// A copy of the frame with the subtitles drawn over it.
+pub fn subtitles(frame: &CellGrid, lines: &[String]) -> CellGrid {
+    if frame.pixels().is_some() || lines.is_empty() {
+        return frame.clone();
+    }
+    let (width, height) = (frame.width(), frame.height());
+    let top = height.saturating_sub(lines.len() as u32 + 1);
+    let mut composed = CellGrid::new(width, height, false);
+    let mut glyph = [0; 4];
+    for y in 0..height {
+        let line = y.checked_sub(top).and_then(|row| lines.get(row as usize));
+        // A column of padding on each side of the text
+        let chars: Vec<char> = line.map_or_else(Vec::new, |line| {
+            format!(" {line} ").chars().take(width as usize).collect()
+        });
+        let left = (width as usize - chars.len()) / 2;
+        for (x, cell) in frame.row(y).enumerate() {
+            let Some(c) = x.checked_sub(left).and_then(|i| chars.get(i)) else {
+                composed.push_cell(cell);
+                continue;
+            };
+            let background = cell
+                .background
+                .unwrap_or(cell.color)
+                .map(|channel| (channel as f32 * SHOW_THROUGH) as u8);
+            composed.push_cell(Cell {
+                glyph: c.encode_utf8(&mut glyph),
+                color: TEXT_COLOR,
+                background: Some(background),
+                transparent: false,
+            });
+        }
+    }
+    composed
+}
+
+/// Blends a frame with a box composed over it, to fade the box in or out. The glyphs of the box
+/// give way to those of the frame halfway.
+///
+/// # Arguments
+///
+/// * `frame` - The frame under the box.
+/// * `composed` - The same frame with the box drawn over it, see `compose`.
+/// * `opacity` - How much the box shows, from 0 (the frame only) to 1 (the box only).
+///
+/// # Returns
+///
+/// The blended frame.
+pub fn blend(frame: &CellGrid, composed: &CellGrid, opacity: f32) -> CellGrid {
+    if opacity <= 0.0 {
+        return frame.clone();
+    }
+    if opacity >= 1.0 || frame.pixels().is_some() || frame.len() != composed.len() {
+        return composed.clone();
+    }
+    let mix = |under: [u8; 3], over: [u8; 3]| {
+        let mut mixed = [0; 3];
+        for ((mixed, under), over) in mixed.iter_mut().zip(under).zip(over) {
+            *mixed = (under as f32 + (over as f32 - under as f32) * opacity) as u8;
+        }
+        mixed
+    };
+    let mut blended = CellGrid::new(frame.width(), frame.height(), false);
+    for (under, over) in frame.cells().zip(composed.cells()) {
+        if under == over {
+            blended.push_cell(under);
+            continue;
+        }
+        let glyph = if opacity >= 0.5 { over } else { under };
+        // The background of the terminal is taken as black
+        let background = match (under.background, over.background) {
+            (None, None) => None,
+            (under, over) => Some(mix(under.unwrap_or([0; 3]), over.unwrap_or([0; 3]))),
+        };
+        blended.push_cell(Cell {
+            color: mix(under.color, over.color),
+            background,
+            ..glyph
+        });
+    }
+    blended
+}
+
+/// Surrounds a frame with a border of cells painted with the average color of the nearby edge
+/// cells of the frame (their background if they have one, their color otherwise).
+///
+/// # Arguments
+///
+/// * `frame` - The frame, made of cells (frames holding pixels are returned as they are).
+///
+/// # Returns
+///
+/// A grid one cell larger than the frame on each side, with the frame in its middle.
+pub fn ambilight(frame: &CellGrid) -> CellGrid {
+    if frame.pixels().is_some() || frame.is_empty() {
+        return frame.clone();
+    }
+    let (width, height) = (frame.width() as i64, frame.height() as i64);
+    let color_at = |x: i64, y: i64| {
+        frame
+            .cell(x as u32, y as u32)
+            .map_or([0; 3], |cell| cell.background.unwrap_or(cell.color))
+    };
+    // Average of the edge cells nearest to a border cell, along the edge it faces
+    let glow = |x: i64, y: i64| {
+        let (cx, cy) = (x.clamp(0, width - 1), y.clamp(0, height - 1));
+        let cells: Vec<[u8; 3]> = if y < 0 || y >= height {
+            let columns = (cx - GLOW_RADIUS).max(0)..=(cx + GLOW_RADIUS).min(width - 1);
+            columns.map(|column| color_at(column, cy)).collect()
+        } else {
+            let rows = (cy - GLOW_RADIUS).max(0)..=(cy + GLOW_RADIUS).min(height - 1);
+            rows.map(|row| color_at(cx, row)).collect()
+        };
+        let mut sum = [0; 3];
+        for color in &cells {
+            for (total, channel) in sum.iter_mut().zip(color) {
+                *total += *channel as usize;
+            }
+        }
+        sum.map(|total| (total / cells.len()) as u8)
+    };
+
+    let mut composed = CellGrid::new(width as u32 + 2, height as u32 + 2, false);
+    for y in -1..=height {
+        if y < 0 || y == height {
+            for x in -1..=width {
+                composed.push_with_background(" ", TEXT_COLOR, glow(x, y));
+            }
+            continue;
+        }
+        composed.push_with_background(" ", TEXT_COLOR, glow(-1, y));
+        for cell in frame.row(y as u32) {
+            composed.push_cell(cell);
+        }
+        composed.push_with_background(" ", TEXT_COLOR, glow(width, y));
+    }
+    composed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        let mut frame = CellGrid::new(9, 5, false);
+        for _ in 0..45 {
+            frame.push("#", [200; 3]);
+        }
+        let lines = ["a".to_string(), "bcd".to_string()];
+        let composed = compose(&frame, &lines, Anchor::Center);
+        assert_eq!(
+            (0..5).map(|y| composed.row_text(y)).collect::<Vec<_>>(),
+            [
+                "#┌─────┐#",
+                "#│ a   │#",
+                "#│ bcd │#",
+                "#└─────┘#",
+                "#########"
+            ]
+        );
+        let inside = composed.cell(2, 1).unwrap();
+        assert_eq!(inside.color, TEXT_COLOR);
+        assert_eq!(inside.background, Some([50; 3]));
+        assert_eq!(composed.cell(0, 0).unwrap().background, None);
+        let composed = compose(&frame, &lines, Anchor::TopLeft);
+        assert_eq!(composed.row_text(0), "┌─────┐##");
+        let composed = compose(&frame, &lines, Anchor::Bottom);
+        assert_eq!(composed.row_text(4), "#└─────┘#");
+        let composed = compose(&frame, &lines, Anchor::TopRight);
+        assert_eq!(composed.row_text(0), "##┌─────┐");
+    }
+
+    #[test]
+    fn test_blend() {
+        let mut frame = CellGrid::new(4, 3, false);
+        for _ in 0..12 {
+            frame.push("#", [200; 3]);
+        }
+        let composed = compose(&frame, &["a".to_string()], Anchor::TopLeft);
+        assert_eq!(blend(&frame, &composed, 1.0), composed);
+        assert_eq!(blend(&frame, &composed, 0.0), frame);
+        let faded = blend(&frame, &composed, 0.75);
+        assert_eq!(faded.row_text(1), "│ a│");
+        let text = faded.cell(2, 1).unwrap();
+        assert_eq!(text.color, [241; 3]);
+        assert_eq!(text.background, Some([37; 3]));
+        assert_eq!(blend(&frame, &composed, 0.25).row_text(1), "####");
+    }
+
+    #[test]
+    fn test_subtitles() {
+        let mut frame = CellGrid::new(9, 4, false);
+        for _ in 0..36 {
+            frame.push("#", [200; 3]);
+        }
+        let composed = subtitles(&frame, &["Hi".to_string(), "there".to_string()]);
+        assert_eq!(
+            (0..4).map(|y| composed.row_text(y)).collect::<Vec<_>>(),
+            ["#########", "## Hi ###", "# there #", "#########"]
+        );
+        assert_eq!(composed.cell(3, 1).unwrap().background, Some([50; 3]));
+        assert_eq!(subtitles(&frame, &[]), frame);
+    }
+
+    #[test]
+    fn test_ambilight() {
+        // Red on the left, blue on the right
+        let mut frame = CellGrid::new(6, 2, false);
+        for _ in 0..2 {
+            for x in 0..6 {
+                frame.push("#", if x < 3 { [255, 0, 0] } else { [0, 0, 255] });
+            }
+        }
+        let composed = ambilight(&frame);
+        assert_eq!((composed.width(), composed.height()), (8, 4));
+        assert_eq!(composed.row_text(1), " ###### ");
+        let glow = |x, y| composed.cell(x, y).unwrap().background.unwrap();
+        assert_eq!(glow(0, 1), [255, 0, 0]);
+        assert_eq!(glow(7, 2), [0, 0, 255]);
+        // The top border blends the colors near the middle
+        assert_eq!(glow(0, 0), [255, 0, 0]);
+        assert_eq!(glow(3, 0), [153, 0, 102]);
+        assert_eq!(composed.cell(1, 1).unwrap().background, None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Saves the frame on screen to a file (`s` key), named after the time it was taken (e.g.
+//! `tplay-20240501-183012.png`), in the current directory.
+//!
+//! The formats (`--screenshot-format`) are:
+//! - `png`: the pixels of the source frame, before they were turned into characters.
+//! - `txt`: the characters of the frame, as plain text.
+//! - `ans`: the characters of the frame with the escape sequences coloring them, as drawn with the
+//!   current theme (shown with `cat`).
+use super::{colorize, colors::ColorDepth};
+use crate::{common::errors::*, display::themes::Theme, CellGrid};
+use image::DynamicImage;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The file format of the screenshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScreenshotFormat {
+    /// The pixels of the source frame.
+    #[default]
+    Png,
+    /// The characters of the frame.
+    Txt,
+    /// The colored characters of the frame.
+    Ans,
+}
+
+impl FromStr for ScreenshotFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Ok(ScreenshotFormat::Png),
+            "txt" | "text" => Ok(ScreenshotFormat::Txt),
+            "ans" | "ansi" => Ok(ScreenshotFormat::Ans),
+            _ => Err(format!(
+                "`{name}` is not a screenshot format (png, txt, ans)"
+            )),
+        }
+    }
+}
+
+impl ScreenshotFormat {
+    /// Returns the extension of the files of the format.
+    fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Txt => "txt",
+            ScreenshotFormat::Ans => "ans",
+        }
+    }
+}
+
+/// Saves a frame to a new file named after the current time.
+///
+/// # Arguments
+///
+/// * `format` - The file format.
+/// * `grid` - The frame on screen.
+/// * `source` - The source frame the grid was rendered from, if known, saved as `png`.
+/// * `theme` - The theme the frame is drawn with, for `ans`.
+/// * `color_depth` - The colors the terminal can show, for `ans`.
+/// * `dir` - The directory where the file is created.
+///
+/// # Returns
+///
+/// A `Result` containing the path of the file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written, or there is no pixel to save as `png`.
+pub fn save(
+    format: ScreenshotFormat,
+    grid: &CellGrid,
+    source: Option<&DynamicImage>,
+    (theme, color_depth): (&Theme, ColorDepth),
+    dir: &Path,
+) -> Result<PathBuf, MyError> {
+    let path = free_path(dir, format.extension());
+    let error = |e: &dyn std::fmt::Display| {
+        MyError::Application(format!("{ERROR_SCREENSHOT}: {}: {e}", path.display()))
+    };
+    match format {
+        ScreenshotFormat::Png => {
+            let pixels = source.cloned().or_else(|| {
+                // Frames drawn as pixels (Sixel) carry them
+                grid.pixels()
+                    .map(|pixels| DynamicImage::ImageRgb8(pixels.clone()))
+            });
+            let pixels = pixels.ok_or_else(|| error(&"no source frame"))?;
+            pixels.to_rgb8().save(&path).map_err(|e| error(&e))?;
+        }
+        ScreenshotFormat::Txt => {
+            fs::write(&path, text(grid, None)).map_err(|e| error(&e))?;
+        }
+        ScreenshotFormat::Ans => {
+            let text = text(grid, Some((theme, color_depth)));
+            fs::write(&path, text).map_err(|e| error(&e))?;
+        }
+    }
+    Ok(path)
+}
+
+/// Returns the characters of a frame, one line per row, as saved in the `txt` and `ans` formats.
+///
+/// # Arguments
+///
+/// * `grid` - The frame on screen.
+/// * `colors` - The theme the frame is drawn with and the colors the terminal can show, to add
+///   the escape sequences coloring the characters, or `None` for plain text.
+pub fn text(grid: &CellGrid, colors: Option<(&Theme, ColorDepth)>) -> String {
+    let rows: Vec<String> = (0..grid.height())
+        .map(|y| match colors {
+            Some((theme, color_depth)) => colorize(grid.row(y), theme, color_depth),
+            None => grid.row_text(y).to_string(),
+        })
+        .collect();
+    rows.join("\n") + "\n"
+}
+
+/// Returns a path named after the current time that no file has yet, e.g.
+/// `tplay-20240501-183012.png`, or `tplay-20240501-183012-2.png` for a second screenshot within
+/// the same second.
+///
+/// # Arguments
+///
+/// * `dir` - The directory of the file.
+/// * `extension` - The extension of the file.
+fn free_path(dir: &Path, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let name = format!("tplay-{}", timestamp(seconds));
+    let mut path = dir.join(format!("{name}.{extension}"));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = dir.join(format!("{name}-{count}.{extension}"));
+    }
+    path
+}
+
+/// Formats a time as `YYYYMMDD-HHMMSS`, in UTC.
+///
+/// # Arguments
+///
+/// * `seconds` - The time, in seconds since the Unix epoch.
+fn timestamp(seconds: u64) -> String {
+    let (days, time) = ((seconds / 86_400) as i64, seconds % 86_400);
+    // The civil date of a number of days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::themes::ThemeColors;
+
+    #[test]
+    fn test_save_screenshot() {
+        assert_eq!(timestamp(0), "19700101-000000");
+        assert_eq!(timestamp(1_709_210_096), "20240229-123456");
+
+        let dir = std::env::temp_dir().join(format!("tplay-screenshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut grid = CellGrid::new(2, 2, false);
+        for glyph in ["a", "b", "c", "d"] {
+            grid.push(glyph, [255, 0, 0]);
+        }
+        let theme = Theme {
+            name: "color".to_string(),
+            colors: ThemeColors::Source,
+        };
+        let style = (&theme, ColorDepth::TrueColor);
+        let txt = save(ScreenshotFormat::Txt, &grid, None, style, &dir).unwrap();
+        assert_eq!(fs::read_to_string(&txt).unwrap(), "ab\ncd\n");
+        // A second screenshot within the same second gets a new name
+        let again = save(ScreenshotFormat::Txt, &grid, None, style, &dir).unwrap();
+        assert_ne!(txt, again);
+        let ans = save(ScreenshotFormat::Ans, &grid, None, style, &dir).unwrap();
+        assert!(fs::read_to_string(&ans).unwrap().contains("\x1b["));
+        assert!(save(ScreenshotFormat::Png, &grid, None, style, &dir).is_err());
+        let source = DynamicImage::new_rgb8(4, 3);
+        let png = save(ScreenshotFormat::Png, &grid, Some(&source), style, &dir).unwrap();
+        assert_eq!(image::open(&png).unwrap().width(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
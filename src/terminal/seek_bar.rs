@@ -0,0 +1,147 @@
+//! Shows a seek bar over the bottom of the frame (`o` key): the position and the duration of the
+//! media, with a bar filled up to the position and, for the media decoded in the background, the
+//! part already decoded. Clicking or dragging on the bar seeks there, like the arrow keys seek
+//! back and forth.
+//!
+//! The same layout draws the bar and maps the clicks to positions, so that a click seeks to the
+//! position drawn under the mouse.
+use super::status_line::format_time;
+use crate::display::Progress;
+
+/// Narrowest bar drawn, the seek bar is left out in narrower frames.
+pub const MIN_BAR_WIDTH: usize = 4;
+
+/// Where the playback is, for a media that can be seeked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeekBar {
+    /// The position of the playback, in seconds.
+    position: f64,
+    /// The duration of the media, in seconds.
+    duration: f64,
+    /// The position up to which the media is decoded, if it is decoded in the background.
+    buffered: Option<f64>,
+}
+
+impl SeekBar {
+    /// Constructs the seek bar of a media.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - Where the playback is, reported by the media pipeline.
+    ///
+    /// # Returns
+    ///
+    /// The seek bar, or `None` if the media has no position or duration (e.g. still images and
+    /// live streams), which cannot be seeked.
+    pub fn new(progress: Progress) -> Option<Self> {
+        let (position, duration) = progress.position.zip(progress.duration)?;
+        (duration > 0.0).then_some(Self {
+            position: position.clamp(0.0, duration),
+            duration,
+            buffered: progress.buffered,
+        })
+    }
+
+    /// Returns the first column and the width of the bar in a line of the given width, between
+    /// the position and the duration.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the line, in characters.
+    fn bar_span(&self, width: usize) -> (usize, usize) {
+        let start = format_time(self.position).chars().count() + 1;
+        let end = format_time(self.duration).chars().count() + 1;
+        (start, width.saturating_sub(start + end))
+    }
+
+    /// Returns the text of the seek bar: the position, the bar, and the duration. The bar is
+    /// filled (`━`) up to the knob (`●`) at the position, then drawn as a line (`─`) up to the
+    /// part decoded, and dotted (`┄`) past it.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the line, in characters.
+    ///
+    /// # Returns
+    ///
+    /// The text, or `None` if the line is too narrow for the bar.
+    pub fn text(&self, width: usize) -> Option<String> {
+        let (_, bar_width) = self.bar_span(width);
+        if bar_width < MIN_BAR_WIDTH {
+            return None;
+        }
+        let cell = |seconds: f64| {
+            let fraction = seconds / self.duration;
+            ((fraction * bar_width as f64) as usize).min(bar_width - 1)
+        };
+        let knob = cell(self.position);
+        let buffered = match self.buffered {
+            Some(buffered) if buffered < self.duration => cell(buffered),
+            _ => bar_width,
+        };
+        let bar: String = (0..bar_width)
+            .map(|x| match x {
+                _ if x == knob => '●',
+                _ if x < knob => '━',
+                _ if x < buffered => '─',
+                _ => '┄',
+            })
+            .collect();
+        Some(format!(
+            "{} {bar} {}",
+            format_time(self.position),
+            format_time(self.duration)
+        ))
+    }
+
+    /// Returns the position under a column of the line, where a click seeks to.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the line, in characters.
+    /// * `column` - The column clicked, from the start of the line.
+    ///
+    /// # Returns
+    ///
+    /// The position in seconds, or `None` if the column is not on the bar.
+    pub fn position_at(&self, width: usize, column: usize) -> Option<f64> {
+        let (start, bar_width) = self.bar_span(width);
+        if bar_width < MIN_BAR_WIDTH || column < start || column >= start + bar_width {
+            return None;
+        }
+        // The middle of the cell, so that the knob lands on the cell clicked
+        let fraction = ((column - start) as f64 + 0.5) / bar_width as f64;
+        Some(fraction * self.duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seek_bar() {
+        let progress = Progress {
+            position: Some(30.0),
+            duration: Some(100.0),
+            buffered: Some(70.0),
+        };
+        let seek_bar = SeekBar::new(progress).unwrap();
+        // 00:30 and 01:40 leave 10 cells for the bar
+        assert_eq!(seek_bar.text(22).unwrap(), "00:30 ━━━●───┄┄┄ 01:40");
+        assert_eq!(seek_bar.text(15), None);
+
+        // A click seeks to the middle of the cell under the mouse
+        assert_eq!(seek_bar.position_at(22, 6), Some(5.0));
+        assert_eq!(seek_bar.position_at(22, 15), Some(95.0));
+        assert_eq!(seek_bar.position_at(22, 5), None);
+        assert_eq!(seek_bar.position_at(22, 16), None);
+
+        // Media without a duration cannot be seeked
+        let live = Progress {
+            duration: None,
+            ..progress
+        };
+        assert_eq!(SeekBar::new(live), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Draws frames as Sixel graphics, for true pixel playback on the terminals supporting it (xterm,
+//! foot, mlterm...).
+//!
+//! With the Sixel backend, the pipeline skips the conversion to characters and sends the pixels of
+//! each frame, resized to the pixel size of the drawing area. They are encoded here as a Sixel
+//! image: bands of 6 rows of pixels, each band drawn color by color, with the colors reduced to a
+//! 6x6x6 color cube.
+use crossterm::terminal;
+use image::RgbImage;
+use std::{env, fmt::Write, str::FromStr};
+
+/// Pixel size of a character cell (width, height), when the terminal does not report it.
+const DEFAULT_CELL_SIZE: (u32, u32) = (10, 20);
+/// Number of levels of each channel in the palette.
+const LEVELS: u32 = 6;
+/// Number of colors of the palette.
+const PALETTE_SIZE: usize = (LEVELS * LEVELS * LEVELS) as usize;
+/// Number of rows of pixels in a Sixel band.
+const BAND_HEIGHT: u32 = 6;
+/// Terminals known to support Sixel graphics, by their `TERM` prefix.
+const SIXEL_TERMINALS: [&str; 4] = ["foot", "mlterm", "contour", "yaft"];
+
+/// How the frames are drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Characters, colored with escape sequences.
+    #[default]
+    Ascii,
+    /// Sixel graphics.
+    Sixel,
+    /// Sixel graphics if the terminal supports them, characters otherwise.
+    Auto,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "ascii" | "text" => Ok(Backend::Ascii),
+            "sixel" => Ok(Backend::Sixel),
+            "auto" => Ok(Backend::Auto),
+            _ => Err(format!("`{name}` is not a backend (ascii, sixel, auto)")),
+        }
+    }
+}
+
+impl Backend {
+    /// Returns the backend to use: `Auto` is replaced by `Sixel` or `Ascii` depending on the
+    /// terminal.
+    pub fn resolve(self) -> Self {
+        match self {
+            Backend::Auto if supports_sixel() => Backend::Sixel,
+            Backend::Auto => Backend::Ascii,
+            backend => backend,
+        }
+    }
+}
+
+/// Returns whether the terminal tplay runs in supports Sixel graphics, from its terminfo name
+/// (`TERM`).
+pub fn supports_sixel() -> bool {
+    is_sixel_terminal(
+        env::var("TERM").ok().as_deref(),
+        env::var_os("XTERM_VERSION").is_some(),
+    )
+}
+
+/// Returns whether a terminal supports Sixel graphics.
+///
+/// # Arguments
+///
+/// * `term` - The terminfo name of the terminal (`TERM`), if set.
+/// * `is_xterm` - Whether the terminal is xterm itself (`XTERM_VERSION` is set). Many terminals
+///   call themselves `xterm-256color`, only xterm has Sixel support.
+fn is_sixel_terminal(term: Option<&str>, is_xterm: bool) -> bool {
+    let Some(term) = term else {
+        return false;
+    };
+    SIXEL_TERMINALS.iter().any(|name| term.starts_with(name))
+        || (is_xterm && term.starts_with("xterm"))
+}
+
+/// Returns the pixel size of a character cell (width, height), as reported by the terminal, or a
+/// common size if it is unknown.
+pub fn cell_size() -> (u32, u32) {
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1) as u32,
+            (size.height / size.rows).max(1) as u32,
+        ),
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// Returns the index of the palette color closest to the given color.
+///
+/// # Arguments
+///
+/// * `color` - The RGB color.
+fn palette_index([r, g, b]: [u8; 3]) -> usize {
+    let level = |channel: u8| (channel as u32 * (LEVELS - 1) + 127) / 255;
+    (level(r) * LEVELS * LEVELS + level(g) * LEVELS + level(b)) as usize
+}
+
+/// Appends a run of identical sixels, with the repeat introducer for long runs.
+///
+/// # Arguments
+///
+/// * `output` - The Sixel data.
+/// * `sixel` - The bits of the 6 pixels of the column.
+/// * `count` - The number of columns in the run.
+fn push_run(output: &mut String, sixel: u8, count: usize) {
+    let c = (b'?' + sixel) as char;
+    match count {
+        0 => {}
+        1..=3 => output.extend(std::iter::repeat_n(c, count)),
+        _ => {
+            let _ = write!(output, "!{count}{c}");
+        }
+    }
+}
+
+/// Encodes an image as a Sixel escape sequence, drawn at the cursor position.
+///
+/// # Arguments
+///
+/// * `image` - The image.
+///
+/// # Returns
+///
+/// The escape sequence.
+pub fn encode(image: &RgbImage) -> String {
+    let (width, height) = (image.width() as usize, image.height());
+    let indices: Vec<usize> = image.pixels().map(|pixel| palette_index(pixel.0)).collect();
+
+    let mut output = String::new();
+    let _ = write!(output, "\x1bPq\"1;1;{width};{height}");
+    let mut defined = [false; PALETTE_SIZE];
+    for &index in &indices {
+        if !defined[index] {
+            defined[index] = true;
+            let percent = |level: usize| level * 100 / (LEVELS as usize - 1);
+            let levels = LEVELS as usize;
+            let _ = write!(
+                output,
+                "#{index};2;{};{};{}",
+                percent(index / (levels * levels)),
+                percent(index / levels % levels),
+                percent(index % levels)
+            );
+        }
+    }
+
+    // The sixels of each color of the band, and the colors in order of appearance
+    let mut sixels = vec![Vec::new(); PALETTE_SIZE];
+    let mut colors = Vec::new();
+    for band in (0..height).step_by(BAND_HEIGHT as usize) {
+        if band > 0 {
+            output.push('-');
+        }
+        for row in 0..BAND_HEIGHT.min(height - band) {
+            let start = (band + row) as usize * width;
+            for (x, &index) in indices[start..start + width].iter().enumerate() {
+                if sixels[index].is_empty() {
+                    sixels[index] = vec![0u8; width];
+                    colors.push(index);
+                }
+                sixels[index][x] |= 1 << row;
+            }
+        }
+        for (i, index) in colors.drain(..).enumerate() {
+            if i > 0 {
+                // Back to the start of the band for the next color
+                output.push('$');
+            }
+            let _ = write!(output, "#{index}");
+            let line = std::mem::take(&mut sixels[index]);
+            // Trailing empty columns need not be drawn
+            let end = line
+                .iter()
+                .rposition(|&sixel| sixel != 0)
+                .map_or(0, |x| x + 1);
+            let mut run = (0, 0);
+            for &sixel in &line[..end] {
+                if sixel == run.0 {
+                    run.1 += 1;
+                } else {
+                    push_run(&mut output, run.0, run.1);
+                    run = (sixel, 1);
+                }
+            }
+            push_run(&mut output, run.0, run.1);
+        }
+    }
+    output.push_str("\x1b\\");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_encode() {
+        // Red on the 6 rows of the left columns, blue on the first row of the last one
+        let image = RgbImage::from_fn(5, 6, |x, y| match (x, y) {
+            (4, 0) => Rgb([0, 0, 255]),
+            (4, _) => Rgb([0, 0, 0]),
+            _ => Rgb([255, 0, 0]),
+        });
+        assert_eq!(
+            encode(&image),
+            "\x1bPq\"1;1;5;6#180;2;100;0;0#5;2;0;0;100#0;2;0;0;0\
+             #180!4~$#5!4?@$#0!4?}\x1b\\"
+        );
+
+        // Two bands
+        let image = RgbImage::from_pixel(1, 8, Rgb([255, 0, 0]));
+        assert!(encode(&image).ends_with("#180~-#180B\x1b\\"));
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(is_sixel_terminal(Some("foot"), false));
+        assert!(is_sixel_terminal(Some("mlterm-256color"), false));
+        assert!(is_sixel_terminal(Some("xterm-256color"), true));
+        assert!(!is_sixel_terminal(Some("xterm-256color"), false));
+        assert!(!is_sixel_terminal(None, true));
+
+        assert_eq!("Sixel".parse(), Ok(Backend::Sixel));
+        assert_eq!(Backend::Ascii.resolve(), Backend::Ascii);
+        assert!("kitty".parse::<Backend>().is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Provides the sleep timer: pauses or quits the playback after a duration, set with
+//! `--quit-after` or with the `:sleep` command during playback.
+//!
+//! The timer is shared by the terminals of all the media played in a session, so that it keeps
+//! counting across the tracks of a playlist. Its countdown is shown in the status bar.
+use crate::common::utils::parse_duration;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// What the sleep timer does when it expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepAction {
+    /// Pauses the playback.
+    Pause,
+    /// Stops the playback and quits.
+    Quit,
+}
+
+impl FromStr for SleepAction {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "pause" => Ok(SleepAction::Pause),
+            "quit" | "exit" => Ok(SleepAction::Quit),
+            _ => Err(format!("`{name}` is not a sleep action (pause, quit)")),
+        }
+    }
+}
+
+/// A command given to the sleep timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepCommand {
+    /// Starts the timer, replacing the running one.
+    Start(Duration, SleepAction),
+    /// Stops the running timer.
+    Cancel,
+}
+
+impl FromStr for SleepCommand {
+    type Err = String;
+
+    /// Parses the arguments of the `:sleep` command: `<duration> [pause|quit]` (quit by
+    /// default), or `off`.
+    fn from_str(arguments: &str) -> Result<Self, Self::Err> {
+        let mut words = arguments.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("off" | "cancel"), None, _) => Ok(SleepCommand::Cancel),
+            (Some(duration), action, None) => Ok(SleepCommand::Start(
+                parse_duration(duration)?,
+                action.map_or(Ok(SleepAction::Quit), str::parse)?,
+            )),
+            _ => Err("usage: sleep <duration> [pause|quit], or sleep off".to_string()),
+        }
+    }
+}
+
+/// The expiry of a running timer.
+#[derive(Debug, Clone, Copy)]
+struct Alarm {
+    /// When the timer expires.
+    deadline: Instant,
+    /// What to do then.
+    action: SleepAction,
+    /// Whether the action was done.
+    expired: bool,
+}
+
+/// The sleep timer of a session, cheap to clone: the clones share the same timer.
+#[derive(Debug, Clone, Default)]
+pub struct SleepTimer {
+    alarm: Arc<Mutex<Option<Alarm>>>,
+}
+
+impl SleepTimer {
+    /// Applies a command to the timer.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Starts the timer from now, or stops it.
+    pub fn apply(&self, command: SleepCommand) {
+        *self.lock() = match command {
+            SleepCommand::Start(duration, action) => Some(Alarm {
+                deadline: Instant::now() + duration,
+                action,
+                expired: false,
+            }),
+            SleepCommand::Cancel => None,
+        };
+    }
+
+    /// Returns the action of the timer the first time it is called after the timer expired,
+    /// `None` otherwise.
+    pub fn poll(&self) -> Option<SleepAction> {
+        let mut alarm = self.lock();
+        match alarm.as_mut() {
+            Some(alarm) if !alarm.expired && Instant::now() >= alarm.deadline => {
+                alarm.expired = true;
+                Some(alarm.action)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether the timer expired and quit the playback.
+    pub fn has_quit(&self) -> bool {
+        matches!(
+            *self.lock(),
+            Some(Alarm {
+                action: SleepAction::Quit,
+                expired: true,
+                ..
+            })
+        )
+    }
+
+    /// Returns the countdown shown in the status bar while the timer runs, e.g. `quit in 19:59`.
+    pub fn countdown(&self) -> Option<String> {
+        let alarm = (*self.lock()).filter(|alarm| !alarm.expired)?;
+        let remaining = alarm.deadline.saturating_duration_since(Instant::now());
+        let verb = match alarm.action {
+            SleepAction::Pause => "pause",
+            SleepAction::Quit => "quit",
+        };
+        Some(format!("{verb} in {}", format_countdown(remaining)))
+    }
+
+    /// Locks the timer, even if a thread panicked while holding it.
+    fn lock(&self) -> MutexGuard<'_, Option<Alarm>> {
+        self.alarm
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Formats a remaining duration as `[h:]mm:ss`, rounded up to the second.
+///
+/// # Arguments
+///
+/// * `remaining` - The remaining duration.
+fn format_countdown(remaining: Duration) -> String {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{minutes:02}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_command() {
+        assert_eq!(
+            "20m".parse(),
+            Ok(SleepCommand::Start(
+                Duration::from_secs(1200),
+                SleepAction::Quit
+            ))
+        );
+        assert_eq!(
+            "1h pause".parse(),
+            Ok(SleepCommand::Start(
+                Duration::from_secs(3600),
+                SleepAction::Pause
+            ))
+        );
+        assert_eq!("off".parse(), Ok(SleepCommand::Cancel));
+        assert!("".parse::<SleepCommand>().is_err());
+        assert!("20m snooze".parse::<SleepCommand>().is_err());
+    }
+
+    #[test]
+    fn test_sleep_timer() {
+        let timer = SleepTimer::default();
+        assert_eq!(timer.countdown(), None);
+        timer.apply(SleepCommand::Start(
+            Duration::from_secs(90),
+            SleepAction::Pause,
+        ));
+        assert_eq!(timer.countdown().as_deref(), Some("pause in 01:30"));
+        assert_eq!(timer.poll(), None);
+
+        let shared = timer.clone();
+        shared.apply(SleepCommand::Start(Duration::ZERO, SleepAction::Quit));
+        assert_eq!(timer.poll(), Some(SleepAction::Quit));
+        assert_eq!(timer.poll(), None);
+        assert!(shared.has_quit());
+        assert_eq!(timer.countdown(), None);
+        assert_eq!(format_countdown(Duration::from_secs(3725)), "1:02:05");
+    }
+}
@@ -0,0 +1,161 @@
+//! Shows a status line below the frame (`--status-line`): whether the playback runs or is paused,
+//! the elapsed and total time with a progress bar, the rate at which the frames are drawn, and the
+//! name of the media.
+//!
+//! The position and the duration are reported by the media pipeline with each frame (see
+//! `display::Progress`); the frame rate is measured as the frames are drawn.
+use crate::display::Progress;
+use std::{
+    collections::VecDeque,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Over how long the frame rate is measured.
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+/// Narrowest progress bar drawn, narrower bars are left out.
+const MIN_BAR_WIDTH: usize = 4;
+
+/// The content of the status line.
+pub struct StatusLine {
+    /// The name of the media, without its directory.
+    name: String,
+    /// Where the playback is, as last reported by the media pipeline.
+    progress: Progress,
+    /// When the frames of the last `FPS_WINDOW` were drawn.
+    draws: VecDeque<Instant>,
+}
+
+impl StatusLine {
+    /// Constructs the status line of a media.
+    ///
+    /// # Arguments
+    ///
+    /// * `media` - The path or URL of the media, only its last component is shown.
+    pub fn new(media: &str) -> Self {
+        let name = Path::new(media.trim_end_matches('/'))
+            .file_name()
+            .map_or(media.to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        Self {
+            name,
+            progress: Progress::default(),
+            draws: VecDeque::new(),
+        }
+    }
+
+    /// Updates the position and duration of the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - Where the playback is, reported by the media pipeline.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    /// Records that a frame was drawn, for the frame rate.
+    pub fn record_draw(&mut self) {
+        let now = Instant::now();
+        self.draws.push_back(now);
+        while let Some(first) = self.draws.front() {
+            if now.duration_since(*first) <= FPS_WINDOW {
+                break;
+            }
+            self.draws.pop_front();
+        }
+    }
+
+    /// Returns the text of the status line, exactly `width` characters long so that it covers the
+    /// previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `paused` - Whether the playback is paused, the frame rate is then left out.
+    /// * `width` - The width of the line, in characters.
+    pub fn text(&self, paused: bool, width: usize) -> String {
+        let state = if paused { "‖" } else { "▶" };
+        let time = match self.progress {
+            Progress {
+                position: Some(position),
+                duration: Some(duration),
+                ..
+            } => format!(" {} / {}", format_time(position), format_time(duration)),
+            Progress {
+                position: Some(position),
+                ..
+            } => format!(" {}", format_time(position)),
+            _ => String::new(),
+        };
+        let fps = match paused {
+            true => String::new(),
+            false => format!("  {} fps", self.draws.len()),
+        };
+        let name = format!("  {}", self.name);
+        let fixed = state.chars().count() + time.chars().count() + fps.len() + 1;
+        // The progress bar takes the room left by the other fields, the name included
+        let bar_width = width.saturating_sub(fixed + name.chars().count() + 1);
+        let bar = match self.progress {
+            Progress {
+                position: Some(position),
+                duration: Some(duration),
+                ..
+            } if bar_width >= MIN_BAR_WIDTH && duration > 0.0 => {
+                format!(" {}", progress_bar(position / duration, bar_width))
+            }
+            _ => String::new(),
+        };
+        let line = format!("{state}{time}{bar}{fps}{name} ");
+        format!("{line:width$.width$}")
+    }
+}
+
+/// Formats a time as `[h:]mm:ss`.
+///
+/// # Arguments
+///
+/// * `seconds` - The time, in seconds.
+pub fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{minutes:02}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+/// Draws a progress bar.
+///
+/// # Arguments
+///
+/// * `fraction` - The part of the bar filled, from 0 to 1.
+/// * `width` - The width of the bar, in characters.
+fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * width as f64).round() as usize;
+    format!("{}{}", "━".repeat(filled), "─".repeat(width - filled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line_text() {
+        let mut status_line = StatusLine::new("/videos/clip.mp4");
+        status_line.set_progress(Progress {
+            position: Some(83.0),
+            duration: Some(166.0),
+            buffered: None,
+        });
+        assert_eq!(
+            status_line.text(true, 36),
+            "‖ 01:23 / 02:46 ━━━━━────  clip.mp4 "
+        );
+        // Too narrow for the progress bar, the line is cut
+        assert_eq!(status_line.text(true, 12), "‖ 01:23 / 02");
+        status_line.set_progress(Progress::default());
+        status_line.record_draw();
+        assert_eq!(status_line.text(false, 20), "▶  1 fps  clip.mp4  ");
+        assert_eq!(format_time(3725.0), "1:02:05");
+    }
+}
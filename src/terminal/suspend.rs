@@ -0,0 +1,65 @@
+//! Suspends tplay to the shell with `Ctrl+Z`, like `less` or `mpv` do: the playback is paused and
+//! the terminal restored before tplay stops, and once the shell continues it (`fg`), the terminal
+//! is set up again and the frame drawn in full, still paused.
+//!
+//! In raw mode, the terminal sends `Ctrl+Z` as a key rather than stopping tplay, so the key is read
+//! like the others. A `SIGTSTP` sent otherwise (e.g. `kill -TSTP`) is caught instead of stopping
+//! tplay with the terminal in raw mode, and handled the same way at the next poll. Job control only
+//! exists on Unix.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether a `SIGTSTP` was caught since the last poll.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether tplay can be suspended, on the platforms with job control.
+pub fn is_supported() -> bool {
+    cfg!(unix)
+}
+
+/// Catches `SIGTSTP`, to suspend once the terminal is restored (see `take_stop_request`). A
+/// `SIGTSTP` caught before, e.g. by an earlier playback, is forgotten.
+pub fn catch_stop_signal() {
+    STOP_REQUESTED.store(false, Ordering::SeqCst);
+    #[cfg(unix)]
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGTSTP,
+            on_stop_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Gives `SIGTSTP` its default action back, once the terminal is restored, so that the shell can
+/// stop tplay between two playbacks.
+pub fn release_stop_signal() {
+    #[cfg(unix)]
+    // SAFETY: restoring the default action of a signal has no precondition
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+    }
+}
+
+/// Records a caught `SIGTSTP`.
+#[cfg(unix)]
+extern "C" fn on_stop_signal(_: libc::c_int) {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Returns whether a `SIGTSTP` was caught since the last call.
+pub fn take_stop_request() -> bool {
+    STOP_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Stops tplay until the shell continues it, the terminal being restored (which releases
+/// `SIGTSTP`, see `release_stop_signal`). Does nothing on the platforms without job control (see
+/// `is_supported`).
+pub fn stop() {
+    #[cfg(unix)]
+    // SAFETY: the default action of the signal stops all the threads until `SIGCONT`, the signal
+    // is caught again when the terminal is set up afterwards
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+    }
+}
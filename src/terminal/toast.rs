@@ -0,0 +1,150 @@
+//! Toasts: short notifications shown over the top right corner of the frame for a few seconds,
+//! telling what the keys pressed did (e.g. the character map chosen, the screenshot saved) or why
+//! they did nothing (e.g. no audio to mute), instead of failing silently.
+//!
+//! The toasts are stacked in a box under the on-screen display (see `osd.rs`), the newest last.
+//! Errors stay longer than the other toasts, and the oldest toasts make room for new ones.
+use crate::common::errors::MyError;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How long a toast is shown.
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+/// How long an error is shown.
+const ERROR_DURATION: Duration = Duration::from_secs(5);
+/// Most toasts shown at once.
+const MAX_TOASTS: usize = 3;
+/// Marks the errors in the box.
+const ERROR_MARK: &str = "! ";
+
+/// What a toast tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastLevel {
+    /// What a key did.
+    Info,
+    /// Why a key did nothing, or what failed.
+    Error,
+}
+
+/// A notification shown until a given time.
+#[derive(Debug, Clone)]
+struct Toast {
+    /// The text of the toast, on a single line.
+    text: String,
+    /// What the toast tells.
+    level: ToastLevel,
+    /// Until when the toast is shown.
+    until: Instant,
+}
+
+/// The toasts shown, the oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+}
+
+impl Toasts {
+    /// Shows a toast from now on. A toast with the same text as the newest one shows longer
+    /// instead, e.g. when a key is held.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of the toast, on a single line.
+    /// * `level` - What the toast tells.
+    pub fn push(&mut self, text: String, level: ToastLevel) {
+        let duration = match level {
+            ToastLevel::Info => TOAST_DURATION,
+            ToastLevel::Error => ERROR_DURATION,
+        };
+        let until = Instant::now() + duration;
+        if let Some(newest) = self
+            .toasts
+            .back_mut()
+            .filter(|toast| toast.text == text && toast.level == level)
+        {
+            newest.until = until;
+            return;
+        }
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back(Toast { text, level, until });
+    }
+
+    /// Shows the outcome of an action: its result, or its error.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The text telling what the action did, or the error it failed with.
+    pub fn push_result(&mut self, result: Result<String, MyError>) {
+        match result {
+            Ok(text) => self.push(text, ToastLevel::Info),
+            Err(MyError::Application(message)) => self.push(message, ToastLevel::Error),
+            Err(err) => self.push(err.to_string(), ToastLevel::Error),
+        }
+    }
+
+    /// Removes the toasts shown long enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// Whether any toast was removed, the frame is then drawn again without it.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let count = self.toasts.len();
+        self.toasts.retain(|toast| toast.until > now);
+        self.toasts.len() != count
+    }
+
+    /// Returns the text of the box, one line per toast, the errors marked.
+    pub fn lines(&self) -> Vec<String> {
+        self.toasts
+            .iter()
+            .map(|toast| match toast.level {
+                ToastLevel::Info => toast.text.clone(),
+                ToastLevel::Error => format!("{ERROR_MARK}{}", toast.text),
+            })
+            .collect()
+    }
+
+    /// Returns whether no toast is shown.
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toasts() {
+        let mut toasts = Toasts::default();
+        toasts.push("char map braille".to_string(), ToastLevel::Info);
+        toasts.push_result(Err(MyError::Application("no audio".to_string())));
+        toasts.push_result(Err(MyError::Application("no audio".to_string())));
+        assert_eq!(toasts.lines(), ["char map braille", "! no audio"]);
+
+        // The oldest toasts make room
+        for name in ["gradient", "blocks"] {
+            toasts.push(format!("char map {name}"), ToastLevel::Info);
+        }
+        assert_eq!(
+            toasts.lines(),
+            ["! no audio", "char map gradient", "char map blocks"]
+        );
+
+        // The errors stay longer
+        let now = Instant::now();
+        assert!(!toasts.expire(now));
+        assert!(toasts.expire(now + TOAST_DURATION));
+        assert_eq!(toasts.lines(), ["! no audio"]);
+        assert!(toasts.expire(now + ERROR_DURATION));
+        assert!(toasts.is_empty());
+    }
+}
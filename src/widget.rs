@@ -0,0 +1,241 @@
+//! A ratatui widget playing media, to embed video previews in TUI dashboards.
+//!
+//! `AsciiPlayer::open` starts the playback of a media file on background threads. The player is
+//! rendered like any other widget, and adapts the frames to the area it is rendered in. Audio is
+//! not played.
+//!
+//! ```no_run
+//! use tplay::widget::{AsciiPlayer, PlayerOptions};
+//!
+//! let mut player = AsciiPlayer::open("video.mp4", PlayerOptions::default())?;
+//! // In the application draw loop: frame.render_widget(&player, area);
+//! player.seek(10.0);
+//! player.pause();
+//! # Ok::<(), tplay::MyError>(())
+//! ```
+use crate::{
+    common::{errors::*, temp::TempStore},
+    display::{
+        ratatui_display::{FrameView, RatatuiDisplay},
+        DisplayRunner, RenderedFrame,
+    },
+    msg::{
+        broker::{Control as MediaControl, MessageBroker},
+        shutdown::{join_in_order, PlaybackThread, SHUTDOWN_TIMEOUT},
+    },
+    pipeline::{
+        char_maps::{CharMapRegistry, CHARS1},
+        frames::open_media,
+        image_pipeline::ImagePipeline,
+        panorama::CELL_ASPECT,
+        runner::{Control as PipelineControl, Runner, RunnerOptions},
+    },
+};
+use crossbeam_channel::{bounded, unbounded};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    widgets::{StatefulWidget, Widget},
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Barrier,
+    },
+    thread,
+};
+
+/// Frame rate used when the media does not report one.
+const DEFAULT_FPS: f64 = 30.0;
+/// Resolution frames are rendered at until the widget is first rendered.
+const DEFAULT_RESOLUTION: (u32, u32) = (80, 24);
+
+/// Options of an `AsciiPlayer`.
+#[derive(Debug, Clone)]
+pub struct PlayerOptions {
+    /// Frame rate to play at, instead of the one of the media.
+    pub fps: Option<f64>,
+    /// The name of a built-in character map, or the lookup characters from darkest to brightest.
+    pub char_map: String,
+    /// Loop back to the first frame at the end of the media.
+    pub loop_playback: bool,
+    /// Draw without colors.
+    pub grayscale: bool,
+    /// Use ffprobe and yt-dlp to read the frame rate of videos and play YouTube links.
+    pub external_tools: bool,
+}
+
+impl Default for PlayerOptions {
+    fn default() -> Self {
+        Self {
+            fps: None,
+            char_map: CHARS1.to_string(),
+            loop_playback: false,
+            grayscale: false,
+            external_tools: true,
+        }
+    }
+}
+
+/// The playback status of an `AsciiPlayer`, updated each time it is rendered as a
+/// `StatefulWidget`. Useful to draw a status bar next to the player.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AsciiPlayerState {
+    /// Whether the playback is paused.
+    pub paused: bool,
+    /// The number of frames rendered since the playback started.
+    pub frames_rendered: u64,
+}
+
+/// A media player rendering into a ratatui `Buffer`. The playback runs on background threads,
+/// which are stopped when the player is dropped.
+pub struct AsciiPlayer {
+    /// The widget showing the frames, also used to send commands to the playback threads.
+    view: FrameView,
+    /// Whether the playback is paused.
+    paused: bool,
+    /// The number of frames rendered by the pipeline.
+    frames_rendered: Arc<AtomicU64>,
+    /// The playback threads, in the order they stop.
+    threads: Vec<PlaybackThread>,
+    /// Keeps downloaded media alive during playback.
+    _temp_store: TempStore,
+}
+
+impl AsciiPlayer {
+    /// Opens a media file or stream and starts playing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The file, URL or device to play, as accepted by the `tplay` command.
+    /// * `options` - The playback options.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the player, or a `MyError` if the media cannot be opened.
+    pub fn open(input: &str, options: PlayerOptions) -> Result<Self, MyError> {
+        let temp_store = TempStore::new(None)?;
+        let media_data = open_media(input.to_string(), options.external_tools, &temp_store)?;
+        let fps = options.fps.or(media_data.fps).unwrap_or(DEFAULT_FPS);
+
+        let (tx_frames, rx_frames) = bounded::<Option<RenderedFrame>>(1);
+        let (tx_controls, rx_controls) = unbounded::<MediaControl>();
+        let (tx_controls_pipeline, rx_controls_pipeline) = unbounded::<PipelineControl>();
+        let barrier = Arc::new(Barrier::new(3));
+
+        let broker_barrier = Arc::clone(&barrier);
+        let broker = thread::spawn(move || -> Result<(), MyError> {
+            MessageBroker::new(rx_controls, Some(tx_controls_pipeline), None).run(broker_barrier)
+        });
+
+        let frames_rendered = Arc::new(AtomicU64::new(0));
+        let frame_counter = Arc::clone(&frames_rendered);
+        let pipeline_barrier = Arc::clone(&barrier);
+        let tx_controls_runner = tx_controls.clone();
+        let char_maps = CharMapRegistry::default();
+        let char_map = char_maps.resolve(&options.char_map);
+        let loop_playback = options.loop_playback;
+        let pipeline = thread::spawn(move || -> Result<(), MyError> {
+            let mut runner = Runner::new(
+                ImagePipeline::new(DEFAULT_RESOLUTION, char_map, false),
+                media_data.frame_iter,
+                tx_frames,
+                rx_controls_pipeline,
+                tx_controls_runner,
+                RunnerOptions {
+                    fps,
+                    cell_aspect: CELL_ASPECT,
+                    loop_playback,
+                    decimate: 1,
+                    char_maps,
+                },
+            );
+            runner.add_frame_observer(move |_| {
+                frame_counter.fetch_add(1, Ordering::Relaxed);
+            });
+            runner.run(pipeline_barrier, false)
+        });
+
+        let (display, view) = RatatuiDisplay::new(options.grayscale);
+        let display = thread::spawn(move || -> Result<(), MyError> {
+            DisplayRunner::new(display, rx_frames, tx_controls).run(barrier)
+        });
+
+        Ok(Self {
+            view,
+            paused: false,
+            frames_rendered,
+            threads: vec![
+                PlaybackThread::new("display", display),
+                PlaybackThread::new("broker", broker),
+                PlaybackThread::new("pipeline", pipeline),
+            ],
+            _temp_store: temp_store,
+        })
+    }
+
+    /// Resumes the playback if it is paused.
+    pub fn play(&mut self) {
+        if self.paused {
+            self.toggle_pause();
+        }
+    }
+
+    /// Pauses the playback if it is playing.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.toggle_pause();
+        }
+    }
+
+    /// Toggles between pause and play.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.view.send_control(MediaControl::PauseContinue);
+    }
+
+    /// Returns whether the playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Moves the playback to the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The position to move to, in seconds from the start of the media.
+    pub fn seek(&self, seconds: f64) {
+        self.view.send_control(MediaControl::Seek(seconds));
+    }
+
+    /// Returns the playback status.
+    pub fn state(&self) -> AsciiPlayerState {
+        AsciiPlayerState {
+            paused: self.paused,
+            frames_rendered: self.frames_rendered.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Widget for &AsciiPlayer {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Widget::render(&self.view, area, buf);
+    }
+}
+
+impl StatefulWidget for &AsciiPlayer {
+    type State = AsciiPlayerState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        Widget::render(&self.view, area, buf);
+        *state = self.state();
+    }
+}
+
+impl Drop for AsciiPlayer {
+    fn drop(&mut self) {
+        self.view.send_control(MediaControl::Exit);
+        // A thread still blocked is left behind rather than blocking the application
+        let _ = join_in_order(std::mem::take(&mut self.threads), SHUTDOWN_TIMEOUT);
+    }
+}